@@ -0,0 +1,67 @@
+//! Benchmarks for catcode lookups in the lexer.
+//!
+//! The input is a multi-megabyte document of mostly ASCII text with some control sequences,
+//! comments and groups, which is typical of real TeX documents.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::io;
+use texide::datastructures::scopedcharmap::ScopedCharMap;
+use texide::datastructures::scopedmap::ScopedMap;
+use texide::tex::token::catcode;
+use texide::tex::token::lexer::Lexer;
+
+const INPUT_SIZE: usize = 4 * 1024 * 1024;
+
+fn input() -> String {
+    let paragraph = "\\section{Introduction} % a comment\n\
+        The quick brown fox jumps over the {\\it lazy} dog, $x^2 + y_1$ times.\n\
+        Caf\u{e9} na\u{ef}ve r\u{e9}sum\u{e9} and \\emph{more} text~here.\n\n";
+    paragraph.repeat(INPUT_SIZE / paragraph.len())
+}
+
+fn lookup(c: &mut Criterion) {
+    let input: Vec<char> = input().chars().collect();
+    let scoped_map = ScopedMap::from_iter(catcode::tex_defaults().iter().map(|(k, v)| (k, *v)));
+    let scoped_char_map = catcode::tex_defaults();
+    let mut group = c.benchmark_group("lookup");
+    group.sample_size(10);
+    group.bench_function("scoped_map", |b| {
+        b.iter(|| {
+            input
+                .iter()
+                .map(|c| catcode::or_default(scoped_map.get(c)).int() as u64)
+                .sum::<u64>()
+        })
+    });
+    group.bench_function("scoped_char_map", |b| {
+        b.iter(|| {
+            input
+                .iter()
+                .map(|c| catcode::or_default(scoped_char_map.get(c)).int() as u64)
+                .sum::<u64>()
+        })
+    });
+    group.finish();
+}
+
+fn lex(c: &mut Criterion) {
+    let input = input();
+    let map: ScopedCharMap<_> = catcode::tex_defaults();
+    let mut group = c.benchmark_group("lex");
+    group.sample_size(10);
+    group.bench_function("lexer", |b| {
+        b.iter(|| {
+            let mut lexer = Lexer::new(io::Cursor::new(input.as_bytes()));
+            let mut n = 0_usize;
+            while let Some(token) = lexer.next(&map).unwrap() {
+                black_box(token);
+                n += 1;
+            }
+            n
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookup, lex);
+criterion_main!(benches);
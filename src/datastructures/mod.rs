@@ -1,3 +1,4 @@
 //! General-purpose data structures.
 
+pub mod scopedcharmap;
 pub mod scopedmap;
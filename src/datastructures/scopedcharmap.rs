@@ -0,0 +1,344 @@
+//! A scoped char map is a scoped map whose keys are characters, optimized for fast lookups.
+//!
+//! The map has the same semantics as the [ScopedMap](super::scopedmap::ScopedMap): all mutations
+//! made since the beginning of a scope are rolled back at the end of the scope, unless they were
+//! made using one of the global methods.
+//! ```
+//! # use texide::datastructures::scopedcharmap::ScopedCharMap;
+//! let mut cat_colors = ScopedCharMap::new();
+//! cat_colors.insert('p', "black");
+//! cat_colors.begin_scope();
+//! cat_colors.insert('p', "gray");
+//! cat_colors.insert_global('m', "ginger");
+//! assert_eq!(cat_colors.get(&'p'), Some(&"gray"));
+//! assert_eq!(cat_colors.end_scope(), true);
+//! assert_eq!(cat_colors.get(&'p'), Some(&"black"));
+//! assert_eq!(cat_colors.get(&'m'), Some(&"ginger"));
+//! ```
+//!
+//! Values for the first 256 code points are stored in a dense array, so looking them up is a
+//! single index operation. Values for all other characters are stored in a hash map. This makes
+//! the map well suited to catcode tables: in most documents nearly all characters are in the
+//! dense range.
+use std::collections::HashMap;
+
+/// The number of code points whose values are stored in the dense array.
+pub const DENSE_LEN: usize = 256;
+
+#[derive(Clone, Copy)]
+struct Entry<V> {
+    value: Option<V>,
+    // The scope level at which the value was set. Level 0 is the global scope.
+    level: usize,
+}
+
+impl<V> Default for Entry<V> {
+    fn default() -> Self {
+        Entry {
+            value: None,
+            level: 0,
+        }
+    }
+}
+
+/// Implementation of the `ScopedCharMap` data structure. See the module docs for more
+/// information.
+#[derive(Clone)]
+pub struct ScopedCharMap<V: Copy> {
+    // The implementation uses the same approach as TeX's save stack. Each entry stores the level
+    // of the scope in which it was last set. When an entry is set in a scope at a higher level,
+    // the old entry is pushed onto the undo log of the current scope; later mutations in the
+    // same scope overwrite the entry without touching the undo log. At the end of a scope, the
+    // old entries in the scope's undo log are restored in reverse order.
+    //
+    // Global mutations set the level of the entry to 0. Old entries are not restored over global
+    // entries, so a global mutation survives the end of all scopes. This is the same rule that
+    // TeX uses (see section 283 of TeX: The Program).
+    //
+    // All operations are O(1), with the exception of end_scope which is O(number of keys
+    // mutated in the scope).
+    dense: Vec<Entry<V>>,
+    sparse: HashMap<char, Entry<V>>,
+    undo_log_stack: Vec<Vec<(char, Entry<V>)>>,
+}
+
+impl<V: Copy> ScopedCharMap<V> {
+    /// Returns a new empty `ScopedCharMap`.
+    pub fn new() -> ScopedCharMap<V> {
+        ScopedCharMap {
+            dense: vec![Default::default(); DENSE_LEN],
+            sparse: HashMap::new(),
+            undo_log_stack: Vec::new(),
+        }
+    }
+
+    /// Returns a new `ScopedCharMap` pre-populated with the provided key, values pairs.
+    pub fn from_iter<T: IntoIterator<Item = (char, V)>>(iter: T) -> ScopedCharMap<V> {
+        let mut map = ScopedCharMap::new();
+        map.extend(iter);
+        map
+    }
+
+    /// Retrieves the value at the provided key.
+    #[inline]
+    pub fn get(&self, key: &char) -> Option<&V> {
+        match self.dense.get(*key as usize) {
+            Some(entry) => entry.value.as_ref(),
+            None => self.sparse.get(key).and_then(|entry| entry.value.as_ref()),
+        }
+    }
+
+    /// Inserts the key, value pair.
+    pub fn insert(&mut self, key: char, val: V) {
+        self.set(key, Some(val));
+    }
+
+    /// Removes the value at the key. Like insertions, removals are rolled back at the end
+    /// of the current scope.
+    pub fn remove(&mut self, key: char) {
+        self.set(key, None);
+    }
+
+    /// Inserts the key, value pair in the global scope.
+    pub fn insert_global(&mut self, key: char, val: V) {
+        self.set_entry(
+            key,
+            Entry {
+                value: Some(val),
+                level: 0,
+            },
+        );
+    }
+
+    /// Removes the value at the key in the global scope.
+    pub fn remove_global(&mut self, key: char) {
+        self.set_entry(key, Default::default());
+    }
+
+    fn set(&mut self, key: char, value: Option<V>) {
+        let level = self.undo_log_stack.len();
+        let old = self.entry(key);
+        if old.level < level {
+            // This is the first mutation of the key in the current scope. The unwrap is safe
+            // because the level of an entry is never larger than the number of scopes.
+            self.undo_log_stack.last_mut().unwrap().push((key, old));
+        }
+        self.set_entry(key, Entry { value, level });
+    }
+
+    fn entry(&self, key: char) -> Entry<V> {
+        match self.dense.get(key as usize) {
+            Some(entry) => *entry,
+            None => self.sparse.get(&key).copied().unwrap_or_default(),
+        }
+    }
+
+    fn set_entry(&mut self, key: char, entry: Entry<V>) {
+        match self.dense.get_mut(key as usize) {
+            Some(e) => *e = entry,
+            None => match (entry.value, entry.level) {
+                (None, 0) => {
+                    self.sparse.remove(&key);
+                }
+                _ => {
+                    self.sparse.insert(key, entry);
+                }
+            },
+        }
+    }
+
+    /// Begins a new scope.
+    pub fn begin_scope(&mut self) {
+        self.undo_log_stack.push(Vec::new());
+    }
+
+    /// Attempts to end the current scope and returns true if there is a scope to end, and false
+    /// otherwise.
+    #[must_use]
+    pub fn end_scope(&mut self) -> bool {
+        let undo_log = match self.undo_log_stack.pop() {
+            None => return false,
+            Some(undo_log) => undo_log,
+        };
+        for (key, old) in undo_log.into_iter().rev() {
+            if self.entry(key).level != 0 {
+                self.set_entry(key, old);
+            }
+        }
+        true
+    }
+
+    /// Extends the `ScopedCharMap` with (key, value) pairs.
+    pub fn extend<T: IntoIterator<Item = (char, V)>>(&mut self, iter: T) {
+        for (key, val) in iter {
+            self.insert(key, val);
+        }
+    }
+
+    /// Returns an iterator over the key, value pairs currently visible in the map, in an
+    /// arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (char, &V)> {
+        let dense = self.dense.iter().enumerate().filter_map(|(i, entry)| {
+            // All indices of the dense array are valid characters.
+            entry
+                .value
+                .as_ref()
+                .map(|v| (std::char::from_u32(i as u32).unwrap(), v))
+        });
+        let sparse = self
+            .sparse
+            .iter()
+            .filter_map(|(&key, entry)| entry.value.as_ref().map(|v| (key, v)));
+        dense.chain(sparse)
+    }
+}
+
+impl<V: Copy> Default for ScopedCharMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test is run on a dense key and a sparse key.
+    const KEYS: [char; 2] = ['a', 'λ'];
+
+    #[test]
+    fn insert_and_get() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            assert_eq!(map.get(&k), None);
+            map.insert(k, 3);
+            assert_eq!(map.get(&k), Some(&3));
+        }
+    }
+
+    #[test]
+    fn insert_in_scope() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            map.insert(k, 3);
+            map.begin_scope();
+            map.insert(k, 4);
+            map.insert(k, 5);
+            assert_eq!(map.get(&k), Some(&5));
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), Some(&3));
+        }
+    }
+
+    #[test]
+    fn insert_after_nested_insert() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            map.begin_scope();
+            map.insert(k, 5);
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), None);
+            map.insert(k, 4);
+            assert_eq!(map.get(&k), Some(&4));
+        }
+    }
+
+    #[test]
+    fn nested_scopes() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            map.insert(k, 1);
+            map.begin_scope();
+            map.insert(k, 2);
+            map.begin_scope();
+            map.insert(k, 3);
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), Some(&2));
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), Some(&1));
+            assert!(!map.end_scope());
+        }
+    }
+
+    #[test]
+    fn insert_global_after_no_insert() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            map.begin_scope();
+            map.insert_global(k, 5);
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), Some(&5));
+        }
+    }
+
+    #[test]
+    fn insert_global_in_nested_scope() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            map.insert(k, 1);
+            map.begin_scope();
+            map.insert(k, 2);
+            map.begin_scope();
+            map.insert(k, 3);
+            map.insert_global(k, 4);
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), Some(&4));
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), Some(&4));
+        }
+    }
+
+    #[test]
+    fn insert_after_insert_global_in_same_scope() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            map.begin_scope();
+            map.insert(k, 1);
+            map.insert_global(k, 2);
+            map.insert(k, 3);
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), Some(&2));
+        }
+    }
+
+    #[test]
+    fn remove_in_scope() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            map.insert(k, 5);
+            map.begin_scope();
+            map.remove(k);
+            assert_eq!(map.get(&k), None);
+            map.insert(k, 4);
+            assert_eq!(map.get(&k), Some(&4));
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), Some(&5));
+        }
+    }
+
+    #[test]
+    fn remove_global_in_nested_scope() {
+        for k in KEYS {
+            let mut map = ScopedCharMap::new();
+            map.insert(k, 5);
+            map.begin_scope();
+            map.insert(k, 4);
+            map.remove_global(k);
+            assert_eq!(map.get(&k), None);
+            assert!(map.end_scope());
+            assert_eq!(map.get(&k), None);
+        }
+    }
+
+    #[test]
+    fn iter() {
+        let mut map = ScopedCharMap::from_iter(KEYS.iter().map(|&k| (k, 1)));
+        map.begin_scope();
+        map.remove('a');
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![('λ', &1)]);
+        assert!(map.end_scope());
+        let mut all: Vec<_> = map.iter().collect();
+        all.sort();
+        assert_eq!(all, vec![('a', &1), ('λ', &1)]);
+    }
+}
@@ -75,6 +75,9 @@ pub struct ScopedMap<K: Eq + Hash, V> {
     // map data structure. The stack has one element for each scope, with the exception of the
     // global scope (we never need to rollback changes to the global scope).
     //
+    // Removing a key is implemented by pushing `None` onto the value stack, so that the removal
+    // can be rolled back at the end of the scope like any other mutation.
+    //
     // The following invariants hold in the implementation:
     // (1) each value stack has at least 1 element. If all of a stack's values are popped off, the
     //     stack is removed from the map.
@@ -87,13 +90,41 @@ pub struct ScopedMap<K: Eq + Hash, V> {
     // make insert_global O(1) as well. The memory usage of the implementation is asymptotically
     // optimal: we store O(N) elements of data where N is the number of values of the map that can
     // be observed using get and end_scope.
-    key_to_value_stack: HashMap<Rc<K>, Vec<V>>,
+    key_to_value_stack: HashMap<Rc<K>, Vec<Option<V>>>,
     changed_keys_stack: Vec<HashSet<Rc<K>>>,
 }
 
 impl<K: Eq + Hash, V> ScopedMap<K, V> {
     /// Inserts the key, value pair.
     pub fn insert(&mut self, key: K, val: V) {
+        self.set(key, Some(val));
+    }
+
+    /// Removes the value at the key. Like insertions, removals are rolled back at the end
+    /// of the current scope.
+    /// ```
+    /// # use texide::datastructures::scopedmap::ScopedMap;
+    /// let mut cat_colors = ScopedMap::new();
+    /// cat_colors.insert("paganini", "black");
+    /// cat_colors.begin_scope();
+    /// cat_colors.remove("paganini");
+    /// assert_eq!(cat_colors.get(&"paganini"), None);
+    /// assert_eq!(cat_colors.end_scope(), true);
+    /// assert_eq!(cat_colors.get(&"paganini"), Some(&"black"));
+    /// ```
+    pub fn remove(&mut self, key: K) {
+        self.set(key, None);
+    }
+
+    fn set(&mut self, key: K, val: Option<V>) {
+        if self.changed_keys_stack.is_empty() {
+            // In the global scope there is nothing to roll back to, so a removal can simply
+            // delete the value stack.
+            if val.is_none() {
+                self.key_to_value_stack.remove(&key);
+                return;
+            }
+        }
         // If the key is already in the map we retrieve the reference counting smart pointer that
         // has already been created for that key. This ensures that each key is stored in memory
         // at most once.
@@ -110,10 +141,7 @@ impl<K: Eq + Hash, V> ScopedMap<K, V> {
         };
         match new_in_scope {
             true => {
-                self.key_to_value_stack
-                    .entry(key)
-                    .or_insert(Vec::new())
-                    .push(val);
+                self.key_to_value_stack.entry(key).or_default().push(val);
             }
             false => {
                 // This key has already been set in this scope, which means the value_stack exists
@@ -133,9 +161,16 @@ impl<K: Eq + Hash, V> ScopedMap<K, V> {
         for changed_keys in &mut self.changed_keys_stack {
             changed_keys.remove(&key);
         }
-        let mut new_stack = Vec::new();
-        new_stack.push(val);
-        self.key_to_value_stack.insert(Rc::new(key), new_stack);
+        self.key_to_value_stack
+            .insert(Rc::new(key), vec![Some(val)]);
+    }
+
+    /// Removes the value at the key in the global scope, and erases all other values.
+    pub fn remove_global(&mut self, key: K) {
+        for changed_keys in &mut self.changed_keys_stack {
+            changed_keys.remove(&key);
+        }
+        self.key_to_value_stack.remove(&key);
     }
 
     // TODO: specialize this for copiable values? What does HashMap do?
@@ -144,10 +179,91 @@ impl<K: Eq + Hash, V> ScopedMap<K, V> {
     pub fn get(&self, key: &K) -> Option<&V> {
         match self.key_to_value_stack.get(key) {
             None => None,
-            Some(value_stack) => value_stack.last(),
+            Some(value_stack) => value_stack.last().and_then(Option::as_ref),
+        }
+    }
+
+    /// Returns a mutable reference to the value at the provided key.
+    ///
+    /// Changes made through the reference modify the value in whichever scope it was inserted,
+    /// and so are not rolled back at the end of the current scope.
+    /// ```
+    /// # use texide::datastructures::scopedmap::ScopedMap;
+    /// let mut cat_colors = ScopedMap::new();
+    /// cat_colors.insert("paganini", "black");
+    /// cat_colors.begin_scope();
+    /// *cat_colors.get_mut(&"paganini").unwrap() = "gray";
+    /// assert_eq!(cat_colors.end_scope(), true);
+    /// assert_eq!(cat_colors.get(&"paganini"), Some(&"gray"));
+    /// ```
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        match self.key_to_value_stack.get_mut(key) {
+            None => None,
+            Some(value_stack) => value_stack.last_mut().and_then(Option::as_mut),
+        }
+    }
+
+    /// Removes and returns the value at the provided key.
+    ///
+    /// Unlike `remove`, the value is removed in whichever scope it was inserted, and so the
+    /// removal is not rolled back at the end of the current scope.
+    /// ```
+    /// # use texide::datastructures::scopedmap::ScopedMap;
+    /// let mut cat_colors = ScopedMap::new();
+    /// cat_colors.insert("paganini", "black");
+    /// cat_colors.begin_scope();
+    /// assert_eq!(cat_colors.take(&"paganini"), Some("black"));
+    /// assert_eq!(cat_colors.end_scope(), true);
+    /// assert_eq!(cat_colors.get(&"paganini"), None);
+    /// ```
+    pub fn take(&mut self, key: &K) -> Option<V> {
+        match self.key_to_value_stack.get_mut(key) {
+            None => None,
+            Some(value_stack) => value_stack.last_mut().and_then(Option::take),
+        }
+    }
+
+    /// Sets the value at the provided key in whichever scope it was last set, or in the global
+    /// scope if it has never been set.
+    ///
+    /// Like `take`, the change is not rolled back at the end of the current scope.
+    /// ```
+    /// # use texide::datastructures::scopedmap::ScopedMap;
+    /// let mut cat_colors = ScopedMap::new();
+    /// cat_colors.insert("paganini", "black");
+    /// cat_colors.begin_scope();
+    /// cat_colors.replace("paganini", "gray");
+    /// cat_colors.replace("mint", "ginger");
+    /// assert_eq!(cat_colors.end_scope(), true);
+    /// assert_eq!(cat_colors.get(&"paganini"), Some(&"gray"));
+    /// assert_eq!(cat_colors.get(&"mint"), Some(&"ginger"));
+    /// ```
+    pub fn replace(&mut self, key: K, val: V) {
+        match self.key_to_value_stack.get_mut(&key) {
+            None => self.insert_global(key, val),
+            Some(value_stack) => *value_stack.last_mut().unwrap() = Some(val),
         }
     }
 
+    /// Returns an iterator over the key, value pairs currently visible in the map, in an
+    /// arbitrary order.
+    /// ```
+    /// # use texide::datastructures::scopedmap::ScopedMap;
+    /// let mut cat_colors = ScopedMap::new();
+    /// cat_colors.insert("paganini", "black");
+    /// cat_colors.begin_scope();
+    /// cat_colors.insert("paganini", "gray");
+    /// assert_eq!(cat_colors.iter().collect::<Vec<_>>(), vec![(&"paganini", &"gray")]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.key_to_value_stack
+            .iter()
+            .filter_map(|(k, value_stack)| match value_stack.last() {
+                Some(Some(v)) => Some((k.as_ref(), v)),
+                _ => None,
+            })
+    }
+
     /// Begins a new scope.
     pub fn begin_scope(&mut self) {
         // Note that `HashSet::new()` is basically a free operation: no allocations will occur
@@ -186,7 +302,7 @@ impl<K: Eq + Hash, V> ScopedMap<K, V> {
     /// ```
     /// # use texide::datastructures::scopedmap::ScopedMap;
     /// let mut cat_colors = ScopedMap::new();
-    /// cat_colors.extend(std::array::IntoIter::new([
+    /// cat_colors.extend(IntoIterator::into_iter([
     ///    ("paganini", "black"),
     ///    ("mint", "ginger"),
     /// ]));
@@ -201,16 +317,16 @@ impl<K: Eq + Hash, V> ScopedMap<K, V> {
 
     /// Returns a new empty `ScopedMap`.
     pub fn new() -> ScopedMap<K, V> {
-        return ScopedMap {
+        ScopedMap {
             key_to_value_stack: HashMap::new(),
             changed_keys_stack: Vec::<HashSet<Rc<K>>>::new(),
-        };
+        }
     }
 
     /// Returns a new `ScopedMap` pre-populated with the provided key, values pairs
     /// ```
     /// # use texide::datastructures::scopedmap::ScopedMap;
-    /// let mut cat_colors = ScopedMap::from_iter(std::array::IntoIter::new([
+    /// let mut cat_colors = ScopedMap::from_iter(IntoIterator::into_iter([
     ///    ("paganini", "black"),
     ///    ("mint", "ginger"),
     /// ]));
@@ -224,6 +340,12 @@ impl<K: Eq + Hash, V> ScopedMap<K, V> {
     }
 }
 
+impl<K: Eq + Hash, V> Default for ScopedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::datastructures::scopedmap::ScopedMap;
@@ -233,18 +355,42 @@ mod tests {
         let mut map = ScopedMap::new();
         map.begin_scope();
         map.insert(3, 5);
-        assert_eq!(map.end_scope(), true);
+        assert!(map.end_scope());
         assert_eq!(map.get(&3), None);
         map.insert(3, 4);
         assert_eq!(map.get(&3), Some(&4));
     }
 
+    #[test]
+    fn remove_global_in_nested_scope() {
+        let mut map = ScopedMap::new();
+        map.insert(3, 5);
+        map.begin_scope();
+        map.insert(3, 4);
+        map.remove_global(3);
+        assert_eq!(map.get(&3), None);
+        assert!(map.end_scope());
+        assert_eq!(map.get(&3), None);
+    }
+
+    #[test]
+    fn insert_after_remove_in_same_scope() {
+        let mut map = ScopedMap::new();
+        map.insert(3, 5);
+        map.begin_scope();
+        map.remove(3);
+        map.insert(3, 4);
+        assert_eq!(map.get(&3), Some(&4));
+        assert!(map.end_scope());
+        assert_eq!(map.get(&3), Some(&5));
+    }
+
     #[test]
     fn insert_global_after_no_insert() {
         let mut map = ScopedMap::new();
         map.begin_scope();
         map.insert_global(3, 5);
-        assert_eq!(map.end_scope(), true);
+        assert!(map.end_scope());
         assert_eq!(map.get(&3), Some(&5));
     }
 }
@@ -1,24 +1,34 @@
 use std::env;
 
+use std::fs;
+use std::io;
+use std::path;
 use std::process;
 use texide::tex::driver;
 
-use std::rc;
-use texide::tex::primitive;
-use texide::tex::primitive::library::conditional;
+use texide::tex::primitive::library;
+use texide::tex::print::Selector;
 use texide::tex::state;
 use texide::tex::state::TexState;
 use texide::tex::token::catcode;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    match args.get(1) {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // With --unicode, all alphabetic Unicode characters are letters, as in XeTeX and LuaTeX.
+    let unicode = match args.iter().position(|arg| arg == "--unicode") {
+        None => false,
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+    };
+    match args.first() {
         None => {
             println!("Pass the tex file as an argument");
             process::exit(1);
         }
         Some(file_name) => {
-            let r = run(file_name);
+            let r = run(file_name, unicode);
             if let Some(err) = r.err() {
                 println!("Failed: {}", err);
                 process::exit(1);
@@ -27,23 +37,42 @@ fn main() {
     };
 }
 
-macro_rules! set_e {
-    ($state: expr, $name: expr, $p: expr) => {
-        &mut $state.set_expansion_primitive($name.to_string(), $p)
-    };
-}
-
-pub fn run(file_name: &str) -> Result<(), anyhow::Error> {
+pub fn run(file_name: &str, unicode: bool) -> Result<(), anyhow::Error> {
     let mut s = state::SimpleState::new();
-    set_e![s, "if", primitive::Expansion::Static(conditional::get_if())];
-    set_e![
-        s,
-        "else",
-        primitive::Expansion::Generic(rc::Rc::new(conditional::get_else()))
-    ];
-    let input_module = &mut s.base_mut().input_module;
-    catcode::set_tex_defaults(&mut input_module.cat_code_map);
-    input_module.open_file(file_name)?;
-    driver::run(s)?;
+    for (name, p) in library::all() {
+        s.set_primitive(name, p);
+    }
+    let base = s.base_mut();
+    if unicode {
+        catcode::set_unicode_defaults(&mut base.input_module.cat_code_map);
+        base.char_codes.set_unicode_defaults();
+    } else {
+        catcode::set_tex_defaults(&mut base.input_module.cat_code_map);
+        base.char_codes.set_initex_defaults();
+    }
+    // As in TeX, the job name is the name of the input file without its directory and extension.
+    let job_name = match path::Path::new(file_name).file_stem() {
+        None => "texput".to_string(),
+        Some(stem) => stem.to_string_lossy().into_owned(),
+    };
+    let log_name = format!("{}.log", job_name);
+    let log = io::BufWriter::new(fs::File::create(&log_name)?);
+    base.printer.open_log(Box::new(log));
+    base.print(
+        Selector::TerminalAndLog,
+        &format!("This is Texide, Version {}", env!("CARGO_PKG_VERSION")),
+    );
+    base.input_module.open_file(file_name)?;
+    base.print_nl(Selector::TerminalAndLog, &format!("({}", file_name));
+    let mut s = driver::run(s)?;
+    let base = s.base_mut();
+    base.print(Selector::TerminalAndLog, ")");
+    base.output_files.close_all()?;
+    base.printer.close_log();
+    base.print_nl(
+        Selector::Terminal,
+        &format!("Transcript written on {}.", log_name),
+    );
+    base.printer.print_ln(Selector::Terminal);
     Ok(())
 }
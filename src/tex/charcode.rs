@@ -0,0 +1,201 @@
+//! Per-character code tables: the lowercase, uppercase, space factor, math and delimiter codes.
+//!
+//! Like catcodes, these codes are assigned per character using the `\lccode`, `\uccode`,
+//! `\sfcode`, `\mathcode` and `\delcode` primitives, and assignments are local to the current
+//! group. Each table is a scoped map; characters that are not in a table have the TeX default
+//! value, which is 0 for the lowercase and uppercase codes, 1000 for the space factor code, the
+//! character itself for the math code and -1 for the delimiter code.
+//!
+//! Two sets of defaults are provided. The IniTeX defaults only assign codes to the ASCII
+//! letters and digits, and make `.` a null delimiter. The Unicode defaults, which are similar to
+//! the defaults in XeTeX and LuaTeX, also assign case codes to every alphabetic Unicode character
+//! based on its case mappings.
+
+use crate::datastructures::scopedmap::ScopedMap;
+
+/// The default lowercase and uppercase code of a character.
+pub const DEFAULT_CASE_CODE: i32 = 0;
+
+/// The default space factor code of a character.
+pub const DEFAULT_SF_CODE: i32 = 1000;
+
+/// The space factor code of uppercase letters in the default tables.
+pub const UPPERCASE_SF_CODE: i32 = 999;
+
+/// The default delimiter code of a character, which means that it is not a delimiter.
+pub const DEFAULT_DEL_CODE: i32 = -1;
+
+/// The per-character code tables.
+#[derive(Default)]
+pub struct CharCodes {
+    pub lc_codes: ScopedMap<char, i32>,
+    pub uc_codes: ScopedMap<char, i32>,
+    pub sf_codes: ScopedMap<char, i32>,
+    pub math_codes: ScopedMap<char, i32>,
+    pub del_codes: ScopedMap<char, i32>,
+}
+
+impl CharCodes {
+    /// Returns new empty tables, in which every character has the default codes.
+    pub fn new() -> CharCodes {
+        Default::default()
+    }
+
+    /// Returns the lowercase code of the character.
+    pub fn lc_code(&self, c: char) -> i32 {
+        self.lc_codes.get(&c).copied().unwrap_or(DEFAULT_CASE_CODE)
+    }
+
+    /// Returns the uppercase code of the character.
+    pub fn uc_code(&self, c: char) -> i32 {
+        self.uc_codes.get(&c).copied().unwrap_or(DEFAULT_CASE_CODE)
+    }
+
+    /// Returns the space factor code of the character.
+    pub fn sf_code(&self, c: char) -> i32 {
+        self.sf_codes.get(&c).copied().unwrap_or(DEFAULT_SF_CODE)
+    }
+
+    /// Returns the math code of the character.
+    pub fn math_code(&self, c: char) -> i32 {
+        self.math_codes.get(&c).copied().unwrap_or(c as i32)
+    }
+
+    /// Returns the delimiter code of the character.
+    pub fn del_code(&self, c: char) -> i32 {
+        self.del_codes.get(&c).copied().unwrap_or(DEFAULT_DEL_CODE)
+    }
+
+    /// Begins a new scope in every table.
+    pub fn begin_scope(&mut self) {
+        self.lc_codes.begin_scope();
+        self.uc_codes.begin_scope();
+        self.sf_codes.begin_scope();
+        self.math_codes.begin_scope();
+        self.del_codes.begin_scope();
+    }
+
+    /// Ends the current scope in every table, and returns false if there is no scope to end.
+    #[must_use]
+    pub fn end_scope(&mut self) -> bool {
+        // Non-short-circuiting so that all tables stay in sync.
+        self.lc_codes.end_scope()
+            & self.uc_codes.end_scope()
+            & self.sf_codes.end_scope()
+            & self.math_codes.end_scope()
+            & self.del_codes.end_scope()
+    }
+
+    /// Assigns the IniTeX default codes: each ASCII letter has its lowercase and uppercase
+    /// versions as codes, and uppercase ASCII letters have space factor code 999. ASCII letters
+    /// have math codes `"7100` plus the character, in the variable family of math italic
+    /// letters, and ASCII digits have math codes `"7000` plus the digit. The delimiter code of
+    /// `.` is 0, so that `\left.` is an empty delimiter.
+    pub fn set_initex_defaults(&mut self) {
+        self.set_letter_codes(('A'..='Z').chain('a'..='z'));
+        self.set_math_codes();
+    }
+
+    /// Assigns the Unicode default codes.
+    ///
+    /// Every alphabetic character is given lowercase and uppercase codes based on its simple
+    /// Unicode case mappings. A letter whose case mapping is not a single character, or
+    /// that has no case at all, has itself as its code. This way `\lowercase` and `\uppercase`
+    /// leave it unchanged, while it is still recognized as a letter by macros that inspect
+    /// `\lccode`. Uppercase letters have space factor code 999.
+    /// ```
+    /// # use texide::tex::charcode::CharCodes;
+    /// let mut char_codes = CharCodes::new();
+    /// char_codes.set_unicode_defaults();
+    /// assert_eq!(char_codes.lc_code('Ж'), 'ж' as i32);
+    /// assert_eq!(char_codes.uc_code('é'), 'É' as i32);
+    /// assert_eq!(char_codes.lc_code('ß'), 'ß' as i32);
+    /// assert_eq!(char_codes.sf_code('Σ'), 999);
+    /// assert_eq!(char_codes.lc_code('1'), 0);
+    /// ```
+    pub fn set_unicode_defaults(&mut self) {
+        self.set_letter_codes(all_chars().filter(|c| c.is_alphabetic()));
+        self.set_math_codes();
+    }
+
+    fn set_math_codes(&mut self) {
+        for c in ('A'..='Z').chain('a'..='z') {
+            self.math_codes.insert(c, 0x7100 + c as i32);
+        }
+        for c in '0'..='9' {
+            self.math_codes.insert(c, 0x7000 + c as i32);
+        }
+        self.del_codes.insert('.', 0);
+    }
+
+    fn set_letter_codes<I: Iterator<Item = char>>(&mut self, letters: I) {
+        for c in letters {
+            self.lc_codes
+                .insert(c, simple_mapping(c, c.to_lowercase()) as i32);
+            self.uc_codes
+                .insert(c, simple_mapping(c, c.to_uppercase()) as i32);
+            if c.is_uppercase() {
+                self.sf_codes.insert(c, UPPERCASE_SF_CODE);
+            }
+        }
+    }
+}
+
+/// Returns every Unicode scalar value.
+pub fn all_chars() -> impl Iterator<Item = char> {
+    ('\u{0}'..='\u{D7FF}').chain('\u{E000}'..=char::MAX)
+}
+
+/// Returns the single character in the mapping, or the original character if the mapping
+/// has more than one character.
+fn simple_mapping<I: Iterator<Item = char>>(c: char, mut mapping: I) -> char {
+    match (mapping.next(), mapping.next()) {
+        (Some(mapped), None) => mapped,
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initex_defaults() {
+        let mut char_codes = CharCodes::new();
+        char_codes.set_initex_defaults();
+        assert_eq!(char_codes.lc_code('A'), 'a' as i32);
+        assert_eq!(char_codes.lc_code('a'), 'a' as i32);
+        assert_eq!(char_codes.uc_code('a'), 'A' as i32);
+        assert_eq!(char_codes.sf_code('A'), 999);
+        assert_eq!(char_codes.sf_code('a'), 1000);
+        assert_eq!(char_codes.lc_code('é'), 0);
+        assert_eq!(char_codes.uc_code('@'), 0);
+        assert_eq!(char_codes.math_code('a'), 0x7161);
+        assert_eq!(char_codes.math_code('1'), 0x7031);
+        assert_eq!(char_codes.math_code('+'), '+' as i32);
+        assert_eq!(char_codes.del_code('.'), 0);
+        assert_eq!(char_codes.del_code('('), -1);
+    }
+
+    #[test]
+    fn unicode_defaults() {
+        let mut char_codes = CharCodes::new();
+        char_codes.set_unicode_defaults();
+        assert_eq!(char_codes.lc_code('Σ'), 'σ' as i32);
+        assert_eq!(char_codes.uc_code('ς'), 'Σ' as i32);
+        assert_eq!(char_codes.lc_code('日'), '日' as i32);
+        assert_eq!(char_codes.sf_code('É'), 999);
+        assert_eq!(char_codes.sf_code('.'), 1000);
+    }
+
+    #[test]
+    fn scopes() {
+        let mut char_codes = CharCodes::new();
+        char_codes.begin_scope();
+        char_codes.sf_codes.insert('.', 3000);
+        assert_eq!(char_codes.sf_code('.'), 3000);
+        assert!(char_codes.end_scope());
+        assert_eq!(char_codes.sf_code('.'), 1000);
+        assert!(!char_codes.end_scope());
+    }
+}
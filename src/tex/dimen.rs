@@ -0,0 +1,199 @@
+//! Dimensions and glue.
+//!
+//! As in TeX, dimensions are stored as integer multiples of the scaled point `sp`, which is
+//! 1/65536 of a point. Using integer arithmetic guarantees that documents are typeset identically
+//! on every machine. Dimensions are printed the way TeX prints them, using the shortest decimal
+//! representation that rounds to the same number of scaled points:
+//! ```
+//! # use texide::tex::dimen::Dimen;
+//! assert_eq!(Dimen::from_pt(10).to_string(), "10.0pt");
+//! assert_eq!(Dimen(98304).to_string(), "1.5pt");
+//! assert_eq!(Dimen(1).to_string(), "0.00002pt");
+//! ```
+//!
+//! Glue consists of a natural width, a stretch and a shrink. The stretch and shrink may be finite
+//! dimensions or infinite with one of the orders `fil`, `fill` and `filll`:
+//! ```
+//! # use texide::tex::dimen::{Dimen, Glue, GlueOrder};
+//! let glue = Glue {
+//!     width: Dimen::from_pt(3),
+//!     stretch: Dimen::from_pt(1),
+//!     stretch_order: GlueOrder::Fil,
+//!     ..Default::default()
+//! };
+//! assert_eq!(glue.to_string(), "3.0pt plus 1.0fil");
+//! ```
+//!
+//! Math glue, like `\thinmuskip`, is also stored as glue. Its finite dimensions are in units of
+//! `mu`, 1/18 of an em in the math symbols font, instead of points:
+//! ```
+//! # use texide::tex::dimen::{Dimen, Glue};
+//! let glue = Glue {
+//!     width: Dimen::from_pt(4),
+//!     stretch: Dimen::from_pt(2),
+//!     ..Default::default()
+//! };
+//! assert_eq!(glue.to_mu_string(), "4.0mu plus 2.0mu");
+//! ```
+
+use std::fmt;
+
+/// The number of scaled points in a point.
+pub const UNITY: i32 = 65536;
+
+/// The largest legal dimension, which is just less than 16384pt.
+pub const MAX_DIMEN: i32 = 0o7777777777;
+
+/// A dimension, in scaled points.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Dimen(pub i32);
+
+impl Dimen {
+    /// Returns the dimension that is the provided number of points.
+    pub fn from_pt(pt: i32) -> Dimen {
+        Dimen(pt * UNITY)
+    }
+
+    /// Returns the dimension multiplied by `n/d`, truncated towards zero. This is `xn_over_d`
+    /// in TeX, which is used for example to scale interword glue by the space factor.
+    pub fn xn_over_d(self, n: i32, d: i32) -> Dimen {
+        Dimen((self.0 as i64 * n as i64 / d as i64) as i32)
+    }
+}
+
+impl fmt::Display for Dimen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_scaled(f, self.0)?;
+        write!(f, "pt")
+    }
+}
+
+/// Writes a number of scaled points in decimal, as in section 103 of TeX: The Program.
+pub fn write_scaled(f: &mut dyn fmt::Write, s: i32) -> fmt::Result {
+    let mut s = s as i64;
+    if s < 0 {
+        write!(f, "-")?;
+        s = -s;
+    }
+    let unity = UNITY as i64;
+    write!(f, "{}.", s / unity)?;
+    s = 10 * (s % unity) + 5;
+    let mut delta = 10;
+    loop {
+        if delta > unity {
+            // Round the last digit.
+            s += 0o100000 - 50000;
+        }
+        write!(f, "{}", s / unity)?;
+        s = 10 * (s % unity);
+        delta *= 10;
+        if s <= delta {
+            return Ok(());
+        }
+    }
+}
+
+/// The order of infinity of the stretch or shrink of glue.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub enum GlueOrder {
+    #[default]
+    Normal,
+    Fil,
+    Fill,
+    Filll,
+}
+
+impl GlueOrder {
+    fn unit(&self, finite_unit: &'static str) -> &'static str {
+        match self {
+            GlueOrder::Normal => finite_unit,
+            GlueOrder::Fil => "fil",
+            GlueOrder::Fill => "fill",
+            GlueOrder::Filll => "filll",
+        }
+    }
+}
+
+/// Glue, or a skip in TeX's terminology.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Glue {
+    pub width: Dimen,
+    pub stretch: Dimen,
+    pub stretch_order: GlueOrder,
+    pub shrink: Dimen,
+    pub shrink_order: GlueOrder,
+}
+
+impl Glue {
+    /// Returns the glue printed as math glue, whose finite dimensions are in `mu`.
+    pub fn to_mu_string(&self) -> String {
+        let mut s = String::new();
+        // Writing to a string cannot fail.
+        self.write_spec(&mut s, "mu").unwrap();
+        s
+    }
+
+    /// Writes the glue with the provided unit for finite dimensions. This is `print_spec` in
+    /// TeX.
+    fn write_spec(&self, f: &mut dyn fmt::Write, unit: &'static str) -> fmt::Result {
+        write_scaled(f, self.width.0)?;
+        write!(f, "{}", unit)?;
+        if self.stretch.0 != 0 {
+            write!(f, " plus ")?;
+            write_scaled(f, self.stretch.0)?;
+            write!(f, "{}", self.stretch_order.unit(unit))?;
+        }
+        if self.shrink.0 != 0 {
+            write!(f, " minus ")?;
+            write_scaled(f, self.shrink.0)?;
+            write!(f, "{}", self.shrink_order.unit(unit))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Glue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_spec(f, "pt")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_dimensions() {
+        for (sp, expected) in [
+            (0, "0.0pt"),
+            (UNITY, "1.0pt"),
+            (-UNITY / 2, "-0.5pt"),
+            (UNITY / 3, "0.33333pt"),
+            (MAX_DIMEN, "16383.99998pt"),
+            (-MAX_DIMEN, "-16383.99998pt"),
+        ] {
+            assert_eq!(Dimen(sp).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn print_glue() {
+        let glue = Glue {
+            width: Dimen::from_pt(3),
+            stretch: Dimen::from_pt(2),
+            stretch_order: GlueOrder::Filll,
+            shrink: Dimen(UNITY / 2),
+            shrink_order: GlueOrder::Normal,
+        };
+        assert_eq!(glue.to_string(), "3.0pt plus 2.0filll minus 0.5pt");
+        assert_eq!(Glue::default().to_string(), "0.0pt");
+        assert_eq!(glue.to_mu_string(), "3.0mu plus 2.0filll minus 0.5mu");
+    }
+
+    #[test]
+    fn xn_over_d() {
+        assert_eq!(Dimen(10).xn_over_d(2, 3), Dimen(6));
+        assert_eq!(Dimen(-10).xn_over_d(2, 3), Dimen(-6));
+        assert_eq!(Dimen(MAX_DIMEN).xn_over_d(1000, 2000), Dimen(MAX_DIMEN / 2));
+    }
+}
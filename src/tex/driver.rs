@@ -1,36 +1,741 @@
 //! TeX execution driver.
 
+use crate::tex::dimen::{Dimen, Glue, MAX_DIMEN};
+use crate::tex::error;
+use crate::tex::font::Fonts;
+use crate::tex::group::{Group, GroupType};
+use crate::tex::hyphenation::Language;
+use crate::tex::ligature;
+use crate::tex::linebreak;
+use crate::tex::nest::{ListState, Mode, IGNORE_DEPTH};
+use crate::tex::node::{BoxNode, Char, GlueSign, Ligature, Math, MathKind, Node, INF_PENALTY};
+use crate::tex::pack;
+use crate::tex::page;
+use crate::tex::parameter::{DimenParam, GlueParam, IntParam, TokenListParam};
 use crate::tex::primitive;
-use crate::tex::primitive::ExpansionGeneric;
-use crate::tex::state::TexState;
+use crate::tex::primitive::library::boxes;
+use crate::tex::primitive::library::discretionary;
+use crate::tex::primitive::library::page as page_primitives;
+use crate::tex::primitive::{ExecutionGeneric, ExpansionGeneric};
+use crate::tex::print::Selector;
+use crate::tex::state::{BaseState, TexState};
+use crate::tex::token::catcode::CatCode;
 use crate::tex::token::stream;
-use crate::tex::token::stream::{Stream};
+use crate::tex::token::stream::Stream;
 use crate::tex::token::token;
+use std::io;
 
 // TODO: accept a mutable reference to the state; we don't need to own it
 pub fn run<S: TexState<S>>(state: S) -> anyhow::Result<S> {
-    let mut input = ExpandedStream::<S> {
-        unexpanded_stream: UnexpandedStream::<S> {
-            s: state,
-            stack: vec![],
+    let mut input = ExpandedStream::new(state);
+    let every_job = input
+        .state()
+        .base()
+        .parameters
+        .token_list(TokenListParam::EveryJob);
+    if !every_job.is_empty() {
+        input.push(stream::VecStream::new(every_job.to_vec()));
+    }
+    while !input.state().base().job_ended {
+        let token = match input.next()? {
+            None => break,
+            Some(token) => token,
+        };
+        let (c, cat_code) = match token.value {
+            token::Value::Character(c, cat_code) if cat_code != CatCode::Active => (c, cat_code),
+            _ => {
+                match input.state().base().primitives.get(&token.value) {
+                    Some(primitive::Primitive::Execution(command)) => {
+                        let command = command.clone();
+                        command.call(token, &mut input)?;
+                        continue;
+                    }
+                    Some(primitive::Primitive::Variable(variable)) => {
+                        let variable = *variable;
+                        variable.assign(token, &mut input, false)?;
+                        continue;
+                    }
+                    Some(primitive::Primitive::Font(id)) => {
+                        let id = *id;
+                        input.state_mut().base_mut().fonts.set_current(id, false);
+                        continue;
+                    }
+                    Some(primitive::Primitive::Character(c, cat_code)) => (*c, *cat_code),
+                    // A character defined using \chardef is typeset like a character with
+                    // catcode other; in particular, it never begins or ends a group.
+                    Some(primitive::Primitive::Constant(primitive::ConstantKind::Char, n)) => {
+                        // The code was validated by \chardef.
+                        (char::from_u32(*n as u32).unwrap(), CatCode::Other)
+                    }
+                    // A math character defined using \mathchardef is only allowed in math mode.
+                    // As formulas do not use the math families yet, it is typeset in the
+                    // current font, like the other characters of a formula.
+                    Some(primitive::Primitive::Constant(primitive::ConstantKind::MathChar, n)) => {
+                        if !input.state().base().nest.mode().is_math() {
+                            return Err(missing_dollar_error(token));
+                        }
+                        // The code was validated by \mathchardef.
+                        (char::from_u32((*n % 256) as u32).unwrap(), CatCode::Other)
+                    }
+                    // Expandable commands only reach here when preceded by \noexpand, in which
+                    // case they mean \relax.
+                    Some(primitive::Primitive::Expansion(_))
+                    | Some(primitive::Primitive::Macro(_)) => continue,
+                    None => {
+                        return Err(error::new_token_error(
+                            token,
+                            "Undefined control sequence".to_string(),
+                            vec![],
+                        ))
+                    }
+                }
+            }
+        };
+        // Explicit characters and implicit characters created using \let are handled
+        // identically.
+        match cat_code {
+            CatCode::BeginGroup => {
+                input.state_mut().base_mut().begin_group(GroupType::Simple);
+            }
+            CatCode::EndGroup => match input.state().base().group_type() {
+                GroupType::HBox | GroupType::AdjustedHBox | GroupType::VBox | GroupType::VTop => {
+                    boxes::package(token, &mut input)?;
+                }
+                GroupType::Output => {
+                    page::resume_output(token, &mut input)?;
+                }
+                GroupType::Insert => {
+                    page_primitives::end_insert(token, &mut input)?;
+                }
+                GroupType::Disc => {
+                    discretionary::end_list(token, &mut input)?;
+                }
+                _ => {
+                    end_group(token, &mut input, GroupType::Simple)?;
+                }
+            },
+            _ => {
+                typeset_character(token, c, cat_code, &mut input)?;
+            }
+        };
+    }
+    let base = input.state_mut().base_mut();
+    // The job ends as if with \par, so that an unfinished paragraph is not lost.
+    end_paragraph(base)?;
+    // As in TeX, only \end outputs the material remaining on the page and in the contributions.
+    let group_level = base.group_level();
+    if group_level > 0 {
+        let text = format!("(end occurred inside a group at level {})", group_level);
+        base.print_nl(Selector::TerminalAndLog, &text);
+    }
+    Ok(input.into_state())
+}
+
+const EXTRA_END_GROUP_ERROR_HELP: &str = "there is no open group to end";
+
+/// Ends the current group, which is expected to be of the provided type, inserts any
+/// tokens saved using `\aftergroup` into the input, and returns the group.
+///
+/// The token is the token that ended the group, and is used for error messages.
+pub fn end_group<S: TexState<S>>(
+    token: token::Token,
+    input: &mut ExpandedStream<S>,
+    expected: GroupType,
+) -> anyhow::Result<Group> {
+    let actual = input.state().base().group_type();
+    if actual != expected {
+        let (message, notes) = match (actual, &token.value) {
+            (GroupType::Bottom, token::Value::Character(..)) => (
+                "Extra }".to_string(),
+                vec![EXTRA_END_GROUP_ERROR_HELP.to_string()],
+            ),
+            (GroupType::Bottom, token::Value::ControlSequence(_, name)) => (
+                format!("Extra \\{}", name),
+                vec![EXTRA_END_GROUP_ERROR_HELP.to_string()],
+            ),
+            (GroupType::MathShift, token::Value::Character(..)) => (
+                "Extra }, or forgotten $".to_string(),
+                vec!["the current group is a formula begun with $".to_string()],
+            ),
+            (GroupType::SemiSimple, _) => (
+                "Extra }, or forgotten \\endgroup".to_string(),
+                vec!["the current group was begun with \\begingroup".to_string()],
+            ),
+            (_, token::Value::ControlSequence(_, name)) => (
+                format!("Extra \\{}, or forgotten }}", name),
+                vec!["the current group was begun with a begin group character".to_string()],
+            ),
+            (_, token::Value::Character(..)) => (
+                "Extra }".to_string(),
+                vec![format!("the current group has type {:?}", actual)],
+            ),
+        };
+        return Err(error::new_token_error(token, message, notes));
+    }
+    // The group exists because its type is not `GroupType::Bottom`.
+    let mut group = input.state_mut().base_mut().end_group().unwrap();
+    if !group.after_group.is_empty() {
+        input.push(stream::VecStream::new(std::mem::take(
+            &mut group.after_group,
+        )));
+    }
+    Ok(group)
+}
+
+/// Executes a character in the current mode.
+fn typeset_character<S: TexState<S>>(
+    token: token::Token,
+    c: char,
+    cat_code: CatCode,
+    input: &mut ExpandedStream<S>,
+) -> anyhow::Result<()> {
+    let mode = input.state().base().nest.mode();
+    match cat_code {
+        CatCode::Letter | CatCode::Other => match mode {
+            Mode::Vertical | Mode::InternalVertical => {
+                input.push(stream::VecStream::new(vec![token]));
+                begin_paragraph(input, true);
+            }
+            Mode::Horizontal | Mode::RestrictedHorizontal => {
+                ligature::append_characters(input, c, true)?;
+            }
+            Mode::Math | Mode::DisplayMath => {
+                let base = input.state_mut().base_mut();
+                let font = base.fonts.current();
+                base.nest.append(Node::Char(Char { font, c }));
+            }
+        },
+        CatCode::Space => {
+            if mode.is_horizontal() {
+                append_space(input.state_mut().base_mut());
+            }
+        }
+        CatCode::MathShift => match mode {
+            Mode::Vertical | Mode::InternalVertical => {
+                input.push(stream::VecStream::new(vec![token]));
+                begin_paragraph(input, true);
+            }
+            Mode::Horizontal | Mode::RestrictedHorizontal => begin_math(input)?,
+            Mode::Math | Mode::DisplayMath => end_math(token, input)?,
         },
+        CatCode::Superscript | CatCode::Subscript => {
+            return Err(match mode.is_math() {
+                false => missing_dollar_error(token),
+                true => error::new_token_error(
+                    token,
+                    "Superscripts and subscripts are not supported yet".to_string(),
+                    vec![],
+                ),
+            });
+        }
+        CatCode::AlignmentTab => {
+            return Err(error::new_token_error(
+                token,
+                format!("Misplaced alignment tab character {}", c),
+                vec![
+                    "there is no \\halign or \\valign for this character to belong to".to_string(),
+                ],
+            ));
+        }
+        CatCode::Parameter => {
+            return Err(illegal_in_mode_error(
+                token,
+                &format!("macro parameter character {}", c),
+                mode,
+            ));
+        }
+        // Begin group and end group characters are handled by the caller, and active characters
+        // are control sequences.
+        CatCode::BeginGroup | CatCode::EndGroup | CatCode::Active => {}
+    }
+    Ok(())
+}
+
+/// Updates the space factor after a character with the provided space factor code has been
+/// appended to a horizontal list.
+pub fn update_space_factor(level: &mut ListState, sf_code: i32) {
+    level.space_factor = if sf_code == 1000 {
+        1000
+    } else if sf_code < 1000 {
+        // A space factor code of 0 leaves the space factor unchanged.
+        match sf_code {
+            0 => level.space_factor,
+            _ => sf_code,
+        }
+    } else if level.space_factor < 1000 {
+        // The space factor never jumps from below 1000 to above 1000, so that for example the
+        // period in "A." after an uppercase letter doesn't end a sentence.
+        1000
+    } else {
+        sf_code
     };
-    loop {
-        match input.next()? {
-            None => break,
-            Some(token) => {
-                // TODO: this is where the execution code goes
-                println!("{:?}", token.value)
+}
+
+/// Appends interword glue to the current horizontal list, scaled by the space factor.
+///
+/// If the space factor is at least 2000 and `\xspaceskip` is not zero, the glue is
+/// `\xspaceskip`. Otherwise it is `\spaceskip` if that is not zero, or else the interword glue
+/// of the current font, with the stretch multiplied and the shrink divided by the space
+/// factor over 1000. If the space factor is at least 2000, the extra space of the font is
+/// added to the width. This is `app_space` in TeX.
+pub fn append_space<S>(base: &mut BaseState<S>) {
+    let space_factor = base.nest.current().space_factor;
+    let space_skip = base.parameters.glue(GlueParam::SpaceSkip);
+    let xspace_skip = base.parameters.glue(GlueParam::XSpaceSkip);
+    let font = base.fonts.get(base.fonts.current());
+    let node = if space_factor == 1000 {
+        match space_skip == Glue::default() {
+            true => Node::new_glue(font.space_glue()),
+            false => Node::new_param_glue(GlueParam::SpaceSkip, space_skip),
+        }
+    } else if space_factor >= 2000 && xspace_skip != Glue::default() {
+        Node::new_param_glue(GlueParam::XSpaceSkip, xspace_skip)
+    } else {
+        let mut glue = match space_skip == Glue::default() {
+            true => font.space_glue(),
+            false => space_skip,
+        };
+        if space_factor >= 2000 {
+            glue.width = Dimen(glue.width.0 + font.extra_space().0);
+        }
+        glue.stretch = glue.stretch.xn_over_d(space_factor, 1000);
+        glue.shrink = glue.shrink.xn_over_d(1000, space_factor);
+        Node::new_glue(glue)
+    };
+    base.nest.append(node);
+}
+
+/// Begins a paragraph: appends `\parskip` glue to the current vertical list, enters horizontal
+/// mode, appends an indentation box of width `\parindent` if requested, and inserts
+/// `\everypar`. This is `new_graf` in TeX.
+pub fn begin_paragraph<S: TexState<S>>(input: &mut ExpandedStream<S>, indent: bool) {
+    let base = input.state_mut().base_mut();
+    base.nest.current_mut().prev_graf = 0;
+    if base.nest.mode() == Mode::Vertical || !base.nest.current().list.is_empty() {
+        let par_skip = base.parameters.glue(GlueParam::ParSkip);
+        base.nest
+            .append(Node::new_param_glue(GlueParam::ParSkip, par_skip));
+    }
+    base.nest.push(Mode::Horizontal);
+    set_paragraph_language(base);
+    if indent {
+        base.nest.append(Node::HList(BoxNode {
+            width: base.parameters.dimen(DimenParam::ParIndent),
+            ..Default::default()
+        }));
+    }
+    let every_par = base.parameters.token_list(TokenListParam::EveryPar);
+    if !every_par.is_empty() {
+        input.push(stream::VecStream::new(every_par.to_vec()));
+    }
+}
+
+/// Ends the current paragraph, if the current mode is horizontal mode, and appends its lines to
+/// the enclosing vertical list. This is `end_graf` in TeX.
+pub fn end_paragraph<S>(base: &mut BaseState<S>) -> anyhow::Result<()> {
+    if base.nest.mode() != Mode::Horizontal {
+        return Ok(());
+    }
+    // Horizontal mode is never the outer mode.
+    let level = base.nest.pop().unwrap();
+    if !level.list.is_empty() {
+        let widow_penalty = base.parameters.int(IntParam::WidowPenalty);
+        append_lines(base, level.list, level.par_language, widow_penalty)?;
+    }
+    normal_paragraph(base);
+    Ok(())
+}
+
+/// Sets the language of the paragraph that has just begun or resumed after a display to
+/// `\language`, along with the hyphenation minimums.
+fn set_paragraph_language<S>(base: &mut BaseState<S>) {
+    let language = Language::current(&base.parameters);
+    let level = base.nest.current_mut();
+    level.language = language.number;
+    level.par_language = language;
+}
+
+/// Breaks the list of a paragraph into lines and appends them to the current vertical list,
+/// and returns the last line.
+fn append_lines<S>(
+    base: &mut BaseState<S>,
+    mut list: Vec<Node>,
+    language: Language,
+    widow_penalty: i32,
+) -> anyhow::Result<BoxNode> {
+    // As in TeX, the paragraph ends with \parfillskip instead of any final space.
+    if let Some(Node::Glue(_)) = list.last() {
+        list.pop();
+    }
+    list.push(Node::Penalty(INF_PENALTY));
+    let par_fill_skip = base.parameters.glue(GlueParam::ParFillSkip);
+    list.push(Node::new_param_glue(GlueParam::ParFillSkip, par_fill_skip));
+    linebreak::line_break(base, list, language, widow_penalty)
+}
+
+/// Resets the parameters that describe the shape of a single paragraph. This is
+/// `normal_paragraph` in TeX.
+pub fn normal_paragraph<S>(base: &mut BaseState<S>) {
+    let parameters = &mut base.parameters;
+    if parameters.int(IntParam::Looseness) != 0 {
+        parameters.set_int(IntParam::Looseness, 0, false);
+    }
+    if parameters.dimen(DimenParam::HangIndent) != Dimen(0) {
+        parameters.set_dimen(DimenParam::HangIndent, Dimen(0), false);
+    }
+    if parameters.int(IntParam::HangAfter) != 1 {
+        parameters.set_int(IntParam::HangAfter, 1, false);
+    }
+    if !parameters.par_shape().is_empty() {
+        parameters.set_par_shape(Default::default(), false);
+    }
+}
+
+/// Appends a box, which must be an hlist or vlist node, to the current vertical list.
+///
+/// Unless `\prevdepth` is the ignore depth, the box is preceded by interline glue: `\baselineskip`
+/// reduced so that the baselines of the previous box and this box are `\baselineskip` apart, or
+/// `\lineskip` if that would leave less than `\lineskiplimit` between the boxes. This is
+/// `append_to_vlist` in TeX.
+pub fn append_to_vlist<S>(base: &mut BaseState<S>, node: Node) {
+    let (height, depth) = match node.as_box() {
+        Some(b) => (b.height, b.depth),
+        None => panic!("only boxes are appended to vertical lists with interline glue"),
+    };
+    let prev_depth = base.nest.current().prev_depth;
+    if prev_depth > IGNORE_DEPTH {
+        let baseline_skip = base.parameters.glue(GlueParam::BaselineSkip);
+        let d = Dimen(baseline_skip.width.0 - prev_depth.0 - height.0);
+        let glue = match d < base.parameters.dimen(DimenParam::LineSkipLimit) {
+            true => Node::new_param_glue(
+                GlueParam::LineSkip,
+                base.parameters.glue(GlueParam::LineSkip),
+            ),
+            false => Node::new_param_glue(
+                GlueParam::BaselineSkip,
+                Glue {
+                    width: d,
+                    ..baseline_skip
+                },
+            ),
+        };
+        base.nest.append(glue);
+    }
+    base.nest.append(node);
+    base.nest.current_mut().prev_depth = depth;
+}
+
+/// Enters math mode after a math shift character in horizontal mode. Two math shift characters
+/// in a paragraph begin a displayed formula. This is `init_math` in TeX.
+fn begin_math<S: TexState<S>>(input: &mut ExpandedStream<S>) -> anyhow::Result<()> {
+    let display = match input.state().base().nest.mode() {
+        Mode::Horizontal => match input.unexpanded_stream().next()? {
+            None => false,
+            Some(next) => match is_math_shift(input, &next) {
+                true => true,
+                false => {
+                    input.push(stream::VecStream::new(vec![next]));
+                    false
+                }
+            },
+        },
+        _ => false,
+    };
+    let base = input.state_mut().base_mut();
+    if !display {
+        base.begin_group(GroupType::MathShift);
+        base.nest.push(Mode::Math);
+        let every_math = base.parameters.token_list(TokenListParam::EveryMath);
+        if !every_math.is_empty() {
+            input.push(stream::VecStream::new(every_math.to_vec()));
+        }
+        return Ok(());
+    }
+    // The part of the paragraph before the display is broken into lines, and the paragraph is
+    // resumed after the display.
+    let level = base.nest.pop().unwrap();
+    let pre_display_size = match level.list.is_empty() {
+        true => Dimen(-MAX_DIMEN),
+        false => {
+            let widow_penalty = base.parameters.int(IntParam::DisplayWidowPenalty);
+            let line = append_lines(base, level.list, level.par_language, widow_penalty)?;
+            let quad = base.fonts.get(base.fonts.current()).param(6);
+            pre_display_size(&line, quad.unwrap_or_default(), &base.fonts)
+        }
+    };
+    let (display_indent, display_width) = display_shape(base);
+    base.begin_group(GroupType::MathShift);
+    base.nest.push(Mode::DisplayMath);
+    let parameters = &mut base.parameters;
+    parameters.set_dimen(DimenParam::PreDisplaySize, pre_display_size, false);
+    parameters.set_dimen(DimenParam::DisplayWidth, display_width, false);
+    parameters.set_dimen(DimenParam::DisplayIndent, display_indent, false);
+    let every_display = parameters.token_list(TokenListParam::EveryDisplay);
+    if !every_display.is_empty() {
+        input.push(stream::VecStream::new(every_display.to_vec()));
+    }
+    Ok(())
+}
+
+/// Returns the width of the text in the last line of a paragraph before a display, plus two
+/// quads, which is where the text ends. If the glue of the line stretched or shrunk before the
+/// text ends, the position of the end is unknown and the result is the maximum dimension.
+/// Without text, the result is the negative of the maximum dimension.
+fn pre_display_size(line: &BoxNode, quad: Dimen, fonts: &Fonts) -> Dimen {
+    let mut v = line.shift.0 as i64 + 2 * quad.0 as i64;
+    let mut w = -MAX_DIMEN as i64;
+    for node in &line.list {
+        let (d, visible) = match node {
+            Node::Char(Char { font, c }) | Node::Ligature(Ligature { font, c, .. }) => {
+                (fonts.get(*font).char(*c).map_or(0, |m| m.width.0), true)
             }
+            Node::HList(b) | Node::VList(b) => (b.width.0, true),
+            Node::Rule(r) => (r.width.map_or(0, |w| w.0), true),
+            Node::Kern(k) => (k.width.0, false),
+            Node::Math(m) => (m.width.0, false),
+            Node::Glue(g) => {
+                let set = line.glue_set;
+                let changed = match set.sign {
+                    GlueSign::Stretching => {
+                        set.order == g.glue.stretch_order && g.glue.stretch.0 != 0
+                    }
+                    GlueSign::Shrinking => set.order == g.glue.shrink_order && g.glue.shrink.0 != 0,
+                    GlueSign::Normal => false,
+                };
+                if changed {
+                    v = MAX_DIMEN as i64;
+                }
+                // Leaders are visible.
+                (g.glue.width.0, g.leaders.is_some())
+            }
+            _ => (0, false),
         };
+        if v < MAX_DIMEN as i64 {
+            v += d as i64;
+        }
+        if visible {
+            if v >= MAX_DIMEN as i64 {
+                return Dimen(MAX_DIMEN);
+            }
+            w = v;
+        }
+    }
+    Dimen(w as i32)
+}
+
+/// Returns the indentation and width of a display that follows line `\prevgraf` of a
+/// paragraph. The display has the shape of the line two lines later, since it takes the space
+/// of about three lines.
+fn display_shape<S>(base: &BaseState<S>) -> (Dimen, Dimen) {
+    let parameters = &base.parameters;
+    let prev_graf = base.nest.current().prev_graf;
+    let par_shape = parameters.par_shape();
+    if !par_shape.is_empty() {
+        let n = par_shape.len() as i32;
+        return par_shape[((prev_graf + 2).min(n) - 1) as usize];
     }
-    Ok(input.unexpanded_stream.s)
+    let hsize = parameters.dimen(DimenParam::HSize);
+    let hang_indent = parameters.dimen(DimenParam::HangIndent);
+    let hang_after = parameters.int(IntParam::HangAfter);
+    if hang_indent != Dimen(0)
+        && ((hang_after >= 0 && prev_graf + 2 > hang_after) || prev_graf + 1 < -hang_after)
+    {
+        (
+            Dimen(hang_indent.0.max(0)),
+            Dimen(hsize.0 - hang_indent.0.abs()),
+        )
+    } else {
+        (Dimen(0), hsize)
+    }
+}
+
+/// Leaves math mode after a math shift character. This is `after_math` in TeX.
+fn end_math<S: TexState<S>>(
+    token: token::Token,
+    input: &mut ExpandedStream<S>,
+) -> anyhow::Result<()> {
+    if input.state().base().group_type() != GroupType::MathShift {
+        return Err(error::new_token_error(
+            token,
+            "Missing } inserted".to_string(),
+            vec!["a group begun inside the formula is still open".to_string()],
+        ));
+    }
+    // TODO: convert the math list to a horizontal list once math typesetting is supported. Until
+    //  then, the characters of the formula are typeset like text.
+    if input.state().base().nest.mode() == Mode::Math {
+        end_group(token, input, GroupType::MathShift)?;
+        let base = input.state_mut().base_mut();
+        // The formula is in math mode, which is never the outer mode.
+        let level = base.nest.pop().unwrap();
+        let math_surround = base.parameters.dimen(DimenParam::MathSurround);
+        base.nest.append(Node::Math(Math {
+            kind: MathKind::Before,
+            width: math_surround,
+        }));
+        for node in level.list {
+            base.nest.append(node);
+        }
+        base.nest.append(Node::Math(Math {
+            kind: MathKind::After,
+            width: math_surround,
+        }));
+        base.nest.current_mut().space_factor = 1000;
+        return Ok(());
+    }
+    match input.next()? {
+        Some(next) if is_math_shift(input, &next) => {}
+        _ => {
+            return Err(error::new_token_error(
+                token,
+                "Display math should end with $$".to_string(),
+                vec!["this math shift character must be followed by another one".to_string()],
+            ))
+        }
+    }
+    let base = input.state_mut().base_mut();
+    // The display is in display math mode, which is never the outer mode.
+    let level = base.nest.pop().unwrap();
+    let parameters = &base.parameters;
+    let pre_display_size = parameters.dimen(DimenParam::PreDisplaySize);
+    let display_width = parameters.dimen(DimenParam::DisplayWidth);
+    let display_indent = parameters.dimen(DimenParam::DisplayIndent);
+    let pre_display_penalty = parameters.int(IntParam::PreDisplayPenalty);
+    let post_display_penalty = parameters.int(IntParam::PostDisplayPenalty);
+    let (mut b, badness, _) = pack::hpack(level.list, pack::Spec::NATURAL, parameters, &base.fonts);
+    base.last_badness = badness;
+    // The display is centered in the display width.
+    let d = Dimen((display_width.0 - b.width.0) / 2);
+    b.shift = Dimen(display_indent.0 + d.0);
+    let (above, below) = match b.shift <= pre_display_size {
+        true => (GlueParam::AboveDisplaySkip, GlueParam::BelowDisplaySkip),
+        false => (
+            GlueParam::AboveDisplayShortSkip,
+            GlueParam::BelowDisplayShortSkip,
+        ),
+    };
+    let (above_skip, below_skip) = (parameters.glue(above), parameters.glue(below));
+    base.nest.append(Node::Penalty(pre_display_penalty));
+    base.nest.append(Node::new_param_glue(above, above_skip));
+    append_to_vlist(base, Node::HList(b));
+    base.nest.append(Node::Penalty(post_display_penalty));
+    base.nest.append(Node::new_param_glue(below, below_skip));
+    base.nest.current_mut().prev_graf += 3;
+    // The paragraph resumes after the display.
+    end_group(token, input, GroupType::MathShift)?;
+    let base = input.state_mut().base_mut();
+    base.nest.push(Mode::Horizontal);
+    set_paragraph_language(base);
+    // A space after the display is ignored.
+    match input.next()? {
+        Some(next) if next.value == token::Value::Character(' ', CatCode::Space) => {}
+        Some(next) => input.push(stream::VecStream::new(vec![next])),
+        None => {}
+    }
+    Ok(())
+}
+
+fn is_math_shift<S: TexState<S>>(input: &ExpandedStream<S>, token: &token::Token) -> bool {
+    matches!(
+        input.state().base().meaning(&token.value),
+        Some(primitive::Primitive::Character(_, CatCode::MathShift))
+    )
+}
+
+/// Prepares for a command that only appends to horizontal lists, like `\hskip`. In vertical
+/// mode, the command is put back into the input and a paragraph is begun, so that it is read
+/// again in horizontal mode; in this case true is returned and the command does nothing more.
+pub fn leave_vertical_mode<S: TexState<S>>(
+    token: token::Token,
+    input: &mut ExpandedStream<S>,
+) -> bool {
+    if !input.state().base().nest.mode().is_vertical() {
+        return false;
+    }
+    input.push(stream::VecStream::new(vec![token]));
+    begin_paragraph(input, true);
+    true
+}
+
+/// Prepares for a command that only appends to vertical lists, like `\vskip`. In a paragraph,
+/// `\par` and the command are put back into the input, so that the paragraph ends before the
+/// command is read again; in this case true is returned and the command does nothing more.
+/// The command is not allowed in restricted horizontal mode or in math mode. This is
+/// `head_for_vmode` in TeX.
+///
+/// The description is how the command is described in error messages.
+pub fn head_for_vertical_mode<S: TexState<S>>(
+    token: token::Token,
+    input: &mut ExpandedStream<S>,
+    description: &str,
+) -> anyhow::Result<bool> {
+    let mode = input.state().base().nest.mode();
+    match mode {
+        Mode::Vertical | Mode::InternalVertical => Ok(false),
+        Mode::Horizontal => {
+            input.push(stream::VecStream::new(vec![
+                token::Token::new_control_sequence("par"),
+                token,
+            ]));
+            Ok(true)
+        }
+        Mode::RestrictedHorizontal => Err(illegal_in_mode_error(token, description, mode)),
+        Mode::Math | Mode::DisplayMath => Err(missing_dollar_error(token)),
+    }
+}
+
+/// Returns the error for a command that is only allowed in math mode, used outside of it.
+pub fn missing_dollar_error(token: token::Token) -> anyhow::Error {
+    error::new_token_error(
+        token,
+        "Missing $ inserted".to_string(),
+        vec!["this command can only appear in math mode".to_string()],
+    )
+}
+
+/// Returns the error for a command that cannot be used in the current mode.
+///
+/// The description is how the command is described in the message, for example `\hrule` or
+/// `macro parameter character #`.
+pub fn illegal_in_mode_error(token: token::Token, description: &str, mode: Mode) -> anyhow::Error {
+    error::new_token_error(
+        token,
+        format!("You can't use `{}' in {}", description, mode.name()),
+        vec![],
+    )
 }
 
 // TODO: maybe a better name?
 struct UnexpandedStream<S> {
     s: S,
     stack: Vec<stream::VecStream>,
+    /// The index in the stack of a stream whose tokens should not be expanded, because they
+    /// were inserted by `\noexpand`, or by `\the` in an expanded token list. The mark belongs to
+    /// that stream, so tokens pushed in front of it, for example by `\expandafter`, are expanded
+    /// as usual.
+    dont_expand: Option<usize>,
+    /// The index in the stack of the stream of the output routine, while it is being read.
+    output: Option<usize>,
+    /// The token lists that were being read when each file that is open was opened using
+    /// `\input`.
+    suspended: Vec<SuspendedTokenLists>,
+}
+
+/// The token lists that were being read when a file was opened using `\input`. As in TeX, the
+/// file is read before them, and they are resumed when it ends.
+struct SuspendedTokenLists {
+    stack: Vec<stream::VecStream>,
+    dont_expand: Option<usize>,
+    output: Option<usize>,
+    /// The number of open files after the file was opened.
+    num_open_files: usize,
+}
+
+impl<S> UnexpandedStream<S> {
+    /// Returns true if the next token is a token of the stream marked as unexpandable.
+    fn next_is_unexpandable(&self) -> bool {
+        self.dont_expand.is_some() && self.dont_expand == self.stack.len().checked_sub(1)
+    }
 }
 
 impl<S: TexState<S>> stream::Stream for UnexpandedStream<S> {
@@ -45,10 +750,38 @@ impl<S: TexState<S>> stream::Stream for UnexpandedStream<S> {
     fn prepare_imut_peek(&mut self) -> anyhow::Result<()> {
         loop {
             match self.stack.last_mut() {
-                None => return self.s.base_mut().input_module.prepare_imut_peek(),
+                None => {
+                    let base = self.s.base_mut();
+                    base.input_module.prepare_imut_peek()?;
+                    if !base.input_module.take_end_of_file() {
+                        return Ok(());
+                    }
+                    let num_open_files = base.input_module.num_open_files();
+                    if let Some(suspended) = self.suspended.last() {
+                        if suspended.num_open_files > num_open_files {
+                            // A file opened using \input has ended.
+                            base.print(Selector::TerminalAndLog, ")");
+                            let suspended = self.suspended.pop().unwrap();
+                            self.stack = suspended.stack;
+                            self.dont_expand = suspended.dont_expand;
+                            self.output = suspended.output;
+                        }
+                    }
+                    // As in e-TeX, \everyeof is inserted when the input file ends.
+                    let every_eof = base.parameters.token_list(TokenListParam::EveryEof);
+                    if !every_eof.is_empty() {
+                        self.stack.push(stream::VecStream::new(every_eof.to_vec()));
+                    }
+                }
                 Some(top) => match top.peek()? {
                     None => {
                         self.stack.pop();
+                        if self.output == Some(self.stack.len()) {
+                            self.output = None;
+                        }
+                        if self.dont_expand == Some(self.stack.len()) {
+                            self.dont_expand = None;
+                        }
                         continue;
                     }
                     Some(..) => return Ok(()),
@@ -87,6 +820,30 @@ impl<S: TexState<S>> stream::Stream for ExpandedStream<S> {
 }
 
 impl<S: TexState<S>> ExpandedStream<S> {
+    /// Returns a new `ExpandedStream` that reads from the input module of the provided state.
+    pub fn new(state: S) -> ExpandedStream<S> {
+        ExpandedStream {
+            unexpanded_stream: UnexpandedStream {
+                s: state,
+                stack: vec![],
+                dont_expand: None,
+                output: None,
+                suspended: Vec::new(),
+            },
+        }
+    }
+
+    /// Consumes the stream and returns the state.
+    pub fn into_state(self) -> S {
+        self.unexpanded_stream.s
+    }
+
+    /// Pushes a stream of tokens to the front of the input. These tokens will be returned
+    /// before any other tokens in the input.
+    pub fn push(&mut self, stream: stream::VecStream) {
+        self.unexpanded_stream.stack.push(stream);
+    }
+
     pub fn state(&self) -> &S {
         &self.unexpanded_stream.s
     }
@@ -103,24 +860,83 @@ impl<S: TexState<S>> ExpandedStream<S> {
         &mut self.unexpanded_stream
     }
 
+    /// Pushes the tokens of the output routine to the front of the input, and remembers their
+    /// stream so that [at_end_of_output](ExpandedStream::at_end_of_output) can find it.
+    pub fn push_output(&mut self, stream: stream::VecStream) {
+        self.push(stream);
+        self.unexpanded_stream.output = Some(self.unexpanded_stream.stack.len() - 1);
+    }
+
+    /// Returns true if the last token read was the last token of the output routine pushed by
+    /// [push_output](ExpandedStream::push_output), and forgets the output routine's stream.
+    pub fn at_end_of_output(&mut self) -> bool {
+        let stream = &mut self.unexpanded_stream;
+        match stream.output.take() {
+            None => false,
+            Some(index) => {
+                index + 1 == stream.stack.len()
+                    && matches!(stream.stack[index].imut_peek(), Ok(None))
+            }
+        }
+    }
+
+    /// Begins reading the file before the rest of the input, as `\input` does.
+    pub fn push_file(&mut self, file: Box<dyn io::BufRead>, file_name: &str) {
+        let stream = &mut self.unexpanded_stream;
+        let input_module = &mut stream.s.base_mut().input_module;
+        input_module.push_file(file, file_name);
+        let num_open_files = input_module.num_open_files();
+        stream.suspended.push(SuspendedTokenLists {
+            stack: std::mem::take(&mut stream.stack),
+            dont_expand: stream.dont_expand.take(),
+            output: stream.output.take(),
+            num_open_files,
+        });
+    }
+
+    /// Pushes a token to the front of the input and marks it so that it is not expanded the
+    /// next time it is read. This is how `\noexpand` is implemented.
+    pub fn push_unexpandable(&mut self, token: token::Token) {
+        self.push_unexpandable_list(vec![token]);
+    }
+
+    /// Pushes tokens to the front of the input and marks them so that they are not expanded
+    /// when they are read. This is how the tokens produced by `\the` are inserted into expanded
+    /// token lists, like the replacement text of `\edef`.
+    pub fn push_unexpandable_list(&mut self, tokens: Vec<token::Token>) {
+        self.push(stream::VecStream::new(tokens));
+        self.unexpanded_stream.dont_expand = Some(self.unexpanded_stream.stack.len() - 1);
+    }
+
+    /// Returns true if the next token was marked as unexpandable by [`Self::push_unexpandable`]
+    /// or [`Self::push_unexpandable_list`].
+    pub fn next_is_unexpandable(&mut self) -> anyhow::Result<bool> {
+        self.unexpanded_stream.prepare_imut_peek()?;
+        Ok(self.unexpanded_stream.next_is_unexpandable())
+    }
+
     pub fn expand_next(&mut self) -> anyhow::Result<bool> {
         self.unexpanded_stream.prepare_imut_peek()?;
+        if self.unexpanded_stream.next_is_unexpandable() {
+            return Ok(false);
+        }
         let command = match self.unexpanded_stream.imut_peek()? {
             None => None,
-            Some(token) => match token.value {
-                token::Value::Character(..) => None,
-                token::Value::ControlSequence(_, ref name) => {
-                    //println!("Considering command {}", name);
-                    self.state().base().primitives.get(name)
-                }
-            },
+            Some(token) => self.state().base().primitives.get(&token.value),
         };
-        let command = match command {
-            Some(primitive::Primitive::Expansion(command)) => command.duplicate(),
-            None => return Ok(false),
+        let output = match command {
+            Some(primitive::Primitive::Expansion(command)) => {
+                let command = command.duplicate();
+                let token = self.unexpanded_stream.next()?.unwrap();
+                command.call(token, self)?
+            }
+            Some(primitive::Primitive::Macro(command)) => {
+                let command = command.clone();
+                let token = self.unexpanded_stream.next()?.unwrap();
+                command.call(&token, self)?
+            }
+            _ => return Ok(false),
         };
-        self.unexpanded_stream.consume()?;
-        let output = command.call(self)?;
         self.unexpanded_stream.stack.push(output);
         Ok(true)
     }
@@ -21,31 +21,51 @@ impl std::error::Error for TokenError {}
 impl std::fmt::Display for TokenError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let bar = "|".bright_yellow().bold();
-        write!(
+        let line_number = self.line_number.to_string();
+        let margin = " ".repeat(line_number.len());
+        writeln!(
             f,
-            "{}: {}\n",
+            "{}: {}",
             "Error".bright_red().bold(),
             ColoredString::from(self.message.as_str()).bold()
         )?;
-        write!(
+        writeln!(
             f,
-            " {} {}:{}:{} \n",
-            " >".bright_yellow().bold(),
-            "foo.tex",
+            "{}{} {}:{}:{} ",
+            margin,
+            "-->".bright_yellow().bold(),
+            self.file_description,
             self.line_number,
-            self.position
+            self.position + 1,
         )?;
-        write!(f, "  {} \n", bar)?;
-        write!(f, "{} {} {} \n", "5".bright_yellow(), bar, self.line)?;
-        write!(
+        writeln!(f, "{} {} ", margin, bar)?;
+        writeln!(
             f,
-            "  {}                           {}\n",
+            "{} {} {} ",
+            line_number.bright_yellow(),
             bar,
-            "^".bright_red().bold()
+            self.line.trim_end()
         )?;
-        write!(f, "  {}    \n", bar)?;
-        write!(f, "  {} {} expected the escape character to be followed by the name of a control sequence\n",
-        "=".bright_yellow().bold(), "note:".bold())
+        writeln!(
+            f,
+            "{} {} {}{}",
+            margin,
+            bar,
+            " ".repeat(self.position),
+            "^".repeat(self.width).bright_red().bold()
+        )?;
+        writeln!(f, "{} {} ", margin, bar)?;
+        for note in &self.notes {
+            writeln!(
+                f,
+                "{} {} {} {}",
+                margin,
+                "=".bright_yellow().bold(),
+                "note:".bold(),
+                note
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -70,7 +90,7 @@ pub fn new_token_error(token: Token, message: String, notes: Vec<String>) -> any
             Value::Character(_, _) => 1,
             Value::ControlSequence(_, name) => 1 + name.len(),
         },
-        file_description: "".to_string(), // TODO token.source.line.file.bo).clone(),
+        file_description: source.line.file.as_ref().clone(),
         message,
         notes,
     })
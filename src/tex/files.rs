@@ -0,0 +1,273 @@
+//! The input streams read by `\read` and the output streams written to by `\write`.
+//!
+//! There are 16 input streams, numbered 0 to 15, each of which may be associated with a file
+//! using `\openin`. Reading from a stream that is not open, or from a stream number that is
+//! out of range, reads a line from the terminal instead.
+//!
+//! There are also 16 output streams, numbered 0 to 15, each of which may be associated with a file
+//! using `\openout`. Text written to a stream that is not open, or to a stream number that is
+//! out of range, goes to the terminal and log file instead; negative stream numbers go to the
+//! log file only.
+//!
+//! Unless preceded by `\immediate`, `\openout`, `\write` and `\closeout` don't act straight away
+//! but create a [Whatsit] that is performed when the page containing it is shipped out. This is
+//! why the token list of a `\write` is only expanded when it is performed: page numbers and
+//! other values are those in effect at shipout time.
+
+use crate::tex::hyphenation::Language;
+use crate::tex::token::token::Token;
+use std::io;
+use std::rc::Rc;
+
+/// The number of input streams and the number of output streams.
+pub const NUM_STREAMS: usize = 16;
+
+/// The destination of text written to an output stream.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Destination {
+    /// An output stream that is open.
+    File(usize),
+    Log,
+    TerminalAndLog,
+}
+
+/// A deferred `\openout`, `\write` or `\closeout`, or a change of language in a paragraph.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Whatsit {
+    OpenOut {
+        stream: i32,
+        file_name: String,
+    },
+    Write {
+        stream: i32,
+        tokens: Rc<Vec<Token>>,
+    },
+    CloseOut {
+        stream: i32,
+    },
+    /// The language of the text that follows in a paragraph, which is used to hyphenate it.
+    /// This is created by `\setlanguage`, or when a character is typeset and `\language`
+    /// differs from the language of the paragraph.
+    Language(Language),
+}
+
+/// The output streams.
+pub struct OutputFiles {
+    files: Vec<Option<Box<dyn io::Write>>>,
+}
+
+impl OutputFiles {
+    pub fn new() -> OutputFiles {
+        OutputFiles {
+            files: (0..NUM_STREAMS).map(|_| None).collect(),
+        }
+    }
+
+    /// Returns where text written to the stream with the provided number goes.
+    pub fn destination(&self, stream: i32) -> Destination {
+        match index(stream) {
+            Some(i) if self.files[i].is_some() => Destination::File(i),
+            _ if stream < 0 => Destination::Log,
+            _ => Destination::TerminalAndLog,
+        }
+    }
+
+    /// Associates the stream with the writer, closing any file the stream was associated with.
+    /// Stream numbers that are out of range are ignored.
+    pub fn open(&mut self, stream: i32, writer: Box<dyn io::Write>) -> io::Result<()> {
+        self.close(stream)?;
+        if let Some(i) = index(stream) {
+            self.files[i] = Some(writer);
+        }
+        Ok(())
+    }
+
+    /// Closes the file associated with the stream, if any.
+    pub fn close(&mut self, stream: i32) -> io::Result<()> {
+        match index(stream).and_then(|i| self.files[i].take()) {
+            None => Ok(()),
+            Some(mut writer) => writer.flush(),
+        }
+    }
+
+    /// Closes every open stream. This is done at the end of the job.
+    pub fn close_all(&mut self) -> io::Result<()> {
+        for stream in 0..NUM_STREAMS {
+            self.close(stream as i32)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a line of text to the open stream with the provided index. Occurrences of the
+    /// new line character, which is `\newlinechar` in TeX, start new lines.
+    pub fn write_line(
+        &mut self,
+        i: usize,
+        text: &str,
+        new_line_char: Option<char>,
+    ) -> io::Result<()> {
+        let writer = match self.files[i].as_mut() {
+            None => return Ok(()),
+            Some(writer) => writer,
+        };
+        let mut line = String::with_capacity(text.len() + 1);
+        for c in text.chars() {
+            match Some(c) == new_line_char {
+                true => line.push('\n'),
+                false => line.push(c),
+            }
+        }
+        line.push('\n');
+        writer.write_all(line.as_bytes())
+    }
+}
+
+impl Default for OutputFiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The input streams, and the terminal that is read from when a stream is not open.
+pub struct InputFiles {
+    files: Vec<Option<Box<dyn io::BufRead>>>,
+    terminal: Box<dyn io::BufRead>,
+}
+
+impl InputFiles {
+    pub fn new() -> InputFiles {
+        InputFiles {
+            files: (0..NUM_STREAMS).map(|_| None).collect(),
+            terminal: Box::new(io::BufReader::new(io::stdin())),
+        }
+    }
+
+    /// Replaces the terminal that lines are read from. By default this is standard input.
+    pub fn set_terminal(&mut self, terminal: Box<dyn io::BufRead>) {
+        self.terminal = terminal;
+    }
+
+    /// Returns whether the stream with the provided number is associated with a file. This is
+    /// the negation of `\ifeof`.
+    pub fn is_open(&self, stream: i32) -> bool {
+        matches!(index(stream), Some(i) if self.files[i].is_some())
+    }
+
+    /// Associates the stream with the reader, closing any file the stream was associated with.
+    /// Stream numbers that are out of range are ignored.
+    pub fn open(&mut self, stream: i32, reader: Box<dyn io::BufRead>) {
+        if let Some(i) = index(stream) {
+            self.files[i] = Some(reader);
+        }
+    }
+
+    /// Closes the file associated with the stream, if any.
+    pub fn close(&mut self, stream: i32) {
+        if let Some(i) = index(stream) {
+            self.files[i] = None;
+        }
+    }
+
+    /// Reads the next line from the open stream, without its line terminator.
+    ///
+    /// As in TeX, the stream is closed when an attempt is made to read past the end of its
+    /// file, in which case `None` is returned. `None` is also returned if the stream is not open.
+    pub fn read_line(&mut self, stream: i32) -> io::Result<Option<String>> {
+        let reader = match index(stream).and_then(|i| self.files[i].as_mut()) {
+            None => return Ok(None),
+            Some(reader) => reader,
+        };
+        match read_line(reader)? {
+            None => {
+                self.close(stream);
+                Ok(None)
+            }
+            line => Ok(line),
+        }
+    }
+
+    /// Reads the next line from the terminal, without its line terminator, or returns `None` if
+    /// the terminal has no more input.
+    pub fn read_terminal_line(&mut self) -> io::Result<Option<String>> {
+        read_line(&mut self.terminal)
+    }
+}
+
+impl Default for InputFiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_line(reader: &mut Box<dyn io::BufRead>) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+fn index(stream: i32) -> Option<usize> {
+    match stream >= 0 && (stream as usize) < NUM_STREAMS {
+        true => Some(stream as usize),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::print::Buffer;
+
+    #[test]
+    fn destinations() {
+        let mut files = OutputFiles::new();
+        files.open(3, Box::new(Buffer::new())).unwrap();
+        assert_eq!(files.destination(3), Destination::File(3));
+        assert_eq!(files.destination(4), Destination::TerminalAndLog);
+        assert_eq!(files.destination(16), Destination::TerminalAndLog);
+        assert_eq!(files.destination(-1), Destination::Log);
+        files.close(3).unwrap();
+        assert_eq!(files.destination(3), Destination::TerminalAndLog);
+    }
+
+    #[test]
+    fn write_lines() {
+        let mut files = OutputFiles::new();
+        let buffer = Buffer::new();
+        files.open(0, Box::new(buffer.clone())).unwrap();
+        files.write_line(0, "a^b", Some('^')).unwrap();
+        files.write_line(0, "c", None).unwrap();
+        assert_eq!(buffer.contents(), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn read_lines() {
+        let mut files = InputFiles::new();
+        assert!(!files.is_open(2));
+        files.open(2, Box::new(io::Cursor::new("a\r\n\nb")));
+        assert!(files.is_open(2));
+        assert_eq!(files.read_line(2).unwrap(), Some("a".to_string()));
+        assert_eq!(files.read_line(2).unwrap(), Some("".to_string()));
+        assert_eq!(files.read_line(2).unwrap(), Some("b".to_string()));
+        assert!(files.is_open(2));
+        assert_eq!(files.read_line(2).unwrap(), None);
+        assert!(!files.is_open(2));
+        assert!(!files.is_open(-1));
+        assert!(!files.is_open(16));
+    }
+
+    #[test]
+    fn read_terminal_lines() {
+        let mut files = InputFiles::new();
+        files.set_terminal(Box::new(io::Cursor::new("a\n")));
+        assert_eq!(files.read_terminal_line().unwrap(), Some("a".to_string()));
+        assert_eq!(files.read_terminal_line().unwrap(), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! Access to the files read and written by documents.
+//!
+//! Primitives like `\openout` don't access the disk directly but go through the state's
+//! [FileSystem]. This makes it possible to run documents against an in-memory file system,
+//! which is how the files written by a document are inspected in tests:
+//! ```
+//! # use texide::tex::filesystem::{FileSystem, InMemoryFileSystem};
+//! # use std::io::Write;
+//! let mut file_system = InMemoryFileSystem::new();
+//! file_system.create("a.tex").unwrap().write_all(b"hello").unwrap();
+//! assert_eq!(file_system.contents("a.tex"), Some("hello".to_string()));
+//! ```
+
+use crate::tex::print::Buffer;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+/// A file system.
+pub trait FileSystem {
+    /// Creates the file, replacing any existing file, and returns a writer for it.
+    fn create(&mut self, path: &str) -> io::Result<Box<dyn io::Write>>;
+
+    /// Opens the file for reading.
+    fn open(&self, path: &str) -> io::Result<Box<dyn io::BufRead>>;
+}
+
+/// Adds the extension to a file name that has no extension, as TeX does with `.tex` for
+/// `\openin` and `\openout` and with `.tfm` for `\font`.
+pub fn with_default_extension(file_name: String, extension: &str) -> String {
+    let base_name = match file_name.rfind('/') {
+        None => &file_name[..],
+        Some(i) => &file_name[i + 1..],
+    };
+    match base_name.contains('.') {
+        true => file_name,
+        false => format!("{}.{}", file_name, extension),
+    }
+}
+
+/// The file system of the operating system.
+#[derive(Default)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn create(&mut self, path: &str) -> io::Result<Box<dyn io::Write>> {
+        Ok(Box::new(io::BufWriter::new(fs::File::create(path)?)))
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<dyn io::BufRead>> {
+        Ok(Box::new(io::BufReader::new(fs::File::open(path)?)))
+    }
+}
+
+/// A file system that stores files in memory.
+///
+/// Clones of the file system share the same files, so the files written by a document can be
+/// read from a clone after the file system has been given to the state.
+#[derive(Clone, Default)]
+pub struct InMemoryFileSystem {
+    files: Rc<RefCell<HashMap<String, Buffer>>>,
+}
+
+impl InMemoryFileSystem {
+    pub fn new() -> InMemoryFileSystem {
+        Default::default()
+    }
+
+    /// Creates a file with the provided contents, replacing any existing file.
+    pub fn insert(&mut self, path: &str, contents: &str) {
+        self.insert_bytes(path, contents.as_bytes());
+    }
+
+    /// Creates a binary file with the provided contents, replacing any existing file.
+    pub fn insert_bytes(&mut self, path: &str, contents: &[u8]) {
+        let mut buffer = Buffer::new();
+        io::Write::write_all(&mut buffer, contents).unwrap();
+        self.files.borrow_mut().insert(path.to_string(), buffer);
+    }
+
+    /// Returns the contents of the file, or `None` if it doesn't exist.
+    pub fn contents(&self, path: &str) -> Option<String> {
+        self.files.borrow().get(path).map(Buffer::contents)
+    }
+}
+
+impl FileSystem for InMemoryFileSystem {
+    fn create(&mut self, path: &str) -> io::Result<Box<dyn io::Write>> {
+        let buffer = Buffer::new();
+        self.files
+            .borrow_mut()
+            .insert(path.to_string(), buffer.clone());
+        Ok(Box::new(buffer))
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<dyn io::BufRead>> {
+        match self.files.borrow().get(path).map(Buffer::bytes) {
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("file {} not found", path),
+            )),
+            Some(contents) => Ok(Box::new(io::Cursor::new(contents))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn in_memory_file_system() {
+        let mut file_system = InMemoryFileSystem::new();
+        assert!(file_system.open("a.tex").is_err());
+        file_system.insert("a.tex", "first");
+        let mut writer = file_system.clone().create("b.tex").unwrap();
+        writer.write_all(b"second").unwrap();
+        let mut contents = String::new();
+        file_system
+            .open("a.tex")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "first");
+        assert_eq!(file_system.contents("b.tex"), Some("second".to_string()));
+    }
+}
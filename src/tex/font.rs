@@ -0,0 +1,541 @@
+//! Fonts: the metrics of loaded fonts, scaled to the size they were loaded at, the table of
+//! loaded fonts, and the fonts of the math families.
+//!
+//! A font is loaded from a TFM file by `\font`, at the design size of the file or at another
+//! size given using `at` or `scaled`. All dimensions of a font are scaled when it is loaded,
+//! exactly as TeX scales them, so that documents typeset identically.
+//!
+//! Font 0 is the null font, which has no characters and seven zero parameters. It is the
+//! current font until another font is selected:
+//! ```
+//! # use texide::tex::font::{Fonts, NULL_FONT};
+//! let fonts = Fonts::new();
+//! assert_eq!(fonts.current(), NULL_FONT);
+//! assert_eq!(fonts.get(NULL_FONT).name, "nullfont");
+//! ```
+//!
+//! Formulas take their characters from 16 math families. Each family has a font for each of
+//! the three sizes of a formula, which is assigned using `\textfont`, `\scriptfont` and
+//! `\scriptscriptfont` and is the null font until then.
+
+use crate::datastructures::scopedmap::ScopedMap;
+use crate::tex::dimen::{Dimen, Glue};
+use crate::tex::node::FontId;
+use crate::tex::tfm;
+
+/// The null font.
+pub const NULL_FONT: FontId = FontId(0);
+
+/// The smallest number of parameters a font has. Fonts whose TFM file has fewer parameters
+/// have zero for the missing ones.
+pub const MIN_PARAMS: usize = 7;
+
+/// The largest number of parameters a font can have.
+pub const MAX_PARAMS: usize = 1 << 16;
+
+/// The number of math families.
+pub const NUM_FAMILIES: usize = 16;
+
+/// The size of the characters of a formula, which determines the font that is used in each
+/// math family.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MathSize {
+    Text,
+    Script,
+    ScriptScript,
+}
+
+/// The size at which a font is loaded, as given after `\font`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Size {
+    /// The font is loaded at this size.
+    At(Dimen),
+    /// The font is loaded at its design size multiplied by this number over 1000.
+    Scaled(i32),
+}
+
+impl Size {
+    /// Returns the size for a font with the provided design size.
+    pub fn resolve(self, design_size: Dimen) -> Dimen {
+        match self {
+            Size::At(size) => size,
+            Size::Scaled(1000) => design_size,
+            Size::Scaled(n) => design_size.xn_over_d(n, 1000),
+        }
+    }
+}
+
+/// The metrics of a character, scaled to the size of its font.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CharMetrics {
+    pub width: Dimen,
+    pub height: Dimen,
+    pub depth: Dimen,
+    pub italic: Dimen,
+    pub tag: tfm::Tag,
+}
+
+/// A loaded font.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Font {
+    /// The file name the font was loaded from, as given to `\font`.
+    pub name: String,
+    /// The size the font was loaded at.
+    pub size: Dimen,
+    pub design_size: Dimen,
+    pub check_sum: u32,
+    /// The name of the control sequence most recently defined by `\font` to select this font,
+    /// which `\the` produces for it.
+    pub identifier: String,
+    /// The character used to hyphenate words set in the font, or a value that is not a
+    /// character for none. This is `\hyphenchar`.
+    pub hyphen_char: i32,
+    /// The character whose kerns position math accents, or a value that is not a character for
+    /// none. This is `\skewchar`.
+    pub skew_char: i32,
+    first_char: u8,
+    chars: Vec<Option<CharMetrics>>,
+    lig_kern: Vec<tfm::LigKernInstruction>,
+    kerns: Vec<Dimen>,
+    extensible: Vec<tfm::ExtensibleRecipe>,
+    params: Vec<Dimen>,
+}
+
+impl Font {
+    /// Returns the null font.
+    pub fn null() -> Font {
+        Font {
+            name: "nullfont".to_string(),
+            size: Dimen(0),
+            design_size: Dimen(0),
+            check_sum: 0,
+            identifier: "nullfont".to_string(),
+            hyphen_char: '-' as i32,
+            skew_char: -1,
+            first_char: 0,
+            chars: Vec::new(),
+            lig_kern: Vec::new(),
+            kerns: Vec::new(),
+            extensible: Vec::new(),
+            params: vec![Dimen(0); MIN_PARAMS],
+        }
+    }
+
+    /// Returns the font described by a TFM file, loaded at the provided size.
+    ///
+    /// The identifier, hyphen character and skew character are set by the caller.
+    pub fn new(name: String, tfm: &tfm::Tfm, size: Dimen) -> Font {
+        let scale = Scale::new(size);
+        let scale_all = |table: &[i32]| table.iter().map(|&w| scale.apply(w)).collect::<Vec<_>>();
+        let widths = scale_all(&tfm.widths);
+        let heights = scale_all(&tfm.heights);
+        let depths = scale_all(&tfm.depths);
+        let italics = scale_all(&tfm.italics);
+        let chars = tfm
+            .char_infos
+            .iter()
+            .map(|info| match info.width_index {
+                0 => None,
+                _ => Some(CharMetrics {
+                    width: widths[info.width_index as usize],
+                    height: heights[info.height_index as usize],
+                    depth: depths[info.depth_index as usize],
+                    italic: italics[info.italic_index as usize],
+                    tag: info.tag,
+                }),
+            })
+            .collect();
+        let mut params: Vec<Dimen> = tfm
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| match i {
+                // The slant is a pure number, which is not scaled.
+                0 => Dimen(w >> 4),
+                _ => scale.apply(w),
+            })
+            .collect();
+        if params.len() < MIN_PARAMS {
+            params.resize(MIN_PARAMS, Dimen(0));
+        }
+        Font {
+            name,
+            size,
+            design_size: Dimen(tfm.design_size >> 4),
+            check_sum: tfm.check_sum,
+            identifier: String::new(),
+            hyphen_char: '-' as i32,
+            skew_char: -1,
+            first_char: tfm.first_char,
+            chars,
+            lig_kern: tfm.lig_kern.clone(),
+            kerns: scale_all(&tfm.kerns),
+            extensible: tfm.extensible.clone(),
+            params,
+        }
+    }
+
+    /// Returns the name of the font, followed by its size if that is not the design size, as
+    /// produced by `\fontname`.
+    pub fn name_and_size(&self) -> String {
+        match self.size == self.design_size {
+            true => self.name.clone(),
+            false => format!("{} at {}", self.name, self.size),
+        }
+    }
+
+    /// Returns the metrics of a character, or `None` if the font doesn't have the character.
+    pub fn char(&self, c: char) -> Option<&CharMetrics> {
+        let i = (c as usize).checked_sub(self.first_char as usize)?;
+        self.chars.get(i)?.as_ref()
+    }
+
+    /// Returns the parameter with the provided number, starting from 1, or `None` if the font
+    /// doesn't have it. This is `\fontdimen`.
+    pub fn param(&self, n: usize) -> Option<Dimen> {
+        self.params.get(n.checked_sub(1)?).copied()
+    }
+
+    /// Returns a mutable reference to the parameter with the provided number, starting from 1.
+    pub fn param_mut(&mut self, n: usize) -> Option<&mut Dimen> {
+        self.params.get_mut(n.checked_sub(1)?)
+    }
+
+    /// Returns the interword glue of the font: its space, space stretch and space shrink
+    /// parameters.
+    pub fn space_glue(&self) -> Glue {
+        Glue {
+            width: self.params[1],
+            stretch: self.params[2],
+            shrink: self.params[3],
+            ..Default::default()
+        }
+    }
+
+    /// Returns the x-height of the font, which is the unit `ex`.
+    pub fn x_height(&self) -> Dimen {
+        self.params[4]
+    }
+
+    /// Returns the quad width of the font, which is the unit `em`.
+    pub fn quad(&self) -> Dimen {
+        self.params[5]
+    }
+
+    /// Returns the extra space of the font, which is added to the interword glue after a
+    /// sentence.
+    pub fn extra_space(&self) -> Dimen {
+        self.params[6]
+    }
+
+    /// Returns the number of parameters of the font.
+    pub fn num_params(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Gives the font zero parameters up to the provided number, if it has fewer.
+    pub fn extend_params(&mut self, n: usize) {
+        if n > self.params.len() {
+            self.params.resize(n, Dimen(0));
+        }
+    }
+
+    /// Returns the index of the first instruction of a character's ligature/kern program, or
+    /// `None` if the character has no program.
+    pub fn lig_kern_start(&self, c: char) -> Option<usize> {
+        match self.char(c)?.tag {
+            tfm::Tag::LigKern(start) => self.program_start(start as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns the index that a program whose first instruction has the provided index
+    /// actually starts at, following the indirection of large fonts.
+    fn program_start(&self, first: usize) -> Option<usize> {
+        let instruction = self.lig_kern.get(first)?;
+        Some(instruction.indirection().unwrap_or(first))
+    }
+
+    /// Returns the ligature/kern instructions of the font.
+    pub fn lig_kern_instructions(&self) -> &[tfm::LigKernInstruction] {
+        &self.lig_kern
+    }
+
+    /// Returns the kern with the provided index.
+    pub fn kern(&self, index: usize) -> Dimen {
+        self.kerns[index]
+    }
+
+    /// Returns the character that stands for the word boundary in ligature/kern programs, if
+    /// the font has one.
+    pub fn boundary_char(&self) -> Option<u8> {
+        match self.lig_kern.first() {
+            Some(first) if first.skip == 255 => Some(first.next_char),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the first instruction of the program applied at the beginning of
+    /// a word, if the font has one.
+    pub fn boundary_program(&self) -> Option<usize> {
+        match self.lig_kern.last() {
+            Some(last) if last.skip == 255 => {
+                Some(256 * last.op as usize + last.remainder as usize)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the extensible recipe of a character, or `None` if the character is not
+    /// extensible.
+    pub fn extensible_recipe(&self, c: char) -> Option<&tfm::ExtensibleRecipe> {
+        match self.char(c)?.tag {
+            tfm::Tag::Extensible(i) => self.extensible.get(i as usize),
+            _ => None,
+        }
+    }
+}
+
+/// Scales fix words to a font size using only integer arithmetic, as TeX does.
+struct Scale {
+    z: i32,
+    alpha: i32,
+    beta: i32,
+}
+
+impl Scale {
+    fn new(size: Dimen) -> Scale {
+        let mut z = size.0;
+        let mut alpha = 16;
+        while z >= 1 << 23 {
+            z /= 2;
+            alpha += alpha;
+        }
+        Scale {
+            z,
+            alpha: alpha * z,
+            beta: 256 / alpha,
+        }
+    }
+
+    fn apply(&self, fix_word: i32) -> Dimen {
+        let [a, b, c, d] = fix_word.to_be_bytes();
+        let z = self.z as i64;
+        let sw = ((((d as i64 * z) / 256 + c as i64 * z) / 256 + b as i64 * z) / self.beta as i64)
+            as i32;
+        match a {
+            0 => Dimen(sw),
+            // Fix words were checked to begin with 0 or 255 when the file was read.
+            _ => Dimen(sw - self.alpha),
+        }
+    }
+}
+
+/// The loaded fonts and the current font.
+pub struct Fonts {
+    fonts: Vec<Font>,
+    /// The current font. Selecting a font is an assignment that is rolled back at the end of the
+    /// group, so the map has a single key.
+    current: ScopedMap<(), FontId>,
+    /// The font of each math family at each size.
+    families: ScopedMap<(MathSize, usize), FontId>,
+}
+
+impl Fonts {
+    /// Returns a table containing only the null font.
+    pub fn new() -> Fonts {
+        Fonts {
+            fonts: vec![Font::null()],
+            current: ScopedMap::new(),
+            families: ScopedMap::new(),
+        }
+    }
+
+    pub fn get(&self, id: FontId) -> &Font {
+        &self.fonts[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: FontId) -> &mut Font {
+        &mut self.fonts[id.0]
+    }
+
+    /// Adds a font to the table and returns its identifier.
+    pub fn add(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    /// Returns the font that was loaded from the file with the provided name at the provided
+    /// size, if any. As in TeX, such a font is shared rather than loaded again.
+    pub fn find(&self, name: &str, size: Size) -> Option<FontId> {
+        self.fonts
+            .iter()
+            .skip(1)
+            .position(|font| font.name == name && font.size == size.resolve(font.design_size))
+            .map(|i| FontId(i + 1))
+    }
+
+    /// Returns the font that was loaded most recently, or the null font if none has been.
+    pub fn last(&self) -> FontId {
+        FontId(self.fonts.len() - 1)
+    }
+
+    pub fn current(&self) -> FontId {
+        self.current.get(&()).copied().unwrap_or(NULL_FONT)
+    }
+
+    pub fn set_current(&mut self, id: FontId, global: bool) {
+        match global {
+            true => self.current.insert_global((), id),
+            false => self.current.insert((), id),
+        }
+    }
+
+    /// Returns the font of the math family at the provided size.
+    pub fn family(&self, size: MathSize, family: usize) -> FontId {
+        self.families
+            .get(&(size, family))
+            .copied()
+            .unwrap_or(NULL_FONT)
+    }
+
+    pub fn set_family(&mut self, size: MathSize, family: usize, id: FontId, global: bool) {
+        match global {
+            true => self.families.insert_global((size, family), id),
+            false => self.families.insert((size, family), id),
+        }
+    }
+
+    pub fn begin_scope(&mut self) {
+        self.current.begin_scope();
+        self.families.begin_scope();
+    }
+
+    #[must_use]
+    pub fn end_scope(&mut self) -> bool {
+        // Non-short-circuiting so that both maps stay in sync.
+        self.current.end_scope() & self.families.end_scope()
+    }
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::tfm::{CharInfo, LigKernInstruction, Tag, Tfm};
+
+    fn tfm() -> Tfm {
+        Tfm {
+            design_size: 10 << 20,
+            first_char: b'a',
+            char_infos: vec![
+                CharInfo {
+                    width_index: 1,
+                    height_index: 1,
+                    depth_index: 1,
+                    italic_index: 1,
+                    tag: Tag::LigKern(0),
+                },
+                CharInfo::default(),
+                CharInfo {
+                    width_index: 2,
+                    tag: Tag::NextLarger(b'a'),
+                    ..Default::default()
+                },
+            ],
+            widths: vec![0, 1 << 19, -(1 << 18)],
+            heights: vec![0, 3 << 18],
+            depths: vec![0, 1 << 16],
+            italics: vec![0, 1],
+            lig_kern: vec![LigKernInstruction {
+                skip: 128,
+                next_char: b'a',
+                op: 128,
+                remainder: 0,
+            }],
+            kerns: vec![-(1 << 16)],
+            params: vec![-(1 << 18), 1 << 20],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scaling() {
+        let font = Font::new("test".to_string(), &tfm(), Dimen::from_pt(10));
+        assert_eq!(font.design_size, Dimen::from_pt(10));
+        let a = font.char('a').unwrap();
+        assert_eq!(a.width, Dimen::from_pt(5));
+        assert_eq!(a.height, Dimen(Dimen::from_pt(15).0 / 2));
+        assert_eq!(a.depth, Dimen(Dimen::from_pt(10).0 / 16));
+        // 2^-20 of 10pt is 0.625sp, which is rounded down.
+        assert_eq!(a.italic, Dimen(0));
+        assert!(font.char('b').is_none());
+        assert_eq!(
+            font.char('c').unwrap().width,
+            Dimen(-Dimen::from_pt(10).0 / 4)
+        );
+        assert!(font.char('d').is_none());
+        assert!(font.char('`').is_none());
+        assert_eq!(font.kern(0), Dimen(-Dimen::from_pt(10).0 / 16));
+    }
+
+    #[test]
+    fn scaling_large_size() {
+        let font = Font::new("test".to_string(), &tfm(), Dimen::from_pt(1000));
+        assert_eq!(font.char('a').unwrap().width, Dimen::from_pt(500));
+        assert_eq!(font.char('c').unwrap().width, Dimen::from_pt(-250));
+    }
+
+    #[test]
+    fn params() {
+        let mut font = Font::new("test".to_string(), &tfm(), Dimen::from_pt(5));
+        assert_eq!(font.num_params(), MIN_PARAMS);
+        // The slant is not scaled.
+        assert_eq!(font.param(1), Some(Dimen(-(1 << 14))));
+        assert_eq!(font.param(2), Some(Dimen::from_pt(5)));
+        assert_eq!(font.param(7), Some(Dimen(0)));
+        assert_eq!(font.param(0), None);
+        assert_eq!(font.param(8), None);
+        font.extend_params(9);
+        *font.param_mut(9).unwrap() = Dimen(3);
+        assert_eq!(font.param(9), Some(Dimen(3)));
+    }
+
+    #[test]
+    fn lig_kern_and_tags() {
+        let font = Font::new("test".to_string(), &tfm(), Dimen::from_pt(10));
+        assert_eq!(font.lig_kern_start('a'), Some(0));
+        assert_eq!(font.lig_kern_start('c'), None);
+        assert_eq!(font.boundary_char(), None);
+        assert_eq!(font.boundary_program(), None);
+        assert_eq!(font.char('c').unwrap().tag, Tag::NextLarger(b'a'));
+        assert!(font.extensible_recipe('a').is_none());
+    }
+
+    #[test]
+    fn fonts_table() {
+        let mut fonts = Fonts::new();
+        assert_eq!(fonts.last(), NULL_FONT);
+        let id = fonts.add(Font::new("a".to_string(), &tfm(), Dimen::from_pt(10)));
+        assert_eq!(fonts.last(), id);
+        assert_eq!(fonts.find("a", Size::At(Dimen::from_pt(10))), Some(id));
+        assert_eq!(fonts.find("a", Size::Scaled(1000)), Some(id));
+        assert_eq!(fonts.find("a", Size::At(Dimen::from_pt(11))), None);
+        assert_eq!(fonts.find("a", Size::Scaled(1100)), None);
+        assert_eq!(fonts.find("nullfont", Size::Scaled(1000)), None);
+        fonts.begin_scope();
+        fonts.set_current(id, false);
+        assert_eq!(fonts.current(), id);
+        assert!(fonts.end_scope());
+        assert_eq!(fonts.current(), NULL_FONT);
+        fonts.begin_scope();
+        fonts.set_current(id, true);
+        assert!(fonts.end_scope());
+        assert_eq!(fonts.current(), id);
+    }
+}
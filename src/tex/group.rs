@@ -0,0 +1,108 @@
+//! Groups and the save stack.
+//!
+//! In TeX, assignments are local to the group in which they occur: when a group ends,
+//! every local assignment made inside it is undone. Groups are opened by begin group
+//! characters like `{`, by `\begingroup`, and by a number of other constructs like boxes.
+//!
+//! Texide implements this using the scoped maps in the state. Beginning a group begins a new
+//! scope in every scoped table of the state, and ending a group ends the scope in every table.
+//! The stack of open groups is thus the analogue of TeX's save stack. In addition to the
+//! scopes, each group keeps track of its type, the tokens saved by `\aftergroup`, and for a
+//! group that builds a box or an insertion, what to do with the material when the group ends.
+
+use crate::tex::dimen::Dimen;
+use crate::tex::pack;
+use crate::tex::token::token;
+
+/// The type of a group.
+///
+/// The integer value of each type is the value returned by e-TeX's `\currentgrouptype`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GroupType {
+    Bottom,
+    Simple,
+    HBox,
+    AdjustedHBox,
+    VBox,
+    VTop,
+    Align,
+    NoAlign,
+    Output,
+    Math,
+    Disc,
+    Insert,
+    VCenter,
+    MathChoice,
+    SemiSimple,
+    MathShift,
+    MathLeft,
+}
+
+impl GroupType {
+    pub fn int(&self) -> i32 {
+        use GroupType::*;
+        match self {
+            Bottom => 0,
+            Simple => 1,
+            HBox => 2,
+            AdjustedHBox => 3,
+            VBox => 4,
+            VTop => 5,
+            Align => 6,
+            NoAlign => 7,
+            Output => 8,
+            Math => 9,
+            Disc => 10,
+            Insert => 11,
+            VCenter => 12,
+            MathChoice => 13,
+            SemiSimple => 14,
+            MathShift => 15,
+            MathLeft => 16,
+        }
+    }
+}
+
+/// Where a box goes when it is finished. This is the box context in TeX.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BoxContext {
+    /// The box is appended to the current list, shifted by the provided amount.
+    Append(Dimen),
+    /// The box is assigned to a box register by `\setbox`.
+    SetBox { register: usize, global: bool },
+    /// The box is shipped out by `\shipout`.
+    ShipOut,
+}
+
+/// An open group.
+pub struct Group {
+    pub group_type: GroupType,
+    /// Tokens saved using `\aftergroup`, in the order they were saved.
+    pub after_group: Vec<token::Token>,
+    /// For a group that builds a box, where the box goes and the size it should have.
+    pub box_spec: Option<(BoxContext, pack::Spec)>,
+    /// For the group of `\insert n{...}`, the insertion class `n`.
+    pub insert_number: Option<u8>,
+    /// For a group of `\discretionary{...}{...}{...}`, which of the three lists it builds.
+    pub discretionary_part: Option<DiscretionaryPart>,
+}
+
+/// The lists of a discretionary, in the order in which `\discretionary` reads them.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DiscretionaryPart {
+    PreBreak,
+    PostBreak,
+    Replacement,
+}
+
+impl Group {
+    pub fn new(group_type: GroupType) -> Group {
+        Group {
+            group_type,
+            after_group: Vec::new(),
+            box_spec: None,
+            insert_number: None,
+            discretionary_part: None,
+        }
+    }
+}
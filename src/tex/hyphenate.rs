@@ -0,0 +1,774 @@
+//! Inserting discretionary hyphens into the words of a paragraph.
+//!
+//! When the first pass of the line breaker finds no acceptable way to break a paragraph, the
+//! words of the paragraph are hyphenated before the second pass: a discretionary is inserted
+//! wherever the [hyphenation](crate::tex::hyphenation) tables allow a word to be hyphenated.
+//!
+//! Only a word that follows glue is hyphenated, and only if its letters are characters of one
+//! font whose `\hyphenchar` is between 0 and 255. A word that begins with an uppercase letter
+//! is only hyphenated if `\uchyph` is positive. The word may be followed by characters that are
+//! not letters, like punctuation, but not by a box or rule, for example.
+//!
+//! The characters of a word may have been replaced by ligatures and separated by kerns, which
+//! can be different when the word is hyphenated. The word is then typeset again, as TeX's
+//! `reconstitute` does: for example, the "ff" ligature of "dif-fer" becomes a discretionary
+//! with "f-" before the break and "f" after it. This is part 40 of TeX: The Program.
+
+use crate::tex::dimen::Dimen;
+use crate::tex::files::Whatsit;
+use crate::tex::font::Font;
+use crate::tex::hyphenation::{Language, MAX_WORD_LENGTH};
+use crate::tex::ligature::STOP_FLAG;
+use crate::tex::node::{Char, Discretionary, FontId, Kern, KernKind, Ligature, MathKind, Node};
+use crate::tex::parameter::IntParam;
+use crate::tex::state::BaseState;
+use crate::tex::tfm::LigKernInstruction;
+
+/// Hyphenates the words of a paragraph whose list is provided, and returns the new list. The
+/// language is the one the paragraph began in, which is changed by language whatsits in the
+/// paragraph. The hyphenation patterns should have been packed.
+pub fn hyphenate_paragraph<S>(
+    base: &BaseState<S>,
+    mut list: Vec<Node>,
+    mut language: Language,
+) -> Vec<Node> {
+    let mut auto_breaking = true;
+    let mut cur = 0;
+    while cur < list.len() {
+        match &list[cur] {
+            Node::Glue(_) if auto_breaking => {
+                if let Some(word) = find_word(base, &list, cur, language) {
+                    let end = word.last + 1;
+                    if let Some(hyf) = hyphen_positions(base, &word) {
+                        let (start, nodes) = Reconstitution::new(base, &list, word, hyf).run();
+                        list.splice(start..end, nodes);
+                    }
+                }
+            }
+            Node::Math(math) => auto_breaking = math.kind == MathKind::After,
+            Node::Whatsit(Whatsit::Language(l)) => language = *l,
+            Node::Discretionary(d) => cur += d.replace_count,
+            _ => {}
+        }
+        cur += 1;
+    }
+    list
+}
+
+/// A word after glue that may be hyphenated.
+struct Word {
+    font: FontId,
+    hyphen_char: char,
+    language: Language,
+    /// The index of the node before the node with the first letter. This is `ha` in TeX.
+    before: usize,
+    /// The index of the last node with a letter of the word. This is `hb` in TeX.
+    last: usize,
+    /// The letters of the word, after an element for the character before the word, which is
+    /// set when the word is typeset again. This is `hu` in TeX.
+    chars: Vec<Option<char>>,
+    /// The lowercase codes of the letters. This is `hc` in TeX.
+    letters: Vec<char>,
+    /// The character after the word that takes part in ligatures and kerns, or `None`. This is
+    /// `hyf_bchar` in TeX.
+    boundary: Option<char>,
+}
+
+/// Returns the lowercase code of a character, or `None` if it is not a letter.
+fn lc_code<S>(base: &BaseState<S>, c: char) -> Option<char> {
+    match base.char_codes.lc_code(c) {
+        0 => None,
+        lc => char::from_u32(lc as u32),
+    }
+}
+
+/// Finds the word after the glue with the provided index, if it may be hyphenated. This is
+/// parts 894 to 899 of TeX.
+fn find_word<S>(
+    base: &BaseState<S>,
+    list: &[Node],
+    glue: usize,
+    mut language: Language,
+) -> Option<Word> {
+    let mut before = glue;
+    let mut s = glue + 1;
+    // Nodes before the first letter are skipped, but a word that begins with an uppercase
+    // letter is not hyphenated unless \uchyph is positive.
+    let font = loop {
+        let first = match list.get(s)? {
+            Node::Char(c) => Some((c.c, c.font)),
+            Node::Ligature(l) => l.original.first().map(|&c| (c, l.font)),
+            Node::Kern(k) if k.kind == KernKind::Normal => None,
+            Node::Whatsit(w) => {
+                if let Whatsit::Language(l) = w {
+                    language = *l;
+                }
+                None
+            }
+            _ => return None,
+        };
+        if let Some((c, font)) = first {
+            if let Some(lc) = lc_code(base, c) {
+                match lc == c || base.parameters.int(IntParam::UcHyph) > 0 {
+                    true => break font,
+                    false => return None,
+                }
+            }
+        }
+        before = s;
+        s += 1;
+    };
+    let f = base.fonts.get(font);
+    let hyphen_char = match f.hyphen_char {
+        0..=255 => char::from(f.hyphen_char as u8),
+        _ => return None,
+    };
+    if language.left_min + language.right_min > MAX_WORD_LENGTH {
+        return None;
+    }
+    let font_boundary = f.boundary_char().map(char::from);
+    let mut word = Word {
+        font,
+        hyphen_char,
+        language,
+        before,
+        last: before,
+        chars: vec![None],
+        letters: vec![],
+        boundary: None,
+    };
+    loop {
+        match list.get(s) {
+            Some(Node::Char(c)) if c.font == font => {
+                word.boundary = Some(c.c);
+                let lc = match lc_code(base, c.c) {
+                    Some(lc) if word.letters.len() < MAX_WORD_LENGTH => lc,
+                    _ => break,
+                };
+                word.last = s;
+                word.chars.push(Some(c.c));
+                word.letters.push(lc);
+                word.boundary = None;
+            }
+            Some(Node::Ligature(l)) if l.font == font => {
+                if let Some(&c) = l.original.first() {
+                    word.boundary = Some(c);
+                }
+                let letters: Vec<(char, char)> = l
+                    .original
+                    .iter()
+                    .map_while(|&c| lc_code(base, c).map(|lc| (c, lc)))
+                    .collect();
+                if letters.len() < l.original.len()
+                    || word.letters.len() + letters.len() > MAX_WORD_LENGTH
+                {
+                    break;
+                }
+                for (c, lc) in letters {
+                    word.chars.push(Some(c));
+                    word.letters.push(lc);
+                }
+                word.last = s;
+                word.boundary = match l.right_boundary {
+                    true => font_boundary,
+                    false => None,
+                };
+            }
+            Some(Node::Kern(k)) if k.kind == KernKind::Normal => {
+                word.last = s;
+                word.boundary = font_boundary;
+            }
+            _ => break,
+        }
+        s += 1;
+    }
+    if word.letters.len() < language.left_min + language.right_min {
+        return None;
+    }
+    // The word may be followed by characters that are not letters, and then by a node that
+    // ends it.
+    loop {
+        match list.get(s) {
+            Some(Node::Char(_)) | Some(Node::Ligature(_)) => {}
+            Some(Node::Kern(k)) if k.kind == KernKind::Normal => {}
+            None
+            | Some(Node::Kern(_))
+            | Some(Node::Whatsit(_))
+            | Some(Node::Glue(_))
+            | Some(Node::Penalty(_))
+            | Some(Node::Insert(_))
+            | Some(Node::Adjust(_))
+            | Some(Node::Mark(_)) => return Some(word),
+            Some(_) => return None,
+        }
+        s += 1;
+    }
+}
+
+/// Returns the hyphenation values of a word, or `None` if it may not be hyphenated anywhere.
+fn hyphen_positions<S>(base: &BaseState<S>, word: &Word) -> Option<Vec<u8>> {
+    let hyf = base.hyphenation.hyphenate(word.language, &word.letters);
+    match hyf.iter().any(|v| v % 2 == 1) {
+        true => Some(hyf),
+        false => None,
+    }
+}
+
+/// A character inserted by a ligature instruction, which the cursor hasn't passed yet.
+struct Inserted {
+    c: char,
+    /// The character of the word that the inserted character replaced, if any.
+    original: Option<char>,
+}
+
+/// Typesetting a hyphenated word again.
+struct Reconstitution<'a> {
+    font: &'a Font,
+    font_id: FontId,
+    hyphen_char: char,
+    /// The characters of the word, after the character before it or `None` for the left
+    /// boundary. This is `hu` in TeX.
+    chars: Vec<Option<char>>,
+    /// The hyphenation values of the word, which are cleared where discretionaries have been
+    /// inserted. This is `hyf` in TeX.
+    hyf: Vec<u8>,
+    /// This is `hyf_bchar` in TeX.
+    boundary: Option<char>,
+    /// The index in the paragraph of the first node that is typeset again.
+    start: usize,
+    /// The index of the first character that is typeset: 0 if the node before the word, or the
+    /// left boundary character, takes part, and otherwise 1.
+    first: usize,
+    /// The characters of the node before the word, if it is typeset again. This is
+    /// `init_list` in TeX.
+    init_list: Vec<char>,
+    /// Whether the node before the word is a ligature. This is `init_lig` in TeX.
+    init_ligature: bool,
+    /// Whether the node before the word is a ligature formed with the left boundary
+    /// character. This is `init_lft` in TeX.
+    init_left_hit: bool,
+    /// A position where the word may be hyphenated that a ligature or kern went across, or 0.
+    hyphen_passed: usize,
+}
+
+/// The state of `reconstitute`, which is like the state of the main loop that typesets
+/// characters.
+struct Cursor {
+    /// The index of the character to the left of the cursor.
+    j: usize,
+    /// The index of the last character.
+    n: usize,
+    /// The character after the last one, or `None`. This is `bchar` in TeX.
+    boundary: Option<char>,
+    /// The hyphen character, if ligatures and kerns with a hyphen are of interest. This is
+    /// `hchar` in TeX.
+    hyphen: Option<char>,
+    /// The character to the left of the cursor, or `None` for the left boundary. This is
+    /// `cur_l` in TeX.
+    left: Option<char>,
+    /// The character to the right of the cursor. This is `cur_r` in TeX.
+    right: Option<char>,
+    /// The hyphen character, if the word may be hyphenated at the cursor. This is `cur_rh`.
+    right_hyphen: Option<char>,
+    /// The number of nodes before the characters of the ligature at the cursor. This is
+    /// `cur_q` in TeX.
+    start: usize,
+    stack: Vec<Inserted>,
+    nodes: Vec<Node>,
+    ligature_present: bool,
+    left_hit: bool,
+    right_hit: bool,
+}
+
+impl<'a> Reconstitution<'a> {
+    /// Prepares to typeset a word again. The node before the word is typeset again too, if it
+    /// is a character or ligature of the same font. This is part 903 of TeX.
+    fn new<S>(base: &'a BaseState<S>, list: &[Node], word: Word, hyf: Vec<u8>) -> Self {
+        let before = word.before;
+        let mut r = Reconstitution {
+            font: base.fonts.get(word.font),
+            font_id: word.font,
+            hyphen_char: word.hyphen_char,
+            chars: word.chars,
+            hyf,
+            boundary: word.boundary,
+            start: before,
+            first: 0,
+            init_list: vec![],
+            init_ligature: false,
+            init_left_hit: false,
+            hyphen_passed: 0,
+        };
+        match &list[before] {
+            Node::Char(c) if c.font == word.font => {
+                r.chars[0] = Some(c.c);
+                r.init_list = vec![c.c];
+                return r;
+            }
+            Node::Ligature(l) if l.font == word.font => {
+                r.init_list = l.original.clone();
+                r.init_left_hit = l.left_boundary;
+                if l.original.is_empty() && l.left_boundary {
+                    // The ligature is formed again from the left boundary character.
+                    return r;
+                }
+                r.chars[0] = Some(l.c);
+                r.init_ligature = true;
+                return r;
+            }
+            Node::Char(_) | Node::Ligature(_) => {}
+            _ => match &list[before + 1] {
+                Node::Ligature(l) if l.left_boundary => {}
+                _ => r.first = 1,
+            },
+        }
+        // The node before the word is kept.
+        r.start = before + 1;
+        r
+    }
+
+    fn char(&self, c: char) -> Node {
+        Node::Char(Char {
+            font: self.font_id,
+            c,
+        })
+    }
+
+    /// Returns the index of the first node of the paragraph that is replaced, and the nodes
+    /// that replace it and the rest of the word, with discretionaries where the word may be
+    /// hyphenated. This is parts 913 to 918 of TeX.
+    fn run(mut self) -> (usize, Vec<Node>) {
+        let n = self.chars.len() - 1;
+        let mut result = Vec::new();
+        let mut j = self.first;
+        loop {
+            let mut l = j;
+            let (last, mut nodes) = self.reconstitute(j, n, self.boundary, Some(self.hyphen_char));
+            j = last + 1;
+            if self.hyphen_passed == 0 {
+                result.append(&mut nodes);
+                if self.hyf[j - 1] % 2 == 1 {
+                    l = j;
+                    self.hyphen_passed = j - 1;
+                }
+            }
+            // The discretionary is followed by the nodes of the unhyphenated word, and both
+            // are developed until they end in the same way.
+            while self.hyphen_passed > 0 {
+                let mut replace = std::mem::take(&mut nodes);
+                let mut i = self.hyphen_passed;
+                self.hyf[i] = 0;
+                let mut pre_break = Vec::new();
+                let hyphen = self.font.char(self.hyphen_char).is_some();
+                let mut saved = None;
+                if hyphen {
+                    i += 1;
+                    saved = self.chars[i].replace(self.hyphen_char);
+                }
+                let font_boundary = self.font.boundary_char().map(char::from);
+                while l <= i {
+                    let (last, mut nodes) = self.reconstitute(l, i, font_boundary, None);
+                    l = last + 1;
+                    pre_break.append(&mut nodes);
+                }
+                if hyphen {
+                    self.chars[i] = saved;
+                    l = i;
+                }
+                let mut post_break = Vec::new();
+                // The next line begins with the left boundary character.
+                let mut saved = None;
+                if self.font.boundary_program().is_some() {
+                    l -= 1;
+                    saved = Some((l, self.chars[l].take()));
+                }
+                while l < j {
+                    loop {
+                        let (last, mut nodes) = self.reconstitute(l, n, self.boundary, None);
+                        l = last + 1;
+                        if let Some((k, c)) = saved.take() {
+                            self.chars[k] = c;
+                        }
+                        post_break.append(&mut nodes);
+                        if l >= j {
+                            break;
+                        }
+                    }
+                    while l > j {
+                        let (last, mut nodes) = self.reconstitute(j, n, self.boundary, None);
+                        j = last + 1;
+                        replace.append(&mut nodes);
+                    }
+                }
+                // A discretionary that would replace too many nodes is left out.
+                if replace.len() <= 127 {
+                    result.push(Node::Discretionary(Discretionary {
+                        pre_break,
+                        post_break,
+                        replace_count: replace.len(),
+                    }));
+                }
+                result.append(&mut replace);
+                self.hyphen_passed = match self.hyf[j - 1] % 2 {
+                    1 => j - 1,
+                    _ => 0,
+                };
+            }
+            if j > n {
+                return (self.start, result);
+            }
+        }
+    }
+
+    /// Typesets characters of the word from the one with index `j`, followed by the boundary
+    /// character after the one with index `n`, and returns the index of the last character
+    /// used and the nodes. The nodes end with the first character, ligature or kern that is
+    /// not followed by a ligature or kern with the next character.
+    ///
+    /// If the hyphen character is provided, a position where the word may be hyphenated that
+    /// is inside a ligature or kern is recorded in `hyphen_passed`, as is a position where
+    /// the hyphen would take part in a ligature or kern. This is `reconstitute` in TeX.
+    fn reconstitute(
+        &mut self,
+        j: usize,
+        n: usize,
+        boundary: Option<char>,
+        hyphen: Option<char>,
+    ) -> (usize, Vec<Node>) {
+        self.hyphen_passed = 0;
+        let mut cursor = Cursor {
+            j,
+            n,
+            boundary,
+            hyphen,
+            left: self.chars[j],
+            right: None,
+            right_hyphen: None,
+            start: 0,
+            stack: vec![],
+            nodes: vec![],
+            ligature_present: false,
+            left_hit: false,
+            right_hit: false,
+        };
+        if j == 0 {
+            cursor.ligature_present = self.init_ligature;
+            if cursor.ligature_present {
+                cursor.left_hit = self.init_left_hit;
+            }
+            for &c in &self.init_list {
+                cursor.nodes.push(self.char(c));
+            }
+        } else if let Some(c) = cursor.left {
+            cursor.nodes.push(self.char(c));
+        }
+        self.set_right(&mut cursor);
+        loop {
+            let kern = self.lig_kern(&mut cursor);
+            let right_hit = cursor.right_hit;
+            self.wrap_ligature(&mut cursor, right_hit);
+            if let Some(width) = kern {
+                cursor.nodes.push(Node::Kern(Kern {
+                    width,
+                    kind: KernKind::Normal,
+                }));
+            }
+            match cursor.stack.last() {
+                None => return (cursor.j, cursor.nodes),
+                Some(top) => {
+                    cursor.left = Some(top.c);
+                    cursor.start = cursor.nodes.len();
+                    cursor.ligature_present = true;
+                    self.pop_stack(&mut cursor);
+                }
+            }
+        }
+    }
+
+    /// Sets the character to the right of the cursor. This is `set_cur_r` in TeX.
+    fn set_right(&self, cursor: &mut Cursor) {
+        cursor.right = match cursor.j < cursor.n {
+            true => self.chars[cursor.j + 1],
+            false => cursor.boundary,
+        };
+        cursor.right_hyphen = cursor.hyphen.filter(|_| self.hyf[cursor.j] % 2 == 1);
+    }
+
+    /// Moves the cursor past the inserted character at the top of the stack. This is
+    /// `pop_lig_stack` in TeX.
+    fn pop_stack(&self, cursor: &mut Cursor) {
+        if let Some(Inserted {
+            original: Some(c), ..
+        }) = cursor.stack.pop()
+        {
+            cursor.nodes.push(self.char(c));
+            cursor.j += 1;
+        }
+        match cursor.stack.last().map(|top| top.c) {
+            None => self.set_right(cursor),
+            c => cursor.right = c,
+        }
+    }
+
+    /// Runs the ligature/kern programs at the cursor until the cursor moves, and returns the
+    /// kern that then follows the character to the left of the cursor, if any. This is parts
+    /// 909 to 911 of TeX.
+    fn lig_kern(&mut self, cursor: &mut Cursor) -> Option<Dimen> {
+        let font = self.font;
+        'cursor: loop {
+            let mut k = match cursor.left {
+                None => font.boundary_program(),
+                Some(c) => font.lig_kern_start(c),
+            }?;
+            let test_char = cursor.right_hyphen.or(cursor.right);
+            loop {
+                let instruction = font.lig_kern_instructions()[k];
+                if test_char == Some(char::from(instruction.next_char))
+                    && instruction.skip <= STOP_FLAG
+                {
+                    if cursor.right_hyphen.is_some() {
+                        // The hyphen would take part, so the next character is tried instead.
+                        self.hyphen_passed = cursor.j;
+                        cursor.hyphen = None;
+                        cursor.right_hyphen = None;
+                        continue 'cursor;
+                    }
+                    if cursor.hyphen.is_some() && self.hyf[cursor.j] % 2 == 1 {
+                        self.hyphen_passed = cursor.j;
+                        cursor.hyphen = None;
+                    }
+                    if let Some(i) = instruction.kern_index() {
+                        return Some(font.kern(i));
+                    }
+                    match self.ligature(cursor, instruction) {
+                        true => continue 'cursor,
+                        false => return None,
+                    }
+                }
+                if instruction.skip >= STOP_FLAG {
+                    // The programs are run again with the hyphen removed.
+                    cursor.right_hyphen?;
+                    cursor.right_hyphen = None;
+                    continue 'cursor;
+                }
+                k += instruction.skip as usize + 1;
+            }
+        }
+    }
+
+    /// Carries out a ligature instruction at the cursor, and returns whether the programs
+    /// should be run again without the cursor moving. This is part 911 of TeX.
+    fn ligature(&self, cursor: &mut Cursor, instruction: LigKernInstruction) -> bool {
+        if cursor.left.is_none() {
+            cursor.left_hit = true;
+        }
+        if cursor.j == cursor.n && cursor.stack.is_empty() {
+            cursor.right_hit = true;
+        }
+        let c = char::from(instruction.remainder);
+        match instruction.op {
+            // =:| and =:|>
+            1 | 5 => {
+                cursor.left = Some(c);
+                cursor.ligature_present = true;
+            }
+            // |=: and |=:>
+            2 | 6 => {
+                cursor.right = Some(c);
+                match cursor.stack.last_mut() {
+                    Some(top) => top.c = c,
+                    None => {
+                        let original = match cursor.j == cursor.n {
+                            true => None,
+                            false => self.chars[cursor.j + 1],
+                        };
+                        if original.is_none() {
+                            // The boundary character is replaced.
+                            cursor.boundary = None;
+                        }
+                        cursor.stack.push(Inserted { c, original });
+                    }
+                }
+            }
+            // |=:|
+            3 => {
+                cursor.right = Some(c);
+                cursor.stack.push(Inserted { c, original: None });
+            }
+            // |=:|> and |=:|>>
+            7 | 11 => {
+                self.wrap_ligature(cursor, false);
+                cursor.start = cursor.nodes.len();
+                cursor.left = Some(c);
+                cursor.ligature_present = true;
+            }
+            // =:
+            _ => {
+                cursor.left = Some(c);
+                cursor.ligature_present = true;
+                if !cursor.stack.is_empty() {
+                    self.pop_stack(cursor);
+                } else if cursor.j == cursor.n {
+                    return false;
+                } else {
+                    if let Some(c) = cursor.right {
+                        cursor.nodes.push(self.char(c));
+                    }
+                    cursor.j += 1;
+                    self.set_right(cursor);
+                }
+            }
+        }
+        instruction.op <= 4 || instruction.op == 7
+    }
+
+    /// Replaces the characters of the ligature at the cursor by the ligature, if there is one.
+    /// It is marked as formed with the right boundary character if `right_hit` is true and no
+    /// inserted characters are left. This is `wrap_lig` in TeX.
+    fn wrap_ligature(&self, cursor: &mut Cursor, right_hit: bool) {
+        let c = match (cursor.ligature_present, cursor.left) {
+            (true, Some(c)) => c,
+            _ => return,
+        };
+        let original = cursor
+            .nodes
+            .drain(cursor.start..)
+            .filter_map(|node| match node {
+                Node::Char(c) => Some(c.c),
+                _ => None,
+            })
+            .collect();
+        let mut ligature = Ligature {
+            font: self.font_id,
+            c,
+            original,
+            left_boundary: cursor.left_hit,
+            right_boundary: false,
+        };
+        cursor.left_hit = false;
+        if right_hit && cursor.stack.is_empty() {
+            ligature.right_boundary = true;
+            cursor.right_hit = false;
+        }
+        cursor.nodes.push(Node::Ligature(ligature));
+        cursor.ligature_present = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::state::TexState;
+    use crate::tex::testutil;
+
+    const FONT: FontId = FontId(1);
+
+    /// Runs the input with the excerpt of cmr10 as the current font, and returns the
+    /// characters, ligatures and discretionaries of the line built by `\showhyphens`.
+    fn show_hyphens(input: &str) -> Vec<Node> {
+        let mut s = testutil::new_state();
+        s.base_mut().char_codes.set_initex_defaults();
+        let fonts = &mut s.base_mut().fonts;
+        let id = fonts.add(Font::new(
+            "cmr10".to_string(),
+            &testutil::cmr10(),
+            Dimen::from_pt(10),
+        ));
+        fonts.set_current(id, true);
+        let s = testutil::run(s, input).unwrap();
+        let b = s
+            .base()
+            .registers
+            .box_register(0)
+            .unwrap()
+            .as_box()
+            .unwrap();
+        let line = b.list[0].as_box().unwrap();
+        line.list
+            .iter()
+            .filter(|node| {
+                matches!(
+                    node,
+                    Node::Char(_) | Node::Ligature(_) | Node::Discretionary(_)
+                )
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn char(c: char) -> Node {
+        Node::Char(Char { font: FONT, c })
+    }
+
+    fn ligature(c: char, original: &str) -> Node {
+        Node::Ligature(Ligature {
+            font: FONT,
+            c,
+            original: original.chars().collect(),
+            left_boundary: false,
+            right_boundary: false,
+        })
+    }
+
+    #[test]
+    fn ligature_across_hyphen() {
+        // The ffi ligature becomes f- at the end of a line and fi at the start of the next.
+        let discretionary = Node::Discretionary(Discretionary {
+            pre_break: vec![char('f'), char('-')],
+            post_break: vec![ligature('\x0C', "fi")],
+            replace_count: 1,
+        });
+        let office = vec![
+            char('o'),
+            discretionary,
+            ligature('\x0E', "ffi"),
+            char('c'),
+            char('e'),
+        ];
+        let input = r"\patterns{f1f}\lefthyphenmin=1 \righthyphenmin=1 ";
+        assert_eq!(
+            show_hyphens(&format!(r"{}\showhyphens{{office}}", input)),
+            office
+        );
+        // A character before the word is typeset again with it.
+        let mut quoted = vec![char('`')];
+        quoted.extend(office);
+        assert_eq!(
+            show_hyphens(&format!(r"{}\showhyphens{{`office}}", input)),
+            quoted
+        );
+    }
+
+    #[test]
+    fn hyphen_between_ligatures() {
+        // The hyphen goes between the ff and i of the ffi ligature.
+        let discretionary = Node::Discretionary(Discretionary {
+            pre_break: vec![ligature('\x0B', "ff"), char('-')],
+            post_break: vec![char('i')],
+            replace_count: 1,
+        });
+        assert_eq!(
+            show_hyphens(r"\patterns{f1i}\lefthyphenmin=1 \righthyphenmin=1 \showhyphens{ffi}"),
+            vec![discretionary, ligature('\x0E', "ffi")]
+        );
+    }
+
+    #[test]
+    fn words_that_are_not_hyphenated() {
+        let input = r"\patterns{f1f}\lefthyphenmin=1 \righthyphenmin=1 ";
+        // The word is too short.
+        assert_eq!(
+            show_hyphens(&format!(r"{}\righthyphenmin=5 \showhyphens{{off}}", input)),
+            vec![char('o'), ligature('\x0B', "ff")]
+        );
+        // The word is followed by a box.
+        assert_eq!(
+            show_hyphens(&format!(r"{}\showhyphens{{off\hbox{{}}}}", input)),
+            vec![char('o'), ligature('\x0B', "ff")]
+        );
+        assert_eq!(
+            show_hyphens(&format!(r"{}\showhyphens{{off}}", input)).len(),
+            3
+        );
+    }
+}
@@ -0,0 +1,467 @@
+//! Hyphenation patterns and exceptions, which determine where words may be hyphenated.
+//!
+//! TeX finds the hyphenation points of a word using Frank Liang's algorithm. A pattern is a
+//! string of letters with a digit between any two of them, like `hen5at` or `1tio`; the
+//! patterns of a language are given using `\patterns`, in which `.` stands for the edge of a
+//! word. Every pattern that occurs in a word contributes its digits to the gaps between its
+//! letters, and a word may be hyphenated at the gaps where the largest digit is odd:
+//! ```tex
+//! \patterns{hy3ph he2n hena4 hen5at 1na n2at 1tio 2io} % hy-phen-ation
+//! ```
+//! Words listed using `\hyphenation` are hyphenated exactly where their hyphens are, instead of
+//! using the patterns:
+//! ```tex
+//! \hyphenation{ta-ble present} % "present" is never hyphenated
+//! ```
+//! The letters of patterns and exceptions are replaced by their `\lccode`, so they match
+//! uppercase letters too. Both belong to the language that is `\language` when they are
+//! given, and both are global.
+//!
+//! While patterns are being loaded they are kept in a trie whose transitions are maps. The
+//! first time a paragraph is hyphenated, the trie is packed into arrays, and patterns can't be
+//! added anymore. This is how INITEX behaves; as Texide doesn't dump formats, every run is
+//! INITEX. The patterns of all languages are in the same trie, and the first transition of
+//! every pattern is on its language.
+
+use crate::tex::parameter::{IntParam, Parameters};
+use std::collections::{BTreeMap, HashMap};
+
+/// The maximum number of letters of a word that is hyphenated, and of a pattern.
+pub const MAX_WORD_LENGTH: usize = 63;
+
+/// The letter that stands for the edge of a word in patterns, which is `.` in `\patterns`.
+pub const EDGE_OF_WORD: char = '\0';
+
+/// The language of text in a paragraph, and the smallest number of letters that hyphenation
+/// leaves at the beginning and end of a word.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Language {
+    pub number: u8,
+    pub left_min: usize,
+    pub right_min: usize,
+}
+
+impl Language {
+    /// Returns the language with the provided number and minimums. Numbers outside of 0 to 255
+    /// are language 0, and the minimums are forced to be between 1 and 63.
+    pub fn new(number: i32, left_min: i32, right_min: i32) -> Language {
+        let normalize = |min: i32| min.clamp(1, MAX_WORD_LENGTH as i32) as usize;
+        Language {
+            number: language_number(number),
+            left_min: normalize(left_min),
+            right_min: normalize(right_min),
+        }
+    }
+
+    /// Returns the language given by `\language`, `\lefthyphenmin` and `\righthyphenmin`.
+    pub fn current(parameters: &Parameters) -> Language {
+        Language::new(
+            parameters.int(IntParam::Language),
+            parameters.int(IntParam::LeftHyphenMin),
+            parameters.int(IntParam::RightHyphenMin),
+        )
+    }
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::new(0, 0, 0)
+    }
+}
+
+/// Returns the number of a language: values outside of 0 to 255 are language 0. This is
+/// `set_cur_lang` in TeX.
+pub fn language_number(n: i32) -> u8 {
+    match n {
+        1..=255 => n as u8,
+        _ => 0,
+    }
+}
+
+/// The hyphenation patterns and exceptions of every language.
+#[derive(Debug)]
+pub struct Hyphenation {
+    trie: Trie,
+    /// The hyphen positions of exception words, which are the numbers of letters before each
+    /// hyphen, by language and lowercase word.
+    exceptions: HashMap<(u8, Vec<char>), Vec<usize>>,
+}
+
+#[derive(Debug)]
+enum Trie {
+    Linked(LinkedTrie),
+    Packed(PackedTrie),
+}
+
+impl Hyphenation {
+    /// Returns tables without patterns or exceptions.
+    pub fn new() -> Hyphenation {
+        Hyphenation {
+            trie: Trie::Linked(LinkedTrie::new()),
+            exceptions: HashMap::new(),
+        }
+    }
+
+    /// Returns whether the patterns have been packed, after which no patterns can be added.
+    /// This is the opposite of `trie_not_ready` in TeX.
+    pub fn is_packed(&self) -> bool {
+        matches!(self.trie, Trie::Packed(_))
+    }
+
+    /// Adds a pattern to a language. The digits of the pattern are in `values`, which has one
+    /// more element than `letters`: the digit before each letter and the digit at the end.
+    /// Letters are lowercase, or the edge of a word.
+    ///
+    /// Returns false if the language already has a pattern with these letters, or if the
+    /// patterns have been packed.
+    pub fn add_pattern(&mut self, language: u8, letters: &[char], values: &[u8]) -> bool {
+        assert_eq!(letters.len() + 1, values.len());
+        let trie = match &mut self.trie {
+            Trie::Linked(trie) => trie,
+            Trie::Packed(_) => return false,
+        };
+        let mut values = values.to_vec();
+        // Digits outside of the edges of a word are meaningless.
+        if letters.first() == Some(&EDGE_OF_WORD) {
+            values[0] = 0;
+        }
+        if letters.last() == Some(&EDGE_OF_WORD) {
+            *values.last_mut().unwrap() = 0;
+        }
+        let key = std::iter::once(char::from(language)).chain(letters.iter().copied());
+        trie.insert(key, values)
+    }
+
+    /// Adds an exception word to a language, replacing any exception for the same word. The
+    /// word is lowercase, and the hyphens are given by the number of letters before each.
+    pub fn add_exception(&mut self, language: u8, word: Vec<char>, hyphens: Vec<usize>) {
+        self.exceptions.insert((language, word), hyphens);
+    }
+
+    /// Packs the patterns, if that hasn't been done yet. This is `init_trie` in TeX.
+    pub fn pack(&mut self) {
+        if let Trie::Linked(trie) = &self.trie {
+            self.trie = Trie::Packed(PackedTrie::new(trie));
+        }
+    }
+
+    /// Returns the hyphenation values of a lowercase word: for each `i` from 0 to the length
+    /// of the word, whether the word may be hyphenated after its first `i` letters is given
+    /// by whether the value is odd. The word is never hyphenated within the minimum numbers
+    /// of letters at its edges.
+    pub fn hyphenate(&self, language: Language, word: &[char]) -> Vec<u8> {
+        let n = word.len();
+        let mut hyf = vec![0_u8; n + 1];
+        match self.exceptions.get(&(language.number, word.to_vec())) {
+            Some(hyphens) => {
+                for &i in hyphens {
+                    hyf[i] = 1;
+                }
+            }
+            None => self.apply_patterns(language, word, &mut hyf),
+        }
+        for value in hyf.iter_mut().take(language.left_min) {
+            *value = 0;
+        }
+        for value in hyf.iter_mut().rev().take(language.right_min) {
+            *value = 0;
+        }
+        hyf
+    }
+
+    fn apply_patterns(&self, language: Language, word: &[char], hyf: &mut [u8]) {
+        let root = match self.trie.transition(0, char::from(language.number)) {
+            None => return,
+            Some(root) => root,
+        };
+        let mut letters = Vec::with_capacity(word.len() + 2);
+        letters.push(EDGE_OF_WORD);
+        letters.extend_from_slice(word);
+        letters.push(EDGE_OF_WORD);
+        let n = word.len();
+        // Patterns that begin later only affect gaps too close to the end of the word.
+        for j in 0..=(n + 1).saturating_sub(language.right_min) {
+            let mut state = root;
+            for (l, &c) in letters.iter().enumerate().skip(j) {
+                state = match self.trie.transition(state, c) {
+                    None => break,
+                    Some(next) => next,
+                };
+                if let Some(values) = self.trie.values(state) {
+                    // The value before the letter with index i of the pattern is for the gap
+                    // after the letter with index j - 1 + i of the word.
+                    let first = l + 1 - (values.len() - 1);
+                    for (i, &v) in values.iter().enumerate() {
+                        if v > 0 && first + i >= 1 && first + i - 1 <= n {
+                            let gap = &mut hyf[first + i - 1];
+                            *gap = (*gap).max(v);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Hyphenation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trie {
+    fn transition(&self, state: usize, c: char) -> Option<usize> {
+        match self {
+            Trie::Linked(trie) => trie.nodes[state].transitions.get(&c).copied(),
+            Trie::Packed(trie) => trie.transition(state, c),
+        }
+    }
+
+    fn values(&self, state: usize) -> Option<&[u8]> {
+        match self {
+            Trie::Linked(trie) => trie.nodes[state].values.as_deref(),
+            Trie::Packed(trie) => trie.values(state),
+        }
+    }
+}
+
+/// A trie whose nodes have a map of transitions, to which patterns can be added.
+#[derive(Debug)]
+struct LinkedTrie {
+    nodes: Vec<LinkedNode>,
+}
+
+#[derive(Debug, Default)]
+struct LinkedNode {
+    transitions: BTreeMap<char, usize>,
+    /// The values of the pattern that ends at this node, if any.
+    values: Option<Vec<u8>>,
+}
+
+impl LinkedTrie {
+    fn new() -> LinkedTrie {
+        LinkedTrie {
+            nodes: vec![Default::default()],
+        }
+    }
+
+    /// Adds a pattern, and returns false if there already is a pattern with the same key.
+    fn insert(&mut self, key: impl Iterator<Item = char>, values: Vec<u8>) -> bool {
+        let mut state = 0;
+        for c in key {
+            state = match self.nodes[state].transitions.get(&c) {
+                Some(&next) => next,
+                None => {
+                    self.nodes.push(Default::default());
+                    let next = self.nodes.len() - 1;
+                    self.nodes[state].transitions.insert(c, next);
+                    next
+                }
+            };
+        }
+        let node = &mut self.nodes[state];
+        if node.values.is_some() {
+            return false;
+        }
+        node.values = Some(values);
+        true
+    }
+}
+
+/// Marks a state that is not the end of a pattern.
+const NO_VALUES: u32 = u32::MAX;
+
+/// A trie packed into arrays: the transitions from each state are a sorted slice of one array
+/// of transitions, and the values of patterns that are the same are only stored once.
+#[derive(Debug)]
+struct PackedTrie {
+    /// The index of the first transition of each state, followed by the number of transitions.
+    first_transition: Vec<u32>,
+    transitions: Vec<(char, u32)>,
+    /// The index in `values` of the values of the pattern that ends at each state.
+    pattern_values: Vec<u32>,
+    values: Vec<Box<[u8]>>,
+}
+
+impl PackedTrie {
+    /// Packs a linked trie. The states are numbered breadth first, so that the root is 0.
+    fn new(trie: &LinkedTrie) -> PackedTrie {
+        let mut packed = PackedTrie {
+            first_transition: Vec::with_capacity(trie.nodes.len() + 1),
+            transitions: Vec::with_capacity(trie.nodes.len()),
+            pattern_values: Vec::with_capacity(trie.nodes.len()),
+            values: Vec::new(),
+        };
+        let mut distinct_values: HashMap<&[u8], u32> = HashMap::new();
+        let mut order = vec![0];
+        let mut i = 0;
+        while i < order.len() {
+            let node = &trie.nodes[order[i]];
+            packed
+                .first_transition
+                .push(packed.transitions.len() as u32);
+            for (&c, &next) in &node.transitions {
+                packed.transitions.push((c, order.len() as u32));
+                order.push(next);
+            }
+            let values = match &node.values {
+                None => NO_VALUES,
+                Some(values) => {
+                    let values_list = &mut packed.values;
+                    *distinct_values.entry(values).or_insert_with(|| {
+                        values_list.push(values.clone().into_boxed_slice());
+                        (values_list.len() - 1) as u32
+                    })
+                }
+            };
+            packed.pattern_values.push(values);
+            i += 1;
+        }
+        packed
+            .first_transition
+            .push(packed.transitions.len() as u32);
+        packed
+    }
+
+    fn transition(&self, state: usize, c: char) -> Option<usize> {
+        let first = self.first_transition[state] as usize;
+        let last = self.first_transition[state + 1] as usize;
+        let transitions = &self.transitions[first..last];
+        transitions
+            .binary_search_by_key(&c, |&(c, _)| c)
+            .ok()
+            .map(|i| transitions[i].1 as usize)
+    }
+
+    fn values(&self, state: usize) -> Option<&[u8]> {
+        match self.pattern_values[state] {
+            NO_VALUES => None,
+            i => Some(&self.values[i as usize]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Adds patterns written as in `\patterns`, with letters that are their own lowercase.
+    fn add_patterns(h: &mut Hyphenation, language: u8, patterns: &str) {
+        for pattern in patterns.split_whitespace() {
+            let mut letters = Vec::new();
+            let mut values = vec![0];
+            for c in pattern.chars() {
+                match c.to_digit(10) {
+                    Some(d) => *values.last_mut().unwrap() = d as u8,
+                    None => {
+                        letters.push(if c == '.' { EDGE_OF_WORD } else { c });
+                        values.push(0);
+                    }
+                }
+            }
+            assert!(h.add_pattern(language, &letters, &values));
+        }
+    }
+
+    /// Returns the word with hyphens where it may be hyphenated.
+    fn show(h: &Hyphenation, language: Language, word: &str) -> String {
+        let letters: Vec<char> = word.chars().collect();
+        let hyf = h.hyphenate(language, &letters);
+        let mut s = String::new();
+        for (i, c) in letters.iter().enumerate() {
+            if i > 0 && hyf[i] % 2 == 1 {
+                s.push('-');
+            }
+            s.push(*c);
+        }
+        s
+    }
+
+    const HYPHENATION: &str = "hy3ph he2n hena4 hen5at 1na n2at 1tio 2io";
+
+    #[test]
+    fn patterns() {
+        let mut h = Hyphenation::new();
+        add_patterns(&mut h, 0, HYPHENATION);
+        let language = Language::new(0, 2, 3);
+        assert_eq!(show(&h, language, "hyphenation"), "hy-phen-ation");
+        h.pack();
+        assert_eq!(show(&h, language, "hyphenation"), "hy-phen-ation");
+        assert_eq!(show(&h, language, "hyphen"), "hy-phen");
+    }
+
+    #[test]
+    fn hyphen_minimums() {
+        let mut h = Hyphenation::new();
+        add_patterns(&mut h, 0, HYPHENATION);
+        h.pack();
+        assert_eq!(
+            show(&h, Language::new(0, 3, 3), "hyphenation"),
+            "hyphen-ation"
+        );
+        assert_eq!(
+            show(&h, Language::new(0, 2, 6), "hyphenation"),
+            "hy-phenation"
+        );
+        assert_eq!(show(&h, Language::new(0, 0, 0), "hyphen"), "hy-phen");
+    }
+
+    #[test]
+    fn edge_of_word() {
+        let mut h = Hyphenation::new();
+        add_patterns(&mut h, 0, ".ab1c 1bc.");
+        let language = Language::new(0, 1, 1);
+        assert_eq!(show(&h, language, "abcbc"), "ab-c-bc");
+        assert_eq!(show(&h, language, "xabc"), "xa-bc");
+        h.pack();
+        assert_eq!(show(&h, language, "abcbc"), "ab-c-bc");
+    }
+
+    #[test]
+    fn largest_value_wins() {
+        let mut h = Hyphenation::new();
+        add_patterns(&mut h, 0, "a1b a2bc");
+        let language = Language::new(0, 1, 1);
+        assert_eq!(show(&h, language, "abd"), "a-bd");
+        assert_eq!(show(&h, language, "abc"), "abc");
+    }
+
+    #[test]
+    fn languages() {
+        let mut h = Hyphenation::new();
+        add_patterns(&mut h, 0, "a1b");
+        add_patterns(&mut h, 2, "b1c");
+        h.pack();
+        assert_eq!(show(&h, Language::new(0, 1, 1), "abc"), "a-bc");
+        assert_eq!(show(&h, Language::new(2, 1, 1), "abc"), "ab-c");
+        assert_eq!(show(&h, Language::new(1, 1, 1), "abc"), "abc");
+        // Numbers out of range are language 0.
+        assert_eq!(show(&h, Language::new(256, 1, 1), "abc"), "a-bc");
+    }
+
+    #[test]
+    fn duplicate_pattern() {
+        let mut h = Hyphenation::new();
+        assert!(h.add_pattern(0, &['a', 'b'], &[0, 1, 0]));
+        assert!(!h.add_pattern(0, &['a', 'b'], &[0, 3, 0]));
+        assert!(h.add_pattern(1, &['a', 'b'], &[0, 3, 0]));
+        h.pack();
+        assert!(h.is_packed());
+        assert!(!h.add_pattern(0, &['c'], &[1, 0]));
+    }
+
+    #[test]
+    fn exceptions() {
+        let mut h = Hyphenation::new();
+        add_patterns(&mut h, 0, HYPHENATION);
+        h.add_exception(0, "hyphenation".chars().collect(), vec![6]);
+        h.add_exception(0, "table".chars().collect(), vec![2]);
+        let language = Language::new(0, 2, 3);
+        assert_eq!(show(&h, language, "hyphenation"), "hyphen-ation");
+        assert_eq!(show(&h, language, "table"), "ta-ble");
+        assert_eq!(show(&h, Language::new(0, 3, 3), "table"), "table");
+        assert_eq!(show(&h, Language::new(1, 2, 3), "table"), "table");
+        h.add_exception(0, "table".chars().collect(), vec![]);
+        assert_eq!(show(&h, language, "table"), "table");
+    }
+}
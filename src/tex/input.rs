@@ -1,35 +1,203 @@
-use crate::datastructures::scopedmap::ScopedMap;
+use crate::datastructures::scopedcharmap::ScopedCharMap;
 use crate::tex::token::catcode::RawCatCode;
 use crate::tex::token::lexer;
 use crate::tex::token::stream;
 use crate::tex::token::token;
+use std::collections::HashMap;
 use std::fs;
 use std::io;
 
-// TODO: this implementation seems....completely wrong?
-// Like, why does the inital input file get special treatment, whereas
-//   subsequent files imported via \input are handled differently?
-// Also how do we handle MULTIPLE sequential input files i.e., plain.tex
+/// The input files and the catcode tables used to read them.
+///
+/// Files opened using `\input` are read before the rest of the file that opened them, so the
+/// files that are open form a stack and tokens are read from the file at the top.
 pub struct InputModule {
-    pub cat_code_map: ScopedMap<char, RawCatCode>,
-    lexer: Option<lexer::Lexer<io::BufReader<fs::File>>>,
+    /// The active catcode table, which is used by the lexer.
+    pub cat_code_map: ScopedCharMap<RawCatCode>,
+    /// The number of the active catcode table.
+    cat_code_table: i32,
+    /// All catcode tables other than the active one.
+    cat_code_tables: HashMap<i32, ScopedCharMap<RawCatCode>>,
+    /// For each open scope, the number of the catcode table that was active when it began.
+    saved_cat_code_tables: Vec<i32>,
+    lexers: Vec<lexer::Lexer<Box<dyn io::BufRead>>>,
     next_token: Option<token::Token>,
+    /// Whether the input file has ended since this was last checked.
+    file_ended: bool,
 }
 
 impl InputModule {
-    pub fn new(cat_code_map: ScopedMap<char, RawCatCode>) -> InputModule {
+    pub fn new(cat_code_map: ScopedCharMap<RawCatCode>) -> InputModule {
         InputModule {
             cat_code_map,
-            lexer: None,
+            cat_code_table: 0,
+            cat_code_tables: HashMap::new(),
+            saved_cat_code_tables: Vec::new(),
+            lexers: Vec::new(),
             next_token: None,
+            file_ended: false,
         }
     }
 
     pub fn open_file(&mut self, file_name: &str) -> anyhow::Result<()> {
         let f = io::BufReader::new(fs::File::open(file_name)?);
-        self.lexer = Some(lexer::Lexer::new(f));
+        self.lexers = vec![lexer::Lexer::with_file_name(Box::new(f), file_name)];
         Ok(())
     }
+
+    /// Uses the provided string as the input. This is mostly useful for testing.
+    pub fn open_string(&mut self, s: &str) {
+        let reader = io::Cursor::new(s.to_string());
+        self.lexers = vec![lexer::Lexer::new(Box::new(reader))];
+    }
+
+    /// Begins reading the file, as `\input` does. The rest of the current file is read after
+    /// the file ends.
+    pub fn push_file(&mut self, file: Box<dyn io::BufRead>, file_name: &str) {
+        self.lexers
+            .push(lexer::Lexer::with_file_name(file, file_name));
+    }
+
+    /// Returns the number of files that are open.
+    pub fn num_open_files(&self) -> usize {
+        self.lexers.len()
+    }
+
+    /// Makes the current line of the current file its last line, as `\endinput` does.
+    pub fn end_file_after_current_line(&mut self) {
+        if let Some(lexer) = self.lexers.last_mut() {
+            lexer.end_after_current_line();
+        }
+    }
+
+    /// Returns true if an input file has ended since this was last called. This is the moment
+    /// when e-TeX inserts `\everyeof`.
+    ///
+    /// When a file ends no more tokens are read until this is called, even if the file was
+    /// opened by another file that has not ended yet.
+    pub fn take_end_of_file(&mut self) -> bool {
+        std::mem::take(&mut self.file_ended)
+    }
+
+    /// Begins a new scope in every catcode table.
+    pub fn begin_scope(&mut self) {
+        self.cat_code_map.begin_scope();
+        for table in self.cat_code_tables.values_mut() {
+            table.begin_scope();
+        }
+        self.saved_cat_code_tables.push(self.cat_code_table);
+    }
+
+    /// Ends the current scope in every catcode table, and reactivates the catcode table that was
+    /// active when the scope began. Returns false if there is no scope to end.
+    #[must_use]
+    pub fn end_scope(&mut self) -> bool {
+        let saved = match self.saved_cat_code_tables.pop() {
+            None => return false,
+            Some(saved) => saved,
+        };
+        // Every table has the same number of scopes, so these scopes always exist.
+        assert!(self.cat_code_map.end_scope());
+        for table in self.cat_code_tables.values_mut() {
+            assert!(table.end_scope());
+        }
+        self.activate_cat_code_table(saved);
+        true
+    }
+
+    /// Returns the number of the active catcode table.
+    pub fn cat_code_table(&self) -> i32 {
+        self.cat_code_table
+    }
+
+    /// Returns true if a catcode table with the provided number exists.
+    pub fn has_cat_code_table(&self, n: i32) -> bool {
+        n == self.cat_code_table || self.cat_code_tables.contains_key(&n)
+    }
+
+    /// Sets catcode table `n` to contain exactly the catcodes in the provided map, creating the
+    /// table if it does not exist. Like in LuaTeX, this is a global operation, and the active
+    /// table cannot be initialized: its values in enclosing scopes would be lost.
+    ///
+    /// ```
+    /// # use texide::tex::input::InputModule;
+    /// # use texide::tex::token::catcode;
+    /// let mut input_module = InputModule::new(catcode::initex_defaults());
+    /// input_module.init_cat_code_table(1, &catcode::tex_defaults()).unwrap();
+    /// input_module.select_cat_code_table(1, false).unwrap();
+    /// assert_eq!(input_module.cat_code_map.get(&'{'), catcode::tex_defaults().get(&'{'));
+    /// assert!(input_module.init_cat_code_table(1, &catcode::tex_defaults()).is_err());
+    /// ```
+    pub fn init_cat_code_table(
+        &mut self,
+        n: i32,
+        cat_code_map: &ScopedCharMap<RawCatCode>,
+    ) -> anyhow::Result<()> {
+        if n == self.cat_code_table {
+            return Err(anyhow::anyhow!(
+                "Invalid \\catcode table ({}); the active table cannot be initialized",
+                n
+            ));
+        }
+        let table = self.new_cat_code_table(cat_code_map);
+        self.cat_code_tables.insert(n, table);
+        Ok(())
+    }
+
+    /// Saves the catcodes of the active table to catcode table `n`, creating the table if it
+    /// does not exist. Like in LuaTeX, this is a global operation.
+    pub fn save_cat_code_table(&mut self, n: i32) {
+        if n == self.cat_code_table {
+            return;
+        }
+        let table = self.new_cat_code_table(&self.cat_code_map);
+        self.cat_code_tables.insert(n, table);
+    }
+
+    /// Returns a table containing the provided catcodes globally, with one scope for each scope
+    /// that is open.
+    fn new_cat_code_table(
+        &self,
+        cat_code_map: &ScopedCharMap<RawCatCode>,
+    ) -> ScopedCharMap<RawCatCode> {
+        let mut table = ScopedCharMap::new();
+        for _ in 0..self.saved_cat_code_tables.len() {
+            table.begin_scope();
+        }
+        for (c, &cat_code) in cat_code_map.iter() {
+            table.insert_global(c, cat_code);
+        }
+        table
+    }
+
+    /// Makes catcode table `n` the active table. If the selection is not global it is rolled
+    /// back at the end of the current scope.
+    pub fn select_cat_code_table(&mut self, n: i32, global: bool) -> anyhow::Result<()> {
+        if !self.has_cat_code_table(n) {
+            return Err(anyhow::anyhow!(
+                "Invalid \\catcode table ({}); tables must be created using \\initcatcodetable or \\savecatcodetable",
+                n
+            ));
+        }
+        self.activate_cat_code_table(n);
+        if global {
+            for saved in &mut self.saved_cat_code_tables {
+                *saved = n;
+            }
+        }
+        Ok(())
+    }
+
+    fn activate_cat_code_table(&mut self, n: i32) {
+        if n == self.cat_code_table {
+            return;
+        }
+        // Tables are never removed, so the table always exists.
+        let table = self.cat_code_tables.remove(&n).unwrap();
+        let previous = std::mem::replace(&mut self.cat_code_map, table);
+        self.cat_code_tables.insert(self.cat_code_table, previous);
+        self.cat_code_table = n;
+    }
 }
 
 impl stream::Stream for InputModule {
@@ -39,9 +207,13 @@ impl stream::Stream for InputModule {
     }
 
     fn prepare_imut_peek(&mut self) -> anyhow::Result<()> {
-        if self.next_token == None {
-            if let Some(lexer) = self.lexer.as_mut() {
+        if self.next_token.is_none() && !self.file_ended {
+            if let Some(lexer) = self.lexers.last_mut() {
                 self.next_token = lexer.next(&self.cat_code_map)?;
+                if self.next_token.is_none() {
+                    self.lexers.pop();
+                    self.file_ended = true;
+                }
             }
         }
         Ok(())
@@ -0,0 +1,879 @@
+//! Appending characters to horizontal lists, applying the ligature/kern programs of fonts.
+//!
+//! A character typeset in horizontal mode is not simply appended to the list. The characters
+//! that follow it in the input are read too, and the ligature/kern program of the current font
+//! is run on each pair of adjacent characters. The program may insert a kern between the two
+//! characters, or replace them by a ligature. In Computer Modern Roman, for example:
+//! ```tex
+//! office  % o, the ligature ffi made of f, f and i, c, e
+//! AV      % A, a kern of -1.11pt, V
+//! ```
+//! Besides `=:`, which replaces both characters by the ligature, TFM files have ligature
+//! operations that keep one or both characters, like `|=:` and `|=:|`, and operations whose
+//! name ends in `>` that skip over characters before the program continues.
+//!
+//! A font may have a boundary character, which stands for the space around a word: the program
+//! of the boundary character runs before the first character of a word, and the boundary
+//! character is the character after the last one. `\noboundary` just before or just after a
+//! word suppresses the boundary character there.
+//!
+//! Characters the current font doesn't have are dropped. If `\tracinglostchars` is positive,
+//! they are reported in the log file.
+//!
+//! This is the main loop of TeX, in part 46 of TeX: The Program. It is followed closely, so
+//! that the ligatures and kerns are exactly those that TeX makes.
+
+use crate::tex::driver;
+use crate::tex::files::Whatsit;
+use crate::tex::hyphenation::Language;
+use crate::tex::nest::Mode;
+use crate::tex::node::{Char, Discretionary, FontId, Kern, KernKind, Ligature, Node};
+use crate::tex::parameter::IntParam;
+use crate::tex::primitive;
+use crate::tex::primitive::library::{catcode, chardef, font};
+use crate::tex::primitive::{ConstantKind, Execution, Primitive};
+use crate::tex::state::{BaseState, TexState};
+use crate::tex::tfm::LigKernInstruction;
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::stream::Stream;
+
+/// The skip of the last instruction of a ligature/kern program.
+pub const STOP_FLAG: u8 = 128;
+
+/// Appends a character, and the characters that follow it in the input, to the current
+/// horizontal list, forming ligatures and inserting kerns.
+///
+/// The word begins with the boundary character of the font, unless `left_boundary` is false
+/// because it follows `\noboundary`. The word ends at the first token that is not a character,
+/// which is left in the input.
+pub fn append_characters<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    c: char,
+    left_boundary: bool,
+) -> anyhow::Result<()> {
+    let base = input.state_mut().base_mut();
+    if base.nest.mode() == Mode::Horizontal {
+        fix_language(base);
+    }
+    adjust_space_factor(base, c);
+    let id = base.fonts.current();
+    let font = base.fonts.get(id);
+    let right_boundary = font.boundary_char().map(char::from);
+    let mut main_loop = MainLoop {
+        font: id,
+        right_boundary,
+        false_boundary: right_boundary.filter(|&b| font.char(b).is_none()),
+        left: Some(c),
+        right: None,
+        last_read: c,
+        start: base.nest.current().list.len(),
+        pending: vec![Pending::Char(c)],
+        ligature_present: false,
+        left_hit: false,
+        right_hit: false,
+    };
+    let left_program = match left_boundary {
+        true => font.boundary_program(),
+        false => None,
+    };
+    let mut step = match left_program {
+        None => Step::AppendChar,
+        Some(k) => {
+            main_loop.right = main_loop.left.take();
+            Step::Instruction(k)
+        }
+    };
+    loop {
+        step = match step {
+            Step::Done => return Ok(()),
+            Step::Lookahead => main_loop.look_ahead(input)?,
+            step => main_loop.step(input.state_mut().base_mut(), step),
+        }
+    }
+}
+
+/// Executes `\noboundary` in horizontal mode: if a character follows, the word it begins has
+/// no left boundary character.
+pub fn no_boundary<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    if let Lookahead::Char(c) = look_ahead(input)? {
+        append_characters(input, c, false)?;
+    }
+    Ok(())
+}
+
+/// Appends a language whatsit to the paragraph if `\language` is not the language of the text
+/// at the end of the paragraph. This is `fix_language` in TeX.
+fn fix_language<S>(base: &mut BaseState<S>) {
+    let language = Language::current(&base.parameters);
+    if language.number != base.nest.current().language {
+        base.nest.current_mut().language = language.number;
+        base.nest.append(Node::Whatsit(Whatsit::Language(language)));
+    }
+}
+
+fn adjust_space_factor<S>(base: &mut BaseState<S>, c: char) {
+    let sf_code = base.char_codes.sf_code(c);
+    driver::update_space_factor(base.nest.current_mut(), sf_code);
+}
+
+/// The next token of the input, as seen by the main loop.
+enum Lookahead {
+    /// A character, which has been read.
+    Char(char),
+    /// `\noboundary`, which is left in the input.
+    NoBoundary,
+    /// Any other token, which is left in the input.
+    Other,
+}
+
+/// Reads the next token if it is a character: a character token of category letter or other, a
+/// control sequence that means one, a character defined using `\chardef`, or `\char` followed by
+/// a character code.
+fn look_ahead<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Lookahead> {
+    input.prepare_imut_peek()?;
+    let meaning = match input.imut_peek()? {
+        None => return Ok(Lookahead::Other),
+        Some(token) => input.state().base().meaning(&token.value),
+    };
+    let meaning = match meaning {
+        None => return Ok(Lookahead::Other),
+        Some(meaning) => meaning,
+    };
+    let is = |get: fn() -> primitive::ExecutionStatic<S>| {
+        meaning.same_meaning(&Primitive::Execution(Execution::Static(get())))
+    };
+    let c = match &meaning {
+        Primitive::Character(c, CatCode::Letter | CatCode::Other) => *c,
+        // The code was validated by \chardef.
+        Primitive::Constant(ConstantKind::Char, n) => char::from_u32(*n as u32).unwrap(),
+        _ if is(chardef::get_char) => {
+            input.consume()?;
+            let n = catcode::parse_char(input)?;
+            // The code was validated by `parse_char`.
+            return Ok(Lookahead::Char(char::from_u32(n as u32).unwrap()));
+        }
+        _ if is(font::get_noboundary) => return Ok(Lookahead::NoBoundary),
+        _ => return Ok(Lookahead::Other),
+    };
+    input.consume()?;
+    Ok(Lookahead::Char(c))
+}
+
+/// A character to the right of the cursor that has not been appended to the list yet. The
+/// pending characters are a stack whose top is the character just right of the cursor. This is
+/// `lig_stack` in TeX.
+#[derive(Debug, Copy, Clone)]
+enum Pending {
+    /// A character read from the input.
+    Char(char),
+    /// A character inserted by a ligature instruction. If it replaced a character read from the
+    /// input, that character becomes one of the original characters of a ligature.
+    Inserted { c: char, original: Option<char> },
+}
+
+impl Pending {
+    fn c(&self) -> char {
+        match self {
+            Pending::Char(c) => *c,
+            Pending::Inserted { c, .. } => *c,
+        }
+    }
+}
+
+/// The places in TeX's main loop.
+#[derive(Debug, Copy, Clone)]
+enum Step {
+    /// Makes the ligature at the cursor, if there is one. This is `main_loop_wrapup`.
+    Wrapup,
+    /// Moves the cursor one character to the right, or finishes the word if there is no
+    /// character there. This is `main_loop_move`.
+    Move,
+    /// Moves the cursor past the top pending character. This is `main_loop_move+1`.
+    MovePending,
+    /// Appends the top pending character, which was read from the input, to the list. This is
+    /// `main_loop_move+2`.
+    AppendChar,
+    /// Reads the next character from the input, if there is one. This is
+    /// `main_loop_lookahead`.
+    Lookahead,
+    /// Runs the program of the character to the left of the cursor. This is `main_lig_loop`.
+    LigKern,
+    /// Runs the program from the instruction with the provided index. This is
+    /// `main_lig_loop+1`.
+    Instruction(usize),
+    /// Moves the cursor past a pending character inserted by a ligature instruction. This is
+    /// `main_loop_move_lig`.
+    MoveInserted,
+    Done,
+}
+
+/// The state of the main loop.
+struct MainLoop {
+    font: FontId,
+    /// The character after the last character of the word, or `None` if there is none. This is
+    /// `bchar` in TeX.
+    right_boundary: Option<char>,
+    /// The boundary character, if the font doesn't have it as a real character. It then never
+    /// forms ligatures when read from the input. This is `false_bchar` in TeX.
+    false_boundary: Option<char>,
+    /// The character to the left of the cursor, or `None` for the left boundary. This is
+    /// `cur_l` in TeX.
+    left: Option<char>,
+    /// The character to the right of the cursor, or `None` if there is none. This is `cur_r`.
+    right: Option<char>,
+    /// The character most recently read from the input. This is `cur_chr`.
+    last_read: char,
+    /// The length of the list before the character to the left of the cursor. The characters
+    /// after it become the original characters of the ligature at the cursor. This is `cur_q`.
+    start: usize,
+    pending: Vec<Pending>,
+    /// Whether the character to the left of the cursor is a ligature that has not been made yet.
+    ligature_present: bool,
+    /// Whether the ligature at the cursor was formed with the left boundary character.
+    left_hit: bool,
+    /// Whether the ligature at the cursor was formed with the right boundary character.
+    right_hit: bool,
+}
+
+impl MainLoop {
+    fn look_ahead<S: TexState<S>>(
+        &mut self,
+        input: &mut primitive::Input<S>,
+    ) -> anyhow::Result<Step> {
+        match look_ahead(input)? {
+            Lookahead::Char(c) => {
+                adjust_space_factor(input.state_mut().base_mut(), c);
+                self.pending.push(Pending::Char(c));
+                self.last_read = c;
+                self.right = match Some(c) == self.false_boundary {
+                    // This prevents spurious ligatures.
+                    true => None,
+                    false => Some(c),
+                };
+            }
+            lookahead => {
+                if let Lookahead::NoBoundary = lookahead {
+                    self.right_boundary = None;
+                }
+                self.right = self.right_boundary;
+            }
+        }
+        Ok(Step::LigKern)
+    }
+
+    fn step<S>(&mut self, base: &mut BaseState<S>, step: Step) -> Step {
+        match step {
+            Step::Wrapup => {
+                self.wrapup(base, self.right_hit);
+                Step::Move
+            }
+            Step::Move => match self.pending.last() {
+                None => Step::Done,
+                Some(top) => {
+                    self.start = base.nest.current().list.len();
+                    self.left = Some(top.c());
+                    Step::MovePending
+                }
+            },
+            Step::MovePending => match self.pending.last() {
+                Some(Pending::Char(_)) => Step::AppendChar,
+                _ => Step::MoveInserted,
+            },
+            Step::AppendChar => {
+                let font = base.fonts.get(self.font);
+                let exists = |c: Option<char>| c.and_then(|c| font.char(c)).is_some();
+                if !exists(Some(self.last_read)) || !exists(self.left) {
+                    missing_character(base, self.font, self.last_read);
+                    return Step::Done;
+                }
+                let c = self.pending.pop().map(|top| top.c());
+                if let Some(c) = c {
+                    base.nest.append(Node::Char(Char { font: self.font, c }));
+                }
+                Step::Lookahead
+            }
+            Step::LigKern => {
+                let font = base.fonts.get(self.font);
+                match (self.left.and_then(|c| font.lig_kern_start(c)), self.right) {
+                    (Some(k), Some(_)) => Step::Instruction(k),
+                    _ => Step::Wrapup,
+                }
+            }
+            Step::Instruction(k) => {
+                let instruction = base.fonts.get(self.font).lig_kern_instructions()[k];
+                if self.right == Some(char::from(instruction.next_char))
+                    && instruction.skip <= STOP_FLAG
+                {
+                    return self.execute(base, instruction);
+                }
+                match instruction.skip {
+                    0 => Step::Instruction(k + 1),
+                    skip if skip >= STOP_FLAG => Step::Wrapup,
+                    skip => Step::Instruction(k + skip as usize + 1),
+                }
+            }
+            Step::MoveInserted => {
+                let original = match self.pending.pop() {
+                    Some(Pending::Inserted { original, .. }) => original,
+                    _ => None,
+                };
+                if let Some(c) = original {
+                    base.nest.append(Node::Char(Char { font: self.font, c }));
+                }
+                self.ligature_present = true;
+                match self.pending.last() {
+                    None if original.is_some() => return Step::Lookahead,
+                    None => self.right = self.right_boundary,
+                    Some(top) => self.right = Some(top.c()),
+                }
+                Step::LigKern
+            }
+            Step::Lookahead | Step::Done => step,
+        }
+    }
+
+    /// Executes a ligature or kern instruction that applies to the characters on either side of
+    /// the cursor.
+    fn execute<S>(&mut self, base: &mut BaseState<S>, instruction: LigKernInstruction) -> Step {
+        if let Some(i) = instruction.kern_index() {
+            self.wrapup(base, self.right_hit);
+            let width = base.fonts.get(self.font).kern(i);
+            base.nest.append(Node::Kern(Kern {
+                width,
+                kind: KernKind::Normal,
+            }));
+            return Step::Move;
+        }
+        if self.left.is_none() {
+            self.left_hit = true;
+        } else if self.pending.is_empty() {
+            self.right_hit = true;
+        }
+        let c = char::from(instruction.remainder);
+        match instruction.op {
+            // =:| and =:|>
+            1 | 5 => {
+                self.left = Some(c);
+                self.ligature_present = true;
+            }
+            // |=: and |=:>
+            2 | 6 => {
+                self.right = Some(c);
+                match self.pending.last_mut() {
+                    // The right boundary character is replaced.
+                    None => {
+                        self.pending.push(Pending::Inserted { c, original: None });
+                        self.right_boundary = None;
+                    }
+                    Some(top) => match *top {
+                        Pending::Char(original) => {
+                            *top = Pending::Inserted {
+                                c,
+                                original: Some(original),
+                            }
+                        }
+                        Pending::Inserted { original, .. } => {
+                            *top = Pending::Inserted { c, original }
+                        }
+                    },
+                }
+            }
+            // |=:|
+            3 => {
+                self.right = Some(c);
+                self.pending.push(Pending::Inserted { c, original: None });
+            }
+            // |=:|> and |=:|>>
+            7 | 11 => {
+                self.wrapup(base, false);
+                self.start = base.nest.current().list.len();
+                self.left = Some(c);
+                self.ligature_present = true;
+            }
+            // =:
+            _ => {
+                self.left = Some(c);
+                self.ligature_present = true;
+                return match self.pending.is_empty() {
+                    true => Step::Wrapup,
+                    false => Step::MovePending,
+                };
+            }
+        }
+        if instruction.op > 4 && instruction.op != 7 {
+            return Step::Wrapup;
+        }
+        match self.left {
+            Some(_) => Step::LigKern,
+            None => {
+                // The left character is only the boundary when the word began with the
+                // program of the boundary character.
+                let k = base.fonts.get(self.font).boundary_program().unwrap();
+                Step::Instruction(k)
+            }
+        }
+    }
+
+    /// Makes the ligature at the cursor, if there is one, and inserts an empty discretionary
+    /// after the hyphen character of the font. This is `wrapup` in TeX.
+    fn wrapup<S>(&mut self, base: &mut BaseState<S>, right_hit: bool) {
+        let left = match self.left {
+            None => return,
+            Some(left) => left,
+        };
+        let hyphen_char = base.fonts.get(self.font).hyphen_char;
+        let list = &base.nest.current().list;
+        let insert_discretionary = list.len() > self.start
+            && matches!(list.last(), Some(Node::Char(c)) if c.c as i32 == hyphen_char);
+        if self.ligature_present {
+            self.make_ligature(base, left, right_hit);
+        }
+        // Discretionaries are only inserted in paragraphs, not in restricted horizontal mode.
+        if insert_discretionary && base.nest.mode() == Mode::Horizontal {
+            base.nest.append(Node::Discretionary(Discretionary {
+                pre_break: vec![],
+                post_break: vec![],
+                replace_count: 0,
+            }));
+        }
+    }
+
+    /// Replaces the characters after the start of the ligature at the cursor by the ligature.
+    /// This is `pack_lig` in TeX.
+    fn make_ligature<S>(&mut self, base: &mut BaseState<S>, c: char, right_hit: bool) {
+        let list = &mut base.nest.current_mut().list;
+        // Only characters are appended after the start of a ligature.
+        let original = list
+            .drain(self.start..)
+            .filter_map(|node| match node {
+                Node::Char(c) => Some(c.c),
+                _ => None,
+            })
+            .collect();
+        let right_boundary = right_hit && self.pending.is_empty();
+        list.push(Node::Ligature(Ligature {
+            font: self.font,
+            c,
+            original,
+            left_boundary: self.left_hit,
+            right_boundary,
+        }));
+        self.left_hit = false;
+        if right_boundary {
+            self.right_hit = false;
+        }
+        self.ligature_present = false;
+    }
+}
+
+/// Reports a character that is missing from its font in the log file, if `\tracinglostchars` is
+/// positive. This is `char_warning` in TeX.
+fn missing_character<S>(base: &mut BaseState<S>, font: FontId, c: char) {
+    if base.parameters.int(IntParam::TracingLostChars) <= 0 {
+        return;
+    }
+    let text = format!(
+        "Missing character: There is no {} in font {}!",
+        c,
+        base.fonts.get(font).name
+    );
+    let selector = base.diagnostic_selector();
+    base.print_nl(selector, &text);
+    base.printer.print_ln(selector);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::dimen::{Dimen, Glue};
+    use crate::tex::font::Font;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+    use crate::tex::testutil::{cmr10, krn, lig, stop, tfm};
+    use crate::tex::tfm::Tfm;
+
+    const FONT: FontId = FontId(1);
+
+    fn run(tfm: &Tfm, input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        testutil::set_single_line_paragraphs(&mut s);
+        let fonts = &mut s.base_mut().fonts;
+        let id = fonts.add(Font::new("cmr10".to_string(), tfm, Dimen::from_pt(10)));
+        fonts.set_current(id, true);
+        testutil::run(s, input)
+    }
+
+    /// Returns the single line of the paragraph typeset from the input, without the penalty,
+    /// `\parfillskip` glue and `\rightskip` glue at the end.
+    fn line(tfm: &Tfm, input: &str) -> Vec<Node> {
+        let s = run(tfm, input).unwrap();
+        let mut line = s
+            .base()
+            .nest
+            .current()
+            .list
+            .last()
+            .unwrap()
+            .as_box()
+            .unwrap()
+            .list
+            .clone();
+        line.truncate(line.len() - 3);
+        line
+    }
+
+    fn char(c: char) -> Node {
+        Node::Char(Char { font: FONT, c })
+    }
+
+    fn ligature(c: char, original: &str) -> Node {
+        Node::Ligature(Ligature {
+            font: FONT,
+            c,
+            original: original.chars().collect(),
+            left_boundary: false,
+            right_boundary: false,
+        })
+    }
+
+    fn kern(sp: i32) -> Node {
+        Node::Kern(Kern {
+            width: Dimen(sp),
+            kind: KernKind::Normal,
+        })
+    }
+
+    fn discretionary() -> Node {
+        Node::Discretionary(Discretionary {
+            pre_break: vec![],
+            post_break: vec![],
+            replace_count: 0,
+        })
+    }
+
+    #[test]
+    fn ligatures() {
+        assert_eq!(
+            line(&cmr10(), r"\noindent office"),
+            vec![char('o'), ligature('\x0E', "ffi"), char('c'), char('e')]
+        );
+        assert_eq!(
+            line(&cmr10(), r"\noindent fl ff"),
+            vec![
+                ligature('\x0D', "fl"),
+                Node::new_glue(Glue {
+                    width: Dimen(218453),
+                    stretch: Dimen(109226),
+                    shrink: Dimen(72818),
+                    ..Default::default()
+                }),
+                ligature('\x0B', "ff"),
+            ]
+        );
+    }
+
+    #[test]
+    fn kerns() {
+        assert_eq!(
+            line(&cmr10(), r"\noindent AV"),
+            [char('A'), kern(-72819), char('V')]
+        );
+        let b = line(&cmr10(), r"\noindent\hbox{AV}")[0].clone();
+        assert_eq!(b.as_box().unwrap().width, Dimen(2 * 491521 - 72819));
+    }
+
+    #[test]
+    fn kern_after_ligature() {
+        assert_eq!(
+            line(&cmr10(), r"\noindent ff'"),
+            vec![ligature('\x0B', "ff"), kern(50973), char('\'')]
+        );
+    }
+
+    #[test]
+    fn quotes_and_dashes() {
+        assert_eq!(
+            line(&cmr10(), r"\noindent ``a''"),
+            vec![ligature('\\', "``"), char('a'), ligature('"', "''")]
+        );
+        // A discretionary follows the hyphen character, even at the end of a ligature.
+        assert_eq!(
+            line(&cmr10(), r"\noindent a--b---a-b"),
+            vec![
+                char('a'),
+                ligature('{', "--"),
+                discretionary(),
+                char('b'),
+                ligature('|', "---"),
+                discretionary(),
+                char('a'),
+                char('-'),
+                discretionary(),
+                char('b'),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_discretionary_in_restricted_horizontal_mode() {
+        let line = line(&cmr10(), r"\noindent\hbox{a-b}");
+        assert_eq!(
+            line[0].as_box().unwrap().list,
+            vec![char('a'), char('-'), char('b')]
+        );
+    }
+
+    #[test]
+    fn implicit_characters() {
+        assert_eq!(
+            line(&cmr10(), r"\chardef\eye=`i \noindent f\eye f\char`i"),
+            vec![ligature('\x0C', "fi"), ligature('\x0C', "fi")]
+        );
+    }
+
+    #[test]
+    fn other_tokens_end_the_word() {
+        assert_eq!(
+            line(&cmr10(), r"\noindent f\relax i f{}i"),
+            vec![
+                char('f'),
+                char('i'),
+                Node::new_glue(Glue {
+                    width: Dimen(218453),
+                    stretch: Dimen(109226),
+                    shrink: Dimen(72818),
+                    ..Default::default()
+                }),
+                char('f'),
+                char('i'),
+            ]
+        );
+    }
+
+    #[test]
+    fn interword_glue_after_sentence() {
+        let line = line(&cmr10(), r"\sfcode`\.=3000 \noindent a. b");
+        assert_eq!(
+            line[2],
+            Node::new_glue(Glue {
+                width: Dimen(218453 + 72818),
+                stretch: Dimen(327678),
+                shrink: Dimen(24272),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn missing_characters_are_dropped() {
+        let s = run(
+            &cmr10(),
+            r"\tracinglostchars=1 \tracingonline=1 \noindent fZi",
+        )
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "Missing character: There is no Z in font cmr10!\n"
+        );
+        let line = s
+            .base()
+            .nest
+            .current()
+            .list
+            .last()
+            .unwrap()
+            .as_box()
+            .unwrap();
+        assert_eq!(line.list[..2], [char('f'), char('i')]);
+        let s = run(&cmr10(), r"\noindent fZi").unwrap();
+        assert_eq!(testutil::terminal(&s), "");
+    }
+
+    /// Returns a font whose character `a` has a program that applies the provided ligature
+    /// operation to `a` and `b`, forming `x`. Kerns follow `a` before `x`, and `x` before `b`.
+    fn ligature_operation(op: u8) -> Tfm {
+        let chars = [('a', 1 << 20), ('b', 1 << 20), ('x', 1 << 20)];
+        let lig_kern = vec![lig('b', op, 'x'), stop(krn('x', 0)), stop(krn('b', 1))];
+        let programs = [('a', 0), ('x', 2)];
+        tfm(&chars, &programs, lig_kern, vec![1 << 16, 1 << 17], vec![])
+    }
+
+    #[test]
+    fn ligature_operations() {
+        let (k0, k1) = (kern(40960), kern(81920));
+        let (a, b) = (char('a'), char('b'));
+        for (op, name, want) in [
+            (0, "=:", vec![ligature('x', "ab")]),
+            (1, "=:|", vec![ligature('x', "a"), k1.clone(), b.clone()]),
+            (2, "|=:", vec![a.clone(), k0.clone(), ligature('x', "b")]),
+            (
+                3,
+                "|=:|",
+                vec![
+                    a.clone(),
+                    k0.clone(),
+                    ligature('x', ""),
+                    k1.clone(),
+                    b.clone(),
+                ],
+            ),
+            (5, "=:|>", vec![ligature('x', "a"), b.clone()]),
+            (6, "|=:>", vec![a.clone(), ligature('x', "b")]),
+            (
+                7,
+                "|=:|>",
+                vec![a.clone(), ligature('x', ""), k1.clone(), b.clone()],
+            ),
+            (11, "|=:|>>", vec![a.clone(), ligature('x', ""), b.clone()]),
+        ] {
+            assert_eq!(
+                line(&ligature_operation(op), r"\noindent ab"),
+                want,
+                "{}",
+                name
+            );
+        }
+    }
+
+    /// Returns a font with the boundary character `|`, which the font doesn't have. At the
+    /// start of a word `a` becomes `y`, at the end of a word `b` becomes `z`, and `a` is followed
+    /// by a kern.
+    fn boundary_font() -> Tfm {
+        let chars = [
+            ('a', 1 << 20),
+            ('b', 1 << 20),
+            ('y', 1 << 20),
+            ('z', 1 << 20),
+        ];
+        let lig_kern = vec![
+            LigKernInstruction {
+                skip: 255,
+                next_char: b'|',
+                op: 0,
+                remainder: 0,
+            },
+            stop(krn('|', 0)),
+            stop(lig('|', 0, 'z')),
+            stop(lig('a', 2, 'y')),
+            LigKernInstruction {
+                skip: 255,
+                next_char: 0,
+                op: 0,
+                remainder: 3,
+            },
+        ];
+        let programs = [('a', 1), ('b', 2)];
+        tfm(&chars, &programs, lig_kern, vec![1 << 16], vec![])
+    }
+
+    #[test]
+    fn boundary_characters() {
+        assert_eq!(
+            line(&boundary_font(), r"\noindent ab"),
+            vec![
+                Node::Ligature(Ligature {
+                    font: FONT,
+                    c: 'y',
+                    original: vec!['a'],
+                    left_boundary: true,
+                    right_boundary: false,
+                }),
+                Node::Ligature(Ligature {
+                    font: FONT,
+                    c: 'z',
+                    original: vec!['b'],
+                    left_boundary: false,
+                    right_boundary: true,
+                }),
+            ]
+        );
+        assert_eq!(
+            line(&boundary_font(), r"\noindent ba"),
+            vec![char('b'), char('a'), kern(40960)]
+        );
+    }
+
+    /// Returns a font whose boundary character `z` is a real character. The program of the
+    /// boundary character applies the ligature operation to `a`, forming `y`, and is followed
+    /// by a kern before `y`. `a` is followed by a kern before `z`.
+    fn real_boundary_font(op: u8) -> Tfm {
+        let chars = [('a', 1 << 20), ('y', 1 << 20), ('z', 1 << 20)];
+        let lig_kern = vec![
+            LigKernInstruction {
+                skip: 255,
+                next_char: b'z',
+                op: 0,
+                remainder: 0,
+            },
+            stop(krn('z', 1)),
+            lig('a', op, 'y'),
+            stop(krn('y', 0)),
+            LigKernInstruction {
+                skip: 255,
+                next_char: 0,
+                op: 0,
+                remainder: 2,
+            },
+        ];
+        let programs = [('a', 1)];
+        tfm(&chars, &programs, lig_kern, vec![1 << 16, 1 << 17], vec![])
+    }
+
+    #[test]
+    fn real_boundary_character() {
+        let tfm = real_boundary_font(2);
+        let y = Node::Ligature(Ligature {
+            font: FONT,
+            c: 'y',
+            original: vec!['a'],
+            left_boundary: true,
+            right_boundary: false,
+        });
+        // |=: runs the program of the boundary character again, which kerns before y.
+        assert_eq!(line(&tfm, r"\noindent a"), vec![kern(40960), y.clone()]);
+        // |=:> skips the boundary character, so there is no kern.
+        assert_eq!(line(&real_boundary_font(6), r"\noindent a"), vec![y]);
+        // The right boundary character is z, so a word ending in a is followed by a kern, but z
+        // itself is not appended.
+        assert_eq!(
+            line(&tfm, r"\noindent\noboundary a"),
+            vec![char('a'), kern(81920)]
+        );
+        assert_eq!(
+            line(&tfm, r"\noindent\noboundary a\noboundary"),
+            vec![char('a')]
+        );
+        // Unlike a boundary character the font doesn't have, z read from the input takes part
+        // in the program of a.
+        assert_eq!(
+            line(&tfm, r"\noindent\noboundary az\noboundary"),
+            vec![char('a'), kern(81920), char('z')]
+        );
+    }
+
+    #[test]
+    fn noboundary() {
+        assert_eq!(
+            line(&boundary_font(), r"\noindent\noboundary ab\noboundary"),
+            vec![char('a'), char('b')]
+        );
+        assert_eq!(
+            line(&boundary_font(), r"\noindent ba\noboundary\relax"),
+            vec![char('b'), char('a')]
+        );
+        // \noboundary begins a paragraph in vertical mode, and then suppresses the left boundary.
+        let s = run(&boundary_font(), r"\noboundary ab").unwrap();
+        let line = &s
+            .base()
+            .nest
+            .current()
+            .list
+            .last()
+            .unwrap()
+            .as_box()
+            .unwrap()
+            .list;
+        assert_eq!(line[1], char('a'));
+        assert!(matches!(&line[2], Node::Ligature(l) if l.c == 'z' && l.right_boundary));
+    }
+}
@@ -0,0 +1,1236 @@
+//! Breaking paragraphs into lines.
+//!
+//! When a paragraph ends, its horizontal list is broken into lines using the optimum-fit
+//! algorithm of Knuth and Plass, which is `line_break` in TeX. The algorithm chooses the
+//! breakpoints that minimize the total demerits of the paragraph, where the demerits of a line
+//! grow with its badness and with the penalty at its end:
+//! ```tex
+//! \hsize=100pt \tolerance=200 \linepenalty=10
+//! \parshape 2 0pt 100pt 20pt 80pt % the second and later lines are indented
+//! ```
+//! A line may end at glue that follows a non-discardable node, at a penalty, at a
+//! discretionary, at a kern followed by glue, or at the end of a formula followed by glue.
+//! The first pass only accepts lines whose badness is at most `\pretolerance`. If there is no
+//! way to break the paragraph, a second pass uses `\tolerance`, and if that also fails and
+//! `\emergencystretch` is positive, a final pass pretends every line has that much additional
+//! stretch. Before the second pass, the words of the paragraph are
+//! [hyphenated](crate::tex::hyphenate), so that lines may also end at hyphens. `\looseness`
+//! asks for a paragraph that is longer or shorter than the optimum by a number of lines.
+//!
+//! TeX keeps the widths of the lines between each active breakpoint and the current position
+//! using a list of differences between consecutive active breakpoints. Here every active
+//! breakpoint instead records the total width of the paragraph up to where its next line
+//! begins, which gives the same widths with simpler bookkeeping.
+
+use crate::tex::dimen::{Dimen, Glue, GlueOrder};
+use crate::tex::driver;
+use crate::tex::font::{Fonts, NULL_FONT};
+use crate::tex::hyphenate;
+use crate::tex::hyphenation::Language;
+use crate::tex::node::{
+    self, BoxNode, Char, Discretionary, FontId, KernKind, Ligature, MathKind, Node, INF_PENALTY,
+};
+use crate::tex::pack::{self, INF_BAD};
+use crate::tex::parameter::{DimenParam, GlueParam, IntParam, ParShape, Parameters};
+use crate::tex::primitive::library::boxes;
+use crate::tex::state::BaseState;
+use std::cell::OnceCell;
+use std::collections::VecDeque;
+
+/// Demerits larger than any that can be reached. This is `awful_bad` in TeX.
+const AWFUL_BAD: i64 = 0o7777777777;
+
+/// The penalty that forces a break; larger penalties forbid the break. This is `eject_penalty`
+/// in TeX.
+const EJECT_PENALTY: i32 = -INF_PENALTY;
+
+/// The fitness classes of lines, by how much their glue stretches or shrinks. Adjacent lines
+/// whose classes are not adjacent get `\adjdemerits`.
+const VERY_LOOSE_FIT: usize = 0;
+const LOOSE_FIT: usize = 1;
+const DECENT_FIT: usize = 2;
+const TIGHT_FIT: usize = 3;
+
+/// Widths of material, as used when measuring lines: the natural width, the stretch of each
+/// order of infinity in the order of [GlueOrder], and the shrink.
+type Widths = [i64; 6];
+
+const SHRINK: usize = 5;
+
+fn add_glue(widths: &mut Widths, glue: &Glue, sign: i64) {
+    widths[0] += sign * glue.width.0 as i64;
+    widths[1 + glue.stretch_order as usize] += sign * glue.stretch.0 as i64;
+    widths[SHRINK] += sign * glue.shrink.0 as i64;
+}
+
+/// Breaks the list of a paragraph into lines and appends them to the current vertical list,
+/// along with interline glue and penalties, and returns the last line. The list must end with
+/// the `\parfillskip` glue. The penalty between the last two lines includes the provided
+/// widow penalty, which is `\displaywidowpenalty` before a display and `\widowpenalty`
+/// otherwise.
+///
+/// The lines are numbered from `\prevgraf` plus one, which matters for the shape of the
+/// paragraph if it continues after a display, and `\prevgraf` is set to the number of the last
+/// line. If the paragraph is hyphenated before the second pass, it is hyphenated in the
+/// language it began in, which is provided. This is `line_break` in TeX.
+pub fn line_break<S>(
+    base: &mut BaseState<S>,
+    list: Vec<Node>,
+    language: Language,
+    final_widow_penalty: i32,
+) -> anyhow::Result<BoxNode> {
+    let parameters = &base.parameters;
+    let left_skip = parameters.glue(GlueParam::LeftSkip);
+    let right_skip = parameters.glue(GlueParam::RightSkip);
+    let glues = list.iter().filter_map(|node| match node {
+        Node::Glue(g) => Some(&g.glue),
+        _ => None,
+    });
+    for glue in [left_skip, right_skip].iter().chain(glues) {
+        if glue.shrink_order != GlueOrder::Normal && glue.shrink.0 != 0 {
+            return Err(anyhow::anyhow!(
+                "Infinite glue shrinkage found in a paragraph: lines cannot be broken if glue can shrink by an arbitrary amount"
+            ));
+        }
+    }
+    let mut background = [0; 6];
+    add_glue(&mut background, &left_skip, 1);
+    add_glue(&mut background, &right_skip, 1);
+    let shape = LineShape::new(parameters);
+    let looseness = parameters.int(IntParam::Looseness);
+    let hyphenated = OnceCell::new();
+    let mut breaker = Breaker {
+        list: &list,
+        fonts: &base.fonts,
+        easy_line: match looseness {
+            0 => shape.last_special_line,
+            _ => i32::MAX,
+        },
+        shape,
+        first_line: base.nest.current().prev_graf + 1,
+        background,
+        line_penalty: parameters.int(IntParam::LinePenalty) as i64,
+        adj_demerits: parameters.int(IntParam::AdjDemerits) as i64,
+        double_hyphen_demerits: parameters.int(IntParam::DoubleHyphenDemerits) as i64,
+        final_hyphen_demerits: parameters.int(IntParam::FinalHyphenDemerits) as i64,
+        hyphen_penalty: parameters.int(IntParam::HyphenPenalty),
+        ex_hyphen_penalty: parameters.int(IntParam::ExHyphenPenalty),
+        threshold: parameters.int(IntParam::PreTolerance),
+        final_pass: false,
+        active: Vec::new(),
+        passive: Vec::new(),
+        totals: [0; 6],
+        disc_width: 0,
+        minimal_demerits: [AWFUL_BAD; 4],
+        minimum_demerits: AWFUL_BAD,
+        best_place: [None; 4],
+        best_line: [0; 4],
+        trace: match parameters.int(IntParam::TracingParagraphs) > 0 {
+            true => Some(Vec::new()),
+            false => None,
+        },
+        printed: 0,
+        display_font: NULL_FONT,
+    };
+    let emergency_stretch = parameters.dimen(DimenParam::EmergencyStretch).0;
+    let mut second_pass = breaker.threshold < 0;
+    if second_pass {
+        base.hyphenation.pack();
+        breaker.list =
+            hyphenated.get_or_init(|| hyphenate::hyphenate_paragraph(base, list.clone(), language));
+        breaker.threshold = parameters.int(IntParam::Tolerance);
+        breaker.final_pass = emergency_stretch <= 0;
+    } else if let Some(trace) = &mut breaker.trace {
+        trace.push("@firstpass".to_string());
+    }
+    let best = loop {
+        breaker.threshold = breaker.threshold.min(INF_BAD);
+        if let Some(best) = breaker.pass(looseness) {
+            break best;
+        }
+        if !second_pass {
+            if let Some(trace) = &mut breaker.trace {
+                trace.push("@secondpass".to_string());
+            }
+            base.hyphenation.pack();
+            breaker.list = hyphenated
+                .get_or_init(|| hyphenate::hyphenate_paragraph(base, list.clone(), language));
+            breaker.threshold = parameters.int(IntParam::Tolerance);
+            second_pass = true;
+            breaker.final_pass = emergency_stretch <= 0;
+        } else {
+            if let Some(trace) = &mut breaker.trace {
+                trace.push("@emergencypass".to_string());
+            }
+            breaker.background[1] += emergency_stretch as i64;
+            breaker.final_pass = true;
+        }
+    };
+    let best = &breaker.active[best];
+    let mut breaks = Vec::new();
+    let mut p = best.passive;
+    while let Some(i) = p {
+        breaks.push(breaker.passive[i].position);
+        p = breaker.passive[i].prev;
+    }
+    breaks.reverse();
+    let trace = breaker.trace.take();
+    let shape = breaker.shape;
+    if let Some(trace) = trace {
+        let selector = base.diagnostic_selector();
+        for line in trace {
+            base.print_nl(selector, &line);
+        }
+        base.print_nl(selector, "");
+        base.printer.print_ln(selector);
+    }
+    Ok(post_line_break(
+        base,
+        hyphenated.into_inner().unwrap_or(list),
+        &breaks,
+        &shape,
+        final_widow_penalty,
+        (left_skip, right_skip),
+    ))
+}
+
+/// Breaks the list at the chosen breakpoints, packages the lines to their lengths and
+/// appends them to the current vertical list. This is `post_line_break` in TeX.
+fn post_line_break<S>(
+    base: &mut BaseState<S>,
+    list: Vec<Node>,
+    breaks: &[Option<usize>],
+    shape: &LineShape,
+    final_widow_penalty: i32,
+    (left_skip, right_skip): (Glue, Glue),
+) -> BoxNode {
+    let first_line = base.nest.current().prev_graf + 1;
+    let last_line = first_line + breaks.len() as i32 - 1;
+    let mut rest: VecDeque<Node> = list.into();
+    // The index in the original list of the first node of `rest`.
+    let mut consumed = 0;
+    // The post-break material of a discretionary, which begins the next line.
+    let mut carry = Vec::new();
+    let mut last_box = BoxNode::default();
+    for (k, position) in breaks.iter().enumerate() {
+        let cur_line = first_line + k as i32;
+        let mut line = std::mem::take(&mut carry);
+        let mut disc_break = false;
+        match *position {
+            None => line.extend(rest.drain(..)),
+            Some(position) => {
+                line.extend(rest.drain(..position - consumed));
+                let node = rest.pop_front().unwrap();
+                consumed = position + 1;
+                match node {
+                    // The glue at the break becomes the \rightskip glue.
+                    Node::Glue(_) => {}
+                    Node::Discretionary(d) => {
+                        rest.drain(..d.replace_count);
+                        consumed += d.replace_count;
+                        line.push(Node::Discretionary(Discretionary {
+                            pre_break: vec![],
+                            post_break: vec![],
+                            replace_count: 0,
+                        }));
+                        line.extend(d.pre_break);
+                        carry = d.post_break;
+                        disc_break = true;
+                    }
+                    Node::Math(mut m) => {
+                        m.width = Dimen(0);
+                        line.push(Node::Math(m));
+                    }
+                    Node::Kern(mut k) => {
+                        k.width = Dimen(0);
+                        line.push(Node::Kern(k));
+                    }
+                    node => line.push(node),
+                }
+            }
+        }
+        line.push(Node::new_param_glue(GlueParam::RightSkip, right_skip));
+        if left_skip != Glue::default() {
+            line.insert(0, Node::new_param_glue(GlueParam::LeftSkip, left_skip));
+        }
+        let (indent, length) = shape.line(cur_line);
+        let adjustments = boxes::take_adjustments(&mut line);
+        let (mut b, badness, report) = pack::hpack(
+            line,
+            pack::Spec::Exactly(length),
+            &base.parameters,
+            &base.fonts,
+        );
+        base.last_badness = badness;
+        b.shift = indent;
+        if let Some(report) = report {
+            // TODO: give the lines of the input that the paragraph came from, as TeX does
+            pack::print_report(base, &report, &b, "in paragraph");
+        }
+        last_box = b.clone();
+        driver::append_to_vlist(base, Node::HList(b));
+        for node in adjustments {
+            base.nest.append(node);
+        }
+        if cur_line != last_line {
+            let parameters = &base.parameters;
+            let mut pen = parameters.int(IntParam::InterLinePenalty);
+            if cur_line == first_line {
+                pen += parameters.int(IntParam::ClubPenalty);
+            }
+            if cur_line + 1 == last_line {
+                pen += final_widow_penalty;
+            }
+            if disc_break {
+                pen += parameters.int(IntParam::BrokenPenalty);
+            }
+            if pen != 0 {
+                base.nest.append(Node::Penalty(pen));
+            }
+        }
+        // Discardable material at the beginning of the next line is removed, unless the line
+        // begins with the post-break material of a discretionary.
+        if carry.is_empty() {
+            let next = breaks.get(k + 1).copied().flatten();
+            while let Some(node) = rest.front() {
+                let discardable = match node {
+                    Node::Glue(_) | Node::Penalty(_) | Node::Math(_) => true,
+                    Node::Kern(k) => k.kind == KernKind::Explicit,
+                    _ => false,
+                };
+                if Some(consumed) == next || !discardable {
+                    break;
+                }
+                rest.pop_front();
+                consumed += 1;
+            }
+        }
+    }
+    base.nest.current_mut().prev_graf = last_line;
+    last_box
+}
+
+fn clamp(x: i64) -> Dimen {
+    Dimen(x.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+}
+
+/// Returns the width of a node that may appear in a discretionary, or among the nodes a
+/// discretionary replaces.
+fn width(node: &Node, fonts: &Fonts) -> i64 {
+    (match node {
+        Node::Char(Char { font, c }) | Node::Ligature(Ligature { font, c, .. }) => {
+            fonts.get(*font).char(*c).map_or(0, |m| m.width.0)
+        }
+        Node::HList(b) | Node::VList(b) => b.width.0,
+        Node::Rule(r) => r.width.map_or(0, |w| w.0),
+        Node::Kern(k) => k.width.0,
+        _ => 0,
+    }) as i64
+}
+
+/// Returns whether a node is one that glue following it may break at. This is
+/// `precedes_break` in TeX.
+fn precedes_break(node: &Node) -> bool {
+    !matches!(
+        node,
+        Node::Glue(_) | Node::Kern(_) | Node::Penalty(_) | Node::Math(_)
+    )
+}
+
+/// The indentation and length of the lines of a paragraph, given by `\parshape` or by
+/// `\hsize`, `\hangindent` and `\hangafter`.
+struct LineShape {
+    par_shape: ParShape,
+    /// The lines after this one all have the second shape.
+    last_special_line: i32,
+    first: (Dimen, Dimen),
+    second: (Dimen, Dimen),
+}
+
+impl LineShape {
+    fn new(parameters: &Parameters) -> LineShape {
+        let par_shape = parameters.par_shape();
+        let hsize = parameters.dimen(DimenParam::HSize);
+        let hang_indent = parameters.dimen(DimenParam::HangIndent);
+        let hang_after = parameters.int(IntParam::HangAfter);
+        let normal = (Dimen(0), hsize);
+        let (last_special_line, first, second) = if let Some(last) = par_shape.last() {
+            (par_shape.len() as i32 - 1, normal, *last)
+        } else if hang_indent == Dimen(0) {
+            (0, normal, normal)
+        } else {
+            // A positive hanging indentation is on the left and a negative one on the right.
+            let hanging = (
+                Dimen(hang_indent.0.max(0)),
+                Dimen(hsize.0 - hang_indent.0.abs()),
+            );
+            match hang_after < 0 {
+                true => (-hang_after, hanging, normal),
+                false => (hang_after, normal, hanging),
+            }
+        };
+        LineShape {
+            par_shape,
+            last_special_line,
+            first,
+            second,
+        }
+    }
+
+    /// Returns the indentation and length of line `l`, counting from 1.
+    fn line(&self, l: i32) -> (Dimen, Dimen) {
+        if l > self.last_special_line {
+            self.second
+        } else if self.par_shape.is_empty() {
+            self.first
+        } else {
+            self.par_shape[(l - 1) as usize]
+        }
+    }
+}
+
+/// A feasible breakpoint, recorded along with the best way of reaching it. This is a passive
+/// node in TeX.
+struct Passive {
+    /// The index of the node the line breaks at, or `None` for the end of the paragraph.
+    position: Option<usize>,
+    /// The previous breakpoint on the best way of reaching this one.
+    prev: Option<usize>,
+    /// The number of the breakpoint in the tracing output.
+    serial: usize,
+}
+
+/// A breakpoint from which the next line may begin. This is an active node in TeX.
+struct Active {
+    /// The breakpoint, or `None` for the beginning of the paragraph.
+    passive: Option<usize>,
+    /// The number of the line that begins here.
+    line_number: i32,
+    fitness: usize,
+    /// Whether the line before the breakpoint ends at a discretionary.
+    hyphenated: bool,
+    total_demerits: i64,
+    /// The total widths up to the current position, minus the widths of the line that begins
+    /// here.
+    start: Widths,
+}
+
+struct Breaker<'a> {
+    list: &'a [Node],
+    fonts: &'a Fonts,
+    shape: LineShape,
+    /// Lines after this one all have the same length, so breakpoints for them can be compared
+    /// regardless of their line numbers.
+    easy_line: i32,
+    first_line: i32,
+    /// The widths of `\leftskip` and `\rightskip`, which are part of every line.
+    background: Widths,
+    line_penalty: i64,
+    adj_demerits: i64,
+    double_hyphen_demerits: i64,
+    final_hyphen_demerits: i64,
+    hyphen_penalty: i32,
+    ex_hyphen_penalty: i32,
+    threshold: i32,
+    final_pass: bool,
+    active: Vec<Active>,
+    passive: Vec<Passive>,
+    /// The total widths of the paragraph up to the current position.
+    totals: Widths,
+    /// The width of the material before a discretionary break, while it is tried.
+    disc_width: i64,
+    minimal_demerits: [i64; 4],
+    minimum_demerits: i64,
+    best_place: [Option<usize>; 4],
+    best_line: [i32; 4],
+    /// The output of `\tracingparagraphs`, if it is positive.
+    trace: Option<Vec<String>>,
+    /// The number of nodes shown in the tracing output.
+    printed: usize,
+    display_font: FontId,
+}
+
+impl<'a> Breaker<'a> {
+    /// Tries to end a line at the current position, which is the node at the provided index or
+    /// the end of the paragraph, with the provided penalty. This is `try_break` in TeX.
+    fn try_break(&mut self, pi: i32, hyphenated: bool, cur: Option<usize>) {
+        let pi = match pi.abs() >= INF_PENALTY {
+            true if pi > 0 => return,
+            true => EJECT_PENALTY,
+            false => pi,
+        };
+        let mut break_width = None;
+        let mut old_l = 0;
+        let mut line_width = 0_i64;
+        let mut i = 0;
+        loop {
+            let l = match self.active.get(i) {
+                None => i32::MAX,
+                Some(r) => r.line_number,
+            };
+            if l > old_l {
+                if self.minimum_demerits < AWFUL_BAD
+                    && (old_l != self.easy_line || i == self.active.len())
+                {
+                    // The best breaks for the lines that just ended become active.
+                    let break_width = *break_width.get_or_insert_with(|| self.break_width(cur));
+                    i = self.insert_active(i, cur, hyphenated, break_width);
+                }
+                if i == self.active.len() {
+                    return;
+                }
+                if l > self.easy_line {
+                    line_width = self.shape.second.1 .0 as i64;
+                    old_l = i32::MAX - 1;
+                } else {
+                    line_width = self.shape.line(l).1 .0 as i64;
+                    old_l = l;
+                }
+            }
+            let r = &self.active[i];
+            let mut width = self.totals;
+            for (w, start) in width.iter_mut().zip(r.start) {
+                *w -= start;
+            }
+            let shortfall = line_width - width[0];
+            let (b, fit) = if shortfall > 0 {
+                if width[2] != 0 || width[3] != 0 || width[4] != 0 {
+                    (0, DECENT_FIT)
+                } else if shortfall > 7230584 && width[1] < 1663497 {
+                    (INF_BAD, VERY_LOOSE_FIT)
+                } else {
+                    let b = pack::badness(clamp(shortfall), clamp(width[1]));
+                    let fit = match b {
+                        _ if b > 99 => VERY_LOOSE_FIT,
+                        _ if b > 12 => LOOSE_FIT,
+                        _ => DECENT_FIT,
+                    };
+                    (b, fit)
+                }
+            } else {
+                let b = match -shortfall > width[SHRINK] {
+                    true => INF_BAD + 1,
+                    false => pack::badness(clamp(-shortfall), clamp(width[SHRINK])),
+                };
+                (b, if b > 12 { TIGHT_FIT } else { DECENT_FIT })
+            };
+            let mut artificial_demerits = false;
+            let stays_active = if b > INF_BAD || pi == EJECT_PENALTY {
+                // Lines from this breakpoint can only get worse, so it is deactivated.
+                if self.final_pass
+                    && self.minimum_demerits == AWFUL_BAD
+                    && i == 0
+                    && self.active.len() == 1
+                {
+                    // There is no other way to break the paragraph, so the break is forced.
+                    artificial_demerits = true;
+                } else if b > self.threshold {
+                    self.active.remove(i);
+                    continue;
+                }
+                false
+            } else {
+                if b > self.threshold {
+                    i += 1;
+                    continue;
+                }
+                true
+            };
+            let d = match artificial_demerits {
+                true => 0,
+                false => self.demerits(b, pi, fit, hyphenated, cur, r),
+            };
+            if self.trace.is_some() {
+                self.trace_feasible_break(cur, i, b, pi, d, artificial_demerits);
+            }
+            let r = &self.active[i];
+            let d = d + r.total_demerits;
+            if d <= self.minimal_demerits[fit] {
+                self.minimal_demerits[fit] = d;
+                self.best_place[fit] = r.passive;
+                self.best_line[fit] = l;
+                self.minimum_demerits = self.minimum_demerits.min(d);
+            }
+            match stays_active {
+                true => i += 1,
+                false => {
+                    self.active.remove(i);
+                }
+            }
+        }
+    }
+
+    /// Returns the demerits of a line from the active breakpoint to the current position.
+    fn demerits(
+        &self,
+        b: i32,
+        pi: i32,
+        fit: usize,
+        hyphenated: bool,
+        cur: Option<usize>,
+        r: &Active,
+    ) -> i64 {
+        let d = self.line_penalty + b as i64;
+        let mut d = match d.abs() >= 10000 {
+            true => 100000000,
+            false => d * d,
+        };
+        let pi = pi as i64;
+        if pi > 0 {
+            d += pi * pi;
+        } else if pi > EJECT_PENALTY as i64 {
+            d -= pi * pi;
+        }
+        if hyphenated && r.hyphenated {
+            d += match cur {
+                Some(_) => self.double_hyphen_demerits,
+                None => self.final_hyphen_demerits,
+            };
+        }
+        if fit.abs_diff(r.fitness) > 1 {
+            d += self.adj_demerits;
+        }
+        d
+    }
+
+    /// Inserts active breakpoints at the current position for the best ways of reaching it
+    /// found in each fitness class, before the active breakpoint at index `i`, and returns the
+    /// index of that breakpoint after the insertion.
+    fn insert_active(
+        &mut self,
+        mut i: usize,
+        cur: Option<usize>,
+        hyphenated: bool,
+        break_width: Widths,
+    ) -> usize {
+        let mut start = self.totals;
+        for (s, w) in start.iter_mut().zip(break_width) {
+            *s -= w;
+        }
+        self.minimum_demerits = match self.adj_demerits.abs() >= AWFUL_BAD - self.minimum_demerits {
+            true => AWFUL_BAD - 1,
+            false => self.minimum_demerits + self.adj_demerits.abs(),
+        };
+        for fit in VERY_LOOSE_FIT..=TIGHT_FIT {
+            if self.minimal_demerits[fit] <= self.minimum_demerits {
+                let serial = self.passive.len() + 1;
+                self.passive.push(Passive {
+                    position: cur,
+                    prev: self.best_place[fit],
+                    serial,
+                });
+                let active = Active {
+                    passive: Some(self.passive.len() - 1),
+                    line_number: self.best_line[fit] + 1,
+                    fitness: fit,
+                    hyphenated,
+                    total_demerits: self.minimal_demerits[fit],
+                    start,
+                };
+                if let Some(trace) = &mut self.trace {
+                    let prev = match self.best_place[fit] {
+                        None => 0,
+                        Some(p) => self.passive[p].serial,
+                    };
+                    trace.push(format!(
+                        "@@{}: line {}.{}{} t={} -> @@{}",
+                        serial,
+                        active.line_number - 1,
+                        fit,
+                        if hyphenated { "-" } else { "" },
+                        active.total_demerits,
+                        prev
+                    ));
+                }
+                self.active.insert(i, active);
+                i += 1;
+            }
+            self.minimal_demerits[fit] = AWFUL_BAD;
+        }
+        self.minimum_demerits = AWFUL_BAD;
+        i
+    }
+
+    /// Returns the widths of the line that begins after breaking at the current position,
+    /// measured from the current position: the background, less the discardable material
+    /// that follows the break, and adjusted for the material of a discretionary.
+    fn break_width(&self, cur: Option<usize>) -> Widths {
+        let mut break_width = self.background;
+        let cur = match cur {
+            None => return break_width,
+            Some(cur) => cur,
+        };
+        let mut s = cur;
+        if let Node::Discretionary(d) = &self.list[cur] {
+            for node in &self.list[cur + 1..cur + 1 + d.replace_count] {
+                break_width[0] -= width(node, self.fonts);
+            }
+            for node in &d.post_break {
+                break_width[0] += width(node, self.fonts);
+            }
+            break_width[0] += self.disc_width;
+            if !d.post_break.is_empty() {
+                // Nothing after a nonempty post-break list is discarded.
+                return break_width;
+            }
+            s = cur + 1 + d.replace_count;
+        }
+        for node in &self.list[s..] {
+            match node {
+                Node::Glue(g) => add_glue(&mut break_width, &g.glue, -1),
+                Node::Penalty(_) => {}
+                Node::Math(m) => break_width[0] -= m.width.0 as i64,
+                Node::Kern(k) if k.kind == KernKind::Explicit => break_width[0] -= k.width.0 as i64,
+                _ => break,
+            }
+        }
+        break_width
+    }
+
+    /// Adds a description of a feasible break from the active breakpoint at index `i` to the
+    /// tracing output, preceded by the material since the last description.
+    fn trace_feasible_break(
+        &mut self,
+        cur: Option<usize>,
+        i: usize,
+        b: i32,
+        pi: i32,
+        d: i64,
+        artificial_demerits: bool,
+    ) {
+        let end = match cur {
+            None => self.list.len() + 1,
+            Some(cur) => cur + 1,
+        };
+        let trace = self.trace.as_mut().unwrap();
+        if self.printed != end {
+            let nodes = &self.list[self.printed.min(self.list.len())..end.min(self.list.len())];
+            trace.push(node::short_display(
+                nodes,
+                self.fonts,
+                &mut self.display_font,
+            ));
+            self.printed = end;
+        }
+        let list = self.list;
+        let position = match cur.map(|cur| &list[cur]) {
+            None => "\\par",
+            Some(Node::Penalty(_)) => "\\penalty",
+            Some(Node::Discretionary(_)) => "\\discretionary",
+            Some(Node::Kern(_)) => "\\kern",
+            Some(Node::Math(_)) => "\\math",
+            Some(_) => "",
+        };
+        let via = match self.active[i].passive {
+            None => 0,
+            Some(p) => self.passive[p].serial,
+        };
+        trace.push(format!(
+            "@{} via @@{} b={} p={} d={}",
+            position,
+            via,
+            if b > INF_BAD {
+                "*".to_string()
+            } else {
+                b.to_string()
+            },
+            pi,
+            if artificial_demerits {
+                "*".to_string()
+            } else {
+                d.to_string()
+            }
+        ));
+    }
+
+    /// Runs one pass over the paragraph, and returns the best active breakpoint at the end of
+    /// the paragraph if the pass found one acceptable with the requested looseness.
+    fn pass(&mut self, looseness: i32) -> Option<usize> {
+        self.active = vec![Active {
+            passive: None,
+            line_number: self.first_line,
+            fitness: DECENT_FIT,
+            hyphenated: false,
+            total_demerits: 0,
+            start: self.background.map(|w| -w),
+        }];
+        self.passive.clear();
+        self.totals = [0; 6];
+        self.minimal_demerits = [AWFUL_BAD; 4];
+        self.minimum_demerits = AWFUL_BAD;
+        self.printed = 0;
+        self.display_font = NULL_FONT;
+        let list = self.list;
+        let mut auto_breaking = true;
+        // Glue at the beginning of the paragraph is not a legal breakpoint.
+        let mut prev = 0;
+        let mut cur = 0;
+        while cur < list.len() && !self.active.is_empty() {
+            if list[cur].is_char() {
+                prev = cur;
+                while cur < list.len() && list[cur].is_char() {
+                    self.totals[0] += width(&list[cur], self.fonts);
+                    cur += 1;
+                }
+                if cur == list.len() {
+                    break;
+                }
+            }
+            match &list[cur] {
+                Node::HList(_) | Node::VList(_) | Node::Rule(_) | Node::Ligature(_) => {
+                    self.totals[0] += width(&list[cur], self.fonts);
+                }
+                Node::Glue(g) => {
+                    if auto_breaking {
+                        let breakable = match &list[prev] {
+                            Node::Kern(k) => k.kind != KernKind::Explicit,
+                            node => precedes_break(node),
+                        };
+                        if breakable {
+                            self.try_break(0, false, Some(cur));
+                        }
+                    }
+                    add_glue(&mut self.totals, &g.glue, 1);
+                }
+                Node::Kern(k) => {
+                    if k.kind == KernKind::Explicit {
+                        self.kern_break(auto_breaking, cur);
+                    }
+                    self.totals[0] += k.width.0 as i64;
+                }
+                Node::Math(m) => {
+                    auto_breaking = m.kind == MathKind::After;
+                    self.kern_break(auto_breaking, cur);
+                    self.totals[0] += m.width.0 as i64;
+                }
+                Node::Penalty(p) => self.try_break(*p, false, Some(cur)),
+                Node::Discretionary(d) => {
+                    self.try_discretionary_break(d, cur);
+                    // The nodes that the discretionary replaces are never breakpoints.
+                    for node in &list[cur + 1..cur + 1 + d.replace_count] {
+                        self.totals[0] += width(node, self.fonts);
+                    }
+                    prev = cur;
+                    cur += 1 + d.replace_count;
+                    continue;
+                }
+                Node::Char(_)
+                | Node::Insert(_)
+                | Node::Mark(_)
+                | Node::Adjust(_)
+                | Node::Whatsit(_) => {}
+            }
+            prev = cur;
+            cur += 1;
+        }
+        if cur < list.len() || self.active.is_empty() {
+            return None;
+        }
+        self.try_break(EJECT_PENALTY, true, None);
+        if self.active.is_empty() {
+            return None;
+        }
+        let mut best = 0;
+        for (i, r) in self.active.iter().enumerate() {
+            if r.total_demerits < self.active[best].total_demerits {
+                best = i;
+            }
+        }
+        if looseness == 0 {
+            return Some(best);
+        }
+        // Among the breakpoints whose number of lines is as close as possible to the requested
+        // difference from the best one, the one with the fewest demerits is chosen.
+        let best_line = self.active[best].line_number;
+        let mut actual_looseness = 0;
+        for (i, r) in self.active.iter().enumerate() {
+            let line_diff = r.line_number - best_line;
+            if (line_diff < actual_looseness && looseness <= line_diff)
+                || (line_diff > actual_looseness && looseness >= line_diff)
+            {
+                best = i;
+                actual_looseness = line_diff;
+            } else if line_diff == actual_looseness
+                && r.total_demerits < self.active[best].total_demerits
+            {
+                best = i;
+            }
+        }
+        match actual_looseness == looseness || self.final_pass {
+            true => Some(best),
+            false => None,
+        }
+    }
+
+    /// Tries to break at an explicit kern or the end of a formula, which are breakpoints if
+    /// glue follows them.
+    fn kern_break(&mut self, auto_breaking: bool, cur: usize) {
+        if auto_breaking {
+            if let Some(Node::Glue(_)) = self.list.get(cur + 1) {
+                self.try_break(0, false, Some(cur));
+            }
+        }
+    }
+
+    /// Tries to break at a discretionary, with the width of its pre-break material added to
+    /// the line.
+    fn try_discretionary_break(&mut self, d: &Discretionary, cur: usize) {
+        if d.pre_break.is_empty() {
+            self.disc_width = 0;
+            self.try_break(self.ex_hyphen_penalty, true, Some(cur));
+            return;
+        }
+        self.disc_width = d.pre_break.iter().map(|n| width(n, self.fonts)).sum();
+        self.totals[0] += self.disc_width;
+        self.try_break(self.hyphen_penalty, true, Some(cur));
+        self.totals[0] -= self.disc_width;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::node::{GlueKind, Kern};
+    use crate::tex::state::TexState;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+    use std::rc::Rc;
+
+    fn pt(n: i32) -> Dimen {
+        Dimen::from_pt(n)
+    }
+
+    fn word(width: i32) -> Node {
+        Node::HList(BoxNode {
+            width: pt(width),
+            ..Default::default()
+        })
+    }
+
+    fn space(stretch: i32) -> Node {
+        Node::new_glue(Glue {
+            width: pt(5),
+            stretch: pt(stretch),
+            shrink: pt(1),
+            ..Default::default()
+        })
+    }
+
+    fn par_end() -> [Node; 2] {
+        [
+            Node::Penalty(INF_PENALTY),
+            Node::new_param_glue(
+                GlueParam::ParFillSkip,
+                Glue {
+                    stretch: pt(1),
+                    stretch_order: GlueOrder::Fil,
+                    ..Default::default()
+                },
+            ),
+        ]
+    }
+
+    /// Returns a paragraph of words of the provided widths separated by spaces, ending as
+    /// paragraphs do.
+    fn paragraph(words: &[i32], stretch: i32) -> Vec<Node> {
+        let mut list = Vec::new();
+        for (i, width) in words.iter().enumerate() {
+            if i > 0 {
+                list.push(space(stretch));
+            }
+            list.push(word(*width));
+        }
+        list.extend(par_end());
+        list
+    }
+
+    fn right_skip() -> Node {
+        Node::new_param_glue(GlueParam::RightSkip, Glue::default())
+    }
+
+    fn new_state(hsize: i32) -> TestState {
+        let mut s = testutil::new_state();
+        s.base_mut()
+            .parameters
+            .set_dimen(DimenParam::HSize, pt(hsize), false);
+        s
+    }
+
+    /// Breaks the list into lines with a widow penalty of 100, and returns the vertical list.
+    fn break_lines(s: &mut TestState, list: Vec<Node>) -> Vec<Node> {
+        line_break(s.base_mut(), list, Language::default(), 100).unwrap();
+        std::mem::take(&mut s.base_mut().nest.current_mut().list)
+    }
+
+    fn lines(s: &mut TestState, list: Vec<Node>) -> Vec<BoxNode> {
+        break_lines(s, list)
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::HList(b) => Some(b),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn optimum_fit() {
+        let mut s = new_state(25);
+        let lines = lines(&mut s, paragraph(&[10, 10, 10, 10], 1));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0].list,
+            vec![word(10), space(1), word(10), right_skip()]
+        );
+        let mut second = vec![word(10), space(1), word(10)];
+        second.extend(par_end());
+        second.push(right_skip());
+        assert_eq!(lines[1].list, second);
+        for line in &lines {
+            assert_eq!(line.width, pt(25));
+        }
+        assert_eq!(s.base().nest.current().prev_graf, 2);
+    }
+
+    #[test]
+    fn tracing_paragraphs() {
+        let mut s = new_state(25);
+        let parameters = &mut s.base_mut().parameters;
+        parameters.set_int(IntParam::LinePenalty, 10, false);
+        parameters.set_int(IntParam::TracingParagraphs, 1, false);
+        parameters.set_int(IntParam::TracingOnline, 1, false);
+        break_lines(&mut s, paragraph(&[10, 10, 10, 10], 1));
+        let expected = "@firstpass\n\
+            [] [] \n\
+            @ via @@0 b=0 p=0 d=100\n\
+            @@1: line 1.2 t=100 -> @@0\n\
+            [] [] \n\
+            @\\par via @@1 b=0 p=-10000 d=100\n\
+            @@2: line 2.2- t=200 -> @@1\n";
+        let terminal = testutil::terminal(&s);
+        assert!(terminal.contains(expected), "{}", terminal);
+    }
+
+    #[test]
+    fn second_pass() {
+        let mut s = new_state(30);
+        let parameters = &mut s.base_mut().parameters;
+        parameters.set_int(IntParam::Tolerance, 100, false);
+        parameters.set_int(IntParam::TracingParagraphs, 1, false);
+        parameters.set_int(IntParam::TracingOnline, 1, false);
+        let lines = lines(&mut s, paragraph(&[10, 10, 10, 10], 5));
+        assert_eq!(lines.len(), 2);
+        let terminal = testutil::terminal(&s);
+        assert!(terminal.contains("@secondpass"), "{}", terminal);
+        assert!(terminal.contains("b=100"), "{}", terminal);
+        assert!(!terminal.contains("Underfull"), "{}", terminal);
+    }
+
+    #[test]
+    fn no_first_pass_if_pretolerance_is_negative() {
+        let mut s = new_state(25);
+        let parameters = &mut s.base_mut().parameters;
+        parameters.set_int(IntParam::PreTolerance, -1, false);
+        parameters.set_int(IntParam::TracingParagraphs, 1, false);
+        parameters.set_int(IntParam::TracingOnline, 1, false);
+        assert_eq!(lines(&mut s, paragraph(&[10, 10, 10, 10], 1)).len(), 2);
+        let terminal = testutil::terminal(&s);
+        assert!(!terminal.contains("pass"), "{}", terminal);
+    }
+
+    #[test]
+    fn final_pass_forces_a_break() {
+        let mut s = new_state(30);
+        s.base_mut()
+            .parameters
+            .set_int(IntParam::Tolerance, 99, false);
+        let lines = lines(&mut s, paragraph(&[10, 10, 10, 10], 5));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].list.len(), 6);
+        let terminal = testutil::terminal(&s);
+        assert!(
+            terminal.contains("Overfull \\hbox (8.0pt too wide) in paragraph"),
+            "{}",
+            terminal
+        );
+    }
+
+    #[test]
+    fn emergency_stretch() {
+        let mut s = new_state(30);
+        let parameters = &mut s.base_mut().parameters;
+        parameters.set_int(IntParam::Tolerance, 99, false);
+        parameters.set_dimen(DimenParam::EmergencyStretch, pt(5), false);
+        parameters.set_int(IntParam::TracingParagraphs, 1, false);
+        parameters.set_int(IntParam::TracingOnline, 1, false);
+        let lines = lines(&mut s, paragraph(&[10, 10, 10, 10], 5));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].list.len(), 4);
+        let terminal = testutil::terminal(&s);
+        assert!(terminal.contains("@emergencypass"), "{}", terminal);
+        assert!(terminal.contains("b=12"), "{}", terminal);
+    }
+
+    #[test]
+    fn looseness() {
+        for (looseness, expected) in [(0, 2), (1, 3), (2, 4), (-1, 2)] {
+            let mut s = new_state(25);
+            s.base_mut()
+                .parameters
+                .set_int(IntParam::Looseness, looseness, false);
+            let lines = lines(&mut s, paragraph(&[10, 10, 10, 10], 20));
+            assert_eq!(lines.len(), expected, "looseness {}", looseness);
+        }
+    }
+
+    #[test]
+    fn par_shape() {
+        let mut s = new_state(100);
+        s.base_mut()
+            .parameters
+            .set_par_shape(Rc::new(vec![(pt(0), pt(25)), (pt(5), pt(25))]), false);
+        let lines = lines(&mut s, paragraph(&[10, 10, 10, 10, 10, 10], 1));
+        let shapes: Vec<(Dimen, Dimen)> = lines.iter().map(|b| (b.shift, b.width)).collect();
+        assert_eq!(
+            shapes,
+            vec![(pt(0), pt(25)), (pt(5), pt(25)), (pt(5), pt(25))]
+        );
+    }
+
+    #[test]
+    fn hanging_indentation() {
+        let shapes = |hang_indent: i32, hang_after: i32, words: &[i32]| {
+            let mut s = new_state(30);
+            let parameters = &mut s.base_mut().parameters;
+            parameters.set_dimen(DimenParam::HangIndent, pt(hang_indent), false);
+            parameters.set_int(IntParam::HangAfter, hang_after, false);
+            lines(&mut s, paragraph(words, 1))
+                .iter()
+                .map(|b| (b.shift, b.width))
+                .collect::<Vec<(Dimen, Dimen)>>()
+        };
+        assert_eq!(
+            shapes(5, 1, &[10, 15, 10, 10]),
+            vec![(pt(0), pt(30)), (pt(5), pt(25))]
+        );
+        assert_eq!(
+            shapes(-5, -1, &[10, 10, 10, 15]),
+            vec![(pt(0), pt(25)), (pt(0), pt(30))]
+        );
+    }
+
+    #[test]
+    fn interline_penalties() {
+        let mut s = new_state(25);
+        let parameters = &mut s.base_mut().parameters;
+        parameters.set_int(IntParam::InterLinePenalty, 1, false);
+        parameters.set_int(IntParam::ClubPenalty, 10, false);
+        let penalties: Vec<Node> = break_lines(&mut s, paragraph(&[10, 10, 10, 10, 10, 10], 1))
+            .into_iter()
+            .filter(|node| matches!(node, Node::Penalty(_)))
+            .collect();
+        assert_eq!(penalties, vec![Node::Penalty(11), Node::Penalty(101)]);
+    }
+
+    #[test]
+    fn discretionary_break() {
+        let mut s = new_state(15);
+        s.base_mut()
+            .parameters
+            .set_int(IntParam::BrokenPenalty, 7, false);
+        let mut list = vec![
+            word(10),
+            Node::Discretionary(Discretionary {
+                pre_break: vec![word(5)],
+                post_break: vec![word(6)],
+                replace_count: 1,
+            }),
+            word(2),
+            word(4),
+        ];
+        list.extend(par_end());
+        let list = break_lines(&mut s, list);
+        let empty = Node::Discretionary(Discretionary {
+            pre_break: vec![],
+            post_break: vec![],
+            replace_count: 0,
+        });
+        assert_eq!(
+            list[0].as_box().unwrap().list,
+            vec![word(10), empty, word(5), right_skip()]
+        );
+        // The widow penalty applies as well, since the second line is the last.
+        assert_eq!(list[1], Node::Penalty(107));
+        let mut second = vec![word(6), word(4)];
+        second.extend(par_end());
+        second.push(right_skip());
+        // The second line is preceded by interline glue.
+        assert_eq!(list[3].as_box().unwrap().list, second);
+    }
+
+    #[test]
+    fn kern_break_and_discarded_glue() {
+        let mut s = new_state(10);
+        let kern = |width| {
+            Node::Kern(Kern {
+                width,
+                kind: KernKind::Explicit,
+            })
+        };
+        let mut list = vec![word(10), kern(pt(3)), space(0), word(10)];
+        list.extend(par_end());
+        let lines = lines(&mut s, list);
+        assert_eq!(lines[0].list, vec![word(10), kern(Dimen(0)), right_skip()]);
+        assert_eq!(lines[1].list[0], word(10));
+    }
+
+    #[test]
+    fn left_and_right_skip() {
+        let mut s = new_state(30);
+        let parameters = &mut s.base_mut().parameters;
+        let skip = |n| Glue {
+            width: pt(n),
+            ..Default::default()
+        };
+        parameters.set_glue(GlueParam::LeftSkip, skip(2), false);
+        parameters.set_glue(GlueParam::RightSkip, skip(3), false);
+        let lines = lines(&mut s, paragraph(&[10, 10, 10, 10], 1));
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            let kind = |node: &Node| match node {
+                Node::Glue(g) => Some(g.kind),
+                _ => None,
+            };
+            assert_eq!(
+                kind(&line.list[0]),
+                Some(GlueKind::Param(GlueParam::LeftSkip))
+            );
+            assert_eq!(
+                kind(line.list.last().unwrap()),
+                Some(GlueKind::Param(GlueParam::RightSkip))
+            );
+        }
+    }
+
+    #[test]
+    fn adjust_material_follows_its_line() {
+        let mut s = new_state(25);
+        let mut list = paragraph(&[10, 10, 10, 10], 1);
+        list.insert(1, Node::Adjust(vec![Node::Penalty(5)]));
+        let list = break_lines(&mut s, list);
+        assert!(list[0].as_box().is_some());
+        assert_eq!(list[1], Node::Penalty(5));
+    }
+
+    #[test]
+    fn infinite_shrinkage_is_an_error() {
+        let mut s = new_state(25);
+        let mut list = paragraph(&[10, 10], 1);
+        list[1] = Node::new_glue(Glue {
+            shrink: pt(1),
+            shrink_order: GlueOrder::Fil,
+            ..Default::default()
+        });
+        let err = line_break(s.base_mut(), list, Language::default(), 0).unwrap_err();
+        assert!(err.to_string().contains("Infinite glue shrinkage"));
+    }
+}
@@ -0,0 +1,428 @@
+//! User-defined macros.
+//!
+//! A TeX macro consists of a parameter text and a replacement text. For the definition
+//! ```tex
+//! \def\a x#1y#2{(#2, #1)}
+//! ```
+//! the parameter text is `x#1y#2` and the replacement text is `(#2, #1)`. When `\a` is
+//! expanded, the tokens following it are matched against the parameter text: first the
+//! prefix `x` must appear, then the argument `#1` is read up to the delimiter `y`, and finally
+//! the undelimited argument `#2` is read. The macro then expands to the replacement text with
+//! each parameter replaced by the corresponding argument.
+//!
+//! Macros are created by the `\def` family of primitives, and are expanded by the driver in the
+//! same way as expansion primitives.
+//!
+//! A macro defined with the `\long` prefix can take arguments containing `\par`. A macro defined
+//! with the `\outer` prefix cannot appear in the arguments of macros, in definitions and other
+//! token lists being read, or in the text skipped by conditionals.
+
+use crate::tex::error;
+use crate::tex::primitive;
+use crate::tex::primitive::library::the;
+use crate::tex::state::TexState;
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::stream;
+use crate::tex::token::token::{Token, Value};
+
+/// An element of the replacement text of a macro.
+#[derive(Debug, Clone)]
+pub enum Replacement {
+    Token(Token),
+    /// A reference to a parameter. The index is zero-based, so `#1` is `Parameter(0)`.
+    Parameter(usize),
+}
+
+/// A user-defined macro.
+#[derive(Debug)]
+pub struct Macro {
+    prefix: Vec<Token>,
+    parameters: Vec<Vec<Token>>,
+    replacement: Vec<Replacement>,
+    long: bool,
+    outer: bool,
+}
+
+impl Macro {
+    /// Returns a new macro.
+    ///
+    /// The prefix consists of the tokens that must appear directly after the macro, before the
+    /// first argument. The parameters vector contains, for each parameter, the tokens that
+    /// delimit the corresponding argument; an empty delimiter denotes an undelimited parameter.
+    /// The macro is long or outer if it was defined with the `\long` or `\outer` prefix.
+    pub fn new(
+        prefix: Vec<Token>,
+        parameters: Vec<Vec<Token>>,
+        replacement: Vec<Replacement>,
+        long: bool,
+        outer: bool,
+    ) -> Macro {
+        Macro {
+            prefix,
+            parameters,
+            replacement,
+            long,
+            outer,
+        }
+    }
+
+    pub fn prefix(&self) -> &[Token] {
+        &self.prefix
+    }
+
+    pub fn parameters(&self) -> &[Vec<Token>] {
+        &self.parameters
+    }
+
+    pub fn replacement(&self) -> &[Replacement] {
+        &self.replacement
+    }
+
+    pub fn is_long(&self) -> bool {
+        self.long
+    }
+
+    pub fn is_outer(&self) -> bool {
+        self.outer
+    }
+
+    /// Reads the arguments of the macro from the input and returns the expanded replacement
+    /// text. The token is the token that invoked the macro, and is used for error messages.
+    pub fn call<S: TexState<S>>(
+        &self,
+        token: &Token,
+        input: &mut primitive::Input<S>,
+    ) -> anyhow::Result<stream::VecStream> {
+        for expected in &self.prefix {
+            match next_token(token, input, self.long)? {
+                Some(actual) if actual.value == expected.value => {}
+                Some(actual) => {
+                    return Err(error::new_token_error(
+                        actual,
+                        format!("Use of {} doesn't match its definition", describe(token)),
+                        vec![format!("expected {} to appear here", describe(expected))],
+                    ))
+                }
+                None => return Err(end_of_input_error(token)),
+            }
+        }
+        let mut arguments: Vec<Vec<Token>> = Vec::with_capacity(self.parameters.len());
+        for delimiter in &self.parameters {
+            let argument = match delimiter.is_empty() {
+                true => read_undelimited_argument(token, input, self.long)?,
+                false => read_delimited_argument(token, input, self.long, delimiter)?,
+            };
+            arguments.push(argument);
+        }
+        let mut result = Vec::with_capacity(self.replacement.len());
+        for replacement in &self.replacement {
+            match replacement {
+                Replacement::Token(t) => result.push(t.clone()),
+                Replacement::Parameter(i) => result.extend(arguments[*i].iter().cloned()),
+            }
+        }
+        Ok(stream::VecStream::new(result))
+    }
+
+    /// Returns true if the two macros have the same prefixes and the same parameter and
+    /// replacement texts.
+    ///
+    /// This is the notion of equality used by `\ifx`. The sources of the tokens are ignored.
+    pub fn same_as(&self, other: &Macro) -> bool {
+        fn tokens_eq(a: &[Token], b: &[Token]) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.value == b.value)
+        }
+        self.long == other.long
+            && self.outer == other.outer
+            && tokens_eq(&self.prefix, &other.prefix)
+            && self.parameters.len() == other.parameters.len()
+            && self
+                .parameters
+                .iter()
+                .zip(&other.parameters)
+                .all(|(a, b)| tokens_eq(a, b))
+            && self.replacement.len() == other.replacement.len()
+            && self
+                .replacement
+                .iter()
+                .zip(&other.replacement)
+                .all(|(a, b)| match (a, b) {
+                    (Replacement::Token(a), Replacement::Token(b)) => a.value == b.value,
+                    (Replacement::Parameter(a), Replacement::Parameter(b)) => a == b,
+                    _ => false,
+                })
+    }
+}
+
+/// Reads the next token of the arguments of a macro, which cannot be an outer macro, or `\par`
+/// unless the macro is long.
+fn next_token<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+    long: bool,
+) -> anyhow::Result<Option<Token>> {
+    let next = match input.unexpanded_stream().next()? {
+        None => return Ok(None),
+        Some(next) => next,
+    };
+    check_outer(input, &next, &format!("use of {}", describe(token)))?;
+    if !long && matches!(&next.value, Value::ControlSequence(_, name) if name == "par") {
+        return Err(error::new_token_error(
+            next,
+            format!("Paragraph ended before {} was complete", describe(token)),
+            vec!["a macro that is not \\long cannot take \\par in its arguments".to_string()],
+        ));
+    }
+    Ok(Some(next))
+}
+
+fn read_undelimited_argument<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+    long: bool,
+) -> anyhow::Result<Vec<Token>> {
+    loop {
+        let next = match next_token(token, input, long)? {
+            None => return Err(end_of_input_error(token)),
+            Some(next) => next,
+        };
+        match next.value {
+            Value::Character(_, CatCode::Space) => continue,
+            Value::Character(_, CatCode::BeginGroup) => {
+                return read_balanced_group(token, input, long);
+            }
+            Value::Character(_, CatCode::EndGroup) => {
+                return Err(extra_end_group_error(token, next))
+            }
+            _ => return Ok(vec![next]),
+        }
+    }
+}
+
+/// Reads tokens up to and including the end group character that matches an already consumed
+/// begin group character, and returns the tokens in between.
+fn read_balanced_group<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+    long: bool,
+) -> anyhow::Result<Vec<Token>> {
+    let mut depth = 0_usize;
+    let mut result = Vec::new();
+    loop {
+        let next = match next_token(token, input, long)? {
+            None => return Err(end_of_input_error(token)),
+            Some(next) => next,
+        };
+        match next.value {
+            Value::Character(_, CatCode::BeginGroup) => depth += 1,
+            Value::Character(_, CatCode::EndGroup) => {
+                if depth == 0 {
+                    return Ok(result);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        result.push(next);
+    }
+}
+
+fn read_delimited_argument<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+    long: bool,
+    delimiter: &[Token],
+) -> anyhow::Result<Vec<Token>> {
+    let mut depth = 0_usize;
+    let mut result: Vec<Token> = Vec::new();
+    loop {
+        let next = match next_token(token, input, long)? {
+            None => return Err(end_of_input_error(token)),
+            Some(next) => next,
+        };
+        if depth == 0 {
+            // The delimiter may end with a begin group character (via the `#{` syntax), so
+            // we check for the delimiter before updating the depth.
+            result.push(next);
+            if ends_with(&result, delimiter) {
+                result.truncate(result.len() - delimiter.len());
+                return Ok(strip_braces(result));
+            }
+            let next = result.last().unwrap();
+            match next.value {
+                Value::Character(_, CatCode::BeginGroup) => depth += 1,
+                Value::Character(_, CatCode::EndGroup) => {
+                    let next = result.pop().unwrap();
+                    return Err(extra_end_group_error(token, next));
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match next.value {
+            Value::Character(_, CatCode::BeginGroup) => depth += 1,
+            Value::Character(_, CatCode::EndGroup) => depth -= 1,
+            _ => {}
+        }
+        result.push(next);
+    }
+}
+
+fn ends_with(tokens: &[Token], suffix: &[Token]) -> bool {
+    tokens.len() >= suffix.len()
+        && tokens[tokens.len() - suffix.len()..]
+            .iter()
+            .zip(suffix)
+            .all(|(a, b)| a.value == b.value)
+}
+
+/// Removes the outer braces from a delimited argument of the form `{...}`, provided the braces
+/// match each other.
+fn strip_braces(mut tokens: Vec<Token>) -> Vec<Token> {
+    if tokens.len() < 2 {
+        return tokens;
+    }
+    if !matches!(tokens[0].value, Value::Character(_, CatCode::BeginGroup)) {
+        return tokens;
+    }
+    let mut depth = 0_usize;
+    for (i, t) in tokens.iter().enumerate() {
+        match t.value {
+            Value::Character(_, CatCode::BeginGroup) => depth += 1,
+            Value::Character(_, CatCode::EndGroup) => {
+                depth -= 1;
+                if depth == 0 && i != tokens.len() - 1 {
+                    return tokens;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.pop();
+    tokens.remove(0);
+    tokens
+}
+
+/// Returns an error if the token is an outer macro, which is forbidden while reading the
+/// provided text, like `definition of \a`. This is `check_outer_validity` in TeX.
+pub fn check_outer<S: TexState<S>>(
+    input: &primitive::Input<S>,
+    token: &Token,
+    scanning: &str,
+) -> anyhow::Result<()> {
+    if !is_outer(input, token) {
+        return Ok(());
+    }
+    Err(error::new_token_error(
+        token.clone(),
+        format!(
+            "Forbidden control sequence found while scanning {}",
+            scanning
+        ),
+        vec!["an outer macro cannot appear here; is a } missing?".to_string()],
+    ))
+}
+
+/// Reads the next token while reading the provided text, and returns an error if it is an outer
+/// macro. If `expand` is true the token is read with expansion, and outer macros are found
+/// before they are expanded.
+///
+/// As in TeX, the tokens that `\the` produces in an expanded text are not expanded further, so
+/// `\edef\a{\the\toks0}` defines `\a` to be the contents of `\toks0`.
+pub fn next_not_outer<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    scanning: &str,
+    expand: bool,
+) -> anyhow::Result<Option<Token>> {
+    if expand {
+        loop {
+            if let Some(next) = input.unexpanded_stream().peek()?.cloned() {
+                check_outer(input, &next, scanning)?;
+                if is_the(input, &next) && !input.next_is_unexpandable()? {
+                    input.unexpanded_stream().consume()?;
+                    let tokens = the::the(input)?;
+                    input.push_unexpandable_list(tokens);
+                    continue;
+                }
+            }
+            if !input.expand_next()? {
+                break;
+            }
+        }
+    }
+    let next = input.unexpanded_stream().next()?;
+    if let Some(next) = &next {
+        check_outer(input, next, scanning)?;
+    }
+    Ok(next)
+}
+
+/// Returns true if the meaning of the token is `\the`.
+fn is_the<S: TexState<S>>(input: &primitive::Input<S>, token: &Token) -> bool {
+    match input.state().base().primitives.get(&token.value) {
+        Some(p) => the::is_the_primitive(p),
+        None => false,
+    }
+}
+
+/// Returns true if the meaning of the token is an outer macro.
+pub fn is_outer<S: TexState<S>>(input: &primitive::Input<S>, token: &Token) -> bool {
+    match &token.value {
+        Value::Character(_, cat_code) if *cat_code != CatCode::Active => false,
+        value => matches!(
+            input.state().base().primitives.get(value),
+            Some(primitive::Primitive::Macro(m)) if m.is_outer()
+        ),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match &token.value {
+        Value::ControlSequence(_, name) => format!("\\{}", name),
+        Value::Character(c, _) => c.to_string(),
+    }
+}
+
+fn end_of_input_error(token: &Token) -> anyhow::Error {
+    error::new_token_error(
+        token.clone(),
+        format!("File ended while scanning use of {}", describe(token)),
+        vec!["the arguments of this macro were not complete".to_string()],
+    )
+}
+
+fn extra_end_group_error(token: &Token, end_group: Token) -> anyhow::Error {
+    error::new_token_error(
+        end_group,
+        format!("Argument of {} has an extra }}", describe(token)),
+        vec!["an end group character cannot begin an argument".to_string()],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_braces_single_group() {
+        let tokens = vec![
+            Token::new_begin_group('{'),
+            Token::new_letter('a'),
+            Token::new_end_group('}'),
+        ];
+        assert_eq!(strip_braces(tokens), vec![Token::new_letter('a')]);
+    }
+
+    #[test]
+    fn strip_braces_two_groups() {
+        let tokens = vec![
+            Token::new_begin_group('{'),
+            Token::new_letter('a'),
+            Token::new_end_group('}'),
+            Token::new_begin_group('{'),
+            Token::new_letter('b'),
+            Token::new_end_group('}'),
+        ];
+        assert_eq!(strip_braces(tokens.clone()), tokens);
+    }
+}
@@ -1,8 +1,30 @@
 //! Implementation of TeX language parsing and execution.
 
+pub mod charcode;
+pub mod dimen;
 pub mod driver;
 pub mod error;
+pub mod files;
+pub mod filesystem;
+pub mod font;
+pub mod group;
+pub mod hyphenate;
+pub mod hyphenation;
 pub mod input;
+pub mod ligature;
+pub mod linebreak;
+pub mod macros;
+pub mod nest;
+pub mod node;
+pub mod pack;
+pub mod page;
+pub mod parameter;
+pub mod parse;
 pub mod primitive;
+pub mod print;
+pub mod register;
 pub mod state;
+#[cfg(test)]
+pub mod testutil;
+pub mod tfm;
 pub mod token;
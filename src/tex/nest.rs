@@ -0,0 +1,194 @@
+//! Modes and the semantic nest.
+//!
+//! At any time TeX is in one of six modes, which determine what happens to the material being
+//! typeset. The document starts in vertical mode, where boxes and glue are stacked vertically
+//! to make pages. A character in vertical mode starts a paragraph and switches to horizontal
+//! mode, in which material is placed side by side; `\par` ends the paragraph and returns to
+//! vertical mode. Math shift characters switch to math mode and back.
+//!
+//! Modes can be nested: `\vbox{...}` enters internal vertical mode, `\hbox{...}` enters
+//! restricted horizontal mode, and a `\vbox` inside a paragraph interrupts horizontal mode
+//! until the box is finished. The stack of modes, each with the list being built in it, is
+//! the semantic nest. The bottom of the nest is the outer vertical mode, whose list is the
+//! list of contributions to the page.
+
+use crate::tex::dimen::Dimen;
+use crate::tex::hyphenation::Language;
+use crate::tex::node::Node;
+
+/// The value of `\prevdepth` that suppresses interline glue before the next box.
+pub const IGNORE_DEPTH: Dimen = Dimen(-65536000);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mode {
+    /// The outer vertical mode, in which pages are built.
+    Vertical,
+    /// The vertical mode of a `\vbox` or `\vtop`.
+    InternalVertical,
+    /// The horizontal mode of a paragraph.
+    Horizontal,
+    /// The horizontal mode of an `\hbox`, in which lines are not broken.
+    RestrictedHorizontal,
+    /// The mode of a formula in a paragraph, entered using `$`.
+    Math,
+    /// The mode of a displayed formula, entered using `$$`.
+    DisplayMath,
+}
+
+impl Mode {
+    /// Returns the name of the mode, as used in TeX's error messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Vertical => "vertical mode",
+            Mode::InternalVertical => "internal vertical mode",
+            Mode::Horizontal => "horizontal mode",
+            Mode::RestrictedHorizontal => "restricted horizontal mode",
+            Mode::Math => "math mode",
+            Mode::DisplayMath => "display math mode",
+        }
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        matches!(self, Mode::Vertical | Mode::InternalVertical)
+    }
+
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, Mode::Horizontal | Mode::RestrictedHorizontal)
+    }
+
+    pub fn is_math(&self) -> bool {
+        matches!(self, Mode::Math | Mode::DisplayMath)
+    }
+}
+
+/// A level of the semantic nest: a mode and the list being built in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListState {
+    pub mode: Mode,
+    pub list: Vec<Node>,
+    /// The depth of the last box appended to a vertical list, which determines the interline
+    /// glue before the next box. This is `\prevdepth` in TeX.
+    pub prev_depth: Dimen,
+    /// The space factor in horizontal mode, which determines the width of interword glue.
+    /// This is `\spacefactor` in TeX.
+    pub space_factor: i32,
+    /// The number of lines in the most recent paragraph. This is `\prevgraf` in TeX.
+    pub prev_graf: i32,
+    /// The language of the text at the end of the list in horizontal mode, which changes when
+    /// a language whatsit is appended. This is `clang` in TeX.
+    pub language: u8,
+    /// The language and hyphenation minimums when the paragraph began or resumed after a
+    /// display, which are used to hyphenate it.
+    pub par_language: Language,
+}
+
+impl ListState {
+    /// Returns a new level of the nest with an empty list.
+    pub fn new(mode: Mode) -> ListState {
+        ListState {
+            mode,
+            list: Vec::new(),
+            prev_depth: IGNORE_DEPTH,
+            space_factor: 1000,
+            prev_graf: 0,
+            language: 0,
+            par_language: Language::default(),
+        }
+    }
+}
+
+/// The semantic nest.
+pub struct Nest {
+    levels: Vec<ListState>,
+}
+
+impl Nest {
+    /// Returns a nest containing only the outer vertical mode.
+    pub fn new() -> Nest {
+        Nest {
+            levels: vec![ListState::new(Mode::Vertical)],
+        }
+    }
+
+    /// Returns the current mode.
+    pub fn mode(&self) -> Mode {
+        self.current().mode
+    }
+
+    /// Returns the innermost level of the nest.
+    pub fn current(&self) -> &ListState {
+        // The outer level is never popped.
+        self.levels.last().unwrap()
+    }
+
+    pub fn current_mut(&mut self) -> &mut ListState {
+        self.levels.last_mut().unwrap()
+    }
+
+    /// Returns the levels of the nest, from the outermost to the innermost.
+    pub fn levels(&self) -> &[ListState] {
+        &self.levels
+    }
+
+    /// Enters a new mode with an empty list. This is `push_nest` in TeX.
+    pub fn push(&mut self, mode: Mode) {
+        let prev_graf = self.current().prev_graf;
+        let mut level = ListState::new(mode);
+        level.prev_graf = prev_graf;
+        self.levels.push(level);
+    }
+
+    /// Leaves the current mode and returns its level, or returns `None` if the current mode is
+    /// the outer vertical mode, which cannot be left.
+    pub fn pop(&mut self) -> Option<ListState> {
+        match self.levels.len() > 1 {
+            true => self.levels.pop(),
+            false => None,
+        }
+    }
+
+    /// Appends a node to the current list.
+    pub fn append(&mut self, node: Node) {
+        self.current_mut().list.push(node);
+    }
+
+    /// Returns the list of the outer vertical mode, which holds the contributions that the page
+    /// builder has not yet moved to the current page.
+    pub fn contributions_mut(&mut self) -> &mut Vec<Node> {
+        &mut self.levels[0].list
+    }
+
+    /// Returns the last node of the current list.
+    pub fn tail(&self) -> Option<&Node> {
+        self.current().list.last()
+    }
+}
+
+impl Default for Nest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_and_pop() {
+        let mut nest = Nest::new();
+        assert_eq!(nest.mode(), Mode::Vertical);
+        nest.append(Node::Penalty(1));
+        nest.current_mut().prev_graf = 3;
+        nest.push(Mode::Horizontal);
+        assert_eq!(nest.mode(), Mode::Horizontal);
+        assert_eq!(nest.tail(), None);
+        assert_eq!(nest.current().prev_graf, 3);
+        nest.append(Node::Penalty(2));
+        let level = nest.pop().unwrap();
+        assert_eq!(level.list, vec![Node::Penalty(2)]);
+        assert_eq!(nest.tail(), Some(&Node::Penalty(1)));
+        assert_eq!(nest.pop(), None);
+        assert_eq!(nest.levels().len(), 1);
+    }
+}
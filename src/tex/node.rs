@@ -0,0 +1,454 @@
+//! Nodes: the typeset material that TeX builds out of tokens.
+//!
+//! Executing tokens appends nodes to the list of the current mode: characters in horizontal
+//! mode become [Char] nodes, `\hskip` becomes a [Glue] node, `\hbox{...}` becomes a box node
+//! containing its own list, and so on. Lists are later packaged into boxes, broken into lines
+//! and broken into pages. The node types follow part 10 of TeX: The Program:
+//! ```
+//! # use texide::tex::dimen::{Dimen, Glue};
+//! # use texide::tex::node::{Char, FontId, Kern, Node};
+//! let list = vec![
+//!     Node::Char(Char { font: FontId(1), c: 'A' }),
+//!     Node::Kern(Kern::new(Dimen::from_pt(1))),
+//!     Node::new_glue(Glue::default()),
+//!     Node::Penalty(10000),
+//! ];
+//! assert!(list[1].is_discardable());
+//! ```
+//!
+//! TeX stores its lists as singly linked lists in a big array of memory words. Here a list is
+//! simply a vector of nodes: appending to the current list, inspecting and removing its last
+//! node (as `\lastskip` and `\unskip` do), and splitting a list at a breakpoint are all cheap
+//! operations on a vector. Nodes that contain lists, like boxes and discretionaries, own them.
+//!
+//! Like TeX, a discretionary refers to the nodes that follow it in the list: its replacement
+//! text is the next `replace_count` nodes, which are removed if the line is broken at the
+//! discretionary.
+
+use crate::tex::dimen::{Dimen, Glue, GlueOrder};
+use crate::tex::files::Whatsit;
+use crate::tex::font::Fonts;
+use crate::tex::parameter::GlueParam;
+use crate::tex::token::token::Token;
+use std::rc::Rc;
+
+/// A penalty this large or larger forbids a break; minus this value forces a break.
+pub const INF_PENALTY: i32 = 10000;
+
+/// Identifies a loaded font. Font 0 is the null font, `\nullfont`, which has no characters.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct FontId(pub usize);
+
+/// An item in a horizontal, vertical or math list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Char(Char),
+    Ligature(Ligature),
+    /// A box whose contents are a horizontal list, created for example by `\hbox`.
+    HList(BoxNode),
+    /// A box whose contents are a vertical list, created for example by `\vbox`.
+    VList(BoxNode),
+    Rule(Rule),
+    Insert(Insert),
+    Mark(Mark),
+    /// Material that `\vadjust` moves out of a paragraph into the enclosing vertical list.
+    Adjust(Vec<Node>),
+    Whatsit(Whatsit),
+    Math(Math),
+    Glue(GlueNode),
+    Kern(Kern),
+    Penalty(i32),
+    Discretionary(Discretionary),
+}
+
+impl Node {
+    /// Returns a glue node with the provided glue that is not a parameter or leaders.
+    pub fn new_glue(glue: Glue) -> Node {
+        Node::Glue(GlueNode {
+            glue,
+            kind: GlueKind::Normal,
+            leaders: None,
+        })
+    }
+
+    /// Returns a glue node for the glue of a parameter, like `\parskip` before a paragraph.
+    pub fn new_param_glue(param: GlueParam, glue: Glue) -> Node {
+        Node::Glue(GlueNode {
+            glue,
+            kind: GlueKind::Param(param),
+            leaders: None,
+        })
+    }
+
+    /// Returns true if the node is discarded after a line or page break: glue, kerns,
+    /// penalties and math nodes. These are also the nodes at which lines and pages can break.
+    pub fn is_discardable(&self) -> bool {
+        matches!(
+            self,
+            Node::Glue(_) | Node::Kern(_) | Node::Penalty(_) | Node::Math(_)
+        )
+    }
+
+    /// Returns true if the node is a character or a ligature.
+    pub fn is_char(&self) -> bool {
+        matches!(self, Node::Char(_) | Node::Ligature(_))
+    }
+
+    /// Returns the box contained in the node, if it is an hlist or vlist.
+    pub fn as_box(&self) -> Option<&BoxNode> {
+        match self {
+            Node::HList(b) | Node::VList(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_box_mut(&mut self) -> Option<&mut BoxNode> {
+        match self {
+            Node::HList(b) | Node::VList(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the short display of a list, which TeX prints in warnings about underfull and
+/// overfull boxes and when tracing paragraphs: characters are shown as themselves, boxes as
+/// `[]`, rules as `|`, glue as a space, and math nodes as `$`. This is `short_display` in TeX.
+///
+/// The font is the font of the last character displayed. When a character is in a different
+/// font, the identifier of its font is shown first, like `\tenrm a`, and the font is updated.
+pub fn short_display(list: &[Node], fonts: &Fonts, font: &mut FontId) -> String {
+    let mut s = String::new();
+    for node in list {
+        match node {
+            Node::Char(Char { font: f, c }) => {
+                show_font_change(&mut s, *f, fonts, font);
+                s.push(*c);
+            }
+            Node::Ligature(l) => {
+                show_font_change(&mut s, l.font, fonts, font);
+                s.extend(&l.original);
+            }
+            Node::HList(_)
+            | Node::VList(_)
+            | Node::Insert(_)
+            | Node::Mark(_)
+            | Node::Adjust(_)
+            | Node::Whatsit(_) => s.push_str("[]"),
+            Node::Rule(_) => s.push('|'),
+            Node::Glue(g) => {
+                if g.glue != Glue::default() {
+                    s.push(' ');
+                }
+            }
+            Node::Math(_) => s.push('$'),
+            Node::Discretionary(d) => {
+                s.push_str(&short_display(&d.pre_break, fonts, font));
+                s.push_str(&short_display(&d.post_break, fonts, font));
+            }
+            Node::Kern(_) | Node::Penalty(_) => {}
+        }
+    }
+    s
+}
+
+fn show_font_change(s: &mut String, f: FontId, fonts: &Fonts, font: &mut FontId) {
+    if f != *font {
+        s.push('\\');
+        s.push_str(&fonts.get(f).identifier);
+        s.push(' ');
+        *font = f;
+    }
+}
+
+/// A character in a font.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Char {
+    pub font: FontId,
+    pub c: char,
+}
+
+/// A ligature: a character of a font that replaces a sequence of characters.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Ligature {
+    pub font: FontId,
+    pub c: char,
+    /// The characters the ligature was formed from. These are used for hyphenation, and when
+    /// the ligature is displayed.
+    pub original: Vec<char>,
+    /// Whether the ligature was formed with the left boundary character of the font.
+    pub left_boundary: bool,
+    /// Whether the ligature was formed with the right boundary character of the font.
+    pub right_boundary: bool,
+}
+
+/// How the glue of a box was set when the box was packaged.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlueSet {
+    pub sign: GlueSign,
+    /// The order of infinity of the glue that is stretched or shrunk. Glue of lower orders keeps
+    /// its natural width.
+    pub order: GlueOrder,
+    /// The amount each unit of stretch or shrink of the given order is multiplied by.
+    pub ratio: f64,
+}
+
+impl Default for GlueSet {
+    fn default() -> Self {
+        GlueSet {
+            sign: GlueSign::Normal,
+            order: GlueOrder::Normal,
+            ratio: 0.0,
+        }
+    }
+}
+
+/// Whether the glue in a box is stretched, shrunk or neither.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum GlueSign {
+    #[default]
+    Normal,
+    Stretching,
+    Shrinking,
+}
+
+/// A box: a list together with its dimensions.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BoxNode {
+    pub width: Dimen,
+    pub height: Dimen,
+    pub depth: Dimen,
+    /// How far the box is shifted: down in a horizontal list, right in a vertical list.
+    pub shift: Dimen,
+    pub glue_set: GlueSet,
+    pub list: Vec<Node>,
+}
+
+impl BoxNode {
+    /// Returns an empty box with zero dimensions.
+    pub fn new() -> BoxNode {
+        Default::default()
+    }
+}
+
+/// A rule: a solid black rectangle. A dimension that is `None` is running; it extends to the
+/// size of the smallest box enclosing the rule.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Rule {
+    pub width: Option<Dimen>,
+    pub height: Option<Dimen>,
+    pub depth: Option<Dimen>,
+}
+
+/// Material that is inserted elsewhere on the page, created by `\insert`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insert {
+    /// The insertion class, between 0 and 254.
+    pub number: u8,
+    /// The natural height plus depth of the vertical list.
+    pub height: Dimen,
+    /// The value of `\splitmaxdepth` when the insert was created.
+    pub split_max_depth: Dimen,
+    /// The value of `\splittopskip` when the insert was created.
+    pub split_top_skip: Glue,
+    /// The value of `\floatingpenalty` when the insert was created.
+    pub float_cost: i32,
+    pub list: Vec<Node>,
+}
+
+/// A mark, created by `\mark` or e-TeX's `\marks`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mark {
+    /// The mark class; `\mark` creates marks of class 0.
+    pub class: i32,
+    pub tokens: Rc<Vec<Token>>,
+}
+
+/// The start or end of a formula in a horizontal list.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Math {
+    pub kind: MathKind,
+    /// The value of `\mathsurround` when the formula was completed.
+    pub width: Dimen,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MathKind {
+    Before,
+    After,
+}
+
+/// Glue in a list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlueNode {
+    pub glue: Glue,
+    pub kind: GlueKind,
+    /// If present, the space is filled with copies of a box or rule instead of being blank.
+    pub leaders: Option<Leaders>,
+}
+
+/// Where a glue node came from. This is used when showing lists and by the math routines.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum GlueKind {
+    /// Glue created by a glue command like `\hskip`, or by a space.
+    Normal,
+    /// Glue inserted automatically from a parameter, like `\baselineskip` between lines.
+    Param(GlueParam),
+    /// Glue created by `\nonscript`, which cancels the following glue or kern in script styles.
+    CondMath,
+    /// Glue created by `\mskip`, measured in math units.
+    MuGlue,
+}
+
+/// Leaders: a box or rule repeated to fill the space of a glue node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leaders {
+    pub kind: LeadersKind,
+    /// The box or rule that is repeated.
+    pub node: Box<Node>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LeadersKind {
+    /// `\leaders`: the boxes are aligned with the enclosing box.
+    Aligned,
+    /// `\cleaders`: the boxes are centered in the space.
+    Centered,
+    /// `\xleaders`: the leftover space is distributed between the boxes.
+    Expanded,
+}
+
+/// A kern: a fixed amount of space that is not a breakpoint unless followed by glue.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Kern {
+    pub width: Dimen,
+    pub kind: KernKind,
+}
+
+impl Kern {
+    /// Returns a kern created by `\kern`.
+    pub fn new(width: Dimen) -> Kern {
+        Kern {
+            width,
+            kind: KernKind::Explicit,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum KernKind {
+    /// A kern inserted from the font's kerning program.
+    Normal,
+    /// A kern created by `\kern`, or an italic correction created by `\/`.
+    Explicit,
+    /// A kern inserted by `\accent`.
+    Accent,
+    /// A kern created by `\mkern`, measured in math units.
+    Math,
+}
+
+/// A place where a line may be broken, created by `\discretionary`, `\-` or hyphenation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discretionary {
+    /// The material inserted at the end of the line if the line breaks here.
+    pub pre_break: Vec<Node>,
+    /// The material inserted at the start of the next line if the line breaks here.
+    pub post_break: Vec<Node>,
+    /// The number of nodes after this one in the list that are removed if the line breaks here.
+    pub replace_count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::font::Font;
+
+    #[test]
+    fn discardable_nodes() {
+        let discardable = [
+            Node::new_glue(Glue::default()),
+            Node::Kern(Kern::new(Dimen(1))),
+            Node::Penalty(0),
+            Node::Math(Math {
+                kind: MathKind::Before,
+                width: Dimen(0),
+            }),
+        ];
+        for node in &discardable {
+            assert!(node.is_discardable(), "{:?}", node);
+        }
+        let not_discardable = [
+            Node::Char(Char {
+                font: FontId(0),
+                c: 'a',
+            }),
+            Node::HList(BoxNode::new()),
+            Node::Rule(Rule::default()),
+            Node::Discretionary(Discretionary {
+                pre_break: vec![],
+                post_break: vec![],
+                replace_count: 0,
+            }),
+        ];
+        for node in &not_discardable {
+            assert!(!node.is_discardable(), "{:?}", node);
+        }
+    }
+
+    #[test]
+    fn short_display_of_list() {
+        let list = vec![
+            Node::HList(BoxNode::new()),
+            Node::Char(Char {
+                font: FontId(0),
+                c: 'a',
+            }),
+            Node::new_glue(Glue {
+                width: Dimen(1),
+                ..Default::default()
+            }),
+            Node::new_glue(Glue::default()),
+            Node::Rule(Rule::default()),
+            Node::Math(Math {
+                kind: MathKind::Before,
+                width: Dimen(0),
+            }),
+        ];
+        let mut font = FontId(0);
+        assert_eq!(short_display(&list, &Fonts::new(), &mut font), "[]a |$");
+    }
+
+    #[test]
+    fn short_display_shows_font_changes() {
+        let mut fonts = Fonts::new();
+        let mut font = Font::null();
+        font.identifier = "tenrm".to_string();
+        let id = fonts.add(font);
+        let c = |font, c| Node::Char(Char { font, c });
+        let list = vec![c(id, 'a'), c(id, 'b'), c(FontId(0), 'c'), c(id, 'd')];
+        let mut font = FontId(0);
+        assert_eq!(
+            short_display(&list, &fonts, &mut font),
+            "\\tenrm ab\\nullfont c\\tenrm d"
+        );
+        assert_eq!(font, id);
+    }
+
+    #[test]
+    fn boxes_own_their_lists() {
+        let inner = BoxNode {
+            width: Dimen::from_pt(2),
+            list: vec![Node::Penalty(5)],
+            ..Default::default()
+        };
+        let outer = Node::VList(BoxNode {
+            list: vec![Node::HList(inner.clone())],
+            ..Default::default()
+        });
+        let mut copy = outer.clone();
+        if let Node::VList(b) = &mut copy {
+            b.list.clear();
+        }
+        assert_eq!(outer.as_box().unwrap().list[0].as_box(), Some(&inner));
+        assert!(copy.as_box().unwrap().list.is_empty());
+    }
+}
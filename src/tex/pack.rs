@@ -0,0 +1,627 @@
+//! Packaging lists into boxes.
+//!
+//! A horizontal list is packaged into an hbox by placing its nodes side by side: the natural
+//! width of the box is the sum of the widths of the nodes, and the height and depth of the box
+//! are the largest height and depth of the nodes. A vertical list is packaged into a vbox by
+//! stacking its nodes. This is `hpack` and `vpack` in TeX.
+//!
+//! The box may be given a different size than its natural size, using `to` or `spread`:
+//! ```tex
+//! \hbox to 100pt{...}   % the box is 100pt wide
+//! \vbox spread 5pt{...} % the box is 5pt higher than its natural height
+//! ```
+//! Then the glue in the list is stretched or shrunk to fill the box. Only the glue of the
+//! highest order of infinity that is present is stretched or shrunk. How much finite glue had
+//! to stretch or shrink is measured by the badness of the box, and boxes that are too bad are
+//! reported as underfull or overfull depending on `\hbadness`, `\vbadness`, `\hfuzz` and
+//! `\vfuzz`.
+
+use crate::tex::dimen::{Dimen, Glue, GlueOrder};
+use crate::tex::font::{Fonts, NULL_FONT};
+use crate::tex::node::{self, BoxNode, Char, GlueSet, GlueSign, Ligature, Node, Rule};
+use crate::tex::parameter::{DimenParam, IntParam, Parameters};
+use crate::tex::print::Selector;
+use crate::tex::state::BaseState;
+
+/// The size of a box being packaged, specified using `to` or `spread`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Spec {
+    /// The box has exactly this size.
+    Exactly(Dimen),
+    /// The box is this much larger than its natural size.
+    Additional(Dimen),
+}
+
+impl Spec {
+    /// The natural size of the list.
+    pub const NATURAL: Spec = Spec::Additional(Dimen(0));
+
+    fn size(&self, natural: Dimen) -> Dimen {
+        match self {
+            Spec::Exactly(size) => *size,
+            Spec::Additional(extra) => Dimen(natural.0 + extra.0),
+        }
+    }
+}
+
+/// The largest badness, which is the badness of a box whose glue cannot stretch enough.
+pub const INF_BAD: i32 = 10000;
+
+/// The badness of an overfull box, whose glue cannot shrink enough.
+pub const OVERFULL_BAD: i32 = 1000000;
+
+/// Returns the badness of stretching or shrinking glue with total stretch or shrink `s` by the
+/// amount `t`. The badness is approximately `100(t/s)^3`, and is `INF_BAD` if the ratio is
+/// large. This is `badness` in TeX, which uses integer arithmetic so that the result is the
+/// same on every machine.
+pub fn badness(t: Dimen, s: Dimen) -> i32 {
+    let (t, s) = (t.0, s.0);
+    if t == 0 {
+        return 0;
+    }
+    if s <= 0 {
+        return INF_BAD;
+    }
+    // r is approximately alpha * t / s, where alpha^3 = 100 * 2^18.
+    let r = if t <= 7230584 {
+        (t * 297) / s
+    } else if s >= 1663497 {
+        t / (s / 297)
+    } else {
+        t
+    };
+    if r > 1290 {
+        // 1290^3 is about 2^31.
+        INF_BAD
+    } else {
+        (r * r * r + 0o400000) / 0o1000000
+    }
+}
+
+/// How a box differs from the size of its contents by too much.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ReportKind {
+    /// The glue stretched by more than is reasonable.
+    Underfull,
+    /// The glue stretched by a reasonable amount, but with badness above `\hbadness`.
+    Loose,
+    /// The glue shrunk with badness above `\hbadness`.
+    Tight,
+    /// The glue could not shrink enough; the contents stick out of the box.
+    Overfull,
+}
+
+/// A warning about the glue of a box, produced when the box was packaged.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Report {
+    pub kind: ReportKind,
+    pub vertical: bool,
+    pub badness: i32,
+    /// For an overfull box, how far the contents stick out.
+    pub excess: Dimen,
+}
+
+impl Report {
+    /// Returns the start of the warning, like `Underfull \hbox (badness 10000`. The caller
+    /// completes the message with where the box was made.
+    pub fn message(&self) -> String {
+        let box_name = match self.vertical {
+            true => "\\vbox",
+            false => "\\hbox",
+        };
+        match self.kind {
+            ReportKind::Overfull => format!(
+                "Overfull {} ({} too {}",
+                box_name,
+                self.excess,
+                match self.vertical {
+                    true => "high",
+                    false => "wide",
+                }
+            ),
+            kind => format!(
+                "{} {} (badness {}",
+                match kind {
+                    ReportKind::Underfull => "Underfull",
+                    ReportKind::Loose => "Loose",
+                    _ => "Tight",
+                },
+                box_name,
+                self.badness
+            ),
+        }
+    }
+}
+
+/// Prints a warning about a box to the terminal and log file. The context completes the first
+/// line of the warning, for example `detected at line 5`. The contents of an hbox are shown
+/// in short form on the second line.
+// TODO: show the box in full in the log file, as \showbox does, once boxes can be shown
+pub fn print_report<S>(base: &mut BaseState<S>, report: &Report, b: &BoxNode, context: &str) {
+    base.printer.print_ln(Selector::TerminalAndLog);
+    let text = format!("{}) {}", report.message(), context);
+    base.print_nl(Selector::TerminalAndLog, &text);
+    base.printer.print_ln(Selector::TerminalAndLog);
+    if !report.vertical {
+        let mut font = NULL_FONT;
+        let contents = node::short_display(&b.list, &base.fonts, &mut font);
+        base.print(Selector::TerminalAndLog, &contents);
+        base.printer.print_ln(Selector::TerminalAndLog);
+    }
+}
+
+/// The total stretch or shrink of the glue in a list, for each order of infinity.
+#[derive(Default)]
+struct Totals([Dimen; 4]);
+
+impl Totals {
+    fn add(&mut self, amount: Dimen, order: GlueOrder) {
+        let total = &mut self.0[order as usize];
+        *total = Dimen(total.0 + amount.0);
+    }
+
+    /// Returns the highest order of infinity with a nonzero total, and the total.
+    fn highest(&self) -> (GlueOrder, Dimen) {
+        for order in [GlueOrder::Filll, GlueOrder::Fill, GlueOrder::Fil] {
+            if self.0[order as usize].0 != 0 {
+                return (order, self.0[order as usize]);
+            }
+        }
+        (GlueOrder::Normal, self.0[GlueOrder::Normal as usize])
+    }
+
+    fn normal(&self) -> Dimen {
+        self.0[GlueOrder::Normal as usize]
+    }
+}
+
+fn add_glue(stretch: &mut Totals, shrink: &mut Totals, glue: &Glue) {
+    stretch.add(glue.stretch, glue.stretch_order);
+    shrink.add(glue.shrink, glue.shrink_order);
+}
+
+/// Packages a horizontal list into a box of the specified width, setting its glue, and returns
+/// the box along with its badness, which becomes the value of `\badness`, and a warning if the
+/// glue had to stretch or shrink too much. Characters and ligatures are measured using the
+/// metrics of their fonts.
+pub fn hpack(
+    list: Vec<Node>,
+    spec: Spec,
+    parameters: &Parameters,
+    fonts: &Fonts,
+) -> (BoxNode, i32, Option<Report>) {
+    let mut width = 0_i32;
+    let mut height = 0_i32;
+    let mut depth = 0_i32;
+    let mut stretch = Totals::default();
+    let mut shrink = Totals::default();
+    for node in &list {
+        let (w, h, d) = match node {
+            Node::Char(Char { font, c }) | Node::Ligature(Ligature { font, c, .. }) => {
+                match fonts.get(*font).char(*c) {
+                    None => (0, 0, 0),
+                    Some(metrics) => (metrics.width.0, metrics.height.0, metrics.depth.0),
+                }
+            }
+            Node::HList(b) | Node::VList(b) => {
+                (b.width.0, b.height.0 - b.shift.0, b.depth.0 + b.shift.0)
+            }
+            // A running height or depth never determines the size of the box.
+            Node::Rule(r) => (
+                r.width.map_or(0, |w| w.0),
+                r.height.map_or(0, |h| h.0),
+                r.depth.map_or(0, |d| d.0),
+            ),
+            Node::Glue(g) => {
+                add_glue(&mut stretch, &mut shrink, &g.glue);
+                let (h, d) = match g.leaders.as_ref() {
+                    None => (0, 0),
+                    Some(leaders) => leader_size(&leaders.node),
+                };
+                (g.glue.width.0, h, d)
+            }
+            Node::Kern(k) => (k.width.0, 0, 0),
+            Node::Math(m) => (m.width.0, 0, 0),
+            Node::Insert(_)
+            | Node::Mark(_)
+            | Node::Adjust(_)
+            | Node::Whatsit(_)
+            | Node::Penalty(_)
+            | Node::Discretionary(_) => (0, 0, 0),
+        };
+        width += w;
+        height = height.max(h);
+        depth = depth.max(d);
+    }
+    let natural = Dimen(width);
+    let mut b = BoxNode {
+        width: spec.size(natural),
+        height: Dimen(height),
+        depth: Dimen(depth),
+        list,
+        ..Default::default()
+    };
+    let excess = Dimen(b.width.0 - natural.0);
+    let (glue_set, badness, report) = set_glue(
+        excess,
+        &stretch,
+        &shrink,
+        !b.list.is_empty(),
+        false,
+        (
+            parameters.int(IntParam::HBadness),
+            parameters.dimen(DimenParam::HFuzz),
+        ),
+    );
+    b.glue_set = glue_set;
+    if let Some(Report {
+        kind: ReportKind::Overfull,
+        excess,
+        ..
+    }) = report
+    {
+        // The overfull rule marks the box in the output.
+        let overfull_rule = parameters.dimen(DimenParam::OverfullRule);
+        if overfull_rule.0 > 0 && excess > parameters.dimen(DimenParam::HFuzz) {
+            b.list.push(Node::Rule(Rule {
+                width: Some(overfull_rule),
+                height: None,
+                depth: None,
+            }));
+        }
+    }
+    (b, badness, report)
+}
+
+/// Packages a vertical list into a box of the specified height, setting its glue, and returns
+/// the box along with its badness and a warning if the glue had to stretch or shrink too much,
+/// as `hpack` does. If the depth of
+/// the box would exceed the maximum depth, the box is made deeper by the difference and its
+/// depth is the maximum depth. This is `vpackage` in TeX.
+pub fn vpack(
+    list: Vec<Node>,
+    spec: Spec,
+    max_depth: Dimen,
+    parameters: &Parameters,
+) -> (BoxNode, i32, Option<Report>) {
+    let mut width = 0_i32;
+    let mut height = 0_i32;
+    let mut depth = 0_i32;
+    let mut stretch = Totals::default();
+    let mut shrink = Totals::default();
+    for node in &list {
+        match node {
+            Node::HList(b) | Node::VList(b) => {
+                height += depth + b.height.0;
+                depth = b.depth.0;
+                width = width.max(b.width.0 + b.shift.0);
+            }
+            // A running width never determines the size of the box.
+            Node::Rule(r) => {
+                height += depth + r.height.map_or(0, |h| h.0);
+                depth = r.depth.map_or(0, |d| d.0);
+                width = width.max(r.width.map_or(0, |w| w.0));
+            }
+            Node::Glue(g) => {
+                height += depth + g.glue.width.0;
+                depth = 0;
+                add_glue(&mut stretch, &mut shrink, &g.glue);
+                if let Some(leaders) = &g.leaders {
+                    width = width.max(leader_width(&leaders.node));
+                }
+            }
+            Node::Kern(k) => {
+                height += depth + k.width.0;
+                depth = 0;
+            }
+            _ => {}
+        }
+    }
+    if depth > max_depth.0 {
+        height += depth - max_depth.0;
+        depth = max_depth.0;
+    }
+    let natural = Dimen(height);
+    let mut b = BoxNode {
+        width: Dimen(width),
+        height: spec.size(natural),
+        depth: Dimen(depth),
+        list,
+        ..Default::default()
+    };
+    let excess = Dimen(b.height.0 - natural.0);
+    let (glue_set, badness, report) = set_glue(
+        excess,
+        &stretch,
+        &shrink,
+        !b.list.is_empty(),
+        true,
+        (
+            parameters.int(IntParam::VBadness),
+            parameters.dimen(DimenParam::VFuzz),
+        ),
+    );
+    b.glue_set = glue_set;
+    (b, badness, report)
+}
+
+/// Returns how the glue of a box is set so that the contents fill the box, given the
+/// difference between the size of the box and the natural size of its contents, along with the
+/// badness of the box. Boxes with badness above the badness limit, and boxes whose contents
+/// stick out by more than the fuzz, are reported.
+///
+/// As in TeX, the badness is only computed for nonempty boxes whose glue has finite stretch or
+/// shrink, and is 0 otherwise. An overfull box has badness 1000000.
+fn set_glue(
+    excess: Dimen,
+    stretch: &Totals,
+    shrink: &Totals,
+    non_empty: bool,
+    vertical: bool,
+    (badness_limit, fuzz): (i32, Dimen),
+) -> (GlueSet, i32, Option<Report>) {
+    let mut glue_set = GlueSet::default();
+    let report = |kind, badness, excess| {
+        Some(Report {
+            kind,
+            vertical,
+            badness,
+            excess,
+        })
+    };
+    if excess.0 > 0 {
+        let (order, total) = stretch.highest();
+        glue_set.order = order;
+        if total.0 != 0 {
+            glue_set.sign = GlueSign::Stretching;
+            glue_set.ratio = excess.0 as f64 / total.0 as f64;
+        }
+        if order == GlueOrder::Normal && non_empty {
+            let badness = badness(excess, stretch.normal());
+            if badness > badness_limit {
+                let kind = match badness > 100 {
+                    true => ReportKind::Underfull,
+                    false => ReportKind::Loose,
+                };
+                return (glue_set, badness, report(kind, badness, Dimen(0)));
+            }
+            return (glue_set, badness, None);
+        }
+    } else if excess.0 < 0 {
+        let (order, total) = shrink.highest();
+        glue_set.order = order;
+        if total.0 != 0 {
+            glue_set.sign = GlueSign::Shrinking;
+            glue_set.ratio = -excess.0 as f64 / total.0 as f64;
+        }
+        if order == GlueOrder::Normal && non_empty {
+            if total.0 < -excess.0 {
+                // The glue shrinks as much as it can, but no further.
+                glue_set.ratio = 1.0;
+                let overfull = Dimen(-excess.0 - total.0);
+                if overfull > fuzz || badness_limit < 100 {
+                    return (
+                        glue_set,
+                        OVERFULL_BAD,
+                        report(ReportKind::Overfull, OVERFULL_BAD, overfull),
+                    );
+                }
+                return (glue_set, OVERFULL_BAD, None);
+            }
+            let badness = badness(Dimen(-excess.0), total);
+            if badness > badness_limit {
+                return (
+                    glue_set,
+                    badness,
+                    report(ReportKind::Tight, badness, Dimen(0)),
+                );
+            }
+            return (glue_set, badness, None);
+        }
+    }
+    (glue_set, 0, None)
+}
+
+/// Returns the height and depth of the box or rule of leaders in a horizontal list.
+fn leader_size(node: &Node) -> (i32, i32) {
+    match node {
+        Node::HList(b) | Node::VList(b) => (b.height.0, b.depth.0),
+        Node::Rule(r) => (r.height.map_or(0, |h| h.0), r.depth.map_or(0, |d| d.0)),
+        _ => (0, 0),
+    }
+}
+
+/// Returns the width of the box or rule of leaders in a vertical list.
+fn leader_width(node: &Node) -> i32 {
+    match node {
+        Node::HList(b) | Node::VList(b) => b.width.0,
+        Node::Rule(r) => r.width.map_or(0, |w| w.0),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::node::{Kern, Rule};
+
+    fn glue(width: i32, stretch: i32, shrink: i32) -> Node {
+        Node::new_glue(Glue {
+            width: Dimen::from_pt(width),
+            stretch: Dimen::from_pt(stretch),
+            shrink: Dimen::from_pt(shrink),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn natural_size() {
+        let inner = BoxNode {
+            width: Dimen::from_pt(3),
+            height: Dimen::from_pt(4),
+            depth: Dimen::from_pt(1),
+            shift: Dimen::from_pt(2),
+            ..Default::default()
+        };
+        let (b, _, report) = hpack(
+            vec![
+                Node::HList(inner),
+                glue(5, 0, 0),
+                Node::Kern(Kern::new(Dimen::from_pt(-1))),
+                Node::Rule(Rule {
+                    width: Some(Dimen::from_pt(1)),
+                    height: Some(Dimen::from_pt(1)),
+                    depth: None,
+                }),
+            ],
+            Spec::NATURAL,
+            &Parameters::new(),
+            &Fonts::new(),
+        );
+        assert_eq!(b.width, Dimen::from_pt(8));
+        assert_eq!(b.height, Dimen::from_pt(2));
+        assert_eq!(b.depth, Dimen::from_pt(3));
+        assert_eq!(b.list.len(), 4);
+        assert_eq!(b.glue_set, GlueSet::default());
+        assert_eq!(report, None);
+    }
+
+    #[test]
+    fn badness_values() {
+        assert_eq!(badness(Dimen(0), Dimen(0)), 0);
+        assert_eq!(badness(Dimen(1), Dimen(0)), INF_BAD);
+        assert_eq!(badness(Dimen::from_pt(1), Dimen::from_pt(1)), 100);
+        assert_eq!(badness(Dimen::from_pt(1), Dimen::from_pt(2)), 12);
+        assert_eq!(badness(Dimen::from_pt(10), Dimen::from_pt(1)), INF_BAD);
+    }
+
+    #[test]
+    fn stretching() {
+        let mut parameters = Parameters::new();
+        parameters.set_int(IntParam::HBadness, 1000, false);
+        let list = vec![glue(1, 2, 0), glue(1, 2, 0)];
+        let (b, _, report) = hpack(
+            list.clone(),
+            Spec::Exactly(Dimen::from_pt(4)),
+            &parameters,
+            &Fonts::new(),
+        );
+        assert_eq!(b.width, Dimen::from_pt(4));
+        assert_eq!(b.glue_set.sign, GlueSign::Stretching);
+        assert_eq!(b.glue_set.ratio, 0.5);
+        assert_eq!(report, None);
+        let (_, _, report) = hpack(
+            list,
+            Spec::Additional(Dimen::from_pt(10)),
+            &parameters,
+            &Fonts::new(),
+        );
+        assert_eq!(report.unwrap().message(), "Underfull \\hbox (badness 1558");
+    }
+
+    #[test]
+    fn infinite_glue_wins() {
+        let list = vec![
+            glue(1, 2, 0),
+            Node::new_glue(Glue {
+                stretch: Dimen::from_pt(1),
+                stretch_order: GlueOrder::Fil,
+                ..Default::default()
+            }),
+        ];
+        let (b, _, report) = hpack(
+            list,
+            Spec::Exactly(Dimen::from_pt(100)),
+            &Parameters::new(),
+            &Fonts::new(),
+        );
+        assert_eq!(b.glue_set.order, GlueOrder::Fil);
+        assert_eq!(b.glue_set.ratio, 99.0);
+        assert_eq!(report, None);
+    }
+
+    #[test]
+    fn shrinking() {
+        let mut parameters = Parameters::new();
+        parameters.set_int(IntParam::HBadness, 0, false);
+        let list = vec![glue(4, 0, 2)];
+        let (b, _, report) = hpack(
+            list.clone(),
+            Spec::Exactly(Dimen::from_pt(3)),
+            &parameters,
+            &Fonts::new(),
+        );
+        assert_eq!(b.glue_set.sign, GlueSign::Shrinking);
+        assert_eq!(b.glue_set.ratio, 0.5);
+        assert_eq!(report.unwrap().message(), "Tight \\hbox (badness 12");
+        let (b, _, report) = hpack(
+            list,
+            Spec::Exactly(Dimen::from_pt(1)),
+            &parameters,
+            &Fonts::new(),
+        );
+        assert_eq!(b.glue_set.ratio, 1.0);
+        assert_eq!(report.unwrap().message(), "Overfull \\hbox (1.0pt too wide");
+    }
+
+    #[test]
+    fn overfull_rule() {
+        let mut parameters = Parameters::new();
+        parameters.set_dimen(DimenParam::OverfullRule, Dimen::from_pt(5), false);
+        let (b, _, _) = hpack(
+            vec![glue(4, 0, 2)],
+            Spec::Exactly(Dimen::from_pt(1)),
+            &parameters,
+            &Fonts::new(),
+        );
+        assert_eq!(b.list.len(), 2);
+        // An overfull box within \hfuzz is only reported if \hbadness is less than 100.
+        parameters.set_dimen(DimenParam::HFuzz, Dimen::from_pt(2), false);
+        parameters.set_int(IntParam::HBadness, 100, false);
+        let (b, _, report) = hpack(
+            vec![glue(4, 0, 2)],
+            Spec::Exactly(Dimen::from_pt(1)),
+            &parameters,
+            &Fonts::new(),
+        );
+        assert_eq!(b.list.len(), 1);
+        assert_eq!(report, None);
+    }
+
+    #[test]
+    fn vertical() {
+        let line = |height, depth| {
+            Node::HList(BoxNode {
+                width: Dimen::from_pt(10),
+                height: Dimen::from_pt(height),
+                depth: Dimen::from_pt(depth),
+                ..Default::default()
+            })
+        };
+        let list = vec![line(7, 2), glue(3, 0, 0), line(7, 2)];
+        let (b, _, _) = vpack(
+            list.clone(),
+            Spec::NATURAL,
+            Dimen(crate::tex::dimen::MAX_DIMEN),
+            &Parameters::new(),
+        );
+        assert_eq!(b.width, Dimen::from_pt(10));
+        assert_eq!(b.height, Dimen::from_pt(19));
+        assert_eq!(b.depth, Dimen::from_pt(2));
+        let (b, _, report) = vpack(list, Spec::NATURAL, Dimen::from_pt(1), &Parameters::new());
+        assert_eq!(b.height, Dimen::from_pt(20));
+        assert_eq!(b.depth, Dimen::from_pt(1));
+        assert_eq!(report, None);
+    }
+
+    #[test]
+    fn underfull_vbox() {
+        let (_, _, report) = vpack(
+            vec![glue(1, 0, 0)],
+            Spec::Exactly(Dimen::from_pt(10)),
+            Dimen(0),
+            &Parameters::new(),
+        );
+        assert_eq!(report.unwrap().message(), "Underfull \\vbox (badness 10000");
+    }
+}
@@ -0,0 +1,1065 @@
+//! The page builder.
+//!
+//! Material appended to the outer vertical list is a contribution to the page. Whenever
+//! something has been contributed, the page builder moves the contributions to the current
+//! page, one node at a time. This is `build_page` in TeX. Glue, kerns and penalties at the top
+//! of a page are discarded, and the first box of the page is preceded by `\topskip` glue. The
+//! page is broken at the breakpoint of least cost, where the cost of a breakpoint grows with
+//! the badness of the page ending there and with the penalty at the break. A page has
+//! height `\vsize`, less the space taken by insertions, and its depth is at most `\maxdepth`:
+//! ```tex
+//! \vsize=8.9in \maxdepth=2pt \topskip=10pt
+//! ```
+//! As soon as the page is full, or a penalty of `-10000` or less is reached, the page is
+//! broken at the best breakpoint found so far and the material before it is packaged into
+//! `\box255`. Insertions are moved into their boxes, and the material after the break goes
+//! back to the contributions. If `\output` is not empty, it is then expanded in a group in
+//! internal vertical mode, and must use `\box255`. Otherwise the page is shipped out.
+//!
+//! Insertions of class `n`, made using `\insert n{...}`, go to `\box n`. Each insertion
+//! reduces the goal height of the page by its height scaled by `\count n` per thousand. The
+//! first insertion of a class on the page also reduces the goal by the height of `\box n` and
+//! by `\skip n`. At most `\dimen n` of insertions of class `n` go on one page, and an insertion
+//! that does not fit is split using the same algorithm that breaks pages. This is
+//! `vert_break` in TeX.
+//!
+//! Marks, made using `\mark{...}`, are remembered by the page builder: when the page is
+//! broken, `\topmark` is the last mark of the previous page, and `\firstmark` and `\botmark`
+//! are the first and last marks of the page. As in e-TeX, `\marks n{...}` makes a mark of
+//! class `n`, and each class has its own top, first and bottom marks.
+//!
+//! The current page is kept as a list of its own. In TeX the current page and the
+//! contributions are linked lists that nodes move between, and the best breakpoint is a
+//! pointer into the page. Here the best breakpoint is an index into the page list, and when
+//! the page is full the nodes after it are moved back to the front of the contributions.
+
+use crate::tex::dimen::{self, Dimen, Glue, GlueOrder, MAX_DIMEN};
+use crate::tex::driver;
+use crate::tex::error;
+use crate::tex::group::GroupType;
+use crate::tex::nest::Mode;
+use crate::tex::node::{BoxNode, Insert, Node, INF_PENALTY};
+use crate::tex::pack::{self, INF_BAD};
+use crate::tex::parameter::{DimenParam, GlueParam, IntParam, TokenListParam};
+use crate::tex::primitive::library::write;
+use crate::tex::print::{Selector, MAX_PRINT_LINE};
+use crate::tex::state::{BaseState, TexState};
+use crate::tex::token::stream::VecStream;
+use crate::tex::token::token::Token;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+type ExpandedStream<S> = driver::ExpandedStream<S>;
+
+/// A cost larger than the cost of any feasible breakpoint. This is `awful_bad` in TeX.
+const AWFUL_BAD: i32 = 0o7777777777;
+
+/// The cost of a breakpoint where the page is underfull or overfull. This is `deplorable`
+/// in TeX.
+const DEPLORABLE: i32 = 100000;
+
+/// The penalty that forces a break. This is `eject_penalty` in TeX.
+const EJECT_PENALTY: i32 = -INF_PENALTY;
+
+/// What the current page contains so far.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Contents {
+    /// The page is empty, or contains only marks and whatsits.
+    Empty,
+    /// The page contains insertions but no box or rule.
+    InsertsOnly,
+    /// The page contains a box or rule, after which glue, kerns and penalties are kept.
+    BoxThere,
+}
+
+/// The top, first and bottom marks of a class.
+#[derive(Debug, Default, Clone)]
+pub struct Marks {
+    pub top: Option<Rc<Vec<Token>>>,
+    pub first: Option<Rc<Vec<Token>>>,
+    pub bot: Option<Rc<Vec<Token>>>,
+}
+
+/// Which of the marks of a class to read.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MarkKind {
+    Top,
+    First,
+    Bot,
+}
+
+/// The insertions of a class on the current page. This is a `page_ins_node` in TeX.
+#[derive(Debug, Clone)]
+struct InsertClass {
+    number: u8,
+    /// The natural height plus depth of `\box n` and the insertions of the class on the page.
+    height: Dimen,
+    /// The index in the page list of the last insertion of the class.
+    last: Option<usize>,
+    /// The index in the page list of the last insertion of the class before the best break.
+    best: Option<usize>,
+    /// If an insertion of the class had to be split, the index in the page list of that
+    /// insertion and the index in its list of the split, which is `None` if the insertion is
+    /// split after its end.
+    split: Option<(usize, Option<usize>)>,
+}
+
+/// The current page and the state of the page builder.
+pub struct Page {
+    /// The nodes on the current page.
+    pub list: Vec<Node>,
+    pub contents: Contents,
+    /// The height the page should have. This is `\pagegoal`.
+    pub goal: Dimen,
+    /// The natural height of the page. This is `\pagetotal`.
+    pub total: Dimen,
+    /// The stretch of the glue on the page, for each order of infinity.
+    pub stretch: [Dimen; 4],
+    /// The finite shrink of the glue on the page. This is `\pageshrink`.
+    pub shrink: Dimen,
+    /// The depth of the page. This is `\pagedepth`.
+    pub depth: Dimen,
+    /// The largest depth the page can have. This is `\maxdepth` when the page began.
+    pub max_depth: Dimen,
+    /// The sum of the penalties of split insertions, and while the output routine is active,
+    /// the number of insertions held over. This is `\insertpenalties`.
+    pub insert_penalties: i32,
+    /// True while the output routine is running.
+    pub output_active: bool,
+    /// The number of times the output routine has been fired since the last page was shipped
+    /// out. This is `\deadcycles`.
+    pub dead_cycles: i32,
+    /// The glue, penalty or kern last moved from the contributions to the page, which are the
+    /// values of `\lastskip`, `\lastpenalty` and `\lastkern` when the contributions are empty.
+    /// The glue is `None` if the last node moved was not glue.
+    pub last_glue: Option<Glue>,
+    pub last_penalty: i32,
+    pub last_kern: Dimen,
+    least_cost: i32,
+    /// The index of the best breakpoint in the page list. If it is the length of the list,
+    /// the breakpoint is the first node of the contributions.
+    best_break: usize,
+    /// The goal height at the best breakpoint.
+    best_size: Dimen,
+    /// The insertion classes on the page, ordered by number.
+    inserts: Vec<InsertClass>,
+    marks: HashMap<i32, Marks>,
+}
+
+impl Page {
+    pub fn new() -> Page {
+        Page {
+            list: Vec::new(),
+            contents: Contents::Empty,
+            goal: Dimen(0),
+            total: Dimen(0),
+            stretch: [Dimen(0); 4],
+            shrink: Dimen(0),
+            depth: Dimen(0),
+            max_depth: Dimen(0),
+            insert_penalties: 0,
+            output_active: false,
+            dead_cycles: 0,
+            last_glue: None,
+            last_penalty: 0,
+            last_kern: Dimen(0),
+            least_cost: 0,
+            best_break: 0,
+            best_size: Dimen(0),
+            inserts: Vec::new(),
+            marks: HashMap::new(),
+        }
+    }
+
+    /// Returns one of the dimensions of the page in the order `\pagegoal`, `\pagetotal`,
+    /// `\pagestretch`, `\pagefilstretch`, `\pagefillstretch`, `\pagefilllstretch`,
+    /// `\pageshrink` and `\pagedepth`. This is `page_so_far` in TeX.
+    pub fn so_far(&self, i: usize) -> Dimen {
+        match i {
+            0 => self.goal,
+            1 => self.total,
+            2..=5 => self.stretch[i - 2],
+            6 => self.shrink,
+            _ => self.depth,
+        }
+    }
+
+    pub fn so_far_mut(&mut self, i: usize) -> &mut Dimen {
+        match i {
+            0 => &mut self.goal,
+            1 => &mut self.total,
+            2..=5 => &mut self.stretch[i - 2],
+            6 => &mut self.shrink,
+            _ => &mut self.depth,
+        }
+    }
+
+    /// Returns a mark of the provided class, or `None` if there is no such mark.
+    pub fn mark(&self, class: i32, kind: MarkKind) -> Option<&Rc<Vec<Token>>> {
+        let marks = self.marks.get(&class)?;
+        match kind {
+            MarkKind::Top => marks.top.as_ref(),
+            MarkKind::First => marks.first.as_ref(),
+            MarkKind::Bot => marks.bot.as_ref(),
+        }
+    }
+
+    /// Begins a new page with the provided goal height and maximum depth. This is
+    /// `freeze_page_specs` in TeX.
+    fn freeze(&mut self, contents: Contents, goal: Dimen, max_depth: Dimen) {
+        self.contents = contents;
+        self.goal = goal;
+        self.max_depth = max_depth;
+        self.total = Dimen(0);
+        self.stretch = [Dimen(0); 4];
+        self.shrink = Dimen(0);
+        self.depth = Dimen(0);
+        self.least_cost = AWFUL_BAD;
+    }
+
+    /// Returns the page total and the stretch and shrink of the page, as TeX prints them when
+    /// tracing. This is `print_totals` in TeX.
+    fn totals(&self) -> String {
+        let mut s = scaled(self.total);
+        let units = ["", "fil", "fill", "filll"];
+        for (stretch, unit) in self.stretch.iter().zip(units) {
+            if stretch.0 != 0 {
+                s.push_str(&format!(" plus {}{}", scaled(*stretch), unit));
+            }
+        }
+        if self.shrink.0 != 0 {
+            s.push_str(&format!(" minus {}", scaled(self.shrink)));
+        }
+        s
+    }
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn scaled(d: Dimen) -> String {
+    let mut s = String::new();
+    // Writing to a string does not fail.
+    dimen::write_scaled(&mut s, d.0).unwrap();
+    s
+}
+
+/// Prints a line of the output of `\tracingpages`.
+fn trace<S>(base: &mut BaseState<S>, line: &str) {
+    let selector = base.diagnostic_selector();
+    base.print_nl(selector, line);
+    base.print_nl(selector, "");
+}
+
+fn infinite_shrinkage_error(source: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Infinite glue shrinkage found {}: the page cannot be broken if glue can shrink by an arbitrary amount",
+        source
+    )
+}
+
+/// Moves the contributions to the current page, and fires up the output routine when the page
+/// is full. This is `build_page` in TeX.
+///
+/// Nothing happens while the output routine is active; its material is contributed when the
+/// routine ends.
+pub fn build_page<S: TexState<S>>(input: &mut ExpandedStream<S>) -> anyhow::Result<()> {
+    loop {
+        let base = input.state_mut().base_mut();
+        if base.page.output_active {
+            return Ok(());
+        }
+        let mut contributions: VecDeque<Node> =
+            std::mem::take(base.nest.contributions_mut()).into();
+        let result = contribute(base, &mut contributions);
+        *base.nest.contributions_mut() = contributions.into();
+        if !result? {
+            return Ok(());
+        }
+        fire_up(input)?;
+    }
+}
+
+/// Moves nodes from the contributions to the current page, and returns true if the page is
+/// full. The node at which the page became full stays in the contributions.
+fn contribute<S>(
+    base: &mut BaseState<S>,
+    contributions: &mut VecDeque<Node>,
+) -> anyhow::Result<bool> {
+    while let Some(node) = contributions.pop_front() {
+        let page = &mut base.page;
+        page.last_glue = None;
+        page.last_penalty = 0;
+        page.last_kern = Dimen(0);
+        match &node {
+            Node::Glue(g) => page.last_glue = Some(g.glue),
+            Node::Penalty(penalty) => page.last_penalty = *penalty,
+            Node::Kern(k) => page.last_kern = k.width,
+            _ => {}
+        }
+        let box_there = page.contents == Contents::BoxThere;
+        let penalty = match &node {
+            Node::HList(_) | Node::VList(_) | Node::Rule(_) => {
+                let (height, depth) = match &node {
+                    Node::Rule(r) => (r.height.unwrap_or_default(), r.depth.unwrap_or_default()),
+                    // The node is a box.
+                    node => {
+                        let b = node.as_box().unwrap();
+                        (b.height, b.depth)
+                    }
+                };
+                if !box_there {
+                    // The first box of the page is preceded by \topskip glue, which makes the
+                    // distance from the top of the page to its baseline \topskip.
+                    if page.contents == Contents::Empty {
+                        freeze_page_specs(base, Contents::BoxThere);
+                    } else {
+                        base.page.contents = Contents::BoxThere;
+                    }
+                    let top_skip = base.parameters.glue(GlueParam::TopSkip);
+                    let width = Dimen((top_skip.width.0 - height.0).max(0));
+                    contributions.push_front(node);
+                    contributions.push_front(Node::new_param_glue(
+                        GlueParam::TopSkip,
+                        Glue { width, ..top_skip },
+                    ));
+                    continue;
+                }
+                page.total = Dimen(page.total.0 + page.depth.0 + height.0);
+                page.depth = depth;
+                None
+            }
+            Node::Whatsit(_) | Node::Mark(_) => None,
+            Node::Insert(insert) => {
+                add_insert(base, insert)?;
+                None
+            }
+            // Discardable nodes at the top of the page are discarded.
+            Node::Glue(_) | Node::Kern(_) | Node::Penalty(_) if !box_there => continue,
+            Node::Glue(_) => match page.list.last() {
+                Some(last) if !last.is_discardable() => Some(0),
+                _ => None,
+            },
+            Node::Kern(_) => match contributions.front() {
+                // Whether the kern is a breakpoint is not known until the next node arrives.
+                None => {
+                    contributions.push_front(node);
+                    return Ok(false);
+                }
+                Some(Node::Glue(_)) => Some(0),
+                Some(_) => None,
+            },
+            Node::Penalty(penalty) => Some(*penalty),
+            // Other nodes do not appear in vertical lists.
+            _ => None,
+        };
+        if let Some(penalty) = penalty {
+            if penalty < INF_PENALTY && consider_break(base, penalty) {
+                contributions.push_front(node);
+                return Ok(true);
+            }
+        }
+        let page = &mut base.page;
+        match &node {
+            Node::Glue(g) => {
+                let glue = &g.glue;
+                let stretch = &mut page.stretch[glue.stretch_order as usize];
+                *stretch = Dimen(stretch.0 + glue.stretch.0);
+                page.shrink = Dimen(page.shrink.0 + glue.shrink.0);
+                if glue.shrink_order != GlueOrder::Normal && glue.shrink.0 != 0 {
+                    return Err(infinite_shrinkage_error("on the current page"));
+                }
+                page.total = Dimen(page.total.0 + page.depth.0 + glue.width.0);
+                page.depth = Dimen(0);
+            }
+            Node::Kern(k) => {
+                page.total = Dimen(page.total.0 + page.depth.0 + k.width.0);
+                page.depth = Dimen(0);
+            }
+            _ => {}
+        }
+        if page.depth > page.max_depth {
+            page.total = Dimen(page.total.0 + page.depth.0 - page.max_depth.0);
+            page.depth = page.max_depth;
+        }
+        page.list.push(node);
+    }
+    Ok(false)
+}
+
+/// Begins a new page using `\vsize` and `\maxdepth`.
+fn freeze_page_specs<S>(base: &mut BaseState<S>, contents: Contents) {
+    let goal = base.parameters.dimen(DimenParam::VSize);
+    let max_depth = base.parameters.dimen(DimenParam::MaxDepth);
+    base.page.freeze(contents, goal, max_depth);
+    if base.parameters.int(IntParam::TracingPages) > 0 {
+        let line = format!(
+            "%% goal height={}, max depth={}",
+            scaled(goal),
+            scaled(max_depth)
+        );
+        trace(base, &line);
+    }
+}
+
+/// Computes the cost of breaking the page at the next node, which is a breakpoint with the
+/// provided penalty, and records it if it is the best breakpoint so far. Returns true if the
+/// page should be broken at the best breakpoint.
+fn consider_break<S>(base: &mut BaseState<S>, penalty: i32) -> bool {
+    let page = &mut base.page;
+    let badness = if page.total < page.goal {
+        let fil_stretch = page.stretch[1..].iter().any(|s| s.0 != 0);
+        match fil_stretch {
+            true => 0,
+            false => pack::badness(
+                Dimen(page.goal.0 - page.total.0),
+                page.stretch[GlueOrder::Normal as usize],
+            ),
+        }
+    } else if page.total.0 - page.goal.0 > page.shrink.0 {
+        AWFUL_BAD
+    } else {
+        pack::badness(Dimen(page.total.0 - page.goal.0), page.shrink)
+    };
+    let mut cost = if badness < AWFUL_BAD {
+        if penalty <= EJECT_PENALTY {
+            penalty
+        } else if badness < INF_BAD {
+            badness + penalty + page.insert_penalties
+        } else {
+            DEPLORABLE
+        }
+    } else {
+        badness
+    };
+    if page.insert_penalties >= INF_PENALTY {
+        cost = AWFUL_BAD;
+    }
+    let best = cost <= page.least_cost;
+    if base.parameters.int(IntParam::TracingPages) > 0 {
+        let page = &base.page;
+        let star = |n: i32| match n == AWFUL_BAD {
+            true => "*".to_string(),
+            false => n.to_string(),
+        };
+        let line = format!(
+            "% t={} g={} b={} p={} c={}{}",
+            page.totals(),
+            scaled(page.goal),
+            star(badness),
+            penalty,
+            star(cost),
+            if best { "#" } else { "" }
+        );
+        trace(base, &line);
+    }
+    let page = &mut base.page;
+    if best {
+        page.best_break = page.list.len();
+        page.best_size = page.goal;
+        page.least_cost = cost;
+        for class in &mut page.inserts {
+            class.best = class.last;
+        }
+    }
+    cost == AWFUL_BAD || penalty <= EJECT_PENALTY
+}
+
+/// Returns an error if `\box n` is an hbox, which cannot hold insertions.
+fn check_insert_box<S>(base: &BaseState<S>, number: u8) -> anyhow::Result<()> {
+    match base.registers.box_register(number as usize) {
+        Some(Node::HList(_)) => Err(anyhow::anyhow!(
+            "Insertions can only be added to a vbox: \\box{} contains an \\hbox",
+            number
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Accounts for an insertion that is about to be appended to the page, splitting it if it does
+/// not fit.
+fn add_insert<S>(base: &mut BaseState<S>, insert: &Insert) -> anyhow::Result<()> {
+    if base.page.contents == Contents::Empty {
+        freeze_page_specs(base, Contents::InsertsOnly);
+    }
+    let number = insert.number;
+    let n = number as usize;
+    let i = match base
+        .page
+        .inserts
+        .binary_search_by_key(&number, |c| c.number)
+    {
+        Ok(i) => i,
+        Err(i) => {
+            // The first insertion of the class on the page.
+            check_insert_box(base, number)?;
+            let height = match base.registers.box_register(n).and_then(Node::as_box) {
+                None => Dimen(0),
+                Some(b) => Dimen(b.height.0 + b.depth.0),
+            };
+            let count = base.registers.count(n);
+            let skip = base.registers.skip(n);
+            let page = &mut base.page;
+            let h = x_over_thousand(height, count);
+            page.goal = Dimen(page.goal.0 - h.0 - skip.width.0);
+            let stretch = &mut page.stretch[skip.stretch_order as usize];
+            *stretch = Dimen(stretch.0 + skip.stretch.0);
+            page.shrink = Dimen(page.shrink.0 + skip.shrink.0);
+            if skip.shrink_order != GlueOrder::Normal && skip.shrink.0 != 0 {
+                return Err(infinite_shrinkage_error(&format!("in \\skip{}", n)));
+            }
+            page.inserts.insert(
+                i,
+                InsertClass {
+                    number,
+                    height,
+                    last: None,
+                    best: None,
+                    split: None,
+                },
+            );
+            i
+        }
+    };
+    let index = base.page.list.len();
+    if base.page.inserts[i].split.is_some() {
+        // Once a class has been split, later insertions of the class are held over.
+        base.page.insert_penalties += insert.float_cost;
+        return Ok(());
+    }
+    base.page.inserts[i].last = Some(index);
+    let count = base.registers.count(n);
+    let dimen = base.registers.dimen(n);
+    let page = &mut base.page;
+    let delta = page.goal.0 - page.total.0 - page.depth.0 + page.shrink.0;
+    let h = x_over_thousand(insert.height, count);
+    let class = &mut page.inserts[i];
+    if (h.0 <= 0 || h.0 <= delta) && insert.height.0 + class.height.0 <= dimen.0 {
+        page.goal = Dimen(page.goal.0 - h.0);
+        class.height = Dimen(class.height.0 + insert.height.0);
+        return Ok(());
+    }
+    // The insertion does not fit, and is split.
+    let mut w = if count <= 0 {
+        MAX_DIMEN
+    } else {
+        let w = page.goal.0 - page.total.0 - page.depth.0;
+        match count == 1000 {
+            true => w,
+            false => (w / count) * 1000,
+        }
+    };
+    w = w.min(dimen.0 - class.height.0);
+    let (split, best_height) = vert_break(&insert.list, Dimen(w), insert.split_max_depth)?;
+    class.height = Dimen(class.height.0 + best_height.0);
+    class.split = Some((index, split));
+    let penalty = match split {
+        None => EJECT_PENALTY,
+        Some(q) => match insert.list[q] {
+            Node::Penalty(penalty) => penalty,
+            _ => 0,
+        },
+    };
+    page.goal = Dimen(page.goal.0 - x_over_thousand(best_height, count).0);
+    page.insert_penalties += penalty;
+    if base.parameters.int(IntParam::TracingPages) > 0 {
+        let line = format!(
+            "% split{} to {},{} p={}",
+            number,
+            scaled(Dimen(w)),
+            scaled(best_height),
+            penalty
+        );
+        trace(base, &line);
+    }
+    Ok(())
+}
+
+/// Returns `d` scaled by `count` per thousand, as insertions of a class with `\count n` equal
+/// to `count` are.
+fn x_over_thousand(d: Dimen, count: i32) -> Dimen {
+    match count == 1000 {
+        true => d,
+        false => Dimen((d.0 / 1000) * count),
+    }
+}
+
+/// Finds the best place to break a vertical list so that the material before the break has
+/// height `h` and depth at most `max_depth`. Returns the index of the breakpoint, or `None` if
+/// the best break is at the end of the list, and the height plus depth of the material before
+/// the break. This is `vert_break` in TeX.
+pub fn vert_break(
+    list: &[Node],
+    h: Dimen,
+    max_depth: Dimen,
+) -> anyhow::Result<(Option<usize>, Dimen)> {
+    let mut least_cost = AWFUL_BAD;
+    let mut best = None;
+    let mut best_height_plus_depth = Dimen(0);
+    // The natural height, the stretch of each order and the shrink of the list so far.
+    let mut height = 0;
+    let mut stretch = [0; 4];
+    let mut shrink = 0;
+    let mut prev_depth = 0;
+    let mut prev_discardable = true;
+    for i in 0..=list.len() {
+        let node = list.get(i);
+        let penalty = match node {
+            None => Some(EJECT_PENALTY),
+            Some(Node::HList(b)) | Some(Node::VList(b)) => {
+                height += prev_depth + b.height.0;
+                prev_depth = b.depth.0;
+                None
+            }
+            Some(Node::Rule(r)) => {
+                height += prev_depth + r.height.unwrap_or_default().0;
+                prev_depth = r.depth.unwrap_or_default().0;
+                None
+            }
+            Some(Node::Glue(_)) if !prev_discardable => Some(0),
+            Some(Node::Kern(_)) => match list.get(i + 1) {
+                Some(Node::Glue(_)) => Some(0),
+                _ => None,
+            },
+            Some(Node::Penalty(penalty)) => Some(*penalty),
+            _ => None,
+        };
+        if let Some(penalty) = penalty {
+            if penalty < INF_PENALTY {
+                let badness = if height < h.0 {
+                    match stretch[1..].iter().any(|s| *s != 0) {
+                        true => 0,
+                        false => pack::badness(Dimen(h.0 - height), Dimen(stretch[0])),
+                    }
+                } else if height - h.0 > shrink {
+                    AWFUL_BAD
+                } else {
+                    pack::badness(Dimen(height - h.0), Dimen(shrink))
+                };
+                let cost = if badness < AWFUL_BAD {
+                    if penalty <= EJECT_PENALTY {
+                        penalty
+                    } else if badness < INF_BAD {
+                        badness + penalty
+                    } else {
+                        DEPLORABLE
+                    }
+                } else {
+                    badness
+                };
+                if cost <= least_cost {
+                    best = if i < list.len() { Some(i) } else { None };
+                    least_cost = cost;
+                    best_height_plus_depth = Dimen(height + prev_depth);
+                }
+                if cost == AWFUL_BAD || penalty <= EJECT_PENALTY {
+                    return Ok((best, best_height_plus_depth));
+                }
+            }
+        }
+        match node {
+            Some(Node::Glue(g)) => {
+                let glue = &g.glue;
+                stretch[glue.stretch_order as usize] += glue.stretch.0;
+                shrink += glue.shrink.0;
+                if glue.shrink_order != GlueOrder::Normal && glue.shrink.0 != 0 {
+                    return Err(infinite_shrinkage_error("in box being split"));
+                }
+                height += prev_depth + glue.width.0;
+                prev_depth = 0;
+            }
+            Some(Node::Kern(k)) => {
+                height += prev_depth + k.width.0;
+                prev_depth = 0;
+            }
+            _ => {}
+        }
+        if prev_depth > max_depth.0 {
+            height += prev_depth - max_depth.0;
+            prev_depth = max_depth.0;
+        }
+        prev_discardable = node.is_none_or(Node::is_discardable);
+    }
+    // The end of the list is a breakpoint with the eject penalty, so the loop always returns.
+    unreachable!()
+}
+
+/// Removes the glue, kerns and penalties at the top of a vertical list that remains after a
+/// split, and inserts glue before its first box so that the box's baseline is `split_top_skip`
+/// from the top. This is `prune_page_top` in TeX.
+pub fn prune_page_top(list: Vec<Node>, split_top_skip: Glue) -> Vec<Node> {
+    let mut pruned = Vec::with_capacity(list.len() + 1);
+    let mut list = list.into_iter();
+    for node in list.by_ref() {
+        match &node {
+            Node::HList(_) | Node::VList(_) | Node::Rule(_) => {
+                let height = match &node {
+                    Node::Rule(r) => r.height.unwrap_or_default(),
+                    node => node.as_box().unwrap().height,
+                };
+                let width = Dimen((split_top_skip.width.0 - height.0).max(0));
+                pruned.push(Node::new_param_glue(
+                    GlueParam::SplitTopSkip,
+                    Glue {
+                        width,
+                        ..split_top_skip
+                    },
+                ));
+                pruned.push(node);
+                break;
+            }
+            Node::Whatsit(_) | Node::Mark(_) | Node::Insert(_) => pruned.push(node),
+            _ => {}
+        }
+    }
+    pruned.extend(list);
+    pruned
+}
+
+/// Breaks the page at the best breakpoint, packages it into `\box255` and the insertions into
+/// their boxes, and then fires up the output routine or ships the page out. This is `fire_up`
+/// in TeX.
+fn fire_up<S: TexState<S>>(input: &mut ExpandedStream<S>) -> anyhow::Result<()> {
+    let base = input.state_mut().base_mut();
+    let page = &mut base.page;
+    let rest = page.list.split_off(page.best_break);
+    let contributions = base.nest.contributions_mut();
+    contributions.splice(0..0, rest);
+    // The break is now the first node of the contributions.
+    let output_penalty = match contributions.first_mut() {
+        Some(Node::Penalty(penalty)) => std::mem::replace(penalty, INF_PENALTY),
+        _ => INF_PENALTY,
+    };
+    base.parameters
+        .set_int(IntParam::OutputPenalty, output_penalty, true);
+    for marks in base.page.marks.values_mut() {
+        if let Some(bot) = &marks.bot {
+            marks.top = Some(bot.clone());
+            marks.first = None;
+        }
+    }
+    if base.registers.box_register(255).is_some() {
+        return Err(anyhow::anyhow!(
+            "\\box255 is not void: you shouldn't use \\box255 except in \\output routines"
+        ));
+    }
+    base.page.insert_penalties = 0;
+    let holding = base.parameters.int(IntParam::HoldingInserts) > 0;
+    // The material of each class that goes into its box on this page.
+    let mut queues: HashMap<u8, Vec<Node>> = HashMap::new();
+    if !holding {
+        let classes: Vec<u8> = base
+            .page
+            .inserts
+            .iter()
+            .filter(|class| class.best.is_some())
+            .map(|class| class.number)
+            .collect();
+        for number in classes {
+            check_insert_box(base, number)?;
+            let list = match base.registers.take_box(number as usize) {
+                Some(Node::VList(b)) => b.list,
+                _ => Vec::new(),
+            };
+            queues.insert(number, list);
+        }
+    }
+    let page = &mut base.page;
+    let list = std::mem::take(&mut page.list);
+    let mut kept = Vec::with_capacity(list.len());
+    let mut held = Vec::new();
+    for (i, node) in list.into_iter().enumerate() {
+        match node {
+            Node::Insert(mut insert) if !holding => {
+                // Every insertion on the page has a class.
+                let class = page
+                    .inserts
+                    .iter_mut()
+                    .find(|class| class.number == insert.number)
+                    .unwrap();
+                let mut wait = true;
+                if class.best.is_some() {
+                    wait = false;
+                    // The queue was prepared above.
+                    let queue = queues.get_mut(&insert.number).unwrap();
+                    if class.best == Some(i) {
+                        let mut material = std::mem::take(&mut insert.list);
+                        if let Some((index, Some(at))) = class.split {
+                            if index == i {
+                                let remainder = material.split_off(at);
+                                let remainder = prune_page_top(remainder, insert.split_top_skip);
+                                if !remainder.is_empty() {
+                                    let (b, badness, _) = pack::vpack(
+                                        remainder,
+                                        pack::Spec::NATURAL,
+                                        Dimen(MAX_DIMEN),
+                                        &base.parameters,
+                                    );
+                                    base.last_badness = badness;
+                                    insert.height = Dimen(b.height.0 + b.depth.0);
+                                    insert.list = b.list;
+                                    wait = true;
+                                }
+                            }
+                        }
+                        class.best = None;
+                        queue.extend(material);
+                        let (b, badness, _) = pack::vpack(
+                            std::mem::take(queue),
+                            pack::Spec::NATURAL,
+                            Dimen(MAX_DIMEN),
+                            &base.parameters,
+                        );
+                        base.last_badness = badness;
+                        base.registers
+                            .put_box(insert.number as usize, Node::VList(b));
+                    } else {
+                        queue.extend(std::mem::take(&mut insert.list));
+                    }
+                }
+                if wait {
+                    held.push(Node::Insert(insert));
+                    page.insert_penalties += 1;
+                }
+            }
+            Node::Mark(mark) => {
+                let marks = page.marks.entry(mark.class).or_default();
+                if marks.first.is_none() {
+                    marks.first = Some(mark.tokens.clone());
+                }
+                marks.bot = Some(mark.tokens.clone());
+                kept.push(Node::Mark(mark));
+            }
+            node => kept.push(node),
+        }
+    }
+    for marks in page.marks.values_mut() {
+        if marks.first.is_none() {
+            marks.first = marks.top.clone();
+        }
+    }
+    let (b, badness, _) = pack::vpack(
+        kept,
+        pack::Spec::Exactly(page.best_size),
+        page.max_depth,
+        &base.parameters,
+    );
+    base.last_badness = badness;
+    base.registers.put_box(255, Node::VList(b));
+    let page = &mut base.page;
+    page.list = held;
+    page.contents = Contents::Empty;
+    page.depth = Dimen(0);
+    page.max_depth = Dimen(0);
+    page.inserts.clear();
+    let output = base.parameters.token_list(TokenListParam::Output);
+    if !output.is_empty() {
+        let max_dead_cycles = base.parameters.int(IntParam::MaxDeadCycles);
+        if base.page.dead_cycles >= max_dead_cycles {
+            return Err(anyhow::anyhow!(
+                "Output loop---{} consecutive dead cycles: the \\output routine never does a \\shipout",
+                base.page.dead_cycles
+            ));
+        }
+        base.page.output_active = true;
+        base.page.dead_cycles += 1;
+        base.nest.push(Mode::InternalVertical);
+        base.begin_group(GroupType::Output);
+        driver::normal_paragraph(base);
+        let mut tokens = output.to_vec();
+        tokens.push(Token::new_end_group('}'));
+        input.push_output(VecStream::new(tokens));
+        return Ok(());
+    }
+    // Without an output routine, the page is shipped out and the held insertions return to the
+    // contributions.
+    let held = std::mem::take(&mut base.page.list);
+    base.nest.contributions_mut().splice(0..0, held);
+    // The box was just put in the register.
+    let b = base.registers.take_box(255).unwrap();
+    ship_out(input, b)
+}
+
+/// Finishes the output routine, after the group of the routine has ended. The material the
+/// routine produced, and the insertions held over, go back to the front of the contributions.
+/// This is `resume_output` in TeX.
+pub fn resume_output<S: TexState<S>>(
+    token: Token,
+    input: &mut ExpandedStream<S>,
+) -> anyhow::Result<()> {
+    // The group must be ended by the brace after the tokens of the routine.
+    if !input.at_end_of_output() {
+        return Err(error::new_token_error(
+            token,
+            "Unbalanced output routine".to_string(),
+            vec!["the output routine ended before the end of its tokens".to_string()],
+        ));
+    }
+    driver::end_paragraph(input.state_mut().base_mut())?;
+    driver::end_group(token, input, GroupType::Output)?;
+    let base = input.state_mut().base_mut();
+    base.page.output_active = false;
+    base.page.insert_penalties = 0;
+    if base.registers.box_register(255).is_some() {
+        return Err(anyhow::anyhow!(
+            "Output routine didn't use all of \\box255: your \\output commands should empty \\box255, e.g., by saying `\\shipout\\box255'"
+        ));
+    }
+    // The output routine runs in internal vertical mode, which is not the outer mode.
+    let level = base.nest.pop().unwrap();
+    let mut list = std::mem::take(&mut base.page.list);
+    list.extend(level.list);
+    base.nest.contributions_mut().splice(0..0, list);
+    build_page(input)
+}
+
+/// Returns true if the job can end at `\end`, which is when the page and the contributions are
+/// empty and no output routine has run since the last page was shipped out. Otherwise the
+/// `\end` token goes back to the input, and the page is forced out by appending an empty box as
+/// wide as `\hsize`, `\vfill` glue and a penalty that breaks the page. This is `its_all_over`
+/// in TeX.
+pub fn its_all_over<S: TexState<S>>(
+    token: Token,
+    input: &mut ExpandedStream<S>,
+) -> anyhow::Result<bool> {
+    let base = input.state_mut().base_mut();
+    if base.page.list.is_empty()
+        && base.nest.contributions_mut().is_empty()
+        && base.page.dead_cycles == 0
+    {
+        return Ok(true);
+    }
+    let mut b = BoxNode::new();
+    b.width = base.parameters.dimen(DimenParam::HSize);
+    let contributions = base.nest.contributions_mut();
+    contributions.push(Node::HList(b));
+    contributions.push(Node::new_glue(Glue {
+        stretch: Dimen(dimen::UNITY),
+        stretch_order: GlueOrder::Fill,
+        ..Default::default()
+    }));
+    contributions.push(Node::Penalty(-0o10000000000));
+    input.push(VecStream::new(vec![token]));
+    build_page(input)?;
+    Ok(false)
+}
+
+/// Ships out a page: prints the page numbers, which are the nonzero values among `\count0`
+/// to `\count9`, and performs the deferred whatsits on the page. This is `ship_out` in TeX.
+pub fn ship_out<S: TexState<S>>(input: &mut ExpandedStream<S>, b: Node) -> anyhow::Result<()> {
+    let base = input.state_mut().base_mut();
+    let selector = Selector::TerminalAndLog;
+    if base.printer.terminal_offset() > MAX_PRINT_LINE - 9 {
+        base.printer.print_ln(selector);
+    } else if base.printer.terminal_offset() > 0 || base.printer.log_offset() > 0 {
+        base.print(selector, " ");
+    }
+    let last = (1..10)
+        .rev()
+        .find(|i| base.registers.count(*i) != 0)
+        .unwrap_or(0);
+    let counts: Vec<String> = (0..=last)
+        .map(|i| base.registers.count(i).to_string())
+        .collect();
+    base.print(selector, &format!("[{}", counts.join(".")));
+    base.printer.flush();
+    // TODO: write the page to the DVI file
+    write::ship_out_whatsits(input, std::slice::from_ref(&b))?;
+    let base = input.state_mut().base_mut();
+    base.print(selector, "]");
+    base.printer.flush();
+    base.page.dead_cycles = 0;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::node::BoxNode;
+
+    fn vbox(height: i32, depth: i32) -> Node {
+        Node::VList(BoxNode {
+            height: Dimen::from_pt(height),
+            depth: Dimen::from_pt(depth),
+            ..Default::default()
+        })
+    }
+
+    fn glue(width: i32, stretch: i32) -> Node {
+        Node::new_glue(Glue {
+            width: Dimen::from_pt(width),
+            stretch: Dimen::from_pt(stretch),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn vert_break_at_best_glue() {
+        let list = vec![
+            vbox(10, 0),
+            glue(0, 5),
+            vbox(10, 0),
+            glue(0, 5),
+            vbox(10, 0),
+        ];
+        let (best, height) = vert_break(&list, Dimen::from_pt(22), Dimen(MAX_DIMEN)).unwrap();
+        assert_eq!(best, Some(3));
+        assert_eq!(height, Dimen::from_pt(20));
+    }
+
+    #[test]
+    fn vert_break_at_penalty() {
+        let list = vec![vbox(10, 0), Node::Penalty(-10000), vbox(10, 0)];
+        let (best, height) = vert_break(&list, Dimen::from_pt(100), Dimen(MAX_DIMEN)).unwrap();
+        assert_eq!(best, Some(1));
+        assert_eq!(height, Dimen::from_pt(10));
+    }
+
+    #[test]
+    fn vert_break_at_end_of_list() {
+        let list = vec![vbox(10, 2), glue(0, 5), vbox(10, 2)];
+        let (best, height) = vert_break(&list, Dimen::from_pt(30), Dimen::from_pt(1)).unwrap();
+        assert_eq!(best, None);
+        // The depth of the last box is limited to the maximum depth.
+        assert_eq!(height, Dimen::from_pt(24));
+    }
+
+    #[test]
+    fn vert_break_infinite_shrinkage() {
+        let list = vec![
+            vbox(10, 0),
+            Node::new_glue(Glue {
+                shrink: Dimen::from_pt(1),
+                shrink_order: GlueOrder::Fil,
+                ..Default::default()
+            }),
+        ];
+        assert!(vert_break(&list, Dimen::from_pt(15), Dimen(MAX_DIMEN)).is_err());
+    }
+
+    #[test]
+    fn prune_page_top_discards_until_box() {
+        let split_top_skip = Glue {
+            width: Dimen::from_pt(12),
+            ..Default::default()
+        };
+        let list = vec![glue(3, 0), Node::Penalty(5), vbox(10, 0), Node::Penalty(5)];
+        assert_eq!(
+            prune_page_top(list, split_top_skip),
+            vec![
+                Node::new_param_glue(
+                    GlueParam::SplitTopSkip,
+                    Glue {
+                        width: Dimen::from_pt(2),
+                        ..Default::default()
+                    }
+                ),
+                vbox(10, 0),
+                Node::Penalty(5),
+            ]
+        );
+    }
+}
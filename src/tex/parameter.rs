@@ -0,0 +1,432 @@
+//! Named internal parameters, like `\tolerance`, `\hsize`, `\parskip` and `\everypar`.
+//!
+//! TeX has integer, dimension, glue, math glue and token list parameters. Each kind of parameter is an enum
+//! here, and the values of all parameters are stored in scoped maps from the parameter to its
+//! value, so assignments are rolled back at the end of the current group unless `\global` is
+//! used.
+//! Parameters that have never been assigned have the value INITEX gives them:
+//! ```
+//! # use texide::tex::parameter::{IntParam, Parameters};
+//! let mut params = Parameters::new();
+//! assert_eq!(params.int(IntParam::Tolerance), 10000);
+//! params.set_int(IntParam::Tolerance, 200, false);
+//! assert_eq!(params.int(IntParam::Tolerance), 200);
+//! ```
+
+use crate::datastructures::scopedmap::ScopedMap;
+use crate::tex::dimen::{Dimen, Glue};
+use crate::tex::token::token::Token;
+use std::rc::Rc;
+
+/// Defines an enum of parameters, along with the name of the control sequence that refers to
+/// each parameter. The variants are listed in the order TeX lists them.
+macro_rules! parameter_enum {
+    ($(#[$attr:meta])* $enum: ident { $($variant: ident => $name: expr,)* }) => {
+        $(#[$attr])*
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        pub enum $enum {
+            $($variant,)*
+        }
+
+        impl $enum {
+            /// All parameters of this kind, in the order of their indices.
+            pub const ALL: [$enum; <[&str]>::len(&[$($name,)*])] = [$($enum::$variant,)*];
+
+            /// Returns the name of the control sequence that refers to the parameter.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($enum::$variant => $name,)*
+                }
+            }
+
+            /// Returns the index of the parameter in `ALL`.
+            pub fn index(self) -> usize {
+                self as usize
+            }
+
+            /// Returns the parameter with the provided index in `ALL`.
+            pub fn from_index(index: usize) -> $enum {
+                $enum::ALL[index]
+            }
+        }
+    };
+}
+
+parameter_enum!(
+    /// An integer parameter.
+    IntParam {
+        PreTolerance => "pretolerance",
+        Tolerance => "tolerance",
+        LinePenalty => "linepenalty",
+        HyphenPenalty => "hyphenpenalty",
+        ExHyphenPenalty => "exhyphenpenalty",
+        ClubPenalty => "clubpenalty",
+        WidowPenalty => "widowpenalty",
+        DisplayWidowPenalty => "displaywidowpenalty",
+        BrokenPenalty => "brokenpenalty",
+        BinOpPenalty => "binoppenalty",
+        RelPenalty => "relpenalty",
+        PreDisplayPenalty => "predisplaypenalty",
+        PostDisplayPenalty => "postdisplaypenalty",
+        InterLinePenalty => "interlinepenalty",
+        DoubleHyphenDemerits => "doublehyphendemerits",
+        FinalHyphenDemerits => "finalhyphendemerits",
+        AdjDemerits => "adjdemerits",
+        Mag => "mag",
+        DelimiterFactor => "delimiterfactor",
+        Looseness => "looseness",
+        Time => "time",
+        Day => "day",
+        Month => "month",
+        Year => "year",
+        ShowBoxBreadth => "showboxbreadth",
+        ShowBoxDepth => "showboxdepth",
+        HBadness => "hbadness",
+        VBadness => "vbadness",
+        Pausing => "pausing",
+        TracingOnline => "tracingonline",
+        TracingMacros => "tracingmacros",
+        TracingStats => "tracingstats",
+        TracingParagraphs => "tracingparagraphs",
+        TracingPages => "tracingpages",
+        TracingOutput => "tracingoutput",
+        TracingLostChars => "tracinglostchars",
+        TracingCommands => "tracingcommands",
+        TracingRestores => "tracingrestores",
+        UcHyph => "uchyph",
+        OutputPenalty => "outputpenalty",
+        MaxDeadCycles => "maxdeadcycles",
+        HangAfter => "hangafter",
+        FloatingPenalty => "floatingpenalty",
+        GlobalDefs => "globaldefs",
+        Fam => "fam",
+        EscapeChar => "escapechar",
+        DefaultHyphenChar => "defaulthyphenchar",
+        DefaultSkewChar => "defaultskewchar",
+        EndLineChar => "endlinechar",
+        NewLineChar => "newlinechar",
+        Language => "language",
+        LeftHyphenMin => "lefthyphenmin",
+        RightHyphenMin => "righthyphenmin",
+        HoldingInserts => "holdinginserts",
+        ErrorContextLines => "errorcontextlines",
+    }
+);
+
+impl IntParam {
+    /// Returns the value of the parameter in INITEX.
+    pub fn initial_value(self) -> i32 {
+        match self {
+            IntParam::Tolerance => 10000,
+            IntParam::Mag => 1000,
+            IntParam::MaxDeadCycles => 25,
+            IntParam::HangAfter => 1,
+            IntParam::EscapeChar => '\\' as i32,
+            // INITEX uses a carriage return. Lines in Texide end with a new line character,
+            // which is the character with catcode end of line.
+            IntParam::EndLineChar => '\n' as i32,
+            // As in TeX when the system clock is unavailable, the date is noon on 4 July 1776.
+            IntParam::Time => 12 * 60,
+            IntParam::Day => 4,
+            IntParam::Month => 7,
+            IntParam::Year => 1776,
+            _ => 0,
+        }
+    }
+}
+
+parameter_enum!(
+    /// A dimension parameter. All dimension parameters are zero in INITEX.
+    DimenParam {
+        ParIndent => "parindent",
+        MathSurround => "mathsurround",
+        LineSkipLimit => "lineskiplimit",
+        HSize => "hsize",
+        VSize => "vsize",
+        MaxDepth => "maxdepth",
+        SplitMaxDepth => "splitmaxdepth",
+        BoxMaxDepth => "boxmaxdepth",
+        HFuzz => "hfuzz",
+        VFuzz => "vfuzz",
+        DelimiterShortfall => "delimitershortfall",
+        NullDelimiterSpace => "nulldelimiterspace",
+        ScriptSpace => "scriptspace",
+        PreDisplaySize => "predisplaysize",
+        DisplayWidth => "displaywidth",
+        DisplayIndent => "displayindent",
+        OverfullRule => "overfullrule",
+        HangIndent => "hangindent",
+        HOffset => "hoffset",
+        VOffset => "voffset",
+        EmergencyStretch => "emergencystretch",
+    }
+);
+
+parameter_enum!(
+    /// A glue parameter. All glue parameters are zero in INITEX.
+    GlueParam {
+        LineSkip => "lineskip",
+        BaselineSkip => "baselineskip",
+        ParSkip => "parskip",
+        AboveDisplaySkip => "abovedisplayskip",
+        BelowDisplaySkip => "belowdisplayskip",
+        AboveDisplayShortSkip => "abovedisplayshortskip",
+        BelowDisplayShortSkip => "belowdisplayshortskip",
+        LeftSkip => "leftskip",
+        RightSkip => "rightskip",
+        TopSkip => "topskip",
+        SplitTopSkip => "splittopskip",
+        TabSkip => "tabskip",
+        SpaceSkip => "spaceskip",
+        XSpaceSkip => "xspaceskip",
+        ParFillSkip => "parfillskip",
+    }
+);
+
+parameter_enum!(
+    /// A math glue parameter, whose finite dimensions are in `mu`. All math glue parameters
+    /// are zero in INITEX.
+    MuGlueParam {
+        ThinMuSkip => "thinmuskip",
+        MedMuSkip => "medmuskip",
+        ThickMuSkip => "thickmuskip",
+    }
+);
+
+parameter_enum!(
+    /// A token list parameter. All token list parameters are empty in INITEX.
+    ///
+    /// Most of these token lists are inserted into the input at a particular moment; for
+    /// example, `\everyjob` at the start of the job and `\everyeof` at the end of each input
+    /// file. `\errhelp` is instead the help message printed for `\errmessage`.
+    TokenListParam {
+        Output => "output",
+        EveryPar => "everypar",
+        EveryMath => "everymath",
+        EveryDisplay => "everydisplay",
+        EveryHBox => "everyhbox",
+        EveryVBox => "everyvbox",
+        EveryJob => "everyjob",
+        EveryCr => "everycr",
+        ErrHelp => "errhelp",
+        EveryEof => "everyeof",
+    }
+);
+
+/// The shape of a paragraph, set using `\parshape`: the indentation and the length of each
+/// line. Lines after the last entry have the shape of the last entry, and an empty shape means
+/// that the lines are shaped by `\hsize`, `\hangindent` and `\hangafter` instead.
+pub type ParShape = Rc<Vec<(Dimen, Dimen)>>;
+
+#[derive(Default)]
+pub struct Parameters {
+    ints: ScopedMap<IntParam, i32>,
+    dimens: ScopedMap<DimenParam, Dimen>,
+    glues: ScopedMap<GlueParam, Glue>,
+    mu_glues: ScopedMap<MuGlueParam, Glue>,
+    token_lists: ScopedMap<TokenListParam, Rc<Vec<Token>>>,
+    par_shape: ScopedMap<(), ParShape>,
+}
+
+impl Parameters {
+    pub fn new() -> Parameters {
+        Default::default()
+    }
+
+    pub fn int(&self, p: IntParam) -> i32 {
+        self.ints
+            .get(&p)
+            .copied()
+            .unwrap_or_else(|| p.initial_value())
+    }
+
+    pub fn set_int(&mut self, p: IntParam, value: i32, global: bool) {
+        match global {
+            true => self.ints.insert_global(p, value),
+            false => self.ints.insert(p, value),
+        }
+    }
+
+    pub fn dimen(&self, p: DimenParam) -> Dimen {
+        self.dimens.get(&p).copied().unwrap_or_default()
+    }
+
+    pub fn set_dimen(&mut self, p: DimenParam, value: Dimen, global: bool) {
+        match global {
+            true => self.dimens.insert_global(p, value),
+            false => self.dimens.insert(p, value),
+        }
+    }
+
+    pub fn glue(&self, p: GlueParam) -> Glue {
+        self.glues.get(&p).copied().unwrap_or_default()
+    }
+
+    pub fn set_glue(&mut self, p: GlueParam, value: Glue, global: bool) {
+        match global {
+            true => self.glues.insert_global(p, value),
+            false => self.glues.insert(p, value),
+        }
+    }
+
+    pub fn mu_glue(&self, p: MuGlueParam) -> Glue {
+        self.mu_glues.get(&p).copied().unwrap_or_default()
+    }
+
+    pub fn set_mu_glue(&mut self, p: MuGlueParam, value: Glue, global: bool) {
+        match global {
+            true => self.mu_glues.insert_global(p, value),
+            false => self.mu_glues.insert(p, value),
+        }
+    }
+
+    pub fn token_list(&self, p: TokenListParam) -> Rc<Vec<Token>> {
+        self.token_lists.get(&p).cloned().unwrap_or_default()
+    }
+
+    pub fn set_token_list(&mut self, p: TokenListParam, value: Rc<Vec<Token>>, global: bool) {
+        match global {
+            true => self.token_lists.insert_global(p, value),
+            false => self.token_lists.insert(p, value),
+        }
+    }
+
+    pub fn par_shape(&self) -> ParShape {
+        self.par_shape.get(&()).cloned().unwrap_or_default()
+    }
+
+    pub fn set_par_shape(&mut self, value: ParShape, global: bool) {
+        match global {
+            true => self.par_shape.insert_global((), value),
+            false => self.par_shape.insert((), value),
+        }
+    }
+
+    /// Returns the character that starts a new line when printed, which is `\newlinechar`
+    /// if it is a valid character.
+    pub fn new_line_char(&self) -> Option<char> {
+        char_or_none(self.int(IntParam::NewLineChar))
+    }
+
+    /// Returns the character that begins printed control sequences, which is `\escapechar`
+    /// if it is a valid character.
+    pub fn escape_char(&self) -> Option<char> {
+        char_or_none(self.int(IntParam::EscapeChar))
+    }
+
+    /// Returns the character appended to lines read by `\read`, which is `\endlinechar` if it
+    /// is a valid character.
+    pub fn end_line_char(&self) -> Option<char> {
+        char_or_none(self.int(IntParam::EndLineChar))
+    }
+
+    /// Begins a new scope in every parameter table.
+    pub fn begin_scope(&mut self) {
+        self.ints.begin_scope();
+        self.dimens.begin_scope();
+        self.glues.begin_scope();
+        self.mu_glues.begin_scope();
+        self.token_lists.begin_scope();
+        self.par_shape.begin_scope();
+    }
+
+    /// Ends the current scope in every parameter table, and returns false if there is no scope
+    /// to end.
+    #[must_use]
+    pub fn end_scope(&mut self) -> bool {
+        // Every table begins a scope at the same time, so the scopes always exist together.
+        self.ints.end_scope()
+            && self.dimens.end_scope()
+            && self.glues.end_scope()
+            && self.mu_glues.end_scope()
+            && self.token_lists.end_scope()
+            && self.par_shape.end_scope()
+    }
+}
+
+/// Converts a parameter value to a character. Values that are not valid characters, like `-1`,
+/// are used by TeX to disable features like `\newlinechar`.
+fn char_or_none(value: i32) -> Option<char> {
+    match value >= 0 {
+        true => char::from_u32(value as u32),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices() {
+        for (i, p) in IntParam::ALL.iter().enumerate() {
+            assert_eq!(p.index(), i);
+            assert_eq!(IntParam::from_index(i), *p);
+        }
+        for (i, p) in DimenParam::ALL.iter().enumerate() {
+            assert_eq!(p.index(), i);
+            assert_eq!(DimenParam::from_index(i), *p);
+        }
+        for (i, p) in GlueParam::ALL.iter().enumerate() {
+            assert_eq!(p.index(), i);
+            assert_eq!(GlueParam::from_index(i), *p);
+        }
+        for (i, p) in MuGlueParam::ALL.iter().enumerate() {
+            assert_eq!(p.index(), i);
+            assert_eq!(MuGlueParam::from_index(i), *p);
+        }
+        for (i, p) in TokenListParam::ALL.iter().enumerate() {
+            assert_eq!(p.index(), i);
+            assert_eq!(TokenListParam::from_index(i), *p);
+        }
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(IntParam::ALL.len(), 55);
+        assert_eq!(DimenParam::ALL.len(), 21);
+        assert_eq!(GlueParam::ALL.len(), 15);
+        assert_eq!(MuGlueParam::ALL.len(), 3);
+        assert_eq!(TokenListParam::ALL.len(), 10);
+    }
+
+    #[test]
+    fn initial_values() {
+        let params = Parameters::new();
+        assert_eq!(params.int(IntParam::Mag), 1000);
+        assert_eq!(params.int(IntParam::PreTolerance), 0);
+        assert_eq!(params.escape_char(), Some('\\'));
+        assert_eq!(params.new_line_char(), Some('\u{0}'));
+        assert_eq!(params.dimen(DimenParam::HSize), Dimen(0));
+        assert_eq!(params.glue(GlueParam::ParSkip), Glue::default());
+        assert!(params.token_list(TokenListParam::EveryPar).is_empty());
+    }
+
+    #[test]
+    fn scoping() {
+        let mut params = Parameters::new();
+        params.set_int(IntParam::NewLineChar, 10, false);
+        params.set_dimen(DimenParam::HSize, Dimen::from_pt(100), false);
+        params.begin_scope();
+        params.set_int(IntParam::NewLineChar, -1, false);
+        params.set_dimen(DimenParam::HSize, Dimen::from_pt(200), false);
+        params.set_dimen(DimenParam::VSize, Dimen::from_pt(300), true);
+        assert_eq!(params.new_line_char(), None);
+        assert!(params.end_scope());
+        assert_eq!(params.new_line_char(), Some('\n'));
+        assert_eq!(params.dimen(DimenParam::HSize), Dimen::from_pt(100));
+        assert_eq!(params.dimen(DimenParam::VSize), Dimen::from_pt(300));
+    }
+
+    #[test]
+    fn par_shape_scoping() {
+        let mut params = Parameters::new();
+        assert!(params.par_shape().is_empty());
+        params.begin_scope();
+        let shape = Rc::new(vec![(Dimen(1), Dimen(2))]);
+        params.set_par_shape(shape.clone(), false);
+        assert_eq!(params.par_shape(), shape);
+        assert!(params.end_scope());
+        assert!(params.par_shape().is_empty());
+    }
+}
@@ -0,0 +1,958 @@
+//! Parsers for the basic syntactic elements of TeX, like numbers.
+//!
+//! The parsers in this module read from an expanded stream, so any macros or expansion
+//! primitives encountered will be expanded. This matches the behavior of TeX, in which
+//! numbers like `\count\numberofcounts` are fully expanded before being interpreted.
+
+use crate::tex::dimen::{Dimen, Glue, GlueOrder, MAX_DIMEN, UNITY};
+use crate::tex::error;
+use crate::tex::font::Font;
+use crate::tex::macros;
+use crate::tex::primitive;
+use crate::tex::primitive::library::{expansion, paragraph};
+use crate::tex::primitive::{Primitive, Variable};
+use crate::tex::state::TexState;
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::stream;
+use crate::tex::token::stream::Stream;
+use crate::tex::token::token;
+use crate::tex::token::token::{Token, Value};
+use std::rc::Rc;
+
+const IMPROPER_ALPHABETIC_CONSTANT_ERROR_HELP: &str =
+    "a one-character control sequence belongs after a backtick";
+const MISSING_NUMBER_ERROR_TITLE: &str = "Missing number";
+const MISSING_NUMBER_ERROR_HELP: &str =
+    "a number should have been here; numbers are digits, a backtick followed by a character, or an internal integer";
+const DIMENSION_TOO_LARGE_ERROR_TITLE: &str = "Dimension too large";
+const DIMENSION_TOO_LARGE_ERROR_HELP: &str = "dimensions must be less than 16384pt";
+
+/// The value of an internal quantity that is read by a parser; for example, the value of
+/// `\count1` when parsing `\dimen0=\count1pt`.
+enum Internal {
+    Int(i32),
+    Dimen(Dimen),
+    Glue(Glue),
+    MuGlue(Glue),
+    TokenList(Rc<Vec<Token>>),
+}
+
+/// Parses an integer from the input, as described in chapter 24 of the TeXbook.
+///
+/// The integer may be preceded by any number of spaces and `+` or `-` signs. The integer itself
+/// is one of: a sequence of decimal digits; a single quote followed by octal digits; a double
+/// quote followed by hexadecimal digits; a backtick followed by a character or a single
+/// character control sequence; or an internal integer variable. In the first four cases,
+/// one optional space after the number is consumed. Internal dimensions and glue are coerced
+/// to integers by taking their width in scaled points.
+pub fn parse_number<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<i32> {
+    let (negative, token) = parse_signs(input)?;
+    let n = match parse_integer_constant(input, &token)? {
+        Some((n, decimal)) => {
+            if decimal {
+                parse_optional_space(input)?;
+            }
+            n
+        }
+        None => match parse_internal(input, &token)? {
+            Some(Internal::Int(n)) => n,
+            Some(Internal::Dimen(d)) => d.0,
+            Some(Internal::Glue(g)) => g.width.0,
+            Some(Internal::MuGlue(_)) => return Err(incompatible_glue_units_error(token)),
+            _ => return Err(missing_number_error(token)),
+        },
+    };
+    Ok(if negative { n.wrapping_neg() } else { n })
+}
+
+/// Parses a dimension from the input, as described in chapter 24 of the TeXbook.
+///
+/// A dimension is an optionally signed factor followed by a unit of measure, like `-1.5pt` or
+/// `2\dimen0`, or an internal dimension like `\dimen0`. The units `em` and `ex` are the quad
+/// width and x-height of the current font, which are its parameters 6 and 5.
+pub fn parse_dimen<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Dimen> {
+    parse_dimen_with_order(input, false).map(|(d, _)| d)
+}
+
+/// Parses glue from the input, as described in chapter 24 of the TeXbook.
+///
+/// Glue is either an optionally signed internal glue, or a dimension optionally followed by
+/// `plus` and a stretch, and `minus` and a shrink. The stretch and shrink may have the infinite
+/// units `fil`, `fill` and `filll`.
+pub fn parse_glue<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Glue> {
+    let (negative, token) = parse_signs(input)?;
+    let width = match parse_internal(input, &token)? {
+        Some(Internal::Glue(g)) => {
+            return Ok(match negative {
+                false => g,
+                true => Glue {
+                    width: Dimen(-g.width.0),
+                    stretch: Dimen(-g.stretch.0),
+                    shrink: Dimen(-g.shrink.0),
+                    ..g
+                },
+            })
+        }
+        Some(Internal::Dimen(d)) => d,
+        Some(Internal::Int(n)) => {
+            let d = parse_dimen_units(input, n.wrapping_abs(), 0, false)?.0;
+            if n < 0 {
+                Dimen(-d.0)
+            } else {
+                d
+            }
+        }
+        Some(Internal::MuGlue(_)) => return Err(incompatible_glue_units_error(token)),
+        Some(Internal::TokenList(_)) => return Err(missing_number_error(token)),
+        None => parse_dimen_after_signs(input, token, false)?.0,
+    };
+    let mut glue = Glue {
+        width: if negative { Dimen(-width.0) } else { width },
+        ..Default::default()
+    };
+    if parse_keyword(input, "plus")? {
+        let (stretch, order) = parse_dimen_with_order(input, true)?;
+        glue.stretch = stretch;
+        glue.stretch_order = order;
+    }
+    if parse_keyword(input, "minus")? {
+        let (shrink, order) = parse_dimen_with_order(input, true)?;
+        glue.shrink = shrink;
+        glue.shrink_order = order;
+    }
+    Ok(glue)
+}
+
+fn parse_dimen_with_order<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    allow_fil: bool,
+) -> anyhow::Result<(Dimen, GlueOrder)> {
+    let (negative, token) = parse_signs(input)?;
+    let (d, order) = match parse_internal(input, &token)? {
+        Some(Internal::Dimen(d)) => (d, GlueOrder::Normal),
+        Some(Internal::Glue(g)) => (g.width, GlueOrder::Normal),
+        Some(Internal::Int(n)) => {
+            let (d, order) = parse_dimen_units(input, n.wrapping_abs(), 0, allow_fil)?;
+            (if n < 0 { Dimen(-d.0) } else { d }, order)
+        }
+        Some(Internal::MuGlue(_)) => return Err(incompatible_glue_units_error(token)),
+        Some(Internal::TokenList(_)) => return Err(missing_number_error(token)),
+        None => parse_dimen_after_signs(input, token, allow_fil)?,
+    };
+    Ok((if negative { Dimen(-d.0) } else { d }, order))
+}
+
+/// Parses math glue from the input, as described in chapter 24 of the TeXbook.
+///
+/// Math glue is like glue, except that its finite dimensions are in units of `mu`, like
+/// `3mu plus 1fil`, and the only internal quantities it can be made of are math glue, like
+/// `\thinmuskip`, and integers, which must then be followed by a unit.
+pub fn parse_mu_glue<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Glue> {
+    let (negative, token) = parse_signs(input)?;
+    let width = match parse_internal(input, &token)? {
+        Some(Internal::MuGlue(g)) => {
+            return Ok(match negative {
+                false => g,
+                true => Glue {
+                    width: Dimen(-g.width.0),
+                    stretch: Dimen(-g.stretch.0),
+                    shrink: Dimen(-g.shrink.0),
+                    ..g
+                },
+            })
+        }
+        Some(Internal::Int(n)) => {
+            let d = parse_mu_units(input, n.wrapping_abs(), 0, false)?.0;
+            if n < 0 {
+                Dimen(-d.0)
+            } else {
+                d
+            }
+        }
+        Some(_) => return Err(incompatible_glue_units_error(token)),
+        None => {
+            let (integer_part, fraction) = parse_decimal(input, token)?;
+            parse_mu_units(input, integer_part, fraction, false)?.0
+        }
+    };
+    let mut glue = Glue {
+        width: if negative { Dimen(-width.0) } else { width },
+        ..Default::default()
+    };
+    if parse_keyword(input, "plus")? {
+        let (stretch, order) = parse_mu_dimen_with_order(input, true)?;
+        glue.stretch = stretch;
+        glue.stretch_order = order;
+    }
+    if parse_keyword(input, "minus")? {
+        let (shrink, order) = parse_mu_dimen_with_order(input, true)?;
+        glue.shrink = shrink;
+        glue.shrink_order = order;
+    }
+    Ok(glue)
+}
+
+fn parse_mu_dimen_with_order<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    allow_fil: bool,
+) -> anyhow::Result<(Dimen, GlueOrder)> {
+    let (negative, token) = parse_signs(input)?;
+    let (d, order) = match parse_internal(input, &token)? {
+        Some(Internal::MuGlue(g)) => (g.width, GlueOrder::Normal),
+        Some(Internal::Int(n)) => {
+            let (d, order) = parse_mu_units(input, n.wrapping_abs(), 0, allow_fil)?;
+            (if n < 0 { Dimen(-d.0) } else { d }, order)
+        }
+        Some(_) => return Err(incompatible_glue_units_error(token)),
+        None => {
+            let (integer_part, fraction) = parse_decimal(input, token)?;
+            parse_mu_units(input, integer_part, fraction, allow_fil)?
+        }
+    };
+    Ok((if negative { Dimen(-d.0) } else { d }, order))
+}
+
+/// Parses the unit of a math dimension whose integer part and fraction have already been read.
+/// The unit is `mu`, an internal math glue, or an infinite unit if allowed.
+fn parse_mu_units<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    integer_part: i32,
+    fraction: i32,
+    allow_fil: bool,
+) -> anyhow::Result<(Dimen, GlueOrder)> {
+    // Internal math glue may be used as the unit.
+    input.prepare_imut_peek()?;
+    let is_internal_mu_glue = match input.imut_peek()? {
+        None => false,
+        Some(token) => matches!(
+            input.state().base().primitives.get(&token.value),
+            Some(Primitive::Variable(Variable::MuGlue(_)))
+        ),
+    };
+    if is_internal_mu_glue {
+        let token = input.next()?.unwrap();
+        if let Some(Internal::MuGlue(g)) = parse_internal(input, &token)? {
+            return Ok((
+                multiply_by_unit(integer_part, fraction, g.width.0)?,
+                GlueOrder::Normal,
+            ));
+        }
+    }
+    if allow_fil {
+        if let Some(result) = parse_fil(input, integer_part, fraction)? {
+            return Ok(result);
+        }
+    }
+    if parse_keyword(input, "mu")? {
+        let d = attach_fraction(integer_part, fraction)?;
+        parse_optional_space(input)?;
+        return Ok((d, GlueOrder::Normal));
+    }
+    Err(match input.peek()? {
+        None => anyhow::anyhow!("Unexpected end of input: Illegal unit of measure (mu inserted)"),
+        Some(token) => error::new_token_error(
+            token.clone(),
+            "Illegal unit of measure (mu inserted)".to_string(),
+            vec!["the units of math glue must be mu".to_string()],
+        ),
+    })
+}
+
+/// Parses a dimension whose first token, which is not a sign or an internal quantity, has
+/// already been read.
+fn parse_dimen_after_signs<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    token: Token,
+    allow_fil: bool,
+) -> anyhow::Result<(Dimen, GlueOrder)> {
+    let (integer_part, fraction) = parse_decimal(input, token)?;
+    parse_dimen_units(input, integer_part, fraction, allow_fil)
+}
+
+/// Parses the factor of a dimension, a decimal constant whose first token has already been
+/// read, and returns its integer part and its fraction in units of 1/65536.
+fn parse_decimal<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    token: Token,
+) -> anyhow::Result<(i32, i32)> {
+    Ok(match token.value {
+        Value::Character('.', CatCode::Other) | Value::Character(',', CatCode::Other) => {
+            (0, parse_fraction(input)?)
+        }
+        _ => match parse_integer_constant(input, &token)? {
+            None => return Err(missing_number_error(token)),
+            Some((n, decimal)) => {
+                let fraction = match (decimal, input.peek()?) {
+                    (
+                        true,
+                        Some(Token {
+                            value: Value::Character('.', CatCode::Other),
+                            ..
+                        }),
+                    )
+                    | (
+                        true,
+                        Some(Token {
+                            value: Value::Character(',', CatCode::Other),
+                            ..
+                        }),
+                    ) => {
+                        input.consume()?;
+                        parse_fraction(input)?
+                    }
+                    (true, _) => {
+                        parse_optional_space(input)?;
+                        0
+                    }
+                    (false, _) => 0,
+                };
+                (n, fraction)
+            }
+        },
+    })
+}
+
+/// Parses the digits after a decimal point and returns the fraction they represent, in units
+/// of 1/65536. The rounding follows section 102 of TeX: The Program.
+fn parse_fraction<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<i32> {
+    // TeX only considers the first 17 digits.
+    let mut digits = Vec::new();
+    while let Some(Token {
+        value: Value::Character(c, CatCode::Other),
+        ..
+    }) = input.peek()?
+    {
+        let digit = match c.to_digit(10) {
+            None => break,
+            Some(digit) => digit as i32,
+        };
+        input.consume()?;
+        if digits.len() < 17 {
+            digits.push(digit);
+        }
+    }
+    parse_optional_space(input)?;
+    let mut a = 0;
+    for digit in digits.iter().rev() {
+        a = (a + digit * 2 * UNITY) / 10;
+    }
+    Ok((a + 1) / 2)
+}
+
+/// Parses the unit of a dimension whose integer part and fraction have already been read.
+fn parse_dimen_units<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    integer_part: i32,
+    fraction: i32,
+    allow_fil: bool,
+) -> anyhow::Result<(Dimen, GlueOrder)> {
+    // An internal dimension may be used as the unit.
+    input.prepare_imut_peek()?;
+    let is_internal_dimen = match input.imut_peek()? {
+        None => false,
+        Some(token) => matches!(
+            input.state().base().primitives.get(&token.value),
+            Some(Primitive::Variable(Variable::Dimen(_)))
+                | Some(Primitive::Variable(Variable::Glue(_)))
+        ),
+    };
+    let unit = match is_internal_dimen {
+        false => None,
+        true => {
+            let token = input.next()?.unwrap();
+            match parse_internal(input, &token)? {
+                Some(Internal::Dimen(d)) => Some(d.0),
+                Some(Internal::Glue(g)) => Some(g.width.0),
+                _ => None,
+            }
+        }
+    };
+    let unit = match unit {
+        Some(unit) => Some(unit),
+        None if parse_keyword(input, "em")? => {
+            parse_optional_space(input)?;
+            Some(current_font(input).quad().0)
+        }
+        None if parse_keyword(input, "ex")? => {
+            parse_optional_space(input)?;
+            Some(current_font(input).x_height().0)
+        }
+        None => None,
+    };
+    if let Some(unit) = unit {
+        return Ok((
+            multiply_by_unit(integer_part, fraction, unit)?,
+            GlueOrder::Normal,
+        ));
+    }
+    if allow_fil {
+        if let Some(result) = parse_fil(input, integer_part, fraction)? {
+            return Ok(result);
+        }
+    }
+    // Magnification is not supported, so true dimensions are the same as regular dimensions.
+    parse_keyword(input, "true")?;
+    let (num, denom) = if parse_keyword(input, "pt")? {
+        (1, 1)
+    } else if parse_keyword(input, "in")? {
+        (7227, 100)
+    } else if parse_keyword(input, "pc")? {
+        (12, 1)
+    } else if parse_keyword(input, "cm")? {
+        (7227, 254)
+    } else if parse_keyword(input, "mm")? {
+        (7227, 2540)
+    } else if parse_keyword(input, "bp")? {
+        (7227, 7200)
+    } else if parse_keyword(input, "dd")? {
+        (1238, 1157)
+    } else if parse_keyword(input, "cc")? {
+        (14856, 1157)
+    } else if parse_keyword(input, "sp")? {
+        parse_optional_space(input)?;
+        return Ok((Dimen(integer_part), GlueOrder::Normal));
+    } else {
+        return Err(match input.peek()? {
+            None => anyhow::anyhow!("Unexpected end of input: Illegal unit of measure"),
+            Some(token) => error::new_token_error(
+                token.clone(),
+                "Illegal unit of measure".to_string(),
+                vec![
+                    "dimensions can be in units of em, ex, in, pt, pc, cm, mm, bp, dd, cc or sp"
+                        .to_string(),
+                ],
+            ),
+        });
+    };
+    let (integer_part, fraction) = match (num, denom) {
+        (1, 1) => (integer_part, fraction),
+        _ => {
+            let n = integer_part as i64 * num;
+            let remainder = n % denom;
+            let fraction = (num * fraction as i64 + UNITY as i64 * remainder) / denom;
+            let integer_part = n / denom + fraction / UNITY as i64;
+            (
+                integer_part.min(i32::MAX as i64) as i32,
+                (fraction % UNITY as i64) as i32,
+            )
+        }
+    };
+    let d = attach_fraction(integer_part, fraction)?;
+    parse_optional_space(input)?;
+    Ok((d, GlueOrder::Normal))
+}
+
+/// Returns the factor with the provided integer part and fraction times a unit given in scaled
+/// points.
+fn multiply_by_unit(integer_part: i32, fraction: i32, unit: i32) -> anyhow::Result<Dimen> {
+    let n = integer_part as i64 * unit as i64 + unit as i64 * fraction as i64 / UNITY as i64;
+    if n.abs() > MAX_DIMEN as i64 {
+        return Err(dimension_too_large_error());
+    }
+    Ok(Dimen(n as i32))
+}
+
+/// Parses one of the infinite units `fil`, `fill` and `filll`, and returns `None` if the input
+/// does not start with one.
+fn parse_fil<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    integer_part: i32,
+    fraction: i32,
+) -> anyhow::Result<Option<(Dimen, GlueOrder)>> {
+    if !parse_keyword(input, "fil")? {
+        return Ok(None);
+    }
+    let mut order = GlueOrder::Fil;
+    while parse_keyword(input, "l")? {
+        order = match order {
+            GlueOrder::Fil => GlueOrder::Fill,
+            GlueOrder::Fill => GlueOrder::Filll,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Illegal unit of measure: the largest infinity is filll"
+                ))
+            }
+        };
+    }
+    let d = attach_fraction(integer_part, fraction)?;
+    parse_optional_space(input)?;
+    Ok(Some((d, order)))
+}
+
+fn current_font<S: TexState<S>>(input: &primitive::Input<S>) -> &Font {
+    let fonts = &input.state().base().fonts;
+    fonts.get(fonts.current())
+}
+
+fn attach_fraction(integer_part: i32, fraction: i32) -> anyhow::Result<Dimen> {
+    if integer_part >= 16384 {
+        return Err(dimension_too_large_error());
+    }
+    Ok(Dimen(integer_part * UNITY + fraction))
+}
+
+/// Reads any number of spaces and signs, and returns whether the signs amount to a negation
+/// along with the first token after them.
+fn parse_signs<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<(bool, Token)> {
+    let mut negative = false;
+    loop {
+        let token = next_or_error(input)?;
+        match token.value {
+            Value::Character(_, CatCode::Space) => {}
+            Value::Character('+', CatCode::Other) => {}
+            Value::Character('-', CatCode::Other) => negative = !negative,
+            _ => return Ok((negative, token)),
+        }
+    }
+}
+
+/// Parses an integer constant whose first token has already been read, or returns `None` if the
+/// token does not begin a constant. Along with the integer, returns whether the constant was in
+/// decimal. For decimal constants the optional space after the constant is not consumed, as the
+/// constant may be the integer part of a dimension.
+fn parse_integer_constant<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    token: &Token,
+) -> anyhow::Result<Option<(i32, bool)>> {
+    Ok(Some(match token.value {
+        Value::Character(c, CatCode::Other) if c.is_ascii_digit() => (
+            parse_digits(input, c.to_digit(10).unwrap() as i64, 10)?,
+            true,
+        ),
+        Value::Character('\'', CatCode::Other) => {
+            let n = parse_digits(input, 0, 8)?;
+            parse_optional_space(input)?;
+            (n, false)
+        }
+        Value::Character('"', CatCode::Other) => {
+            let n = parse_digits(input, 0, 16)?;
+            parse_optional_space(input)?;
+            (n, false)
+        }
+        Value::Character('`', CatCode::Other) => {
+            let c = match input.unexpanded_stream().next()? {
+                None => return Err(missing_number_error(token.clone())),
+                Some(Token {
+                    value: Value::Character(c, _),
+                    ..
+                }) => c,
+                Some(Token {
+                    value: Value::ControlSequence(_, name),
+                    ..
+                }) if name.chars().count() == 1 => name.chars().next().unwrap(),
+                Some(other) => {
+                    return Err(error::new_token_error(
+                        other,
+                        "Improper alphabetic constant".to_string(),
+                        vec![IMPROPER_ALPHABETIC_CONSTANT_ERROR_HELP.to_string()],
+                    ))
+                }
+            };
+            parse_optional_space(input)?;
+            (c as i32, false)
+        }
+        _ => return Ok(None),
+    }))
+}
+
+/// Reads the value of the internal quantity referred to by the token, or returns `None` if the
+/// token does not refer to an internal quantity.
+fn parse_internal<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    token: &Token,
+) -> anyhow::Result<Option<Internal>> {
+    let variable = match input.state().base().primitives.get(&token.value) {
+        Some(Primitive::Variable(variable)) => *variable,
+        Some(Primitive::Constant(_, n)) => return Ok(Some(Internal::Int(*n))),
+        Some(p) if paragraph::is_parshape_primitive(p) => {
+            let n = input.state().base().parameters.par_shape().len();
+            return Ok(Some(Internal::Int(n as i32)));
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(match variable {
+        Variable::Int(v) => {
+            let index = v.index(input)?;
+            Internal::Int(v.get(input.state(), index))
+        }
+        Variable::Dimen(v) => {
+            let index = v.index(input)?;
+            Internal::Dimen(v.get(input.state(), index))
+        }
+        Variable::Glue(v) => {
+            let index = v.index(input)?;
+            Internal::Glue(v.get(input.state(), index))
+        }
+        Variable::MuGlue(v) => {
+            let index = v.index(input)?;
+            Internal::MuGlue(v.get(input.state(), index))
+        }
+        Variable::TokenList(v) => {
+            let index = v.index(input)?;
+            Internal::TokenList(v.get(input.state(), index))
+        }
+    }))
+}
+
+fn parse_digits<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    initial: i64,
+    radix: u32,
+) -> anyhow::Result<i32> {
+    let mut n = initial;
+    let mut read_a_digit = radix == 10;
+    while let Some(token) = input.peek()? {
+        let digit = match token.value {
+            Value::Character(c, CatCode::Other) => c.to_digit(radix),
+            // Hexadecimal digits A-F may also have catcode letter.
+            Value::Character(c, CatCode::Letter) if radix == 16 && c.is_ascii_uppercase() => {
+                c.to_digit(radix)
+            }
+            _ => None,
+        };
+        let digit = match digit {
+            None => break,
+            Some(digit) => digit,
+        };
+        let token = input.next()?.unwrap();
+        n = n * radix as i64 + digit as i64;
+        if n > i32::MAX as i64 {
+            return Err(error::new_token_error(
+                token,
+                "Number too big".to_string(),
+                vec![format!("the largest number allowed is {}", i32::MAX)],
+            ));
+        }
+        read_a_digit = true;
+    }
+    if !read_a_digit {
+        return Err(anyhow::anyhow!(MISSING_NUMBER_ERROR_TITLE));
+    }
+    Ok(n as i32)
+}
+
+/// Consumes the keyword if it appears next in the input, and returns whether it did.
+///
+/// As in TeX, the keyword is matched case-insensitively against character tokens that are
+/// not active, and spaces before the keyword are skipped. If the keyword does not appear, all
+/// tokens read other than the initial spaces are put back into the input.
+/// The keyword must be lowercase.
+pub fn parse_keyword<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    keyword: &str,
+) -> anyhow::Result<bool> {
+    let mut matched: Vec<Token> = Vec::new();
+    let mut chars = keyword.chars();
+    let mut next_char = chars.next();
+    while let Some(k) = next_char {
+        let token = match input.next()? {
+            None => break,
+            Some(token) => token,
+        };
+        match token.value {
+            Value::Character(c, cat_code)
+                if cat_code != CatCode::Active && (c == k || c == k.to_ascii_uppercase()) =>
+            {
+                matched.push(token);
+                next_char = chars.next();
+            }
+            Value::Character(_, CatCode::Space) if matched.is_empty() => {}
+            _ => {
+                matched.push(token);
+                break;
+            }
+        }
+    }
+    if next_char.is_none() {
+        return Ok(true);
+    }
+    if !matched.is_empty() {
+        input.push(stream::VecStream::new(matched));
+    }
+    Ok(false)
+}
+
+/// Parses a token list, like the value in the assignment `\toks0={abc}`, and returns it.
+///
+/// The token list is either a balanced text or an internal token list like `\toks1`.
+pub fn parse_token_list<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Rc<Vec<Token>>> {
+    loop {
+        let token = match input.next()? {
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Unexpected end of input: expected a token list"
+                ))
+            }
+            Some(token) => token,
+        };
+        if let Value::Character(_, CatCode::Space) = token.value {
+            continue;
+        }
+        if let Some(Internal::TokenList(tokens)) = parse_internal(input, &token)? {
+            return Ok(tokens);
+        }
+        return read_balanced_text(input, token).map(Rc::new);
+    }
+}
+
+/// Parses a balanced text, like the argument of `\uppercase`, and returns the tokens between
+/// the outer braces.
+///
+/// The balanced text begins with a begin group character or an implicit begin group character
+/// like `\bgroup`, which may be preceded by spaces. Tokens up to this character are expanded.
+/// The rest of the text is read without expansion up to the matching end group character.
+pub fn parse_balanced_text<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Vec<Token>> {
+    loop {
+        let token = match input.next()? {
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Unexpected end of input: expected a balanced text"
+                ))
+            }
+            Some(token) => token,
+        };
+        if let Value::Character(_, CatCode::Space) = token.value {
+            continue;
+        }
+        return read_balanced_text(input, token);
+    }
+}
+
+/// Parses a balanced text and expands it, like the argument of `\message`, and returns the
+/// expanded tokens between the outer braces.
+pub fn parse_expanded_balanced_text<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Vec<Token>> {
+    let token = loop {
+        match input.next()? {
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Unexpected end of input: expected a balanced text"
+                ))
+            }
+            Some(Token {
+                value: Value::Character(_, CatCode::Space),
+                ..
+            }) => continue,
+            Some(token) => break token,
+        }
+    };
+    if !matches!(
+        input.state().base().meaning(&token.value),
+        Some(Primitive::Character(_, CatCode::BeginGroup))
+    ) {
+        return Err(error::new_token_error(
+            token,
+            "Missing { inserted".to_string(),
+            vec!["a left brace was mandatory here".to_string()],
+        ));
+    }
+    let mut depth = 0_usize;
+    let mut result = Vec::new();
+    loop {
+        let next = match macros::next_not_outer(input, "text", true)? {
+            None => {
+                return Err(error::new_token_error(
+                    token,
+                    "File ended while scanning text".to_string(),
+                    vec!["this begin group character was not matched".to_string()],
+                ))
+            }
+            Some(next) => next,
+        };
+        match next.value {
+            Value::Character(_, CatCode::BeginGroup) => depth += 1,
+            Value::Character(_, CatCode::EndGroup) => {
+                if depth == 0 {
+                    return Ok(result);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        result.push(next);
+    }
+}
+
+/// Reads a balanced text whose first token has already been read.
+fn read_balanced_text<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    token: Token,
+) -> anyhow::Result<Vec<Token>> {
+    match input.state().base().meaning(&token.value) {
+        Some(Primitive::Character(_, CatCode::BeginGroup)) => {}
+        _ => {
+            return Err(error::new_token_error(
+                token,
+                "Missing { inserted".to_string(),
+                vec!["a left brace was mandatory here".to_string()],
+            ))
+        }
+    }
+    let mut depth = 0_usize;
+    let mut result = Vec::new();
+    loop {
+        let next = match macros::next_not_outer(input, "text", false)? {
+            None => {
+                return Err(error::new_token_error(
+                    token,
+                    "File ended while scanning text".to_string(),
+                    vec!["this begin group character was not matched".to_string()],
+                ))
+            }
+            Some(next) => next,
+        };
+        match next.value {
+            Value::Character(_, CatCode::BeginGroup) => depth += 1,
+            Value::Character(_, CatCode::EndGroup) => {
+                if depth == 0 {
+                    return Ok(result);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        result.push(next);
+    }
+}
+
+/// Parses a file name, like the argument of `\input` or `\openout`.
+///
+/// As in TeX, leading spaces are skipped and the file name consists of the following character
+/// tokens, with expansion, up to the next space or non-character token. A terminating space is
+/// consumed; any other terminating token is left in the input.
+pub fn parse_file_name<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<String> {
+    let mut file_name = String::new();
+    loop {
+        let c = match input.peek()? {
+            Some(Token {
+                value: Value::Character(c, cat_code),
+                ..
+            }) if *cat_code != CatCode::Active => (*c, *cat_code),
+            _ => return Ok(file_name),
+        };
+        input.consume()?;
+        match c {
+            (_, CatCode::Space) if file_name.is_empty() => continue,
+            (_, CatCode::Space) => return Ok(file_name),
+            (c, _) => file_name.push(c),
+        }
+    }
+}
+
+/// Returns the next token in the input that is not a space or `\relax`, or `None` if the input
+/// ends first. This is how TeX reads for example the box after `\setbox`.
+pub fn parse_non_blank_non_relax<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Option<Token>> {
+    let relax = Primitive::Execution(primitive::Execution::Static(expansion::get_relax()));
+    loop {
+        let token = match input.next()? {
+            None => return Ok(None),
+            Some(token) => token,
+        };
+        let skip = match input.state().base().meaning(&token.value) {
+            Some(Primitive::Character(_, CatCode::Space)) => true,
+            Some(p) => p.same_meaning(&relax),
+            None => false,
+        };
+        if !skip {
+            return Ok(Some(token));
+        }
+    }
+}
+
+/// Reads a begin group character, like the `{` after `\hbox`, which may be preceded by spaces
+/// and `\relax`, and returns it.
+pub fn parse_left_brace<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Token> {
+    let token = match parse_non_blank_non_relax(input)? {
+        None => {
+            return Err(anyhow::anyhow!(
+                "Unexpected end of input: missing {{ inserted"
+            ))
+        }
+        Some(token) => token,
+    };
+    match input.state().base().meaning(&token.value) {
+        Some(Primitive::Character(_, CatCode::BeginGroup)) => Ok(token),
+        _ => Err(error::new_token_error(
+            token,
+            "Missing { inserted".to_string(),
+            vec!["a left brace was mandatory here".to_string()],
+        )),
+    }
+}
+
+/// Consumes any spaces and then the next token in the input if it is an equals sign with
+/// catcode other.
+pub fn parse_optional_equals<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    while let Some(Token {
+        value: Value::Character(_, CatCode::Space),
+        ..
+    }) = input.peek()?
+    {
+        input.consume()?;
+    }
+    if let Some(Token {
+        value: Value::Character('=', CatCode::Other),
+        ..
+    }) = input.peek()?
+    {
+        input.consume()?;
+    }
+    Ok(())
+}
+
+/// Consumes the next token in the input if it is a space.
+pub fn parse_optional_space<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    if let Some(Token {
+        value: Value::Character(_, CatCode::Space),
+        ..
+    }) = input.peek()?
+    {
+        input.consume()?;
+    }
+    Ok(())
+}
+
+fn next_or_error<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<token::Token> {
+    match input.next()? {
+        None => Err(anyhow::anyhow!(
+            "Unexpected end of input: {}",
+            MISSING_NUMBER_ERROR_TITLE
+        )),
+        Some(token) => Ok(token),
+    }
+}
+
+fn dimension_too_large_error() -> anyhow::Error {
+    anyhow::anyhow!(
+        "{}: {}",
+        DIMENSION_TOO_LARGE_ERROR_TITLE,
+        DIMENSION_TOO_LARGE_ERROR_HELP
+    )
+}
+
+/// Returns the error for math glue used where a number, dimension or glue is expected, or the
+/// other way around.
+fn incompatible_glue_units_error(token: token::Token) -> anyhow::Error {
+    error::new_token_error(
+        token,
+        "Incompatible glue units".to_string(),
+        vec!["math glue in mu cannot be mixed with glue in pt".to_string()],
+    )
+}
+
+fn missing_number_error(token: token::Token) -> anyhow::Error {
+    error::new_token_error(
+        token,
+        MISSING_NUMBER_ERROR_TITLE.to_string(),
+        vec![MISSING_NUMBER_ERROR_HELP.to_string()],
+    )
+}
@@ -0,0 +1,297 @@
+//! Arithmetic primitives: `\advance`, `\multiply` and `\divide`.
+//!
+//! These primitives operate on integer, dimension, glue and math glue variables, like registers
+//! and parameters. The keyword `by` is optional:
+//! ```tex
+//! \advance\count1 by 2
+//! \multiply\dimen0 3
+//! \divide\skip2 by -2 % divides the width, stretch and shrink
+//! ```
+//! As in TeX, division truncates towards zero, and multiplying or dividing so that the result
+//! is out of range, or dividing by zero, is an arithmetic overflow.
+
+use crate::tex::dimen::{Dimen, Glue, GlueOrder, MAX_DIMEN};
+use crate::tex::error;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::prefix;
+use crate::tex::primitive::{Primitive, TypedVariable, Variable};
+use crate::tex::state::TexState;
+use crate::tex::token::stream::Stream;
+use crate::tex::token::token::{Token, Value};
+
+static ADVANCE_DOC: &str = "Add to an integer, dimension or glue variable";
+static MULTIPLY_DOC: &str = "Multiply an integer, dimension or glue variable";
+static DIVIDE_DOC: &str = "Divide an integer, dimension or glue variable";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Operation {
+    Advance,
+    Multiply,
+    Divide,
+}
+
+fn advance_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    arithmetic(token, input, Operation::Advance)
+}
+
+fn multiply_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    arithmetic(token, input, Operation::Multiply)
+}
+
+fn divide_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    arithmetic(token, input, Operation::Divide)
+}
+
+fn arithmetic<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    operation: Operation,
+) -> anyhow::Result<()> {
+    let target = match input.next()? {
+        None => {
+            return Err(error::new_token_error(
+                token,
+                "Unexpected end of input".to_string(),
+                vec!["expected a variable after this command".to_string()],
+            ))
+        }
+        Some(target) => target,
+    };
+    let variable = match input.state().base().primitives.get(&target.value) {
+        Some(Primitive::Variable(variable)) => *variable,
+        _ => return Err(not_a_variable_error(token, target)),
+    };
+    match variable {
+        Variable::Int(v) => apply(target, input, v, |input, a| {
+            Ok(match operation {
+                Operation::Advance => a.wrapping_add(parse::parse_number(input)?),
+                Operation::Multiply => a
+                    .checked_mul(parse::parse_number(input)?)
+                    .ok_or_else(arithmetic_overflow_error)?,
+                Operation::Divide => a
+                    .checked_div(parse::parse_number(input)?)
+                    .ok_or_else(arithmetic_overflow_error)?,
+            })
+        }),
+        Variable::Dimen(v) => apply(target, input, v, |input, a| {
+            Ok(match operation {
+                Operation::Advance => Dimen(a.0.wrapping_add(parse::parse_dimen(input)?.0)),
+                Operation::Multiply => multiply(a, parse::parse_number(input)?)?,
+                Operation::Divide => divide(a, parse::parse_number(input)?)?,
+            })
+        }),
+        Variable::Glue(v) => glue_arithmetic(target, input, v, operation, parse::parse_glue),
+        Variable::MuGlue(v) => glue_arithmetic(target, input, v, operation, parse::parse_mu_glue),
+        Variable::TokenList(_) => Err(not_a_variable_error(token, target)),
+    }
+}
+
+/// Performs the operation on a glue or math glue variable. The parser reads the glue that is
+/// added by `\advance`.
+fn glue_arithmetic<S: TexState<S>>(
+    target: Token,
+    input: &mut primitive::Input<S>,
+    variable: TypedVariable<S, Glue>,
+    operation: Operation,
+    parse_glue: fn(&mut primitive::Input<S>) -> anyhow::Result<Glue>,
+) -> anyhow::Result<()> {
+    apply(target, input, variable, |input, a| {
+        Ok(match operation {
+            Operation::Advance => add_glue(a, parse_glue(input)?),
+            Operation::Multiply => {
+                let n = parse::parse_number(input)?;
+                Glue {
+                    width: multiply(a.width, n)?,
+                    stretch: multiply(a.stretch, n)?,
+                    shrink: multiply(a.shrink, n)?,
+                    ..a
+                }
+            }
+            Operation::Divide => {
+                let n = parse::parse_number(input)?;
+                Glue {
+                    width: divide(a.width, n)?,
+                    stretch: divide(a.stretch, n)?,
+                    shrink: divide(a.shrink, n)?,
+                    ..a
+                }
+            }
+        })
+    })
+}
+
+/// Reads the index of the variable and the optional keyword `by`, and then sets the variable
+/// to the result of the operation, which reads its operand from the input.
+fn apply<S: TexState<S>, T>(
+    target: Token,
+    input: &mut primitive::Input<S>,
+    variable: TypedVariable<S, T>,
+    operation: impl FnOnce(&mut primitive::Input<S>, T) -> anyhow::Result<T>,
+) -> anyhow::Result<()> {
+    if !variable.is_settable() {
+        return Err(primitive::read_only_error(target));
+    }
+    let index = variable.index(input)?;
+    parse::parse_keyword(input, "by")?;
+    let value = variable.get(input.state(), index);
+    let value = operation(input, value)?;
+    let global = input.state().base().global_prefix;
+    variable
+        .set(input.state_mut(), index, value, global)
+        .map_err(|err| error::new_token_error(target, err.to_string(), vec![]))
+}
+
+fn multiply(d: Dimen, n: i32) -> anyhow::Result<Dimen> {
+    let product = d.0 as i64 * n as i64;
+    match product.abs() <= MAX_DIMEN as i64 {
+        true => Ok(Dimen(product as i32)),
+        false => Err(arithmetic_overflow_error()),
+    }
+}
+
+fn divide(d: Dimen, n: i32) -> anyhow::Result<Dimen> {
+    match n {
+        0 => Err(arithmetic_overflow_error()),
+        _ => Ok(Dimen(d.0 / n)),
+    }
+}
+
+/// Adds two glues. As in TeX, stretch and shrink components of different orders of infinity
+/// are not added: the component of the higher order wins, unless it is zero.
+fn add_glue(a: Glue, b: Glue) -> Glue {
+    let (stretch, stretch_order) =
+        add_component((a.stretch, a.stretch_order), (b.stretch, b.stretch_order));
+    let (shrink, shrink_order) =
+        add_component((a.shrink, a.shrink_order), (b.shrink, b.shrink_order));
+    Glue {
+        width: Dimen(a.width.0.wrapping_add(b.width.0)),
+        stretch,
+        stretch_order,
+        shrink,
+        shrink_order,
+    }
+}
+
+fn add_component(a: (Dimen, GlueOrder), b: (Dimen, GlueOrder)) -> (Dimen, GlueOrder) {
+    let a_order = match a.0 .0 {
+        0 => GlueOrder::Normal,
+        _ => a.1,
+    };
+    if a_order == b.1 {
+        (Dimen(a.0 .0.wrapping_add(b.0 .0)), a_order)
+    } else if a_order < b.1 && b.0 .0 != 0 {
+        b
+    } else {
+        (a.0, a_order)
+    }
+}
+
+fn arithmetic_overflow_error() -> anyhow::Error {
+    anyhow::anyhow!(
+        "Arithmetic overflow: the result is out of range, or the divisor is zero; the variable was not changed"
+    )
+}
+
+fn not_a_variable_error(token: Token, target: Token) -> anyhow::Error {
+    let command = match &token.value {
+        Value::ControlSequence(_, name) => format!("\\{}", name),
+        Value::Character(c, _) => c.to_string(),
+    };
+    let name = match &target.value {
+        Value::ControlSequence(_, name) => format!("\\{}", name),
+        Value::Character(c, _) => c.to_string(),
+    };
+    error::new_token_error(
+        target,
+        format!("You can't use `{}' after {}", name, command),
+        vec![format!(
+            "{} must be followed by an integer, dimension or glue variable",
+            command
+        )],
+    )
+}
+
+pub fn get_advance<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("advance", advance_fn, ADVANCE_DOC)
+}
+
+pub fn get_multiply<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("multiply", multiply_fn, MULTIPLY_DOC)
+}
+
+pub fn get_divide<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("divide", divide_fn, DIVIDE_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        testutil::run(testutil::new_state(), input)
+    }
+
+    fn count(input: &str) -> i32 {
+        run(input).unwrap().base().registers.count(1)
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(count(r"\count1=5 \advance\count1 by 3 "), 8);
+        assert_eq!(count(r"\count1=5 \advance\count1-7 "), -2);
+        assert_eq!(count(r"\count1=5 \multiply\count1 by -3 "), -15);
+        assert_eq!(count(r"\count1=7 \divide\count1 by 2 "), 3);
+        assert_eq!(count(r"\count1=-7 \divide\count1 by 2 "), -3);
+    }
+
+    #[test]
+    fn dimensions() {
+        let s = run(r"\dimen1=1pt \advance\dimen1 by 0.5pt \dimen2=\dimen1 \multiply\dimen2 by 4 \dimen3=\dimen2 \divide\dimen3 3 ").unwrap();
+        let r = &s.base().registers;
+        assert_eq!(r.dimen(1), Dimen(3 * 65536 / 2));
+        assert_eq!(r.dimen(2), Dimen::from_pt(6));
+        assert_eq!(r.dimen(3), Dimen::from_pt(2));
+    }
+
+    #[test]
+    fn glue() {
+        let s = run(r"\skip1=1pt plus 2pt minus 1fil \advance\skip1 by 1pt plus 1fil minus 3fil \skip2=\skip1 \multiply\skip2 by 2 ").unwrap();
+        let r = &s.base().registers;
+        assert_eq!(r.skip(1).to_string(), "2.0pt plus 1.0fil minus 4.0fil");
+        assert_eq!(r.skip(2).to_string(), "4.0pt plus 2.0fil minus 8.0fil");
+    }
+
+    #[test]
+    fn glue_lower_order_is_ignored() {
+        let s = run(r"\skip1=0pt plus 1fil \advance\skip1 by 0pt plus 5pt ").unwrap();
+        assert_eq!(s.base().registers.skip(1).to_string(), "0.0pt plus 1.0fil");
+    }
+
+    #[test]
+    fn local_and_global() {
+        let s = run(r"\count1=1 \count2=1 {\advance\count1 1 \global\advance\count2 1 }").unwrap();
+        assert_eq!(s.base().registers.count(1), 1);
+        assert_eq!(s.base().registers.count(2), 2);
+    }
+
+    #[test]
+    fn arithmetic_overflow() {
+        assert!(run(r"\count1=1 \divide\count1 by 0 ").is_err());
+        assert!(run(r"\count1=2000000000 \multiply\count1 by 2 ").is_err());
+        assert!(run(r"\dimen1=10000pt \multiply\dimen1 by 2 ").is_err());
+        assert!(run(r"\skip1=1pt \divide\skip1 by 0 ").is_err());
+    }
+
+    #[test]
+    fn invalid_targets() {
+        assert!(run(r"\advance\toks1 by 1 ").is_err());
+        assert!(run(r"\advance\currentgrouplevel by 1 ").is_err());
+        assert!(run(r"\advance 1 ").is_err());
+    }
+}
@@ -0,0 +1,964 @@
+//! Box primitives: `\hbox`, `\vbox` and `\vtop`, which build boxes, and the box registers with
+//! `\setbox`, `\box`, `\copy`, `\lastbox`, `\unhbox`, `\unvbox`, `\unhcopy`, `\unvcopy` and the
+//! box dimensions `\wd`, `\ht` and `\dp`:
+//! ```tex
+//! \setbox0=\hbox to 100pt{a b}  % a box 100pt wide, with the space stretched
+//! \wd0=0pt                      % the box now has zero width
+//! \box0                         % append the box, and void the register
+//! ```
+//! A box whose glue has to stretch or shrink too much is reported on the terminal as underfull
+//! or overfull, depending on `\hbadness` and `\hfuzz` for horizontal boxes and `\vbadness` and
+//! `\vfuzz` for vertical boxes. The badness of the box that was built most recently is
+//! `\badness`.
+//!
+//! `\shipout` outputs a box as a page. It takes a box in the same way as `\setbox`, and is
+//! usually called by the output routine as `\shipout\box255`.
+
+use crate::tex::dimen::Dimen;
+use crate::tex::driver;
+use crate::tex::error;
+use crate::tex::group::{BoxContext, GroupType};
+use crate::tex::nest::Mode;
+use crate::tex::node::{BoxNode, Node};
+use crate::tex::pack;
+use crate::tex::page;
+use crate::tex::parameter::{DimenParam, TokenListParam};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::prefix;
+use crate::tex::primitive::library::register::parse_register;
+use crate::tex::primitive::{Execution, Primitive};
+use crate::tex::state::TexState;
+use crate::tex::token::stream;
+use crate::tex::token::token::Token;
+
+static HBOX_DOC: &str = "Build a box containing a horizontal list";
+static VBOX_DOC: &str = "Build a box containing a vertical list, aligned at its last line";
+static VTOP_DOC: &str = "Build a box containing a vertical list, aligned at its first line";
+static BOX_DOC: &str = "Append the box in a register, and void the register";
+static COPY_DOC: &str = "Append a copy of the box in a register";
+static LASTBOX_DOC: &str = "Remove the last box from the current list";
+static SETBOX_DOC: &str = "Put a box in a register";
+static SHIPOUT_DOC: &str = "Output a box as a page";
+static UNHBOX_DOC: &str = "Append the contents of a horizontal box register, and void it";
+static UNVBOX_DOC: &str = "Append the contents of a vertical box register, and void it";
+static UNHCOPY_DOC: &str = "Append the contents of a horizontal box register";
+static UNVCOPY_DOC: &str = "Append the contents of a vertical box register";
+static WD_DOC: &str = "Get or set the width of a box register";
+static HT_DOC: &str = "Get or set the height of a box register";
+static DP_DOC: &str = "Get or set the depth of a box register";
+static BADNESS_DOC: &str = "The badness of the box built most recently";
+
+/// The commands that produce a box, and so can follow `\setbox`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BoxCommand {
+    Box,
+    Copy,
+    LastBox,
+    HBox,
+    VBox,
+    VTop,
+}
+
+fn hbox_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    begin_box(token, input, BoxCommand::HBox, BoxContext::Append(Dimen(0)))
+}
+
+fn vbox_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    begin_box(token, input, BoxCommand::VBox, BoxContext::Append(Dimen(0)))
+}
+
+fn vtop_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    begin_box(token, input, BoxCommand::VTop, BoxContext::Append(Dimen(0)))
+}
+
+fn box_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    begin_box(token, input, BoxCommand::Box, BoxContext::Append(Dimen(0)))
+}
+
+fn copy_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    begin_box(token, input, BoxCommand::Copy, BoxContext::Append(Dimen(0)))
+}
+
+fn lastbox_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    begin_box(
+        token,
+        input,
+        BoxCommand::LastBox,
+        BoxContext::Append(Dimen(0)),
+    )
+}
+
+fn setbox_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let register = parse_register(input)?;
+    parse::parse_optional_equals(input)?;
+    let global = input.state().base().global_prefix;
+    scan_box(
+        token,
+        input,
+        "\\setbox",
+        BoxContext::SetBox { register, global },
+    )
+}
+
+fn shipout_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    scan_box(token, input, "\\shipout", BoxContext::ShipOut)
+}
+
+/// Reads the box command after `\setbox` or `\shipout`, skipping blank spaces and `\relax`, and
+/// begins the box in the provided context. This is `scan_box` in TeX.
+fn scan_box<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    name: &str,
+    context: BoxContext,
+) -> anyhow::Result<()> {
+    let next = match parse::parse_non_blank_non_relax(input)? {
+        None => {
+            return Err(error::new_token_error(
+                token,
+                format!("Unexpected end of input while reading the box for {}", name),
+                vec![],
+            ))
+        }
+        Some(next) => next,
+    };
+    match box_command(input, &next) {
+        None => Err(error::new_token_error(
+            next,
+            "A <box> was supposed to be here".to_string(),
+            vec![format!(
+                "{} must be followed by \\hbox, \\vbox, \\vtop, \\box, \\copy or \\lastbox",
+                name
+            )],
+        )),
+        Some(command) => begin_box(next, input, command, context),
+    }
+}
+
+/// Returns the box command that the token means, if any.
+fn box_command<S: TexState<S>>(input: &primitive::Input<S>, token: &Token) -> Option<BoxCommand> {
+    let meaning = input.state().base().meaning(&token.value)?;
+    let is = |get: fn() -> primitive::ExecutionStatic<S>| {
+        meaning.same_meaning(&Primitive::Execution(Execution::Static(get())))
+    };
+    if is(get_box) {
+        Some(BoxCommand::Box)
+    } else if is(get_copy) {
+        Some(BoxCommand::Copy)
+    } else if is(get_lastbox) {
+        Some(BoxCommand::LastBox)
+    } else if is(get_hbox) {
+        Some(BoxCommand::HBox)
+    } else if is(get_vbox) {
+        Some(BoxCommand::VBox)
+    } else if is(get_vtop) {
+        Some(BoxCommand::VTop)
+    } else {
+        None
+    }
+}
+
+/// Begins a box that is put in the provided context.
+///
+/// A box from a register or the current list is put in its context straight away. A box built
+/// using `\hbox`, `\vbox` or `\vtop` is put in its context by [package] when its group ends.
+fn begin_box<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    command: BoxCommand,
+    context: BoxContext,
+) -> anyhow::Result<()> {
+    let b = match command {
+        BoxCommand::Box => {
+            let n = parse_register(input)?;
+            input.state_mut().base_mut().registers.take_box(n)
+        }
+        BoxCommand::Copy => {
+            let n = parse_register(input)?;
+            input.state().base().registers.box_register(n).cloned()
+        }
+        BoxCommand::LastBox => last_box(token, input)?,
+        BoxCommand::HBox | BoxCommand::VBox | BoxCommand::VTop => {
+            let spec = parse_spec(input)?;
+            parse::parse_left_brace(input)?;
+            let base = input.state_mut().base_mut();
+            let mode = base.nest.mode();
+            let group_type = match (command, context) {
+                (BoxCommand::HBox, BoxContext::Append(_)) if mode.is_vertical() => {
+                    GroupType::AdjustedHBox
+                }
+                (BoxCommand::HBox, _) => GroupType::HBox,
+                (BoxCommand::VBox, _) => GroupType::VBox,
+                _ => GroupType::VTop,
+            };
+            base.begin_box_group(group_type, context, spec);
+            let every = match command {
+                BoxCommand::HBox => {
+                    base.nest.push(Mode::RestrictedHorizontal);
+                    TokenListParam::EveryHBox
+                }
+                _ => {
+                    driver::normal_paragraph(base);
+                    base.nest.push(Mode::InternalVertical);
+                    TokenListParam::EveryVBox
+                }
+            };
+            let every = base.parameters.token_list(every);
+            if !every.is_empty() {
+                input.push(stream::VecStream::new(every.to_vec()));
+            }
+            return Ok(());
+        }
+    };
+    box_end(input, context, b, Vec::new())
+}
+
+/// Removes the last node from the current list if it is a box, and returns it.
+fn last_box<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Option<Node>> {
+    let base = input.state_mut().base_mut();
+    let mode = base.nest.mode();
+    if mode.is_math() {
+        return Err(driver::illegal_in_mode_error(token, "\\lastbox", mode));
+    }
+    if mode == Mode::Vertical && base.nest.current().list.is_empty() {
+        return Err(error::new_token_error(
+            token,
+            "You can't use `\\lastbox' in vertical mode".to_string(),
+            vec!["boxes cannot be taken from the current page".to_string()],
+        ));
+    }
+    Ok(match base.nest.tail() {
+        Some(Node::HList(_)) | Some(Node::VList(_)) => base.nest.current_mut().list.pop(),
+        _ => None,
+    })
+}
+
+/// Parses the optional `to <dimen>` or `spread <dimen>` after `\hbox`, `\vbox` or `\vtop`.
+fn parse_spec<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<pack::Spec> {
+    if parse::parse_keyword(input, "to")? {
+        return Ok(pack::Spec::Exactly(parse::parse_dimen(input)?));
+    }
+    if parse::parse_keyword(input, "spread")? {
+        return Ok(pack::Spec::Additional(parse::parse_dimen(input)?));
+    }
+    Ok(pack::Spec::NATURAL)
+}
+
+/// Finishes a box built using `\hbox`, `\vbox` or `\vtop` when its group ends, and puts it in
+/// the context in which it was begun.
+///
+/// The token is the token that ended the group.
+pub fn package<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let base = input.state_mut().base_mut();
+    let group_type = base.group_type();
+    let vertical = matches!(group_type, GroupType::VBox | GroupType::VTop);
+    if vertical {
+        driver::end_paragraph(base)?;
+    }
+    // The depth limit is the one in force inside the box.
+    let max_depth = base.parameters.dimen(DimenParam::BoxMaxDepth);
+    let context = line_context(&token);
+    let group = driver::end_group(token, input, group_type)?;
+    // Box groups are begun by begin_box, which records the context and specification.
+    let (box_context, spec) = group.box_spec.unwrap();
+    let base = input.state_mut().base_mut();
+    // The box's list is not the outer vertical list, which is never popped.
+    let list = base.nest.pop().unwrap().list;
+    let (node, report, adjustments) = if vertical {
+        let (mut b, badness, report) = pack::vpack(list, spec, max_depth, &base.parameters);
+        base.last_badness = badness;
+        if group_type == GroupType::VTop {
+            let height = match b.list.first() {
+                Some(Node::HList(first)) | Some(Node::VList(first)) => first.height,
+                Some(Node::Rule(rule)) => rule.height.unwrap_or_default(),
+                _ => Dimen(0),
+            };
+            b.depth = Dimen(b.depth.0 + b.height.0 - height.0);
+            b.height = height;
+        }
+        (Node::VList(b), report, Vec::new())
+    } else {
+        let mut list = list;
+        let adjustments = match group_type {
+            GroupType::AdjustedHBox => take_adjustments(&mut list),
+            _ => Vec::new(),
+        };
+        let (b, badness, report) = pack::hpack(list, spec, &base.parameters, &base.fonts);
+        base.last_badness = badness;
+        (Node::HList(b), report, adjustments)
+    };
+    if let Some(report) = report {
+        // The node was just built as a box.
+        pack::print_report(base, &report, node.as_box().unwrap(), &context);
+    }
+    box_end(input, box_context, Some(node), adjustments)
+}
+
+/// Returns how a box report describes where the box ended.
+fn line_context(token: &Token) -> String {
+    match &token.source {
+        None => "detected".to_string(),
+        Some(source) => format!("detected at line {}", source.line.line_number),
+    }
+}
+
+/// Removes the inserts, marks and `\vadjust` material from the top level of a horizontal
+/// list, and returns them in order. When a box built in vertical mode is appended, this
+/// material goes into the vertical list after it.
+pub fn take_adjustments(list: &mut Vec<Node>) -> Vec<Node> {
+    let mut adjustments = Vec::new();
+    let mut kept = Vec::with_capacity(list.len());
+    for node in list.drain(..) {
+        match node {
+            Node::Insert(_) | Node::Mark(_) => adjustments.push(node),
+            Node::Adjust(material) => adjustments.extend(material),
+            _ => kept.push(node),
+        }
+    }
+    *list = kept;
+    adjustments
+}
+
+/// Puts a box, or a void box, in its context. The adjustments are material taken out of the
+/// box, which is appended after it.
+fn box_end<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    context: BoxContext,
+    b: Option<Node>,
+    adjustments: Vec<Node>,
+) -> anyhow::Result<()> {
+    let base = input.state_mut().base_mut();
+    match context {
+        BoxContext::Append(shift) => {
+            let mut node = match b {
+                // A void box appends nothing.
+                None => return Ok(()),
+                Some(node) => node,
+            };
+            if let Some(b) = node.as_box_mut() {
+                b.shift = shift;
+            }
+            let mode = base.nest.mode();
+            if mode.is_vertical() {
+                driver::append_to_vlist(base, node);
+                for adjustment in adjustments {
+                    base.nest.append(adjustment);
+                }
+                if mode == Mode::Vertical {
+                    page::build_page(input)?;
+                }
+            } else {
+                if mode.is_horizontal() {
+                    base.nest.current_mut().space_factor = 1000;
+                }
+                // TODO: wrap the box in a noad in math mode once math lists exist
+                base.nest.append(node);
+            }
+        }
+        BoxContext::SetBox { register, global } => {
+            let boxes = &mut base.registers.boxes;
+            match (b, global) {
+                (Some(node), false) => boxes.insert(register, node),
+                (Some(node), true) => boxes.insert_global(register, node),
+                (None, false) => boxes.remove(register),
+                (None, true) => boxes.remove_global(register),
+            }
+        }
+        BoxContext::ShipOut => {
+            // Shipping out a void box does nothing.
+            if let Some(node) = b {
+                page::ship_out(input, node)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn unhbox_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    unpackage(token, input, false, false)
+}
+
+fn unhcopy_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    unpackage(token, input, false, true)
+}
+
+fn unvbox_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    unpackage(token, input, true, false)
+}
+
+fn unvcopy_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    unpackage(token, input, true, true)
+}
+
+/// Appends the contents of a box register to the current list.
+///
+/// `\unhbox` and `\unhcopy` in vertical mode begin a paragraph, and `\unvbox` and `\unvcopy` in
+/// a paragraph end it, before the register is read.
+fn unpackage<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    vertical: bool,
+    copy: bool,
+) -> anyhow::Result<()> {
+    let mode = input.state().base().nest.mode();
+    match (vertical, mode) {
+        (false, Mode::Vertical) | (false, Mode::InternalVertical) => {
+            input.push(stream::VecStream::new(vec![token]));
+            driver::begin_paragraph(input, true);
+            return Ok(());
+        }
+        (true, Mode::Horizontal) => {
+            input.push(stream::VecStream::new(vec![
+                Token::new_control_sequence("par"),
+                token,
+            ]));
+            return Ok(());
+        }
+        (true, Mode::RestrictedHorizontal) => {
+            let description = match copy {
+                false => "\\unvbox",
+                true => "\\unvcopy",
+            };
+            return Err(driver::illegal_in_mode_error(token, description, mode));
+        }
+        (true, Mode::Math) | (true, Mode::DisplayMath) => {
+            return Err(driver::missing_dollar_error(token));
+        }
+        _ => {}
+    }
+    let n = parse_register(input)?;
+    let base = input.state_mut().base_mut();
+    let compatible = match base.registers.box_register(n) {
+        // A void register appends nothing in any mode.
+        None => return Ok(()),
+        Some(Node::HList(_)) => mode.is_horizontal(),
+        Some(_) => mode.is_vertical(),
+    };
+    if !compatible {
+        return Err(error::new_token_error(
+            token,
+            "Incompatible list can't be unboxed".to_string(),
+            vec![
+                "the contents of a horizontal box can only be appended in horizontal mode"
+                    .to_string(),
+                "and the contents of a vertical box only in vertical mode".to_string(),
+            ],
+        ));
+    }
+    let list = match copy {
+        // The register holds a box, as checked above.
+        true => base
+            .registers
+            .box_register(n)
+            .unwrap()
+            .as_box()
+            .unwrap()
+            .list
+            .clone(),
+        false => match base.registers.take_box(n) {
+            Some(Node::HList(b)) | Some(Node::VList(b)) => b.list,
+            _ => unreachable!(),
+        },
+    };
+    for node in list {
+        base.nest.append(node);
+    }
+    Ok(())
+}
+
+/// Returns the box in a register, or None if the register is void.
+fn box_dimensions<S: TexState<S>>(state: &S, index: usize) -> Option<&BoxNode> {
+    state
+        .base()
+        .registers
+        .box_register(index)
+        .and_then(Node::as_box)
+}
+
+/// Returns the box in a register for changing its dimensions, or None if the register is void.
+///
+/// The box is changed in place: a box dimension assignment is not undone at the end of a
+/// group, whether or not it is `\global`.
+fn box_dimensions_mut<S: TexState<S>>(state: &mut S, index: usize) -> Option<&mut BoxNode> {
+    state
+        .base_mut()
+        .registers
+        .boxes
+        .get_mut(&index)
+        .and_then(Node::as_box_mut)
+}
+
+fn wd_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Dimen,
+    _: bool,
+) -> anyhow::Result<()> {
+    if let Some(b) = box_dimensions_mut(state, index) {
+        b.width = value;
+    }
+    Ok(())
+}
+
+fn ht_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Dimen,
+    _: bool,
+) -> anyhow::Result<()> {
+    if let Some(b) = box_dimensions_mut(state, index) {
+        b.height = value;
+    }
+    Ok(())
+}
+
+fn dp_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Dimen,
+    _: bool,
+) -> anyhow::Result<()> {
+    if let Some(b) = box_dimensions_mut(state, index) {
+        b.depth = value;
+    }
+    Ok(())
+}
+
+pub fn get_hbox<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("hbox", hbox_fn, HBOX_DOC)
+}
+
+pub fn get_vbox<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("vbox", vbox_fn, VBOX_DOC)
+}
+
+pub fn get_vtop<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("vtop", vtop_fn, VTOP_DOC)
+}
+
+pub fn get_box<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("box", box_fn, BOX_DOC)
+}
+
+pub fn get_copy<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("copy", copy_fn, COPY_DOC)
+}
+
+pub fn get_lastbox<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("lastbox", lastbox_fn, LASTBOX_DOC)
+}
+
+pub fn get_setbox<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("setbox", setbox_fn, SETBOX_DOC)
+}
+
+pub fn get_shipout<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("shipout", shipout_fn, SHIPOUT_DOC)
+}
+
+pub fn get_unhbox<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("unhbox", unhbox_fn, UNHBOX_DOC)
+}
+
+pub fn get_unvbox<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("unvbox", unvbox_fn, UNVBOX_DOC)
+}
+
+pub fn get_unhcopy<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("unhcopy", unhcopy_fn, UNHCOPY_DOC)
+}
+
+pub fn get_unvcopy<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("unvcopy", unvcopy_fn, UNVCOPY_DOC)
+}
+
+/// Returns the `\wd` primitive. The width of a void register is zero, and setting it does
+/// nothing.
+pub fn get_wd<S: TexState<S>>() -> primitive::TypedVariable<S, Dimen> {
+    primitive::TypedVariable {
+        name: "wd",
+        index: primitive::Index::Parsed(parse_register),
+        get_fn: |state: &S, index| box_dimensions(state, index).map_or(Dimen(0), |b| b.width),
+        set_fn: Some(wd_set),
+        docs: WD_DOC,
+    }
+}
+
+/// Returns the `\ht` primitive. The height of a void register is zero, and setting it does
+/// nothing.
+pub fn get_ht<S: TexState<S>>() -> primitive::TypedVariable<S, Dimen> {
+    primitive::TypedVariable {
+        name: "ht",
+        index: primitive::Index::Parsed(parse_register),
+        get_fn: |state: &S, index| box_dimensions(state, index).map_or(Dimen(0), |b| b.height),
+        set_fn: Some(ht_set),
+        docs: HT_DOC,
+    }
+}
+
+/// Returns the `\dp` primitive. The depth of a void register is zero, and setting it does
+/// nothing.
+pub fn get_dp<S: TexState<S>>() -> primitive::TypedVariable<S, Dimen> {
+    primitive::TypedVariable {
+        name: "dp",
+        index: primitive::Index::Parsed(parse_register),
+        get_fn: |state: &S, index| box_dimensions(state, index).map_or(Dimen(0), |b| b.depth),
+        set_fn: Some(dp_set),
+        docs: DP_DOC,
+    }
+}
+
+/// Returns the `\badness` primitive, which is the badness of the box that was packaged most
+/// recently.
+pub fn get_badness<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "badness",
+        index: primitive::Index::Fixed(0),
+        get_fn: |state: &S, _| state.base().last_badness,
+        set_fn: None,
+        docs: BADNESS_DOC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::dimen::Glue;
+    use crate::tex::node::{Char, GlueSign};
+    use crate::tex::parameter::GlueParam;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        testutil::run(s, input)
+    }
+
+    /// Returns the outer vertical list after running the input.
+    fn vertical_list(input: &str) -> Vec<Node> {
+        let s = run(input).unwrap();
+        assert_eq!(s.base().nest.levels().len(), 1);
+        testutil::outer_list(&s)
+    }
+
+    fn error(input: &str) -> String {
+        match run(input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn count_boxes(list: &[Node]) -> usize {
+        list.iter().filter(|node| node.as_box().is_some()).count()
+    }
+
+    fn recorded(input: &str) -> Vec<i32> {
+        run(input).unwrap().recorded
+    }
+
+    fn char(c: char) -> Node {
+        Node::Char(Char {
+            font: testutil::TEST_FONT,
+            c,
+        })
+    }
+
+    fn pt(n: i32) -> i32 {
+        Dimen::from_pt(n).0
+    }
+
+    #[test]
+    fn hbox_appended_in_vertical_mode() {
+        let list = vertical_list(r"\hbox{ab}");
+        assert_eq!(
+            list,
+            vec![
+                Node::new_param_glue(GlueParam::TopSkip, Glue::default()),
+                Node::HList(BoxNode {
+                    list: vec![char('a'), char('b')],
+                    ..Default::default()
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn hbox_to_sets_glue() {
+        let list = vertical_list(r"\spaceskip=1pt plus 2pt \hbox to 5pt{a b}");
+        let b = list[1].as_box().unwrap();
+        assert_eq!(b.width, Dimen::from_pt(5));
+        assert_eq!(b.glue_set.sign, GlueSign::Stretching);
+        assert_eq!(b.glue_set.ratio, 2.0);
+    }
+
+    #[test]
+    fn hbox_spread_sets_glue() {
+        let list = vertical_list(r"\spaceskip=4pt minus 2pt \hbox spread -1pt{a b}");
+        let b = list[1].as_box().unwrap();
+        assert_eq!(b.width, Dimen::from_pt(3));
+        assert_eq!(b.glue_set.sign, GlueSign::Shrinking);
+        assert_eq!(b.glue_set.ratio, 0.5);
+    }
+
+    #[test]
+    fn box_dimensions() {
+        let input = r"
+            \setbox1=\hbox{}
+            \record\wd1 \wd1=5pt \record\wd1
+            \ht1=2pt \dp1=1pt \record\ht1 \record\dp1
+            \wd2=3pt \record\wd2
+        ";
+        assert_eq!(recorded(input), vec![0, pt(5), pt(2), pt(1), 0]);
+    }
+
+    #[test]
+    fn box_dimension_assignment_is_not_undone_by_group() {
+        let input = r"\setbox1=\hbox{}{\wd1=5pt}\record\wd1";
+        assert_eq!(recorded(input), vec![pt(5)]);
+    }
+
+    #[test]
+    fn setbox_is_local_unless_global() {
+        let input = r"
+            {\setbox1=\hbox{}\wd1=1pt \record\wd1}\record\wd1
+            {\global\setbox1=\hbox{}\wd1=1pt}\record\wd1
+        ";
+        assert_eq!(recorded(input), vec![pt(1), 0, pt(1)]);
+    }
+
+    #[test]
+    fn box_voids_register_and_copy_does_not() {
+        let list = vertical_list(r"\setbox1=\hbox{a}\copy1\box1\box1\copy1");
+        assert_eq!(count_boxes(&list), 2);
+    }
+
+    #[test]
+    fn box_voids_register_in_place() {
+        let list = vertical_list(r"\setbox1=\hbox{a}{\box1}\box1");
+        assert_eq!(count_boxes(&list), 1);
+    }
+
+    #[test]
+    fn setbox_skips_spaces_and_relax() {
+        let input = r"\setbox1= \relax \hbox{}\wd1=1pt \record\wd1";
+        assert_eq!(recorded(input), vec![pt(1)]);
+    }
+
+    #[test]
+    fn setbox_requires_box() {
+        let err = error(r"\setbox1=a");
+        assert!(err.contains("A <box> was supposed to be here"));
+    }
+
+    #[test]
+    fn shipout() {
+        let s = run(r"\shipout\box3 \shipout \relax\hbox{}").unwrap();
+        // Shipping out a void box does nothing.
+        assert_eq!(testutil::terminal(&s), "[0]");
+        assert!(testutil::outer_list(&s).is_empty());
+        assert!(error(r"\shipout a").contains("A <box> was supposed to be here"));
+    }
+
+    #[test]
+    fn lastbox() {
+        let input = r"
+            \setbox3=\vbox{
+                \hbox{}\hbox{}
+                \setbox1=\lastbox \wd1=2pt \record\wd1
+                \hbox{\setbox1=\lastbox}
+            }
+        ";
+        let s = run(input).unwrap();
+        assert_eq!(s.recorded, vec![pt(2)]);
+        let b = s
+            .base()
+            .registers
+            .box_register(3)
+            .unwrap()
+            .as_box()
+            .unwrap();
+        assert_eq!(count_boxes(&b.list), 2);
+    }
+
+    #[test]
+    fn lastbox_in_empty_outer_vertical_list() {
+        let err = error(r"\setbox1=\lastbox");
+        assert!(err.contains("You can't use `\\lastbox'"));
+        // The box has moved to the current page.
+        let err = error(r"\hbox{}\setbox1=\lastbox");
+        assert!(err.contains("You can't use `\\lastbox'"));
+    }
+
+    #[test]
+    fn vbox_and_vtop() {
+        let input = r"
+            \baselineskip=0pt \lineskip=0pt \boxmaxdepth=100pt
+            \setbox1=\hbox{}\ht1=3pt \dp1=1pt
+            \setbox2=\vbox{\copy1\copy1}\record\ht2 \record\dp2
+            \setbox2=\vtop{\copy1\copy1}\record\ht2 \record\dp2
+        ";
+        assert_eq!(recorded(input), vec![pt(7), pt(1), pt(3), pt(5)]);
+    }
+
+    #[test]
+    fn vbox_ends_paragraph() {
+        let list = vertical_list(r"\vbox{\noindent a}");
+        let b = list[1].as_box().unwrap();
+        assert_eq!(b.list.len(), 1);
+        assert!(matches!(b.list[0], Node::HList(_)));
+    }
+
+    #[test]
+    fn everyhbox_and_everyvbox() {
+        let input = r"\everyhbox{\record1}\everyvbox{\record2}\hbox{}\vbox{\hbox{}}";
+        assert_eq!(recorded(input), vec![1, 2, 1]);
+    }
+
+    #[test]
+    fn everyhbox_is_inserted_inside_the_box() {
+        let list = vertical_list(r"\everyhbox{a}\hbox{b}");
+        assert_eq!(list[1].as_box().unwrap().list, vec![char('a'), char('b')]);
+        let input = r"\everyhbox{\parindent=7pt }\hbox{\record\parindent}\record\parindent";
+        assert_eq!(recorded(input), vec![pt(7), 0]);
+    }
+
+    #[test]
+    fn everyvbox_is_inserted_inside_the_box() {
+        for command in ["vbox", "vtop"] {
+            let list = vertical_list(&format!(
+                r"\everyvbox{{\hbox{{a}}}}\{}{{\hbox{{b}}}}",
+                command
+            ));
+            let b = list[1].as_box().unwrap();
+            let contents: Vec<Vec<Node>> = b
+                .list
+                .iter()
+                .filter_map(Node::as_box)
+                .map(|b| b.list.clone())
+                .collect();
+            assert_eq!(contents, vec![vec![char('a')], vec![char('b')]]);
+        }
+    }
+
+    #[test]
+    fn unhbox_begins_paragraph() {
+        let list = vertical_list(r"\parindent=0pt \setbox1=\hbox{ab}\unhbox1\par");
+        let line = list.iter().find_map(Node::as_box).unwrap();
+        assert_eq!(
+            line.list[..3],
+            [Node::HList(BoxNode::new()), char('a'), char('b')]
+        );
+    }
+
+    #[test]
+    fn unhcopy_keeps_register() {
+        let input = r"\setbox1=\hbox{}\wd1=1pt \noindent\unhcopy1\unhbox1 \record\wd1\par";
+        assert_eq!(recorded(input), vec![0]);
+        let input = r"\setbox1=\hbox{}\wd1=1pt \noindent\unhcopy1 \record\wd1\par";
+        assert_eq!(recorded(input), vec![pt(1)]);
+    }
+
+    #[test]
+    fn unvbox_ends_paragraph() {
+        let list = vertical_list(r"\setbox1=\vbox{\hbox{}\hbox{}}\noindent a\unvbox1");
+        assert_eq!(list.len(), 5);
+        assert!(matches!(list[2], Node::HList(_)));
+        assert!(matches!(list[4], Node::HList(_)));
+    }
+
+    #[test]
+    fn unvbox_in_restricted_horizontal_mode() {
+        let err = error(r"\setbox1=\vbox{}\hbox{\unvbox1}");
+        assert!(err.contains("You can't use `\\unvbox' in restricted horizontal mode"));
+    }
+
+    #[test]
+    fn incompatible_list() {
+        let err = error(r"\setbox1=\vbox{}\noindent\unhbox1");
+        assert!(err.contains("Incompatible list can't be unboxed"));
+        assert!(run(r"\noindent\unhbox1\par").is_ok());
+    }
+
+    #[test]
+    fn underfull_hbox() {
+        let s = run("\\spaceskip=1pt plus 1pt\n\\hbox to 100pt{a b}").unwrap();
+        let terminal = testutil::terminal(&s);
+        assert!(terminal.contains("Underfull \\hbox (badness 10000) detected at line 2"));
+        assert!(terminal.contains("a b"));
+    }
+
+    #[test]
+    fn overfull_hbox() {
+        let input = r"\hbadness=100 \setbox1=\hbox{}\wd1=5pt \hbox to 1pt{\box1}";
+        let s = run(input).unwrap();
+        assert!(
+            testutil::terminal(&s).contains("Overfull \\hbox (4.0pt too wide) detected at line 1")
+        );
+    }
+
+    #[test]
+    fn overfull_within_hfuzz() {
+        let input = r"\hbadness=100 \hfuzz=5pt \setbox1=\hbox{}\wd1=5pt \hbox to 1pt{\box1}";
+        let s = run(input).unwrap();
+        assert!(!testutil::terminal(&s).contains("Overfull"));
+    }
+
+    #[test]
+    fn underfull_vbox() {
+        let s = run(r"\vbox to 10pt{\hbox{}}").unwrap();
+        assert!(
+            testutil::terminal(&s).contains("Underfull \\vbox (badness 10000) detected at line 1")
+        );
+        let s = run(r"\vbadness=10000 \vbox to 10pt{\hbox{}}").unwrap();
+        assert!(!testutil::terminal(&s).contains("Underfull"));
+    }
+
+    #[test]
+    fn take_adjustments_from_list() {
+        let mut list = vec![
+            char('a'),
+            Node::Adjust(vec![Node::Penalty(1), Node::Penalty(2)]),
+            Node::new_glue(Glue::default()),
+        ];
+        assert_eq!(
+            take_adjustments(&mut list),
+            vec![Node::Penalty(1), Node::Penalty(2)]
+        );
+        assert_eq!(list, vec![char('a'), Node::new_glue(Glue::default())]);
+    }
+
+    #[test]
+    fn badness() {
+        let s = run(concat!(
+            r"\hbadness=10000 \hfuzz=100pt ",
+            r"\spaceskip=1pt plus 2pt \setbox1=\hbox{a b}\wd1=10pt ",
+            r"\showthe\badness",
+            r"\setbox2=\hbox to 2pt{a b}\showthe\badness",
+            r"\setbox2=\hbox to 3pt{a b}\showthe\badness",
+            r"\setbox2=\hbox to 0pt{\copy1}\showthe\badness",
+            r"\setbox2=\hbox to 20pt{\copy1}\showthe\badness",
+            r"\spaceskip=1pt minus 2pt \setbox2=\hbox to 0pt{a b}\showthe\badness",
+            r"\setbox2=\vbox to 5pt{}\showthe\badness",
+            r"\setbox2=\hbox{a b}\record\badness",
+        ))
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> 0.\n> 12.\n> 100.\n> 1000000.\n> 10000.\n> 12.\n> 0."
+        );
+        assert_eq!(s.recorded, vec![0]);
+    }
+
+    #[test]
+    fn badness_is_read_only() {
+        assert!(run(r"\badness=1 ").is_err());
+    }
+}
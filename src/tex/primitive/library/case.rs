@@ -0,0 +1,134 @@
+//! Case-changing primitives: `\uppercase` and `\lowercase`.
+//!
+//! These primitives read a balanced text without expansion and change the character code of
+//! each character token using the `\uccode` or `\lccode` table. Characters whose code is zero
+//! are unchanged, catcodes are always preserved, and control sequences are left untouched. The
+//! result is then read again from the input:
+//! ```tex
+//! \uppercase{\def\a{abc}}  % equivalent to \def\a{ABC}
+//! ```
+
+use crate::tex::charcode::CharCodes;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::state::TexState;
+use crate::tex::token::stream;
+use crate::tex::token::token::{Token, Value};
+
+static UPPERCASE_DOC: &str = "Convert a token list to uppercase using the \\uccode table";
+static LOWERCASE_DOC: &str = "Convert a token list to lowercase using the \\lccode table";
+
+fn uppercase_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let tokens = parse::parse_balanced_text(input)?;
+    let stream = change_case(&input.state().base().char_codes, tokens, CharCodes::uc_code);
+    input.push(stream);
+    Ok(())
+}
+
+fn lowercase_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let tokens = parse::parse_balanced_text(input)?;
+    let stream = change_case(&input.state().base().char_codes, tokens, CharCodes::lc_code);
+    input.push(stream);
+    Ok(())
+}
+
+/// Maps the character code of each character token through the provided case code table.
+pub fn change_case(
+    char_codes: &CharCodes,
+    mut tokens: Vec<Token>,
+    case_code: fn(&CharCodes, char) -> i32,
+) -> stream::VecStream {
+    for token in &mut tokens {
+        if let Value::Character(c, cat_code) = token.value {
+            // Case codes are validated when they are assigned, so the conversion succeeds.
+            match case_code(char_codes, c) {
+                0 => {}
+                code => {
+                    token.value =
+                        Value::Character(std::char::from_u32(code as u32).unwrap(), cat_code)
+                }
+            }
+        }
+    }
+    stream::VecStream::new(tokens)
+}
+
+pub fn get_uppercase<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("uppercase", uppercase_fn, UPPERCASE_DOC)
+}
+
+pub fn get_lowercase<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("lowercase", lowercase_fn, LOWERCASE_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<Vec<i32>> {
+        let mut s = testutil::new_state();
+        s.base_mut().char_codes.set_initex_defaults();
+        testutil::run(s, input).map(|s: TestState| s.recorded)
+    }
+
+    #[test]
+    fn uppercase_letters() {
+        assert_eq!(
+            run(r"\uppercase{\def\a{abC}}\def\b{ABC}\ifx\a\b\record1\else\record0\fi").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn lowercase_letters() {
+        assert_eq!(
+            run(r"\lowercase{\def\a{AbC1}}\def\b{abc1}\ifx\a\b\record1\else\record0\fi").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn control_sequences_unchanged() {
+        assert_eq!(run(r"\def\a{\record1}\uppercase{\a}").unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn catcodes_preserved() {
+        assert_eq!(
+            run(r"\lccode`\1=`\a \lowercase{\def\a{1}}\def\b{a}\ifx\a\b\record1\else\record0\fi")
+                .unwrap(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn custom_case_code() {
+        assert_eq!(
+            run(r"\lccode`\A=`\! \lowercase{\record`A}").unwrap(),
+            vec![33]
+        );
+    }
+
+    #[test]
+    fn implicit_begin_group() {
+        assert_eq!(
+            run(
+                r"\let\bgroup={\uppercase\bgroup\def\a{a}}\def\b{A}\ifx\a\b\record1\else\record0\fi"
+            )
+            .unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn missing_begin_group() {
+        assert!(run(r"\uppercase a").is_err());
+    }
+
+    #[test]
+    fn unterminated_text() {
+        assert!(run(r"\uppercase{a").is_err());
+    }
+}
@@ -0,0 +1,360 @@
+//! Primitives for reading and changing catcodes: `\catcode`, and LuaTeX's catcode table
+//! primitives `\initcatcodetable`, `\savecatcodetable` and `\catcodetable`.
+//!
+//! Catcode assignments write to the active catcode table of the input module, which is a scoped
+//! map, so they are rolled back at the end of the current group unless `\global` is used. The
+//! lexer consults the map for each character it reads, so an assignment takes effect starting
+//! with the next character that has not yet been read.
+//!
+//! Catcode tables allow switching between catcode regimes in a single assignment:
+//! ```tex
+//! \savecatcodetable 1       % save the current catcodes to table 1
+//! \catcode`\@=11            % modifies table 0, which is active
+//! \catcodetable 1           % activate table 1, in which @ still has catcode other
+//! ```
+//! Initializing and saving tables are global operations, while selecting a table with
+//! `\catcodetable` is a regular assignment that may be local or global.
+
+use crate::tex::error;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::state::TexState;
+use crate::tex::token::catcode;
+use crate::tex::token::catcode::RawCatCode;
+use crate::tex::token::token;
+
+static CATCODE_DOC: &str = "Get or set the catcode of a character";
+static INITCATCODETABLE_DOC: &str = "Initialize a catcode table with the IniTeX catcodes";
+static SAVECATCODETABLE_DOC: &str = "Save the current catcodes to a catcode table";
+static CATCODETABLE_DOC: &str = "Get or set the active catcode table";
+
+/// Reads a character code from the input.
+pub fn parse_char<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<usize> {
+    let n = parse::parse_number(input)?;
+    match std::char::from_u32(n as u32) {
+        Some(_) if n >= 0 => Ok(n as usize),
+        _ => Err(anyhow::anyhow!(
+            "Bad character code ({}); a character code must be a valid Unicode scalar value",
+            n
+        )),
+    }
+}
+
+fn to_char(index: usize) -> char {
+    // The index is validated by `parse_char`.
+    std::char::from_u32(index as u32).unwrap()
+}
+
+fn catcode_get<S: TexState<S>>(state: &S, index: usize) -> i32 {
+    let map = &state.base().input_module.cat_code_map;
+    catcode::or_default(map.get(&to_char(index))).int() as i32
+}
+
+fn catcode_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    let cat_code = match value {
+        0..=15 => RawCatCode::from_int(value as u8),
+        _ => None,
+    };
+    let cat_code = match cat_code {
+        None => {
+            return Err(anyhow::anyhow!(
+                "Invalid code ({}), should be between 0 and 15",
+                value
+            ))
+        }
+        Some(cat_code) => cat_code,
+    };
+    let map = &mut state.base_mut().input_module.cat_code_map;
+    match global {
+        true => map.insert_global(to_char(index), cat_code),
+        false => map.insert(to_char(index), cat_code),
+    }
+    Ok(())
+}
+
+pub fn get_catcode<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "catcode",
+        index: primitive::Index::Parsed(parse_char),
+        get_fn: catcode_get,
+        set_fn: Some(catcode_set),
+        docs: CATCODE_DOC,
+    }
+}
+
+/// Reads a catcode table number from the input.
+fn parse_cat_code_table<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<i32> {
+    let n = parse::parse_number(input)?;
+    if n < 0 {
+        return Err(anyhow::anyhow!(
+            "Invalid \\catcode table ({}); table numbers must be non-negative",
+            n
+        ));
+    }
+    Ok(n)
+}
+
+fn initcatcodetable_fn<S: TexState<S>>(
+    token: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let n = parse_cat_code_table(input)?;
+    input
+        .state_mut()
+        .base_mut()
+        .input_module
+        .init_cat_code_table(n, &catcode::initex_defaults())
+        .map_err(|err| error::new_token_error(token, err.to_string(), vec![]))
+}
+
+fn savecatcodetable_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let n = parse_cat_code_table(input)?;
+    input
+        .state_mut()
+        .base_mut()
+        .input_module
+        .save_cat_code_table(n);
+    Ok(())
+}
+
+fn catcodetable_set<S: TexState<S>>(
+    state: &mut S,
+    _: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    state
+        .base_mut()
+        .input_module
+        .select_cat_code_table(value, global)
+}
+
+pub fn get_initcatcodetable<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new(
+        "initcatcodetable",
+        initcatcodetable_fn,
+        INITCATCODETABLE_DOC,
+    )
+}
+
+pub fn get_savecatcodetable<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new(
+        "savecatcodetable",
+        savecatcodetable_fn,
+        SAVECATCODETABLE_DOC,
+    )
+}
+
+pub fn get_catcodetable<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "catcodetable",
+        index: primitive::Index::Fixed(0),
+        get_fn: |state: &S, _| state.base().input_module.cat_code_table(),
+        set_fn: Some(catcodetable_set),
+        docs: CATCODETABLE_DOC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<Vec<i32>> {
+        testutil::run(testutil::new_state(), input).map(|s: TestState| s.recorded)
+    }
+
+    #[test]
+    fn read_catcode() {
+        assert_eq!(
+            run(r"\record\catcode`\\\record\catcode`\A\record\catcode`\@").unwrap(),
+            vec![0, 11, 12]
+        );
+    }
+
+    #[test]
+    fn read_catcode_using_the() {
+        assert_eq!(run(r"\record\the\catcode 65").unwrap(), vec![11]);
+    }
+
+    #[test]
+    fn set_catcode() {
+        assert_eq!(run(r"\catcode`\@=11 \record\catcode`\@").unwrap(), vec![11]);
+    }
+
+    #[test]
+    fn set_catcode_without_equals() {
+        assert_eq!(run(r"\catcode`\@ 11 \record\catcode`\@").unwrap(), vec![11]);
+    }
+
+    #[test]
+    fn lexer_observes_new_catcode() {
+        assert_eq!(
+            run(r"\catcode`\@=11 \def\a@{\record 1}\a@").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn lexer_observes_new_catcode_on_next_character() {
+        assert_eq!(
+            run("\\catcode`\\A=14 A\\record 1\n\\record 2").unwrap(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn set_catcode_of_expanded_character() {
+        assert_eq!(
+            run(r"\catcode`\^^A=11 \def\a^^A{\record 1}\record\catcode 1 \a^^01").unwrap(),
+            vec![11, 1]
+        );
+    }
+
+    #[test]
+    fn catcode_assignment_is_local() {
+        assert_eq!(
+            run(r"{\catcode`\@=11 \record\catcode`\@}\record\catcode`\@").unwrap(),
+            vec![11, 12]
+        );
+    }
+
+    #[test]
+    fn global_catcode_assignment() {
+        assert_eq!(
+            run(r"{\global\catcode`\@=11 }\record\catcode`\@").unwrap(),
+            vec![11]
+        );
+    }
+
+    #[test]
+    fn global_def() {
+        assert_eq!(run(r"{\global\def\a{\record 3}}\a").unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn global_with_non_assignment() {
+        assert!(run(r"\global a").is_err());
+    }
+
+    #[test]
+    fn invalid_catcode() {
+        assert!(run(r"\catcode`\@=16 ").is_err());
+    }
+
+    #[test]
+    fn invalid_character_code() {
+        assert!(run(r"\catcode-1=12 ").is_err());
+    }
+
+    #[test]
+    fn initial_catcode_table() {
+        assert_eq!(run(r"\record\catcodetable").unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn save_and_select_catcode_table() {
+        assert_eq!(
+            run(r"\savecatcodetable 1 \catcode`\@=11 \record\catcode`\@\catcodetable 1 \record\catcode`\@\record\catcodetable")
+                .unwrap(),
+            vec![11, 12, 1]
+        );
+    }
+
+    #[test]
+    fn changes_to_active_table_persist() {
+        assert_eq!(
+            run(r"\savecatcodetable 1 \catcodetable 1 \catcode`\@=11 \catcodetable 0 \record\catcode`\@\catcodetable 1 \record\catcode`\@")
+                .unwrap(),
+            vec![12, 11]
+        );
+    }
+
+    #[test]
+    fn init_catcode_table() {
+        assert_eq!(
+            run(r"\initcatcodetable 2 \catcodetable 2 \record\catcode`\{\record\catcode`\A\record\catcode`\\").unwrap(),
+            vec![12, 11, 0]
+        );
+    }
+
+    #[test]
+    fn init_active_catcode_table() {
+        assert!(run(r"\initcatcodetable 0 ").is_err());
+        assert!(run(r"\savecatcodetable 1 \catcodetable 1 {\initcatcodetable 1 }").is_err());
+    }
+
+    #[test]
+    fn init_inactive_catcode_table_in_group() {
+        // The initialization is global, and local changes to the selected table are rolled back.
+        assert_eq!(
+            run(r"\savecatcodetable 1 \catcode`\@=11 {\catcodetable 1 \initcatcodetable 0 \catcode`\@=11 }\record\catcodetable\record\catcode`\@\catcodetable 1 \record\catcode`\@")
+                .unwrap(),
+            vec![0, 12, 12]
+        );
+    }
+
+    #[test]
+    fn lexer_observes_new_catcode_table() {
+        assert_eq!(
+            run(r"\catcode`\@=11 \savecatcodetable 1 \catcode`\@=12 \catcodetable 1 \def\a@{\record 1}\a@").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn catcode_table_selection_is_local() {
+        assert_eq!(
+            run(r"\savecatcodetable 1 {\catcodetable 1 \record\catcodetable}\record\catcodetable")
+                .unwrap(),
+            vec![1, 0]
+        );
+    }
+
+    #[test]
+    fn global_catcode_table_selection() {
+        assert_eq!(
+            run(r"\savecatcodetable 1 {{\global\catcodetable 1 }}\record\catcodetable").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn local_changes_to_inactive_table_rolled_back() {
+        assert_eq!(
+            run(r"\savecatcodetable 1 {\catcodetable 1 \catcode`\@=11 }\catcodetable 1 \record\catcode`\@").unwrap(),
+            vec![12]
+        );
+    }
+
+    #[test]
+    fn catcode_table_saved_in_group_survives() {
+        assert_eq!(
+            run(r"{\catcode`\@=11 \savecatcodetable 1 }\catcodetable 1 \record\catcode`\@")
+                .unwrap(),
+            vec![11]
+        );
+    }
+
+    #[test]
+    fn select_undefined_catcode_table() {
+        assert!(run(r"\catcodetable 3 ").is_err());
+    }
+
+    #[test]
+    fn unicode_defaults_control_sequence_names() {
+        let mut s = testutil::new_state();
+        catcode::set_unicode_defaults(&mut s.base_mut().input_module.cat_code_map);
+        let s = testutil::run(s, r"\def\défini{\record 1}\def\λ{\record 2}\défini\λ").unwrap();
+        assert_eq!(s.recorded, vec![1, 2]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Primitives for reading and changing the per-character code tables: `\lccode`, `\uccode`,
+//! `\sfcode`, `\mathcode` and `\delcode`.
+//!
+//! Like catcode assignments, assignments to these tables are rolled back at the end of the
+//! current group unless `\global` is used.
+//!
+//! A math code is `"8000`, which makes the character active in math mode, or a math character
+//! `"cfxx` with class `c`, family `f` and position `xx`. A delimiter code is negative for
+//! characters that are not delimiters, or `"fxxgyy`, the small variant `xx` in family `f`
+//! followed by the large variant `yy` in family `g`:
+//! ```tex
+//! \mathcode`\+="202B
+//! \delcode`\(="028300
+//! ```
+
+use crate::datastructures::scopedmap::ScopedMap;
+use crate::tex::primitive;
+use crate::tex::primitive::library::catcode::parse_char;
+use crate::tex::state::TexState;
+
+static LCCODE_DOC: &str = "Get or set the lowercase code of a character";
+static UCCODE_DOC: &str = "Get or set the uppercase code of a character";
+static SFCODE_DOC: &str = "Get or set the space factor code of a character";
+static MATHCODE_DOC: &str = "Get or set the math code of a character";
+static DELCODE_DOC: &str = "Get or set the delimiter code of a character";
+
+/// The largest valid space factor code.
+const MAX_SF_CODE: i32 = 32767;
+
+/// The largest valid math code, which makes the character active in math mode.
+const MAX_MATH_CODE: i32 = 0x8000;
+
+/// The largest valid delimiter code.
+const MAX_DEL_CODE: i32 = 0xFFFFFF;
+
+fn to_char(index: usize) -> char {
+    // The index is validated by `parse_char`.
+    std::char::from_u32(index as u32).unwrap()
+}
+
+fn set(
+    map: &mut ScopedMap<char, i32>,
+    index: usize,
+    value: i32,
+    min: i32,
+    max: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    if value < min || value > max {
+        return Err(anyhow::anyhow!(
+            "Invalid code ({}), should be between {} and {}",
+            value,
+            min,
+            max
+        ));
+    }
+    match global {
+        true => map.insert_global(to_char(index), value),
+        false => map.insert(to_char(index), value),
+    }
+    Ok(())
+}
+
+fn lccode_get<S: TexState<S>>(state: &S, index: usize) -> i32 {
+    state.base().char_codes.lc_code(to_char(index))
+}
+
+fn lccode_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().char_codes.lc_codes;
+    set(map, index, value, 0, char::MAX as i32, global)
+}
+
+fn uccode_get<S: TexState<S>>(state: &S, index: usize) -> i32 {
+    state.base().char_codes.uc_code(to_char(index))
+}
+
+fn uccode_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().char_codes.uc_codes;
+    set(map, index, value, 0, char::MAX as i32, global)
+}
+
+fn sfcode_get<S: TexState<S>>(state: &S, index: usize) -> i32 {
+    state.base().char_codes.sf_code(to_char(index))
+}
+
+fn sfcode_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().char_codes.sf_codes;
+    set(map, index, value, 0, MAX_SF_CODE, global)
+}
+
+fn mathcode_get<S: TexState<S>>(state: &S, index: usize) -> i32 {
+    state.base().char_codes.math_code(to_char(index))
+}
+
+fn mathcode_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().char_codes.math_codes;
+    set(map, index, value, 0, MAX_MATH_CODE, global)
+}
+
+fn delcode_get<S: TexState<S>>(state: &S, index: usize) -> i32 {
+    state.base().char_codes.del_code(to_char(index))
+}
+
+fn delcode_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().char_codes.del_codes;
+    // As in TeX, any negative delimiter code is valid.
+    set(map, index, value, i32::MIN, MAX_DEL_CODE, global)
+}
+
+pub fn get_lccode<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "lccode",
+        index: primitive::Index::Parsed(parse_char),
+        get_fn: lccode_get,
+        set_fn: Some(lccode_set),
+        docs: LCCODE_DOC,
+    }
+}
+
+pub fn get_uccode<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "uccode",
+        index: primitive::Index::Parsed(parse_char),
+        get_fn: uccode_get,
+        set_fn: Some(uccode_set),
+        docs: UCCODE_DOC,
+    }
+}
+
+pub fn get_sfcode<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "sfcode",
+        index: primitive::Index::Parsed(parse_char),
+        get_fn: sfcode_get,
+        set_fn: Some(sfcode_set),
+        docs: SFCODE_DOC,
+    }
+}
+
+pub fn get_mathcode<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "mathcode",
+        index: primitive::Index::Parsed(parse_char),
+        get_fn: mathcode_get,
+        set_fn: Some(mathcode_set),
+        docs: MATHCODE_DOC,
+    }
+}
+
+pub fn get_delcode<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "delcode",
+        index: primitive::Index::Parsed(parse_char),
+        get_fn: delcode_get,
+        set_fn: Some(delcode_set),
+        docs: DELCODE_DOC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<Vec<i32>> {
+        let mut s = testutil::new_state();
+        s.base_mut().char_codes.set_initex_defaults();
+        testutil::run(s, input).map(|s: TestState| s.recorded)
+    }
+
+    #[test]
+    fn read_defaults() {
+        assert_eq!(
+            run(r"\record\lccode`\A\record\uccode`\a\record\sfcode`\A\record\sfcode`\.\record\lccode`\.").unwrap(),
+            vec![97, 65, 999, 1000, 0]
+        );
+    }
+
+    #[test]
+    fn set_codes() {
+        assert_eq!(
+            run(r"\lccode`\.=`\, \sfcode`\.=3000 \record\lccode`\.\record\sfcode`\.").unwrap(),
+            vec![44, 3000]
+        );
+    }
+
+    #[test]
+    fn assignment_is_local() {
+        assert_eq!(
+            run(r"{\sfcode`\.=3000 }\record\sfcode`\.").unwrap(),
+            vec![1000]
+        );
+    }
+
+    #[test]
+    fn invalid_sfcode() {
+        assert!(run(r"\sfcode`\.=32768 ").is_err());
+    }
+
+    #[test]
+    fn invalid_lccode() {
+        assert!(run(r"\lccode`\.=-1 ").is_err());
+    }
+
+    #[test]
+    fn math_and_delimiter_codes() {
+        assert_eq!(
+            run(r#"\record\mathcode`\a\record\delcode`\.\record\delcode`\(
+                \mathcode`\+="202B \delcode`\(="028300 \delcode`\.=-2
+                \record\mathcode`\+\record\delcode`\(\record\delcode`\."#)
+            .unwrap(),
+            vec![0x7161, 0, -1, 0x202B, 0x028300, -2]
+        );
+    }
+
+    #[test]
+    fn invalid_math_and_delimiter_codes() {
+        assert!(run(r#"\mathcode`\a="8001 "#).is_err());
+        assert!(run(r"\mathcode`\a=-1 ").is_err());
+        assert!(run(r#"\delcode`\a="1000000 "#).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Primitives for character codes: `\chardef`, `\mathchardef` and `\char`.
+//!
+//! `\chardef` and `\mathchardef` bind a control sequence to a constant integer. The control
+//! sequence can then be used wherever a number is expected, and `\the` produces its value:
+//! ```tex
+//! \chardef\active=13
+//! \catcode`\~=\active   % the same as \catcode`\~=13
+//! \mathchardef\alpha="10B
+//! ```
+//! When executed, a control sequence defined using `\chardef` typesets its character, just like
+//! `\char` does.
+
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::{catcode, def, prefix};
+use crate::tex::primitive::{ConstantKind, Primitive};
+use crate::tex::state::TexState;
+use crate::tex::token::stream;
+use crate::tex::token::token::Token;
+
+static CHARDEF_DOC: &str = "Define a control sequence to be a character code";
+static MATHCHARDEF_DOC: &str = "Define a control sequence to be a math character code";
+static CHAR_DOC: &str = "Typeset the character with the provided code";
+
+/// The largest math character code, `"7FFF`. The code `"8000`, which makes a character behave
+/// like an active character in math mode, can't be used in `\mathchardef`.
+const MAX_MATH_CHAR: i32 = 0x7FFF;
+
+fn chardef_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let target = def::parse_target(&token, input)?;
+    parse::parse_optional_equals(input)?;
+    let c = catcode::parse_char(input)?;
+    define(input, target, ConstantKind::Char, c as i32);
+    Ok(())
+}
+
+fn mathchardef_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let target = def::parse_target(&token, input)?;
+    parse::parse_optional_equals(input)?;
+    let n = parse::parse_number(input)?;
+    if !(0..=MAX_MATH_CHAR).contains(&n) {
+        return Err(anyhow::anyhow!(
+            "Bad mathchar ({}); a math character code must be between 0 and {}",
+            n,
+            MAX_MATH_CHAR
+        ));
+    }
+    define(input, target, ConstantKind::MathChar, n);
+    Ok(())
+}
+
+fn define<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    target: primitive::Name,
+    kind: ConstantKind,
+    n: i32,
+) {
+    let base = input.state_mut().base_mut();
+    base.primitives.set(
+        target,
+        Some(Primitive::Constant(kind, n)),
+        base.global_prefix,
+    );
+}
+
+fn char_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let c = catcode::parse_char(input)?;
+    // The character is typeset like a character with catcode other, so that for example
+    // \char`\{ doesn't begin a group.
+    // The code was validated by `parse_char`.
+    let c = char::from_u32(c as u32).unwrap();
+    input.push(stream::VecStream::new(vec![Token::new_other(c)]));
+    Ok(())
+}
+
+pub fn get_chardef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("chardef", chardef_fn, CHARDEF_DOC)
+}
+
+pub fn get_mathchardef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("mathchardef", mathchardef_fn, MATHCHARDEF_DOC)
+}
+
+pub fn get_char<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("char", char_fn, CHAR_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        testutil::run(testutil::new_state(), input)
+    }
+
+    #[test]
+    fn chardef_is_a_number() {
+        let s = run(r"\chardef\active=13 \count1=\active\record\count1\record\active").unwrap();
+        assert_eq!(s.recorded, vec![13, 13]);
+    }
+
+    #[test]
+    fn chardef_in_catcode_assignment() {
+        let s = run(r"\chardef\other 12 \catcode`\b=\other\record\catcode`\b").unwrap();
+        assert_eq!(s.recorded, vec![12]);
+    }
+
+    #[test]
+    fn the_and_show() {
+        let s =
+            run(r"\chardef\a=`A \mathchardef\b=7000 \showthe\a\showthe\b\show\a\show\b").unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> 65.\n> 7000.\n> \\a=\\char\"41.\n> \\b=\\mathchar\"1B58."
+        );
+    }
+
+    #[test]
+    fn chardef_is_local_unless_global() {
+        let s = run(r"{\chardef\a=1 \global\chardef\b=2 }\show\a\show\b").unwrap();
+        assert_eq!(testutil::terminal(&s), "> \\a=undefined.\n> \\b=\\char\"2.");
+    }
+
+    #[test]
+    fn same_meaning() {
+        let s = run(
+            r"\chardef\a=1 \chardef\b=1 \mathchardef\c=1 \chardef\d=2 \ifx\a\b\record1\fi\ifx\a\c\record2\fi\ifx\a\d\record3\fi",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, vec![1]);
+    }
+
+    #[test]
+    fn char_does_not_begin_a_group() {
+        let s = run(r"\char`\{\chardef\b=`\{ \b").unwrap();
+        assert_eq!(s.base().group_level(), 0);
+    }
+
+    #[test]
+    fn mathchar_outside_math_mode() {
+        let err = run(r#"\mathchardef\alpha="010B \alpha"#).err().unwrap();
+        assert!(err.to_string().contains("Missing $ inserted"), "{}", err);
+        let s = run(r#"\mathchardef\alpha="010B $\alpha$"#).unwrap();
+        assert_eq!(testutil::terminal(&s), "");
+    }
+
+    #[test]
+    fn bad_codes() {
+        assert!(run(r"\chardef\a=-1 ").is_err());
+        assert!(run(r"\char-1 ").is_err());
+        assert!(run(r"\mathchardef\a=32768 ").is_err());
+        assert!(run(r"\mathchardef\a=-1 ").is_err());
+    }
+}
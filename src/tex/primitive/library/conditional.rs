@@ -1,82 +1,580 @@
 //! Conditional primitives
+//!
+//! Conditionals in TeX are expansion primitives. When a conditional like `\ifx` is expanded,
+//! the condition is evaluated. If it is true, the conditional expands to nothing and a true
+//! branch is recorded in the state. When the matching `\else` is later expanded, the tokens up to
+//! the matching `\fi` are skipped. If the condition is false, the tokens up to the matching
+//! `\else` or `\fi` are skipped immediately.
+//!
+//! `\ifcase` chooses between any number of cases separated by `\or`. The cases before the
+//! chosen one are skipped immediately, and the cases after it are skipped when the `\or` that
+//! ends it is expanded.
+//!
+//! Conditionals, `\or`, `\else` and `\fi` are identified while skipping using their ids, so that
+//! nested conditionals in skipped tokens are matched correctly even if the primitives
+//! have been renamed using `\let`. Outer macros cannot appear in skipped tokens.
 
+use crate::tex::error;
+use crate::tex::macros;
+use crate::tex::parse;
 use crate::tex::primitive;
-use crate::tex::primitive::ExpansionGeneric;
+use crate::tex::primitive::library::read;
+use crate::tex::primitive::{ExpansionGeneric, Primitive};
 
 use crate::tex::state::TexState;
+use crate::tex::token::catcode::CatCode;
 use crate::tex::token::stream;
-use crate::tex::token::stream::Stream;
+use crate::tex::token::token;
 use crate::tex::token::token::Value;
 use std::any;
 use std::any::TypeId;
 
 struct If;
+struct Or;
 struct Else;
 struct Fi;
 
-fn IfF<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<stream::VecStream> {
-    while let Some(token) = input.unexpanded_stream().next()? {
-        if let Value::ControlSequence(_, name) = token.value {
-            if let Some(c) = input.state().get_expansion_primitive(&name) {
-                // TODO: switch on If, Else and Fi
-                if Some(any::TypeId::of::<Else>()) == c.id() {
-                    return Ok(stream::VecStream::new_empty());
-                }
+/// A branch of a conditional that is currently being expanded.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Branch {
+    /// The true branch of a conditional; i.e., the tokens before the `\else`.
+    True,
+    /// A case of an `\ifcase`; i.e., the tokens before the next `\or` or `\else`.
+    Case,
+    /// The false branch of a conditional; i.e., the tokens after the `\else`.
+    Else,
+}
+
+fn if_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let a = char_and_cat_code(input)?;
+    let b = char_and_cat_code(input)?;
+    branch(input, a.0 == b.0)
+}
+
+fn ifcat_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let a = char_and_cat_code(input)?;
+    let b = char_and_cat_code(input)?;
+    branch(input, a.1 == b.1)
+}
+
+fn ifx_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let a = meaning(input)?;
+    let b = meaning(input)?;
+    let condition = match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => a.same_meaning(&b),
+        _ => false,
+    };
+    branch(input, condition)
+}
+
+fn ifeof_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let stream = read::parse_stream(input)?;
+    let condition = !input.state().base().input_files.is_open(stream);
+    branch(input, condition)
+}
+
+fn ifnum_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let a = parse::parse_number(input)?;
+    let relation = parse_relation(input, "\\ifnum")?;
+    let b = parse::parse_number(input)?;
+    branch(input, relation == a.cmp(&b))
+}
+
+fn ifdim_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let a = parse::parse_dimen(input)?;
+    let relation = parse_relation(input, "\\ifdim")?;
+    let b = parse::parse_dimen(input)?;
+    branch(input, relation == a.0.cmp(&b.0))
+}
+
+fn ifodd_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let n = parse::parse_number(input)?;
+    branch(input, n % 2 != 0)
+}
+
+fn iftrue_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    branch(input, true)
+}
+
+fn iffalse_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    branch(input, false)
+}
+
+fn ifcase_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let mut n = parse::parse_number(input)?;
+    while n > 0 {
+        match skip(input, true, true)? {
+            Stop::Or => n -= 1,
+            Stop::Else => {
+                input.state_mut().base_mut().branches.push(Branch::Else);
+                return Ok(stream::VecStream::new_empty());
             }
+            Stop::Fi => return Ok(stream::VecStream::new_empty()),
         }
     }
-    // TODO: end of the stream, ran out, should return an unexpected end of input error
+    // A negative number selects the \else branch, if there is one.
+    if n < 0 {
+        return branch(input, false);
+    }
+    input.state_mut().base_mut().branches.push(Branch::Case);
     Ok(stream::VecStream::new_empty())
 }
 
-impl<S: TexState<S>> primitive::ExpansionGeneric<S> for If {
-    fn call(&self, input: &mut primitive::Input<S>) -> anyhow::Result<stream::VecStream> {
-        while let Some(token) = input.unexpanded_stream().next()? {
-            if let Value::ControlSequence(_, name) = token.value {
-                if let Some(c) = input.state().get_expansion_primitive(&name) {
-                    // TODO: switch on If, Else and Fi
-                    if Some(any::TypeId::of::<Else>()) == c.id() {
-                        return Ok(stream::VecStream::new_empty());
-                    }
-                }
-            }
+/// Parses the relation `<`, `=` or `>` of `\ifnum` and `\ifdim`, skipping spaces before it.
+fn parse_relation<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    name: &str,
+) -> anyhow::Result<std::cmp::Ordering> {
+    loop {
+        let token = next_or_error(input.stream())?;
+        return match token.value {
+            Value::Character(_, CatCode::Space) => continue,
+            Value::Character('<', CatCode::Other) => Ok(std::cmp::Ordering::Less),
+            Value::Character('=', CatCode::Other) => Ok(std::cmp::Ordering::Equal),
+            Value::Character('>', CatCode::Other) => Ok(std::cmp::Ordering::Greater),
+            _ => Err(error::new_token_error(
+                token,
+                format!("Missing = inserted for {}", name),
+                vec!["I was expecting to see `<', `=', or `>'".to_string()],
+            )),
+        };
+    }
+}
+
+fn or_fn<S: TexState<S>>(
+    token: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    // Only a case of an \ifcase ends at an \or.
+    match input.state().base().branches.last() {
+        Some(Branch::Case) => {
+            input.state_mut().base_mut().branches.pop();
+            skip(input, false, false)?;
+            Ok(stream::VecStream::new_empty())
         }
-        // TODO: end of the stream, ran out, should return an unexpected end of input error
-        Ok(stream::VecStream::new_empty())
+        _ => Err(error::new_token_error(
+            token,
+            "Extra \\or".to_string(),
+            vec!["there is no \\ifcase for this \\or to match".to_string()],
+        )),
     }
 }
 
-impl<State> primitive::ExpansionGeneric<State> for Else {
-    fn call(&self, _: &mut primitive::Input<State>) -> anyhow::Result<stream::VecStream> {
-        Ok(stream::VecStream::new(vec![]))
+fn else_fn<S: TexState<S>>(
+    token: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    match input.state().base().branches.last() {
+        Some(Branch::True) | Some(Branch::Case) => {
+            input.state_mut().base_mut().branches.pop();
+            skip(input, false, false)?;
+            Ok(stream::VecStream::new_empty())
+        }
+        _ => Err(error::new_token_error(
+            token,
+            "Extra \\else".to_string(),
+            vec!["there is no conditional for this \\else to match".to_string()],
+        )),
     }
+}
 
-    fn id(&self) -> Option<TypeId> {
-        return Some(any::TypeId::of::<Else>());
+fn fi_fn<S: TexState<S>>(
+    token: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    match input.state_mut().base_mut().branches.pop() {
+        Some(_) => Ok(stream::VecStream::new_empty()),
+        None => Err(error::new_token_error(
+            token,
+            "Extra \\fi".to_string(),
+            vec!["there is no conditional for this \\fi to match".to_string()],
+        )),
     }
 }
 
-impl<State> primitive::ExpansionGeneric<State> for Fi {
-    fn call(&self, _: &mut primitive::Input<State>) -> anyhow::Result<stream::VecStream> {
-        Ok(stream::VecStream::new(vec![]))
+fn branch<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    condition: bool,
+) -> anyhow::Result<stream::VecStream> {
+    if condition {
+        input.state_mut().base_mut().branches.push(Branch::True);
+    } else if skip(input, true, false)? == Stop::Else {
+        input.state_mut().base_mut().branches.push(Branch::Else);
     }
+    Ok(stream::VecStream::new_empty())
 }
 
-static IF_DOC: &str = "";
+/// The token at which skipping stopped.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Stop {
+    Or,
+    Else,
+    Fi,
+}
 
-pub fn get_if<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
-    primitive::ExpansionStatic {
-        call_fn: IfF,
-        docs: IF_DOC,
-        id: Some(any::TypeId::of::<Else>()),
+/// Skips unexpanded tokens up to the matching `\fi` or, if `stop_at_else` is true, the matching
+/// `\else` or, if `stop_at_or` is true, the matching `\or`. Returns the token skipping stopped at.
+fn skip<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    stop_at_else: bool,
+    stop_at_or: bool,
+) -> anyhow::Result<Stop> {
+    let mut depth = 0_usize;
+    while let Some(token) = input.unexpanded_stream().next()? {
+        if macros::is_outer(input, &token) {
+            return Err(error::new_token_error(
+                token,
+                "Incomplete conditional; all text was ignored".to_string(),
+                vec![
+                    "an outer macro cannot appear in skipped text; is a \\fi missing?".to_string(),
+                ],
+            ));
+        }
+        let id = match input.state().base().primitives.get(&token.value) {
+            Some(Primitive::Expansion(e)) => e.id(),
+            _ => None,
+        };
+        if id == Some(any::TypeId::of::<If>()) {
+            depth += 1;
+        } else if id == Some(any::TypeId::of::<Fi>()) {
+            if depth == 0 {
+                return Ok(Stop::Fi);
+            }
+            depth -= 1;
+        } else if id == Some(any::TypeId::of::<Else>()) && depth == 0 && stop_at_else {
+            return Ok(Stop::Else);
+        } else if id == Some(any::TypeId::of::<Or>()) && depth == 0 && stop_at_or {
+            return Ok(Stop::Or);
+        }
     }
-    //return If {};
+    Err(anyhow::anyhow!(
+        "Unexpected end of input: incomplete conditional; all text was ignored"
+    ))
+}
+
+/// Reads the next token with expansion and returns its character code and catcode, as used by
+/// `\if` and `\ifcat`. Control sequences that are not implicit characters have neither.
+fn char_and_cat_code<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<(Option<char>, Option<CatCode>)> {
+    let token = next_or_error(input.stream())?;
+    Ok(match input.state().base().meaning(&token.value) {
+        Some(Primitive::Character(c, cat_code)) => (Some(c), Some(cat_code)),
+        // An active character that is not an implicit character has its own character code and
+        // the active catcode.
+        _ => match token.value {
+            Value::Character(c, CatCode::Active) => (Some(c), Some(CatCode::Active)),
+            _ => (None, None),
+        },
+    })
 }
 
-pub fn get_else<State>() -> impl primitive::ExpansionGeneric<State> {
-    return Else {};
+/// Reads the next token without expansion and returns its meaning, as used by `\ifx`.
+/// Undefined control sequences have no meaning.
+fn meaning<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Option<Primitive<S>>> {
+    let token = next_or_error(input.unexpanded_stream())?;
+    Ok(input.state().base().meaning(&token.value))
 }
 
-pub fn get_fi<State>() -> impl primitive::ExpansionGeneric<State> {
-    return Fi {};
+fn next_or_error(stream: &mut dyn stream::Stream) -> anyhow::Result<token::Token> {
+    match stream.next()? {
+        None => Err(anyhow::anyhow!(
+            "Unexpected end of input: expected a token to compare"
+        )),
+        Some(token) => Ok(token),
+    }
+}
+
+static IF_DOC: &str = "Compare the character codes of the next two tokens";
+static IFCAT_DOC: &str = "Compare the catcodes of the next two tokens";
+static IFX_DOC: &str = "Compare the meanings of the next two unexpanded tokens";
+static IFEOF_DOC: &str = "Test whether an input stream is not open";
+static IFNUM_DOC: &str = "Compare two integers";
+static IFDIM_DOC: &str = "Compare two dimensions";
+static IFODD_DOC: &str = "Test whether an integer is odd";
+static IFTRUE_DOC: &str = "Begin a conditional whose condition is always true";
+static IFFALSE_DOC: &str = "Begin a conditional whose condition is always false";
+static IFCASE_DOC: &str = "Choose between cases separated by \\or using an integer";
+static OR_DOC: &str = "Begin the next case of an \\ifcase";
+static ELSE_DOC: &str = "Begin the false branch of a conditional";
+static FI_DOC: &str = "End a conditional";
+
+fn get_conditional<S: TexState<S>>(
+    name: &'static str,
+    call_fn: fn(token::Token, &mut primitive::Input<S>) -> anyhow::Result<stream::VecStream>,
+    docs: &'static str,
+) -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new(name, call_fn, docs).with_id(any::TypeId::of::<If>())
+}
+
+pub fn get_if<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("if", if_fn, IF_DOC)
+}
+
+pub fn get_ifcat<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("ifcat", ifcat_fn, IFCAT_DOC)
+}
+
+pub fn get_ifx<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("ifx", ifx_fn, IFX_DOC)
+}
+
+pub fn get_ifeof<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("ifeof", ifeof_fn, IFEOF_DOC)
+}
+
+pub fn get_ifnum<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("ifnum", ifnum_fn, IFNUM_DOC)
+}
+
+pub fn get_ifdim<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("ifdim", ifdim_fn, IFDIM_DOC)
+}
+
+pub fn get_ifodd<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("ifodd", ifodd_fn, IFODD_DOC)
+}
+
+pub fn get_iftrue<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("iftrue", iftrue_fn, IFTRUE_DOC)
+}
+
+pub fn get_iffalse<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("iffalse", iffalse_fn, IFFALSE_DOC)
+}
+
+pub fn get_ifcase<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    get_conditional("ifcase", ifcase_fn, IFCASE_DOC)
+}
+
+pub fn get_or<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("or", or_fn, OR_DOC).with_id(TypeId::of::<Or>())
+}
+
+pub fn get_else<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("else", else_fn, ELSE_DOC).with_id(TypeId::of::<Else>())
+}
+
+pub fn get_fi<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("fi", fi_fn, FI_DOC).with_id(TypeId::of::<Fi>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::primitive::Execution;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn nothing_fn(_: token::Token, _: &mut primitive::Input<TestState>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn run(input: &str) -> anyhow::Result<Vec<i32>> {
+        let mut s = testutil::new_state();
+        for (name, p) in [
+            // Two primitives that share a call function but are different primitives.
+            (
+                "nothingA",
+                primitive::ExecutionStatic::new("nothingA", nothing_fn, ""),
+            ),
+            (
+                "nothingB",
+                primitive::ExecutionStatic::new("nothingB", nothing_fn, ""),
+            ),
+        ] {
+            s.set_primitive(name, Primitive::Execution(Execution::Static(p)));
+        }
+        testutil::run(s, input).map(|s: TestState| s.recorded)
+    }
+
+    macro_rules! conditional_test {
+        ($name: ident, $input: expr, $expected: expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!(
+                    run(&format!(r"{}\record1\else\record0\fi", $input)).unwrap(),
+                    vec![$expected]
+                );
+            }
+        };
+    }
+
+    conditional_test!(if_same_character, r"\if aa", 1);
+    conditional_test!(if_different_characters, r"\if ab", 0);
+    conditional_test!(if_ignores_catcode, r"\if a1", 0);
+    conditional_test!(if_implicit_character, r"\let\a=a\if\a a", 1);
+    conditional_test!(if_two_control_sequences, r"\if\record\record", 1);
+    conditional_test!(ifcat_same_catcode, r"\ifcat ab", 1);
+    conditional_test!(ifcat_different_catcodes, r"\ifcat a1", 0);
+    conditional_test!(ifcat_implicit_character, r"\let\b={\ifcat\b{", 1);
+    conditional_test!(ifcat_implicit_and_explicit, r"\let\b={\ifcat\b}", 0);
+    conditional_test!(ifx_same_character, r"\ifx aa", 1);
+    conditional_test!(ifx_same_character_different_catcode, r"\ifx a1", 0);
+    conditional_test!(ifx_same_primitive, r"\ifx\record\record", 1);
+    conditional_test!(ifx_different_primitives, r"\ifx\record\let", 0);
+    conditional_test!(ifx_primitives_with_same_body, r"\ifx\long\outer", 0);
+    conditional_test!(
+        ifx_primitives_with_same_call_fn,
+        r"\ifx\nothingA\nothingB",
+        0
+    );
+    conditional_test!(
+        ifx_variables_with_same_get_fn,
+        r"\ifx\pagegoal\pagetotal",
+        0
+    );
+    conditional_test!(ifx_parameters, r"\let\a\tolerance\ifx\a\tolerance", 1);
+    conditional_test!(ifx_different_parameters, r"\ifx\tolerance\pretolerance", 0);
+    conditional_test!(
+        ifx_same_register,
+        r"\countdef\a=5 \countdef\b=5 \ifx\a\b",
+        1
+    );
+    conditional_test!(
+        ifx_different_registers,
+        r"\countdef\a=5 \countdef\b=6 \ifx\a\b",
+        0
+    );
+    conditional_test!(ifx_register_and_count, r"\countdef\a=5 \ifx\a\count", 0);
+    conditional_test!(
+        ifx_count_and_dimen_registers,
+        r"\countdef\a=5 \dimendef\b=5 \ifx\a\b",
+        0
+    );
+    conditional_test!(ifx_same_macros, r"\def\a#1{x}\def\b#1{x}\ifx\a\b", 1);
+    conditional_test!(
+        ifx_long_and_short_macros,
+        r"\long\def\a{}\def\b{}\ifx\a\b",
+        0
+    );
+    conditional_test!(ifx_let_primitive, r"\let\a\record\ifx\a\record", 1);
+    conditional_test!(ifx_implicit_character, r"\let\a=b\ifx\a b", 1);
+    conditional_test!(ifx_two_implicit_characters, r"\let\a=b\let\c=b\ifx\a\c", 1);
+    conditional_test!(ifx_implicit_and_other_character, r"\let\a=b\ifx\a c", 0);
+    conditional_test!(ifx_undefined, r"\ifx\undefinedA\undefinedB", 1);
+    conditional_test!(ifx_undefined_and_defined, r"\ifx\undefined\record", 0);
+    conditional_test!(nested_false_conditional, r"\ifx ab\ifx aa\else\fi", 0);
+    conditional_test!(nested_true_conditional, r"\ifx aa\ifx ab\else\fi", 1);
+
+    conditional_test!(ifnum_less, r"\ifnum 1<2", 1);
+    conditional_test!(ifnum_equal, r"\ifnum 2 = 2 ", 1);
+    conditional_test!(ifnum_greater, r"\ifnum -3>2", 0);
+    conditional_test!(ifdim_less, r"\ifdim 1pt<1.5pt", 1);
+    conditional_test!(ifdim_greater, r"\ifdim 1in>1cm", 1);
+    conditional_test!(ifdim_equal, r"\ifdim 1pt=1.5pt", 0);
+    conditional_test!(ifodd_odd, r"\ifodd 3", 1);
+    conditional_test!(ifodd_negative, r"\ifodd -3", 1);
+    conditional_test!(ifodd_even, r"\ifodd 0", 0);
+    conditional_test!(iftrue, r"\iftrue", 1);
+    conditional_test!(iffalse, r"\iffalse", 0);
+
+    fn ifcase(n: i32) -> Vec<i32> {
+        run(&format!(
+            r"\ifcase {} \record0\or\record1\or\ifcase1 \or\fi\record2\else\record3\fi",
+            n
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn ifcase_cases() {
+        assert_eq!(ifcase(0), vec![0]);
+        assert_eq!(ifcase(1), vec![1]);
+        assert_eq!(ifcase(2), vec![2]);
+        assert_eq!(ifcase(3), vec![3]);
+        assert_eq!(ifcase(-1), vec![3]);
+    }
+
+    #[test]
+    fn ifcase_without_else() {
+        assert_eq!(
+            run(r"\ifcase 2 \record0\or\record1\fi\record4").unwrap(),
+            vec![4]
+        );
+    }
+
+    #[test]
+    fn missing_relation() {
+        assert!(run(r"\ifnum 1 2").is_err());
+    }
+
+    fn error(input: &str) -> String {
+        match run(input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn extra_or() {
+        assert!(run(r"\or").is_err());
+        assert!(run(r"\iftrue\else\or\fi").is_ok());
+        assert!(run(r"\iffalse\else\or\fi").is_err());
+        assert!(run(r"\iftrue a\or b\fi").is_err());
+        assert!(run(r"\ifcase1 \or\ifnum1=1 \or\fi\fi").is_err());
+        assert!(run(r"\ifcase2 \or\else\or\fi").is_err());
+        assert!(run(r"\ifcase0 \ifcase0 \or\fi\or\fi").is_ok());
+    }
+
+    #[test]
+    fn extra_or_position() {
+        let err = error(r"\ifcase1 \or\fi \or");
+        assert!(err.contains(r"Extra \or"), "{}", err);
+        assert!(err.contains(":1:17"), "{}", err);
+    }
+
+    #[test]
+    fn extra_else() {
+        assert!(run(r"\else").is_err());
+        let err = error(r"\iftrue\else\fi \else");
+        assert!(err.contains(r"Extra \else"), "{}", err);
+        assert!(err.contains(":1:17"), "{}", err);
+    }
+
+    #[test]
+    fn extra_fi() {
+        assert!(run(r"\fi").is_err());
+        let err = error("\\iftrue\\fi\n  \\fi");
+        assert!(err.contains(r"Extra \fi"), "{}", err);
+        assert!(err.contains(":2:3"), "{}", err);
+    }
+
+    #[test]
+    fn incomplete_conditional() {
+        assert!(run(r"\ifx ab").is_err());
+    }
 }
@@ -0,0 +1,168 @@
+//! Primitives that convert their argument into character tokens: `\number`, `\romannumeral`
+//! and `\string`.
+//!
+//! ```tex
+//! \number 007        % 7
+//! \romannumeral 1984 % mcmlxxxiv
+//! \string\foo        % \foo, as four characters with catcode other
+//! ```
+//! As with `\the`, the resulting characters have catcode other, except for spaces which have
+//! catcode space.
+
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::the;
+use crate::tex::state::TexState;
+use crate::tex::token::stream;
+use crate::tex::token::token::{Token, Value};
+
+static NUMBER_DOC: &str = "Insert the decimal representation of a number";
+static ROMANNUMERAL_DOC: &str = "Insert the lowercase roman numeral representation of a number";
+static STRING_DOC: &str = "Insert the characters of the next token";
+
+fn number_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let n = parse::parse_number(input)?;
+    Ok(stream::VecStream::new(the::int_to_tokens(n)))
+}
+
+fn romannumeral_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let n = parse::parse_number(input)?;
+    Ok(stream::VecStream::new(the::string_to_tokens(
+        &roman_numeral(n),
+    )))
+}
+
+/// Returns the lowercase roman numeral for the number, or the empty string if the number is
+/// not positive. As in TeX, there is no upper limit: large numbers begin with many `m`s.
+pub fn roman_numeral(n: i32) -> String {
+    const NUMERALS: [(i32, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut n = n;
+    let mut s = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            s.push_str(numeral);
+            n -= value;
+        }
+    }
+    s
+}
+
+fn string_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let token = match input.unexpanded_stream().next()? {
+        None => {
+            return Err(anyhow::anyhow!(
+                "Unexpected end of input: expected a token after \\string"
+            ))
+        }
+        Some(token) => token,
+    };
+    let text = match &token.value {
+        Value::ControlSequence(_, name) => {
+            let mut text = String::new();
+            input
+                .state()
+                .base()
+                .write_style()
+                .write_control_sequence_name(&mut text, name);
+            text
+        }
+        Value::Character(c, _) => c.to_string(),
+    };
+    Ok(stream::VecStream::new(the::string_to_tokens(&text)))
+}
+
+pub fn get_number<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("number", number_fn, NUMBER_DOC)
+}
+
+pub fn get_romannumeral<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("romannumeral", romannumeral_fn, ROMANNUMERAL_DOC)
+}
+
+pub fn get_string<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("string", string_fn, STRING_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+
+    fn run(input: &str) -> String {
+        let s = testutil::run(testutil::new_state(), input).unwrap();
+        testutil::terminal(&s)
+    }
+
+    /// Returns the replacement text of a macro defined using `\edef` with the provided text.
+    fn expand(text: &str) -> String {
+        let output = run(&format!(r"\edef\x{{{}}}\show\x", text));
+        output
+            .strip_prefix("> \\x=macro:->")
+            .and_then(|s| s.strip_suffix('.'))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn number() {
+        assert_eq!(expand(r"\number 007"), "7");
+        assert_eq!(expand(r"\number-'17"), "-15");
+    }
+
+    #[test]
+    fn romannumeral() {
+        assert_eq!(expand(r"\romannumeral1984"), "mcmlxxxiv");
+        assert_eq!(expand(r"\romannumeral0"), "");
+        assert_eq!(expand(r"\romannumeral-5"), "");
+    }
+
+    #[test]
+    fn roman_numerals() {
+        assert_eq!(roman_numeral(4), "iv");
+        assert_eq!(roman_numeral(49), "xlix");
+        assert_eq!(roman_numeral(3999), "mmmcmxcix");
+        assert_eq!(roman_numeral(5000), "mmmmm");
+    }
+
+    #[test]
+    fn string() {
+        assert_eq!(expand(r"\string\foo"), r"\foo");
+        assert_eq!(expand(r"\string{"), "{");
+        assert_eq!(expand(r"\string\ "), r"\ ");
+    }
+
+    #[test]
+    fn string_uses_escapechar() {
+        assert_eq!(
+            run(r"\escapechar=`/ \edef\x{\string\foo}\show\x"),
+            "> /x=macro:->/foo."
+        );
+        assert_eq!(
+            run(r"\escapechar=-1 \edef\x{\string\foo}\show\x"),
+            "> x=macro:->foo."
+        );
+    }
+}
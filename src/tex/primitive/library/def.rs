@@ -0,0 +1,580 @@
+//! Primitives that define the meaning of control sequences and active characters: `\def`,
+//! `\gdef`, `\edef`, `\xdef`, `\let` and `\futurelet`.
+
+use crate::tex::error;
+use crate::tex::macros::{self, Macro, Replacement};
+use crate::tex::primitive;
+use crate::tex::primitive::library::prefix;
+use crate::tex::primitive::{Name, Primitive};
+use crate::tex::state::TexState;
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::stream;
+use crate::tex::token::token::{Token, Value};
+
+static DEF_DOC: &str = "Define a macro";
+static GDEF_DOC: &str = "Define a macro globally";
+static EDEF_DOC: &str = "Define a macro, expanding its replacement text";
+static XDEF_DOC: &str = "Define a macro globally, expanding its replacement text";
+static LET_DOC: &str = "Give a control sequence the meaning of a token";
+static FUTURELET_DOC: &str =
+    "Give a control sequence the meaning of the token after next, then reinsert both tokens";
+
+const MISSING_CONTROL_SEQUENCE_ERROR_TITLE: &str = "Missing control sequence";
+const MISSING_CONTROL_SEQUENCE_ERROR_HELP: &str =
+    "the control sequence or active character being defined should appear here";
+
+fn def_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let global = input.state().base().global_prefix;
+    def(token, input, global, false)
+}
+
+fn gdef_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    def(token, input, true, false)
+}
+
+fn edef_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let global = input.state().base().global_prefix;
+    def(token, input, global, true)
+}
+
+fn xdef_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    def(token, input, true, true)
+}
+
+fn def<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    global: bool,
+    expand: bool,
+) -> anyhow::Result<()> {
+    let target = parse_target(&token, input)?;
+    let scanning = format!("definition of {}", describe_name(&target));
+    let (prefix, parameters, brace_delimited) = parse_parameter_text(&token, input, &scanning)?;
+    let mut replacement =
+        parse_replacement_text(&token, input, &scanning, parameters.len(), expand)?;
+    if let Some(brace) = brace_delimited {
+        // With the `#{` syntax the begin group character that delimits the last argument is
+        // also inserted at the end of the replacement text.
+        replacement.push(Replacement::Token(brace));
+    }
+    let base = input.state().base();
+    let m = Macro::new(
+        prefix,
+        parameters,
+        replacement,
+        base.long_prefix,
+        base.outer_prefix,
+    );
+    input.state_mut().base_mut().primitives.set(
+        target,
+        Some(Primitive::Macro(std::rc::Rc::new(m))),
+        global,
+    );
+    Ok(())
+}
+
+type ParameterText = (Vec<Token>, Vec<Vec<Token>>, Option<Token>);
+
+/// Parses the parameter text of a macro definition, up to and including the begin group
+/// character that starts the replacement text.
+///
+/// Returns the prefix, the delimiters of each parameter, and the begin group character if
+/// the last parameter was delimited using the `#{` syntax.
+fn parse_parameter_text<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+    scanning: &str,
+) -> anyhow::Result<ParameterText> {
+    let mut prefix = Vec::new();
+    let mut parameters: Vec<Vec<Token>> = Vec::new();
+    loop {
+        let next = next_in_definition(token, input, scanning, false)?;
+        match next.value {
+            Value::Character(_, CatCode::BeginGroup) => {
+                return Ok((prefix, parameters, None));
+            }
+            Value::Character(_, CatCode::EndGroup) => {
+                return Err(error::new_token_error(
+                    next,
+                    "Missing { inserted".to_string(),
+                    vec![
+                        "the replacement text of a macro must begin with a begin group character"
+                            .to_string(),
+                    ],
+                ));
+            }
+            Value::Character(_, CatCode::Parameter) => {
+                let number = next_in_definition(token, input, scanning, false)?;
+                match number.value {
+                    Value::Character(_, CatCode::BeginGroup) => {
+                        match parameters.last_mut() {
+                            None => prefix.push(number.clone()),
+                            Some(delimiter) => delimiter.push(number.clone()),
+                        }
+                        return Ok((prefix, parameters, Some(number)));
+                    }
+                    Value::Character(c, _)
+                        if c.to_digit(10) == Some(parameters.len() as u32 + 1) =>
+                    {
+                        parameters.push(Vec::new());
+                    }
+                    _ => {
+                        return Err(error::new_token_error(
+                            number,
+                            "Parameters must be numbered consecutively".to_string(),
+                            vec![format!(
+                                "expected the parameter number {} to appear here",
+                                parameters.len() + 1
+                            )],
+                        ));
+                    }
+                }
+                if parameters.len() > 9 {
+                    return Err(error::new_token_error(
+                        number,
+                        "You already have nine parameters".to_string(),
+                        vec!["a macro can have at most nine parameters".to_string()],
+                    ));
+                }
+            }
+            _ => match parameters.last_mut() {
+                None => prefix.push(next),
+                Some(delimiter) => delimiter.push(next),
+            },
+        }
+    }
+}
+
+/// Parses the replacement text of a macro definition, up to and including the matching end
+/// group character. If `expand` is true, as for `\edef`, the replacement text is expanded as it
+/// is read.
+fn parse_replacement_text<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+    scanning: &str,
+    num_parameters: usize,
+    expand: bool,
+) -> anyhow::Result<Vec<Replacement>> {
+    let mut result = Vec::new();
+    let mut depth = 0_usize;
+    let read = |input: &mut primitive::Input<S>| next_in_definition(token, input, scanning, expand);
+    loop {
+        let next = read(input)?;
+        match next.value {
+            Value::Character(_, CatCode::BeginGroup) => depth += 1,
+            Value::Character(_, CatCode::EndGroup) => {
+                if depth == 0 {
+                    return Ok(result);
+                }
+                depth -= 1;
+            }
+            Value::Character(_, CatCode::Parameter) => {
+                let number = read(input)?;
+                match number.value {
+                    Value::Character(_, CatCode::Parameter) => {
+                        result.push(Replacement::Token(number));
+                        continue;
+                    }
+                    Value::Character(c, _) => match c.to_digit(10) {
+                        Some(n) if n >= 1 && (n as usize) <= num_parameters => {
+                            result.push(Replacement::Parameter(n as usize - 1));
+                            continue;
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                return Err(error::new_token_error(
+                    number,
+                    "Illegal parameter number in definition".to_string(),
+                    vec![format!(
+                        "a parameter character in the replacement text must be followed by another parameter character or a number between 1 and {}",
+                        num_parameters
+                    )],
+                ));
+            }
+            _ => {}
+        }
+        result.push(Replacement::Token(next));
+    }
+}
+
+fn let_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let target = parse_target(&token, input)?;
+    // The syntax is \let<target><optional spaces><=><one optional space><token>, where the
+    // equals sign is optional.
+    while let Some(Token {
+        value: Value::Character(_, CatCode::Space),
+        ..
+    }) = input.unexpanded_stream().peek()?
+    {
+        input.unexpanded_stream().consume()?;
+    }
+    if let Some(Token {
+        value: Value::Character('=', CatCode::Other),
+        ..
+    }) = input.unexpanded_stream().peek()?
+    {
+        input.unexpanded_stream().consume()?;
+        if let Some(Token {
+            value: Value::Character(_, CatCode::Space),
+            ..
+        }) = input.unexpanded_stream().peek()?
+        {
+            input.unexpanded_stream().consume()?;
+        }
+    }
+    let source = next_or_error(&token, input)?;
+    assign(input, target, &source.value);
+    Ok(())
+}
+
+fn futurelet_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let target = parse_target(&token, input)?;
+    let first = next_or_error(&token, input)?;
+    let second = next_or_error(&token, input)?;
+    assign(input, target, &second.value);
+    input.push(stream::VecStream::new(vec![first, second]));
+    Ok(())
+}
+
+fn assign<S: TexState<S>>(input: &mut primitive::Input<S>, target: Name, value: &Value) {
+    let base = input.state_mut().base_mut();
+    let meaning = base.meaning(value);
+    base.primitives.set(target, meaning, base.global_prefix);
+}
+
+/// Reads the control sequence or active character whose meaning is being defined. As in TeX,
+/// spaces before it are skipped.
+pub fn parse_target<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Name> {
+    let mut target = next_or_error(token, input)?;
+    while let Value::Character(_, CatCode::Space) = target.value {
+        target = next_or_error(token, input)?;
+    }
+    match Name::from_value(&target.value) {
+        Some(name) => Ok(name),
+        None => Err(error::new_token_error(
+            target,
+            MISSING_CONTROL_SEQUENCE_ERROR_TITLE.to_string(),
+            vec![MISSING_CONTROL_SEQUENCE_ERROR_HELP.to_string()],
+        )),
+    }
+}
+
+/// Reads the next token of a definition, which cannot be an outer macro. If `expand` is true,
+/// as for `\edef`, the token is read with expansion.
+fn next_in_definition<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+    scanning: &str,
+    expand: bool,
+) -> anyhow::Result<Token> {
+    match macros::next_not_outer(input, scanning, expand)? {
+        None => Err(end_of_input_error(token)),
+        Some(next) => Ok(next),
+    }
+}
+
+/// Returns how error messages refer to a control sequence or active character.
+fn describe_name(name: &Name) -> String {
+    match name {
+        Name::ControlSequence(name) => format!("\\{}", name),
+        Name::ActiveCharacter(c) => c.to_string(),
+    }
+}
+
+fn next_or_error<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Token> {
+    match input.unexpanded_stream().next()? {
+        None => Err(end_of_input_error(token)),
+        Some(token) => Ok(token),
+    }
+}
+
+fn end_of_input_error(token: &Token) -> anyhow::Error {
+    error::new_token_error(
+        token.clone(),
+        "Unexpected end of input".to_string(),
+        vec!["expected more tokens after this command".to_string()],
+    )
+}
+
+pub fn get_def<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_definition("def", def_fn, DEF_DOC)
+}
+
+pub fn get_gdef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_definition("gdef", gdef_fn, GDEF_DOC)
+}
+
+pub fn get_edef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_definition("edef", edef_fn, EDEF_DOC)
+}
+
+pub fn get_xdef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_definition("xdef", xdef_fn, XDEF_DOC)
+}
+
+pub fn get_let<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("let", let_fn, LET_DOC)
+}
+
+pub fn get_futurelet<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("futurelet", futurelet_fn, FUTURELET_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<Vec<i32>> {
+        testutil::run(testutil::new_state(), input).map(|s: TestState| s.recorded)
+    }
+
+    #[test]
+    fn def_no_parameters() {
+        assert_eq!(run(r"\def\a{\record 3}\a\a").unwrap(), vec![3, 3]);
+    }
+
+    #[test]
+    fn def_undelimited_parameters() {
+        assert_eq!(
+            run(r"\def\a#1#2{\record #2\record #1}\a 1{23}").unwrap(),
+            vec![23, 1]
+        );
+    }
+
+    #[test]
+    fn def_undelimited_parameter_skips_spaces() {
+        assert_eq!(run(r"\def\a#1{\record #1}\a   4").unwrap(), vec![4]);
+    }
+
+    #[test]
+    fn def_delimited_parameters() {
+        assert_eq!(
+            run(r"\def\a#1.#2.{\record #2\record #1}\a 12.34.").unwrap(),
+            vec![34, 12]
+        );
+    }
+
+    #[test]
+    fn def_delimited_parameter_strips_braces() {
+        assert_eq!(run(r"\def\a#1.{\record #1}\a{5}.").unwrap(), vec![5]);
+    }
+
+    #[test]
+    fn def_delimited_parameter_with_nested_delimiter() {
+        assert_eq!(
+            run(r"\def\a#1.{\def\b##1{\record ##1}\b#1}\a{6.}.").unwrap(),
+            vec![6]
+        );
+    }
+
+    #[test]
+    fn def_prefix() {
+        assert_eq!(run(r"\def\a x#1{\record #1}\a x7").unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn def_prefix_mismatch() {
+        assert!(run(r"\def\a x#1{\record #1}\a y7").is_err());
+    }
+
+    #[test]
+    fn def_brace_delimited_parameter() {
+        assert_eq!(
+            run(r"\def\a#1#{\record #1\def\b}\a 8{\record 9}\b").unwrap(),
+            vec![8, 9]
+        );
+    }
+
+    #[test]
+    fn def_double_parameter_character() {
+        assert_eq!(
+            run(r"\def\a{\def\b##1{\record ##1}}\a\b 2").unwrap(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn def_parameters_not_consecutive() {
+        assert!(run(r"\def\a#2{}").is_err());
+    }
+
+    #[test]
+    fn def_illegal_parameter_number() {
+        assert!(run(r"\def\a#1{#2}").is_err());
+    }
+
+    #[test]
+    fn def_extra_end_group_in_argument() {
+        assert!(run(r"\def\a#1{}{\a}").is_err());
+    }
+
+    #[test]
+    fn def_is_local() {
+        assert_eq!(
+            run(r"\def\a{\record 1}{\def\a{\record 2}\a}\a").unwrap(),
+            vec![2, 1]
+        );
+    }
+
+    #[test]
+    fn gdef_is_global() {
+        assert_eq!(
+            run(r"\def\a{\record 1}{\gdef\a{\record 2}}\a").unwrap(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn edef_expands_replacement_text() {
+        assert_eq!(
+            run(r"\def\a{1}\edef\b{\record\a}\def\a{2}\b").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn edef_parameters() {
+        assert_eq!(run(r"\def\a{\record}\edef\b#1{\a#1}\b 5").unwrap(), vec![5]);
+    }
+
+    #[test]
+    fn edef_does_not_expand_the_output_of_the() {
+        assert_eq!(
+            run(r"\def\a{\record 1}\toks0={\a}\edef\b{\the\toks0}\def\a{\record 2}\b").unwrap(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn edef_expands_after_the_output_of_the() {
+        assert_eq!(
+            run(r"\def\a{\record 1}\toks0={\a}\edef\b{\the\toks0\a}\def\a{\record 2}\b").unwrap(),
+            vec![2, 1]
+        );
+    }
+
+    #[test]
+    fn edef_expands_the_inside_the() {
+        // The tokens that \the reads are expanded; only its output is not.
+        assert_eq!(
+            run(r"\def\a{\toks}\toks1={\record 1}\edef\b{\the\a1}\b").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn xdef_is_global() {
+        assert_eq!(
+            run(r"\def\a{\record 1}{\def\c{\record 2}\xdef\a{\c}}\a").unwrap(),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn def_active_character() {
+        assert_eq!(run(r"\def~{\record 3}~~").unwrap(), vec![3, 3]);
+    }
+
+    #[test]
+    fn def_active_character_with_parameter() {
+        assert_eq!(run(r"\def~#1{\record #1}~4").unwrap(), vec![4]);
+    }
+
+    #[test]
+    fn let_active_character() {
+        assert_eq!(run(r"\let~\record~5").unwrap(), vec![5]);
+    }
+
+    #[test]
+    fn let_control_sequence_to_active_character() {
+        assert_eq!(run(r"\def~{\record 6}\let\a~\a").unwrap(), vec![6]);
+    }
+
+    #[test]
+    fn active_character_is_not_a_control_symbol() {
+        assert!(run(r"\def~{\record 6}\~").is_err());
+    }
+
+    #[test]
+    fn undefined_active_character() {
+        assert!(run(r"~").is_err());
+    }
+
+    #[test]
+    fn ifx_macros() {
+        assert_eq!(
+            run(r"\def\a{x}\def\b{x}\def\c{y}\ifx\a\b\record1\fi\ifx\a\c\else\record0\fi").unwrap(),
+            vec![1, 0]
+        );
+    }
+
+    #[test]
+    fn let_primitive() {
+        assert_eq!(run(r"\let\a\record\a 3").unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn let_with_equals_and_space() {
+        assert_eq!(run(r"\let\a= \record\a 3").unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn let_with_spaces_before_equals() {
+        assert_eq!(run(r"\let\a  =\record\a 3").unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn let_equals_sign() {
+        assert_eq!(
+            run(r"\let\a==\let\b\a\ifx\b=\record1\else\record0\fi").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn let_implicit_begin_and_end_group() {
+        assert_eq!(
+            run(r"\let\bgroup={\let\egroup=}\bgroup\record\currentgrouplevel\egroup\record\currentgrouplevel")
+                .unwrap(),
+            vec![1, 0]
+        );
+    }
+
+    #[test]
+    fn let_is_local() {
+        assert_eq!(
+            run(r"\let\a=b{\let\a\record}\ifx\a b\record1\else\record0\fi").unwrap(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn let_undefined() {
+        assert!(run(r"\let\a\record\let\a\undefined\a 1").is_err());
+    }
+
+    #[test]
+    fn let_missing_control_sequence() {
+        assert!(run(r"\let a\record").is_err());
+    }
+
+    #[test]
+    fn futurelet() {
+        assert_eq!(
+            run(r"\futurelet\a\record 5\ifx\a 5\record1\else\record0\fi").unwrap(),
+            vec![5, 1]
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Primitives that append discretionaries: `\discretionary` and `\-`.
+//!
+//! A discretionary is a place where a line may be broken. It has three lists: the pre-break
+//! list, which ends the line if the line is broken there, the post-break list, which begins the
+//! next line, and the replacement, which is typeset if the line is not broken there:
+//! ```tex
+//! \discretionary{tick-}{et}{ticket}  % "ticket", or "tick-" and "et" across two lines
+//! \-                                 % \discretionary{-}{}{} with the font's \hyphenchar
+//! ```
+//! The lists can only contain characters, ligatures, boxes, rules and kerns.
+
+use crate::tex::driver;
+use crate::tex::error;
+use crate::tex::group::{DiscretionaryPart, GroupType};
+use crate::tex::nest::Mode;
+use crate::tex::node::{Char, Discretionary, Node};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::state::TexState;
+use crate::tex::token::token::Token;
+
+static DISCRETIONARY_DOC: &str = "Append a discretionary with the provided three lists";
+static HYPHEN_DOC: &str = "Append a discretionary hyphen";
+
+fn discretionary_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    if driver::leave_vertical_mode(token, input) {
+        return Ok(());
+    }
+    input
+        .state_mut()
+        .base_mut()
+        .nest
+        .append(Node::Discretionary(Discretionary {
+            pre_break: vec![],
+            post_break: vec![],
+            replace_count: 0,
+        }));
+    begin_list(input, DiscretionaryPart::PreBreak)
+}
+
+fn hyphen_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    if driver::leave_vertical_mode(token, input) {
+        return Ok(());
+    }
+    let base = input.state_mut().base_mut();
+    let font = base.fonts.current();
+    let hyphen_char = base.fonts.get(font).hyphen_char;
+    // As in TeX, the pre-break list is empty if the hyphen character is not in the font.
+    let c = match (0..=255).contains(&hyphen_char) {
+        true => char::from_u32(hyphen_char as u32),
+        false => None,
+    };
+    let pre_break = match c {
+        Some(c) if base.fonts.get(font).char(c).is_some() => vec![Node::Char(Char { font, c })],
+        _ => vec![],
+    };
+    base.nest.append(Node::Discretionary(Discretionary {
+        pre_break,
+        post_break: vec![],
+        replace_count: 0,
+    }));
+    Ok(())
+}
+
+/// Begins the group of one of the lists of a discretionary.
+fn begin_list<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    part: DiscretionaryPart,
+) -> anyhow::Result<()> {
+    parse::parse_left_brace(input)?;
+    let base = input.state_mut().base_mut();
+    base.begin_discretionary_group(part);
+    base.nest.push(Mode::RestrictedHorizontal);
+    base.nest.current_mut().space_factor = 1000;
+    Ok(())
+}
+
+/// Finishes one of the lists of a discretionary when its group ends, and begins the next one.
+/// The replacement, which is the last list, is appended to the current list after the
+/// discretionary. This is `build_discretionary` in TeX.
+pub fn end_list<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let group = driver::end_group(token.clone(), input, GroupType::Disc)?;
+    // Discretionary groups are begun with a part.
+    let part = group.discretionary_part.unwrap();
+    let base = input.state_mut().base_mut();
+    // The list's restricted horizontal mode is not the outer mode.
+    let list = base.nest.pop().unwrap().list;
+    let improper = list.iter().any(|node| {
+        !matches!(
+            node,
+            Node::Char(_)
+                | Node::Ligature(_)
+                | Node::HList(_)
+                | Node::VList(_)
+                | Node::Rule(_)
+                | Node::Kern(_)
+        )
+    });
+    if improper {
+        return Err(error::new_token_error(
+            token,
+            "Improper discretionary list".to_string(),
+            vec!["discretionary lists must contain only boxes and kerns".to_string()],
+        ));
+    }
+    let level = base.nest.current_mut();
+    // The discretionary was appended before its lists were begun.
+    let discretionary = match level.list.last_mut() {
+        Some(Node::Discretionary(d)) => d,
+        _ => unreachable!(),
+    };
+    let next = match part {
+        DiscretionaryPart::PreBreak => {
+            discretionary.pre_break = list;
+            DiscretionaryPart::PostBreak
+        }
+        DiscretionaryPart::PostBreak => {
+            discretionary.post_break = list;
+            DiscretionaryPart::Replacement
+        }
+        DiscretionaryPart::Replacement => {
+            if !list.is_empty() && level.mode.is_math() {
+                return Err(error::new_token_error(
+                    token,
+                    "Illegal math \\discretionary".to_string(),
+                    vec![
+                        "in a formula, the third list of a discretionary must be empty".to_string(),
+                    ],
+                ));
+            }
+            if list.len() > 255 {
+                return Err(error::new_token_error(
+                    token,
+                    "Discretionary list is too long".to_string(),
+                    vec!["the replacement of a discretionary has at most 255 items".to_string()],
+                ));
+            }
+            discretionary.replace_count = list.len();
+            level.list.extend(list);
+            return Ok(());
+        }
+    };
+    begin_list(input, next)
+}
+
+pub fn get_discretionary<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("discretionary", discretionary_fn, DISCRETIONARY_DOC)
+}
+
+pub fn get_hyphen<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("-", hyphen_fn, HYPHEN_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::dimen::Dimen;
+    use crate::tex::node::Kern;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        testutil::run(s, input)
+    }
+
+    /// Returns the list of box register 1 after running the input.
+    fn box_list(input: &str) -> Vec<Node> {
+        let s = run(input).unwrap();
+        s.base()
+            .registers
+            .box_register(1)
+            .unwrap()
+            .as_box()
+            .unwrap()
+            .list
+            .clone()
+    }
+
+    fn error(input: &str) -> String {
+        match run(input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn char(c: char) -> Node {
+        Node::Char(Char {
+            font: testutil::TEST_FONT,
+            c,
+        })
+    }
+
+    #[test]
+    fn discretionary() {
+        assert_eq!(
+            box_list(r"\setbox1\hbox{a\discretionary{b-}{c}{de}f}"),
+            vec![
+                char('a'),
+                Node::Discretionary(Discretionary {
+                    pre_break: vec![char('b'), char('-')],
+                    post_break: vec![char('c')],
+                    replace_count: 2,
+                }),
+                char('d'),
+                char('e'),
+                char('f'),
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_are_groups() {
+        let input = r"\count1=1
+            \setbox1\hbox{\discretionary{\count1=2 \record\currentgrouptype}{}{\record\count1}}";
+        let s = run(input).unwrap();
+        assert_eq!(s.recorded, vec![10, 1]);
+    }
+
+    #[test]
+    fn discretionary_hyphen() {
+        assert_eq!(
+            box_list(r"\setbox1\hbox{\-\hyphenchar\font=`x \-\hyphenchar\font=-1 \-}"),
+            vec![
+                Node::Discretionary(Discretionary {
+                    pre_break: vec![char('-')],
+                    post_break: vec![],
+                    replace_count: 0,
+                }),
+                Node::Discretionary(Discretionary {
+                    pre_break: vec![char('x')],
+                    post_break: vec![],
+                    replace_count: 0,
+                }),
+                Node::Discretionary(Discretionary {
+                    pre_break: vec![],
+                    post_break: vec![],
+                    replace_count: 0,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn discretionary_begins_a_paragraph() {
+        for input in [r"\setbox1\vbox{\discretionary{}{}{}}", r"\setbox1\vbox{\-}"] {
+            let list = box_list(input);
+            assert!(matches!(list.first(), Some(Node::HList(_))), "{}", input);
+        }
+    }
+
+    #[test]
+    fn replacement_cannot_be_removed() {
+        assert_eq!(
+            box_list(r"\setbox1\hbox{\discretionary{}{}{\kern1pt}\unkern}"),
+            vec![
+                Node::Discretionary(Discretionary {
+                    pre_break: vec![],
+                    post_break: vec![],
+                    replace_count: 1,
+                }),
+                Node::Kern(Kern::new(Dimen::from_pt(1))),
+            ]
+        );
+    }
+
+    #[test]
+    fn improper_lists() {
+        assert!(
+            error(r"\hbox{\discretionary{\penalty0}{}{}}").contains("Improper discretionary list")
+        );
+        assert!(
+            error(r"\hbox{\discretionary{}{}{a\hskip1pt}}").contains("Improper discretionary list")
+        );
+        assert!(error(r"\hbox{$\discretionary{}{}{a}$}").contains(r"Illegal math \discretionary"));
+    }
+}
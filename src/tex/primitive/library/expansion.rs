@@ -0,0 +1,217 @@
+//! Primitives that control expansion: `\relax`, `\expandafter`, `\noexpand`, and `\csname` with
+//! `\endcsname`.
+//!
+//! `\expandafter` expands the token after next once, and `\noexpand` stops the next token from
+//! being expanded. `\csname` builds a control sequence out of the characters that follow it:
+//! ```tex
+//! \expandafter\def\csname a b\endcsname{x}  % defines the control sequence `a b`
+//! \edef\c{\noexpand\d}                      % the replacement text of \c is \d
+//! ```
+//! A control sequence built using `\csname` that is undefined is given the meaning `\relax`,
+//! which does nothing.
+
+use crate::tex::error;
+use crate::tex::primitive;
+use crate::tex::primitive::{Execution, Name, Primitive};
+use crate::tex::state::TexState;
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::stream;
+use crate::tex::token::stream::Stream;
+use crate::tex::token::token::{Token, Value};
+
+static RELAX_DOC: &str = "Do nothing";
+static EXPANDAFTER_DOC: &str = "Expand the token after the next token once";
+static NOEXPAND_DOC: &str = "Do not expand the next token";
+static CSNAME_DOC: &str = "Build a control sequence from the characters up to \\endcsname";
+static ENDCSNAME_DOC: &str = "End the name of a control sequence begun with \\csname";
+
+fn relax_fn<S: TexState<S>>(_: Token, _: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+fn endcsname_fn<S: TexState<S>>(token: Token, _: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    Err(error::new_token_error(
+        token,
+        "Extra \\endcsname".to_string(),
+        vec!["there is no \\csname for this \\endcsname to match".to_string()],
+    ))
+}
+
+fn expandafter_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let first = next_unexpanded(input)?;
+    input.expand_next()?;
+    Ok(stream::VecStream::new(vec![first]))
+}
+
+fn noexpand_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let token = next_unexpanded(input)?;
+    let expandable = matches!(
+        input.state().base().primitives.get(&token.value),
+        Some(Primitive::Expansion(_)) | Some(Primitive::Macro(_))
+    );
+    match expandable {
+        true => input.push_unexpandable(token),
+        false => input.push(stream::VecStream::new(vec![token])),
+    }
+    Ok(stream::VecStream::new_empty())
+}
+
+fn csname_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let mut name = String::new();
+    loop {
+        let token = match input.next()? {
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Unexpected end of input: missing \\endcsname"
+                ))
+            }
+            Some(token) => token,
+        };
+        match token.value {
+            Value::Character(c, cat_code) if cat_code != CatCode::Active => name.push(c),
+            _ => {
+                if is_endcsname(input, &token) {
+                    break;
+                }
+                return Err(error::new_token_error(
+                    token,
+                    "Missing \\endcsname inserted".to_string(),
+                    vec![
+                        "the control sequence marked should not appear between \\csname and \\endcsname"
+                            .to_string(),
+                    ],
+                ));
+            }
+        }
+    }
+    let token = Token::new_control_sequence(&name);
+    let primitives = &mut input.state_mut().base_mut().primitives;
+    if primitives.get(&token.value).is_none() {
+        primitives.set(
+            Name::ControlSequence(name),
+            Some(Primitive::Execution(Execution::Static(get_relax()))),
+            false,
+        );
+    }
+    Ok(stream::VecStream::new(vec![token]))
+}
+
+fn is_endcsname<S: TexState<S>>(input: &primitive::Input<S>, token: &Token) -> bool {
+    match input.state().base().primitives.get(&token.value) {
+        None => false,
+        Some(p) => p.same_meaning(&Primitive::Execution(Execution::Static(get_endcsname()))),
+    }
+}
+
+fn next_unexpanded<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Token> {
+    match input.unexpanded_stream().next()? {
+        None => Err(anyhow::anyhow!("Unexpected end of input: expected a token")),
+        Some(token) => Ok(token),
+    }
+}
+
+pub fn get_relax<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("relax", relax_fn, RELAX_DOC)
+}
+
+pub fn get_expandafter<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("expandafter", expandafter_fn, EXPANDAFTER_DOC)
+}
+
+pub fn get_noexpand<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("noexpand", noexpand_fn, NOEXPAND_DOC)
+}
+
+pub fn get_csname<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("csname", csname_fn, CSNAME_DOC)
+}
+
+pub fn get_endcsname<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("endcsname", endcsname_fn, ENDCSNAME_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        testutil::run(testutil::new_state(), input)
+    }
+
+    #[test]
+    fn relax_does_nothing() {
+        let s = run(r"\relax\record1\relax").unwrap();
+        assert_eq!(s.recorded, vec![1]);
+    }
+
+    #[test]
+    fn expandafter() {
+        let s = run(r"\def\a{\record}\def\b{2}\expandafter\a\b").unwrap();
+        assert_eq!(s.recorded, vec![2]);
+        let s = run(r"\def\a{1}\expandafter\def\expandafter\b\expandafter{\a}\def\a{2}\record\b")
+            .unwrap();
+        assert_eq!(s.recorded, vec![1]);
+    }
+
+    #[test]
+    fn noexpand_in_edef() {
+        let s = run(r"\def\a{1}\edef\b{\noexpand\a}\def\a{2}\record\b").unwrap();
+        assert_eq!(s.recorded, vec![2]);
+    }
+
+    #[test]
+    fn noexpanded_macro_means_relax() {
+        let s = run(r"\def\a{\record1}\noexpand\a\record2").unwrap();
+        assert_eq!(s.recorded, vec![2]);
+    }
+
+    #[test]
+    fn expandafter_noexpand() {
+        let s = run(r"\def\a#1{\show#1}\def\b{B}\expandafter\a\noexpand\b").unwrap();
+        assert_eq!(testutil::terminal(&s), "> \\b=macro:->B.");
+        let s = run(r"\def\a{\record1}\def\b{\record2}\expandafter\a\noexpand\b\record3").unwrap();
+        assert_eq!(s.recorded, vec![1, 3]);
+    }
+
+    #[test]
+    fn csname() {
+        let s =
+            run(r"\expandafter\def\csname a b\endcsname{\record3}\csname a b\endcsname").unwrap();
+        assert_eq!(s.recorded, vec![3]);
+    }
+
+    #[test]
+    fn csname_with_expansion() {
+        let s = run(r"\def\a{x}\def\x{\record4}\csname\a\endcsname").unwrap();
+        assert_eq!(s.recorded, vec![4]);
+    }
+
+    #[test]
+    fn csname_defines_relax() {
+        let s = run(r"\expandafter\show\csname undefined\endcsname").unwrap();
+        assert_eq!(testutil::terminal(&s), "> \\undefined=\\relax.");
+    }
+
+    #[test]
+    fn csname_definition_is_local() {
+        let s = run(r"{\csname a\endcsname}\show\a").unwrap();
+        assert_eq!(testutil::terminal(&s), "> \\a=undefined.");
+    }
+
+    #[test]
+    fn csname_errors() {
+        assert!(run(r"\csname\relax\endcsname").is_err());
+        assert!(run(r"\csname a").is_err());
+        assert!(run(r"\endcsname").is_err());
+    }
+}
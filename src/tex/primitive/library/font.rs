@@ -0,0 +1,632 @@
+//! Font primitives: `\font`, which loads a font from a TFM file and defines a control sequence
+//! that selects it, and `\fontname`, `\fontdimen`, `\hyphenchar` and `\skewchar`, which refer to
+//! a loaded font:
+//! ```tex
+//! \font\tenrm=cmr10              % load cmr10.tfm at its design size
+//! \font\twelverm=cmr10 at 12pt   % load it again at 12pt
+//! \font\magrm=cmr10 scaled 2000  % and at twice its design size
+//! \tenrm                         % select the font
+//! \dimen0=\fontdimen2\tenrm      % the interword space of the font
+//! \hyphenchar\tenrm=`-           % the character used to hyphenate words
+//! ```
+//! `\noboundary` suppresses the boundary character of the current font before or after a word;
+//! see [crate::tex::ligature].
+//!
+//! `\textfont`, `\scriptfont` and `\scriptscriptfont` assign the fonts of a math family:
+//! ```tex
+//! \textfont1=\teni \scriptfont1=\seveni \scriptscriptfont1=\fivei
+//! ```
+//!
+//! A font is referred to by a control sequence defined using `\font`, by `\nullfont`, by
+//! `\font` itself, which refers to the current font, or by a math family font like
+//! `\textfont1`.
+//!
+//! TFM files are read through the state's file system. A file name without an extension is
+//! given the extension `.tfm`.
+
+use crate::tex::dimen::Dimen;
+use crate::tex::driver;
+use crate::tex::error;
+use crate::tex::filesystem;
+use crate::tex::font::{Font, MathSize, Size, MAX_PARAMS, NULL_FONT, NUM_FAMILIES};
+use crate::tex::ligature;
+use crate::tex::nest::Mode;
+use crate::tex::node::FontId;
+use crate::tex::parameter::IntParam;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::{def, prefix, the};
+use crate::tex::primitive::{Execution, Name, Primitive};
+use crate::tex::state::{BaseState, TexState};
+use crate::tex::tfm::Tfm;
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::stream;
+use crate::tex::token::stream::Stream;
+use crate::tex::token::token::Token;
+use std::io::Read;
+
+static FONT_DOC: &str = "Load a font and define a control sequence that selects it";
+static FONTNAME_DOC: &str = "Insert the file name and size of a font";
+static FONTDIMEN_DOC: &str = "Get or set a parameter of a font";
+static HYPHENCHAR_DOC: &str = "Get or set the character used to hyphenate words in a font";
+static SKEWCHAR_DOC: &str = "Get or set the character used to position math accents in a font";
+static NOBOUNDARY_DOC: &str = "Suppress the boundary character of the font at the edge of a word";
+static TEXTFONT_DOC: &str = "Set the text size font of a math family";
+static SCRIPTFONT_DOC: &str = "Set the script size font of a math family";
+static SCRIPTSCRIPTFONT_DOC: &str = "Set the scriptscript size font of a math family";
+
+/// The largest size a font can be loaded at, 2048pt.
+const MAX_SIZE: Dimen = Dimen(2048 << 16);
+
+/// The largest magnification a font can be loaded at using `scaled`.
+const MAX_SCALE: i32 = 32768;
+
+fn font_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let target = def::parse_target(&token, input)?;
+    let global = input.state().base().global_prefix;
+    // As in TeX, the control sequence means \nullfont while the font is being loaded.
+    define(
+        input.state_mut().base_mut(),
+        target.clone(),
+        NULL_FONT,
+        global,
+    );
+    parse::parse_optional_equals(input)?;
+    let name = parse::parse_file_name(input)?;
+    let size = parse_size(&token, input)?;
+    let identifier = match &target {
+        Name::ControlSequence(name) => name.clone(),
+        Name::ActiveCharacter(c) => c.to_string(),
+    };
+    let base = input.state_mut().base_mut();
+    let id = match base.fonts.find(&name, size) {
+        Some(id) => id,
+        None => load(token, base, &identifier, name, size)?,
+    };
+    base.fonts.get_mut(id).identifier = identifier;
+    define(base, target, id, global);
+    Ok(())
+}
+
+fn define<S>(base: &mut BaseState<S>, target: Name, id: FontId, global: bool) {
+    base.primitives
+        .set(target, Some(Primitive::Font(id)), global);
+}
+
+/// Parses the optional `at <dimen>` or `scaled <number>` after the file name of `\font`.
+fn parse_size<S: TexState<S>>(
+    token: &Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<Size> {
+    if parse::parse_keyword(input, "at")? {
+        let size = parse::parse_dimen(input)?;
+        if size.0 <= 0 || size >= MAX_SIZE {
+            return Err(error::new_token_error(
+                token.clone(),
+                format!("Improper `at' size ({})", size),
+                vec![format!(
+                    "a font must be loaded at a positive size less than {}",
+                    MAX_SIZE
+                )],
+            ));
+        }
+        return Ok(Size::At(size));
+    }
+    if parse::parse_keyword(input, "scaled")? {
+        let n = parse::parse_number(input)?;
+        if n <= 0 || n > MAX_SCALE {
+            return Err(error::new_token_error(
+                token.clone(),
+                format!("Illegal magnification ({})", n),
+                vec![format!(
+                    "the magnification must be between 1 and {}",
+                    MAX_SCALE
+                )],
+            ));
+        }
+        return Ok(Size::Scaled(n));
+    }
+    Ok(Size::Scaled(1000))
+}
+
+/// Reads the TFM file of a font, adds the font to the table of fonts and returns its
+/// identifier.
+fn load<S>(
+    token: Token,
+    base: &mut BaseState<S>,
+    identifier: &str,
+    name: String,
+    size: Size,
+) -> anyhow::Result<FontId> {
+    let path = filesystem::with_default_extension(name.clone(), "tfm");
+    let tfm = read_tfm(base, &path);
+    let tfm = match tfm {
+        Ok(tfm) => tfm,
+        Err(err) => {
+            let mut text = String::new();
+            base.write_style()
+                .write_control_sequence_name(&mut text, identifier);
+            text.push('=');
+            text.push_str(&name);
+            match size {
+                Size::At(size) => text.push_str(&format!(" at {}", size)),
+                Size::Scaled(1000) => {}
+                Size::Scaled(n) => text.push_str(&format!(" scaled {}", n)),
+            }
+            return Err(error::new_token_error(
+                token,
+                format!("Font {} not loadable: {}", text, err),
+                vec![],
+            ));
+        }
+    };
+    let design_size = Dimen(tfm.design_size >> 4);
+    let mut font = Font::new(name, &tfm, size.resolve(design_size));
+    font.hyphen_char = base.parameters.int(IntParam::DefaultHyphenChar);
+    font.skew_char = base.parameters.int(IntParam::DefaultSkewChar);
+    Ok(base.fonts.add(font))
+}
+
+fn read_tfm<S>(base: &BaseState<S>, path: &str) -> anyhow::Result<Tfm> {
+    let mut bytes = Vec::new();
+    match base.file_system.open(path) {
+        Err(_) => return Err(anyhow::anyhow!("Metric (TFM) file not found")),
+        Ok(mut reader) => reader.read_to_end(&mut bytes)?,
+    };
+    Tfm::parse(&bytes)
+}
+
+/// Reads a font identifier: a control sequence defined using `\font`, `\nullfont`, `\font`
+/// itself, which refers to the current font, or a math family font like `\textfont1`. Spaces
+/// before the identifier are skipped.
+pub fn parse_font<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<FontId> {
+    loop {
+        let token = match input.next()? {
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Unexpected end of input: missing font identifier"
+                ))
+            }
+            Some(token) => token,
+        };
+        let base = input.state().base();
+        let size = match base.meaning(&token.value) {
+            Some(Primitive::Character(_, CatCode::Space)) => continue,
+            Some(Primitive::Font(id)) => return Ok(id),
+            Some(p) if is_font_primitive(&p) => return Ok(base.fonts.current()),
+            Some(p) => family_size(&p),
+            None => None,
+        };
+        return match size {
+            Some(size) => {
+                let family = parse_family(input)?;
+                Ok(input.state().base().fonts.family(size, family))
+            }
+            None => Err(error::new_token_error(
+                token,
+                "Missing font identifier".to_string(),
+                vec!["a font identifier is a control sequence defined using \\font".to_string()],
+            )),
+        };
+    }
+}
+
+/// Reads the number of a math family, which must be between 0 and 15, from the input.
+pub fn parse_family<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<usize> {
+    let n = parse::parse_number(input)?;
+    match n >= 0 && (n as usize) < NUM_FAMILIES {
+        true => Ok(n as usize),
+        false => Err(anyhow::anyhow!(
+            "Bad number ({}); a math family number must be between 0 and {}",
+            n,
+            NUM_FAMILIES - 1
+        )),
+    }
+}
+
+/// Returns the size of the math family fonts that the primitive refers to, if it is
+/// `\textfont`, `\scriptfont` or `\scriptscriptfont`.
+pub fn family_size<S: TexState<S>>(p: &Primitive<S>) -> Option<MathSize> {
+    [
+        (get_textfont(), MathSize::Text),
+        (get_scriptfont(), MathSize::Script),
+        (get_scriptscriptfont(), MathSize::ScriptScript),
+    ]
+    .iter()
+    .find(|(e, _)| p.same_meaning(&Primitive::Execution(Execution::Static(*e))))
+    .map(|(_, size)| *size)
+}
+
+/// Reads a family number, an optional equals sign and a font, and assigns the font to the
+/// family at the provided size.
+fn family_fn<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    size: MathSize,
+) -> anyhow::Result<()> {
+    let family = parse_family(input)?;
+    parse::parse_optional_equals(input)?;
+    let id = parse_font(input)?;
+    let base = input.state_mut().base_mut();
+    let global = base.global_prefix;
+    base.fonts.set_family(size, family, id, global);
+    Ok(())
+}
+
+fn textfont_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    family_fn(input, MathSize::Text)
+}
+
+fn scriptfont_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    family_fn(input, MathSize::Script)
+}
+
+fn scriptscriptfont_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    family_fn(input, MathSize::ScriptScript)
+}
+
+/// Returns true if the primitive is `\font`.
+pub fn is_font_primitive<S: TexState<S>>(p: &Primitive<S>) -> bool {
+    p.same_meaning(&Primitive::Execution(Execution::Static(get_font())))
+}
+
+/// Returns the token that `\the` produces for a font: the control sequence most recently
+/// defined by `\font` to select it.
+pub fn identifier_token<S>(base: &BaseState<S>, id: FontId) -> Token {
+    Token::new_control_sequence(&base.fonts.get(id).identifier)
+}
+
+fn fontname_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let id = parse_font(input)?;
+    let text = input.state().base().fonts.get(id).name_and_size();
+    Ok(stream::VecStream::new(the::string_to_tokens(&text)))
+}
+
+/// Reads the parameter number and font after `\fontdimen`, and combines them into the index of
+/// the variable.
+///
+/// As in TeX, referring to a parameter beyond the last one of the most recently loaded font
+/// gives that font more parameters. This is how macro packages add parameters to fonts.
+fn parse_fontdimen_index<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<usize> {
+    let n = parse::parse_number(input)?;
+    let id = parse_font(input)?;
+    let base = input.state_mut().base_mut();
+    let num_params = base.fonts.get(id).num_params();
+    if n <= 0 || n as usize > num_params {
+        if n <= 0 || n as usize >= MAX_PARAMS || id != base.fonts.last() {
+            let mut name = String::new();
+            base.write_style()
+                .write_control_sequence_name(&mut name, &base.fonts.get(id).identifier);
+            return Err(anyhow::anyhow!(
+                "Font {} has only {} fontdimen parameters",
+                name,
+                num_params
+            ));
+        }
+        base.fonts.get_mut(id).extend_params(n as usize);
+    }
+    Ok(id.0 * MAX_PARAMS + n as usize)
+}
+
+/// Splits the index of `\fontdimen` into the font and the parameter number.
+fn split_fontdimen_index(index: usize) -> (FontId, usize) {
+    (FontId(index / MAX_PARAMS), index % MAX_PARAMS)
+}
+
+fn fontdimen_get<S: TexState<S>>(state: &S, index: usize) -> Dimen {
+    let (id, n) = split_fontdimen_index(index);
+    // The parameter was checked to exist when the index was parsed.
+    state.base().fonts.get(id).param(n).unwrap()
+}
+
+/// Sets a font parameter. As in TeX, font parameters are global; `\global` makes no difference.
+fn fontdimen_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Dimen,
+    _: bool,
+) -> anyhow::Result<()> {
+    let (id, n) = split_fontdimen_index(index);
+    *state.base_mut().fonts.get_mut(id).param_mut(n).unwrap() = value;
+    Ok(())
+}
+
+fn parse_font_index<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<usize> {
+    Ok(parse_font(input)?.0)
+}
+
+/// Sets the hyphen character of a font. The assignment is always global.
+fn hyphenchar_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    _: bool,
+) -> anyhow::Result<()> {
+    state.base_mut().fonts.get_mut(FontId(index)).hyphen_char = value;
+    Ok(())
+}
+
+/// Sets the skew character of a font. The assignment is always global.
+fn skewchar_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    _: bool,
+) -> anyhow::Result<()> {
+    state.base_mut().fonts.get_mut(FontId(index)).skew_char = value;
+    Ok(())
+}
+
+fn noboundary_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    match input.state().base().nest.mode() {
+        Mode::Vertical | Mode::InternalVertical => {
+            input.push(stream::VecStream::new(vec![token]));
+            driver::begin_paragraph(input, true);
+        }
+        Mode::Horizontal | Mode::RestrictedHorizontal => ligature::no_boundary(input)?,
+        Mode::Math | Mode::DisplayMath => {}
+    }
+    Ok(())
+}
+
+pub fn get_font<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("font", font_fn, FONT_DOC)
+}
+
+pub fn get_fontname<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("fontname", fontname_fn, FONTNAME_DOC)
+}
+
+pub fn get_fontdimen<S: TexState<S>>() -> primitive::TypedVariable<S, Dimen> {
+    primitive::TypedVariable {
+        name: "fontdimen",
+        index: primitive::Index::Parsed(parse_fontdimen_index),
+        get_fn: fontdimen_get,
+        set_fn: Some(fontdimen_set),
+        docs: FONTDIMEN_DOC,
+    }
+}
+
+pub fn get_hyphenchar<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "hyphenchar",
+        index: primitive::Index::Parsed(parse_font_index),
+        get_fn: |state: &S, index| state.base().fonts.get(FontId(index)).hyphen_char,
+        set_fn: Some(hyphenchar_set),
+        docs: HYPHENCHAR_DOC,
+    }
+}
+
+pub fn get_skewchar<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "skewchar",
+        index: primitive::Index::Parsed(parse_font_index),
+        get_fn: |state: &S, index| state.base().fonts.get(FontId(index)).skew_char,
+        set_fn: Some(skewchar_set),
+        docs: SKEWCHAR_DOC,
+    }
+}
+
+pub fn get_textfont<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("textfont", textfont_fn, TEXTFONT_DOC)
+}
+
+pub fn get_scriptfont<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("scriptfont", scriptfont_fn, SCRIPTFONT_DOC)
+}
+
+pub fn get_scriptscriptfont<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment(
+        "scriptscriptfont",
+        scriptscriptfont_fn,
+        SCRIPTSCRIPTFONT_DOC,
+    )
+}
+
+pub fn get_noboundary<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("noboundary", noboundary_fn, NOBOUNDARY_DOC)
+}
+
+/// Returns the meaning of `\nullfont`, which selects the null font.
+pub fn get_nullfont<S>() -> Primitive<S> {
+    Primitive::Font(NULL_FONT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::filesystem::InMemoryFileSystem;
+    use crate::tex::node::{Char, Node};
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+    use crate::tex::tfm::CharInfo;
+
+    /// A font with design size 10pt, the single character `a` of width 5pt, and the
+    /// interword space 2.5pt.
+    fn tfm() -> Tfm {
+        Tfm {
+            design_size: 10 << 20,
+            first_char: b'a',
+            char_infos: vec![CharInfo {
+                width_index: 1,
+                ..Default::default()
+            }],
+            widths: vec![0, 1 << 19],
+            params: vec![0, 1 << 18],
+            ..Default::default()
+        }
+    }
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        let mut file_system = InMemoryFileSystem::new();
+        file_system.insert_bytes("test.tfm", &tfm().to_bytes());
+        file_system.insert_bytes("bad.tfm", &tfm().to_bytes()[..30]);
+        s.base_mut().file_system = Box::new(file_system);
+        testutil::run(s, input)
+    }
+
+    fn terminal(input: &str) -> String {
+        testutil::terminal(&run(input).unwrap())
+    }
+
+    fn recorded(input: &str) -> Vec<i32> {
+        run(input).unwrap().recorded
+    }
+
+    fn error(input: &str) -> String {
+        match run(input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn font_of(s: &TestState, name: &str) -> Option<FontId> {
+        match s.base().primitives.get_control_sequence(name) {
+            Some(Primitive::Font(id)) => Some(*id),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn load_and_show() {
+        assert_eq!(
+            terminal(r"\font\a=test \font\b=test at 12pt \show\a \show\b \show\nullfont"),
+            "> \\a=select font test.\n> \\b=select font test at 12.0pt.\n\
+             > \\nullfont=select font nullfont."
+        );
+    }
+
+    #[test]
+    fn fontname() {
+        assert_eq!(
+            terminal(r"\font\a=test scaled 1500 \message{\fontname\a/\fontname\nullfont}"),
+            "test at 15.0pt/nullfont"
+        );
+    }
+
+    #[test]
+    fn fonts_are_shared() {
+        let s = run(r"\font\a=test \font\b=test scaled 1000 \font\c=test at 11pt").unwrap();
+        assert_eq!(font_of(&s, "a"), font_of(&s, "b"));
+        assert_ne!(font_of(&s, "a"), font_of(&s, "c"));
+        let s = run(r"\font\a=test \font\b=test at 10pt").unwrap();
+        assert_eq!(font_of(&s, "a"), font_of(&s, "b"));
+    }
+
+    #[test]
+    fn font_definition_is_local_unless_global() {
+        let s = run(r"{\font\a=test \global\font\b=test at 5pt}").unwrap();
+        assert_eq!(font_of(&s, "a"), None);
+        assert_eq!(font_of(&s, "b"), Some(FontId(2)));
+    }
+
+    #[test]
+    fn select_font() {
+        assert_eq!(
+            terminal(
+                r"\font\a=test \showthe\font{\a\showthe\font}\showthe\font{\global\a}\showthe\font"
+            ),
+            "> \\nullfont .\n> \\a .\n> \\nullfont .\n> \\a ."
+        );
+    }
+
+    #[test]
+    fn characters_use_current_font() {
+        // The null font has no characters, so the second a is dropped.
+        let s = run(r"\font\a=test \a a\nullfont a").unwrap();
+        let line = s.base().nest.current().list[1]
+            .as_box()
+            .unwrap()
+            .list
+            .clone();
+        assert_eq!(
+            line[1],
+            Node::Char(Char {
+                font: FontId(1),
+                c: 'a'
+            })
+        );
+        assert_eq!(line[2], Node::Penalty(10000));
+    }
+
+    #[test]
+    fn the_font_identifier_is_the_latest_definition() {
+        assert_eq!(terminal(r"\font\a=test \font\b=test \showthe\a"), "> \\b .");
+    }
+
+    #[test]
+    fn fontdimen() {
+        let input = r"
+            \font\a=test at 20pt
+            \dimen0=\fontdimen2\a \record\dimen0
+            \record\fontdimen7\a
+            {\fontdimen2\a=1pt}\record\fontdimen2\font
+        ";
+        assert_eq!(recorded(input), vec![Dimen::from_pt(5).0, 0, 0]);
+        let input = r"
+            \font\a=test {\a\global\fontdimen2\font=3pt}\record\fontdimen2\a
+        ";
+        assert_eq!(recorded(input), vec![Dimen::from_pt(3).0]);
+    }
+
+    #[test]
+    fn fontdimen_extends_last_font() {
+        let input = r"\font\a=test \fontdimen9\a=1pt \record\fontdimen9\a \record\fontdimen8\a";
+        assert_eq!(recorded(input), vec![Dimen::from_pt(1).0, 0]);
+        let err = error(r"\font\a=test \font\b=test at 5pt \fontdimen8\a=1pt");
+        assert!(err.contains("Font \\a has only 7 fontdimen parameters"));
+        let err = error(r"\record\fontdimen0\nullfont");
+        assert!(err.contains("Font \\nullfont has only 7 fontdimen parameters"));
+    }
+
+    #[test]
+    fn hyphenchar_and_skewchar() {
+        let input = r"
+            \defaulthyphenchar=`- \defaultskewchar=`a
+            \font\a=test
+            \record\hyphenchar\a \record\skewchar\a
+            \record\hyphenchar\nullfont \record\skewchar\nullfont
+            {\hyphenchar\a=1 \skewchar\a=2}\record\hyphenchar\a \record\skewchar\a
+        ";
+        assert_eq!(recorded(input), vec![45, 97, 45, -1, 1, 2]);
+    }
+
+    #[test]
+    fn math_families() {
+        let input = r"
+            \font\a=test \font\b=test at 7pt
+            \textfont1=\a \scriptfont1=\b {\global\scriptscriptfont15\b \textfont1=\nullfont}
+            \showthe\textfont1 \showthe\scriptfont1 \showthe\scriptscriptfont15
+            \showthe\textfont0 \message{\fontname\scriptfont1}
+        ";
+        assert_eq!(
+            terminal(input),
+            "> \\a .\n> \\b .\n> \\b .\n> \\nullfont . test at 7.0pt"
+        );
+        let input = r"\font\a=test at 20pt \textfont2=\a \record\fontdimen2\textfont2";
+        assert_eq!(recorded(input), vec![Dimen::from_pt(5).0]);
+    }
+
+    #[test]
+    fn errors() {
+        let err = error(r"\font\a=missing");
+        assert!(err.contains("Font \\a=missing not loadable: Metric (TFM) file not found"));
+        let err = error(r"\font\a=bad scaled 2000");
+        assert!(err.contains("Font \\a=bad scaled 2000 not loadable: Bad metric (TFM) file"));
+        assert!(error(r"\font\a=test at -1pt").contains("Improper `at' size (-1.0pt)"));
+        assert!(error(r"\font\a=test at 2048pt").contains("Improper `at' size"));
+        assert!(error(r"\font\a=test scaled 0").contains("Illegal magnification (0)"));
+        assert!(error(r"\message{\fontname a}").contains("Missing font identifier"));
+        assert!(error(r"\textfont16=\nullfont").contains("Bad number (16)"));
+        assert!(error(r"\textfont0=a").contains("Missing font identifier"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Primitives for working with groups: `\begingroup`, `\endgroup`, `\aftergroup`, and e-TeX's
+//! `\currentgrouplevel` and `\currentgrouptype`.
+//!
+//! Groups begun and ended by begin group and end group characters are handled directly
+//! in the driver.
+
+use crate::tex::driver;
+use crate::tex::group::GroupType;
+use crate::tex::primitive;
+use crate::tex::state::TexState;
+use crate::tex::token::token;
+
+static BEGINGROUP_DOC: &str = "Begin a new semi-simple group";
+static ENDGROUP_DOC: &str = "End a semi-simple group begun with \\begingroup";
+static AFTERGROUP_DOC: &str = "Insert the next token into the input after the current group ends";
+static CURRENTGROUPLEVEL_DOC: &str = "The number of currently open groups";
+static CURRENTGROUPTYPE_DOC: &str = "The type of the innermost open group";
+
+fn begingroup_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    input
+        .state_mut()
+        .base_mut()
+        .begin_group(GroupType::SemiSimple);
+    Ok(())
+}
+
+fn endgroup_fn<S: TexState<S>>(
+    token: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    driver::end_group(token, input, GroupType::SemiSimple)?;
+    Ok(())
+}
+
+fn aftergroup_fn<S: TexState<S>>(
+    _: token::Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    match input.unexpanded_stream().next()? {
+        None => Err(anyhow::anyhow!(
+            "Unexpected end of input: expected a token after \\aftergroup"
+        )),
+        Some(token) => {
+            input.state_mut().base_mut().after_group(token);
+            Ok(())
+        }
+    }
+}
+
+pub fn get_begingroup<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("begingroup", begingroup_fn, BEGINGROUP_DOC)
+}
+
+pub fn get_endgroup<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("endgroup", endgroup_fn, ENDGROUP_DOC)
+}
+
+pub fn get_aftergroup<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("aftergroup", aftergroup_fn, AFTERGROUP_DOC)
+}
+
+pub fn get_currentgrouplevel<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "currentgrouplevel",
+        index: primitive::Index::Fixed(0),
+        get_fn: |state: &S, _| state.base().group_level() as i32,
+        set_fn: None,
+        docs: CURRENTGROUPLEVEL_DOC,
+    }
+}
+
+pub fn get_currentgrouptype<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "currentgrouptype",
+        index: primitive::Index::Fixed(0),
+        get_fn: |state: &S, _| state.base().group_type().int(),
+        set_fn: None,
+        docs: CURRENTGROUPTYPE_DOC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+
+    fn run_success(input: &str) -> Vec<i32> {
+        testutil::run(testutil::new_state(), input)
+            .unwrap()
+            .recorded
+    }
+
+    fn run_failure(input: &str) {
+        assert!(testutil::run(testutil::new_state(), input).is_err());
+    }
+
+    #[test]
+    fn group_level() {
+        assert_eq!(
+            run_success(
+                r"\record\currentgrouplevel{\record\currentgrouplevel\begingroup\record\currentgrouplevel\endgroup}\record\currentgrouplevel"
+            ),
+            vec![0, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn group_type() {
+        assert_eq!(
+            run_success(
+                r"\record\currentgrouptype{\record\currentgrouptype\begingroup\record\currentgrouptype\endgroup}"
+            ),
+            vec![0, 1, 14]
+        );
+    }
+
+    #[test]
+    fn aftergroup() {
+        assert_eq!(run_success(r"{\aftergroup\record\record2}5"), vec![2, 5]);
+    }
+
+    #[test]
+    fn aftergroup_multiple_tokens() {
+        assert_eq!(
+            run_success(r"\begingroup\aftergroup\record\aftergroup7\endgroup"),
+            vec![7]
+        );
+    }
+
+    #[test]
+    fn aftergroup_at_bottom_level() {
+        assert_eq!(run_success(r"\aftergroup\record\record 3"), vec![3]);
+    }
+
+    #[test]
+    fn assign_to_group_level() {
+        run_failure(r"\currentgrouplevel=3 ");
+    }
+
+    #[test]
+    fn extra_end_group_character() {
+        run_failure(r"}");
+    }
+
+    #[test]
+    fn extra_endgroup() {
+        run_failure(r"\endgroup");
+    }
+
+    #[test]
+    fn mismatched_end_group_character() {
+        run_failure(r"\begingroup}");
+    }
+
+    #[test]
+    fn mismatched_endgroup() {
+        run_failure(r"{\endgroup");
+    }
+
+    #[test]
+    fn scopes_are_rolled_back() {
+        let mut s = testutil::new_state();
+        s.base_mut().begin_group(GroupType::Simple);
+        s.base_mut()
+            .input_module
+            .cat_code_map
+            .insert('@', crate::tex::token::catcode::RawCatCode::Escape);
+        assert_eq!(s.base().group_level(), 1);
+        assert!(s.base_mut().end_group().is_some());
+        assert_eq!(s.base().input_module.cat_code_map.get(&'@'), None);
+        assert!(s.base_mut().end_group().is_none());
+    }
+}
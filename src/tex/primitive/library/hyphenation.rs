@@ -0,0 +1,457 @@
+//! Hyphenation primitives: `\patterns` and `\hyphenation`, which add to the hyphenation tables
+//! of the current `\language`, `\setlanguage`, and `\showhyphens`.
+//!
+//! The tables are described in the [hyphenation](crate::tex::hyphenation) module. Patterns
+//! must be given before the first paragraph is hyphenated, while exceptions can be given at any
+//! time:
+//! ```tex
+//! \patterns{hy3ph he2n hena4 hen5at 1na n2at 1tio 2io}
+//! \hyphenation{ta-ble present}
+//! \showhyphens{hyphenation table present}  % shows "hy-phen-ation ta-ble present"
+//! ```
+//! `\setlanguage` changes the language of the rest of a paragraph without changing
+//! `\language`. `\showhyphens` is a macro in plain TeX; here it is a primitive that does the
+//! same: it typesets its argument as a paragraph with a single underfull line, which is shown
+//! on the terminal with the hyphens where the words may be hyphenated.
+
+use crate::tex::dimen::{Dimen, Glue, MAX_DIMEN};
+use crate::tex::driver;
+use crate::tex::error;
+use crate::tex::files::Whatsit;
+use crate::tex::group::{BoxContext, GroupType};
+use crate::tex::hyphenation::{self, Language, EDGE_OF_WORD, MAX_WORD_LENGTH};
+use crate::tex::nest::Mode;
+use crate::tex::node::Node;
+use crate::tex::pack;
+use crate::tex::parameter::{DimenParam, GlueParam, IntParam};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::{catcode, chardef, prefix};
+use crate::tex::primitive::{ConstantKind, Execution, Primitive};
+use crate::tex::state::TexState;
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::stream;
+use crate::tex::token::stream::Stream;
+use crate::tex::token::token::Token;
+
+static PATTERNS_DOC: &str = "Add hyphenation patterns to the current language";
+static HYPHENATION_DOC: &str = "Add words with explicit hyphenation to the current language";
+static SETLANGUAGE_DOC: &str = "Set the language of the rest of the paragraph";
+static SHOWHYPHENS_DOC: &str = "Show where the words of a text may be hyphenated";
+
+/// A token of the list of `\patterns` or `\hyphenation`.
+enum Item {
+    Char(char),
+    /// A space, which ends a pattern or word.
+    Space,
+    /// The right brace that ends the list.
+    End,
+    Other,
+}
+
+/// Reads the next token of the list of `\patterns` or `\hyphenation`, with expansion. Only
+/// `\hyphenation` accepts characters given using `\chardef` or `\char`.
+fn next_item<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    command: &str,
+) -> anyhow::Result<(Token, Item)> {
+    let token = match input.next()? {
+        None => {
+            return Err(anyhow::anyhow!(
+                "Unexpected end of input while reading the list of {}",
+                command
+            ))
+        }
+        Some(token) => token,
+    };
+    let meaning = input.state().base().meaning(&token.value);
+    let accept_chars = command == "\\hyphenation";
+    let item = match meaning {
+        Some(Primitive::Character(c, CatCode::Letter | CatCode::Other)) => Item::Char(c),
+        Some(Primitive::Character(_, CatCode::Space)) => Item::Space,
+        Some(Primitive::Character(_, CatCode::EndGroup)) => Item::End,
+        // The code was validated by \chardef.
+        Some(Primitive::Constant(ConstantKind::Char, n)) if accept_chars => {
+            Item::Char(char::from_u32(n as u32).unwrap())
+        }
+        Some(p)
+            if accept_chars
+                && p.same_meaning(&Primitive::Execution(
+                    Execution::Static(chardef::get_char()),
+                )) =>
+        {
+            let n = catcode::parse_char(input)?;
+            // The code was validated by `parse_char`.
+            Item::Char(char::from_u32(n as u32).unwrap())
+        }
+        _ => Item::Other,
+    };
+    Ok((token, item))
+}
+
+/// Returns the lowercase code of a character in a pattern or exception, or an error if the
+/// character is not a letter.
+fn letter<S: TexState<S>>(
+    input: &primitive::Input<S>,
+    token: Token,
+    c: char,
+    command: &str,
+) -> anyhow::Result<char> {
+    match input.state().base().char_codes.lc_code(c) {
+        0 => Err(error::new_token_error(
+            token,
+            format!("Not a letter in {}", command),
+            vec![format!(
+                "letters in {} must have a nonzero \\lccode",
+                command
+            )],
+        )),
+        // Case codes are valid characters.
+        lc => Ok(char::from_u32(lc as u32).unwrap()),
+    }
+}
+
+fn patterns_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    if input.state().base().hyphenation.is_packed() {
+        return Err(error::new_token_error(
+            token,
+            "Too late for \\patterns".to_string(),
+            vec!["all patterns must be given before typesetting begins".to_string()],
+        ));
+    }
+    let language =
+        hyphenation::language_number(input.state().base().parameters.int(IntParam::Language));
+    parse::parse_left_brace(input)?;
+    let mut letters = Vec::new();
+    let mut values = vec![0];
+    let mut digit_sensed = false;
+    loop {
+        let (token, item) = next_item(input, "\\patterns")?;
+        match item {
+            Item::Char(c) if !digit_sensed && c.is_ascii_digit() => {
+                if letters.len() < MAX_WORD_LENGTH {
+                    // The digit is an ASCII digit.
+                    *values.last_mut().unwrap() = c.to_digit(10).unwrap() as u8;
+                    digit_sensed = true;
+                }
+            }
+            Item::Char(c) => {
+                let c = match c {
+                    '.' => EDGE_OF_WORD,
+                    c => letter(input, token, c, "\\patterns")?,
+                };
+                if letters.len() < MAX_WORD_LENGTH {
+                    letters.push(c);
+                    values.push(0);
+                    digit_sensed = false;
+                }
+            }
+            Item::Space | Item::End => {
+                if !letters.is_empty() {
+                    let hyphenation = &mut input.state_mut().base_mut().hyphenation;
+                    if !hyphenation.add_pattern(language, &letters, &values) {
+                        return Err(error::new_token_error(
+                            token,
+                            "Duplicate pattern".to_string(),
+                            vec![
+                                "the language already has a pattern with these letters".to_string()
+                            ],
+                        ));
+                    }
+                }
+                if let Item::End = item {
+                    return Ok(());
+                }
+                letters.clear();
+                values = vec![0];
+                digit_sensed = false;
+            }
+            Item::Other => {
+                return Err(error::new_token_error(
+                    token,
+                    "Bad \\patterns".to_string(),
+                    vec!["patterns may only contain letters, digits and dots".to_string()],
+                ))
+            }
+        }
+    }
+}
+
+fn hyphenation_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let language =
+        hyphenation::language_number(input.state().base().parameters.int(IntParam::Language));
+    parse::parse_left_brace(input)?;
+    let mut word = Vec::new();
+    let mut hyphens = Vec::new();
+    loop {
+        let (token, item) = next_item(input, "\\hyphenation")?;
+        match item {
+            Item::Char('-') => hyphens.push(word.len()),
+            Item::Char(c) => {
+                let c = letter(input, token, c, "\\hyphenation")?;
+                if word.len() < MAX_WORD_LENGTH {
+                    word.push(c);
+                }
+            }
+            Item::Space | Item::End => {
+                // As in TeX, words of one letter are ignored.
+                if word.len() > 1 {
+                    input
+                        .state_mut()
+                        .base_mut()
+                        .hyphenation
+                        .add_exception(language, word, hyphens);
+                }
+                if let Item::End = item {
+                    return Ok(());
+                }
+                word = Vec::new();
+                hyphens = Vec::new();
+            }
+            Item::Other => {
+                return Err(error::new_token_error(
+                    token,
+                    "Improper \\hyphenation".to_string(),
+                    vec![
+                        "hyphenation exceptions must contain only letters and hyphens".to_string(),
+                    ],
+                ))
+            }
+        }
+    }
+}
+
+fn setlanguage_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let mode = input.state().base().nest.mode();
+    if !mode.is_horizontal() {
+        return Err(driver::illegal_in_mode_error(token, "\\setlanguage", mode));
+    }
+    let number = parse::parse_number(input)?;
+    let base = input.state_mut().base_mut();
+    let parameters = &base.parameters;
+    let language = Language::new(
+        number,
+        parameters.int(IntParam::LeftHyphenMin),
+        parameters.int(IntParam::RightHyphenMin),
+    );
+    base.nest.current_mut().language = language.number;
+    base.nest.append(Node::Whatsit(Whatsit::Language(language)));
+    Ok(())
+}
+
+/// Typesets the text as plain TeX's `\showhyphens` macro does:
+/// ```tex
+/// \setbox0\vbox{\parfillskip=0pt \hsize=\maxdimen \pretolerance=-1 \tolerance=-1
+///   \hbadness=0 \showboxdepth=0 \ #1}
+/// ```
+/// Unlike the macro, this uses the current font instead of `\tenrm`.
+fn showhyphens_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let mut text = parse::parse_balanced_text(input)?;
+    let base = input.state_mut().base_mut();
+    base.begin_box_group(
+        GroupType::VBox,
+        BoxContext::SetBox {
+            register: 0,
+            global: false,
+        },
+        pack::Spec::NATURAL,
+    );
+    driver::normal_paragraph(base);
+    base.nest.push(Mode::InternalVertical);
+    let parameters = &mut base.parameters;
+    parameters.set_glue(GlueParam::ParFillSkip, Glue::default(), false);
+    parameters.set_dimen(DimenParam::HSize, Dimen(MAX_DIMEN), false);
+    parameters.set_int(IntParam::PreTolerance, -1, false);
+    parameters.set_int(IntParam::Tolerance, -1, false);
+    parameters.set_int(IntParam::HBadness, 0, false);
+    parameters.set_int(IntParam::ShowBoxDepth, 0, false);
+    // The first word follows a space, so that it can be hyphenated.
+    driver::begin_paragraph(input, true);
+    driver::append_space(input.state_mut().base_mut());
+    text.push(Token::new_end_group('}'));
+    input.push(stream::VecStream::new(text));
+    Ok(())
+}
+
+pub fn get_patterns<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("patterns", patterns_fn, PATTERNS_DOC)
+}
+
+pub fn get_hyphenation<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("hyphenation", hyphenation_fn, HYPHENATION_DOC)
+}
+
+pub fn get_setlanguage<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("setlanguage", setlanguage_fn, SETLANGUAGE_DOC)
+}
+
+pub fn get_showhyphens<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("showhyphens", showhyphens_fn, SHOWHYPHENS_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    /// The US English patterns, with the exceptions of plain TeX.
+    const HYPH_EN_US: &str = include_str!("../../testdata/hyph-en-us.tex");
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        s.base_mut().char_codes.set_initex_defaults();
+        testutil::select_test_font(&mut s);
+        let fonts = &mut s.base_mut().fonts;
+        let font = fonts.get_mut(fonts.current());
+        font.identifier = "tenrm".to_string();
+        *font.param_mut(2).unwrap() = Dimen::from_pt(3);
+        testutil::run(s, input)
+    }
+
+    /// Returns the line shown by `\showhyphens` after loading the patterns of plain TeX.
+    fn show_hyphens(input: &str) -> String {
+        let input = format!(
+            r"{}\lefthyphenmin=2 \righthyphenmin=3 {}",
+            HYPH_EN_US, input
+        );
+        let s = run(&input).unwrap();
+        let terminal = testutil::terminal(&s);
+        assert!(
+            terminal.contains("Underfull \\hbox (badness 10000) in paragraph"),
+            "{}",
+            terminal
+        );
+        terminal
+            .lines()
+            .find_map(|line| line.strip_prefix(r"[] \tenrm "))
+            .unwrap_or_else(|| panic!("no line shown: {}", terminal))
+            .to_string()
+    }
+
+    fn error(input: &str) -> String {
+        match run(&format!("{}{}", HYPH_EN_US, input)) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(show_hyphens(r"\showhyphens{hyphenation}"), "hy-phen-ation");
+        assert_eq!(
+            show_hyphens(r"\showhyphens{(hyphenation).}"),
+            "(hy-phen-ation)."
+        );
+    }
+
+    #[test]
+    fn real_words() {
+        // The hyphenations TeX finds with the US English patterns. The first is also shown in
+        // Appendix H of The TeXbook.
+        let words = [
+            "su-per-cal-ifrag-ilis-tic-ex-pi-ali-do-cious",
+            "man-u-script",
+            "com-puter",
+            "type-set-ting",
+            "al-go-rithm",
+            "math-e-mat-ics",
+            "dic-tio-nary",
+            "para-graph",
+            "pro-gram-ming",
+            "uni-ver-sity",
+            "con-cate-na-tion",
+            "en-cy-clo-pe-dia",
+            "bib-li-og-ra-phy",
+            "doc-u-men-ta-tion",
+            "char-ac-ter",
+            "rep-re-sen-ta-tion",
+        ];
+        for word in words {
+            let input = format!(r"\showhyphens{{{}}}", word.replace('-', ""));
+            assert_eq!(show_hyphens(&input), word);
+        }
+    }
+
+    #[test]
+    fn exceptions() {
+        assert_eq!(
+            show_hyphens(r"\showhyphens{table present associates}"),
+            "ta-ble present as-so-ciates"
+        );
+        assert_eq!(
+            show_hyphens(r"\hyphenation{hyp-hena-ti-on}\showhyphens{hyphenation}"),
+            "hyp-hena-tion"
+        );
+        assert_eq!(
+            show_hyphens(r"\hyphenation{hyphe-nation}\showhyphens{hyphenation}"),
+            "hyphe-nation"
+        );
+    }
+
+    #[test]
+    fn hyphen_minimums() {
+        assert_eq!(
+            show_hyphens(r"\lefthyphenmin=3 \showhyphens{hyphenation}"),
+            "hyphen-ation"
+        );
+        assert_eq!(
+            show_hyphens(r"\righthyphenmin=6 \showhyphens{hyphenation}"),
+            "hy-phenation"
+        );
+    }
+
+    #[test]
+    fn uppercase_words() {
+        assert_eq!(
+            show_hyphens(r"\uchyph=0 \showhyphens{Hyphenation hyPhenation}"),
+            "Hyphenation hy-Phen-ation"
+        );
+        assert_eq!(
+            show_hyphens(r"\uchyph=1 \showhyphens{Hyphenation}"),
+            "Hy-phen-ation"
+        );
+    }
+
+    #[test]
+    fn languages() {
+        let input = format!(
+            r"\language=2 {}\language=0 \lefthyphenmin=2 \righthyphenmin=3 ",
+            HYPH_EN_US
+        );
+        for (text, line) in [
+            // Language 0 has no patterns or exceptions.
+            (r"hyphenation table", r"[] \tenrm hyphenation table"),
+            (r"\language=2 hyphenation", r"[] []\tenrm hy-phen-ation"),
+            (r"table \language=2 table", r"[] \tenrm table []ta-ble"),
+            // The language is changed back to \language by the next character.
+            (r"\setlanguage2 hyphenation", r"[] [][]\tenrm hyphenation"),
+        ] {
+            let s = run(&format!(r"{}\showhyphens{{{}}}", input, text)).unwrap();
+            let terminal = testutil::terminal(&s);
+            assert!(
+                terminal.lines().any(|l| l == line),
+                "{}: {}",
+                text,
+                terminal
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert!(error(r"\showhyphens{hyphenation}\patterns{ab}").contains("Too late"));
+        assert!(error(r"\patterns{1na}").contains("Duplicate pattern"));
+        assert!(error(r"\patterns{a$}").contains(r"Bad \patterns"));
+        assert!(error(r"\patterns{a-b}").contains(r"Not a letter in \patterns"));
+        assert!(error(r"\hyphenation{ab1}").contains(r"Not a letter in \hyphenation"));
+        assert!(error(r"\hyphenation{a$}").contains(r"Improper \hyphenation"));
+        assert!(error(r"\setlanguage1").contains(r"You can't use `\setlanguage'"));
+    }
+}
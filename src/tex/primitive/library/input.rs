@@ -0,0 +1,169 @@
+//! Primitives that read other files: `\input` and `\endinput`.
+//!
+//! `\input` reads a file name and begins reading the file, before the rest of the current
+//! input. `\endinput` makes the current line the last line of the current file:
+//! ```tex
+//! \input macros     % reads macros.tex or, if it doesn't exist, macros
+//! \endinput % the rest of this line is still read, but not the lines that follow
+//! ```
+//! As in TeX, the name of each file is printed in parentheses on the terminal and in the log
+//! file while it is being read, so that `\input a` followed by `\message{x}` inside `a.tex`
+//! prints `(a.tex x)`.
+
+use crate::tex::error;
+use crate::tex::filesystem;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::print::{Selector, MAX_PRINT_LINE};
+use crate::tex::state::TexState;
+use crate::tex::token::stream;
+use crate::tex::token::token::Token;
+
+static INPUT_DOC: &str = "Read a file before the rest of the input";
+static ENDINPUT_DOC: &str = "Make the current line the last line of the current file";
+
+fn input_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let file_name = parse::parse_file_name(input)?;
+    let with_extension = filesystem::with_default_extension(file_name.clone(), "tex");
+    let base = input.state_mut().base_mut();
+    let (file, name) = match base.file_system.open(&with_extension) {
+        Ok(file) => (file, with_extension),
+        Err(_) => match base.file_system.open(&file_name) {
+            Ok(file) => (file, file_name),
+            Err(_) => {
+                return Err(error::new_token_error(
+                    token,
+                    format!("I can't find file `{}'", with_extension),
+                    vec![],
+                ))
+            }
+        },
+    };
+    // As in TeX, the file name starts on a new line if it doesn't fit on the current one, and
+    // is otherwise separated from preceding text by a space.
+    let printer = &mut base.printer;
+    if printer.terminal_offset() + name.chars().count() > MAX_PRINT_LINE - 2 {
+        printer.print_ln(Selector::TerminalAndLog);
+    } else if printer.terminal_offset() > 0 || printer.log_offset() > 0 {
+        base.print(Selector::TerminalAndLog, " ");
+    }
+    base.print(Selector::TerminalAndLog, &format!("({}", name));
+    base.printer.flush();
+    input.push_file(file, &name);
+    Ok(stream::VecStream::new(Vec::new()))
+}
+
+fn endinput_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    input
+        .state_mut()
+        .base_mut()
+        .input_module
+        .end_file_after_current_line();
+    Ok(stream::VecStream::new(Vec::new()))
+}
+
+pub fn get_input<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("input", input_fn, INPUT_DOC)
+}
+
+pub fn get_endinput<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("endinput", endinput_fn, ENDINPUT_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tex::filesystem::InMemoryFileSystem;
+    use crate::tex::state::TexState;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(files: &[(&str, &str)], input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        let mut file_system = InMemoryFileSystem::new();
+        for (path, contents) in files {
+            file_system.insert(path, contents);
+        }
+        s.base_mut().file_system = Box::new(file_system);
+        testutil::run(s, input)
+    }
+
+    #[test]
+    fn input() {
+        let s = run(
+            &[("a.tex", "\\record 2\n\\def\\b{\\record 4}\n")],
+            r"\record 1 \input a \record 3 \b",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn input_without_extension() {
+        let s = run(&[("a", "\\record 2")], r"\input a \record 3").unwrap();
+        assert_eq!(s.recorded, vec![2, 3]);
+    }
+
+    #[test]
+    fn file_is_read_before_the_rest_of_a_macro() {
+        let s = run(
+            &[("a.tex", "\\record 2")],
+            r"\def\x{\input a \record 3}\x\record 4",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn nested_files() {
+        let s = run(
+            &[
+                ("a.tex", "\\message{a}\\input b \\message{c}"),
+                ("b.tex", "\\message{b}"),
+            ],
+            r"\input a \message{d}",
+        )
+        .unwrap();
+        assert_eq!(testutil::terminal(&s), "(a.tex a (b.tex b) c) d");
+    }
+
+    #[test]
+    fn everyeof_is_inserted_before_the_rest_of_the_input() {
+        let s = run(
+            &[("a.tex", "\\everyeof{\\record 3}\\record 2")],
+            r"\def\x{\input a \record 4}\x\everyeof{}",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn endinput() {
+        let s = run(
+            &[("a.tex", "\\record 1 \\endinput \\record 2\n\\record 3\n")],
+            r"\input a \record 4",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn missing_file() {
+        let err = run(&[], r"\input missing ").err().unwrap().to_string();
+        assert!(err.contains("I can't find file `missing.tex'"));
+    }
+
+    #[test]
+    fn errors_show_the_file_name() {
+        let err = run(&[("a.tex", "\n  \\undefined")], r"\input a ")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("a.tex:2:3"));
+    }
+}
@@ -0,0 +1,670 @@
+//! Primitives that append nodes to the current list and remove them from it: `\penalty`,
+//! `\kern`, the glue commands like `\hskip` and `\vfil`, and `\unpenalty`, `\unkern` and
+//! `\unskip`. This module also contains the primitives that read the last node of the current
+//! list: `\lastskip`, `\lastpenalty` and `\lastkern`.
+//!
+//! The horizontal glue commands begin a paragraph in vertical mode, and the vertical glue
+//! commands end the paragraph in horizontal mode. Penalties and kerns can be appended in any
+//! mode.
+//! ```tex
+//! \hskip 1pt plus 2fil  % equivalent to \hskip 1pt \hfil\hfil, but a single glue node
+//! \penalty-10000        % forces a line break
+//! \unskip               % removes the glue appended last
+//! ```
+//!
+//! Each of the last quantities is zero unless the last node of the current list is glue, a
+//! penalty or a kern respectively. In the outer vertical mode the page builder may already have
+//! moved the contributions to the current page; if the contributions are empty, the last node
+//! that was moved is used instead, as in TeX:
+//! ```tex
+//! \hbox{a \showthe\lastskip}  % shows the interword glue after a
+//! ```
+
+use crate::tex::dimen::{Dimen, Glue, GlueOrder, UNITY};
+use crate::tex::driver;
+use crate::tex::error;
+use crate::tex::nest::Mode;
+use crate::tex::node::{Kern, Node};
+use crate::tex::page;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::state::TexState;
+use crate::tex::token::token::Token;
+
+static PENALTY_DOC: &str = "Append a penalty to the current list";
+static KERN_DOC: &str = "Append a kern to the current list";
+static HSKIP_DOC: &str = "Append glue to the current horizontal list";
+static VSKIP_DOC: &str = "Append glue to the current vertical list";
+static HFIL_DOC: &str = "Append glue that stretches by 1fil to the current horizontal list";
+static HFILL_DOC: &str = "Append glue that stretches by 1fill to the current horizontal list";
+static HSS_DOC: &str =
+    "Append glue that stretches and shrinks by 1fil to the current horizontal list";
+static HFILNEG_DOC: &str = "Append glue that stretches by -1fil to the current horizontal list";
+static VFIL_DOC: &str = "Append glue that stretches by 1fil to the current vertical list";
+static VFILL_DOC: &str = "Append glue that stretches by 1fill to the current vertical list";
+static VSS_DOC: &str =
+    "Append glue that stretches and shrinks by 1fil to the current vertical list";
+static VFILNEG_DOC: &str = "Append glue that stretches by -1fil to the current vertical list";
+static UNPENALTY_DOC: &str = "Remove the penalty at the end of the current list";
+static UNKERN_DOC: &str = "Remove the kern at the end of the current list";
+static UNSKIP_DOC: &str = "Remove the glue at the end of the current list";
+static LASTSKIP_DOC: &str = "The glue at the end of the current list";
+static LASTPENALTY_DOC: &str = "The penalty at the end of the current list";
+static LASTKERN_DOC: &str = "The kern at the end of the current list";
+
+fn penalty_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let penalty = parse::parse_number(input)?;
+    let base = input.state_mut().base_mut();
+    base.nest.append(Node::Penalty(penalty));
+    if base.nest.mode() == Mode::Vertical {
+        page::build_page(input)?;
+    }
+    Ok(())
+}
+
+fn kern_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let width = parse::parse_dimen(input)?;
+    let base = input.state_mut().base_mut();
+    base.nest.append(Node::Kern(Kern::new(width)));
+    Ok(())
+}
+
+/// The glue of the commands like `\hfil` that append fixed glue.
+#[derive(Debug, Copy, Clone)]
+enum FixedGlue {
+    /// `\hfil` and `\vfil`: 0pt plus 1fil.
+    Fil,
+    /// `\hfill` and `\vfill`: 0pt plus 1fill.
+    Fill,
+    /// `\hss` and `\vss`: 0pt plus 1fil minus 1fil.
+    Ss,
+    /// `\hfilneg` and `\vfilneg`: 0pt plus -1fil.
+    FilNeg,
+}
+
+impl FixedGlue {
+    fn glue(self) -> Glue {
+        let (stretch, stretch_order, shrink) = match self {
+            FixedGlue::Fil => (UNITY, GlueOrder::Fil, 0),
+            FixedGlue::Fill => (UNITY, GlueOrder::Fill, 0),
+            FixedGlue::Ss => (UNITY, GlueOrder::Fil, UNITY),
+            FixedGlue::FilNeg => (-UNITY, GlueOrder::Fil, 0),
+        };
+        Glue {
+            width: Dimen(0),
+            stretch: Dimen(stretch),
+            stretch_order,
+            shrink: Dimen(shrink),
+            shrink_order: match shrink {
+                0 => GlueOrder::Normal,
+                _ => GlueOrder::Fil,
+            },
+        }
+    }
+}
+
+fn hskip_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, false, None)
+}
+
+fn vskip_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, true, None)
+}
+
+fn hfil_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, false, Some(FixedGlue::Fil))
+}
+
+fn hfill_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, false, Some(FixedGlue::Fill))
+}
+
+fn hss_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, false, Some(FixedGlue::Ss))
+}
+
+fn hfilneg_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, false, Some(FixedGlue::FilNeg))
+}
+
+fn vfil_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, true, Some(FixedGlue::Fil))
+}
+
+fn vfill_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, true, Some(FixedGlue::Fill))
+}
+
+fn vss_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, true, Some(FixedGlue::Ss))
+}
+
+fn vfilneg_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_glue(token, input, true, Some(FixedGlue::FilNeg))
+}
+
+/// Appends glue to the current list: the fixed glue if provided, and otherwise glue read from
+/// the input. This is `append_glue` in TeX.
+///
+/// Horizontal glue in vertical mode begins a paragraph, and vertical glue in a paragraph ends
+/// it, before the glue is read.
+fn append_glue<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    vertical: bool,
+    fixed: Option<FixedGlue>,
+) -> anyhow::Result<()> {
+    let done = match vertical {
+        false => driver::leave_vertical_mode(token.clone(), input),
+        true => driver::head_for_vertical_mode(token, input, vertical_glue_description(fixed))?,
+    };
+    if done {
+        return Ok(());
+    }
+    let glue = match fixed {
+        Some(fixed) => fixed.glue(),
+        None => parse::parse_glue(input)?,
+    };
+    input
+        .state_mut()
+        .base_mut()
+        .nest
+        .append(Node::new_glue(glue));
+    Ok(())
+}
+
+fn vertical_glue_description(fixed: Option<FixedGlue>) -> &'static str {
+    match fixed {
+        None => "\\vskip",
+        Some(FixedGlue::Fil) => "\\vfil",
+        Some(FixedGlue::Fill) => "\\vfill",
+        Some(FixedGlue::Ss) => "\\vss",
+        Some(FixedGlue::FilNeg) => "\\vfilneg",
+    }
+}
+
+fn unpenalty_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    delete_last(token, input, Removable::Penalty)
+}
+
+fn unkern_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    delete_last(token, input, Removable::Kern)
+}
+
+fn unskip_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    delete_last(token, input, Removable::Glue)
+}
+
+/// The kinds of nodes that can be removed from the end of the current list.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Removable {
+    Penalty,
+    Kern,
+    Glue,
+}
+
+impl Removable {
+    fn matches(self, node: &Node) -> bool {
+        matches!(
+            (self, node),
+            (Removable::Penalty, Node::Penalty(_))
+                | (Removable::Kern, Node::Kern(_))
+                | (Removable::Glue, Node::Glue(_))
+        )
+    }
+}
+
+/// Removes the last node of the current list if it is of the provided kind. This is
+/// `delete_last` in TeX.
+///
+/// As in TeX, nodes cannot be removed from the current page: in the outer vertical mode with no
+/// contributions, `\unpenalty` and `\unkern` are errors, and so is `\unskip` if the last node
+/// moved to the current page is glue. A node that is part of the replacement of a
+/// discretionary is not removed either.
+fn delete_last<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    kind: Removable,
+) -> anyhow::Result<()> {
+    let state = input.state_mut();
+    if let Last::Page = last(state) {
+        if kind == Removable::Glue && state.base().page.last_glue.is_none() {
+            return Ok(());
+        }
+        let (description, help) = match kind {
+            Removable::Penalty => (
+                "\\unpenalty",
+                "perhaps you can make the output routine do it",
+            ),
+            Removable::Kern => ("\\unkern", "try `\\kern-\\lastkern' instead"),
+            Removable::Glue => ("\\unskip", "try `\\vskip-\\lastskip' instead"),
+        };
+        return Err(error::new_token_error(
+            token,
+            format!("You can't use `{}' in vertical mode", description),
+            vec![
+                "sorry, nodes usually can't be taken from the current page".to_string(),
+                help.to_string(),
+            ],
+        ));
+    }
+    let list = &mut state.base_mut().nest.current_mut().list;
+    let n = list.len();
+    match list.last() {
+        Some(node) if kind.matches(node) => {}
+        _ => return Ok(()),
+    }
+    let replaced = list[..n - 1]
+        .iter()
+        .enumerate()
+        .any(|(i, node)| match node {
+            Node::Discretionary(d) => i + d.replace_count >= n - 1,
+            _ => false,
+        });
+    if !replaced {
+        list.pop();
+    }
+    Ok(())
+}
+
+/// The last node of the current list, or, in the outer vertical mode with no contributions,
+/// the page builder's record of the last node it moved to the current page.
+enum Last<'a> {
+    Node(Option<&'a Node>),
+    Page,
+}
+
+fn last<S: TexState<S>>(state: &S) -> Last<'_> {
+    let nest = &state.base().nest;
+    match (nest.tail(), nest.mode()) {
+        (None, Mode::Vertical) => Last::Page,
+        (tail, _) => Last::Node(tail),
+    }
+}
+
+fn lastskip_get<S: TexState<S>>(state: &S, _: usize) -> Glue {
+    match last(state) {
+        Last::Node(Some(Node::Glue(g))) => g.glue,
+        Last::Node(_) => Glue::default(),
+        Last::Page => state.base().page.last_glue.unwrap_or_default(),
+    }
+}
+
+fn lastpenalty_get<S: TexState<S>>(state: &S, _: usize) -> i32 {
+    match last(state) {
+        Last::Node(Some(Node::Penalty(penalty))) => *penalty,
+        Last::Node(_) => 0,
+        Last::Page => state.base().page.last_penalty,
+    }
+}
+
+fn lastkern_get<S: TexState<S>>(state: &S, _: usize) -> Dimen {
+    match last(state) {
+        Last::Node(Some(Node::Kern(k))) => k.width,
+        Last::Node(_) => Dimen(0),
+        Last::Page => state.base().page.last_kern,
+    }
+}
+
+pub fn get_penalty<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("penalty", penalty_fn, PENALTY_DOC)
+}
+
+pub fn get_kern<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("kern", kern_fn, KERN_DOC)
+}
+
+pub fn get_hskip<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("hskip", hskip_fn, HSKIP_DOC)
+}
+
+pub fn get_vskip<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("vskip", vskip_fn, VSKIP_DOC)
+}
+
+pub fn get_hfil<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("hfil", hfil_fn, HFIL_DOC)
+}
+
+pub fn get_hfill<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("hfill", hfill_fn, HFILL_DOC)
+}
+
+pub fn get_hss<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("hss", hss_fn, HSS_DOC)
+}
+
+pub fn get_hfilneg<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("hfilneg", hfilneg_fn, HFILNEG_DOC)
+}
+
+pub fn get_vfil<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("vfil", vfil_fn, VFIL_DOC)
+}
+
+pub fn get_vfill<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("vfill", vfill_fn, VFILL_DOC)
+}
+
+pub fn get_vss<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("vss", vss_fn, VSS_DOC)
+}
+
+pub fn get_vfilneg<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("vfilneg", vfilneg_fn, VFILNEG_DOC)
+}
+
+pub fn get_unpenalty<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("unpenalty", unpenalty_fn, UNPENALTY_DOC)
+}
+
+pub fn get_unkern<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("unkern", unkern_fn, UNKERN_DOC)
+}
+
+pub fn get_unskip<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("unskip", unskip_fn, UNSKIP_DOC)
+}
+
+pub fn get_lastskip<S: TexState<S>>() -> primitive::TypedVariable<S, Glue> {
+    primitive::TypedVariable {
+        name: "lastskip",
+        index: primitive::Index::Fixed(0),
+        get_fn: lastskip_get,
+        set_fn: None,
+        docs: LASTSKIP_DOC,
+    }
+}
+
+pub fn get_lastpenalty<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "lastpenalty",
+        index: primitive::Index::Fixed(0),
+        get_fn: lastpenalty_get,
+        set_fn: None,
+        docs: LASTPENALTY_DOC,
+    }
+}
+
+pub fn get_lastkern<S: TexState<S>>() -> primitive::TypedVariable<S, Dimen> {
+    primitive::TypedVariable {
+        name: "lastkern",
+        index: primitive::Index::Fixed(0),
+        get_fn: lastkern_get,
+        set_fn: None,
+        docs: LASTKERN_DOC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tex::dimen::{Dimen, Glue, GlueOrder};
+    use crate::tex::node::{Char, Kern, Node};
+    use crate::tex::state::TexState;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> TestState {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        testutil::run(s, input).unwrap()
+    }
+
+    /// Runs the input after appending the nodes to the outer vertical list, and returns what
+    /// was shown on the terminal.
+    fn show_after(nodes: Vec<Node>, input: &str) -> String {
+        let mut s = testutil::new_state();
+        for node in nodes {
+            s.base_mut().nest.append(node);
+        }
+        testutil::terminal(&testutil::run(s, input).unwrap())
+    }
+
+    /// Returns the list of box register 1 after running the input.
+    fn box_list(input: &str) -> Vec<Node> {
+        let s = run(input);
+        s.base()
+            .registers
+            .box_register(1)
+            .unwrap()
+            .as_box()
+            .unwrap()
+            .list
+            .clone()
+    }
+
+    fn error(input: &str) -> String {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        match testutil::run(s, input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn kern(pt: i32) -> Node {
+        Node::Kern(Kern::new(Dimen::from_pt(pt)))
+    }
+
+    fn fil(stretch: i32, stretch_order: GlueOrder, shrink: i32) -> Node {
+        Node::new_glue(Glue {
+            stretch: Dimen(stretch * 65536),
+            stretch_order,
+            shrink: Dimen(shrink * 65536),
+            shrink_order: match shrink {
+                0 => GlueOrder::Normal,
+                _ => GlueOrder::Fil,
+            },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn penalty_and_kern() {
+        assert_eq!(
+            box_list(r"\setbox1\hbox{\penalty-7 \kern 3pt}"),
+            vec![Node::Penalty(-7), kern(3)]
+        );
+        assert_eq!(
+            box_list(r"\setbox1\vbox{\penalty7 \kern-1pt}"),
+            vec![Node::Penalty(7), kern(-1)]
+        );
+    }
+
+    #[test]
+    fn penalty_in_vertical_mode_builds_the_page() {
+        let s = run(r"\hbox{}\penalty7 \kern 3pt \showthe\lastkern");
+        assert_eq!(s.base().page.last_penalty, 7);
+        assert_eq!(s.base().nest.levels()[0].list, vec![kern(3)]);
+        assert_eq!(testutil::terminal(&s), "> 3.0pt.");
+    }
+
+    #[test]
+    fn lastpenalty_on_the_current_page() {
+        let s = run(r"\hbox{}\penalty7 \showthe\lastpenalty");
+        assert_eq!(testutil::terminal(&s), "> 7.");
+    }
+
+    #[test]
+    fn horizontal_glue() {
+        assert_eq!(
+            box_list(r"\setbox1\hbox{\hskip 1pt plus 2fil minus 3pt\hfil\hfill\hss\hfilneg}"),
+            vec![
+                Node::new_glue(Glue {
+                    width: Dimen::from_pt(1),
+                    stretch: Dimen::from_pt(2),
+                    stretch_order: GlueOrder::Fil,
+                    shrink: Dimen::from_pt(3),
+                    shrink_order: GlueOrder::Normal,
+                }),
+                fil(1, GlueOrder::Fil, 0),
+                fil(1, GlueOrder::Fill, 0),
+                fil(1, GlueOrder::Fil, 1),
+                fil(-1, GlueOrder::Fil, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn vertical_glue() {
+        assert_eq!(
+            box_list(r"\setbox1\vbox{\vskip 1pt\vfil\vfill\vss\vfilneg}"),
+            vec![
+                Node::new_glue(Glue {
+                    width: Dimen::from_pt(1),
+                    ..Default::default()
+                }),
+                fil(1, GlueOrder::Fil, 0),
+                fil(1, GlueOrder::Fill, 0),
+                fil(1, GlueOrder::Fil, 1),
+                fil(-1, GlueOrder::Fil, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn horizontal_glue_begins_a_paragraph() {
+        let s = run(r"\setbox1\vbox{\hfil\showthe\lastskip}");
+        assert_eq!(testutil::terminal(&s), "> 0.0pt plus 1.0fil.");
+        let list = s
+            .base()
+            .registers
+            .box_register(1)
+            .unwrap()
+            .as_box()
+            .unwrap();
+        assert!(matches!(list.list.first(), Some(Node::HList(_))));
+    }
+
+    #[test]
+    fn vertical_glue_ends_a_paragraph() {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        testutil::set_single_line_paragraphs(&mut s);
+        let s = testutil::run(s, r"\setbox1\vbox{a\vskip 2pt}").unwrap();
+        let list = &s
+            .base()
+            .registers
+            .box_register(1)
+            .unwrap()
+            .as_box()
+            .unwrap()
+            .list;
+        assert_eq!(list.len(), 2);
+        assert!(matches!(list[0], Node::HList(_)));
+        assert_eq!(
+            list[1],
+            Node::new_glue(Glue {
+                width: Dimen::from_pt(2),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn glue_in_the_wrong_mode() {
+        assert!(error(r"\hbox{\vskip 1pt}")
+            .contains("You can't use `\\vskip' in restricted horizontal mode"));
+        assert!(error(r"\hbox{\vfilneg}")
+            .contains("You can't use `\\vfilneg' in restricted horizontal mode"));
+        assert!(error(r"\hbox{$\vfil$}").contains("Missing $ inserted"));
+    }
+
+    #[test]
+    fn remove_last_nodes() {
+        let a = Node::Char(Char {
+            font: testutil::TEST_FONT,
+            c: 'a',
+        });
+        assert_eq!(
+            box_list(
+                r"\setbox1\hbox{a\hskip1pt\unskip\kern2pt\unkern\penalty3\unpenalty\kern1pt\unskip\unpenalty}"
+            ),
+            vec![a, kern(1)]
+        );
+        assert_eq!(
+            box_list(r"\setbox1\vbox{\vskip1pt\penalty2\unskip\unpenalty\unskip}"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn nodes_cannot_be_removed_from_the_current_page() {
+        // Without glue on the current page, \unskip does nothing.
+        let s = run(r"\unskip\hbox{}\par\unskip");
+        assert!(matches!(
+            testutil::outer_list(&s).last(),
+            Some(Node::HList(_))
+        ));
+        assert!(error(r"\hbox{}\vskip1pt\par\unskip")
+            .contains("You can't use `\\unskip' in vertical mode"));
+        assert!(error(r"\unkern").contains("You can't use `\\unkern' in vertical mode"));
+        assert!(error(r"\unpenalty").contains("You can't use `\\unpenalty' in vertical mode"));
+    }
+
+    #[test]
+    fn initial_values() {
+        let s = run(r"\showthe\lastskip\showthe\lastpenalty\showthe\lastkern");
+        assert_eq!(testutil::terminal(&s), "> 0.0pt.\n> 0.\n> 0.0pt.");
+    }
+
+    #[test]
+    fn lastskip_in_horizontal_mode() {
+        let s = run(r"\spaceskip=1pt plus 2fil \hbox{a \showthe\lastskip a\showthe\lastskip}");
+        assert_eq!(testutil::terminal(&s), "> 1.0pt plus 2.0fil.\n> 0.0pt.");
+    }
+
+    #[test]
+    fn lastskip_with_the() {
+        let s = run(
+            r"\spaceskip=1pt plus 2pt \setbox1=\hbox{a \global\skip1=\lastskip \global\dimen1=\lastskip}",
+        );
+        let r = &s.base().registers;
+        assert_eq!(
+            r.skip(1),
+            Glue {
+                width: Dimen::from_pt(1),
+                stretch: Dimen::from_pt(2),
+                stretch_order: GlueOrder::Normal,
+                ..Default::default()
+            }
+        );
+        assert_eq!(r.dimen(1), Dimen::from_pt(1));
+    }
+
+    #[test]
+    fn lastpenalty_and_lastkern() {
+        assert_eq!(
+            show_after(
+                vec![Node::Penalty(7)],
+                r"\showthe\lastpenalty\showthe\lastkern"
+            ),
+            "> 7.\n> 0.0pt."
+        );
+        assert_eq!(
+            show_after(
+                vec![Node::Penalty(7), Node::Kern(Kern::new(Dimen::from_pt(3)))],
+                r"\showthe\lastpenalty\showthe\lastkern"
+            ),
+            "> 0.\n> 3.0pt."
+        );
+    }
+
+    #[test]
+    fn last_node_of_inner_list() {
+        assert_eq!(
+            show_after(vec![Node::Penalty(7)], r"\hbox{\showthe\lastpenalty}"),
+            "> 0."
+        );
+    }
+
+    #[test]
+    fn last_quantities_are_read_only() {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        assert!(testutil::run(s, r"\lastpenalty=1 ").is_err());
+    }
+}
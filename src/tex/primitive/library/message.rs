@@ -0,0 +1,171 @@
+//! Primitives that print messages: `\message`, `\errmessage` and `\wlog`.
+//!
+//! Each primitive reads a balanced text and expands it, as `\edef` does, before printing it:
+//! ```tex
+//! \message{Chapter \the\count1}  % prints "Chapter 3" to the terminal and log file
+//! \wlog{Some detail}             % prints "Some detail" to the log file only
+//! \errmessage{Bad input}         % stops with the error "Bad input"
+//! ```
+//!
+//! The help message shown for `\errmessage` is the contents of `\errhelp`, if it is not empty.
+
+use crate::tex::error;
+use crate::tex::parameter::TokenListParam;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::print::{Selector, MAX_PRINT_LINE};
+use crate::tex::state::TexState;
+use crate::tex::token::token::Token;
+
+static MESSAGE_DOC: &str = "Print a message to the terminal and log file";
+static ERRMESSAGE_DOC: &str = "Stop with an error message";
+static WLOG_DOC: &str = "Print a line to the log file";
+
+const ERRMESSAGE_HELP: &str = "This error message was generated by an \\errmessage command, so \
+    I can't give any explicit help. Pretend that you're Hercule Poirot: Examine all clues, and \
+    deduce the truth by order and method.";
+
+fn message_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let text = read_text(input)?;
+    let base = input.state_mut().base_mut();
+    // As in TeX, the message starts on a new line if it doesn't fit on the current one, and
+    // is otherwise separated from preceding text by a space.
+    let printer = &mut base.printer;
+    if printer.terminal_offset() + text.chars().count() > MAX_PRINT_LINE - 2 {
+        printer.print_ln(Selector::TerminalAndLog);
+    } else if printer.terminal_offset() > 0 || printer.log_offset() > 0 {
+        base.print(Selector::TerminalAndLog, " ");
+    }
+    base.print(Selector::TerminalAndLog, &text);
+    base.printer.flush();
+    Ok(())
+}
+
+fn errmessage_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let text = read_text(input)?;
+    // As in TeX, the help message is the unexpanded contents of \errhelp, if it is not empty.
+    let base = input.state().base();
+    let err_help = base.parameters.token_list(TokenListParam::ErrHelp);
+    let help = match err_help.is_empty() {
+        true => ERRMESSAGE_HELP.to_string(),
+        false => {
+            let mut help = String::new();
+            base.write_style().write_tokens(&mut help, &err_help);
+            help
+        }
+    };
+    Err(error::new_token_error(token, text, vec![help]))
+}
+
+fn wlog_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let text = read_text(input)?;
+    let base = input.state_mut().base_mut();
+    base.print_nl(Selector::Log, &text);
+    base.printer.print_ln(Selector::Log);
+    Ok(())
+}
+
+/// Reads and expands a balanced text, and returns it as a string.
+fn read_text<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<String> {
+    let tokens = parse::parse_expanded_balanced_text(input)?;
+    let mut text = String::new();
+    input
+        .state()
+        .base()
+        .write_style()
+        .write_tokens(&mut text, &tokens);
+    Ok(text)
+}
+
+pub fn get_message<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("message", message_fn, MESSAGE_DOC)
+}
+
+pub fn get_errmessage<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("errmessage", errmessage_fn, ERRMESSAGE_DOC)
+}
+
+pub fn get_wlog<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("wlog", wlog_fn, WLOG_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::print::Buffer;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn new_state() -> (TestState, Buffer) {
+        let mut s = testutil::new_state();
+        let log = Buffer::new();
+        s.base_mut().printer.open_log(Box::new(log.clone()));
+        (s, log)
+    }
+
+    #[test]
+    fn message() {
+        let (s, log) = new_state();
+        let s = testutil::run(
+            s,
+            r"\def\a{world}\count1=3 \message{Hello \a\the\count1}\message{again}",
+        )
+        .unwrap();
+        assert_eq!(testutil::terminal(&s), "Hello world3 again");
+        assert_eq!(log.contents(), "Hello world3 again");
+    }
+
+    #[test]
+    fn long_message_starts_a_new_line() {
+        let (s, _) = new_state();
+        let long = "x".repeat(MAX_PRINT_LINE - 2);
+        let s = testutil::run(s, &format!(r"\message{{a}}\message{{{}}}", long)).unwrap();
+        assert_eq!(testutil::terminal(&s), format!("a\n{}", long));
+    }
+
+    #[test]
+    fn message_control_sequences() {
+        let (s, _) = new_state();
+        let s = testutil::run(s, r"\message{\count\%#}").unwrap();
+        assert_eq!(testutil::terminal(&s), r"\count \%##");
+    }
+
+    #[test]
+    fn message_does_not_expand_the_output_of_the() {
+        let (s, _) = new_state();
+        let s = testutil::run(s, r"\def\a{x}\toks0={\a}\message{\the\toks0}").unwrap();
+        assert_eq!(testutil::terminal(&s), r"\a ");
+    }
+
+    #[test]
+    fn wlog() {
+        let (s, log) = new_state();
+        let s = testutil::run(s, r"\message{a}\wlog{b}\message{c}").unwrap();
+        assert_eq!(testutil::terminal(&s), "a c");
+        // As in TeX, the space separating the messages on the terminal also appears in the log.
+        assert_eq!(log.contents(), "a\nb\n c");
+    }
+
+    #[test]
+    fn errmessage() {
+        let (s, _) = new_state();
+        let err = testutil::run(s, r"\def\a{bad}\errmessage{This is \a}")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("This is bad"), "{}", err);
+        assert!(err.to_string().contains("Hercule Poirot"), "{}", err);
+    }
+
+    #[test]
+    fn errmessage_with_errhelp() {
+        let (s, _) = new_state();
+        let err = testutil::run(s, r"\def\a{bad}\errhelp{Try \a}\errmessage{This is \a}")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Try \\a"), "{}", err);
+        assert!(!err.to_string().contains("Hercule Poirot"), "{}", err);
+    }
+}
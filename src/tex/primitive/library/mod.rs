@@ -1,6 +1,330 @@
 //! Texide primitive library.
 //!
-//! This module contains implementations of TeX primtives for Texide.
+//! This module contains implementations of TeX primtives for Texide. The function `all`
+//! returns every primitive with its TeX name, ready to be defined in a new state.
 
+pub mod arithmetic;
+pub mod boxes;
+pub mod case;
+pub mod catcode;
+pub mod charcode;
+pub mod chardef;
 pub mod conditional;
+pub mod convert;
+pub mod def;
+pub mod discretionary;
+pub mod expansion;
+pub mod font;
+pub mod group;
+pub mod hyphenation;
+pub mod input;
+pub mod list;
+pub mod message;
+pub mod page;
+pub mod paragraph;
+pub mod parameter;
+pub mod prefix;
+pub mod read;
+pub mod register;
+pub mod rule;
+pub mod show;
 pub mod texide;
+pub mod the;
+pub mod write;
+
+use crate::tex::primitive::{Execution, Expansion, Primitive, Variable};
+use crate::tex::state::TexState;
+
+/// Returns the name and meaning of every primitive in the library.
+pub fn all<S: TexState<S>>() -> Vec<(&'static str, Primitive<S>)> {
+    let mut result = Vec::new();
+    for (name, p) in [
+        ("if", conditional::get_if()),
+        ("ifcat", conditional::get_ifcat()),
+        ("ifx", conditional::get_ifx()),
+        ("ifnum", conditional::get_ifnum()),
+        ("ifdim", conditional::get_ifdim()),
+        ("ifodd", conditional::get_ifodd()),
+        ("iftrue", conditional::get_iftrue()),
+        ("iffalse", conditional::get_iffalse()),
+        ("ifcase", conditional::get_ifcase()),
+        ("or", conditional::get_or()),
+        ("else", conditional::get_else()),
+        ("ifeof", conditional::get_ifeof()),
+        ("fi", conditional::get_fi()),
+        ("expandafter", expansion::get_expandafter()),
+        ("noexpand", expansion::get_noexpand()),
+        ("csname", expansion::get_csname()),
+        ("number", convert::get_number()),
+        ("romannumeral", convert::get_romannumeral()),
+        ("string", convert::get_string()),
+        ("the", the::get_the()),
+        ("meaning", show::get_meaning()),
+        ("fontname", font::get_fontname()),
+        ("input", input::get_input()),
+        ("endinput", input::get_endinput()),
+        ("topmark", page::get_topmark()),
+        ("firstmark", page::get_firstmark()),
+        ("botmark", page::get_botmark()),
+        ("topmarks", page::get_topmarks()),
+        ("firstmarks", page::get_firstmarks()),
+        ("botmarks", page::get_botmarks()),
+    ] {
+        result.push((name, Primitive::Expansion(Expansion::Static(p))));
+    }
+    for (name, p) in [
+        ("def", def::get_def()),
+        ("gdef", def::get_gdef()),
+        ("edef", def::get_edef()),
+        ("xdef", def::get_xdef()),
+        ("let", def::get_let()),
+        ("futurelet", def::get_futurelet()),
+        ("relax", expansion::get_relax()),
+        ("endcsname", expansion::get_endcsname()),
+        ("showthe", the::get_showthe()),
+        ("show", show::get_show()),
+        ("showtokens", show::get_showtokens()),
+        ("message", message::get_message()),
+        ("errmessage", message::get_errmessage()),
+        ("wlog", message::get_wlog()),
+        ("openout", write::get_openout()),
+        ("write", write::get_write()),
+        ("closeout", write::get_closeout()),
+        ("immediate", write::get_immediate()),
+        ("openin", read::get_openin()),
+        ("read", read::get_read()),
+        ("readline", read::get_readline()),
+        ("closein", read::get_closein()),
+        ("hbox", boxes::get_hbox()),
+        ("vbox", boxes::get_vbox()),
+        ("vtop", boxes::get_vtop()),
+        ("box", boxes::get_box()),
+        ("copy", boxes::get_copy()),
+        ("lastbox", boxes::get_lastbox()),
+        ("setbox", boxes::get_setbox()),
+        ("unhbox", boxes::get_unhbox()),
+        ("unvbox", boxes::get_unvbox()),
+        ("unhcopy", boxes::get_unhcopy()),
+        ("unvcopy", boxes::get_unvcopy()),
+        ("shipout", boxes::get_shipout()),
+        ("font", font::get_font()),
+        ("noboundary", font::get_noboundary()),
+        ("textfont", font::get_textfont()),
+        ("scriptfont", font::get_scriptfont()),
+        ("scriptscriptfont", font::get_scriptscriptfont()),
+        ("patterns", hyphenation::get_patterns()),
+        ("hyphenation", hyphenation::get_hyphenation()),
+        ("setlanguage", hyphenation::get_setlanguage()),
+        ("showhyphens", hyphenation::get_showhyphens()),
+        ("global", prefix::get_global()),
+        ("long", prefix::get_long()),
+        ("outer", prefix::get_outer()),
+        ("initcatcodetable", catcode::get_initcatcodetable()),
+        ("savecatcodetable", catcode::get_savecatcodetable()),
+        ("chardef", chardef::get_chardef()),
+        ("mathchardef", chardef::get_mathchardef()),
+        ("char", chardef::get_char()),
+        ("uppercase", case::get_uppercase()),
+        ("lowercase", case::get_lowercase()),
+        ("begingroup", group::get_begingroup()),
+        ("endgroup", group::get_endgroup()),
+        ("aftergroup", group::get_aftergroup()),
+        ("countdef", register::get_countdef()),
+        ("dimendef", register::get_dimendef()),
+        ("skipdef", register::get_skipdef()),
+        ("muskipdef", register::get_muskipdef()),
+        ("toksdef", register::get_toksdef()),
+        ("advance", arithmetic::get_advance()),
+        ("multiply", arithmetic::get_multiply()),
+        ("divide", arithmetic::get_divide()),
+        ("par", paragraph::get_par()),
+        ("indent", paragraph::get_indent()),
+        ("noindent", paragraph::get_noindent()),
+        ("leavevmode", paragraph::get_leavevmode()),
+        ("parshape", paragraph::get_parshape()),
+        ("insert", page::get_insert()),
+        ("mark", page::get_mark()),
+        ("marks", page::get_marks()),
+        ("end", page::get_end()),
+        ("penalty", list::get_penalty()),
+        ("kern", list::get_kern()),
+        ("hskip", list::get_hskip()),
+        ("vskip", list::get_vskip()),
+        ("hfil", list::get_hfil()),
+        ("hfill", list::get_hfill()),
+        ("hss", list::get_hss()),
+        ("hfilneg", list::get_hfilneg()),
+        ("vfil", list::get_vfil()),
+        ("vfill", list::get_vfill()),
+        ("vss", list::get_vss()),
+        ("vfilneg", list::get_vfilneg()),
+        ("unpenalty", list::get_unpenalty()),
+        ("unkern", list::get_unkern()),
+        ("unskip", list::get_unskip()),
+        ("hrule", rule::get_hrule()),
+        ("vrule", rule::get_vrule()),
+        ("discretionary", discretionary::get_discretionary()),
+        ("-", discretionary::get_hyphen()),
+    ] {
+        result.push((name, Primitive::Execution(Execution::Static(p))));
+    }
+    for (name, v) in [
+        ("hyphenchar", font::get_hyphenchar()),
+        ("skewchar", font::get_skewchar()),
+        ("catcode", catcode::get_catcode()),
+        ("catcodetable", catcode::get_catcodetable()),
+        ("lccode", charcode::get_lccode()),
+        ("uccode", charcode::get_uccode()),
+        ("sfcode", charcode::get_sfcode()),
+        ("mathcode", charcode::get_mathcode()),
+        ("delcode", charcode::get_delcode()),
+        ("currentgrouplevel", group::get_currentgrouplevel()),
+        ("currentgrouptype", group::get_currentgrouptype()),
+        ("count", register::get_count()),
+        ("insertpenalties", page::get_insertpenalties()),
+        ("deadcycles", page::get_deadcycles()),
+        ("lastpenalty", list::get_lastpenalty()),
+        ("badness", boxes::get_badness()),
+    ] {
+        result.push((name, Primitive::Variable(Variable::Int(v))));
+    }
+    for (name, v) in [
+        ("wd", boxes::get_wd()),
+        ("ht", boxes::get_ht()),
+        ("dp", boxes::get_dp()),
+        ("fontdimen", font::get_fontdimen()),
+        ("dimen", register::get_dimen()),
+        ("lastkern", list::get_lastkern()),
+    ] {
+        result.push((name, Primitive::Variable(Variable::Dimen(v))));
+    }
+    for (name, v) in [
+        ("skip", register::get_skip()),
+        ("lastskip", list::get_lastskip()),
+    ] {
+        result.push((name, Primitive::Variable(Variable::Glue(v))));
+    }
+    for (name, v) in [("muskip", register::get_muskip())] {
+        result.push((name, Primitive::Variable(Variable::MuGlue(v))));
+    }
+    for (name, v) in [("toks", register::get_toks())] {
+        result.push((name, Primitive::Variable(Variable::TokenList(v))));
+    }
+    result.push(("nullfont", font::get_nullfont()));
+    for (name, v) in page::page_dimens().into_iter().chain(parameter::all()) {
+        result.push((name, Primitive::Variable(v)));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tex::filesystem::InMemoryFileSystem;
+    use crate::tex::state::TexState;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+    use crate::tex::tfm::{CharInfo, Tfm};
+
+    /// The fonts loaded by plain.tex.
+    const FONTS: &[&str] = &[
+        "cmr10", "cmr9", "cmr8", "cmr7", "cmr6", "cmr5", "cmmi10", "cmmi9", "cmmi8", "cmmi7",
+        "cmmi6", "cmmi5", "cmsy10", "cmsy9", "cmsy8", "cmsy7", "cmsy6", "cmsy5", "cmex10",
+        "cmss10", "cmssq8", "cmssi10", "cmssqi8", "cmbx10", "cmbx9", "cmbx8", "cmbx7", "cmbx6",
+        "cmbx5", "cmtt10", "cmtt9", "cmtt8", "cmsltt10", "cmsl10", "cmsl9", "cmsl8", "cmti10",
+        "cmti9", "cmti8", "cmti7", "cmu10", "cmmib10", "cmbsy10", "cmcsc10", "cmssbx10",
+        "cmdunh10",
+    ];
+
+    /// Returns a font with 128 characters of width 0.5pt, which stands in for every font that
+    /// plain.tex loads. Plain TeX only reads the parameters of its math fonts when typesetting.
+    fn font() -> Tfm {
+        Tfm {
+            design_size: 10 << 20,
+            char_infos: vec![
+                CharInfo {
+                    width_index: 1,
+                    ..Default::default()
+                };
+                128
+            ],
+            widths: vec![0, 1 << 19],
+            heights: vec![0],
+            depths: vec![0],
+            italics: vec![0],
+            params: vec![0; 7],
+            ..Default::default()
+        }
+    }
+
+    /// Loads plain.tex and then runs the input.
+    ///
+    /// The hyphenation patterns that plain.tex inputs as hyphen.tex are served from
+    /// hyph-en-us.tex, whose exceptions are those of hyphen.tex.
+    fn run_plain(input: &str) -> TestState {
+        let mut s = testutil::new_state();
+        s.base_mut().char_codes.set_initex_defaults();
+        let mut file_system = InMemoryFileSystem::new();
+        file_system.insert("plain.tex", include_str!("../../testdata/plain.tex"));
+        file_system.insert("hyphen.tex", include_str!("../../testdata/hyph-en-us.tex"));
+        for name in FONTS {
+            file_system.insert_bytes(&format!("{}.tfm", name), &font().to_bytes());
+        }
+        s.base_mut().file_system = Box::new(file_system);
+        testutil::run(s, &format!("\\input plain {}", input)).unwrap()
+    }
+
+    #[test]
+    fn plain_loads() {
+        let s = run_plain("");
+        assert_eq!(
+            testutil::terminal(&s),
+            "(plain.tex Preloading the plain format: codes, registers, parameters, fonts,\n\
+             more fonts, macros, math definitions, output routines, hyphenation (hyphen.tex)\n)"
+        );
+    }
+
+    #[test]
+    fn plain_register_allocations() {
+        let s = run_plain(
+            r"\record\count10\record\count11\record\count12\record\count13\record\count14
+              \record\count15\record\count16\record\count17\record\count18\record\count19
+              \record\count20\record\allocationnumber\record\footins\record\topins",
+        );
+        assert_eq!(
+            s.recorded,
+            vec![25, 15, 17, 9, 15, 11, -1, -1, 7, 0, 253, 253, 254, 253]
+        );
+    }
+
+    #[test]
+    fn plain_codes_and_parameters() {
+        let s = run_plain(
+            r#"\record\catcode`\@\record\catcode`\~\record\catcode`\^^L\record\sfcode`\.
+              \record\mathcode`\+\record\delcode`\(\record\tolerance
+              \record\interdisplaylinepenalty\record\mscount"#,
+        );
+        assert_eq!(
+            s.recorded,
+            vec![12, 13, 13, 3000, 0x202B, 0x028300, 200, 100, 0]
+        );
+    }
+
+    #[test]
+    fn plain_macros_and_fonts() {
+        let s = run_plain(
+            r"\showthe\thinmuskip\showthe\baselineskip\showthe\hsize\showthe\textfont1
+              \show\fmtname\show\newcount",
+        );
+        let terminal = testutil::terminal(&s);
+        let shown = terminal.split_once("\n)\n").unwrap().1;
+        assert_eq!(
+            shown,
+            "> 3.0mu.\n\
+             > 12.0pt.\n\
+             > 469.75499pt.\n\
+             > \\teni .\n\
+             > \\fmtname=macro:->plain.\n\
+             > \\newcount=\\outer macro:->\\alloc@ 0\\count \\countdef \\insc@unt ."
+        );
+    }
+}
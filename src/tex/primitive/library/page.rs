@@ -0,0 +1,630 @@
+//! Primitives for the page builder: insertions with `\insert`, marks with `\mark` and e-TeX's
+//! `\marks`, the marks of the current page `\topmark`, `\firstmark` and `\botmark`, and the
+//! dimensions of the current page like `\pagegoal` and `\pagetotal`.
+//!
+//! Insertions of class n go to `\box n` when the page is output. The insertion is placed where
+//! it is made on the page, but the goal height of the page is reduced to make room for it:
+//! ```tex
+//! \count100=1000 \dimen100=2in \skip100=12pt plus 4pt
+//! \insert100{\hbox{A footnote}}
+//! ```
+//! Marks are read by the output routine, for example to print running heads:
+//! ```tex
+//! \mark{Chapter 1}
+//! \output={\shipout\vbox{\hbox{\topmark}\box255}}
+//! ```
+//! The page dimensions, and `\insertpenalties` and `\deadcycles`, can be read and assigned, and
+//! assignments to them are always global.
+//!
+//! `\end` ends the job. Before it does, the material left on the page and in the contributions
+//! is output, firing the output routine until it has shipped out everything.
+
+use crate::tex::dimen::{Dimen, MAX_DIMEN};
+use crate::tex::driver;
+use crate::tex::group::GroupType;
+use crate::tex::nest::Mode;
+use crate::tex::node::{Insert, Mark, Node};
+use crate::tex::pack;
+use crate::tex::page::{self, Contents, MarkKind};
+use crate::tex::parameter::{DimenParam, GlueParam, IntParam};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::register::parse_register;
+use crate::tex::state::TexState;
+use crate::tex::token::stream;
+use crate::tex::token::token::Token;
+use std::rc::Rc;
+
+static INSERT_DOC: &str = "Insert vertical material into a box when the page is output";
+static MARK_DOC: &str = "Append a mark, which the output routine can read";
+static MARKS_DOC: &str = "Append a mark of a class, which the output routine can read";
+static TOPMARK_DOC: &str = "Insert the last mark of the previous page";
+static FIRSTMARK_DOC: &str = "Insert the first mark of the page being output";
+static BOTMARK_DOC: &str = "Insert the last mark of the page being output";
+static TOPMARKS_DOC: &str = "Insert the last mark of a class on the previous page";
+static FIRSTMARKS_DOC: &str = "Insert the first mark of a class on the page being output";
+static BOTMARKS_DOC: &str = "Insert the last mark of a class on the page being output";
+static PAGE_DIMEN_DOC: &str = "Get or set a dimension of the current page";
+static INSERTPENALTIES_DOC: &str = "Get or set the sum of the penalties of split insertions";
+static END_DOC: &str = "Output the remaining pages and end the job";
+static DEADCYCLES_DOC: &str = "Get or set the number of outputs since the last \\shipout";
+
+fn insert_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let n = parse::parse_number(input)?;
+    let number = match n {
+        0..=254 => n as u8,
+        255 => return Err(anyhow::anyhow!("You can't \\insert255: box 255 is special")),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Bad register code ({}); an insertion number must be between 0 and 254",
+                n
+            ))
+        }
+    };
+    parse::parse_left_brace(input)?;
+    let base = input.state_mut().base_mut();
+    base.begin_insert_group(number);
+    driver::normal_paragraph(base);
+    base.nest.push(Mode::InternalVertical);
+    Ok(())
+}
+
+/// Finishes an insertion when the group of `\insert n{...}` ends, and appends it to the current
+/// list.
+pub fn end_insert<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    let base = input.state_mut().base_mut();
+    driver::end_paragraph(base)?;
+    // The parameters are those in force inside the insertion.
+    let split_top_skip = base.parameters.glue(GlueParam::SplitTopSkip);
+    let split_max_depth = base.parameters.dimen(DimenParam::SplitMaxDepth);
+    let float_cost = base.parameters.int(IntParam::FloatingPenalty);
+    let group = driver::end_group(token, input, GroupType::Insert)?;
+    // Insert groups are begun with an insertion number.
+    let number = group.insert_number.unwrap();
+    let base = input.state_mut().base_mut();
+    // The insertion's internal vertical mode is not the outer mode.
+    let level = base.nest.pop().unwrap();
+    let (b, badness, _) = pack::vpack(
+        level.list,
+        pack::Spec::NATURAL,
+        Dimen(MAX_DIMEN),
+        &base.parameters,
+    );
+    base.last_badness = badness;
+    base.nest.append(Node::Insert(Insert {
+        number,
+        height: Dimen(b.height.0 + b.depth.0),
+        split_max_depth,
+        split_top_skip,
+        float_cost,
+        list: b.list,
+    }));
+    if base.nest.levels().len() == 1 {
+        page::build_page(input)?;
+    }
+    Ok(())
+}
+
+fn mark_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    append_mark(input, 0)
+}
+
+fn marks_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let class = parse_register(input)?;
+    append_mark(input, class as i32)
+}
+
+fn append_mark<S: TexState<S>>(input: &mut primitive::Input<S>, class: i32) -> anyhow::Result<()> {
+    let tokens = parse::parse_expanded_balanced_text(input)?;
+    input.state_mut().base_mut().nest.append(Node::Mark(Mark {
+        class,
+        tokens: Rc::new(tokens),
+    }));
+    Ok(())
+}
+
+/// Returns the tokens of a mark of the current page, which are empty if there is no such mark.
+fn mark_tokens<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    class: i32,
+    kind: MarkKind,
+) -> stream::VecStream {
+    let tokens = match input.state().base().page.mark(class, kind) {
+        None => Vec::new(),
+        Some(tokens) => tokens.to_vec(),
+    };
+    stream::VecStream::new(tokens)
+}
+
+fn topmark_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    Ok(mark_tokens(input, 0, MarkKind::Top))
+}
+
+fn firstmark_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    Ok(mark_tokens(input, 0, MarkKind::First))
+}
+
+fn botmark_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    Ok(mark_tokens(input, 0, MarkKind::Bot))
+}
+
+fn topmarks_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let class = parse_register(input)?;
+    Ok(mark_tokens(input, class as i32, MarkKind::Top))
+}
+
+fn firstmarks_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let class = parse_register(input)?;
+    Ok(mark_tokens(input, class as i32, MarkKind::First))
+}
+
+fn botmarks_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let class = parse_register(input)?;
+    Ok(mark_tokens(input, class as i32, MarkKind::Bot))
+}
+
+/// Ends the job once the remaining material has been output. In a paragraph, `\end` first ends
+/// the paragraph.
+fn end_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let mode = input.state().base().nest.mode();
+    match mode {
+        Mode::Vertical => {}
+        Mode::Horizontal => {
+            input.push(stream::VecStream::new(vec![
+                Token::new_control_sequence("par"),
+                token,
+            ]));
+            return Ok(());
+        }
+        Mode::Math | Mode::DisplayMath => return Err(driver::missing_dollar_error(token)),
+        _ => return Err(driver::illegal_in_mode_error(token, "\\end", mode)),
+    }
+    if page::its_all_over(token, input)? {
+        input.state_mut().base_mut().job_ended = true;
+    }
+    Ok(())
+}
+
+fn page_dimen_get<S: TexState<S>>(state: &S, index: usize) -> Dimen {
+    let page = &state.base().page;
+    // While the page is empty, \pagegoal is the largest dimension and the others are zero.
+    if page.contents == Contents::Empty && !page.output_active {
+        return match index {
+            0 => Dimen(MAX_DIMEN),
+            _ => Dimen(0),
+        };
+    }
+    page.so_far(index)
+}
+
+fn page_dimen_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Dimen,
+    _: bool,
+) -> anyhow::Result<()> {
+    *state.base_mut().page.so_far_mut(index) = value;
+    Ok(())
+}
+
+fn get_page_dimen<S: TexState<S>>(
+    name: &'static str,
+    index: usize,
+) -> primitive::TypedVariable<S, Dimen> {
+    primitive::TypedVariable {
+        name,
+        index: primitive::Index::Fixed(index),
+        get_fn: page_dimen_get,
+        set_fn: Some(page_dimen_set),
+        docs: PAGE_DIMEN_DOC,
+    }
+}
+
+/// Returns the page dimensions `\pagegoal`, `\pagetotal`, `\pagestretch`, `\pagefilstretch`,
+/// `\pagefillstretch`, `\pagefilllstretch`, `\pageshrink` and `\pagedepth`.
+pub fn page_dimens<S: TexState<S>>() -> Vec<(&'static str, primitive::Variable<S>)> {
+    const NAMES: [&str; 8] = [
+        "pagegoal",
+        "pagetotal",
+        "pagestretch",
+        "pagefilstretch",
+        "pagefillstretch",
+        "pagefilllstretch",
+        "pageshrink",
+        "pagedepth",
+    ];
+    let mut result = Vec::new();
+    for (i, name) in NAMES.iter().enumerate() {
+        result.push((*name, primitive::Variable::Dimen(get_page_dimen(name, i))));
+    }
+    result
+}
+
+pub fn get_insert<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("insert", insert_fn, INSERT_DOC)
+}
+
+pub fn get_mark<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("mark", mark_fn, MARK_DOC)
+}
+
+pub fn get_marks<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("marks", marks_fn, MARKS_DOC)
+}
+
+pub fn get_topmark<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("topmark", topmark_fn, TOPMARK_DOC)
+}
+
+pub fn get_firstmark<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("firstmark", firstmark_fn, FIRSTMARK_DOC)
+}
+
+pub fn get_botmark<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("botmark", botmark_fn, BOTMARK_DOC)
+}
+
+pub fn get_topmarks<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("topmarks", topmarks_fn, TOPMARKS_DOC)
+}
+
+pub fn get_firstmarks<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("firstmarks", firstmarks_fn, FIRSTMARKS_DOC)
+}
+
+pub fn get_botmarks<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("botmarks", botmarks_fn, BOTMARKS_DOC)
+}
+
+pub fn get_end<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("end", end_fn, END_DOC)
+}
+
+pub fn get_insertpenalties<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "insertpenalties",
+        index: primitive::Index::Fixed(0),
+        get_fn: |state: &S, _| state.base().page.insert_penalties,
+        set_fn: Some(|state: &mut S, _, value, _| {
+            state.base_mut().page.insert_penalties = value;
+            Ok(())
+        }),
+        docs: INSERTPENALTIES_DOC,
+    }
+}
+
+pub fn get_deadcycles<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "deadcycles",
+        index: primitive::Index::Fixed(0),
+        get_fn: |state: &S, _| state.base().page.dead_cycles,
+        set_fn: Some(|state: &mut S, _, value, _| {
+            state.base_mut().page.dead_cycles = value;
+            Ok(())
+        }),
+        docs: DEADCYCLES_DOC,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    /// Sets up box 1 as an empty box of height 10pt, and interline glue that does not stretch
+    /// or shrink, so that `\copy1` appends 10pt to the page.
+    const SETUP: &str = r"
+        \baselineskip=0pt \lineskip=0pt \lineskiplimit=0pt \topskip=0pt
+        \setbox1=\hbox{}\ht1=10pt
+    ";
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        let s = testutil::new_state();
+        let mut input = input.to_string();
+        input.insert_str(0, SETUP);
+        testutil::run(s, &input)
+    }
+
+    fn recorded(input: &str) -> Vec<i32> {
+        run(input).unwrap().recorded
+    }
+
+    fn terminal(input: &str) -> String {
+        testutil::terminal(&run(input).unwrap())
+    }
+
+    fn error(input: &str) -> String {
+        match run(input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn pt(n: i32) -> i32 {
+        Dimen::from_pt(n).0
+    }
+
+    #[test]
+    fn page_dimensions() {
+        let input = r"
+            \vsize=100pt \maxdepth=1pt \dp1=2pt
+            \record\pagegoal \record\pagetotal
+            \copy1 \record\pagegoal \record\pagetotal \record\pagedepth
+            \lineskip=0pt plus 3pt minus 2pt \copy1
+            \record\pagetotal \record\pagestretch \record\pageshrink
+            \pagegoal=50pt \record\pagegoal
+        ";
+        assert_eq!(
+            recorded(input),
+            vec![
+                MAX_DIMEN,
+                0,
+                pt(100),
+                // The depth of the page is at most \maxdepth, and the excess is added to the
+                // page total.
+                pt(11),
+                pt(1),
+                pt(23),
+                pt(3),
+                pt(2),
+                pt(50)
+            ]
+        );
+    }
+
+    #[test]
+    fn output_routine_receives_page() {
+        let input = r"
+            \vsize=25pt
+            \output={\record\ht255 \record\outputpenalty \record\deadcycles \setbox0=\box255}
+            \copy1\copy1\copy1\copy1
+            \record\pagetotal
+        ";
+        // The page breaks before the third box, at the last breakpoint that fits. The third
+        // box then begins the next page.
+        assert_eq!(recorded(input), vec![pt(25), 10000, 1, pt(20)]);
+    }
+
+    #[test]
+    fn output_routine_runs_in_group() {
+        let input = r"
+            \vsize=15pt \count3=1
+            \output={\count3=2 \record\currentgrouptype \setbox0=\box255}
+            \copy1\copy1\copy1
+            \record\count3
+        ";
+        assert_eq!(recorded(input), vec![8, 1]);
+    }
+
+    #[test]
+    fn output_routine_material_returns_to_contributions() {
+        let input = r"
+            \vsize=15pt
+            \output={\record1 \global\vsize=100pt \setbox0=\box255 \copy1 \copy1}
+            \copy1\copy1\copy1
+        ";
+        let s = run(input).unwrap();
+        assert_eq!(s.recorded, vec![1]);
+        let list = testutil::outer_list(&s);
+        // The two boxes from the output routine are followed by the second and third boxes.
+        assert_eq!(list.iter().filter(|n| n.as_box().is_some()).count(), 4);
+    }
+
+    #[test]
+    fn output_routine_must_use_box255() {
+        let err = error(r"\vsize=15pt \output={\record1}\copy1\copy1\copy1");
+        assert!(err.contains("Output routine didn't use all of \\box255"));
+    }
+
+    #[test]
+    fn unbalanced_output_routine() {
+        let input = r"
+            \let\egroup=}
+            \vsize=15pt \output={\setbox0=\box255 \egroup\record1}
+            \copy1\copy1\copy1
+        ";
+        assert!(error(input).contains("Unbalanced output routine"));
+    }
+
+    #[test]
+    fn box255_must_be_void() {
+        let err = error(r"\vsize=15pt \setbox255=\hbox{}\copy1\copy1\copy1");
+        assert!(err.contains("\\box255 is not void"));
+    }
+
+    #[test]
+    fn output_loop() {
+        let input = r"
+            \vsize=15pt \maxdeadcycles=1
+            \output={\global\setbox0=\box255}
+            \copy1\copy1\copy1\copy1
+        ";
+        assert!(error(input).contains("Output loop---1 consecutive dead cycles"));
+    }
+
+    #[test]
+    fn default_output_ships_out_page() {
+        let input = r"\vsize=15pt \count0=3 \count2=1 \copy1\copy1\copy1";
+        assert_eq!(terminal(input), "[3.0.1]");
+        let s = run(r"\vsize=15pt \deadcycles=4 \copy1\copy1\copy1 \record\deadcycles").unwrap();
+        assert_eq!(s.recorded, vec![0]);
+    }
+
+    #[test]
+    fn output_routine_ships_out_pages() {
+        let input = r"
+            \vsize=15pt
+            \output={\record\deadcycles \shipout\box255 \record\deadcycles}
+            \copy1\copy1\copy1 \write16{last}\end
+            \record9
+        ";
+        let s = run(input).unwrap();
+        // \end forces out the last page, with the deferred write on it, and nothing after
+        // \end is read.
+        assert_eq!(s.recorded, vec![1, 0, 1, 0, 1, 0]);
+        assert_eq!(testutil::terminal(&s), "[0] [0] [0\nlast\n]");
+        assert!(testutil::outer_list(&s).is_empty());
+    }
+
+    #[test]
+    fn end_without_output_routine() {
+        assert_eq!(terminal(r"\copy1\end"), "[0]");
+        assert_eq!(terminal(r"\end"), "");
+        // \end in a paragraph ends the paragraph first.
+        let s = run(r"\hsize=100pt \setbox0=\hbox{}\unhbox0\end").unwrap();
+        assert!(testutil::outer_list(&s).is_empty());
+    }
+
+    #[test]
+    fn end_waits_for_output_routine_to_ship_out() {
+        let input = r"
+            \output={\record\deadcycles \global\setbox2=\box255}
+            \copy1\end
+        ";
+        // The first output does not ship out the page, so \end forces out another page,
+        // which is empty.
+        assert!(error(input).contains("Output loop---"));
+        let input = r"
+            \output={\record\deadcycles \global\setbox2=\box255 \global\output={\shipout\box255}}
+            \copy1\end
+        ";
+        let s = run(input).unwrap();
+        assert_eq!(s.recorded, vec![1]);
+        assert_eq!(testutil::terminal(&s), "[0]");
+    }
+
+    #[test]
+    fn marks() {
+        let input = r"
+            \vsize=25pt
+            \output={\message{\topmark/\firstmark/\botmark/\firstmarks1}\setbox0=\box255}
+            \mark{a}\copy1\mark{b}\marks1{x}\copy1\mark{c}\copy1
+            \copy1\copy1\copy1
+        ";
+        // The glue after the third mark is the best break, so the mark is on the first page.
+        // The second page has no marks, so its first mark is its top mark.
+        assert_eq!(terminal(input), "/a/c/x c/c/c/x");
+    }
+
+    #[test]
+    fn marks_before_output_are_empty() {
+        let input = r"\mark{a}\message{[\topmark\firstmark\botmark\botmarks0]}";
+        assert_eq!(terminal(input), "[]");
+    }
+
+    #[test]
+    fn insertions_reduce_page_goal() {
+        let input = r"
+            \vsize=100pt \count100=500 \dimen100=100pt \skip100=5pt plus 1pt
+            \insert100{\copy1}\record\pagegoal \record\pagestretch
+            \insert100{\copy1}\record\pagegoal \record\pagestretch
+        ";
+        // As in TeX, the height of an insertion is divided by 1000 before it is multiplied by
+        // \count100.
+        let h = (pt(10) / 1000) * 500;
+        assert_eq!(
+            recorded(input),
+            vec![pt(95) - h, pt(1), pt(95) - 2 * h, pt(1)]
+        );
+    }
+
+    #[test]
+    fn insertions_go_to_their_box() {
+        let input = r"
+            \vsize=40pt \count100=1000 \dimen100=100pt
+            \output={\record\ht100 \record\ht255 \setbox0=\box255 \setbox0=\box100}
+            \copy1\insert100{\copy1}\copy1\insert100{\copy1}\copy1\copy1\copy1
+        ";
+        assert_eq!(recorded(input), vec![pt(20), pt(20)]);
+    }
+
+    #[test]
+    fn holding_inserts() {
+        let input = r"
+            \vsize=20pt \count100=1000 \dimen100=100pt \holdinginserts=1
+            \output={\record\insertpenalties \record\ht100 \global\setbox2=\box255}
+            \insert100{\copy1}\copy1\copy1\copy1
+        ";
+        let s = run(input).unwrap();
+        assert_eq!(s.recorded, vec![0, 0]);
+        // The insertion stays in \box255.
+        let page = s
+            .base()
+            .registers
+            .box_register(2)
+            .unwrap()
+            .as_box()
+            .unwrap();
+        assert!(matches!(page.list[0], Node::Insert(_)));
+    }
+
+    #[test]
+    fn insertion_is_split() {
+        let input = r"
+            \vsize=30pt \count100=1000 \dimen100=15pt \splittopskip=3pt
+            \output={\record\ht100 \record\insertpenalties \setbox0=\box255 \setbox0=\box100}
+            \insert100{\copy1\copy1\copy1}\copy1\copy1\copy1\copy1
+        ";
+        let s = run(input).unwrap();
+        // While the output routine is active, \insertpenalties is the number of insertions held
+        // over.
+        assert_eq!(s.recorded, vec![pt(10), 1]);
+        // The rest of the insertion is held over to the next page, with \splittopskip glue.
+        let remainder = testutil::outer_list(&s)
+            .into_iter()
+            .find_map(|node| match node {
+                Node::Insert(insert) => Some(insert),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(remainder.height, Dimen::from_pt(20));
+        assert_eq!(remainder.split_top_skip.width, Dimen::from_pt(3));
+    }
+
+    #[test]
+    fn tracing_pages() {
+        let input = r"
+            \tracingpages=1 \tracingonline=1 \vsize=25pt \maxdepth=2pt
+            \output={\setbox0=\box255}
+            \copy1\copy1\copy1\copy1
+        ";
+        assert_eq!(
+            terminal(input),
+            "%% goal height=25.0, max depth=2.0\n\
+             % t=10.0 g=25.0 b=10000 p=0 c=100000#\n\
+             % t=20.0 g=25.0 b=10000 p=0 c=100000#\n\
+             % t=30.0 g=25.0 b=* p=0 c=*\n\
+             %% goal height=25.0, max depth=2.0\n\
+             % t=10.0 g=25.0 b=10000 p=0 c=100000#\n"
+        );
+    }
+
+    #[test]
+    fn insert_errors() {
+        assert!(error(r"\insert255{}").contains("You can't \\insert255"));
+        let err = error(r"\setbox100=\hbox{}\insert100{}");
+        assert!(err.contains("Insertions can only be added to a vbox"));
+    }
+}
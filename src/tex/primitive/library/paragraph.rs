@@ -0,0 +1,464 @@
+//! Primitives that begin and end paragraphs, `\par`, `\indent`, `\noindent` and `\leavevmode`,
+//! and `\parshape`, which sets the shape of the next paragraph.
+//!
+//! A character in vertical mode begins an indented paragraph, and `\par`, which the lexer also
+//! produces for a blank line, ends it:
+//! ```tex
+//! This is an indented paragraph.\par
+//! \noindent This paragraph is not indented.
+//!
+//! \leavevmode\write1{x}% the \write is part of the paragraph
+//! ```
+//! In horizontal mode `\indent` appends an empty box of width `\parindent`, and `\noindent` does
+//! nothing.
+//!
+//! `\parshape` is followed by a number of lines n and then n pairs of dimensions, the
+//! indentation and length of each line. Lines after the n-th have the shape of the n-th line:
+//! ```tex
+//! \parshape 3 0pt 100pt 10pt 80pt 20pt 60pt
+//! ```
+//! The shape is reset at the end of every paragraph. Used as an integer, `\parshape` is the
+//! number of lines in the current shape.
+
+use crate::tex::driver;
+use crate::tex::nest::Mode;
+use crate::tex::node::{BoxNode, Node};
+use crate::tex::page;
+use crate::tex::parameter::DimenParam;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::prefix;
+use crate::tex::primitive::{Execution, Primitive};
+use crate::tex::state::TexState;
+use crate::tex::token::token::Token;
+use std::rc::Rc;
+
+static PAR_DOC: &str = "End the current paragraph";
+static INDENT_DOC: &str = "Begin an indented paragraph, or append an indentation box";
+static NOINDENT_DOC: &str = "Begin a paragraph that is not indented";
+static LEAVEVMODE_DOC: &str = "Begin an indented paragraph if in vertical mode";
+static PARSHAPE_DOC: &str = "Set the indentation and length of the lines of the next paragraph";
+
+fn par_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let base = input.state_mut().base_mut();
+    match base.nest.mode() {
+        Mode::Vertical | Mode::InternalVertical => driver::normal_paragraph(base),
+        Mode::Horizontal => driver::end_paragraph(base)?,
+        Mode::RestrictedHorizontal => {}
+        Mode::Math | Mode::DisplayMath => return Err(driver::missing_dollar_error(token)),
+    }
+    if input.state().base().nest.mode() == Mode::Vertical {
+        page::build_page(input)?;
+    }
+    Ok(())
+}
+
+fn indent_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    if input.state().base().nest.mode().is_vertical() {
+        driver::begin_paragraph(input, true);
+        return Ok(());
+    }
+    let base = input.state_mut().base_mut();
+    let width = base.parameters.dimen(DimenParam::ParIndent);
+    base.nest.append(Node::HList(BoxNode {
+        width,
+        ..Default::default()
+    }));
+    base.nest.current_mut().space_factor = 1000;
+    Ok(())
+}
+
+fn noindent_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    if input.state().base().nest.mode().is_vertical() {
+        driver::begin_paragraph(input, false);
+    }
+    Ok(())
+}
+
+fn leavevmode_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    if input.state().base().nest.mode().is_vertical() {
+        driver::begin_paragraph(input, true);
+    }
+    Ok(())
+}
+
+fn parshape_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    parse::parse_optional_equals(input)?;
+    let n = parse::parse_number(input)?;
+    let mut shape = Vec::with_capacity(n.max(0) as usize);
+    for _ in 0..n {
+        let indent = parse::parse_dimen(input)?;
+        let length = parse::parse_dimen(input)?;
+        shape.push((indent, length));
+    }
+    let base = input.state_mut().base_mut();
+    let global = base.global_prefix;
+    base.parameters.set_par_shape(Rc::new(shape), global);
+    Ok(())
+}
+
+pub fn get_par<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("par", par_fn, PAR_DOC)
+}
+
+pub fn get_indent<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("indent", indent_fn, INDENT_DOC)
+}
+
+pub fn get_noindent<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("noindent", noindent_fn, NOINDENT_DOC)
+}
+
+/// Returns the `\leavevmode` primitive.
+///
+/// In plain TeX `\leavevmode` is a macro that expands to `\unhbox\voidb@x`, which begins an
+/// indented paragraph in vertical mode and appends nothing otherwise. Texide provides it as a
+/// primitive with the same effect.
+pub fn get_leavevmode<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("leavevmode", leavevmode_fn, LEAVEVMODE_DOC)
+}
+
+/// Returns the `\parshape` primitive. Reading it as an integer is handled by the parsers and
+/// by `\the`, using [is_parshape_primitive].
+pub fn get_parshape<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("parshape", parshape_fn, PARSHAPE_DOC)
+}
+
+/// Returns whether a primitive is `\parshape`.
+pub fn is_parshape_primitive<S: TexState<S>>(p: &Primitive<S>) -> bool {
+    p.same_meaning(&Primitive::Execution(Execution::Static(get_parshape())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::dimen::{Dimen, Glue};
+    use crate::tex::node::{Char, GlueKind, Math, MathKind};
+    use crate::tex::parameter::{GlueParam, IntParam};
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        testutil::set_single_line_paragraphs(&mut s);
+        testutil::run(s, input)
+    }
+
+    /// Returns the outer vertical list after running the input.
+    fn vertical_list(input: &str) -> Vec<Node> {
+        let s = run(input).unwrap();
+        assert_eq!(s.base().nest.levels().len(), 1);
+        testutil::outer_list(&s)
+    }
+
+    /// Returns the contents of the lines in the outer vertical list after running the input.
+    fn lines(input: &str) -> Vec<Vec<Node>> {
+        vertical_list(input)
+            .iter()
+            .filter_map(Node::as_box)
+            .map(|b| b.list.clone())
+            .collect()
+    }
+
+    fn char(c: char) -> Node {
+        Node::Char(Char {
+            font: testutil::TEST_FONT,
+            c,
+        })
+    }
+
+    fn indent(width: Dimen) -> Node {
+        Node::HList(BoxNode {
+            width,
+            ..Default::default()
+        })
+    }
+
+    fn glue_kind(node: &Node) -> Option<GlueKind> {
+        match node {
+            Node::Glue(g) => Some(g.kind),
+            _ => None,
+        }
+    }
+
+    /// Removes the penalty, `\parfillskip` glue and `\rightskip` glue that end every
+    /// paragraph.
+    fn without_end(mut line: Vec<Node>) -> Vec<Node> {
+        assert_eq!(
+            glue_kind(&line.pop().unwrap()),
+            Some(GlueKind::Param(GlueParam::RightSkip))
+        );
+        assert_eq!(
+            glue_kind(&line.pop().unwrap()),
+            Some(GlueKind::Param(GlueParam::ParFillSkip))
+        );
+        assert_eq!(line.pop(), Some(Node::Penalty(10000)));
+        line
+    }
+
+    #[test]
+    fn character_begins_indented_paragraph() {
+        let list = vertical_list(r"\parindent=2pt ab\par");
+        assert_eq!(list.len(), 2);
+        // The \parskip glue is discarded at the top of the page, and \topskip glue is added.
+        assert_eq!(
+            glue_kind(&list[0]),
+            Some(GlueKind::Param(GlueParam::TopSkip))
+        );
+        let line = without_end(list[1].as_box().unwrap().list.clone());
+        assert_eq!(line, vec![indent(Dimen::from_pt(2)), char('a'), char('b')]);
+    }
+
+    #[test]
+    fn noindent() {
+        let lines = lines(r"\noindent a\par");
+        assert_eq!(without_end(lines[0].clone()), vec![char('a')]);
+    }
+
+    #[test]
+    fn indent_and_leavevmode() {
+        let lines = lines(r"\parindent=1pt \indent\par\leavevmode\par a\indent\noindent\par");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            without_end(lines[0].clone()),
+            vec![indent(Dimen::from_pt(1))]
+        );
+        assert_eq!(
+            without_end(lines[1].clone()),
+            vec![indent(Dimen::from_pt(1))]
+        );
+        assert_eq!(
+            without_end(lines[2].clone()),
+            vec![
+                indent(Dimen::from_pt(1)),
+                char('a'),
+                indent(Dimen::from_pt(1))
+            ]
+        );
+    }
+
+    #[test]
+    fn par_in_vertical_mode_does_nothing() {
+        assert_eq!(vertical_list(r"\par\par"), vec![]);
+        assert_eq!(lines(r"\noindent a\par\par").len(), 1);
+    }
+
+    #[test]
+    fn par_resets_paragraph_shape() {
+        let s = run(r"\looseness=2 \hangafter=3 \hangindent=1pt a\par").unwrap();
+        let parameters = &s.base().parameters;
+        assert_eq!(parameters.int(IntParam::Looseness), 0);
+        assert_eq!(parameters.int(IntParam::HangAfter), 1);
+        assert_eq!(parameters.dimen(DimenParam::HangIndent), Dimen(0));
+    }
+
+    #[test]
+    fn parshape() {
+        let s = run(r"\parshape 2 1pt 2pt 3pt 4pt \record\parshape \parshape=-1 \record\parshape")
+            .unwrap();
+        assert_eq!(s.recorded, vec![2, 0]);
+        let s = run(r"{\parshape 1 0pt 5pt \record\parshape}\record\parshape").unwrap();
+        assert_eq!(s.recorded, vec![1, 0]);
+        let s = run(r"\parshape 1 1pt 5pt \noindent a\par\record\parshape").unwrap();
+        assert_eq!(s.recorded, vec![0]);
+        let line = vertical_list(r"\parshape 1 1pt 5pt \noindent a\par")[1].clone();
+        let line = line.as_box().unwrap();
+        assert_eq!(
+            (line.shift, line.width),
+            (Dimen::from_pt(1), Dimen::from_pt(5))
+        );
+    }
+
+    #[test]
+    fn display_shape() {
+        let s = run(
+            r"\parshape 3 1pt 10pt 2pt 20pt 3pt 30pt \parindent=5pt \indent$$\record\displayindent \record\displaywidth \record\predisplaysize$$",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, vec![3 << 16, 30 << 16, 6 << 16]);
+        let s = run(
+            r"\hsize=100pt \hangindent=4pt \hangafter=0 \indent$$\record\displayindent \record\displaywidth$$",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, vec![4 << 16, 96 << 16]);
+        // Without text before the display, the pre-display size is the most negative dimension.
+        let s = run(r"\noindent$$\record\predisplaysize$$").unwrap();
+        assert_eq!(s.recorded, vec![-0x3fffffff]);
+    }
+
+    #[test]
+    fn final_space_is_removed() {
+        let lines = lines("\\noindent a b \\par");
+        assert_eq!(without_end(lines[0].clone()).len(), 3);
+    }
+
+    #[test]
+    fn end_of_job_ends_paragraph() {
+        assert_eq!(lines(r"\noindent a").len(), 1);
+    }
+
+    #[test]
+    fn everypar() {
+        let s = run(r"\everypar{\record1}a\par\noindent b").unwrap();
+        assert_eq!(s.recorded, vec![1, 1]);
+    }
+
+    #[test]
+    fn interline_glue() {
+        let list = vertical_list(r"\baselineskip=12pt a\par b\par");
+        let kinds: Vec<Option<GlueKind>> = list.iter().map(glue_kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Some(GlueKind::Param(GlueParam::TopSkip)),
+                None,
+                Some(GlueKind::Param(GlueParam::ParSkip)),
+                Some(GlueKind::Param(GlueParam::BaselineSkip)),
+                None,
+            ]
+        );
+        match &list[3] {
+            Node::Glue(g) => assert_eq!(g.glue.width, Dimen::from_pt(12)),
+            _ => unreachable!(),
+        }
+        let list = vertical_list(r"\baselineskip=12pt \lineskiplimit=13pt a\par b\par");
+        assert_eq!(
+            glue_kind(&list[3]),
+            Some(GlueKind::Param(GlueParam::LineSkip))
+        );
+    }
+
+    #[test]
+    fn space_factor() {
+        let line = without_end(
+            lines(r"\spaceskip=1pt plus 1pt minus 1pt \xspaceskip=5pt \sfcode`\.=3000 \sfcode`\,=2000 \sfcode`\B=999 \noindent a, B. c\par")[0]
+                .clone(),
+        );
+        let glues: Vec<Node> = line.into_iter().filter(|n| !n.is_char()).collect();
+        assert_eq!(
+            glue_kind(&glues[0]),
+            Some(GlueKind::Param(GlueParam::XSpaceSkip))
+        );
+        // The space factor after an uppercase letter is 999, so the period doesn't end a
+        // sentence.
+        match &glues[1] {
+            Node::Glue(g) => {
+                assert_eq!(g.kind, GlueKind::Param(GlueParam::SpaceSkip));
+                assert_eq!(
+                    g.glue,
+                    Glue {
+                        width: Dimen::from_pt(1),
+                        stretch: Dimen::from_pt(1),
+                        shrink: Dimen::from_pt(1),
+                        ..Default::default()
+                    }
+                );
+            }
+            _ => unreachable!(),
+        }
+        let line = without_end(
+            lines(r"\spaceskip=1pt plus 1pt minus 1pt \sfcode`\.=3000 \noindent a. c\par")[0]
+                .clone(),
+        );
+        match &line[2] {
+            Node::Glue(g) => {
+                assert_eq!(g.kind, GlueKind::Normal);
+                assert_eq!(g.glue.stretch, Dimen::from_pt(3));
+                assert_eq!(g.glue.shrink, Dimen(65536 / 3));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn spaces_in_vertical_mode_are_ignored() {
+        assert_eq!(vertical_list(" \\par  "), vec![]);
+    }
+
+    #[test]
+    fn inline_math() {
+        let lines = lines(r"\mathsurround=1pt \noindent a$b$\par");
+        let math = |kind| {
+            Node::Math(Math {
+                kind,
+                width: Dimen::from_pt(1),
+            })
+        };
+        assert_eq!(
+            without_end(lines[0].clone()),
+            vec![
+                char('a'),
+                math(MathKind::Before),
+                char('b'),
+                math(MathKind::After)
+            ]
+        );
+    }
+
+    #[test]
+    fn everymath_is_inserted_inside_the_formula() {
+        let lines = lines(r"\everymath{x}\everydisplay{y}\noindent a$b$\par");
+        let line = without_end(lines[0].clone());
+        assert_eq!(&line[2..4], &[char('x'), char('b')]);
+        assert!(matches!(line[4], Node::Math(_)));
+    }
+
+    #[test]
+    fn everydisplay_is_inserted_inside_the_display() {
+        let s = run(r"\everymath{x}\everydisplay{y}\noindent a$$b$$ c\par").unwrap();
+        let list = &testutil::outer_list(&s);
+        let boxes: Vec<&BoxNode> = list.iter().filter_map(Node::as_box).collect();
+        assert_eq!(boxes[1].list, vec![char('y'), char('b')]);
+    }
+
+    #[test]
+    fn math_shift_begins_paragraph() {
+        let lines = lines(r"$a$");
+        assert_eq!(without_end(lines[0].clone()).len(), 4);
+    }
+
+    #[test]
+    fn display_math() {
+        let s = run(r"\noindent a$$b$$ c\par").unwrap();
+        let list = &testutil::outer_list(&s);
+        let boxes: Vec<&BoxNode> = list.iter().filter_map(Node::as_box).collect();
+        assert_eq!(boxes.len(), 3);
+        assert_eq!(without_end(boxes[0].list.clone()), vec![char('a')]);
+        assert_eq!(boxes[1].list, vec![char('b')]);
+        // The space after the display is ignored.
+        assert_eq!(without_end(boxes[2].list.clone()), vec![char('c')]);
+        assert!(list.contains(&Node::Penalty(0)));
+        assert_eq!(s.base().nest.current().prev_graf, 5);
+    }
+
+    #[test]
+    fn math_shift_group() {
+        assert!(run(r"${a}$").is_ok());
+        assert!(run(r"${a$}").is_err());
+        assert!(run(r"$a}$").is_err());
+        assert!(run(r"$$a$b").is_err());
+    }
+
+    fn error(input: &str) -> String {
+        match run(input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let err = error("#");
+        assert!(
+            err.contains("You can't use `macro parameter character #' in vertical mode"),
+            "{}",
+            err
+        );
+        let err = error("a#");
+        assert!(err.contains("in horizontal mode"), "{}", err);
+        assert!(error("a^").contains("Missing $ inserted"));
+        assert!(error(r"$\par$").contains("Missing $ inserted"));
+        assert!(error("&").contains("Misplaced alignment tab character &"));
+    }
+}
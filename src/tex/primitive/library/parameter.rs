@@ -0,0 +1,326 @@
+//! Primitives for named internal parameters, like `\tolerance`, `\hsize`, `\parskip` and
+//! `\everypar`.
+//!
+//! Every parameter is a variable that can be read using `\the` and assigned to, locally or
+//! with `\global`:
+//! ```tex
+//! \newlinechar=`\^^J
+//! \global\hsize=6.5in
+//! \parskip=0pt plus 1pt
+//! \medmuskip=4mu plus 2mu minus 4mu
+//! \everypar={\indent}
+//! \everypar=\toks0
+//! ```
+
+use crate::tex::dimen::{Dimen, Glue};
+use crate::tex::parameter::{DimenParam, GlueParam, IntParam, MuGlueParam, TokenListParam};
+use crate::tex::primitive;
+use crate::tex::state::TexState;
+use crate::tex::token::token::Token;
+use std::rc::Rc;
+
+static INT_PARAM_DOC: &str = "Get or set an integer parameter";
+static DIMEN_PARAM_DOC: &str = "Get or set a dimension parameter";
+static GLUE_PARAM_DOC: &str = "Get or set a glue parameter";
+static MU_GLUE_PARAM_DOC: &str = "Get or set a math glue parameter";
+static TOKEN_LIST_PARAM_DOC: &str = "Get or set a token list parameter";
+
+fn int_param_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    state
+        .base_mut()
+        .parameters
+        .set_int(IntParam::from_index(index), value, global);
+    Ok(())
+}
+
+pub fn get_int_param<S: TexState<S>>(p: IntParam) -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: p.name(),
+        index: primitive::Index::Fixed(p.index()),
+        get_fn: |state: &S, index| state.base().parameters.int(IntParam::from_index(index)),
+        set_fn: Some(int_param_set),
+        docs: INT_PARAM_DOC,
+    }
+}
+
+fn dimen_param_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Dimen,
+    global: bool,
+) -> anyhow::Result<()> {
+    state
+        .base_mut()
+        .parameters
+        .set_dimen(DimenParam::from_index(index), value, global);
+    Ok(())
+}
+
+pub fn get_dimen_param<S: TexState<S>>(p: DimenParam) -> primitive::TypedVariable<S, Dimen> {
+    primitive::TypedVariable {
+        name: p.name(),
+        index: primitive::Index::Fixed(p.index()),
+        get_fn: |state: &S, index| state.base().parameters.dimen(DimenParam::from_index(index)),
+        set_fn: Some(dimen_param_set),
+        docs: DIMEN_PARAM_DOC,
+    }
+}
+
+fn glue_param_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Glue,
+    global: bool,
+) -> anyhow::Result<()> {
+    state
+        .base_mut()
+        .parameters
+        .set_glue(GlueParam::from_index(index), value, global);
+    Ok(())
+}
+
+pub fn get_glue_param<S: TexState<S>>(p: GlueParam) -> primitive::TypedVariable<S, Glue> {
+    primitive::TypedVariable {
+        name: p.name(),
+        index: primitive::Index::Fixed(p.index()),
+        get_fn: |state: &S, index| state.base().parameters.glue(GlueParam::from_index(index)),
+        set_fn: Some(glue_param_set),
+        docs: GLUE_PARAM_DOC,
+    }
+}
+
+fn mu_glue_param_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Glue,
+    global: bool,
+) -> anyhow::Result<()> {
+    state
+        .base_mut()
+        .parameters
+        .set_mu_glue(MuGlueParam::from_index(index), value, global);
+    Ok(())
+}
+
+pub fn get_mu_glue_param<S: TexState<S>>(p: MuGlueParam) -> primitive::TypedVariable<S, Glue> {
+    primitive::TypedVariable {
+        name: p.name(),
+        index: primitive::Index::Fixed(p.index()),
+        get_fn: |state: &S, index| {
+            state
+                .base()
+                .parameters
+                .mu_glue(MuGlueParam::from_index(index))
+        },
+        set_fn: Some(mu_glue_param_set),
+        docs: MU_GLUE_PARAM_DOC,
+    }
+}
+
+fn token_list_param_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Rc<Vec<Token>>,
+    global: bool,
+) -> anyhow::Result<()> {
+    state
+        .base_mut()
+        .parameters
+        .set_token_list(TokenListParam::from_index(index), value, global);
+    Ok(())
+}
+
+pub fn get_token_list_param<S: TexState<S>>(
+    p: TokenListParam,
+) -> primitive::TypedVariable<S, Rc<Vec<Token>>> {
+    primitive::TypedVariable {
+        name: p.name(),
+        index: primitive::Index::Fixed(p.index()),
+        get_fn: |state: &S, index| {
+            state
+                .base()
+                .parameters
+                .token_list(TokenListParam::from_index(index))
+        },
+        set_fn: Some(token_list_param_set),
+        docs: TOKEN_LIST_PARAM_DOC,
+    }
+}
+
+/// Returns every parameter as a variable, along with its name.
+pub fn all<S: TexState<S>>() -> Vec<(&'static str, primitive::Variable<S>)> {
+    let mut result = Vec::new();
+    for p in IntParam::ALL {
+        result.push((p.name(), primitive::Variable::Int(get_int_param(p))));
+    }
+    for p in DimenParam::ALL {
+        result.push((p.name(), primitive::Variable::Dimen(get_dimen_param(p))));
+    }
+    for p in GlueParam::ALL {
+        result.push((p.name(), primitive::Variable::Glue(get_glue_param(p))));
+    }
+    for p in MuGlueParam::ALL {
+        result.push((p.name(), primitive::Variable::MuGlue(get_mu_glue_param(p))));
+    }
+    for p in TokenListParam::ALL {
+        result.push((
+            p.name(),
+            primitive::Variable::TokenList(get_token_list_param(p)),
+        ));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::primitive::{Primitive, Variable};
+    use crate::tex::print::Selector;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run_state(input: &str) -> anyhow::Result<TestState> {
+        testutil::run(testutil::new_state(), input)
+    }
+
+    fn run(input: &str) -> anyhow::Result<Vec<i32>> {
+        run_state(input).map(|s| s.recorded)
+    }
+
+    #[test]
+    fn int_params() {
+        assert_eq!(
+            run(r"\newlinechar=10 \tracingonline=1 \record\newlinechar\record\tracingonline")
+                .unwrap(),
+            vec![10, 1]
+        );
+    }
+
+    #[test]
+    fn int_params_are_scoped() {
+        assert_eq!(
+            run(r"{\newlinechar=10 \global\tracingonline=1 }\record\newlinechar\record\tracingonline")
+                .unwrap(),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn initial_values() {
+        assert_eq!(
+            run(r"\record\tolerance\record\escapechar\record\year\record\pretolerance").unwrap(),
+            vec![10000, 92, 1776, 0]
+        );
+    }
+
+    #[test]
+    fn dimen_and_glue_params() {
+        let s = run_state(
+            r"\hsize=6.5in {\global\vsize=2pt \parskip=1pt plus 2fil}\showthe\hsize\showthe\vsize\showthe\parskip\baselineskip=3pt minus 1pt\showthe\baselineskip",
+        )
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> 469.75499pt.\n> 2.0pt.\n> 0.0pt.\n> 3.0pt minus 1.0pt."
+        );
+    }
+
+    #[test]
+    fn mu_glue_params() {
+        let s = run_state(
+            r"\thinmuskip=3mu {\medmuskip=4mu plus 2mu minus 4mu \showthe\medmuskip}\showthe\medmuskip\thickmuskip=\thinmuskip \advance\thickmuskip by 2mu plus 5mu \showthe\thickmuskip",
+        )
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> 4.0mu plus 2.0mu minus 4.0mu.\n> 0.0mu.\n> 5.0mu plus 5.0mu."
+        );
+    }
+
+    #[test]
+    fn token_list_params() {
+        let s = run_state(
+            r"\toks0={b}\everypar={a}{\everycr=\toks0 \global\output=\everypar \showthe\everycr}\showthe\everycr\showthe\output",
+        )
+        .unwrap();
+        assert_eq!(testutil::terminal(&s), "> b.\n> .\n> a.");
+    }
+
+    #[test]
+    fn everyjob_is_inserted_at_the_start() {
+        let mut s = testutil::new_state();
+        s.base_mut().parameters.set_token_list(
+            TokenListParam::EveryJob,
+            Rc::new(vec![
+                Token::new_control_sequence("record"),
+                Token::new_other('1'),
+                Token::new_space(' '),
+            ]),
+            false,
+        );
+        assert_eq!(testutil::run(s, r"\record2").unwrap().recorded, vec![1, 2]);
+    }
+
+    #[test]
+    fn everyeof_is_inserted_once_at_the_end_of_the_file() {
+        assert_eq!(run(r"\everyeof{\record2 }\record1 ").unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn everyeof_is_read_when_the_file_ends() {
+        // The tokens are those of \everyeof when the file ends, and the end of another token
+        // list, like \everyjob, is not the end of the file.
+        let mut s = testutil::new_state();
+        s.base_mut().parameters.set_token_list(
+            TokenListParam::EveryJob,
+            Rc::new(vec![
+                Token::new_control_sequence("record"),
+                Token::new_other('1'),
+                Token::new_space(' '),
+            ]),
+            false,
+        );
+        let s = testutil::run(s, r"\everyeof{\record4 }\record2 \everyeof{\record3 }").unwrap();
+        assert_eq!(s.recorded, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn escapechar() {
+        let s = run_state(r"\show\hsize{\escapechar=`\! \show\hsize}\escapechar=-1 \show\hsize")
+            .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> \\hsize=\\hsize.\n> !hsize=!hsize.\n> hsize=hsize."
+        );
+    }
+
+    #[test]
+    fn int_params_are_distinct() {
+        let a = Primitive::<testutil::TestState>::Variable(Variable::Int(get_int_param(
+            IntParam::NewLineChar,
+        )));
+        let b = Primitive::Variable(Variable::Int(get_int_param(IntParam::TracingOnline)));
+        assert!(!a.same_meaning(&b));
+        assert!(a.same_meaning(&a.clone()));
+        let c = Primitive::<testutil::TestState>::Variable(Variable::Dimen(get_dimen_param(
+            DimenParam::HSize,
+        )));
+        let d = Primitive::Variable(Variable::Dimen(get_dimen_param(DimenParam::VSize)));
+        assert!(!c.same_meaning(&d));
+    }
+
+    #[test]
+    fn tracingonline_routes_diagnostics() {
+        let mut s = testutil::new_state();
+        assert_eq!(s.base().diagnostic_selector(), Selector::Log);
+        s.base_mut()
+            .parameters
+            .set_int(IntParam::TracingOnline, 1, false);
+        assert_eq!(s.base().diagnostic_selector(), Selector::TerminalAndLog);
+    }
+}
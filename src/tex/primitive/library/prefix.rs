@@ -0,0 +1,273 @@
+//! Prefixes for assignments: `\global`, `\long` and `\outer`.
+//!
+//! Any assignment can be prefixed by `\global`. `\long` and `\outer` can only prefix macro
+//! definitions: a long macro can take arguments that contain `\par`, and an outer macro cannot
+//! appear in the arguments of macros, in definitions and other token lists being read, or in
+//! the text skipped by conditionals. Prefixes can be combined, as in `\long\outer\global\def`,
+//! and spaces and `\relax` after a prefix are skipped.
+//!
+//! Commands are identified as assignments and macro definitions using their ids, so that the
+//! check survives renaming the commands with `\let`.
+
+use crate::tex::error;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::{Execution, ExecutionGeneric, Primitive};
+use crate::tex::state::TexState;
+use crate::tex::token::token::{Token, Value};
+use std::any::TypeId;
+
+/// The id of assignments other than macro definitions.
+struct Assignment;
+/// The id of macro definitions, which are the assignments that `\long` and `\outer` can prefix.
+struct Definition;
+
+static GLOBAL_DOC: &str = "Make the following assignment global";
+static LONG_DOC: &str = "Allow the arguments of the following macro definition to contain \\par";
+static OUTER_DOC: &str = "Forbid the following macro from appearing in arguments and skipped text";
+
+/// Returns a new static execution primitive for an assignment, which can be prefixed by
+/// `\global`.
+pub fn new_assignment<S>(
+    name: &'static str,
+    call_fn: fn(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()>,
+    docs: &'static str,
+) -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new(name, call_fn, docs).with_id(TypeId::of::<Assignment>())
+}
+
+/// Returns a new static execution primitive for a macro definition, which can be prefixed by
+/// `\global`, `\long` and `\outer`.
+pub fn new_definition<S>(
+    name: &'static str,
+    call_fn: fn(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()>,
+    docs: &'static str,
+) -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new(name, call_fn, docs).with_id(TypeId::of::<Definition>())
+}
+
+fn global_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let prefixes = Prefixes {
+        global: true,
+        ..Default::default()
+    };
+    prefix(token, input, prefixes)
+}
+
+fn long_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let prefixes = Prefixes {
+        long: true,
+        ..Default::default()
+    };
+    prefix(token, input, prefixes)
+}
+
+fn outer_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let prefixes = Prefixes {
+        outer: true,
+        ..Default::default()
+    };
+    prefix(token, input, prefixes)
+}
+
+/// The prefixes before an assignment.
+#[derive(Debug, Default, Copy, Clone)]
+struct Prefixes {
+    global: bool,
+    long: bool,
+    outer: bool,
+}
+
+/// Reads any further prefixes and performs the assignment that follows them. This is
+/// `prefixed_command` in TeX.
+fn prefix<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    mut prefixes: Prefixes,
+) -> anyhow::Result<()> {
+    let (next, meaning) = loop {
+        let next = match parse::parse_non_blank_non_relax(input)? {
+            None => {
+                return Err(error::new_token_error(
+                    token,
+                    "Unexpected end of input".to_string(),
+                    vec!["expected an assignment after this prefix".to_string()],
+                ))
+            }
+            Some(next) => next,
+        };
+        let meaning = input.state().base().meaning(&next.value);
+        let is = |get: fn() -> primitive::ExecutionStatic<S>| match &meaning {
+            None => false,
+            Some(meaning) => meaning.same_meaning(&Primitive::Execution(Execution::Static(get()))),
+        };
+        if is(get_global) {
+            prefixes.global = true;
+        } else if is(get_long) {
+            prefixes.long = true;
+        } else if is(get_outer) {
+            prefixes.outer = true;
+        } else {
+            break (next, meaning);
+        }
+    };
+    let id = match &meaning {
+        Some(Primitive::Execution(command)) => command.id(),
+        _ => None,
+    };
+    if (prefixes.long || prefixes.outer) && id != Some(TypeId::of::<Definition>()) {
+        return Err(error::new_token_error(
+            next.clone(),
+            format!(
+                "You can't use `\\long' or `\\outer' with `{}'",
+                describe(&next)
+            ),
+            vec!["\\long and \\outer can only prefix macro definitions".to_string()],
+        ));
+    }
+    match meaning {
+        Some(Primitive::Execution(command))
+            if id == Some(TypeId::of::<Assignment>()) || id == Some(TypeId::of::<Definition>()) =>
+        {
+            let base = input.state_mut().base_mut();
+            base.global_prefix = prefixes.global;
+            base.long_prefix = prefixes.long;
+            base.outer_prefix = prefixes.outer;
+            let result = command.call(next, input);
+            let base = input.state_mut().base_mut();
+            base.global_prefix = false;
+            base.long_prefix = false;
+            base.outer_prefix = false;
+            result
+        }
+        Some(Primitive::Variable(variable)) => variable.assign(next, input, prefixes.global),
+        Some(Primitive::Font(id)) => {
+            input
+                .state_mut()
+                .base_mut()
+                .fonts
+                .set_current(id, prefixes.global);
+            Ok(())
+        }
+        _ => Err(error::new_token_error(
+            next.clone(),
+            format!("You can't use a prefix with `{}'", describe(&next)),
+            vec!["a prefix must be followed by an assignment".to_string()],
+        )),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match &token.value {
+        Value::ControlSequence(_, name) => format!("\\{}", name),
+        Value::Character(c, _) => c.to_string(),
+    }
+}
+
+pub fn get_global<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("global", global_fn, GLOBAL_DOC)
+}
+
+pub fn get_long<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("long", long_fn, LONG_DOC)
+}
+
+pub fn get_outer<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("outer", outer_fn, OUTER_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tex::testutil;
+
+    fn run(input: &str) -> anyhow::Result<Vec<i32>> {
+        testutil::run(testutil::new_state(), input).map(|s| s.recorded)
+    }
+
+    #[test]
+    fn combined_prefixes() {
+        assert_eq!(
+            run(r"{\long\outer\global\def\a{\record1}}\a{\global\long\def\b{\record2}}\b").unwrap(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn long_is_local() {
+        assert_eq!(
+            run(r"\def\a{\record1}{\long\def\a{\record2}\a}\a").unwrap(),
+            vec![2, 1]
+        );
+    }
+
+    #[test]
+    fn global_variable() {
+        assert_eq!(
+            run(r"{\global\count1=3 \count2=4 }\record\count1\record\count2").unwrap(),
+            vec![3, 0]
+        );
+    }
+
+    #[test]
+    fn spaces_and_relax_after_prefix() {
+        assert_eq!(
+            run(r"{\global \relax\count1=3 }\record\count1").unwrap(),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn prefixes_of_renamed_commands() {
+        assert_eq!(
+            run(r"\let\g=\global \let\d=\def {\g\long\d\a#1{\record1}}\a\par").unwrap(),
+            vec![1]
+        );
+    }
+
+    fn error(input: &str) -> String {
+        match run(input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn prefix_without_assignment() {
+        assert!(error(r"\global a").contains("You can't use a prefix with `a'"));
+        assert!(error(r"\global\record1").contains(r"You can't use a prefix with `\record'"));
+        assert!(error(r"\global\global").contains("Unexpected end of input"));
+        assert!(run(r"\long").is_err());
+    }
+
+    #[test]
+    fn long_or_outer_without_definition() {
+        let err = error(r"\long\count1=2");
+        assert!(err.contains(r"You can't use `\long' or `\outer' with `\count'"));
+        let err = error(r"\outer\global\let\a=\relax");
+        assert!(err.contains(r"You can't use `\long' or `\outer' with `\let'"));
+    }
+
+    #[test]
+    fn par_in_argument() {
+        let err = error(r"\def\a#1{}\a{x\par}");
+        assert!(err.contains(r"Paragraph ended before \a was complete"));
+        let err = error(r"\def\a#1.{}\a x\par.");
+        assert!(err.contains(r"Paragraph ended before \a was complete"));
+        assert_eq!(
+            run(r"\long\def\a#1.{\record1}\a{x\par}.\a x\par.").unwrap(),
+            vec![1, 1]
+        );
+    }
+
+    #[test]
+    fn outer_macro() {
+        // An outer macro can be used outside of arguments, definitions and skipped text.
+        assert_eq!(run(r"\outer\def\b{\record1}\b").unwrap(), vec![1]);
+        let err = error(r"\outer\def\b{}\def\a#1{}\a\b");
+        assert!(err.contains(r"Forbidden control sequence found while scanning use of \a"));
+        let err = error(r"\outer\def\b{}\def\a{\b}");
+        assert!(err.contains(r"Forbidden control sequence found while scanning definition of \a"));
+        let err = error(r"\outer\def\b{}\iffalse\b\fi");
+        assert!(err.contains("Incomplete conditional"));
+    }
+}
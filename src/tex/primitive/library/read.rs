@@ -0,0 +1,431 @@
+//! Primitives for the input streams: `\openin`, `\read`, `\readline` and `\closein`.
+//!
+//! `\read` reads a line from an input stream, lexes it using the current catcodes and defines
+//! a macro whose replacement text is the resulting tokens. If the line has unbalanced braces,
+//! further lines are read until the braces are balanced. `\readline` reads a single line as
+//! raw text instead. Reading from a stream that is not open reads from the terminal; see the
+//! [files](crate::tex::files) module.
+//! ```tex
+//! \openin1=data      % opens data.tex or, if it doesn't exist, data
+//! \ifeof1 \else
+//!   \read1 to \line  % \line is now a macro containing the first line of the file
+//! \fi
+//! \closein1
+//! ```
+
+use crate::datastructures::scopedcharmap::ScopedCharMap;
+use crate::tex::error;
+use crate::tex::files::NUM_STREAMS;
+use crate::tex::filesystem;
+use crate::tex::macros::{Macro, Replacement};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::{def, prefix};
+use crate::tex::primitive::{Name, Primitive};
+use crate::tex::print::Selector;
+use crate::tex::state::TexState;
+use crate::tex::token::catcode::{CatCode, RawCatCode};
+use crate::tex::token::lexer;
+use crate::tex::token::token::{Token, Value};
+use std::io;
+use std::rc::Rc;
+
+static OPENIN_DOC: &str = "Open a file for reading using \\read";
+static CLOSEIN_DOC: &str = "Close a file opened using \\openin";
+static READ_DOC: &str = "Define a macro to be the tokens on the next line of an input stream";
+static READLINE_DOC: &str = "Define a macro to be the raw text of the next line of an input stream";
+
+fn openin_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let stream = parse_stream(input)?;
+    parse::parse_optional_equals(input)?;
+    let file_name = parse::parse_file_name(input)?;
+    let base = input.state_mut().base_mut();
+    base.input_files.close(stream);
+    // As in TeX, a file that cannot be opened leaves the stream closed, which can be detected
+    // using \ifeof.
+    let with_extension = filesystem::with_default_extension(file_name.clone(), "tex");
+    if let Ok(reader) = base
+        .file_system
+        .open(&with_extension)
+        .or_else(|_| base.file_system.open(&file_name))
+    {
+        base.input_files.open(stream, reader);
+    }
+    Ok(())
+}
+
+fn closein_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let stream = parse_stream(input)?;
+    input.state_mut().base_mut().input_files.close(stream);
+    Ok(())
+}
+
+fn read_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    read(token, input, false)
+}
+
+fn readline_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    read(token, input, true)
+}
+
+fn read<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    raw: bool,
+) -> anyhow::Result<()> {
+    let stream = parse::parse_number(input)?;
+    if !parse::parse_keyword(input, "to")? {
+        return Err(error::new_token_error(
+            token,
+            "Missing `to' inserted".to_string(),
+            vec!["you should have said `\\read<number> to \\cs'".to_string()],
+        ));
+    }
+    let target = def::parse_target(&token, input)?;
+    let tokens = match raw {
+        true => read_raw_line(input, stream, &target)?,
+        false => read_lines(token, input, stream, &target)?,
+    };
+    let replacement = tokens.into_iter().map(Replacement::Token).collect();
+    let m = Macro::new(Vec::new(), Vec::new(), replacement, false, false);
+    let base = input.state_mut().base_mut();
+    base.primitives.set(
+        target,
+        Some(Primitive::Macro(Rc::new(m))),
+        base.global_prefix,
+    );
+    Ok(())
+}
+
+/// Reads lines from the stream, as `\read` does, until the braces in them are balanced and
+/// returns the tokens they contain.
+fn read_lines<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    mut stream: i32,
+    target: &Name,
+) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut depth = 0_usize;
+    loop {
+        let line = match read_line(input, stream, target)? {
+            Some(line) => line,
+            None if depth > 0 => {
+                return Err(error::new_token_error(
+                    token,
+                    "File ended within \\read".to_string(),
+                    vec!["the braces in the lines read were not balanced".to_string()],
+                ))
+            }
+            // As in TeX, reading past the end of a file gives an empty line.
+            None => String::new(),
+        };
+        // Subsequent lines read from the terminal don't show the prompt again.
+        if !input.state().base().input_files.is_open(stream) {
+            stream = -1;
+        }
+        let base = input.state().base();
+        let end_line_char = base.parameters.end_line_char();
+        for token in lex_line(&base.input_module.cat_code_map, &line, end_line_char)? {
+            match token.value {
+                Value::Character(_, CatCode::BeginGroup) => depth += 1,
+                Value::Character(_, CatCode::EndGroup) => {
+                    // As in TeX, an unmatched end group character ends the line.
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+            tokens.push(token);
+        }
+        if depth == 0 {
+            return Ok(tokens);
+        }
+    }
+}
+
+/// Reads a single line from the stream, as `\readline` does, and returns its characters,
+/// followed by the end of line character, as tokens with catcode other, except for spaces
+/// which have catcode space.
+fn read_raw_line<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    stream: i32,
+    target: &Name,
+) -> anyhow::Result<Vec<Token>> {
+    let mut line = read_line(input, stream, target)?
+        .unwrap_or_default()
+        .trim_end_matches(' ')
+        .to_string();
+    line.extend(input.state().base().parameters.end_line_char());
+    Ok(line
+        .chars()
+        .map(|c| match c {
+            ' ' => Token::new_space(c),
+            _ => Token::new_other(c),
+        })
+        .collect())
+}
+
+/// Reads the next line from the stream or, if the stream is not open, from the terminal.
+/// Returns `None` if the stream reached the end of its file.
+fn read_line<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    stream: i32,
+    target: &Name,
+) -> anyhow::Result<Option<String>> {
+    let base = input.state_mut().base_mut();
+    if base.input_files.is_open(stream) {
+        return Ok(base.input_files.read_line(stream)?);
+    }
+    // As in TeX, reading from the terminal for a non-negative stream number prompts with the
+    // name of the control sequence being defined.
+    if stream >= 0 {
+        let mut prompt = String::new();
+        match target {
+            Name::ControlSequence(name) => base
+                .write_style()
+                .write_control_sequence_name(&mut prompt, name),
+            Name::ActiveCharacter(c) => prompt.push(*c),
+        }
+        prompt.push('=');
+        base.print_nl(Selector::TerminalAndLog, &prompt);
+        base.printer.flush();
+    }
+    match base.input_files.read_terminal_line()? {
+        None => Err(anyhow::anyhow!("End of file on the terminal!")),
+        Some(line) => {
+            // As in TeX, the terminal input is echoed to the log file.
+            base.printer.end_terminal_input();
+            base.print(Selector::Log, &line);
+            base.printer.print_ln(Selector::Log);
+            Ok(Some(line))
+        }
+    }
+}
+
+/// Lexes a line using the provided catcodes, after appending the end of line character.
+///
+/// As in TeX, spaces at the start of the line are skipped, spaces at the end of the line are
+/// removed, and an empty line becomes a `\par` token if the end of line character has catcode
+/// end of line.
+fn lex_line(
+    map: &ScopedCharMap<RawCatCode>,
+    line: &str,
+    end_line_char: Option<char>,
+) -> anyhow::Result<Vec<Token>> {
+    let mut line = line
+        .trim_start_matches(|c| map.get(&c) == Some(&RawCatCode::Regular(CatCode::Space)))
+        .trim_end_matches(' ')
+        .to_string();
+    if line.is_empty() && end_line_char.and_then(|c| map.get(&c)) == Some(&RawCatCode::EndOfLine) {
+        return Ok(vec![Token::new_control_sequence("par")]);
+    }
+    line.extend(end_line_char);
+    let mut lexer = lexer::Lexer::new(io::Cursor::new(line));
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next(map)? {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Reads an input stream number, which must be between 0 and 15, from the input.
+pub fn parse_stream<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<i32> {
+    let n = parse::parse_number(input)?;
+    match n >= 0 && (n as usize) < NUM_STREAMS {
+        true => Ok(n),
+        false => Err(anyhow::anyhow!(
+            "Bad number ({}); an input stream number must be between 0 and {}",
+            n,
+            NUM_STREAMS - 1
+        )),
+    }
+}
+
+pub fn get_openin<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("openin", openin_fn, OPENIN_DOC)
+}
+
+pub fn get_closein<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("closein", closein_fn, CLOSEIN_DOC)
+}
+
+pub fn get_read<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("read", read_fn, READ_DOC)
+}
+
+pub fn get_readline<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("readline", readline_fn, READLINE_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::filesystem::InMemoryFileSystem;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(files: &[(&str, &str)], terminal: &str, input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        let mut file_system = InMemoryFileSystem::new();
+        for (path, contents) in files {
+            file_system.insert(path, contents);
+        }
+        s.base_mut().file_system = Box::new(file_system);
+        s.base_mut()
+            .input_files
+            .set_terminal(Box::new(io::Cursor::new(terminal.to_string())));
+        testutil::run(s, input)
+    }
+
+    #[test]
+    fn read_lines_from_file() {
+        let s = run(
+            &[("data.tex", "a b  \n\n  {c\nd}e\n")],
+            "",
+            r"\openin1=data \read1 to\x\show\x\read1 to \x\show\x\read1 to\x\show\x\ifeof1 \record1\fi\read1 to\x\show\x\ifeof1 \record2\fi",
+        )
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> \\x=macro:->a b\n.\n> \\x=macro:->\\par .\n> \\x=macro:->{c\nd}e\n.\n> \\x=macro:->\\par ."
+        );
+        assert_eq!(s.recorded, vec![2]);
+    }
+
+    #[test]
+    fn read_lexes_with_current_catcodes() {
+        let s = run(
+            &[("data.tex", "\\a b%c\n")],
+            "",
+            r"\openin1=data \read1 to\x\show\x",
+        )
+        .unwrap();
+        assert_eq!(testutil::terminal(&s), "> \\x=macro:->\\a b.");
+    }
+
+    #[test]
+    fn unmatched_end_group_ends_the_line() {
+        let s = run(
+            &[("data.tex", "a}b\nc\n")],
+            "",
+            r"\openin1=data \read1 to\x\show\x\read1 to\x\show\x",
+        )
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> \\x=macro:->a.\n> \\x=macro:->c\n."
+        );
+    }
+
+    #[test]
+    fn file_ended_within_read() {
+        let err = run(&[("data.tex", "{a\n")], "", r"\openin1=data \read1 to\x")
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("File ended within \\read"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn readline() {
+        let s = run(
+            &[("data.tex", "  a{\\b %c  \n")],
+            "",
+            r"\openin1=data \readline1 to\x\show\x",
+        )
+        .unwrap();
+        assert_eq!(testutil::terminal(&s), "> \\x=macro:->  a{\\b %c\n.");
+    }
+
+    #[test]
+    fn end_line_char() {
+        let s = run(
+            &[("data.tex", "a \n\nb\n")],
+            "",
+            r"\openin1=data \endlinechar=-1 \read1 to\x\show\x\read1 to\x\show\x\endlinechar=`\} \readline1 to\x\show\x",
+        )
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> \\x=macro:->a.\n> \\x=macro:->.\n> \\x=macro:->b}."
+        );
+    }
+
+    #[test]
+    fn openin_file_without_extension() {
+        let s = run(
+            &[("data", "a\n")],
+            "",
+            r"\openin1=data \ifeof1 \record1\fi\read1 to\x\show\x",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, Vec::<i32>::new());
+        assert_eq!(testutil::terminal(&s), "> \\x=macro:->a\n.");
+    }
+
+    #[test]
+    fn openin_missing_file() {
+        let s = run(&[], "", r"\openin1=missing \ifeof1 \record1\fi").unwrap();
+        assert_eq!(s.recorded, vec![1]);
+    }
+
+    #[test]
+    fn closein() {
+        let s = run(
+            &[("data.tex", "a\n")],
+            "",
+            r"\openin1=data \ifeof1 \record1\fi\closein1 \ifeof1 \record2\fi",
+        )
+        .unwrap();
+        assert_eq!(s.recorded, vec![2]);
+    }
+
+    #[test]
+    fn read_from_terminal() {
+        let s = run(
+            &[],
+            "a\n{b\nc}\n",
+            r"\read16 to\x\show\x\read-1 to\x\show\x",
+        )
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "\\x=> \\x=macro:->a\n.> \\x=macro:->{b\nc}\n."
+        );
+    }
+
+    #[test]
+    fn end_of_file_on_the_terminal() {
+        let err = run(&[], "", r"\read-1 to\x").err().unwrap();
+        assert!(err.to_string().contains("End of file on the terminal"));
+    }
+
+    #[test]
+    fn read_is_local_unless_global() {
+        let s = run(
+            &[],
+            "a\nb\n",
+            r"{\read-1 to\x\global\read-1 to\y}\show\x\show\y",
+        )
+        .unwrap();
+        assert_eq!(
+            testutil::terminal(&s),
+            "> \\x=undefined.\n> \\y=macro:->b\n."
+        );
+    }
+
+    #[test]
+    fn missing_to() {
+        let err = run(&[], "a\n", r"\read-1 \x").err().unwrap();
+        assert!(err.to_string().contains("Missing `to' inserted"), "{}", err);
+    }
+}
@@ -0,0 +1,633 @@
+//! Register primitives: `\count`, `\dimen`, `\skip`, `\muskip` and `\toks`, and `\countdef`,
+//! `\dimendef`, `\skipdef`, `\muskipdef` and `\toksdef`, which bind a control sequence to a
+//! single register:
+//! ```tex
+//! \countdef\pageno=0 \pageno=5   % the same as \count0=5
+//! ```
+
+use crate::tex::dimen::{Dimen, Glue};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::{def, prefix};
+use crate::tex::primitive::{Primitive, Variable};
+use crate::tex::register::NUM_REGISTERS;
+use crate::tex::state::TexState;
+use crate::tex::token::token::Token;
+use std::rc::Rc;
+
+static COUNT_DOC: &str = "Get or set an integer register";
+static DIMEN_DOC: &str = "Get or set a dimension register";
+static SKIP_DOC: &str = "Get or set a glue register";
+static MUSKIP_DOC: &str = "Get or set a math glue register";
+static TOKS_DOC: &str = "Get or set a token list register";
+static COUNTDEF_DOC: &str = "Define a control sequence to be an integer register";
+static DIMENDEF_DOC: &str = "Define a control sequence to be a dimension register";
+static SKIPDEF_DOC: &str = "Define a control sequence to be a glue register";
+static MUSKIPDEF_DOC: &str = "Define a control sequence to be a math glue register";
+static TOKSDEF_DOC: &str = "Define a control sequence to be a token list register";
+
+/// Reads a register number from the input.
+pub fn parse_register<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<usize> {
+    let n = parse::parse_number(input)?;
+    match n >= 0 && (n as usize) < NUM_REGISTERS {
+        true => Ok(n as usize),
+        false => Err(anyhow::anyhow!(
+            "Bad register code ({}); a register number must be between 0 and {}",
+            n,
+            NUM_REGISTERS - 1
+        )),
+    }
+}
+
+fn count_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: i32,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().registers.counts;
+    match global {
+        true => map.insert_global(index, value),
+        false => map.insert(index, value),
+    }
+    Ok(())
+}
+
+fn dimen_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Dimen,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().registers.dimens;
+    match global {
+        true => map.insert_global(index, value),
+        false => map.insert(index, value),
+    }
+    Ok(())
+}
+
+fn skip_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Glue,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().registers.skips;
+    match global {
+        true => map.insert_global(index, value),
+        false => map.insert(index, value),
+    }
+    Ok(())
+}
+
+fn muskip_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Glue,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().registers.mu_skips;
+    match global {
+        true => map.insert_global(index, value),
+        false => map.insert(index, value),
+    }
+    Ok(())
+}
+
+fn toks_set<S: TexState<S>>(
+    state: &mut S,
+    index: usize,
+    value: Rc<Vec<Token>>,
+    global: bool,
+) -> anyhow::Result<()> {
+    let map = &mut state.base_mut().registers.toks;
+    match global {
+        true => map.insert_global(index, value),
+        false => map.insert(index, value),
+    }
+    Ok(())
+}
+
+pub fn get_count<S: TexState<S>>() -> primitive::TypedVariable<S, i32> {
+    primitive::TypedVariable {
+        name: "count",
+        index: primitive::Index::Parsed(parse_register),
+        get_fn: |state: &S, index| state.base().registers.count(index),
+        set_fn: Some(count_set),
+        docs: COUNT_DOC,
+    }
+}
+
+pub fn get_dimen<S: TexState<S>>() -> primitive::TypedVariable<S, Dimen> {
+    primitive::TypedVariable {
+        name: "dimen",
+        index: primitive::Index::Parsed(parse_register),
+        get_fn: |state: &S, index| state.base().registers.dimen(index),
+        set_fn: Some(dimen_set),
+        docs: DIMEN_DOC,
+    }
+}
+
+pub fn get_skip<S: TexState<S>>() -> primitive::TypedVariable<S, Glue> {
+    primitive::TypedVariable {
+        name: "skip",
+        index: primitive::Index::Parsed(parse_register),
+        get_fn: |state: &S, index| state.base().registers.skip(index),
+        set_fn: Some(skip_set),
+        docs: SKIP_DOC,
+    }
+}
+
+pub fn get_muskip<S: TexState<S>>() -> primitive::TypedVariable<S, Glue> {
+    primitive::TypedVariable {
+        name: "muskip",
+        index: primitive::Index::Parsed(parse_register),
+        get_fn: |state: &S, index| state.base().registers.mu_skip(index),
+        set_fn: Some(muskip_set),
+        docs: MUSKIP_DOC,
+    }
+}
+
+pub fn get_toks<S: TexState<S>>() -> primitive::TypedVariable<S, Rc<Vec<Token>>> {
+    primitive::TypedVariable {
+        name: "toks",
+        index: primitive::Index::Parsed(parse_register),
+        get_fn: |state: &S, index| state.base().registers.toks(index),
+        set_fn: Some(toks_set),
+        docs: TOKS_DOC,
+    }
+}
+
+fn countdef_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    register_def(token, input, |n| {
+        Variable::Int(primitive::TypedVariable {
+            index: primitive::Index::Fixed(n),
+            ..get_count()
+        })
+    })
+}
+
+fn dimendef_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    register_def(token, input, |n| {
+        Variable::Dimen(primitive::TypedVariable {
+            index: primitive::Index::Fixed(n),
+            ..get_dimen()
+        })
+    })
+}
+
+fn skipdef_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    register_def(token, input, |n| {
+        Variable::Glue(primitive::TypedVariable {
+            index: primitive::Index::Fixed(n),
+            ..get_skip()
+        })
+    })
+}
+
+fn muskipdef_fn<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<()> {
+    register_def(token, input, |n| {
+        Variable::MuGlue(primitive::TypedVariable {
+            index: primitive::Index::Fixed(n),
+            ..get_muskip()
+        })
+    })
+}
+
+fn toksdef_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    register_def(token, input, |n| {
+        Variable::TokenList(primitive::TypedVariable {
+            index: primitive::Index::Fixed(n),
+            ..get_toks()
+        })
+    })
+}
+
+/// Reads a control sequence, an optional equals sign and a register number, and binds the
+/// control sequence to the variable for the register.
+fn register_def<S: TexState<S>>(
+    token: Token,
+    input: &mut primitive::Input<S>,
+    variable: fn(usize) -> Variable<S>,
+) -> anyhow::Result<()> {
+    let target = def::parse_target(&token, input)?;
+    parse::parse_optional_equals(input)?;
+    let n = parse_register(input)?;
+    let base = input.state_mut().base_mut();
+    base.primitives.set(
+        target,
+        Some(Primitive::Variable(variable(n))),
+        base.global_prefix,
+    );
+    Ok(())
+}
+
+pub fn get_countdef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("countdef", countdef_fn, COUNTDEF_DOC)
+}
+
+pub fn get_dimendef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("dimendef", dimendef_fn, DIMENDEF_DOC)
+}
+
+pub fn get_skipdef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("skipdef", skipdef_fn, SKIPDEF_DOC)
+}
+
+pub fn get_muskipdef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("muskipdef", muskipdef_fn, MUSKIPDEF_DOC)
+}
+
+pub fn get_toksdef<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    prefix::new_assignment("toksdef", toksdef_fn, TOKSDEF_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::dimen::{GlueOrder, UNITY};
+    use crate::tex::primitive::library::the;
+    use crate::tex::primitive::Execution;
+    use crate::tex::print::Buffer;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+    use crate::tex::token::write;
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        testutil::run(testutil::new_state(), input)
+    }
+
+    fn dimen(input: &str) -> Dimen {
+        run(&format!(r"\dimen0={} ", input))
+            .map(|s| s.base().registers.dimen(0))
+            .unwrap()
+    }
+
+    fn glue(input: &str) -> Glue {
+        run(&format!(r"\skip0={} ", input))
+            .map(|s| s.base().registers.skip(0))
+            .unwrap()
+    }
+
+    fn store_the_fn(_: Token, input: &mut primitive::Input<TestState>) -> anyhow::Result<()> {
+        let tokens = the::the(input)?;
+        input
+            .state_mut()
+            .base_mut()
+            .registers
+            .toks
+            .insert(9, Rc::new(tokens));
+        Ok(())
+    }
+
+    /// Returns the text produced by `\the` for the provided quantity.
+    fn the_text(setup: &str, quantity: &str) -> String {
+        let mut s = testutil::new_state();
+        s.set_primitive(
+            "storethe",
+            Primitive::Execution(Execution::Static(primitive::ExecutionStatic::new(
+                "storethe",
+                store_the_fn,
+                "",
+            ))),
+        );
+        let s = testutil::run(s, &format!(r"{}\storethe{}", setup, quantity)).unwrap();
+        write::write_tokens(&s.base().registers.toks(9))
+    }
+
+    #[test]
+    fn count_register() {
+        let s = run(r"\count1=-17 \record\count1\record\count2").unwrap();
+        assert_eq!(s.recorded, vec![-17, 0]);
+    }
+
+    #[test]
+    fn registers_are_local() {
+        let s = run(r"{\count1=5 \global\count2=6 }\record\count1\record\count2").unwrap();
+        assert_eq!(s.recorded, vec![0, 6]);
+    }
+
+    #[test]
+    fn bad_register_code() {
+        assert!(run(r"\count32768=1 ").is_err());
+        assert!(run(r"\count-1=1 ").is_err());
+    }
+
+    #[test]
+    fn parse_dimensions() {
+        for (input, expected) in [
+            ("1pt", UNITY),
+            ("1.5pt", 3 * UNITY / 2),
+            ("-.5pt", -UNITY / 2),
+            ("1,5pt", 3 * UNITY / 2),
+            ("1 pt", UNITY),
+            ("1PT", UNITY),
+            ("12sp", 12),
+            ("1pc", 12 * UNITY),
+            ("1in", 4736286),
+            ("1cm", 1864679),
+            ("1mm", 186467),
+            ("1bp", 65781),
+            ("1dd", 70124),
+            ("1cc", 841489),
+            ("1truept", UNITY),
+            ("0.33333pt", 21845),
+            ("--2pt", 2 * UNITY),
+            ("'10pt", 8 * UNITY),
+            ("\"10pt", 16 * UNITY),
+        ] {
+            assert_eq!(dimen(input), Dimen(expected), "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn parse_dimension_with_internal_quantities() {
+        let s = run(r"\dimen1=2pt \count1=3 \dimen2=\count1pt \dimen3=-1.5\dimen1 \dimen4=-\dimen1 \skip1=4pt plus 1fil \dimen5=\skip1 ").unwrap();
+        let r = &s.base().registers;
+        assert_eq!(r.dimen(2), Dimen::from_pt(3));
+        assert_eq!(r.dimen(3), Dimen::from_pt(-3));
+        assert_eq!(r.dimen(4), Dimen::from_pt(-2));
+        assert_eq!(r.dimen(5), Dimen::from_pt(4));
+    }
+
+    #[test]
+    fn parse_font_dependent_units() {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        let s = testutil::run(
+            s,
+            r"\fontdimen6\font=10pt \fontdimen5\font=4.5pt \dimen1=1.5em \dimen2=-2ex \skip1=1em plus 2ex ",
+        )
+        .unwrap();
+        let r = &s.base().registers;
+        assert_eq!(r.dimen(1), Dimen::from_pt(15));
+        assert_eq!(r.dimen(2), Dimen::from_pt(-9));
+        assert_eq!(r.skip(1).width, Dimen::from_pt(10));
+        assert_eq!(r.skip(1).stretch, Dimen::from_pt(9));
+    }
+
+    #[test]
+    fn font_dependent_units_of_null_font() {
+        assert_eq!(dimen("3em"), Dimen(0));
+        assert_eq!(dimen("3ex"), Dimen(0));
+        assert!(run(r"\dimen0=1trueem ").is_err());
+    }
+
+    #[test]
+    fn dimension_too_large() {
+        assert!(run(r"\dimen0=16384pt ").is_err());
+        assert!(run(r"\dimen0=16383.99999pt ").is_ok());
+    }
+
+    #[test]
+    fn illegal_unit() {
+        assert!(run(r"\dimen0=3 ").is_err());
+        assert!(run(r"\dimen0=3fil ").is_err());
+    }
+
+    #[test]
+    fn parse_glue() {
+        assert_eq!(
+            glue("1pt"),
+            Glue {
+                width: Dimen::from_pt(1),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            glue("1pt plus 2fil minus 3fill"),
+            Glue {
+                width: Dimen::from_pt(1),
+                stretch: Dimen::from_pt(2),
+                stretch_order: GlueOrder::Fil,
+                shrink: Dimen::from_pt(3),
+                shrink_order: GlueOrder::Fill,
+            }
+        );
+        assert_eq!(
+            glue("0pt plus -1 fil l L"),
+            Glue {
+                stretch: Dimen::from_pt(-1),
+                stretch_order: GlueOrder::Filll,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            glue("1pt minus 2pt"),
+            Glue {
+                width: Dimen::from_pt(1),
+                shrink: Dimen::from_pt(2),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn keyword_mismatch_restores_tokens() {
+        let s = run(r"\def\a{\record 7}\skip0=1pt\a").unwrap();
+        assert_eq!(s.recorded, vec![7]);
+        let s = run(r"\skip0=1pt pl\record 8").unwrap();
+        assert_eq!(s.base().registers.skip(0).width, Dimen::from_pt(1));
+        assert_eq!(s.recorded, vec![8]);
+    }
+
+    #[test]
+    fn negated_internal_glue() {
+        let s = run(r"\skip1=1pt plus 2pt minus 3fil \skip2=-\skip1 ").unwrap();
+        assert_eq!(
+            s.base().registers.skip(2).to_string(),
+            "-1.0pt plus -2.0pt minus -3.0fil"
+        );
+    }
+
+    #[test]
+    fn parse_mu_glue() {
+        let s = run(
+            r"\muskip1=3mu \muskip2=1.5mu plus 2fil minus -\muskip1 \count1=2
+              \muskip3=-\count1 mu plus 2\muskip1 \muskip4=-\muskip2 ",
+        )
+        .unwrap();
+        let r = &s.base().registers;
+        assert_eq!(r.mu_skip(1).to_mu_string(), "3.0mu");
+        assert_eq!(
+            r.mu_skip(2).to_mu_string(),
+            "1.5mu plus 2.0fil minus -3.0mu"
+        );
+        assert_eq!(r.mu_skip(3).to_mu_string(), "-2.0mu plus 6.0mu");
+        assert_eq!(
+            r.mu_skip(4).to_mu_string(),
+            "-1.5mu plus -2.0fil minus 3.0mu"
+        );
+    }
+
+    #[test]
+    fn incompatible_glue_units() {
+        for input in [
+            r"\muskip1=3pt ",
+            r"\muskip1=\skip1 ",
+            r"\muskip1=1mu plus \dimen1 ",
+            r"\skip1=\muskip1 ",
+            r"\dimen1=\muskip1 ",
+            r"\count1=\muskip1 ",
+        ] {
+            assert!(run(input).is_err(), "{}", input);
+        }
+        let err = run(r"\skip1=\muskip1 ").err().unwrap().to_string();
+        assert!(err.contains("Incompatible glue units"));
+        let err = run(r"\muskip1=3pt ").err().unwrap().to_string();
+        assert!(err.contains("Illegal unit of measure (mu inserted)"));
+    }
+
+    #[test]
+    fn toks_register() {
+        let s = run(r"\toks1={a\b #} \toks2=\toks1 ").unwrap();
+        assert_eq!(write::write_tokens(&s.base().registers.toks(2)), r"a\b ##");
+    }
+
+    #[test]
+    fn the_int() {
+        assert_eq!(the_text(r"\count3=-42 ", r"\count3"), "-42");
+    }
+
+    #[test]
+    fn the_dimen() {
+        assert_eq!(the_text(r"\dimen3=1.5pt ", r"\dimen3"), "1.5pt");
+        assert_eq!(the_text(r"\dimen3=1in ", r"\dimen3"), "72.26999pt");
+    }
+
+    #[test]
+    fn the_glue() {
+        assert_eq!(
+            the_text(r"\skip3=3pt plus 1fil minus 2pt", r"\skip3"),
+            "3.0pt plus 1.0fil minus 2.0pt"
+        );
+    }
+
+    #[test]
+    fn the_mu_glue() {
+        assert_eq!(
+            the_text(r"\muskip3=4mu plus 2mu minus 4mu", r"\muskip3"),
+            "4.0mu plus 2.0mu minus 4.0mu"
+        );
+    }
+
+    #[test]
+    fn the_toks() {
+        assert_eq!(the_text(r"\toks3={x\y}", r"\toks3"), r"x\y ");
+    }
+
+    #[test]
+    fn the_dimen_round_trip() {
+        let s = run(r"\dimen1=0.1pt \dimen2=\the\dimen1 ").unwrap();
+        assert_eq!(s.base().registers.dimen(2), s.base().registers.dimen(1));
+    }
+
+    #[test]
+    fn countdef_and_friends() {
+        let s = run(
+            r"\countdef\a=3 \dimendef\b=4 \skipdef\c=5 \toksdef\d=6 \muskipdef\e=7 \a=7 \b=1pt \c=2pt plus 1fil \d={x}\e=1mu \record\count3\record\a",
+        )
+        .unwrap();
+        let r = &s.base().registers;
+        assert_eq!(s.recorded, vec![7, 7]);
+        assert_eq!(r.dimen(4), Dimen::from_pt(1));
+        assert_eq!(r.skip(5).to_string(), "2.0pt plus 1.0fil");
+        assert_eq!(r.mu_skip(7).to_mu_string(), "1.0mu");
+        assert_eq!(write::write_tokens(&r.toks(6)), "x");
+    }
+
+    #[test]
+    fn countdef_is_local_unless_global() {
+        let run_after_group = |input: &str| {
+            run(&format!(
+                r"{{\countdef\a=1 \global\countdef\b=2 }}{}",
+                input
+            ))
+        };
+        assert_eq!(
+            run_after_group(r"\b=3 ").unwrap().base().registers.count(2),
+            3
+        );
+        assert!(run_after_group(r"\a=3 ").is_err());
+    }
+
+    /// Runs the register allocation macros of Knuth's `plain.tex`, which use `\countdef` and
+    /// related primitives, `\advance`, `\ifnum` and `\string`.
+    fn run_plain_allocation(input: &str) -> anyhow::Result<(TestState, Buffer)> {
+        let mut s = testutil::new_state();
+        let log = Buffer::new();
+        s.base_mut().printer.open_log(Box::new(log.clone()));
+        let preamble = r"\catcode`\@=11
+\count10=22 \count11=9 \count12=9 \count13=9 \count14=9 \count15=9 \count16=-1 \count17=-1
+\count18=3 \count19=255
+\countdef\insc@unt=19 \countdef\allocationnumber=20 \countdef\m@ne=21 \m@ne=-1
+\def\wlog{\immediate\write\m@ne}
+\chardef\@ne=1 \mathchardef\@cclvi=256
+\outer\def\newcount{\alloc@0\count\countdef\insc@unt}
+\outer\def\newdimen{\alloc@1\dimen\dimendef\insc@unt}
+\outer\def\newskip{\alloc@2\skip\skipdef\insc@unt}
+\outer\def\newtoks{\alloc@5\toks\toksdef\@cclvi}
+\def\alloc@#1#2#3#4#5{\global\advance\count1#1by\@ne
+  \ch@ck#1#4#2% make sure there's still room
+  \allocationnumber=\count1#1
+  \global#3#5=\allocationnumber
+  \wlog{\string#5=\string#2\the\allocationnumber}}
+\def\ch@ck#1#2#3{\ifnum\count1#1<#2%
+  \else\errmessage{No room for a new #3}\fi}
+";
+        let s = testutil::run(s, &format!("{}{}", preamble, input))?;
+        Ok((s, log))
+    }
+
+    #[test]
+    fn plain_allocation() {
+        let (s, log) = run_plain_allocation(
+            r"\newcount\a \newcount\b \newdimen\c \newskip\d \newtoks\e {\newcount\f}
+\b=5 \show\a\show\b\show\c\show\d\show\e\show\f",
+        )
+        .unwrap();
+        let r = &s.base().registers;
+        assert_eq!(r.count(10), 25);
+        assert_eq!(r.count(11), 10);
+        assert_eq!(r.count(12), 10);
+        assert_eq!(r.count(15), 10);
+        assert_eq!(r.count(24), 5);
+        // Spaces between the definitions are typeset, which isn't implemented yet, so only the
+        // lines printed by \show are checked.
+        let shown: Vec<String> = testutil::terminal(&s)
+            .lines()
+            .filter(|line| line.starts_with("> "))
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            shown.join("\n"),
+            "> \\a=\\count23.\n> \\b=\\count24.\n> \\c=\\dimen10.\n> \\d=\\skip10.\n> \\e=\\toks10.\n> \\f=\\count25."
+        );
+        assert!(log.contents().starts_with(
+            "\\a=\\count23\n\\b=\\count24\n\\c=\\dimen10\n\\d=\\skip10\n\\e=\\toks10\n\\f=\\count25\n"
+        ));
+    }
+
+    #[test]
+    fn plain_allocation_no_room() {
+        match run_plain_allocation(r"\count19=23 \newcount\a") {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => assert!(err.to_string().contains("No room for a new \\count")),
+        }
+    }
+
+    #[test]
+    fn the_non_quantity() {
+        assert!(run(r"\the\def").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Primitives that append rules: `\hrule` and `\vrule`.
+//!
+//! A rule is a solid black rectangle. Its dimensions are given by the keywords `width`, `height`
+//! and `depth`, in any order; a dimension that is given more than once takes the last value.
+//! Unspecified dimensions of a horizontal rule are the width of the enclosing box, a height of
+//! 0.4pt and a depth of zero. Unspecified dimensions of a vertical rule are a width of 0.4pt and
+//! the height and depth of the enclosing box.
+//! ```tex
+//! \hrule height 1pt                  % a line across the enclosing vertical box
+//! \vrule width 2pt height 5pt depth 0pt
+//! ```
+//!
+//! `\hrule` is a vertical command, so it ends a paragraph, and `\vrule` is a horizontal command,
+//! so it begins one.
+
+use crate::tex::dimen::Dimen;
+use crate::tex::driver;
+use crate::tex::error;
+use crate::tex::nest::{Mode, IGNORE_DEPTH};
+use crate::tex::node::{Node, Rule};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::state::TexState;
+use crate::tex::token::token::Token;
+
+static HRULE_DOC: &str = "Append a horizontal rule to the current vertical list";
+static VRULE_DOC: &str = "Append a vertical rule to the current horizontal list";
+
+/// The default thickness of a rule, 0.4pt.
+const DEFAULT_RULE: Dimen = Dimen(26214);
+
+fn hrule_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    if input.state().base().nest.mode() == Mode::RestrictedHorizontal {
+        return Err(error::new_token_error(
+            token,
+            "You can't use `\\hrule' here except with leaders".to_string(),
+            vec!["to put a horizontal rule in an hbox, use \\leaders or \\hrulefill".to_string()],
+        ));
+    }
+    if driver::head_for_vertical_mode(token, input, "\\hrule")? {
+        return Ok(());
+    }
+    let rule = parse_rule_spec(
+        input,
+        Rule {
+            width: None,
+            height: Some(DEFAULT_RULE),
+            depth: Some(Dimen(0)),
+        },
+    )?;
+    let nest = &mut input.state_mut().base_mut().nest;
+    nest.append(Node::Rule(rule));
+    // As in TeX, there is no interline glue after a rule.
+    nest.current_mut().prev_depth = IGNORE_DEPTH;
+    Ok(())
+}
+
+fn vrule_fn<S: TexState<S>>(token: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    if driver::leave_vertical_mode(token, input) {
+        return Ok(());
+    }
+    let rule = parse_rule_spec(
+        input,
+        Rule {
+            width: Some(DEFAULT_RULE),
+            height: None,
+            depth: None,
+        },
+    )?;
+    let nest = &mut input.state_mut().base_mut().nest;
+    nest.append(Node::Rule(rule));
+    if nest.mode().is_horizontal() {
+        nest.current_mut().space_factor = 1000;
+    }
+    Ok(())
+}
+
+/// Parses the dimensions of a rule, starting from the provided defaults. This is
+/// `scan_rule_spec` in TeX.
+pub fn parse_rule_spec<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    mut rule: Rule,
+) -> anyhow::Result<Rule> {
+    loop {
+        if parse::parse_keyword(input, "width")? {
+            rule.width = Some(parse::parse_dimen(input)?);
+        } else if parse::parse_keyword(input, "height")? {
+            rule.height = Some(parse::parse_dimen(input)?);
+        } else if parse::parse_keyword(input, "depth")? {
+            rule.depth = Some(parse::parse_dimen(input)?);
+        } else {
+            return Ok(rule);
+        }
+    }
+}
+
+pub fn get_hrule<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("hrule", hrule_fn, HRULE_DOC)
+}
+
+pub fn get_vrule<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("vrule", vrule_fn, VRULE_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    fn run(input: &str) -> anyhow::Result<TestState> {
+        let mut s = testutil::new_state();
+        testutil::select_test_font(&mut s);
+        testutil::set_single_line_paragraphs(&mut s);
+        testutil::run(s, input)
+    }
+
+    /// Returns the list of box register 1 after running the input.
+    fn box_list(input: &str) -> Vec<Node> {
+        let s = run(input).unwrap();
+        s.base()
+            .registers
+            .box_register(1)
+            .unwrap()
+            .as_box()
+            .unwrap()
+            .list
+            .clone()
+    }
+
+    fn error(input: &str) -> String {
+        match run(input) {
+            Ok(_) => panic!("no error for {}", input),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn rule(width: Option<i32>, height: Option<i32>, depth: Option<i32>) -> Node {
+        Node::Rule(Rule {
+            width: width.map(Dimen::from_pt),
+            height: height.map(Dimen::from_pt),
+            depth: depth.map(Dimen::from_pt),
+        })
+    }
+
+    #[test]
+    fn default_dimensions() {
+        assert_eq!(
+            box_list(r"\setbox1\vbox{\hrule}"),
+            vec![Node::Rule(Rule {
+                width: None,
+                height: Some(DEFAULT_RULE),
+                depth: Some(Dimen(0)),
+            })]
+        );
+        assert_eq!(
+            box_list(r"\setbox1\hbox{\vrule}"),
+            vec![Node::Rule(Rule {
+                width: Some(DEFAULT_RULE),
+                height: None,
+                depth: None,
+            })]
+        );
+    }
+
+    #[test]
+    fn keywords_in_any_order() {
+        assert_eq!(
+            box_list(r"\setbox1\hbox{\vrule depth 1pt height 2pt width 3pt depth 4pt}"),
+            vec![rule(Some(3), Some(2), Some(4))]
+        );
+        assert_eq!(
+            box_list(r"\setbox1\vbox{\hrule WIDTH 5pt\hrule height 0pt}"),
+            vec![
+                Node::Rule(Rule {
+                    width: Some(Dimen::from_pt(5)),
+                    height: Some(DEFAULT_RULE),
+                    depth: Some(Dimen(0)),
+                }),
+                rule(None, Some(0), Some(0))
+            ]
+        );
+    }
+
+    #[test]
+    fn no_interline_glue_after_hrule() {
+        let list = box_list(r"\setbox1\vbox{\hrule\hbox{}}");
+        assert_eq!(list.len(), 2);
+        assert!(matches!(list[1], Node::HList(_)));
+    }
+
+    #[test]
+    fn hrule_ends_a_paragraph() {
+        let list = box_list(r"\setbox1\vbox{a\hrule}");
+        assert_eq!(list.len(), 2);
+        assert!(matches!(list[0], Node::HList(_)));
+        assert!(matches!(list[1], Node::Rule(_)));
+    }
+
+    #[test]
+    fn vrule_begins_a_paragraph() {
+        let list = box_list(r"\setbox1\vbox{\vrule}");
+        assert_eq!(list.len(), 1);
+        let line = list[0].as_box().unwrap();
+        assert!(line.list.iter().any(|node| matches!(node, Node::Rule(_))));
+    }
+
+    #[test]
+    fn rules_in_the_wrong_mode() {
+        assert!(
+            error(r"\hbox{\hrule}").contains(r"You can't use `\hrule' here except with leaders")
+        );
+        assert!(error(r"$\hrule$").contains("Missing $ inserted"));
+    }
+}
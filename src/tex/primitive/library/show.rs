@@ -0,0 +1,366 @@
+//! Primitives for inspecting meanings and token lists: `\show`, `\meaning` and e-TeX's
+//! `\showtokens`.
+//!
+//! The meaning of a control sequence is described in the same way as in TeX. Macros are
+//! described by their parameter and replacement texts, built-in primitives by their names, and
+//! implicit characters by their catcodes:
+//! ```tex
+//! \def\a#1{(#1)}\show\a     % > \a=macro:#1->(#1).
+//! \let\b=\def\show\b        % > \b=\def.
+//! \let\c={\show\c           % > \c=begin-group character {.
+//! ```
+//! `\meaning` inserts the description into the input as character tokens instead of printing
+//! it.
+
+use crate::tex::macros::{Macro, Replacement};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library::the;
+use crate::tex::primitive::{ConstantKind, Primitive};
+use crate::tex::print::Selector;
+use crate::tex::state::{BaseState, TexState};
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::stream;
+use crate::tex::token::token::{Token, Value};
+use crate::tex::token::write;
+
+static SHOW_DOC: &str = "Show the meaning of a token";
+static MEANING_DOC: &str = "Insert the meaning of a token into the input";
+static SHOWTOKENS_DOC: &str = "Show a token list";
+
+fn show_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let text = show(input)?;
+    print_show(input.state_mut().base_mut(), &text);
+    Ok(())
+}
+
+fn meaning_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    let token = next_unexpanded(input)?;
+    let text = meaning(input.state().base(), &token.value);
+    Ok(stream::VecStream::new(the::string_to_tokens(&text)))
+}
+
+fn showtokens_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let tokens = parse::parse_balanced_text(input)?;
+    let mut text = String::new();
+    input
+        .state()
+        .base()
+        .write_style()
+        .write_tokens(&mut text, &tokens);
+    print_show(input.state_mut().base_mut(), &text);
+    Ok(())
+}
+
+/// Reads a token from the input without expansion and returns the text that `\show` prints
+/// for it, without the leading `> ` and the trailing period.
+pub fn show<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<String> {
+    let token = next_unexpanded(input)?;
+    let base = input.state().base();
+    let style = base.write_style();
+    let mut text = String::new();
+    match &token.value {
+        Value::ControlSequence(_, name) => {
+            style.write_control_sequence_name(&mut text, name);
+            text.push('=');
+        }
+        Value::Character(c, CatCode::Active) => {
+            text.push(*c);
+            text.push('=');
+        }
+        Value::Character(..) => {}
+    }
+    text.push_str(&meaning(base, &token.value));
+    Ok(text)
+}
+
+/// Prints text in the format used by `\show`, `\showthe` and `\showtokens`.
+pub fn print_show<S>(base: &mut BaseState<S>, text: &str) {
+    base.print_nl(Selector::TerminalAndLog, &format!("> {}.", text));
+}
+
+/// Returns the description of the meaning of a token value, as produced by `\meaning`.
+pub fn meaning<S>(base: &BaseState<S>, value: &Value) -> String {
+    let style = base.write_style();
+    let mut text = String::new();
+    match base.meaning(value) {
+        None => text.push_str("undefined"),
+        Some(Primitive::Character(c, cat_code)) => {
+            text.push_str(describe_cat_code(cat_code));
+            text.push(c);
+        }
+        Some(Primitive::Macro(m)) => write_macro(&style, &mut text, &m),
+        // As in TeX, constants are described by the primitive that inserts them, with the
+        // value in hexadecimal.
+        Some(Primitive::Constant(kind, n)) => {
+            let name = match kind {
+                ConstantKind::Char => "char",
+                ConstantKind::MathChar => "mathchar",
+            };
+            style.write_control_sequence_name(&mut text, name);
+            text.push_str(&format!("\"{:X}", n));
+        }
+        Some(Primitive::Font(id)) => {
+            text.push_str("select font ");
+            text.push_str(&base.fonts.get(id).name_and_size());
+        }
+        Some(p) => match base.primitives.primitive_name(&p) {
+            Some(name) => style.write_control_sequence_name(&mut text, name),
+            // A control sequence defined using \countdef and related primitives is described by
+            // the register it refers to, like \count3.
+            None => match base.primitives.indexed_variable_name(&p) {
+                Some((name, index)) => {
+                    style.write_control_sequence_name(&mut text, name);
+                    text.push_str(&index.to_string());
+                }
+                None => text.push_str("[unknown primitive]"),
+            },
+        },
+    }
+    text
+}
+
+fn describe_cat_code(cat_code: CatCode) -> &'static str {
+    match cat_code {
+        CatCode::BeginGroup => "begin-group character ",
+        CatCode::EndGroup => "end-group character ",
+        CatCode::MathShift => "math shift character ",
+        CatCode::AlignmentTab => "alignment tab character ",
+        CatCode::Parameter => "macro parameter character ",
+        CatCode::Superscript => "superscript character ",
+        CatCode::Subscript => "subscript character ",
+        CatCode::Space => "blank space ",
+        CatCode::Letter => "the letter ",
+        CatCode::Other => "the character ",
+        // Active characters always have a meaning of their own, so this is never reached.
+        CatCode::Active => "active character ",
+    }
+}
+
+/// Writes the description of a macro, like `macro:#1->(#1)` or `\long macro:#1->(#1)`.
+fn write_macro(style: &write::Style, text: &mut String, m: &Macro) {
+    if m.is_long() {
+        style.write_control_sequence_name(text, "long");
+    }
+    if m.is_outer() {
+        style.write_control_sequence_name(text, "outer");
+    }
+    if m.is_long() || m.is_outer() {
+        text.push(' ');
+    }
+    text.push_str("macro:");
+    style.write_tokens(text, m.prefix());
+    for (i, delimiter) in m.parameters().iter().enumerate() {
+        text.push('#');
+        text.push_str(&(i + 1).to_string());
+        style.write_tokens(text, delimiter);
+    }
+    text.push_str("->");
+    for replacement in m.replacement() {
+        match replacement {
+            Replacement::Token(token) => style.write_token(text, token),
+            Replacement::Parameter(i) => {
+                text.push('#');
+                text.push_str(&(i + 1).to_string());
+            }
+        }
+    }
+}
+
+fn next_unexpanded<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Token> {
+    match input.unexpanded_stream().next()? {
+        None => Err(anyhow::anyhow!(
+            "Unexpected end of input: expected a token whose meaning is to be shown"
+        )),
+        Some(token) => Ok(token),
+    }
+}
+
+pub fn get_show<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("show", show_fn, SHOW_DOC)
+}
+
+pub fn get_meaning<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("meaning", meaning_fn, MEANING_DOC)
+}
+
+pub fn get_showtokens<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("showtokens", showtokens_fn, SHOWTOKENS_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::primitive::Execution;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+    use std::rc::Rc;
+
+    fn store_show_fn(_: Token, input: &mut primitive::Input<TestState>) -> anyhow::Result<()> {
+        let text = show(input)?;
+        store(input, the::string_to_tokens(&text));
+        Ok(())
+    }
+
+    fn store_meaning_fn(_: Token, input: &mut primitive::Input<TestState>) -> anyhow::Result<()> {
+        input.expand_next()?;
+        let mut tokens = Vec::new();
+        while let Some(token) = input.unexpanded_stream().next()? {
+            if token.value == Token::new_control_sequence("end").value {
+                break;
+            }
+            tokens.push(token);
+        }
+        store(input, tokens);
+        Ok(())
+    }
+
+    fn nothing_fn(_: Token, _: &mut primitive::Input<TestState>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn store(input: &mut primitive::Input<TestState>, tokens: Vec<Token>) {
+        input
+            .state_mut()
+            .base_mut()
+            .registers
+            .toks
+            .insert(0, Rc::new(tokens));
+    }
+
+    fn run(input: &str) -> String {
+        let mut s = testutil::new_state();
+        s.set_primitive(
+            "storeshow",
+            Primitive::Execution(Execution::Static(primitive::ExecutionStatic::new(
+                "storeshow",
+                store_show_fn,
+                "",
+            ))),
+        );
+        s.set_primitive(
+            "storemeaning",
+            Primitive::Execution(Execution::Static(primitive::ExecutionStatic::new(
+                "storemeaning",
+                store_meaning_fn,
+                "",
+            ))),
+        );
+        for (name, p) in [
+            (
+                "nothingA",
+                primitive::ExecutionStatic::new("nothingA", nothing_fn, ""),
+            ),
+            (
+                "nothingB",
+                primitive::ExecutionStatic::new("nothingB", nothing_fn, ""),
+            ),
+        ] {
+            s.set_primitive(name, Primitive::Execution(Execution::Static(p)));
+        }
+        let s = testutil::run(s, input).unwrap();
+        write::write_tokens(&s.base().registers.toks(0))
+    }
+
+    #[test]
+    fn show_macro() {
+        assert_eq!(
+            run(r"\def\a#1x#2{(#2,##,#1)\b}\storeshow\a"),
+            r"\a=macro:#1x#2->(#2,##,#1)\b "
+        );
+    }
+
+    #[test]
+    fn show_macro_with_prefix() {
+        assert_eq!(run(r"\def\a.#1{}\storeshow\a"), r"\a=macro:.#1->");
+    }
+
+    #[test]
+    fn show_macro_with_brace_delimited_parameter() {
+        assert_eq!(run(r"\def\a#1#{x}\storeshow\a"), r"\a=macro:#1{->x{");
+    }
+
+    #[test]
+    fn show_long_and_outer_macros() {
+        assert_eq!(
+            run(r"\long\outer\def\a#1{x}\storeshow\a"),
+            r"\a=\long\outer macro:#1->x"
+        );
+        assert_eq!(
+            run(r"\long\def\a{}\storemeaning\meaning\a\end"),
+            r"\long macro:->"
+        );
+    }
+
+    #[test]
+    fn show_primitive() {
+        assert_eq!(run(r"\storeshow\def"), r"\def=\def");
+        assert_eq!(run(r"\let\a=\def\storeshow\a"), r"\a=\def");
+        assert_eq!(run(r"\let\a=\meaning\storeshow\a"), r"\a=\meaning");
+    }
+
+    #[test]
+    fn show_primitives_with_same_body() {
+        assert_eq!(run(r"\storeshow\outer"), r"\outer=\outer");
+        assert_eq!(run(r"\let\a=\outer\storeshow\a"), r"\a=\outer");
+        assert_eq!(run(r"\storeshow\nothingB"), r"\nothingB=\nothingB");
+    }
+
+    #[test]
+    fn show_undefined() {
+        assert_eq!(run(r"\storeshow\undefined"), r"\undefined=undefined");
+    }
+
+    #[test]
+    fn show_characters() {
+        assert_eq!(run(r"\storeshow a"), "the letter a");
+        assert_eq!(run(r"\storeshow 1"), "the character 1");
+        assert_eq!(run(r"\let\a={\storeshow\a"), r"\a=begin-group character {");
+        assert_eq!(
+            run(r"\let\a=#\storeshow\a"),
+            r"\a=macro parameter character #"
+        );
+    }
+
+    #[test]
+    fn show_single_character_control_sequences() {
+        assert_eq!(run(r"\def\a{\%\x}\storeshow\a"), r"\a=macro:->\%\x ");
+        assert_eq!(run(r"\storeshow\%"), r"\%=undefined");
+    }
+
+    #[test]
+    fn meaning() {
+        assert_eq!(
+            run(r"\def\a#1{#1}\storemeaning\meaning\a\end"),
+            "macro:#1->#1"
+        );
+        assert_eq!(run(r"\storemeaning\meaning\def\end"), r"\def");
+        assert_eq!(run(r"\storemeaning\meaning b\end"), "the letter b");
+    }
+
+    #[test]
+    fn meaning_produces_other_and_space_characters() {
+        let s = testutil::new_state();
+        let mut input = primitive::Input::new(s);
+        input
+            .state_mut()
+            .base_mut()
+            .input_module
+            .open_string(r"\meaning a");
+        input.expand_next().unwrap();
+        let mut cat_codes = Vec::new();
+        while let Some(token) = input.unexpanded_stream().next().unwrap() {
+            if let Value::Character(_, cat_code) = token.value {
+                cat_codes.push(cat_code);
+            }
+        }
+        assert_eq!(cat_codes[3], CatCode::Space);
+        assert!(cat_codes
+            .iter()
+            .enumerate()
+            .all(|(i, c)| i == 3 || i == 10 || *c == CatCode::Other));
+    }
+}
@@ -9,7 +9,11 @@ use crate::tex::token::token;
 struct James {}
 
 impl<State> primitive::ExpansionGeneric<State> for James {
-    fn call(&self, _: &mut primitive::Input<State>) -> anyhow::Result<stream::VecStream> {
+    fn call(
+        &self,
+        _: token::Token,
+        _: &mut primitive::Input<State>,
+    ) -> anyhow::Result<stream::VecStream> {
         Ok(stream::VecStream::new(vec![
             token::Token::new_letter('T'),
             token::Token::new_letter('e'),
@@ -22,10 +26,13 @@ impl<State> primitive::ExpansionGeneric<State> for James {
 }
 
 pub fn get_texide<State>() -> impl primitive::ExpansionGeneric<State> {
-    return James {};
+    James {}
 }
 
-pub fn texide_command<State>(_: &mut primitive::Input<State>) -> anyhow::Result<stream::VecStream> {
+pub fn texide_command<State>(
+    _: token::Token,
+    _: &mut primitive::Input<State>,
+) -> anyhow::Result<stream::VecStream> {
     Ok(stream::VecStream::new(vec![
         token::Token::new_letter('T'),
         token::Token::new_letter('e'),
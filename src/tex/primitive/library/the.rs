@@ -0,0 +1,127 @@
+//! Primitives for inspecting internal quantities: `\the`, which converts an internal quantity
+//! into tokens, and `\showthe`, which prints the value of an internal quantity.
+//!
+//! Integers, dimensions and glue are converted into character tokens using TeX's printing
+//! conventions, so `\the\dimen0` may produce `1.5pt` and `\the\skip0` may produce
+//! `3.0pt plus 1.0fil`. These characters have catcode other, except for spaces which have
+//! catcode space. Token lists are inserted as is.
+
+use crate::tex::error;
+use crate::tex::primitive;
+use crate::tex::primitive::library::{font, paragraph, show};
+use crate::tex::primitive::{Expansion, Primitive, Variable};
+use crate::tex::state::TexState;
+use crate::tex::token::stream;
+use crate::tex::token::stream::Stream;
+use crate::tex::token::token::Token;
+
+static THE_DOC: &str = "Insert the value of an internal quantity into the input";
+static SHOWTHE_DOC: &str = "Show the value of an internal quantity";
+
+fn the_fn<S: TexState<S>>(
+    _: Token,
+    input: &mut primitive::Input<S>,
+) -> anyhow::Result<stream::VecStream> {
+    Ok(stream::VecStream::new(the(input)?))
+}
+
+fn showthe_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let tokens = the(input)?;
+    let mut text = String::new();
+    input
+        .state()
+        .base()
+        .write_style()
+        .write_tokens(&mut text, &tokens);
+    show::print_show(input.state_mut().base_mut(), &text);
+    Ok(())
+}
+
+/// Reads an internal quantity from the input and returns its value as tokens.
+pub fn the<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Vec<Token>> {
+    let token = match input.next()? {
+        None => {
+            return Err(anyhow::anyhow!(
+                "Unexpected end of input: expected an internal quantity"
+            ))
+        }
+        Some(token) => token,
+    };
+    let base = input.state().base();
+    let variable = match base.primitives.get(&token.value) {
+        Some(Primitive::Variable(variable)) => *variable,
+        Some(Primitive::Constant(_, n)) => return Ok(int_to_tokens(*n)),
+        // A font is converted into the control sequence that selects it.
+        Some(Primitive::Font(id)) => return Ok(vec![font::identifier_token(base, *id)]),
+        Some(p) if font::is_font_primitive(p) => {
+            return Ok(vec![font::identifier_token(base, base.fonts.current())])
+        }
+        Some(p) if paragraph::is_parshape_primitive(p) => {
+            return Ok(int_to_tokens(base.parameters.par_shape().len() as i32))
+        }
+        Some(p) if font::family_size(p).is_some() => {
+            // The font of a math family is converted like a font, after reading the family.
+            input.push(stream::VecStream::new(vec![token]));
+            let id = font::parse_font(input)?;
+            return Ok(vec![font::identifier_token(input.state().base(), id)]);
+        }
+        _ => {
+            return Err(error::new_token_error(
+                token,
+                "You can't use this after \\the".to_string(),
+                vec!["\\the must be followed by an internal quantity".to_string()],
+            ))
+        }
+    };
+    Ok(match variable {
+        Variable::Int(v) => {
+            let index = v.index(input)?;
+            int_to_tokens(v.get(input.state(), index))
+        }
+        Variable::Dimen(v) => {
+            let index = v.index(input)?;
+            string_to_tokens(&v.get(input.state(), index).to_string())
+        }
+        Variable::Glue(v) => {
+            let index = v.index(input)?;
+            string_to_tokens(&v.get(input.state(), index).to_string())
+        }
+        Variable::MuGlue(v) => {
+            let index = v.index(input)?;
+            string_to_tokens(&v.get(input.state(), index).to_mu_string())
+        }
+        Variable::TokenList(v) => {
+            let index = v.index(input)?;
+            v.get(input.state(), index).as_ref().clone()
+        }
+    })
+}
+
+/// Returns true if the primitive is `\the`.
+pub fn is_the_primitive<S: TexState<S>>(p: &Primitive<S>) -> bool {
+    p.same_meaning(&Primitive::Expansion(Expansion::Static(get_the())))
+}
+
+/// Converts an integer into character tokens, as TeX does when printing integers.
+pub fn int_to_tokens(n: i32) -> Vec<Token> {
+    string_to_tokens(&n.to_string())
+}
+
+/// Converts a string into character tokens. Spaces have catcode space and all other characters
+/// have catcode other.
+pub fn string_to_tokens(s: &str) -> Vec<Token> {
+    s.chars()
+        .map(|c| match c {
+            ' ' => Token::new_space(c),
+            _ => Token::new_other(c),
+        })
+        .collect()
+}
+
+pub fn get_the<S: TexState<S>>() -> primitive::ExpansionStatic<S> {
+    primitive::ExpansionStatic::new("the", the_fn, THE_DOC)
+}
+
+pub fn get_showthe<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("showthe", showthe_fn, SHOWTHE_DOC)
+}
@@ -0,0 +1,269 @@
+//! Primitives for the output streams: `\openout`, `\write`, `\closeout` and `\immediate`.
+//!
+//! By default these primitives create whatsits that are performed when the page is shipped
+//! out; see the [files](crate::tex::files) module. Preceded by `\immediate` they are performed
+//! straight away:
+//! ```tex
+//! \immediate\openout3=notes      % opens notes.tex
+//! \immediate\write3{\the\count1} % writes the current value of \count1
+//! \immediate\closeout3
+//! ```
+
+use crate::tex::files::{Destination, Whatsit};
+use crate::tex::filesystem;
+use crate::tex::node::Node;
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::{Execution, Primitive};
+use crate::tex::print::Selector;
+use crate::tex::state::TexState;
+use crate::tex::token::stream;
+use crate::tex::token::stream::Stream;
+use crate::tex::token::token::Token;
+use std::rc::Rc;
+
+static OPENOUT_DOC: &str = "Open a file for writing when the page is shipped out";
+static WRITE_DOC: &str = "Write a token list to a stream when the page is shipped out";
+static CLOSEOUT_DOC: &str = "Close a file when the page is shipped out";
+static IMMEDIATE_DOC: &str = "Perform the following \\openout, \\write or \\closeout immediately";
+
+fn openout_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let whatsit = parse_openout(input)?;
+    input
+        .state_mut()
+        .base_mut()
+        .nest
+        .append(Node::Whatsit(whatsit));
+    Ok(())
+}
+
+fn write_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let whatsit = parse_write(input)?;
+    input
+        .state_mut()
+        .base_mut()
+        .nest
+        .append(Node::Whatsit(whatsit));
+    Ok(())
+}
+
+fn closeout_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let whatsit = parse_closeout(input)?;
+    input
+        .state_mut()
+        .base_mut()
+        .nest
+        .append(Node::Whatsit(whatsit));
+    Ok(())
+}
+
+fn immediate_fn<S: TexState<S>>(_: Token, input: &mut primitive::Input<S>) -> anyhow::Result<()> {
+    let token = match input.next()? {
+        None => return Ok(()),
+        Some(token) => token,
+    };
+    let meaning = input.state().base().meaning(&token.value);
+    let is = |get: fn() -> primitive::ExecutionStatic<S>| match &meaning {
+        None => false,
+        Some(p) => p.same_meaning(&Primitive::Execution(Execution::Static(get()))),
+    };
+    let whatsit = if is(get_openout) {
+        parse_openout(input)?
+    } else if is(get_write) {
+        parse_write(input)?
+    } else if is(get_closeout) {
+        parse_closeout(input)?
+    } else {
+        // As in TeX, \immediate has no effect on other commands.
+        input.push(stream::VecStream::new(vec![token]));
+        return Ok(());
+    };
+    perform(input, whatsit)
+}
+
+fn parse_openout<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Whatsit> {
+    let stream = parse::parse_number(input)?;
+    parse::parse_optional_equals(input)?;
+    let file_name = parse::parse_file_name(input)?;
+    Ok(Whatsit::OpenOut { stream, file_name })
+}
+
+fn parse_write<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Whatsit> {
+    let stream = parse::parse_number(input)?;
+    let tokens = parse::parse_balanced_text(input)?;
+    Ok(Whatsit::Write {
+        stream,
+        tokens: Rc::new(tokens),
+    })
+}
+
+fn parse_closeout<S: TexState<S>>(input: &mut primitive::Input<S>) -> anyhow::Result<Whatsit> {
+    let stream = parse::parse_number(input)?;
+    Ok(Whatsit::CloseOut { stream })
+}
+
+/// Performs the deferred whatsits in a list, including those inside boxes, in the order they
+/// appear. This happens when the page containing the list is shipped out.
+pub fn ship_out_whatsits<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    list: &[Node],
+) -> anyhow::Result<()> {
+    for node in list {
+        match node {
+            Node::Whatsit(whatsit) => perform(input, whatsit.clone())?,
+            Node::HList(b) | Node::VList(b) => ship_out_whatsits(input, &b.list)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Performs an `\openout`, `\write` or `\closeout`. Language whatsits do nothing.
+pub fn perform<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    whatsit: Whatsit,
+) -> anyhow::Result<()> {
+    match whatsit {
+        Whatsit::OpenOut { stream, file_name } => {
+            let file_name = filesystem::with_default_extension(file_name, "tex");
+            let base = input.state_mut().base_mut();
+            let writer = base
+                .file_system
+                .create(&file_name)
+                .map_err(|err| anyhow::anyhow!("I can't write on file `{}': {}", file_name, err))?;
+            base.output_files.open(stream, writer)?;
+        }
+        Whatsit::Write { stream, tokens } => write_out(input, stream, &tokens)?,
+        Whatsit::CloseOut { stream } => input.state_mut().base_mut().output_files.close(stream)?,
+        Whatsit::Language(_) => {}
+    }
+    Ok(())
+}
+
+/// Expands the tokens and writes the result to the stream.
+fn write_out<S: TexState<S>>(
+    input: &mut primitive::Input<S>,
+    stream: i32,
+    tokens: &[Token],
+) -> anyhow::Result<()> {
+    let mut braced = Vec::with_capacity(tokens.len() + 2);
+    braced.push(Token::new_begin_group('{'));
+    braced.extend(tokens.iter().cloned());
+    braced.push(Token::new_end_group('}'));
+    input.push(stream::VecStream::new(braced));
+    let expanded = parse::parse_expanded_balanced_text(input)?;
+    let base = input.state_mut().base_mut();
+    let mut text = String::new();
+    base.write_style().write_tokens(&mut text, &expanded);
+    let selector = match base.output_files.destination(stream) {
+        Destination::File(i) => {
+            let new_line_char = base.parameters.new_line_char();
+            base.output_files.write_line(i, &text, new_line_char)?;
+            return Ok(());
+        }
+        Destination::Log => Selector::Log,
+        Destination::TerminalAndLog => Selector::TerminalAndLog,
+    };
+    base.print_nl(selector, &text);
+    base.printer.print_ln(selector);
+    Ok(())
+}
+
+pub fn get_openout<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("openout", openout_fn, OPENOUT_DOC)
+}
+
+pub fn get_write<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("write", write_fn, WRITE_DOC)
+}
+
+pub fn get_closeout<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("closeout", closeout_fn, CLOSEOUT_DOC)
+}
+
+pub fn get_immediate<S: TexState<S>>() -> primitive::ExecutionStatic<S> {
+    primitive::ExecutionStatic::new("immediate", immediate_fn, IMMEDIATE_DOC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::filesystem::InMemoryFileSystem;
+    use crate::tex::print::Buffer;
+    use crate::tex::testutil;
+    use crate::tex::testutil::TestState;
+
+    struct Output {
+        files: InMemoryFileSystem,
+        log: Buffer,
+        state: TestState,
+    }
+
+    fn run(input: &str) -> Output {
+        let mut s = testutil::new_state();
+        let files = InMemoryFileSystem::new();
+        s.base_mut().file_system = Box::new(files.clone());
+        let log = Buffer::new();
+        s.base_mut().printer.open_log(Box::new(log.clone()));
+        let state = testutil::run(s, input).unwrap();
+        Output { files, log, state }
+    }
+
+    #[test]
+    fn immediate_write_to_file() {
+        let output = run(
+            r"\def\a{b}\immediate\openout3=out \immediate\write3{a\a c}\immediate\write3{\the\count0}\immediate\closeout3 \immediate\write3{d}",
+        );
+        assert_eq!(
+            output.files.contents("out.tex"),
+            Some("abc\n0\n".to_string())
+        );
+        assert_eq!(testutil::terminal(&output.state), "d\n");
+    }
+
+    #[test]
+    fn file_name_with_extension() {
+        let output = run(r"\immediate\openout0 = dir/out.aux\immediate\write0{x}");
+        assert_eq!(
+            output.files.contents("dir/out.aux"),
+            Some("x\n".to_string())
+        );
+    }
+
+    #[test]
+    fn write_to_terminal_and_log() {
+        let output = run(r"\immediate\write16{a}\immediate\write-1{b}\immediate\write5{c}");
+        assert_eq!(testutil::terminal(&output.state), "a\nc\n");
+        assert_eq!(output.log.contents(), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn write_control_sequences() {
+        let output = run(r"\immediate\write16{\count\undefined}");
+        assert_eq!(testutil::terminal(&output.state), "\\count \\undefined \n");
+    }
+
+    #[test]
+    fn deferred_write_is_expanded_at_shipout() {
+        let output = run(
+            r"\count1=1 \shipout\hbox{\openout1=out \count1=2 \write1{\the\count1}\closeout1 \write1{lost}}",
+        );
+        // The assignment inside the box is undone when the box ends, before it is shipped out.
+        assert_eq!(output.files.contents("out.tex"), Some("1\n".to_string()));
+        assert_eq!(testutil::terminal(&output.state), "[0.1\nlost\n]");
+        assert!(output.state.base().nest.current().list.is_empty());
+    }
+
+    #[test]
+    fn deferred_commands_wait_for_shipout() {
+        let output = run(r"\openout1=out \write1{a}");
+        assert_eq!(output.files.contents("out.tex"), None);
+        assert_eq!(output.state.base().nest.current().list.len(), 2);
+    }
+
+    #[test]
+    fn immediate_other_command() {
+        let output = run(r"\immediate\count1=4 \immediate\write16{\the\count1}");
+        assert_eq!(testutil::terminal(&output.state), "4\n");
+    }
+}
@@ -1,8 +1,17 @@
 //! Texide primitives API and primitives library.
 
+use crate::datastructures::scopedmap::ScopedMap;
+use crate::tex::dimen::{Dimen, Glue};
+use crate::tex::error;
+use crate::tex::macros;
+use crate::tex::node::FontId;
+use crate::tex::parse;
 use crate::tex::state;
+use crate::tex::token::catcode::CatCode;
 use crate::tex::token::stream;
+use crate::tex::token::token;
 
+use std::collections::HashMap;
 use std::rc;
 
 pub mod library;
@@ -17,15 +26,42 @@ pub use driver::ExpandedStream as Input;
 // TODO: default clone implementation does not seem to work
 #[derive(Copy, Clone)]
 pub struct ExpansionStatic<S> {
-    call_fn: fn(input: &mut Input<S>) -> anyhow::Result<stream::VecStream>,
+    /// The name of the primitive in TeX, which identifies it: two static primitives have the
+    /// same meaning if and only if they have the same name.
+    name: &'static str,
+    call_fn: fn(token: token::Token, input: &mut Input<S>) -> anyhow::Result<stream::VecStream>,
     docs: &'static str,
     id: Option<TypeId>,
 }
 
 impl<S> ExpansionStatic<S> {
+    /// Returns a new static expansion primitive with the provided name, call function and docs.
+    pub fn new(
+        name: &'static str,
+        call_fn: fn(token: token::Token, input: &mut Input<S>) -> anyhow::Result<stream::VecStream>,
+        docs: &'static str,
+    ) -> ExpansionStatic<S> {
+        ExpansionStatic {
+            name,
+            call_fn,
+            docs,
+            id: None,
+        }
+    }
+
+    /// Returns the primitive with the provided id, which places it in a class of primitives
+    /// that other primitives look for, like the conditionals `\else` and `\fi`.
+    pub fn with_id(self, id: TypeId) -> ExpansionStatic<S> {
+        ExpansionStatic {
+            id: Some(id),
+            ..self
+        }
+    }
+
     // TODO: why doesn't clone work
     pub fn duplicate(&self) -> ExpansionStatic<S> {
         ExpansionStatic {
+            name: self.name,
             call_fn: self.call_fn,
             docs: self.docs,
             id: self.id,
@@ -34,8 +70,8 @@ impl<S> ExpansionStatic<S> {
 }
 
 impl<S: state::TexState<S>> ExpansionGeneric<S> for ExpansionStatic<S> {
-    fn call(&self, input: &mut Input<S>) -> anyhow::Result<stream::VecStream> {
-        (self.call_fn)(input)
+    fn call(&self, token: token::Token, input: &mut Input<S>) -> anyhow::Result<stream::VecStream> {
+        (self.call_fn)(token, input)
     }
 
     fn doc(&self) -> &str {
@@ -43,12 +79,12 @@ impl<S: state::TexState<S>> ExpansionGeneric<S> for ExpansionStatic<S> {
     }
 
     fn id(&self) -> Option<TypeId> {
-        return self.id;
+        self.id
     }
 }
 
 pub trait ExpansionGeneric<S> {
-    fn call(&self, input: &mut Input<S>) -> anyhow::Result<stream::VecStream>;
+    fn call(&self, token: token::Token, input: &mut Input<S>) -> anyhow::Result<stream::VecStream>;
 
     fn doc(&self) -> &str {
         "this command has no documentation"
@@ -75,10 +111,10 @@ impl<S> Expansion<S> {
 }
 
 impl<S: TexState<S>> ExpansionGeneric<S> for Expansion<S> {
-    fn call(&self, input: &mut Input<S>) -> anyhow::Result<stream::VecStream> {
+    fn call(&self, token: token::Token, input: &mut Input<S>) -> anyhow::Result<stream::VecStream> {
         match self {
-            Expansion::Static(e) => ExpansionStatic::call(e, input),
-            Expansion::Generic(e) => ExpansionGeneric::call(e.as_ref(), input),
+            Expansion::Static(e) => ExpansionStatic::call(e, token, input),
+            Expansion::Generic(e) => ExpansionGeneric::call(e.as_ref(), token, input),
         }
     }
 
@@ -97,8 +133,553 @@ impl<S: TexState<S>> ExpansionGeneric<S> for Expansion<S> {
     }
 }
 
+/// An execution primitive is a command that is not expanded but instead performs some
+/// operation, like changing the state.
+///
+/// The token that invoked the primitive is passed to the call function so that it can be
+/// used in error messages.
+pub struct ExecutionStatic<S> {
+    /// The name of the primitive in TeX, which identifies it as for expansion primitives.
+    name: &'static str,
+    call_fn: fn(token: token::Token, input: &mut Input<S>) -> anyhow::Result<()>,
+    docs: &'static str,
+    id: Option<TypeId>,
+}
+
+impl<S> ExecutionStatic<S> {
+    /// Returns a new static execution primitive with the provided name, call function and docs.
+    pub fn new(
+        name: &'static str,
+        call_fn: fn(token: token::Token, input: &mut Input<S>) -> anyhow::Result<()>,
+        docs: &'static str,
+    ) -> ExecutionStatic<S> {
+        ExecutionStatic {
+            name,
+            call_fn,
+            docs,
+            id: None,
+        }
+    }
+
+    /// Returns the primitive with the provided id, which places it in a class of primitives,
+    /// like the assignments that may follow `\global`.
+    pub fn with_id(self, id: TypeId) -> ExecutionStatic<S> {
+        ExecutionStatic {
+            id: Some(id),
+            ..self
+        }
+    }
+}
+
+impl<S> Clone for ExecutionStatic<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for ExecutionStatic<S> {}
+
+impl<S: state::TexState<S>> ExecutionGeneric<S> for ExecutionStatic<S> {
+    fn call(&self, token: token::Token, input: &mut Input<S>) -> anyhow::Result<()> {
+        (self.call_fn)(token, input)
+    }
+
+    fn doc(&self) -> &str {
+        self.docs
+    }
+
+    fn id(&self) -> Option<TypeId> {
+        self.id
+    }
+}
+
+pub trait ExecutionGeneric<S> {
+    fn call(&self, token: token::Token, input: &mut Input<S>) -> anyhow::Result<()>;
+
+    fn doc(&self) -> &str {
+        "this command has no documentation"
+    }
+
+    fn id(&self) -> Option<TypeId> {
+        None
+    }
+}
+
+pub enum Execution<S> {
+    Static(ExecutionStatic<S>),
+    Generic(rc::Rc<dyn ExecutionGeneric<S>>),
+}
+
+impl<S> Clone for Execution<S> {
+    fn clone(&self) -> Self {
+        match self {
+            Execution::Static(s) => Execution::Static(*s),
+            Execution::Generic(g) => Execution::Generic(g.clone()),
+        }
+    }
+}
+
+impl<S: TexState<S>> ExecutionGeneric<S> for Execution<S> {
+    fn call(&self, token: token::Token, input: &mut Input<S>) -> anyhow::Result<()> {
+        match self {
+            Execution::Static(e) => e.call(token, input),
+            Execution::Generic(e) => e.call(token, input),
+        }
+    }
+
+    fn doc(&self) -> &str {
+        match self {
+            Execution::Static(e) => e.doc(),
+            Execution::Generic(e) => e.doc(),
+        }
+    }
+
+    fn id(&self) -> Option<TypeId> {
+        match self {
+            Execution::Static(e) => e.id,
+            Execution::Generic(e) => e.id(),
+        }
+    }
+}
+
+/// A variable is a primitive that refers to an internal quantity of the state, like
+/// e-TeX's `\currentgrouplevel`.
+///
+/// Variables are the way a primitive declares that it is an internal quantity: the value of
+/// every variable can be read by `\the` and used wherever TeX expects a quantity of its type.
+/// Read-only variables, like `\currentgrouplevel`, have no set function.
+///
+/// Some variables take an index that is read from the input after the variable's control
+/// sequence. For example, `\catcode` is followed by the number of the character whose
+/// catcode is being referred to.
+pub enum Variable<S> {
+    Int(TypedVariable<S, i32>),
+    Dimen(TypedVariable<S, Dimen>),
+    Glue(TypedVariable<S, Glue>),
+    /// Math glue, like `\thinmuskip`, whose finite dimensions are in units of `mu`.
+    MuGlue(TypedVariable<S, Glue>),
+    TokenList(TypedVariable<S, rc::Rc<Vec<token::Token>>>),
+}
+
+impl<S> Clone for Variable<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for Variable<S> {}
+
+impl<S: TexState<S>> Variable<S> {
+    /// Performs an assignment to the variable. The index of the variable, an optional equals
+    /// sign, and the new value are read from the input.
+    ///
+    /// The token is the token that referred to the variable, and is used for error messages.
+    pub fn assign(
+        &self,
+        token: token::Token,
+        input: &mut Input<S>,
+        global: bool,
+    ) -> anyhow::Result<()> {
+        match self {
+            Variable::Int(v) => v.assign(token, input, global, parse::parse_number),
+            Variable::Dimen(v) => v.assign(token, input, global, parse::parse_dimen),
+            Variable::Glue(v) => v.assign(token, input, global, parse::parse_glue),
+            Variable::MuGlue(v) => v.assign(token, input, global, parse::parse_mu_glue),
+            Variable::TokenList(v) => v.assign(token, input, global, parse::parse_token_list),
+        }
+    }
+}
+
+impl<S> Variable<S> {
+    /// Returns true if the two variables are the same, in the sense of `\ifx`.
+    fn same_as(&self, other: &Variable<S>) -> bool {
+        match (self, other) {
+            (Variable::Int(_), Variable::Int(_))
+            | (Variable::Dimen(_), Variable::Dimen(_))
+            | (Variable::Glue(_), Variable::Glue(_))
+            | (Variable::MuGlue(_), Variable::MuGlue(_))
+            | (Variable::TokenList(_), Variable::TokenList(_)) => self.key() == other.key(),
+            _ => false,
+        }
+    }
+
+    /// Returns the name of the variable and its fixed index, if any. Two variables of the same
+    /// type are the same if they have the same key.
+    fn key(&self) -> (&'static str, Option<usize>) {
+        match self {
+            Variable::Int(v) => v.key(),
+            Variable::Dimen(v) => v.key(),
+            Variable::Glue(v) => v.key(),
+            Variable::MuGlue(v) => v.key(),
+            Variable::TokenList(v) => v.key(),
+        }
+    }
+}
+
+pub(crate) fn read_only_error(token: token::Token) -> anyhow::Error {
+    let name = match &token.value {
+        token::Value::ControlSequence(_, name) => format!("\\{}", name),
+        token::Value::Character(c, _) => c.to_string(),
+    };
+    error::new_token_error(
+        token,
+        format!("You can't assign to {}", name),
+        vec!["this internal quantity is read-only".to_string()],
+    )
+}
+
+/// A function that reads the index of a variable from the input.
+pub type IndexFn<S> = fn(input: &mut Input<S>) -> anyhow::Result<usize>;
+
+/// The way the index of a variable is determined.
+pub enum Index<S> {
+    /// The variable always has the same index. Parameters like `\tolerance` share their get
+    /// and set functions and are distinguished by their fixed indices.
+    Fixed(usize),
+    /// The index is read from the input after the variable's control sequence.
+    Parsed(IndexFn<S>),
+}
+
+impl<S> Clone for Index<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for Index<S> {}
+
+/// A function that sets the value of a variable. The function returns an error if the value is
+/// not valid for the variable.
+pub type SetFn<S, T> =
+    fn(state: &mut S, index: usize, value: T, global: bool) -> anyhow::Result<()>;
+
+/// A variable of a specific type.
+pub struct TypedVariable<S, T> {
+    /// The name of the primitive in TeX, which identifies the variable together with its fixed
+    /// index. Variables created using `\countdef` and related primitives keep the name of the
+    /// register primitive they refer to.
+    name: &'static str,
+    index: Index<S>,
+    get_fn: fn(state: &S, index: usize) -> T,
+    set_fn: Option<SetFn<S, T>>,
+    docs: &'static str,
+}
+
+impl<S, T> Clone for TypedVariable<S, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S, T> Copy for TypedVariable<S, T> {}
+
+impl<S, T> TypedVariable<S, T> {
+    /// Reads the index of the variable from the input, if the variable is indexed.
+    pub fn index(&self, input: &mut Input<S>) -> anyhow::Result<usize> {
+        match self.index {
+            Index::Fixed(index) => Ok(index),
+            Index::Parsed(index_fn) => index_fn(input),
+        }
+    }
+
+    /// Returns the value of the variable at the provided index.
+    pub fn get(&self, state: &S, index: usize) -> T {
+        (self.get_fn)(state, index)
+    }
+
+    /// Returns true if the variable can be assigned to.
+    pub fn is_settable(&self) -> bool {
+        self.set_fn.is_some()
+    }
+
+    /// Sets the value of the variable at the provided index. Setting a read-only variable
+    /// is a no-op; callers should check `is_settable` first.
+    pub fn set(&self, state: &mut S, index: usize, value: T, global: bool) -> anyhow::Result<()> {
+        match self.set_fn {
+            None => Ok(()),
+            Some(set_fn) => set_fn(state, index, value, global),
+        }
+    }
+
+    pub fn doc(&self) -> &str {
+        self.docs
+    }
+
+    fn key(&self) -> (&'static str, Option<usize>) {
+        let index = match self.index {
+            Index::Fixed(index) => Some(index),
+            Index::Parsed(_) => None,
+        };
+        (self.name, index)
+    }
+}
+
+impl<S: TexState<S>, T> TypedVariable<S, T> {
+    fn assign(
+        &self,
+        token: token::Token,
+        input: &mut Input<S>,
+        global: bool,
+        parse_fn: fn(&mut Input<S>) -> anyhow::Result<T>,
+    ) -> anyhow::Result<()> {
+        if !self.is_settable() {
+            return Err(read_only_error(token));
+        }
+        let index = self.index(input)?;
+        parse::parse_optional_equals(input)?;
+        let value = parse_fn(input)?;
+        self.set(input.state_mut(), index, value, global)
+            .map_err(|err| error::new_token_error(token, err.to_string(), vec![]))
+    }
+}
+
+/// The meaning of a control sequence.
 pub enum Primitive<S> {
     Expansion(Expansion<S>),
+    Execution(Execution<S>),
+    Variable(Variable<S>),
+    /// An implicit character, created using `\let`. For example, after `\let\bgroup={` the
+    /// control sequence `\bgroup` is bound to the character `{` with catcode begin group.
+    Character(char, CatCode),
+    /// A user-defined macro, created using `\def` and related primitives.
+    Macro(rc::Rc<macros::Macro>),
+    /// A constant integer, created using `\chardef` or `\mathchardef`. For example, after
+    /// `\chardef\active=13` the control sequence `\active` is bound to the integer 13, and can
+    /// be used wherever a number is expected.
+    Constant(ConstantKind, i32),
+    /// A font identifier, created using `\font`. For example, after `\font\tenrm=cmr10` the
+    /// control sequence `\tenrm` is bound to the font loaded from `cmr10.tfm`, and executing it
+    /// selects that font.
+    Font(FontId),
+}
+
+/// The kind of a constant integer meaning.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ConstantKind {
+    /// A character code, created using `\chardef`. When executed it typesets the character.
+    Char,
+    /// A math character code, created using `\mathchardef`.
+    MathChar,
+}
+
+impl<S> Clone for Primitive<S> {
+    fn clone(&self) -> Self {
+        match self {
+            Primitive::Expansion(e) => Primitive::Expansion(e.duplicate()),
+            Primitive::Execution(e) => Primitive::Execution(e.clone()),
+            Primitive::Variable(v) => Primitive::Variable(*v),
+            Primitive::Character(c, cat_code) => Primitive::Character(*c, *cat_code),
+            Primitive::Macro(m) => Primitive::Macro(m.clone()),
+            Primitive::Constant(kind, n) => Primitive::Constant(*kind, *n),
+            Primitive::Font(id) => Primitive::Font(*id),
+        }
+    }
+}
+
+impl<S> Primitive<S> {
+    /// Returns true if the two primitives have the same meaning in the sense of `\ifx`.
+    ///
+    /// Static primitives have the same meaning if they have the same name, and generic
+    /// primitives have the same meaning if they are the same object. Macros have the
+    /// same meaning if they have the same parameter and replacement texts.
+    pub fn same_meaning(&self, other: &Primitive<S>) -> bool {
+        match (self, other) {
+            (Primitive::Expansion(a), Primitive::Expansion(b)) => match (a, b) {
+                (Expansion::Static(a), Expansion::Static(b)) => a.name == b.name,
+                (Expansion::Generic(a), Expansion::Generic(b)) => rc::Rc::ptr_eq(a, b),
+                _ => false,
+            },
+            (Primitive::Execution(a), Primitive::Execution(b)) => match (a, b) {
+                (Execution::Static(a), Execution::Static(b)) => a.name == b.name,
+                (Execution::Generic(a), Execution::Generic(b)) => rc::Rc::ptr_eq(a, b),
+                _ => false,
+            },
+            (Primitive::Variable(a), Primitive::Variable(b)) => a.same_as(b),
+            (Primitive::Character(c_a, cat_code_a), Primitive::Character(c_b, cat_code_b)) => {
+                c_a == c_b && cat_code_a == cat_code_b
+            }
+            (Primitive::Macro(a), Primitive::Macro(b)) => a.same_as(b),
+            (Primitive::Constant(kind_a, n_a), Primitive::Constant(kind_b, n_b)) => {
+                kind_a == kind_b && n_a == n_b
+            }
+            (Primitive::Font(a), Primitive::Font(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Returns a key that identifies a built-in primitive, or `None` for implicit characters,
+    /// macros, constants and fonts. Two primitives have the same key if they have the same meaning.
+    fn key(&self) -> Option<PrimitiveKey> {
+        match self {
+            Primitive::Expansion(Expansion::Static(e)) => Some(PrimitiveKey::Expansion(e.name)),
+            Primitive::Expansion(Expansion::Generic(e)) => Some(PrimitiveKey::Generic(
+                0,
+                rc::Rc::as_ptr(e) as *const () as usize,
+            )),
+            Primitive::Execution(Execution::Static(e)) => Some(PrimitiveKey::Execution(e.name)),
+            Primitive::Execution(Execution::Generic(e)) => Some(PrimitiveKey::Generic(
+                1,
+                rc::Rc::as_ptr(e) as *const () as usize,
+            )),
+            Primitive::Variable(v) => {
+                let (name, index) = v.key();
+                let kind = match v {
+                    Variable::Int(_) => 0,
+                    Variable::Dimen(_) => 1,
+                    Variable::Glue(_) => 2,
+                    Variable::TokenList(_) => 3,
+                    Variable::MuGlue(_) => 4,
+                };
+                Some(PrimitiveKey::Variable(kind, name, index))
+            }
+            Primitive::Character(..)
+            | Primitive::Macro(_)
+            | Primitive::Constant(..)
+            | Primitive::Font(_) => None,
+        }
+    }
+}
+
+/// A key that identifies a built-in primitive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum PrimitiveKey {
+    /// A static expansion primitive, identified by its name.
+    Expansion(&'static str),
+    /// A static execution primitive, identified by its name.
+    Execution(&'static str),
+    /// A generic expansion (0) or execution (1) primitive, identified by the address of its
+    /// object.
+    Generic(u8, usize),
+    /// A variable of type int (0), dimen (1), glue (2) or token list (3), identified by its
+    /// name and its fixed index.
+    Variable(u8, &'static str, Option<usize>),
+}
+
+/// The name of a control sequence or active character; i.e., a key in the meaning table.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Name {
+    ControlSequence(String),
+    ActiveCharacter(char),
+}
+
+impl Name {
+    /// Returns the name corresponding to a token value, or `None` if the value is a character
+    /// that is not active.
+    pub fn from_value(value: &token::Value) -> Option<Name> {
+        match value {
+            token::Value::ControlSequence(_, name) => Some(Name::ControlSequence(name.clone())),
+            token::Value::Character(c, CatCode::Active) => Some(Name::ActiveCharacter(*c)),
+            token::Value::Character(..) => None,
+        }
+    }
+}
+
+/// The meaning table maps the names of control sequences and active characters to their
+/// meanings.
+///
+/// As in TeX, control sequences and active characters share a single table and are defined
+/// and looked up the same way. Internally the two kinds of names are stored in separate scoped
+/// maps so that lookups can be performed directly on token values without allocating.
+///
+/// The table also remembers the name each built-in primitive was first inserted with, so that
+/// the primitive can be described by `\show` and `\meaning` even after it has been copied to
+/// another control sequence using `\let`.
+pub struct MeaningTable<S> {
+    control_sequences: ScopedMap<String, Primitive<S>>,
+    active_characters: ScopedMap<char, Primitive<S>>,
+    primitive_names: HashMap<PrimitiveKey, String>,
+}
+
+impl<S> MeaningTable<S> {
+    pub fn new() -> MeaningTable<S> {
+        MeaningTable {
+            control_sequences: ScopedMap::new(),
+            active_characters: ScopedMap::new(),
+            primitive_names: HashMap::new(),
+        }
+    }
+
+    /// Retrieves the meaning of a control sequence or active character token value. Returns
+    /// `None` if the value is undefined, or is a character that is not active.
+    pub fn get(&self, value: &token::Value) -> Option<&Primitive<S>> {
+        match value {
+            token::Value::ControlSequence(_, name) => self.control_sequences.get(name),
+            token::Value::Character(c, CatCode::Active) => self.active_characters.get(c),
+            token::Value::Character(..) => None,
+        }
+    }
+
+    /// Retrieves the meaning of the control sequence with the provided name.
+    pub fn get_control_sequence(&self, name: &str) -> Option<&Primitive<S>> {
+        self.control_sequences.get(&name.to_string())
+    }
+
+    /// Sets the meaning of a name, either locally or globally. A meaning of `None` makes the
+    /// name undefined.
+    pub fn set(&mut self, name: Name, meaning: Option<Primitive<S>>, global: bool) {
+        match (name, meaning, global) {
+            (Name::ControlSequence(n), Some(p), false) => self.control_sequences.insert(n, p),
+            (Name::ControlSequence(n), Some(p), true) => self.control_sequences.insert_global(n, p),
+            (Name::ControlSequence(n), None, false) => self.control_sequences.remove(n),
+            (Name::ControlSequence(n), None, true) => self.control_sequences.remove_global(n),
+            (Name::ActiveCharacter(c), Some(p), false) => self.active_characters.insert(c, p),
+            (Name::ActiveCharacter(c), Some(p), true) => self.active_characters.insert_global(c, p),
+            (Name::ActiveCharacter(c), None, false) => self.active_characters.remove(c),
+            (Name::ActiveCharacter(c), None, true) => self.active_characters.remove_global(c),
+        }
+    }
+
+    /// Sets the meaning of the control sequence with the provided name in the current scope.
+    ///
+    /// If the meaning is a built-in primitive that has not been inserted before, the name
+    /// becomes the primitive's name.
+    pub fn insert(&mut self, name: String, p: Primitive<S>) {
+        if let Some(key) = p.key() {
+            self.primitive_names
+                .entry(key)
+                .or_insert_with(|| name.clone());
+        }
+        self.control_sequences.insert(name, p)
+    }
+
+    /// Returns the name of a built-in primitive, or `None` if the primitive was never inserted
+    /// into the table or is an implicit character, macro or constant.
+    pub fn primitive_name(&self, p: &Primitive<S>) -> Option<&str> {
+        p.key()
+            .and_then(|key| self.primitive_names.get(&key))
+            .map(String::as_str)
+    }
+
+    /// Returns the name of the indexed variable that a variable with a fixed index refers to,
+    /// along with the index. For example, after `\countdef\a=3` the meaning of `\a` refers to
+    /// `\count` with index 3.
+    pub fn indexed_variable_name(&self, p: &Primitive<S>) -> Option<(&str, usize)> {
+        match p.key() {
+            Some(PrimitiveKey::Variable(kind, name, Some(index))) => self
+                .primitive_names
+                .get(&PrimitiveKey::Variable(kind, name, None))
+                .map(|name| (name.as_str(), index)),
+            _ => None,
+        }
+    }
+
+    pub fn begin_scope(&mut self) {
+        self.control_sequences.begin_scope();
+        self.active_characters.begin_scope();
+    }
+
+    #[must_use]
+    pub fn end_scope(&mut self) -> bool {
+        let control_sequences_ended = self.control_sequences.end_scope();
+        let active_characters_ended = self.active_characters.end_scope();
+        control_sequences_ended && active_characters_ended
+    }
+}
+
+impl<S> Default for MeaningTable<S> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /*
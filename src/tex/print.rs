@@ -0,0 +1,274 @@
+//! Printing to the terminal and the log file.
+//!
+//! As in TeX, text is printed to a selection of the two streams: diagnostics that should only be
+//! recorded appear in the log file, while messages from `\message` appear in both. Each stream
+//! keeps track of the current column, so that long lines are broken after `MAX_PRINT_LINE`
+//! characters, and so that text that must begin on a new line, like the output of `\show`, does
+//! so:
+//! ```
+//! # use texide::tex::print::{Buffer, Printer, Selector};
+//! let terminal = Buffer::new();
+//! let mut printer = Printer::new(Box::new(terminal.clone()));
+//! printer.print(Selector::TerminalAndLog, "(story.tex", None);
+//! printer.print_nl(Selector::TerminalAndLog, "> 1.0pt.", None);
+//! assert_eq!(terminal.contents(), "(story.tex\n> 1.0pt.");
+//! ```
+//!
+//! Errors writing to the streams are ignored, as there is nowhere to report them.
+
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+/// The length of the longest line written to the terminal and log file; `max_print_line` in
+/// TeX.
+pub const MAX_PRINT_LINE: usize = 79;
+
+/// The streams that text is printed to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Selector {
+    Terminal,
+    Log,
+    TerminalAndLog,
+}
+
+impl Selector {
+    fn terminal(self) -> bool {
+        self != Selector::Log
+    }
+
+    fn log(self) -> bool {
+        self != Selector::Terminal
+    }
+}
+
+/// A stream of text with a current column.
+struct Stream {
+    writer: Box<dyn io::Write>,
+    offset: usize,
+}
+
+impl Stream {
+    fn new(writer: Box<dyn io::Write>) -> Stream {
+        Stream { writer, offset: 0 }
+    }
+
+    fn print_char(&mut self, c: char) {
+        let mut buf = [0_u8; 4];
+        let _ = self.writer.write_all(c.encode_utf8(&mut buf).as_bytes());
+        self.offset += 1;
+        if self.offset == MAX_PRINT_LINE {
+            self.print_ln();
+        }
+    }
+
+    fn print_ln(&mut self) {
+        let _ = self.writer.write_all(b"\n");
+        self.offset = 0;
+    }
+}
+
+/// The terminal and log file.
+pub struct Printer {
+    terminal: Stream,
+    log: Option<Stream>,
+}
+
+impl Printer {
+    /// Returns a new printer that writes to the provided terminal. There is no log file until
+    /// one is opened.
+    pub fn new(terminal: Box<dyn io::Write>) -> Printer {
+        Printer {
+            terminal: Stream::new(terminal),
+            log: None,
+        }
+    }
+
+    /// Starts writing the log to the provided writer, closing any log that is already open.
+    pub fn open_log(&mut self, log: Box<dyn io::Write>) {
+        self.close_log();
+        self.log = Some(Stream::new(log));
+    }
+
+    /// Ends the current line of the log and closes it. Returns false if no log was open.
+    pub fn close_log(&mut self) -> bool {
+        match self.log.take() {
+            None => false,
+            Some(mut log) => {
+                if log.offset > 0 {
+                    log.print_ln();
+                }
+                let _ = log.writer.flush();
+                true
+            }
+        }
+    }
+
+    /// Returns true if a log is open.
+    pub fn has_log(&self) -> bool {
+        self.log.is_some()
+    }
+
+    /// Returns the number of characters on the current line of the terminal.
+    pub fn terminal_offset(&self) -> usize {
+        self.terminal.offset
+    }
+
+    /// Returns the number of characters on the current line of the log, or zero if there is no
+    /// log.
+    pub fn log_offset(&self) -> usize {
+        self.log.as_ref().map(|log| log.offset).unwrap_or(0)
+    }
+
+    /// Prints text to the selected streams. Occurrences of the new line character, which is
+    /// `\newlinechar` in TeX, and of `\n` end the current line.
+    pub fn print(&mut self, selector: Selector, text: &str, new_line_char: Option<char>) {
+        for c in text.chars() {
+            if c == '\n' || Some(c) == new_line_char {
+                self.print_ln(selector);
+                continue;
+            }
+            self.for_each(selector, |stream| stream.print_char(c));
+        }
+    }
+
+    /// Ends the current line in the selected streams.
+    pub fn print_ln(&mut self, selector: Selector) {
+        self.for_each(selector, Stream::print_ln);
+    }
+
+    /// Prints text to the selected streams, starting on a new line in each stream that is not
+    /// already at the start of a line.
+    pub fn print_nl(&mut self, selector: Selector, text: &str, new_line_char: Option<char>) {
+        self.for_each(selector, |stream| {
+            if stream.offset > 0 {
+                stream.print_ln();
+            }
+        });
+        self.print(selector, text, new_line_char);
+    }
+
+    /// Records that a line was typed on the terminal. The new line the user typed to end the
+    /// input moves the terminal to the start of a line.
+    pub fn end_terminal_input(&mut self) {
+        self.terminal.offset = 0;
+    }
+
+    /// Flushes the terminal, so that partial lines become visible.
+    pub fn flush(&mut self) {
+        let _ = self.terminal.writer.flush();
+    }
+
+    fn for_each<F: FnMut(&mut Stream)>(&mut self, selector: Selector, mut f: F) {
+        if selector.terminal() {
+            f(&mut self.terminal);
+        }
+        if selector.log() {
+            if let Some(log) = self.log.as_mut() {
+                f(log);
+            }
+        }
+    }
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Printer::new(Box::new(io::stdout()))
+    }
+}
+
+/// An in-memory writer whose contents can be read after it has been given to a printer. This is
+/// mostly useful for testing.
+#[derive(Clone, Default)]
+pub struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+    pub fn new() -> Buffer {
+        Default::default()
+    }
+
+    /// Returns everything written to the buffer so far.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    /// Returns the bytes written to the buffer so far.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+}
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_printer() -> (Printer, Buffer, Buffer) {
+        let terminal = Buffer::new();
+        let log = Buffer::new();
+        let mut printer = Printer::new(Box::new(terminal.clone()));
+        printer.open_log(Box::new(log.clone()));
+        (printer, terminal, log)
+    }
+
+    #[test]
+    fn selectors() {
+        let (mut printer, terminal, log) = new_printer();
+        printer.print(Selector::Terminal, "a", None);
+        printer.print(Selector::Log, "b", None);
+        printer.print(Selector::TerminalAndLog, "c", None);
+        assert_eq!(terminal.contents(), "ac");
+        assert_eq!(log.contents(), "bc");
+    }
+
+    #[test]
+    fn print_nl() {
+        let (mut printer, terminal, log) = new_printer();
+        printer.print(Selector::Terminal, "a", None);
+        printer.print_nl(Selector::TerminalAndLog, "b", None);
+        printer.print_nl(Selector::TerminalAndLog, "c", None);
+        assert_eq!(terminal.contents(), "a\nb\nc");
+        assert_eq!(log.contents(), "b\nc");
+    }
+
+    #[test]
+    fn new_line_char() {
+        let (mut printer, terminal, _) = new_printer();
+        printer.print(Selector::Terminal, "a^^Jb", Some('^'));
+        assert_eq!(terminal.contents(), "a\n\nJb");
+        assert_eq!(printer.terminal_offset(), 2);
+    }
+
+    #[test]
+    fn long_lines_are_broken() {
+        let (mut printer, terminal, _) = new_printer();
+        let text = "x".repeat(2 * MAX_PRINT_LINE + 1);
+        printer.print(Selector::Terminal, &text, None);
+        let lengths: Vec<usize> = terminal
+            .contents()
+            .split('\n')
+            .map(|line| line.len())
+            .collect();
+        assert_eq!(lengths, vec![MAX_PRINT_LINE, MAX_PRINT_LINE, 1]);
+    }
+
+    #[test]
+    fn close_log_ends_the_line() {
+        let (mut printer, _, log) = new_printer();
+        printer.print(Selector::Log, "a", None);
+        assert!(printer.close_log());
+        assert!(!printer.close_log());
+        printer.print(Selector::Log, "b", None);
+        assert_eq!(log.contents(), "a\n");
+    }
+}
@@ -0,0 +1,97 @@
+//! Registers: `\count`, `\dimen`, `\skip`, `\muskip`, `\toks` and `\box`.
+//!
+//! Each kind of register is stored in a scoped map from the register number to its value, so
+//! assignments are rolled back at the end of the current group unless `\global` is used.
+//! Registers that have never been assigned have the value zero, or the empty token list, and
+//! box registers are void.
+//! As in e-TeX, there are 32768 registers of each kind.
+
+use crate::datastructures::scopedmap::ScopedMap;
+use crate::tex::dimen::{Dimen, Glue};
+use crate::tex::node::Node;
+use crate::tex::token::token::Token;
+use std::rc::Rc;
+
+/// The number of registers of each kind.
+pub const NUM_REGISTERS: usize = 32768;
+
+#[derive(Default)]
+pub struct Registers {
+    pub counts: ScopedMap<usize, i32>,
+    pub dimens: ScopedMap<usize, Dimen>,
+    pub skips: ScopedMap<usize, Glue>,
+    /// The math glue registers, whose finite dimensions are in `mu`.
+    pub mu_skips: ScopedMap<usize, Glue>,
+    pub toks: ScopedMap<usize, Rc<Vec<Token>>>,
+    /// The box registers, each of which holds an hlist or vlist node. A register that is not
+    /// in the map is void.
+    pub boxes: ScopedMap<usize, Node>,
+}
+
+impl Registers {
+    pub fn new() -> Registers {
+        Default::default()
+    }
+
+    pub fn count(&self, i: usize) -> i32 {
+        self.counts.get(&i).copied().unwrap_or_default()
+    }
+
+    pub fn dimen(&self, i: usize) -> Dimen {
+        self.dimens.get(&i).copied().unwrap_or_default()
+    }
+
+    pub fn skip(&self, i: usize) -> Glue {
+        self.skips.get(&i).copied().unwrap_or_default()
+    }
+
+    pub fn mu_skip(&self, i: usize) -> Glue {
+        self.mu_skips.get(&i).copied().unwrap_or_default()
+    }
+
+    pub fn toks(&self, i: usize) -> Rc<Vec<Token>> {
+        self.toks.get(&i).cloned().unwrap_or_default()
+    }
+
+    /// Returns the box in a box register, or `None` if the register is void.
+    pub fn box_register(&self, i: usize) -> Option<&Node> {
+        self.boxes.get(&i)
+    }
+
+    /// Returns the box in a box register and makes the register void.
+    ///
+    /// As in TeX, the register is made void in place: if the box was assigned outside the
+    /// current group, it does not reappear when the group ends.
+    pub fn take_box(&mut self, i: usize) -> Option<Node> {
+        self.boxes.take(&i)
+    }
+
+    /// Puts a box in a box register, in place: like `take_box`, this is not undone when the
+    /// current group ends. The page builder uses this for `\box255` and the insertion boxes.
+    pub fn put_box(&mut self, i: usize, b: Node) {
+        self.boxes.replace(i, b);
+    }
+
+    /// Begins a new scope in every register table.
+    pub fn begin_scope(&mut self) {
+        self.counts.begin_scope();
+        self.dimens.begin_scope();
+        self.skips.begin_scope();
+        self.mu_skips.begin_scope();
+        self.toks.begin_scope();
+        self.boxes.begin_scope();
+    }
+
+    /// Ends the current scope in every register table, and returns false if there is no scope
+    /// to end.
+    #[must_use]
+    pub fn end_scope(&mut self) -> bool {
+        // Non-short-circuiting so that all tables stay in sync.
+        self.counts.end_scope()
+            & self.dimens.end_scope()
+            & self.skips.end_scope()
+            & self.mu_skips.end_scope()
+            & self.toks.end_scope()
+            & self.boxes.end_scope()
+    }
+}
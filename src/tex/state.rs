@@ -1,7 +1,23 @@
-use crate::datastructures::scopedmap::ScopedMap;
+use crate::datastructures::scopedcharmap::ScopedCharMap;
+use crate::tex::charcode::CharCodes;
+use crate::tex::files::{InputFiles, OutputFiles};
+use crate::tex::filesystem::{FileSystem, RealFileSystem};
+use crate::tex::font::Fonts;
+use crate::tex::group::{BoxContext, DiscretionaryPart, Group, GroupType};
+use crate::tex::hyphenation::Hyphenation;
 use crate::tex::input;
+use crate::tex::nest::Nest;
+use crate::tex::pack;
+use crate::tex::page::Page;
+use crate::tex::parameter::{IntParam, Parameters};
 use crate::tex::primitive;
+use crate::tex::primitive::library::conditional;
 use crate::tex::primitive::Primitive;
+use crate::tex::print::{Printer, Selector};
+use crate::tex::register::Registers;
+use crate::tex::token::catcode::CatCode;
+use crate::tex::token::token;
+use crate::tex::token::write;
 
 // TeXState is a trait that every state in Texide satisfies. It ensures that the state
 // can be used for perform expansion, handle macro processing, etc.
@@ -22,8 +38,8 @@ pub trait TexState<S>: 'static {
     }
     */
 
-    fn get_expansion_primitive(&self, name: &String) -> Option<primitive::Expansion<S>> {
-        if let Some(Primitive::Expansion(p)) = self.base().primitives.get(name) {
+    fn get_expansion_primitive(&self, name: &str) -> Option<primitive::Expansion<S>> {
+        if let Some(Primitive::Expansion(p)) = self.base().primitives.get_control_sequence(name) {
             Some(p.duplicate())
         } else {
             None
@@ -34,25 +50,210 @@ pub trait TexState<S>: 'static {
         self.base_mut()
             .primitives
             .insert(name, Primitive::Expansion(p));
-        ()
+    }
+
+    fn set_primitive(&mut self, name: &str, p: Primitive<S>) {
+        self.base_mut().primitives.insert(name.to_string(), p);
     }
 }
 
 pub struct BaseState<S> {
-    pub primitives: ScopedMap<String, primitive::Primitive<S>>,
+    pub primitives: primitive::MeaningTable<S>,
     pub input_module: input::InputModule,
+    /// The lowercase, uppercase and space factor codes of characters.
+    pub char_codes: CharCodes,
+    /// The `\count`, `\dimen`, `\skip` and `\toks` registers.
+    pub registers: Registers,
+    /// Named internal parameters like `\newlinechar`.
+    pub parameters: Parameters,
+    /// The loaded fonts and the current font.
+    pub fonts: Fonts,
+    /// The terminal and log file.
+    pub printer: Printer,
+    /// The file system that documents read files from and write files to.
+    pub file_system: Box<dyn FileSystem>,
+    /// The input streams read by `\read`.
+    pub input_files: InputFiles,
+    /// The output streams written to by `\write`.
+    pub output_files: OutputFiles,
+    /// The modes currently entered and the lists being built in them.
+    pub nest: Nest,
+    /// The current page and the state of the page builder.
+    pub page: Page,
+    /// The hyphenation patterns and exceptions of each language.
+    pub hyphenation: Hyphenation,
+    /// The branches of the conditionals currently being expanded.
+    pub branches: Vec<conditional::Branch>,
+    /// Whether the assignment currently being performed was prefixed by `\global`.
+    pub global_prefix: bool,
+    /// Whether the macro definition currently being performed was prefixed by `\long`.
+    pub long_prefix: bool,
+    /// Whether the macro definition currently being performed was prefixed by `\outer`.
+    pub outer_prefix: bool,
+    /// Set by `\end` when the job is over, after which no more input is read.
+    pub job_ended: bool,
+    /// The badness of the box that was packaged most recently, which is `\badness`.
+    pub last_badness: i32,
+    groups: Vec<Group>,
 }
 
 impl<S> BaseState<S> {
     // Create a new BaseState
     pub fn new() -> BaseState<S> {
         BaseState {
-            primitives: ScopedMap::new(),
-            input_module: input::InputModule::new(ScopedMap::new()),
+            primitives: primitive::MeaningTable::new(),
+            input_module: input::InputModule::new(ScopedCharMap::new()),
+            char_codes: CharCodes::new(),
+            registers: Registers::new(),
+            parameters: Parameters::new(),
+            fonts: Fonts::new(),
+            printer: Default::default(),
+            file_system: Box::new(RealFileSystem),
+            input_files: InputFiles::new(),
+            output_files: OutputFiles::new(),
+            nest: Nest::new(),
+            page: Page::new(),
+            hyphenation: Hyphenation::new(),
+            branches: Vec::new(),
+            global_prefix: false,
+            long_prefix: false,
+            outer_prefix: false,
+            job_ended: false,
+            last_badness: 0,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Returns the meaning of a token value, or `None` if the value is an undefined control
+    /// sequence.
+    ///
+    /// The meaning of a character that is not active is the character itself as an implicit
+    /// character.
+    pub fn meaning(&self, value: &token::Value) -> Option<Primitive<S>> {
+        match value {
+            token::Value::Character(c, cat_code) if *cat_code != CatCode::Active => {
+                Some(Primitive::Character(*c, *cat_code))
+            }
+            _ => self.primitives.get(value).cloned(),
+        }
+    }
+
+    /// Returns the style in which tokens are currently written by primitives like `\show`.
+    pub fn write_style(&self) -> write::Style<'_> {
+        write::Style {
+            escape_char: self.parameters.escape_char(),
+            cat_codes: Some(&self.input_module.cat_code_map),
+        }
+    }
+
+    /// Prints text to the selected streams. Occurrences of `\newlinechar` end the current line.
+    pub fn print(&mut self, selector: Selector, text: &str) {
+        let new_line_char = self.parameters.new_line_char();
+        self.printer.print(selector, text, new_line_char);
+    }
+
+    /// Prints text to the selected streams, starting on a new line.
+    pub fn print_nl(&mut self, selector: Selector, text: &str) {
+        let new_line_char = self.parameters.new_line_char();
+        self.printer.print_nl(selector, text, new_line_char);
+    }
+
+    /// Returns the streams that diagnostics, like tracing output, are printed to. Diagnostics
+    /// are only printed to the terminal if `\tracingonline` is positive.
+    pub fn diagnostic_selector(&self) -> Selector {
+        match self.parameters.int(IntParam::TracingOnline) > 0 {
+            true => Selector::TerminalAndLog,
+            false => Selector::Log,
+        }
+    }
+
+    /// Begins a new group of the provided type.
+    ///
+    /// This begins a new scope in every scoped table of the state.
+    pub fn begin_group(&mut self, group_type: GroupType) {
+        self.primitives.begin_scope();
+        self.input_module.begin_scope();
+        self.char_codes.begin_scope();
+        self.registers.begin_scope();
+        self.parameters.begin_scope();
+        self.fonts.begin_scope();
+        self.groups.push(Group::new(group_type));
+    }
+
+    /// Begins a new group that builds a box, like the group of `\hbox{...}`. The context and spec
+    /// are returned with the group when it ends.
+    pub fn begin_box_group(
+        &mut self,
+        group_type: GroupType,
+        context: BoxContext,
+        spec: pack::Spec,
+    ) {
+        self.begin_group(group_type);
+        // The group was just pushed.
+        self.groups.last_mut().unwrap().box_spec = Some((context, spec));
+    }
+
+    /// Begins the group of `\insert n{...}`. The insertion class is returned with the group when
+    /// it ends.
+    pub fn begin_insert_group(&mut self, number: u8) {
+        self.begin_group(GroupType::Insert);
+        // The group was just pushed.
+        self.groups.last_mut().unwrap().insert_number = Some(number);
+    }
+
+    /// Begins the group of one of the lists of `\discretionary`. The part is returned with the
+    /// group when it ends.
+    pub fn begin_discretionary_group(&mut self, part: DiscretionaryPart) {
+        self.begin_group(GroupType::Disc);
+        // The group was just pushed.
+        self.groups.last_mut().unwrap().discretionary_part = Some(part);
+    }
+
+    /// Ends the current group and returns it, or returns `None` if there is no group to end.
+    ///
+    /// All local assignments made in every scoped table since the group began are rolled back.
+    /// The caller is responsible for replaying the group's `\aftergroup` tokens.
+    pub fn end_group(&mut self) -> Option<Group> {
+        let group = self.groups.pop()?;
+        // Every table begins a scope when a group begins, so these scopes always exist.
+        assert!(self.primitives.end_scope());
+        assert!(self.input_module.end_scope());
+        assert!(self.char_codes.end_scope());
+        assert!(self.registers.end_scope());
+        assert!(self.parameters.end_scope());
+        assert!(self.fonts.end_scope());
+        Some(group)
+    }
+
+    /// Returns the number of open groups.
+    pub fn group_level(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Returns the type of the innermost open group, or `GroupType::Bottom` if there is none.
+    pub fn group_type(&self) -> GroupType {
+        match self.groups.last() {
+            None => GroupType::Bottom,
+            Some(group) => group.group_type,
+        }
+    }
+
+    /// Saves a token to be inserted into the input after the current group ends.
+    ///
+    /// As in TeX, the token is discarded if there is no open group.
+    pub fn after_group(&mut self, token: token::Token) {
+        if let Some(group) = self.groups.last_mut() {
+            group.after_group.push(token);
         }
     }
 }
 
+impl<S> Default for BaseState<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // SimpleState is the simplest posible state in Texide. It contains only
 // the base state. The base state is required for all states.
 pub struct SimpleState {
@@ -67,6 +268,12 @@ impl SimpleState {
     }
 }
 
+impl Default for SimpleState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TexState<SimpleState> for SimpleState {
     fn base(&self) -> &BaseState<SimpleState> {
         &self.b
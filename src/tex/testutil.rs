@@ -0,0 +1,271 @@
+//! Utilities for testing primitives.
+//!
+//! The main utility is a test state that contains, in addition to the base state, a list of
+//! integers recorded using the `\record` primitive. `\record` parses a number from the input
+//! and appends it to the list. It can be used to observe internal quantities from TeX input:
+//! ```tex
+//! \record\currentgrouplevel
+//! ```
+//!
+//! Text printed to the terminal is captured, and can be retrieved using `terminal`. The terminal
+//! has no input.
+//!
+//! Characters missing from the current font are dropped, and the null font, which is current
+//! initially, has no characters. Tests that typeset characters select the test font, which has
+//! every character up to 255 with zero dimensions and no ligatures or kerns. Tests of
+//! ligatures and kerns build fonts using `tfm`, like the excerpt of Computer Modern Roman
+//! returned by `cmr10`.
+//!
+//! `\vsize` is the largest dimension, so that pages are only broken by tests that set it.
+//! Material appended in vertical mode still moves to the current page, and `outer_list`
+//! returns the current page followed by the contributions that have not yet moved.
+
+use crate::tex::dimen::{Dimen, Glue, GlueOrder, MAX_DIMEN};
+use crate::tex::driver;
+use crate::tex::font::Font;
+use crate::tex::ligature::STOP_FLAG;
+use crate::tex::node::{FontId, Node};
+use crate::tex::parameter::{DimenParam, GlueParam};
+use crate::tex::parse;
+use crate::tex::primitive;
+use crate::tex::primitive::library;
+use crate::tex::primitive::Primitive;
+use crate::tex::print::{Buffer, Printer};
+use crate::tex::state::{BaseState, TexState};
+use crate::tex::tfm;
+use crate::tex::tfm::{CharInfo, LigKernInstruction, Tag, Tfm};
+use crate::tex::token::catcode;
+use crate::tex::token::token;
+use std::io;
+
+pub struct TestState {
+    b: BaseState<TestState>,
+    pub recorded: Vec<i32>,
+    terminal: Buffer,
+}
+
+impl TexState<TestState> for TestState {
+    fn base(&self) -> &BaseState<TestState> {
+        &self.b
+    }
+    fn base_mut(&mut self) -> &mut BaseState<TestState> {
+        &mut self.b
+    }
+}
+
+fn record_fn(_: token::Token, input: &mut primitive::Input<TestState>) -> anyhow::Result<()> {
+    let n = parse::parse_number(input)?;
+    input.state_mut().recorded.push(n);
+    Ok(())
+}
+
+/// Returns a new test state with the TeX default catcodes, every primitive in the library and
+/// the `\record` primitive.
+pub fn new_state() -> TestState {
+    let mut state = TestState {
+        b: BaseState::new(),
+        recorded: Vec::new(),
+        terminal: Buffer::new(),
+    };
+    state.b.printer = Printer::new(Box::new(state.terminal.clone()));
+    state.b.input_files.set_terminal(Box::new(io::empty()));
+    catcode::set_tex_defaults(&mut state.base_mut().input_module.cat_code_map);
+    state
+        .b
+        .parameters
+        .set_dimen(DimenParam::VSize, Dimen(MAX_DIMEN), true);
+    for (name, p) in library::all() {
+        state.set_primitive(name, p);
+    }
+    state.set_primitive(
+        "record",
+        Primitive::Execution(primitive::Execution::Static(
+            primitive::ExecutionStatic::new("record", record_fn, ""),
+        )),
+    );
+    state
+}
+
+/// Returns the material appended in outer vertical mode that has not been output: the current
+/// page followed by the contributions.
+pub fn outer_list(state: &TestState) -> Vec<Node> {
+    let base = state.base();
+    let mut list = base.page.list.clone();
+    list.extend(base.nest.levels()[0].list.iter().cloned());
+    list
+}
+
+/// The test font, once it has been selected using `select_test_font`.
+pub const TEST_FONT: FontId = FontId(1);
+
+/// Loads the test font and makes it the current font.
+pub fn select_test_font(state: &mut TestState) {
+    let tfm = tfm::Tfm {
+        design_size: 10 << 20,
+        char_infos: vec![
+            tfm::CharInfo {
+                width_index: 1,
+                ..Default::default()
+            };
+            tfm::MAX_CHAR + 1
+        ],
+        widths: vec![0, 0],
+        heights: vec![0],
+        depths: vec![0],
+        italics: vec![0],
+        ..Default::default()
+    };
+    let fonts = &mut state.base_mut().fonts;
+    let id = fonts.add(Font::new("test".to_string(), &tfm, Dimen::from_pt(10)));
+    fonts.set_current(id, true);
+}
+
+/// Sets `\hsize` and `\parfillskip` so that short paragraphs are set as a single line:
+/// `\hsize` is wide, and `\parfillskip` fills the last line as it does in plain TeX.
+pub fn set_single_line_paragraphs(state: &mut TestState) {
+    let parameters = &mut state.base_mut().parameters;
+    parameters.set_dimen(DimenParam::HSize, Dimen::from_pt(1000), true);
+    parameters.set_glue(
+        GlueParam::ParFillSkip,
+        Glue {
+            stretch: Dimen::from_pt(1),
+            stretch_order: GlueOrder::Fil,
+            ..Default::default()
+        },
+        true,
+    );
+}
+
+/// Runs the driver on the provided input.
+pub fn run(mut state: TestState, input: &str) -> anyhow::Result<TestState> {
+    state.base_mut().input_module.open_string(input);
+    driver::run(state)
+}
+
+/// Returns everything printed to the terminal.
+pub fn terminal(state: &TestState) -> String {
+    state.terminal.contents()
+}
+
+/// Returns a ligature instruction for the provided next character, operation and ligature
+/// character.
+pub fn lig(next_char: char, op: u8, c: char) -> LigKernInstruction {
+    LigKernInstruction {
+        skip: 0,
+        next_char: next_char as u8,
+        op,
+        remainder: c as u8,
+    }
+}
+
+/// Returns a kern instruction for the provided next character and kern index.
+pub fn krn(next_char: char, index: u8) -> LigKernInstruction {
+    LigKernInstruction {
+        skip: 0,
+        next_char: next_char as u8,
+        op: 128,
+        remainder: index,
+    }
+}
+
+/// Returns the instruction, marked as the last of its program.
+pub fn stop(instruction: LigKernInstruction) -> LigKernInstruction {
+    LigKernInstruction {
+        skip: STOP_FLAG,
+        ..instruction
+    }
+}
+
+/// Returns a font with design size 10pt and the provided characters, whose widths are fix
+/// words, and ligature/kern programs.
+pub fn tfm(
+    chars: &[(char, i32)],
+    programs: &[(char, u8)],
+    lig_kern: Vec<LigKernInstruction>,
+    kerns: Vec<i32>,
+    params: Vec<i32>,
+) -> Tfm {
+    let mut tfm = Tfm {
+        design_size: 10 << 20,
+        char_infos: vec![CharInfo::default(); 128],
+        widths: vec![0],
+        heights: vec![0],
+        depths: vec![0],
+        italics: vec![0],
+        lig_kern,
+        kerns,
+        params,
+        ..Default::default()
+    };
+    for &(c, width) in chars {
+        tfm.widths.push(width);
+        tfm.char_infos[c as usize].width_index = (tfm.widths.len() - 1) as u8;
+    }
+    for &(c, start) in programs {
+        tfm.char_infos[c as usize].tag = Tag::LigKern(start);
+    }
+    // The font must be valid.
+    Tfm::parse(&tfm.to_bytes()).unwrap()
+}
+
+/// Returns a font with some of the characters, ligatures and kerns of Computer Modern Roman
+/// at 10pt, `cmr10`.
+pub fn cmr10() -> Tfm {
+    let chars = [
+        ('A', 786434),
+        ('V', 786434),
+        ('a', 524290),
+        ('b', 582544),
+        ('c', 466035),
+        ('e', 466035),
+        ('f', 320400),
+        ('i', 291272),
+        ('l', 291272),
+        ('o', 524288),
+        ('.', 291272),
+        ('\'', 291272),
+        ('`', 291272),
+        ('-', 349526),
+        ('\x0B', 611671),
+        ('\x0C', 582544),
+        ('\x0D', 582544),
+        ('\x0E', 873816),
+        ('\x0F', 873816),
+        ('"', 524290),
+        ('\\', 524290),
+        ('{', 524290),
+        ('|', 1048579),
+    ];
+    let lig_kern = vec![
+        // f
+        lig('i', 0, '\x0C'),
+        lig('f', 0, '\x0B'),
+        lig('l', 0, '\x0D'),
+        stop(krn('\'', 0)),
+        // ff
+        lig('i', 0, '\x0E'),
+        lig('l', 0, '\x0F'),
+        stop(krn('\'', 0)),
+        // A
+        stop(krn('V', 1)),
+        // `
+        stop(lig('`', 0, '\\')),
+        // '
+        stop(lig('\'', 0, '"')),
+        // -
+        stop(lig('-', 0, '{')),
+        // en dash
+        stop(lig('-', 0, '|')),
+    ];
+    let programs = [
+        ('f', 0),
+        ('\x0B', 4),
+        ('A', 7),
+        ('`', 8),
+        ('\'', 9),
+        ('-', 10),
+        ('{', 11),
+    ];
+    let params = vec![0, 349526, 174763, 116509, 451470, 1048579, 116509];
+    tfm(&chars, &programs, lig_kern, vec![81557, -116509], params)
+}
@@ -0,0 +1,583 @@
+//! The TeX font metric (TFM) file format.
+//!
+//! A TFM file describes the characters of a font: their widths, heights, depths and italic
+//! corrections, the ligatures and kerns between pairs of characters, the recipes for building
+//! large delimiters out of pieces, and the font's parameters, like the width of a space. It
+//! contains no glyphs. The format is documented in TFtoPL and in part 30 of TeX: The Program.
+//!
+//! This module reads and writes the file format as is. Dimensions are kept as fix words, which
+//! are fractions of the design size in units of 2<sup>-20</sup>; they are scaled to the size
+//! at which a font is loaded by [crate::tex::font::Font].
+//! ```
+//! # use texide::tex::tfm::Tfm;
+//! let tfm = Tfm {
+//!     design_size: 10 << 20,
+//!     widths: vec![0],
+//!     heights: vec![0],
+//!     depths: vec![0],
+//!     italics: vec![0],
+//!     ..Default::default()
+//! };
+//! let bytes = tfm.to_bytes();
+//! assert_eq!(Tfm::parse(&bytes).unwrap(), tfm);
+//! ```
+
+/// The largest character code in a TFM file.
+pub const MAX_CHAR: usize = 255;
+
+/// The contents of a TFM file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tfm {
+    /// The check sum, which should match the check sum in the font's glyph files.
+    pub check_sum: u32,
+    /// The design size of the font in points, as a fix word in units of 2<sup>-20</sup>pt.
+    pub design_size: i32,
+    /// The header words after the check sum and design size, like the coding scheme.
+    pub header: Vec<u32>,
+    /// The code of the first character in `char_infos`.
+    pub first_char: u8,
+    /// The character information for the characters from `first_char` on.
+    pub char_infos: Vec<CharInfo>,
+    pub widths: Vec<i32>,
+    pub heights: Vec<i32>,
+    pub depths: Vec<i32>,
+    pub italics: Vec<i32>,
+    pub lig_kern: Vec<LigKernInstruction>,
+    pub kerns: Vec<i32>,
+    pub extensible: Vec<ExtensibleRecipe>,
+    /// The font parameters, which `\fontdimen` refers to starting from 1. The first parameter,
+    /// the slant, is a pure number; the others are fix words.
+    pub params: Vec<i32>,
+}
+
+/// The character information word of a character.
+///
+/// The indices refer to the width, height, depth and italic correction tables. A character whose
+/// width index is zero doesn't exist in the font.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct CharInfo {
+    pub width_index: u8,
+    pub height_index: u8,
+    pub depth_index: u8,
+    pub italic_index: u8,
+    pub tag: Tag,
+}
+
+/// How the remainder byte of a character information word is interpreted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tag {
+    None(u8),
+    /// The character has a ligature/kern program starting at this instruction.
+    LigKern(u8),
+    /// The character is part of a chain of successively larger characters, and this is the
+    /// next one.
+    NextLarger(u8),
+    /// The character is built out of pieces using this extensible recipe.
+    Extensible(u8),
+}
+
+impl Default for Tag {
+    fn default() -> Self {
+        Tag::None(0)
+    }
+}
+
+/// An instruction of a ligature/kern program.
+///
+/// The program of a character is a sequence of instructions, each of which applies if the next
+/// character is `next_char`. An instruction with `skip >= 128` is the last of its program. In the
+/// first instruction of a program, `skip > 128` means that the program actually starts at
+/// instruction `256 * op + remainder`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct LigKernInstruction {
+    pub skip: u8,
+    pub next_char: u8,
+    /// A ligature if less than 128, otherwise a kern.
+    pub op: u8,
+    /// The ligature character, or the low byte of the kern index.
+    pub remainder: u8,
+}
+
+impl LigKernInstruction {
+    /// Returns the index of the kern of a kern instruction, or `None` if this is a ligature.
+    pub fn kern_index(&self) -> Option<usize> {
+        match self.op >= 128 {
+            true => Some(256 * (self.op as usize - 128) + self.remainder as usize),
+            false => None,
+        }
+    }
+
+    /// Returns the index of the instruction that a program actually starts at, if this is the
+    /// first instruction of a program and points elsewhere.
+    pub fn indirection(&self) -> Option<usize> {
+        match self.skip > 128 {
+            true => Some(256 * self.op as usize + self.remainder as usize),
+            false => None,
+        }
+    }
+}
+
+/// The pieces a large delimiter is built from. Zero means there is no such piece, except for
+/// the repeated piece which always exists.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ExtensibleRecipe {
+    pub top: u8,
+    pub middle: u8,
+    pub bottom: u8,
+    pub repeat: u8,
+}
+
+/// A reader of the 32-bit words of a TFM file.
+struct Words<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Words<'a> {
+    fn next(&mut self) -> anyhow::Result<[u8; 4]> {
+        let word = match self.bytes.get(self.position..self.position + 4) {
+            None => return Err(bad_tfm("the file ends too early")),
+            Some(word) => [word[0], word[1], word[2], word[3]],
+        };
+        self.position += 4;
+        Ok(word)
+    }
+
+    /// Reads the two 16-bit halves of the next word, which must both be less than 2<sup>15</sup>.
+    fn halves(&mut self) -> anyhow::Result<(usize, usize)> {
+        let [a, b, c, d] = self.next()?;
+        if a > 127 || c > 127 {
+            return Err(bad_tfm("a length in the header is too large"));
+        }
+        Ok((
+            u16::from_be_bytes([a, b]) as usize,
+            u16::from_be_bytes([c, d]) as usize,
+        ))
+    }
+
+    /// Reads `n` fix words. As in TeX, a fix word must be less than 16 in absolute value.
+    fn fix_words(&mut self, n: usize) -> anyhow::Result<Vec<i32>> {
+        (0..n)
+            .map(|_| {
+                let word = self.next()?;
+                match word[0] {
+                    0 | 255 => Ok(i32::from_be_bytes(word)),
+                    _ => Err(bad_tfm("a dimension is too large")),
+                }
+            })
+            .collect()
+    }
+}
+
+fn bad_tfm(reason: &str) -> anyhow::Error {
+    anyhow::anyhow!("Bad metric (TFM) file: {}", reason)
+}
+
+impl Tfm {
+    /// Parses a TFM file, checking it in the same way TeX does when loading a font.
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Tfm> {
+        let mut words = Words { bytes, position: 0 };
+        let (lf, lh) = words.halves()?;
+        let (bc, ec) = words.halves()?;
+        let (nw, nh) = words.halves()?;
+        let (nd, ni) = words.halves()?;
+        let (nl, nk) = words.halves()?;
+        let (ne, np) = words.halves()?;
+        // An empty font has bc = ec + 1.
+        if bc > ec + 1 || ec > MAX_CHAR {
+            return Err(bad_tfm("the character range is invalid"));
+        }
+        let num_chars = ec + 1 - bc;
+        if lh < 2 || nw == 0 || nh == 0 || nd == 0 || ni == 0 {
+            return Err(bad_tfm("a table is missing"));
+        }
+        if lf != 6 + lh + num_chars + nw + nh + nd + ni + nl + nk + ne + np {
+            return Err(bad_tfm("the table lengths don't add up to the file length"));
+        }
+        if bytes.len() < 4 * lf {
+            return Err(bad_tfm("the file ends too early"));
+        }
+        let check_sum = u32::from_be_bytes(words.next()?);
+        let design_size = i32::from_be_bytes(words.next()?);
+        // The design size must be at least 1pt.
+        if design_size < 1 << 20 {
+            return Err(bad_tfm("the design size is less than 1pt"));
+        }
+        let header = (2..lh)
+            .map(|_| Ok(u32::from_be_bytes(words.next()?)))
+            .collect::<anyhow::Result<_>>()?;
+        let mut char_infos = Vec::with_capacity(num_chars);
+        for _ in 0..num_chars {
+            let [a, b, c, d] = words.next()?;
+            char_infos.push(CharInfo {
+                width_index: a,
+                height_index: b >> 4,
+                depth_index: b & 15,
+                italic_index: c >> 2,
+                tag: match c & 3 {
+                    0 => Tag::None(d),
+                    1 => Tag::LigKern(d),
+                    2 => Tag::NextLarger(d),
+                    _ => Tag::Extensible(d),
+                },
+            });
+        }
+        let widths = words.fix_words(nw)?;
+        let heights = words.fix_words(nh)?;
+        let depths = words.fix_words(nd)?;
+        let italics = words.fix_words(ni)?;
+        if widths[0] != 0 || heights[0] != 0 || depths[0] != 0 || italics[0] != 0 {
+            return Err(bad_tfm("the first entry of a dimension table is not zero"));
+        }
+        let lig_kern = (0..nl)
+            .map(|_| {
+                let [skip, next_char, op, remainder] = words.next()?;
+                Ok(LigKernInstruction {
+                    skip,
+                    next_char,
+                    op,
+                    remainder,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let kerns = words.fix_words(nk)?;
+        let extensible = (0..ne)
+            .map(|_| {
+                let [top, middle, bottom, repeat] = words.next()?;
+                Ok(ExtensibleRecipe {
+                    top,
+                    middle,
+                    bottom,
+                    repeat,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let mut params = Vec::with_capacity(np);
+        for i in 0..np {
+            let word = words.next()?;
+            // The slant is a pure number, and can be larger than other fix words.
+            if i > 0 && word[0] != 0 && word[0] != 255 {
+                return Err(bad_tfm("a parameter is too large"));
+            }
+            params.push(i32::from_be_bytes(word));
+        }
+        let tfm = Tfm {
+            check_sum,
+            design_size,
+            header,
+            first_char: match num_chars {
+                0 => 0,
+                _ => bc as u8,
+            },
+            char_infos,
+            widths,
+            heights,
+            depths,
+            italics,
+            lig_kern,
+            kerns,
+            extensible,
+            params,
+        };
+        tfm.check()?;
+        Ok(tfm)
+    }
+
+    /// Checks that the indices in the file refer to entries and characters that exist.
+    fn check(&self) -> anyhow::Result<()> {
+        for (i, info) in self.char_infos.iter().enumerate() {
+            if info.width_index == 0 {
+                continue;
+            }
+            if info.width_index as usize >= self.widths.len()
+                || info.height_index as usize >= self.heights.len()
+                || info.depth_index as usize >= self.depths.len()
+                || info.italic_index as usize >= self.italics.len()
+            {
+                return Err(bad_tfm("a dimension index is out of range"));
+            }
+            match info.tag {
+                Tag::None(_) => {}
+                Tag::LigKern(start) => {
+                    if start as usize >= self.lig_kern.len() {
+                        return Err(bad_tfm("a ligature/kern program is out of range"));
+                    }
+                }
+                Tag::NextLarger(next) => {
+                    self.check_existence(next)?;
+                    self.check_no_cycle(self.first_char as usize + i)?;
+                }
+                Tag::Extensible(recipe) => {
+                    if recipe as usize >= self.extensible.len() {
+                        return Err(bad_tfm("an extensible recipe is out of range"));
+                    }
+                }
+            }
+        }
+        let boundary_char = match self.lig_kern.first() {
+            Some(first) if first.skip == 255 => Some(first.next_char),
+            _ => None,
+        };
+        for (i, instruction) in self.lig_kern.iter().enumerate() {
+            if instruction.skip > 128 {
+                if instruction.indirection().unwrap() >= self.lig_kern.len() {
+                    return Err(bad_tfm("a ligature/kern program is out of range"));
+                }
+                continue;
+            }
+            if Some(instruction.next_char) != boundary_char {
+                self.check_existence(instruction.next_char)?;
+            }
+            match instruction.kern_index() {
+                None => self.check_existence(instruction.remainder)?,
+                Some(k) if k >= self.kerns.len() => {
+                    return Err(bad_tfm("a kern index is out of range"));
+                }
+                Some(_) => {}
+            }
+            if instruction.skip < 128 && i + instruction.skip as usize + 1 >= self.lig_kern.len() {
+                return Err(bad_tfm("a ligature/kern program skips past the end"));
+            }
+        }
+        for recipe in &self.extensible {
+            for piece in [recipe.top, recipe.middle, recipe.bottom] {
+                if piece != 0 {
+                    self.check_existence(piece)?;
+                }
+            }
+            self.check_existence(recipe.repeat)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the character information of a character, or `None` if it doesn't exist.
+    pub fn char_info(&self, c: u8) -> Option<&CharInfo> {
+        let i = (c as usize).checked_sub(self.first_char as usize)?;
+        self.char_infos.get(i).filter(|info| info.width_index != 0)
+    }
+
+    fn check_existence(&self, c: u8) -> anyhow::Result<()> {
+        match self.char_info(c) {
+            None => Err(bad_tfm("a character that doesn't exist is referred to")),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Checks that the chain of larger characters starting at the character doesn't loop.
+    fn check_no_cycle(&self, c: usize) -> anyhow::Result<()> {
+        let mut current = c;
+        for _ in 0..=MAX_CHAR {
+            match self.char_info(current as u8).map(|info| info.tag) {
+                Some(Tag::NextLarger(next)) if next as usize == c => {
+                    return Err(bad_tfm("a chain of larger characters is a cycle"));
+                }
+                Some(Tag::NextLarger(next)) => current = next as usize,
+                _ => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the file contents. The tables are written as they are, except that empty
+    /// dimension tables are given the zero entry every TFM file has.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let with_zero = |table: &Vec<i32>| match table.is_empty() {
+            true => vec![0],
+            false => table.clone(),
+        };
+        let (widths, heights, depths, italics) = (
+            with_zero(&self.widths),
+            with_zero(&self.heights),
+            with_zero(&self.depths),
+            with_zero(&self.italics),
+        );
+        let (bc, ec) = match self.char_infos.len() {
+            0 => (1, 0),
+            n => (self.first_char as usize, self.first_char as usize + n - 1),
+        };
+        let lh = 2 + self.header.len();
+        let lengths = [
+            lh,
+            bc,
+            ec,
+            widths.len(),
+            heights.len(),
+            depths.len(),
+            italics.len(),
+            self.lig_kern.len(),
+            self.kerns.len(),
+            self.extensible.len(),
+            self.params.len(),
+        ];
+        let lf = 6 + lengths.iter().sum::<usize>() - bc - ec + self.char_infos.len();
+        let mut bytes = Vec::with_capacity(4 * lf);
+        for n in std::iter::once(lf).chain(lengths) {
+            bytes.extend((n as u16).to_be_bytes());
+        }
+        bytes.extend(self.check_sum.to_be_bytes());
+        bytes.extend(self.design_size.to_be_bytes());
+        for word in &self.header {
+            bytes.extend(word.to_be_bytes());
+        }
+        for info in &self.char_infos {
+            let (tag, remainder) = match info.tag {
+                Tag::None(r) => (0, r),
+                Tag::LigKern(r) => (1, r),
+                Tag::NextLarger(r) => (2, r),
+                Tag::Extensible(r) => (3, r),
+            };
+            bytes.extend([
+                info.width_index,
+                info.height_index << 4 | info.depth_index,
+                info.italic_index << 2 | tag,
+                remainder,
+            ]);
+        }
+        for table in [&widths, &heights, &depths, &italics] {
+            for word in table {
+                bytes.extend(word.to_be_bytes());
+            }
+        }
+        for i in &self.lig_kern {
+            bytes.extend([i.skip, i.next_char, i.op, i.remainder]);
+        }
+        for word in &self.kerns {
+            bytes.extend(word.to_be_bytes());
+        }
+        for r in &self.extensible {
+            bytes.extend([r.top, r.middle, r.bottom, r.repeat]);
+        }
+        for word in &self.params {
+            bytes.extend(word.to_be_bytes());
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A font with the characters A and B, in which A followed by B is a ligature B, and B
+    /// followed by A is kerned.
+    fn tfm() -> Tfm {
+        Tfm {
+            check_sum: 0x12345678,
+            design_size: 10 << 20,
+            header: vec![7],
+            first_char: b'A',
+            char_infos: vec![
+                CharInfo {
+                    width_index: 1,
+                    height_index: 1,
+                    depth_index: 0,
+                    italic_index: 0,
+                    tag: Tag::LigKern(0),
+                },
+                CharInfo {
+                    width_index: 2,
+                    height_index: 1,
+                    depth_index: 1,
+                    italic_index: 1,
+                    tag: Tag::LigKern(1),
+                },
+            ],
+            widths: vec![0, 1 << 19, 1 << 18],
+            heights: vec![0, 3 << 18],
+            depths: vec![0, 1 << 16],
+            italics: vec![0, 1 << 14],
+            lig_kern: vec![
+                LigKernInstruction {
+                    skip: 128,
+                    next_char: b'B',
+                    op: 0,
+                    remainder: b'B',
+                },
+                LigKernInstruction {
+                    skip: 128,
+                    next_char: b'A',
+                    op: 128,
+                    remainder: 0,
+                },
+            ],
+            kerns: vec![-(1 << 16)],
+            extensible: vec![],
+            params: vec![1 << 18, 1 << 18, 1 << 17],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let tfm = tfm();
+        assert_eq!(Tfm::parse(&tfm.to_bytes()).unwrap(), tfm);
+    }
+
+    #[test]
+    fn empty_font() {
+        let tfm = Tfm {
+            design_size: 1 << 20,
+            widths: vec![0],
+            heights: vec![0],
+            depths: vec![0],
+            italics: vec![0],
+            ..Default::default()
+        };
+        assert_eq!(Tfm::parse(&tfm.to_bytes()).unwrap(), tfm);
+    }
+
+    #[test]
+    fn char_info() {
+        let tfm = tfm();
+        assert_eq!(tfm.char_info(b'A').unwrap().width_index, 1);
+        assert_eq!(tfm.char_info(b'B').unwrap().width_index, 2);
+        assert!(tfm.char_info(b'C').is_none());
+        assert!(tfm.char_info(b'@').is_none());
+    }
+
+    fn error(tfm: &Tfm) -> String {
+        Tfm::parse(&tfm.to_bytes()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn bad_files() {
+        assert!(Tfm::parse(&[]).is_err());
+        let bytes = tfm().to_bytes();
+        assert!(Tfm::parse(&bytes[..bytes.len() - 1])
+            .unwrap_err()
+            .to_string()
+            .contains("ends too early"));
+        let mut t = tfm();
+        t.design_size = 1 << 19;
+        assert!(error(&t).contains("design size"));
+        let mut t = tfm();
+        t.widths[1] = 16 << 20;
+        assert!(error(&t).contains("too large"));
+        let mut t = tfm();
+        t.char_infos[0].height_index = 2;
+        assert!(error(&t).contains("dimension index"));
+        let mut t = tfm();
+        t.lig_kern[0].remainder = b'C';
+        assert!(error(&t).contains("doesn't exist"));
+        let mut t = tfm();
+        t.lig_kern[1].op = 129;
+        assert!(error(&t).contains("kern index"));
+        let mut t = tfm();
+        t.char_infos[0].tag = Tag::NextLarger(b'B');
+        t.char_infos[1].tag = Tag::NextLarger(b'A');
+        assert!(error(&t).contains("cycle"));
+        let mut t = tfm();
+        t.widths[0] = 1;
+        assert!(error(&t).contains("first entry"));
+    }
+
+    #[test]
+    fn length_mismatch() {
+        let mut bytes = tfm().to_bytes();
+        bytes[1] += 1;
+        bytes.extend([0, 0, 0, 0]);
+        assert!(Tfm::parse(&bytes)
+            .unwrap_err()
+            .to_string()
+            .contains("don't add up"));
+    }
+}
@@ -19,11 +19,12 @@
 //! | `Space`        | 10 | ` `  | Whitespace.
 //! | `Letter`       | 11 | `A`  | A character that can be used as a control sequence name.
 //! | `Other`        | 12 | `@`  | A character than cannot be used as a control sequence name.
-//! | `Active`       | 13 |      |
+//! | `Active`       | 13 | `~`  | A character that behaves like a control sequence and can be given a meaning using `\def` or `\let`.
 //! | `Comment`*     | 14 | `%`  | Denotes the beginning of a comment; all remaining characters on the line will be ignored.
 //! | `Invalid`*     | 15 |      | An invalid character; if this is read in the input, a error will fire.
 //!
-use crate::datastructures::scopedmap::ScopedMap;
+use crate::datastructures::scopedcharmap::ScopedCharMap;
+use crate::tex::charcode;
 
 use CatCode::*;
 use RawCatCode::*;
@@ -110,7 +111,7 @@ impl RawCatCode {
             9 => Some(Ignored),
             14 => Some(Comment),
             15 => Some(Invalid),
-            int => CatCode::from_int(int).map(|cat_code| Regular(cat_code)),
+            int => CatCode::from_int(int).map(Regular),
         }
     }
 }
@@ -122,15 +123,32 @@ pub fn or_default(c: Option<&RawCatCode>) -> RawCatCode {
     }
 }
 
+/// Returns the catcodes that IniTeX starts with: letters have catcode letter, and only the
+/// escape, end of line, space, comment, ignored and invalid characters are otherwise set.
+/// All other characters have the default catcode other.
+pub fn initex_defaults() -> ScopedCharMap<RawCatCode> {
+    let mut cat_code_map = ScopedCharMap::new();
+    cat_code_map.extend(IntoIterator::into_iter([
+        ('\\', Escape),
+        ('\n', EndOfLine),
+        (' ', Regular(Space)),
+        ('%', Comment),
+        ('\u{0}', Ignored),
+        ('\u{7f}', Invalid),
+    ]));
+    cat_code_map.extend(('A'..='Z').chain('a'..='z').map(|c| (c, Regular(Letter))));
+    cat_code_map
+}
+
 // TODO: the cat code wrapper should have nice insert ops for regular catcodes
-pub fn tex_defaults() -> ScopedMap<char, RawCatCode> {
-    let mut cat_code_map = ScopedMap::new();
+pub fn tex_defaults() -> ScopedCharMap<RawCatCode> {
+    let mut cat_code_map = ScopedCharMap::new();
     set_tex_defaults(&mut cat_code_map);
     cat_code_map
 }
 
-pub fn set_tex_defaults(cat_code_map: &mut ScopedMap<char, RawCatCode>) {
-    cat_code_map.extend(std::array::IntoIter::new([
+pub fn set_tex_defaults(cat_code_map: &mut ScopedCharMap<RawCatCode>) {
+    cat_code_map.extend(IntoIterator::into_iter([
         ('\\', Escape),
         ('{', Regular(BeginGroup)),
         ('}', Regular(EndGroup)),
@@ -201,6 +219,38 @@ pub fn set_tex_defaults(cat_code_map: &mut ScopedMap<char, RawCatCode>) {
     ]))
 }
 
+/// Returns the plain TeX defaults extended to Unicode, as in XeTeX and LuaTeX.
+pub fn unicode_defaults() -> ScopedCharMap<RawCatCode> {
+    let mut cat_code_map = ScopedCharMap::new();
+    set_unicode_defaults(&mut cat_code_map);
+    cat_code_map
+}
+
+/// Sets the plain TeX defaults and additionally gives every alphabetic Unicode character
+/// catcode letter.
+///
+/// A character is alphabetic if it has the Unicode `Alphabetic` property. This consists of
+/// the letter general categories (`Lu`, `Ll`, `Lt`, `Lm`, `Lo`), letter numbers (`Nl`) and the
+/// combining marks that are used as parts of letters, like the vowel signs of Indic scripts.
+/// ```
+/// # use texide::tex::token::catcode;
+/// # use texide::tex::token::catcode::{CatCode, RawCatCode};
+/// let cat_code_map = catcode::unicode_defaults();
+/// for c in ['é', 'Ж', 'λ', '日'] {
+///     assert_eq!(cat_code_map.get(&c), Some(&RawCatCode::Regular(CatCode::Letter)));
+/// }
+/// assert_eq!(cat_code_map.get(&'€'), None);
+/// assert_eq!(cat_code_map.get(&'\\'), Some(&RawCatCode::Escape));
+/// ```
+pub fn set_unicode_defaults(cat_code_map: &mut ScopedCharMap<RawCatCode>) {
+    set_tex_defaults(cat_code_map);
+    cat_code_map.extend(
+        charcode::all_chars()
+            .filter(|c| !c.is_ascii() && c.is_alphabetic())
+            .map(|c| (c, Regular(Letter))),
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use crate::tex::token::catcode::RawCatCode::Regular;
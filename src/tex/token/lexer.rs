@@ -14,7 +14,7 @@
 //! whitespace characters after a control sequence. The correct result is thus the control sequence
 //! followed by the single letter token B.
 
-use crate::datastructures::scopedmap::ScopedMap;
+use crate::datastructures::scopedcharmap::ScopedCharMap;
 use crate::tex::error;
 use crate::tex::token::catcode::{CatCode, RawCatCode};
 use crate::tex::token::token;
@@ -58,7 +58,7 @@ pub struct Lexer<T: io::BufRead> {
 impl<T: io::BufRead> Lexer<T> {
     pub fn next(
         &mut self,
-        map: &ScopedMap<char, RawCatCode>,
+        map: &ScopedCharMap<RawCatCode>,
     ) -> Result<Option<token::Token>, LexerError> {
         while let Some(raw_token) = self.raw_lexer.next(map)? {
             let value = match raw_token.code {
@@ -105,10 +105,7 @@ impl<T: io::BufRead> Lexer<T> {
         Ok(None)
     }
 
-    fn consume_whitespace(
-        &mut self,
-        map: &ScopedMap<char, RawCatCode>,
-    ) -> Result<usize, LexerError> {
+    fn consume_whitespace(&mut self, map: &ScopedCharMap<RawCatCode>) -> Result<usize, LexerError> {
         let mut num_new_lines: usize = 0;
         while let Some(RawToken { code, .. }) = self.raw_lexer.peek(map)? {
             num_new_lines += match code {
@@ -126,7 +123,7 @@ impl<T: io::BufRead> Lexer<T> {
     fn read_control_sequence(
         &mut self,
         raw_token: &RawToken,
-        map: &ScopedMap<char, RawCatCode>,
+        map: &ScopedCharMap<RawCatCode>,
     ) -> Result<token::Value, LexerError> {
         let name = match self.raw_lexer.next(map)? {
             None => {
@@ -165,12 +162,22 @@ impl<T: io::BufRead> Lexer<T> {
     }
 
     pub fn new(file: T) -> Lexer<T> {
+        Lexer::with_file_name(file, "")
+    }
+
+    /// Returns a lexer that reads the file, whose name appears in the sources of its tokens.
+    pub fn with_file_name(file: T, file_name: &str) -> Lexer<T> {
         Lexer {
-            raw_lexer: RawLexer::new(file),
+            raw_lexer: RawLexer::new(file, file_name),
             trim_next_whitespace: false,
             new_par_control_sequence_name: "par".to_string(),
         }
     }
+
+    /// Makes the current line the last line that is read, as `\endinput` does.
+    pub fn end_after_current_line(&mut self) {
+        self.raw_lexer.last_line = true;
+    }
 }
 
 struct RawToken {
@@ -184,10 +191,12 @@ struct RawLexer<T: io::BufRead> {
     current_line: Rc<token::Line>,
     current_line_as_chars: Vec<char>,
     next_char_index: usize,
+    /// Whether the current line is the last line that is read.
+    last_line: bool,
 }
 
 impl<T: io::BufRead> RawLexer<T> {
-    fn next(&mut self, map: &ScopedMap<char, RawCatCode>) -> Result<Option<RawToken>, LexerError> {
+    fn next(&mut self, map: &ScopedCharMap<RawCatCode>) -> Result<Option<RawToken>, LexerError> {
         let result = self.peek(map);
         self.advance();
         result
@@ -197,8 +206,9 @@ impl<T: io::BufRead> RawLexer<T> {
         self.next_char_index += 1;
     }
 
-    fn peek(&mut self, map: &ScopedMap<char, RawCatCode>) -> Result<Option<RawToken>, LexerError> {
+    fn peek(&mut self, map: &ScopedCharMap<RawCatCode>) -> Result<Option<RawToken>, LexerError> {
         self.fill_buffer()?;
+        while self.reduce_expanded_character(map) {}
         Ok(self
             .current_line_as_chars
             .get(self.next_char_index)
@@ -216,10 +226,54 @@ impl<T: io::BufRead> RawLexer<T> {
             }))
     }
 
+    /// Replaces the `^^` notation for a character at the current position of the line by the
+    /// character itself, and returns true if there was such notation. As in TeX, the notation is
+    /// two identical superscript characters followed either by two lowercase hexadecimal digits,
+    /// which are the code of the character, or by a character whose code c is less than 128,
+    /// which stands for the character with code c+64 or c-64:
+    /// ```tex
+    /// \catcode`\^^I=10 % ^^I is the tab character, code 9
+    /// \message{^^5c}   % ^^5c is the backslash
+    /// ```
+    /// The line is modified in place, so that the result is read again, which allows notation
+    /// like `^^5e^41` to reduce twice.
+    fn reduce_expanded_character(&mut self, map: &ScopedCharMap<RawCatCode>) -> bool {
+        let chars = match self.current_line_as_chars.get(self.next_char_index..) {
+            None => return false,
+            Some(chars) => chars,
+        };
+        let c = match chars {
+            [c, d, ..] if c == d => *c,
+            _ => return false,
+        };
+        if map.get(&c) != Some(&RawCatCode::Regular(CatCode::Superscript)) {
+            return false;
+        }
+        let (reduced, len) = match chars[2..] {
+            [h, l, ..] if is_lowercase_hex_digit(h) && is_lowercase_hex_digit(l) => {
+                let code = h.to_digit(16).unwrap() * 16 + l.to_digit(16).unwrap();
+                (char::from_u32(code).unwrap(), 4)
+            }
+            [x, ..] if (x as u32) < 128 && x != '\n' => {
+                let code = match (x as u32) < 64 {
+                    true => x as u32 + 64,
+                    false => x as u32 - 64,
+                };
+                (char::from_u32(code).unwrap(), 3)
+            }
+            _ => return false,
+        };
+        let i = self.next_char_index;
+        self.current_line_as_chars.splice(i..i + len, [reduced]);
+        true
+    }
+
     fn fill_buffer(&mut self) -> Result<(), LexerError> {
         if self.next_char_index >= self.current_line_as_chars.len() {
             let mut line = String::new();
-            self.reader.read_line(&mut line)?;
+            if !self.last_line {
+                self.reader.read_line(&mut line)?;
+            }
             self.current_line_as_chars = Vec::from_iter(line.chars());
             self.next_char_index = 0;
             self.current_line = Rc::new(token::Line {
@@ -231,20 +285,25 @@ impl<T: io::BufRead> RawLexer<T> {
         Ok(())
     }
 
-    pub fn new(file: T) -> RawLexer<T> {
+    pub fn new(file: T, file_name: &str) -> RawLexer<T> {
         RawLexer {
             reader: file,
             current_line_as_chars: Vec::new(),
             next_char_index: 0,
+            last_line: false,
             current_line: Rc::new(token::Line {
                 content: "".to_string(),
-                line_number: -1,
-                file: Rc::new("".to_string()),
+                line_number: 0,
+                file: Rc::new(file_name.to_string()),
             }),
         }
     }
 }
 
+fn is_lowercase_hex_digit(c: char) -> bool {
+    c.is_ascii_digit() || ('a'..='f').contains(&c)
+}
+
 // what about the TeX edge case \input{file}b where file ends in \a. Do as \ab control sequence
 // get created? If so, can't isolate inputs behind an expansion runner
 #[cfg(test)]
@@ -255,13 +314,12 @@ mod tests {
     use crate::tex::token::token::Value;
     use crate::tex::token::token::Value::Character;
     use crate::tex::token::token::Value::ControlSequence;
-    use std::array::IntoIter;
 
     #[test]
     fn case_1() {
         run_test(
             r"\a{b}",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 ControlSequence('\\', "a".to_string()),
                 Character('{', BeginGroup),
                 Character('b', Letter),
@@ -274,7 +332,7 @@ mod tests {
     fn case_2() {
         run_test(
             r"\a b",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 ControlSequence('\\', "a".to_string()),
                 Character('b', Letter),
             ])),
@@ -284,7 +342,7 @@ mod tests {
     fn case_3() {
         run_test(
             "\\a  b",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 ControlSequence('\\', "a".to_string()),
                 Character('b', Letter),
             ])),
@@ -294,7 +352,7 @@ mod tests {
     fn case_4() {
         run_test(
             "\\a\n b",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 ControlSequence('\\', "a".to_string()),
                 Character('b', Letter),
             ])),
@@ -304,7 +362,7 @@ mod tests {
     fn case_5() {
         run_test(
             "\\ABC{D}",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 ControlSequence('\\', "ABC".to_string()),
                 Character('{', BeginGroup),
                 Character('D', Letter),
@@ -316,14 +374,17 @@ mod tests {
     fn multi_character_control_sequence() {
         run_test(
             "\\ABC",
-            Vec::from_iter(IntoIter::new([ControlSequence('\\', "ABC".to_string())])),
+            Vec::from_iter(IntoIterator::into_iter([ControlSequence(
+                '\\',
+                "ABC".to_string(),
+            )])),
         );
     }
     #[test]
     fn single_non_letter_character_control_sequence() {
         run_test(
             "\\{{",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 ControlSequence('\\', "{".to_string()),
                 Character('{', BeginGroup),
             ])),
@@ -334,7 +395,7 @@ mod tests {
     fn single_non_letter_character_control_sequence_followed_by_letter() {
         run_test(
             "\\{A",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 ControlSequence('\\', "{".to_string()),
                 Character('A', Letter),
             ])),
@@ -345,7 +406,7 @@ mod tests {
     fn case_8() {
         run_test(
             "A%a comment here\nC",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 Character('C', Letter),
             ])),
@@ -355,7 +416,7 @@ mod tests {
     fn case_9() {
         run_test(
             "A%a comment here\n%A second comment\nC",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 Character('C', Letter),
             ])),
@@ -365,14 +426,14 @@ mod tests {
     fn case_10() {
         run_test(
             "A%a comment here",
-            Vec::from_iter(IntoIter::new([Character('A', Letter)])),
+            Vec::from_iter(IntoIterator::into_iter([Character('A', Letter)])),
         );
     }
     #[test]
     fn case_11() {
         run_test(
             "A%\n B",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 Character('B', Letter),
             ])),
@@ -382,7 +443,7 @@ mod tests {
     fn case_12() {
         run_test(
             "A%\n\n B",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 ControlSequence('\\', "par".to_string()),
                 Character('B', Letter),
@@ -393,7 +454,7 @@ mod tests {
     fn case_13() {
         run_test(
             "\\A %\nB",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 ControlSequence('\\', "A".to_string()),
                 Character('B', Letter),
             ])),
@@ -403,7 +464,7 @@ mod tests {
     fn double_space_creates_one_space() {
         run_test(
             "A  B",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 Character(' ', Space),
                 Character('B', Letter),
@@ -414,7 +475,7 @@ mod tests {
     fn single_newline_creates_one_space() {
         run_test(
             "A\nB",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 Character('\n', Space),
                 Character('B', Letter),
@@ -425,7 +486,7 @@ mod tests {
     fn space_and_newline_creates_space() {
         run_test(
             "A \nB",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 Character(' ', Space),
                 Character('B', Letter),
@@ -436,7 +497,7 @@ mod tests {
     fn double_newline_creates_par() {
         run_test(
             "A\n\nB",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 ControlSequence('\\', "par".to_string()),
                 Character('B', Letter),
@@ -447,7 +508,7 @@ mod tests {
     fn newline_space_newline_creates_par() {
         run_test(
             "A\n \nB",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 ControlSequence('\\', "par".to_string()),
                 Character('B', Letter),
@@ -459,7 +520,7 @@ mod tests {
     fn non_standard_whitespace_character() {
         run_test(
             "AYB",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 Character('Y', Space),
                 Character('B', Letter),
@@ -471,7 +532,7 @@ mod tests {
     fn non_standard_newline_character() {
         run_test(
             "AXB",
-            Vec::from_iter(IntoIter::new([
+            Vec::from_iter(IntoIterator::into_iter([
                 Character('A', Letter),
                 Character('X', Space),
                 Character('B', Letter),
@@ -484,6 +545,68 @@ mod tests {
         run_test("Z", Vec::new());
     }
 
+    #[test]
+    fn expanded_characters() {
+        run_test(
+            "^^41^^3b^^I^^?",
+            Vec::from_iter(IntoIterator::into_iter([
+                Character('A', Letter),
+                Character(';', Other),
+                Character('\t', Other),
+                Character('\u{7f}', Other),
+            ])),
+        );
+    }
+
+    #[test]
+    fn expanded_characters_are_reduced_again() {
+        run_test(
+            "^^5e^41",
+            Vec::from_iter(IntoIterator::into_iter([Character('A', Letter)])),
+        );
+    }
+
+    #[test]
+    fn expanded_characters_in_control_sequence_names() {
+        run_test(
+            "\\^^M\\a^^62c",
+            Vec::from_iter(IntoIterator::into_iter([
+                ControlSequence('\\', "\r".to_string()),
+                ControlSequence('\\', "abc".to_string()),
+            ])),
+        );
+    }
+
+    #[test]
+    fn incomplete_expanded_characters() {
+        run_test(
+            "^^",
+            Vec::from_iter(IntoIterator::into_iter([
+                Character('^', Superscript),
+                Character('^', Superscript),
+            ])),
+        );
+        run_test(
+            "^A",
+            Vec::from_iter(IntoIterator::into_iter([
+                Character('^', Superscript),
+                Character('A', Letter),
+            ])),
+        );
+    }
+
+    #[test]
+    fn lines_are_numbered_from_one() {
+        // Line numbers appear in error messages and, as in TeX's `l.1`, start at 1.
+        let mut lexer = Lexer::new("A\nB".as_bytes());
+        let map = catcode::tex_defaults();
+        let mut line_numbers = Vec::new();
+        while let Some(t) = lexer.next(&map).unwrap() {
+            line_numbers.push(t.source.unwrap().line.line_number);
+        }
+        assert_eq!(line_numbers, vec![1, 1, 2]);
+    }
+
     fn run_test(input: &str, expected: Vec<Value>) {
         let mut lexer = Lexer::new(input.as_bytes());
         let mut map = catcode::tex_defaults();
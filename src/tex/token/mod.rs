@@ -4,4 +4,6 @@
 pub mod catcode;
 pub mod lexer;
 pub mod stream;
+#[allow(clippy::module_inception)]
 pub mod token;
+pub mod write;
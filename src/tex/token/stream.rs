@@ -71,13 +71,13 @@
 //! what the next token is. For example:
 //!
 //! 1. When reading tokens from a file, peeking at the next token may involve reading more bytes
-//!     from the file and thus mutating the file pointer. This mutations is easy to undo in
-//!     general.
+//!    from the file and thus mutating the file pointer. This mutations is easy to undo in
+//!    general.
 //!
 //! 1. When performing expansion on a stream, the next token in the stream may need to be expanded
-//!     rather than returned. The next token will be the first token in the expansion in this case,
-//!     or the following token in the remaining stream if the expansion returns no tokens.
-//!     This mutation is generally irreversable.
+//!    rather than returned. The next token will be the first token in the expansion in this case,
+//!    or the following token in the remaining stream if the expansion returns no tokens.
+//!    This mutation is generally irreversable.
 //!
 //! # Immutable peeking
 //!
@@ -132,9 +132,6 @@
 
 use crate::tex::token::token;
 
-
-
-
 /// A `Stream` is a source of tokens that are possibly generated on demand.
 ///
 /// See the module documentation for details.
@@ -269,7 +266,13 @@ impl<T: Stream> StackStream<T> {
 
     /// Create a new empty stack stream.
     pub fn new() -> StackStream<T> {
-        return StackStream { stack: Vec::new() };
+        StackStream { stack: Vec::new() }
+    }
+}
+
+impl<T: Stream> Default for StackStream<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
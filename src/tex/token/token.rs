@@ -1,4 +1,4 @@
-///! Definition of the token type and related types
+//! Definition of the token type and related types
 use crate::tex::token::catcode::CatCode;
 use std::rc::Rc;
 
@@ -16,10 +16,37 @@ pub struct Token {
 
 impl Token {
     pub fn new_letter(c: char) -> Token {
-        return Token {
-            value: Value::Character(c, CatCode::Letter),
+        Token::new_character(c, CatCode::Letter)
+    }
+
+    pub fn new_other(c: char) -> Token {
+        Token::new_character(c, CatCode::Other)
+    }
+
+    pub fn new_space(c: char) -> Token {
+        Token::new_character(c, CatCode::Space)
+    }
+
+    pub fn new_begin_group(c: char) -> Token {
+        Token::new_character(c, CatCode::BeginGroup)
+    }
+
+    pub fn new_end_group(c: char) -> Token {
+        Token::new_character(c, CatCode::EndGroup)
+    }
+
+    pub fn new_character(c: char, cat_code: CatCode) -> Token {
+        Token {
+            value: Value::Character(c, cat_code),
+            source: None,
+        }
+    }
+
+    pub fn new_control_sequence(name: &str) -> Token {
+        Token {
+            value: Value::ControlSequence('\\', name.to_string()),
             source: None,
-        };
+        }
     }
 }
 
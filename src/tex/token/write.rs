@@ -0,0 +1,166 @@
+//! Conversion of token lists to text, as TeX does when showing token lists.
+//!
+//! The conventions follow the `show_token_list` procedure of TeX: control sequences are printed
+//! with the escape character, and a space is printed after a control sequence whose name is a
+//! word or a single letter. Parameter characters are doubled.
+//! ```
+//! # use texide::tex::token::token::Token;
+//! # use texide::tex::token::write;
+//! let tokens = vec![
+//!     Token::new_control_sequence("hbox"),
+//!     Token::new_begin_group('{'),
+//!     Token::new_control_sequence("%"),
+//!     Token::new_character('#', texide::tex::token::catcode::CatCode::Parameter),
+//!     Token::new_end_group('}'),
+//! ];
+//! assert_eq!(write::write_tokens(&tokens), r"\hbox {\%##}");
+//! ```
+//!
+//! In TeX the output depends on the state: the escape character is `\escapechar`, and whether a
+//! space follows a single character control sequence depends on the catcode of the character.
+//! These settings are captured by a [Style].
+
+use crate::datastructures::scopedcharmap::ScopedCharMap;
+use crate::tex::token::catcode::{CatCode, RawCatCode};
+use crate::tex::token::token::{Token, Value};
+
+/// Settings that determine how tokens are written.
+#[derive(Clone, Copy)]
+pub struct Style<'a> {
+    /// The character written before the names of control sequences. If `None`, no escape
+    /// character is written. This corresponds to `\escapechar` in TeX.
+    pub escape_char: Option<char>,
+    /// The catcodes used to decide whether a single character control sequence is a letter and
+    /// hence followed by a space. If `None`, alphabetic characters are considered letters.
+    pub cat_codes: Option<&'a ScopedCharMap<RawCatCode>>,
+}
+
+impl Default for Style<'_> {
+    fn default() -> Self {
+        Style {
+            escape_char: Some('\\'),
+            cat_codes: None,
+        }
+    }
+}
+
+impl Style<'_> {
+    /// Appends the text representation of the tokens to the string.
+    pub fn write_tokens(&self, s: &mut String, tokens: &[Token]) {
+        for token in tokens {
+            self.write_token(s, token);
+        }
+    }
+
+    /// Appends the text representation of the token to the string.
+    pub fn write_token(&self, s: &mut String, token: &Token) {
+        match &token.value {
+            Value::ControlSequence(_, name) => self.write_control_sequence(s, name),
+            Value::Character(c, CatCode::Parameter) => {
+                s.push(*c);
+                s.push(*c);
+            }
+            Value::Character(c, _) => s.push(*c),
+        }
+    }
+
+    /// Appends the control sequence with the provided name to the string, followed by a space
+    /// if the name is a word or a single letter. This is `print_cs` in TeX.
+    pub fn write_control_sequence(&self, s: &mut String, name: &str) {
+        self.write_control_sequence_name(s, name);
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => s.push(' '),
+            (Some(c), None) if !self.is_letter(c) => {}
+            _ => s.push(' '),
+        }
+    }
+
+    /// Appends the control sequence with the provided name to the string, without a trailing
+    /// space. This is `sprint_cs` in TeX.
+    pub fn write_control_sequence_name(&self, s: &mut String, name: &str) {
+        match name.is_empty() {
+            true => {
+                self.write_escape_char(s);
+                s.push_str("csname");
+                self.write_escape_char(s);
+                s.push_str("endcsname");
+            }
+            false => {
+                self.write_escape_char(s);
+                s.push_str(name);
+            }
+        }
+    }
+
+    /// Appends the escape character to the string, if there is one.
+    pub fn write_escape_char(&self, s: &mut String) {
+        if let Some(c) = self.escape_char {
+            s.push(c);
+        }
+    }
+
+    fn is_letter(&self, c: char) -> bool {
+        match self.cat_codes {
+            None => c.is_alphabetic(),
+            Some(cat_codes) => cat_codes.get(&c) == Some(&RawCatCode::Regular(CatCode::Letter)),
+        }
+    }
+}
+
+/// Returns the text representation of the tokens, using the default style.
+pub fn write_tokens(tokens: &[Token]) -> String {
+    let mut s = String::new();
+    Style::default().write_tokens(&mut s, tokens);
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::token::catcode;
+
+    fn write_with(style: &Style, tokens: &[Token]) -> String {
+        let mut s = String::new();
+        style.write_tokens(&mut s, tokens);
+        s
+    }
+
+    #[test]
+    fn escape_char() {
+        let tokens = vec![Token::new_control_sequence("a"), Token::new_letter('b')];
+        let style = Style {
+            escape_char: Some('/'),
+            cat_codes: None,
+        };
+        assert_eq!(write_with(&style, &tokens), "/a b");
+        let style = Style {
+            escape_char: None,
+            cat_codes: None,
+        };
+        assert_eq!(write_with(&style, &tokens), "a b");
+    }
+
+    #[test]
+    fn empty_control_sequence() {
+        let tokens = vec![Token::new_control_sequence(""), Token::new_letter('a')];
+        assert_eq!(write_tokens(&tokens), r"\csname\endcsname a");
+    }
+
+    #[test]
+    fn single_character_control_sequences_use_catcodes() {
+        let mut cat_codes = ScopedCharMap::new();
+        catcode::set_tex_defaults(&mut cat_codes);
+        cat_codes.insert('@', RawCatCode::Regular(CatCode::Letter));
+        let style = Style {
+            escape_char: Some('\\'),
+            cat_codes: Some(&cat_codes),
+        };
+        let tokens = vec![
+            Token::new_control_sequence("@"),
+            Token::new_control_sequence("1"),
+            Token::new_control_sequence("a"),
+        ];
+        assert_eq!(write_with(&style, &tokens), r"\@ \1\a ");
+    }
+}